@@ -0,0 +1,37 @@
+//! Fuzzes number extraction and name comparison on arbitrary file names.
+//!
+//! Run with `cargo +nightly fuzz run filename_numbers` from the repository root.
+
+#![no_main]
+
+use hozon::collector::{Collector, DEFAULT_NAME_GROUPING_REGEX, DEFAULT_NUMBER_REGEX};
+use hozon::path_utils::{compare_names_natural, extract_number_from_filename_safe};
+use libfuzzer_sys::fuzz_target;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+fuzz_target!(|names: (&str, &str)| {
+    let (a, b) = names;
+    let (path_a, path_b) = (PathBuf::from(a), PathBuf::from(b));
+
+    for regex in [&*DEFAULT_NUMBER_REGEX, &*DEFAULT_NAME_GROUPING_REGEX] {
+        if let Some(number) = extract_number_from_filename_safe(&path_a, regex) {
+            assert!(!number.is_nan(), "extracted NaN from {:?}", a);
+        }
+    }
+
+    // Comparators must be antisymmetric, or sorting panics or loses elements
+    assert_eq!(
+        compare_names_natural(a, b),
+        compare_names_natural(b, a).reverse()
+    );
+    assert_eq!(compare_names_natural(a, a), Ordering::Equal);
+    assert_eq!(
+        Collector::sort_name_by_number_default(&path_a, &path_b),
+        Collector::sort_name_by_number_default(&path_b, &path_a).reverse()
+    );
+    assert_eq!(
+        Collector::sort_by_name_volume_chapter_default(&path_a, &path_b),
+        Collector::sort_by_name_volume_chapter_default(&path_b, &path_a).reverse()
+    );
+});
@@ -0,0 +1,23 @@
+//! Fuzzes the CSV and JSON parsers of chapter-to-volume mapping files.
+//!
+//! Run with `cargo +nightly fuzz run volume_mapping` from the repository root.
+
+#![no_main]
+
+use hozon::VolumeMapping;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    // Malformed files must be rejected with an error, never a panic
+    for mapping in [
+        VolumeMapping::parse_csv(content),
+        VolumeMapping::parse_json(content),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        for chapter in [0.0, 1.0, 1.5, f64::MAX] {
+            let _ = mapping.volume_of(chapter);
+        }
+    }
+});
@@ -0,0 +1,149 @@
+//! Alternative text for page images in EPUB output.
+//!
+//! By default, the `alt` attribute of every page image repeats the page title
+//! ("Chapter 1 - Page 3"), which tells screen reader users nothing about the page. An
+//! [`AltTextSource`] supplies real descriptions, e.g. written by hand in a sidecar file
+//! or produced by an OCR tool, which are then injected into the page XHTML.
+//!
+//! Sidecar files are either JSON objects (`{"001.jpg": "Alt text"}`) or CSV files with
+//! one `key,alt text` pair per line. CSV values may be quoted (`"a, b"`) with `""` as
+//! escaped quote; empty lines, lines starting with `#` and a `page,...` header are skipped.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::photo::embedded_caption;
+
+/// Where to read alternative text for page images from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AltTextSource {
+    /// A single JSON or CSV file. Keys are page file names (`001.jpg`), paths relative to
+    /// the chapter's parent (`Chapter 1/001.jpg`), or chapter directory names
+    /// (`Chapter 1`) whose text is used for every page of that chapter without its own entry.
+    File(PathBuf),
+    /// A JSON or CSV file with the given name inside each chapter directory, keyed by
+    /// page file name. Chapters without the file get no alt text.
+    ChapterSidecar(String),
+    /// A text file next to each page with the same stem (`001.txt` for `001.jpg`), as
+    /// written by OCR tools like Tesseract. Pages without one get no alt text.
+    PageTextFiles,
+    /// Captions embedded in the images by photo managers: IPTC `Caption-Abstract`, EXIF
+    /// `ImageDescription`, Windows `XPComment` or EXIF `UserComment`. Pages without one
+    /// get no alt text.
+    EmbeddedCaptions,
+}
+
+/// Parses a JSON object or CSV sidecar into a key → alt text map.
+fn parse_sidecar(path: &Path) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    if is_json {
+        return serde_json::from_str(&content).map_err(|e| {
+            Error::InvalidPath(path.to_path_buf(), format!("Invalid alt-text JSON: {}", e))
+        });
+    }
+
+    let mut entries = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, text)) = line.split_once(',') else {
+            return Err(Error::InvalidPath(
+                path.to_path_buf(),
+                format!("Invalid alt-text CSV line (expected `key,text`): {}", line),
+            ));
+        };
+        let key = unquote_csv(key.trim());
+        if entries.is_empty() && key.eq_ignore_ascii_case("page") {
+            continue; // Header
+        }
+        entries.insert(key, unquote_csv(text.trim()));
+    }
+    Ok(entries)
+}
+
+/// Removes surrounding quotes from a CSV value and unescapes doubled quotes.
+fn unquote_csv(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => value.to_string(),
+    }
+}
+
+/// Returns the file name of `path` as string, or an empty string.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl AltTextSource {
+    /// Resolves the alt text of every page of one chapter. Blocking; reads files.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<Option<String>>)` - One entry per page, `None` where no text was found
+    /// * `Err(Error)` - A sidecar file exists but could not be read or parsed
+    pub(crate) fn resolve_chapter(&self, pages: &[PathBuf]) -> Result<Vec<Option<String>>> {
+        let Some(chapter_dir) = pages.first().and_then(|p| p.parent()) else {
+            return Ok(Vec::new());
+        };
+
+        match self {
+            AltTextSource::File(path) => {
+                let entries = parse_sidecar(path)?;
+                let chapter_name = file_name(chapter_dir);
+                let chapter_text = entries.get(&chapter_name);
+                Ok(pages
+                    .iter()
+                    .map(|page| {
+                        let page_name = file_name(page);
+                        entries
+                            .get(&format!("{}/{}", chapter_name, page_name))
+                            .or_else(|| entries.get(&page_name))
+                            .or(chapter_text)
+                            .cloned()
+                    })
+                    .collect())
+            }
+            AltTextSource::ChapterSidecar(name) => {
+                let sidecar = chapter_dir.join(name);
+                if !sidecar.is_file() {
+                    return Ok(vec![None; pages.len()]);
+                }
+                let entries = parse_sidecar(&sidecar)?;
+                Ok(pages
+                    .iter()
+                    .map(|page| entries.get(&file_name(page)).cloned())
+                    .collect())
+            }
+            AltTextSource::PageTextFiles => pages
+                .iter()
+                .map(|page| {
+                    let text_file = page.with_extension("txt");
+                    if !text_file.is_file() {
+                        return Ok(None);
+                    }
+                    let text = std::fs::read_to_string(&text_file)?;
+                    // OCR output spans several lines; alt text is a single line
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    Ok((!text.is_empty()).then_some(text))
+                })
+                .collect(),
+            AltTextSource::EmbeddedCaptions => {
+                Ok(pages.iter().map(|page| embedded_caption(page)).collect())
+            }
+        }
+    }
+}
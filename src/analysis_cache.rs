@@ -0,0 +1,241 @@
+//! Persistent cache of cover analysis results.
+//!
+//! [`VolumeGroupingStrategy::ImageAnalysis`](crate::VolumeGroupingStrategy::ImageAnalysis)
+//! decodes the first page of every chapter, which dominates structuring time on large
+//! libraries. With [`HozonConfig::analysis_cache`](crate::HozonConfig::analysis_cache)
+//! set, the share of gray pixels and the dimensions of every analyzed cover are stored
+//! in a JSON file, keyed by the SHA-256 of the file contents. The share doesn't depend on
+//! the sensibility, so structuring again with a different sensibility only hashes the
+//! covers instead of decoding them.
+//!
+//! Entries never go stale: an edited cover has a different hash and is analyzed again.
+//! Entries of deleted files stay in the file until the cache is [cleared](AnalysisCache::clear).
+//! Caches written by a version of Hozon analyzing covers differently are discarded.
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::collector::Collector;
+use crate::error::{Error, Result};
+use crate::path_utils::{path_to_string_lossy, retry_while_locked};
+
+/// Version of the analysis the cached entries were produced by; bumped whenever the
+/// grayscale sampling changes, which invalidates existing caches.
+const CACHE_VERSION: u64 = 1;
+
+/// Size of the buffer used when hashing cover files
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The analysis result of one cover image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverAnalysis {
+    pub gray_share: f64, // Share of sampled pixels that are gray, 0.0-1.0
+    pub width: u32,      // Dimensions of the analyzed image (the preview, if one was used)
+    pub height: u32,
+}
+
+impl CoverAnalysis {
+    /// Whether the cover counts as grayscale at `sensibility` (0.0-1.0), matching
+    /// [`Collector::is_grayscale`].
+    pub fn is_grayscale(&self, sensibility: f64) -> bool {
+        self.gray_share > sensibility
+    }
+}
+
+/// Cover analysis results persisted in a JSON file, see the [module documentation](self).
+///
+/// Shared by all analysis tasks of a structuring run; changes are written back with
+/// [`AnalysisCache::save`].
+#[derive(Debug)]
+pub struct AnalysisCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CoverAnalysis>>,
+    dirty: AtomicBool, // Entries were added since loading or saving
+}
+
+impl AnalysisCache {
+    /// Loads the cache stored at `path`, or starts an empty one if the file doesn't exist,
+    /// can't be parsed, or was written by an incompatible version. Blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file exists but can't be read.
+    pub fn open(path: &Path) -> Result<Self> {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(content) => parse_entries(&content).unwrap_or_else(|| {
+                log::warn!(
+                    "Discarding unreadable or outdated analysis cache '{}'",
+                    path_to_string_lossy(path)
+                );
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to read analysis cache '{}': {}",
+                        path_to_string_lossy(path),
+                        e
+                    ),
+                )));
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// The file the cache is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of cached covers.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no covers are cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Analyzes the cover at `path`, or returns the cached result for its contents.
+    /// With `embedded_thumbnails`, the EXIF preview is analyzed when there is one, as in
+    /// [`Collector::with_embedded_thumbnails`]; such results are cached separately.
+    /// Blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or decoded.
+    pub fn analyze(&self, path: &Path, embedded_thumbnails: bool) -> Result<CoverAnalysis> {
+        let hash = hash_file(path)?;
+        let key = if embedded_thumbnails {
+            format!("preview:{}", hash)
+        } else {
+            hash
+        };
+        if let Some(analysis) = self.lock().get(&key) {
+            return Ok(*analysis);
+        }
+
+        let image = Collector::load_cover(path, embedded_thumbnails)?;
+        let analysis = CoverAnalysis {
+            gray_share: Collector::grayscale_share(&image),
+            width: image.width(),
+            height: image.height(),
+        };
+        self.lock().insert(key, analysis);
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(analysis)
+    }
+
+    /// Writes the cache back to its file if covers were analyzed since it was loaded or
+    /// last saved. Blocking.
+    pub fn save(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let entries: serde_json::Map<String, Value> = self
+            .lock()
+            .iter()
+            .map(|(key, analysis)| {
+                let entry = json!({
+                    "gray_share": analysis.gray_share,
+                    "width": analysis.width,
+                    "height": analysis.height,
+                });
+                (key.clone(), entry)
+            })
+            .collect();
+        let content = serde_json::to_string(&json!({
+            "version": CACHE_VERSION,
+            "entries": entries,
+        }))
+        .map_err(|e| Error::Other(format!("Failed to serialize analysis cache: {}", e)))?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write a sibling first so concurrent readers never see a partial file
+        let temporary = self.path.with_extension("json.tmp");
+        retry_while_locked(&temporary, "write analysis cache", || {
+            std::fs::write(&temporary, &content)
+        })?;
+        retry_while_locked(&self.path, "replace analysis cache", || {
+            std::fs::rename(&temporary, &self.path)
+        })
+    }
+
+    /// Forgets every cached cover and deletes the cache file. Blocking.
+    pub fn clear(&self) -> Result<()> {
+        self.lock().clear();
+        self.dirty.store(false, Ordering::Relaxed);
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Error::Io(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CoverAnalysis>> {
+        // Entries are plain values; a panicking task can't leave them inconsistent
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Parses the entries of a cache file, or `None` if it's invalid or outdated.
+fn parse_entries(content: &str) -> Option<HashMap<String, CoverAnalysis>> {
+    let cache: Value = serde_json::from_str(content).ok()?;
+    if cache.get("version")?.as_u64()? != CACHE_VERSION {
+        return None;
+    }
+    cache
+        .get("entries")?
+        .as_object()?
+        .iter()
+        .map(|(key, entry)| {
+            let analysis = CoverAnalysis {
+                gray_share: entry.get("gray_share")?.as_f64()?,
+                width: u32::try_from(entry.get("width")?.as_u64()?).ok()?,
+                height: u32::try_from(entry.get("height")?.as_u64()?).ok()?,
+            };
+            Some((key.clone(), analysis))
+        })
+        .collect()
+}
+
+/// Hashes the contents of a file. Blocking.
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to open '{}' for analysis: {}",
+                path_to_string_lossy(path),
+                e
+            ),
+        ))
+    })?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,237 @@
+//! Source cleanup after successful conversions, see
+//! [`HozonConfig::source_cleanup`](crate::HozonConfig::source_cleanup).
+//!
+//! Every generated file is read back from disk and each of its entries checked against
+//! the stored checksum before the source is moved or deleted. Moves fall back to copying
+//! when the destination is on another file system; deletions can go to the OS trash with
+//! the `trash` feature.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::HozonConfig;
+use crate::error::{Error, Result};
+use crate::generator::mobi;
+use crate::path_utils::{path_to_string_lossy, retry_while_locked};
+use crate::types::{FileFormat, ImageFormat, SourceCleanup};
+
+/// Entries every generated EPUB must contain.
+const REQUIRED_EPUB_ENTRIES: [&str; 2] = ["mimetype", "META-INF/container.xml"];
+
+/// Verifies `outputs` and applies the configured [`SourceCleanup`] to the source path.
+///
+/// # Returns
+///
+/// * `Ok(Some(path))` - The source was moved to `path`
+/// * `Ok(None)` - The source was kept or deleted
+/// * `Err(Error)` - An output failed verification (the source is kept) or the source
+///   couldn't be moved or deleted
+pub(crate) async fn clean_up_source(
+    config: &HozonConfig,
+    outputs: &[PathBuf],
+) -> Result<Option<PathBuf>> {
+    if config.source_cleanup == SourceCleanup::KeepSource {
+        return Ok(None);
+    }
+    let config = config.clone();
+    let outputs = outputs.to_vec();
+    tokio::task::spawn_blocking(move || {
+        for output in &outputs {
+            verify_output(
+                output,
+                config.output_format,
+                config.output_password.as_deref(),
+            )?;
+        }
+        let source = &config.source_path;
+        match &config.source_cleanup {
+            SourceCleanup::KeepSource => Ok(None),
+            SourceCleanup::MoveSourceTo(directory) => move_directory(source, directory).map(Some),
+            SourceCleanup::DeleteSource if config.use_trash => {
+                move_to_trash(source)?;
+                log::info!(
+                    "Moved source '{}' to the trash",
+                    path_to_string_lossy(source)
+                );
+                Ok(None)
+            }
+            SourceCleanup::DeleteSource => {
+                retry_while_locked(source, "delete source", || remove_path(source))?;
+                log::info!("Deleted source '{}'", path_to_string_lossy(source));
+                Ok(None)
+            }
+        }
+    })
+    .await?
+}
+
+/// Reads every entry of the archive at `path`, failing with [`Error::InvalidOutput`] if
+/// it can't be opened, an entry doesn't match its checksum, or required entries are
+/// missing. MOBI files are checked with [`mobi::verify`] instead, CB7 files with
+/// [`verify_seven_zip`] and directory outputs with [`verify_directory`].
+fn verify_output(path: &Path, format: FileFormat, password: Option<&str>) -> Result<()> {
+    let invalid = |reason: String| Error::InvalidOutput(path.to_path_buf(), reason);
+    if format == FileFormat::Mobi {
+        let bytes = std::fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        return mobi::verify(&bytes).map_err(invalid);
+    }
+    if format == FileFormat::Cb7 {
+        return verify_seven_zip(path).map_err(|e| invalid(e.to_string()));
+    }
+    if format == FileFormat::Directory {
+        return verify_directory(path).map_err(|e| invalid(e.to_string()));
+    }
+    let file = std::fs::File::open(path).map_err(|e| invalid(e.to_string()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+    if archive.is_empty() {
+        return Err(invalid("the archive has no entries".to_string()));
+    }
+    if format.is_epub() {
+        for name in REQUIRED_EPUB_ENTRIES {
+            if archive.index_for_name(name).is_none() {
+                return Err(invalid(format!("'{}' is missing", name)));
+            }
+        }
+    }
+
+    let mut buffer = Vec::new();
+    for i in 0..archive.len() {
+        let entry = match password {
+            Some(password) => archive.by_index_decrypt(i, password.as_bytes()),
+            None => archive.by_index(i),
+        };
+        let mut entry = entry.map_err(|e| invalid(format!("entry {}: {}", i, e)))?;
+        // Reading to the end checks the CRC-32
+        buffer.clear();
+        entry
+            .read_to_end(&mut buffer)
+            .map_err(|e| invalid(format!("'{}': {}", entry.name(), e)))?;
+    }
+    Ok(())
+}
+
+/// Reads every entry of the 7z archive at `path`, which checks their CRCs.
+fn verify_seven_zip(path: &Path) -> Result<()> {
+    #[cfg(feature = "cb7")]
+    {
+        let mut entries = 0;
+        crate::generator::archive::for_each_seven_zip_entry(path, |_, data| {
+            std::io::copy(data, &mut std::io::sink())?;
+            entries += 1;
+            Ok(true)
+        })?;
+        if entries == 0 {
+            return Err(Error::Other("the archive has no entries".to_string()));
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "cb7"))]
+    {
+        let _ = path;
+        Err(Error::Unsupported(
+            "Reading CB7 files requires the `cb7` feature".to_string(),
+        ))
+    }
+}
+
+/// Reads every page of the volume directory at `path` and checks that it is an image.
+fn verify_directory(path: &Path) -> Result<()> {
+    let mut pages = 0;
+    for entry in std::fs::read_dir(path)? {
+        let page = entry?.path();
+        let bytes = std::fs::read(&page)?;
+        if ImageFormat::from_bytes(&bytes).is_none() && ImageFormat::from_path(&page).is_err() {
+            return Err(Error::Other(format!(
+                "'{}' is not an image",
+                path_to_string_lossy(&page)
+            )));
+        }
+        pages += 1;
+    }
+    if pages == 0 {
+        return Err(Error::Other("the directory has no pages".to_string()));
+    }
+    Ok(())
+}
+
+/// Moves `source` into `directory`, keeping its name, and returns the new path.
+fn move_directory(source: &Path, directory: &Path) -> Result<PathBuf> {
+    let name = source.file_name().ok_or_else(|| {
+        Error::InvalidPath(source.to_path_buf(), "has no directory name".to_string())
+    })?;
+    let destination = directory.join(name);
+    if destination.exists() {
+        return Err(Error::InvalidPath(
+            destination,
+            "the source can't be moved there, the path already exists".to_string(),
+        ));
+    }
+    std::fs::create_dir_all(directory)?;
+
+    if std::fs::rename(source, &destination).is_err() {
+        // Renaming fails across file systems; copy first, so a failed copy keeps the source
+        let copied = if source.is_file() {
+            std::fs::copy(source, &destination)
+                .map(|_| ())
+                .map_err(Error::from)
+        } else {
+            copy_directory(source, &destination)
+        };
+        if let Err(e) = copied {
+            let _ = remove_path(&destination);
+            return Err(e);
+        }
+        retry_while_locked(source, "remove moved source", || remove_path(source))?;
+    }
+    log::info!(
+        "Moved source '{}' to '{}'",
+        path_to_string_lossy(source),
+        path_to_string_lossy(&destination)
+    );
+    Ok(destination)
+}
+
+/// Sends `path` to the OS trash. Requires the `trash` feature.
+fn move_to_trash(path: &Path) -> Result<()> {
+    #[cfg(feature = "trash")]
+    {
+        trash::delete(path).map_err(|e| {
+            Error::Io(std::io::Error::other(format!(
+                "Failed to move '{}' to the trash: {}",
+                path_to_string_lossy(path),
+                e
+            )))
+        })
+    }
+    #[cfg(not(feature = "trash"))]
+    {
+        let _ = path;
+        Err(Error::Unsupported(
+            "`use_trash` requires the `trash` feature".to_string(),
+        ))
+    }
+}
+
+/// Removes the directory or, for archive sources, the file at `path`.
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Recursively copies the directory `source` to `destination`.
+fn copy_directory(source: &Path, destination: &Path) -> Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_directory(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
@@ -4,9 +4,10 @@
 //! from a directory structure, typically representing chapters and pages of comics or manga.
 //! It includes tools for sorting files numerically and detecting chapter boundaries.
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use futures::future::try_join_all;
@@ -19,16 +20,23 @@ use tokio::spawn;
 use tokio::sync::Semaphore;
 use tokio::task::{JoinHandle, spawn_blocking};
 
+use crate::analysis_cache::AnalysisCache;
 use crate::error::{Error, Result};
 use crate::path_utils::{
-    compare_paths_by_number_safe, extract_number_from_filename_safe, get_file_name_lossy,
-    get_file_name_safe, is_hidden_file, validate_path,
+    compare_names_natural, compare_paths_by_number_safe, extract_number_from_filename_safe,
+    extract_number_suffix_safe, get_file_name_lossy, get_file_name_safe, is_hidden_file,
+    path_to_string_lossy, validate_path,
 };
-use crate::types::CollectionDepth;
+use crate::photo::{embedded_thumbnail, sort_by_capture_time};
+use crate::runtime::{RuntimeLimits, acquire_file_handle, open_error};
+use crate::source_archive::{self, ArchiveExtractions};
+use crate::storage::StorageKind;
+use crate::types::{CollectionDepth, CoverBreak, ImageFormat, SortSpec, SortStrategy};
 use crate::{AnalyzeFinding, AnalyzeReport, CollectedContent, VolumeGroupingStrategy};
 
-/// Limits the number of concurrent directory operations
-const MAX_CONCURRENT_DIRS: usize = 64;
+/// Maximum directory nesting followed by [`CollectionDepth::Recursive`], guarding against
+/// symlink loops
+const MAX_RECURSION_DEPTH: usize = 16;
 /// Controls how many pixels to skip when sampling for grayscale detection
 const GRAYSCALE_SAMPLE_RATE: u32 = 10;
 /// Maximum dimension for grayscale detection before downsampling
@@ -36,6 +44,33 @@ const GRAYSCALE_MAX_DIMENSION: u32 = 500;
 /// RGB difference threshold for determining if a pixel is grayscale
 const RGB_GRAYSCALE_THRESHOLD: u8 = 10;
 
+/// Extensions of archive files that sources sometimes contain instead of loose images.
+const ARCHIVE_EXTENSIONS: [&str; 8] = ["zip", "cbz", "rar", "cbr", "7z", "cb7", "tar", "cbt"];
+
+/// How to handle archives reported as [`AnalyzeFinding::NestedArchive`].
+pub(crate) const NESTED_ARCHIVE_GUIDANCE: &str = "Hozon reads loose image files, and \
+     `.cbz`/`.zip` archives (`.cbr`/`.rar` with the `cbr` feature) only as the source itself \
+     or directly in a source collected with `CollectionDepth::Deep`; move or extract the \
+     archive accordingly and convert again";
+
+/// Whether `path` has one of the [`ARCHIVE_EXTENSIONS`].
+fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ARCHIVE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+/// How clearly a cover with `gray_share` gray pixels falls below the grayscale
+/// `threshold` (both 0.0-1.0): 0.0 right at the threshold, 1.0 without gray pixels.
+fn break_confidence(gray_share: f64, threshold: f64) -> f64 {
+    if threshold <= 0.0 {
+        return 1.0;
+    }
+    ((threshold - gray_share) / threshold).clamp(0.0, 1.0)
+}
+
 lazy_static! {
     /// Default Regex pattern for extracting numeric values from chapter/page filenames.
     /// Matches "001", "1", "1.5" etc.
@@ -45,6 +80,58 @@ lazy_static! {
     pub static ref DEFAULT_NAME_GROUPING_REGEX: Regex = Regex::new(r"\d+-\d+(\.\d+)?").unwrap();
 }
 
+/// Orders two paths, as accepted by [`Collector::collect_pages`].
+pub type PathComparator = Arc<dyn Fn(&PathBuf, &PathBuf) -> Ordering + Sync + Send + 'static>;
+
+/// Returns the comparator ordering paths by their file names according to `spec`.
+///
+/// [`SortSpec::ExifDate`] orders by number here; capture times are applied separately by
+/// [`Collector::collect_pages`], since they require reading every page.
+///
+/// # Errors
+///
+/// Fails if the pattern of a [`SortSpec::RegexCapture`] is invalid or has no such group.
+pub fn sort_spec_comparator(spec: &SortSpec) -> Result<PathComparator> {
+    Ok(match spec {
+        SortSpec::Numeric | SortSpec::ExifDate => Arc::new(Collector::sort_name_by_number_default),
+        SortSpec::Natural => Arc::new(|a: &PathBuf, b: &PathBuf| {
+            compare_names_natural(&get_file_name_lossy(a), &get_file_name_lossy(b))
+                .then_with(|| a.cmp(b))
+        }),
+        SortSpec::Lexicographic => Arc::new(|a: &PathBuf, b: &PathBuf| {
+            get_file_name_lossy(a)
+                .cmp(&get_file_name_lossy(b))
+                .then_with(|| a.cmp(b))
+        }),
+        SortSpec::RegexCapture { pattern, group } => {
+            let regex = Regex::new(pattern)
+                .map_err(|e| Error::Other(format!("Invalid sort pattern '{}': {}", pattern, e)))?;
+            if *group >= regex.captures_len() {
+                return Err(Error::Other(format!(
+                    "Sort pattern '{}' has no capture group {}",
+                    pattern, group
+                )));
+            }
+            let group = *group;
+            let number = move |path: &PathBuf| {
+                regex
+                    .captures(&get_file_name_lossy(path))
+                    .and_then(|captures| captures.get(group))
+                    .and_then(|capture| capture.as_str().parse::<f64>().ok())
+            };
+            Arc::new(move |a: &PathBuf, b: &PathBuf| {
+                number(a).partial_cmp(&number(b)).unwrap_or(Ordering::Equal)
+            })
+        }
+    })
+}
+
+/// Returns the number in the name of `chapter_dir`, extracted with `name_regex` (or
+/// [`DEFAULT_NUMBER_REGEX`]) the way chapters are sorted by number.
+pub(crate) fn chapter_number(chapter_dir: &Path, name_regex: Option<&Regex>) -> Option<f64> {
+    extract_number_from_filename_safe(chapter_dir, name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX))
+}
+
 /// Manages collection and organization of image files in a directory structure
 #[derive(Debug)]
 pub struct Collector<'a> {
@@ -53,6 +140,16 @@ pub struct Collector<'a> {
     chapter_name_regex: Option<&'a Regex>, // Custom regex for chapter name parsing
     page_name_regex: Option<&'a Regex>,    // Custom regex for page name parsing
     image_analysis_sensibility: u8,        // 0-100%
+    chapter_sort: SortSpec,                // Used when no custom chapter sorter is given
+    page_sort: SortSpec,                   // Used when no custom page sorter is given
+    storage: StorageKind,                  // Caps concurrent directory scans and cover reads
+    cpu_limit: Option<Arc<Semaphore>>,     // Shared cap on concurrent cover decoding, if set
+    embedded_thumbnails: bool,             // Analyze EXIF previews instead of full covers
+    analysis_cache: Option<Arc<AnalysisCache>>, // Persisted cover analysis results, if set
+    page_gap_check: bool,                  // Report gaps in the page numbering
+    min_break_confidence: f64,             // 0.0-1.0, weaker cover breaks start no volume
+    archive_extractions: Arc<ArchiveExtractions>, // Where source archives are extracted to
+    archive_password: Option<String>,      // Decrypts encrypted CBZ/ZIP entries
 }
 
 impl<'a> Collector<'a> {
@@ -78,9 +175,98 @@ impl<'a> Collector<'a> {
             chapter_name_regex,
             page_name_regex,
             image_analysis_sensibility: image_analysis_sensibility.min(100),
+            chapter_sort: SortSpec::default(),
+            page_sort: SortSpec::default(),
+            storage: StorageKind::Auto,
+            cpu_limit: None,
+            embedded_thumbnails: false,
+            analysis_cache: None,
+            page_gap_check: false,
+            min_break_confidence: 0.0,
+            archive_extractions: Arc::default(),
+            archive_password: None,
         }
     }
 
+    /// Sets the storage the base directory lives on, which caps how many directories are
+    /// scanned and covers read at once. [`StorageKind::Auto`] (the default) detects it.
+    pub fn with_storage_kind(mut self, storage: StorageKind) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Makes cover analysis draw from the shared CPU permits of `limits`, so it competes
+    /// fairly with page processing of other conversions using the same limits.
+    pub fn with_runtime_limits(mut self, limits: &RuntimeLimits) -> Self {
+        self.cpu_limit = Some(limits.cpu_semaphore());
+        self
+    }
+
+    /// Makes cover analysis decode the JPEG preview embedded in a cover's EXIF data, when
+    /// there is one, instead of the full image. Much faster for camera and phone scans,
+    /// but trusts the preview to match the image.
+    pub fn with_embedded_thumbnails(mut self, enabled: bool) -> Self {
+        self.embedded_thumbnails = enabled;
+        self
+    }
+
+    /// Makes cover analysis reuse and record results in `cache`, see the
+    /// [`analysis_cache`](crate::analysis_cache) module.
+    pub fn with_analysis_cache(mut self, cache: Arc<AnalysisCache>) -> Self {
+        self.analysis_cache = Some(cache);
+        self
+    }
+
+    /// Makes [`Collector::analyze_source_content`] compare the page numbers in the file
+    /// names of each chapter and report missing ones as [`AnalyzeFinding::MissingPages`].
+    pub fn with_page_gap_check(mut self, enabled: bool) -> Self {
+        self.page_gap_check = enabled;
+        self
+    }
+
+    /// Makes cover analysis ignore volume breaks with a [`CoverBreak::confidence`] below
+    /// `confidence` (0.0-1.0), so borderline covers don't split off short volumes.
+    pub fn with_min_break_confidence(mut self, confidence: f64) -> Self {
+        self.min_break_confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Makes the collector extract source archives into `extractions`, so the extracted
+    /// pages outlive the collector. By default they are removed with it.
+    pub(crate) fn with_archive_extractions(mut self, extractions: Arc<ArchiveExtractions>) -> Self {
+        self.archive_extractions = extractions;
+        self
+    }
+
+    /// Sets the password decrypting encrypted entries of CBZ/ZIP archives in the source.
+    pub fn with_archive_password(mut self, password: Option<String>) -> Self {
+        self.archive_password = password;
+        self
+    }
+
+    /// The storage of the base directory, detected if not set.
+    fn resolved_storage(&self) -> StorageKind {
+        self.storage.resolve(self.base_directory)
+    }
+
+    /// Sets how pages are ordered when [`Collector::collect_pages`] gets no custom sorter.
+    pub fn with_page_sort_strategy(self, strategy: SortStrategy) -> Self {
+        self.with_page_sort(strategy.into())
+    }
+
+    /// Sets how pages are ordered when [`Collector::collect_pages`] gets no custom sorter.
+    pub fn with_page_sort(mut self, sort: SortSpec) -> Self {
+        self.page_sort = sort;
+        self
+    }
+
+    /// Sets how chapters are ordered when [`Collector::collect_chapters`] gets no custom
+    /// sorter. [`SortSpec::ExifDate`] is not supported for chapters and orders by number.
+    pub fn with_chapter_sort(mut self, sort: SortSpec) -> Self {
+        self.chapter_sort = sort;
+        self
+    }
+
     /// Collects chapter directories from the base directory
     ///
     /// # Arguments
@@ -89,21 +275,55 @@ impl<'a> Collector<'a> {
     ///
     /// # Returns
     ///
-    /// * `Result<Vec<PathBuf>>` - Vector of paths to chapter directories
+    /// * `Result<Vec<PathBuf>>` - Vector of paths to chapter directories. Source archives
+    ///   that can't be extracted are skipped with a warning.
     pub async fn collect_chapters<F>(&self, custom_sorter: Option<F>) -> Result<Vec<PathBuf>>
     where
         F: Fn(&PathBuf, &PathBuf) -> Ordering + Sync,
     {
-        let mut chapters = if self.collection_depth == CollectionDepth::Shallow {
+        let mut unreadable = Vec::new();
+        self.collect_chapters_reporting(custom_sorter, &mut unreadable)
+            .await
+    }
+
+    /// [`Collector::collect_chapters`], adding the source archives that fail to extract
+    /// to `findings` as [`AnalyzeFinding::UnreadableArchive`] instead of failing.
+    async fn collect_chapters_reporting<F>(
+        &self,
+        custom_sorter: Option<F>,
+        findings: &mut Vec<AnalyzeFinding>,
+    ) -> Result<Vec<PathBuf>>
+    where
+        F: Fn(&PathBuf, &PathBuf) -> Ordering + Sync,
+    {
+        let mut chapters = match self.collection_depth {
+            // An archive as the source is the single chapter, whatever the depth
+            _ if source_archive::is_source_archive(self.base_directory) => {
+                self.extract_archives(vec![self.base_directory.clone()], findings)
+                    .await?
+            }
             // In shallow mode, the base_directory itself is the single "chapter"
-            vec![self.base_directory.clone()]
-        } else {
-            // In deep mode, find subdirectories
-            Self::collect_parallel(self.base_directory, true).await?
+            CollectionDepth::Shallow => vec![self.base_directory.clone()],
+            // In deep mode, find subdirectories and archives
+            CollectionDepth::Deep => {
+                let mut chapters = Self::collect_parallel(self.base_directory, true).await?;
+                let archives = Self::source_archives(self.base_directory).await?;
+                chapters.extend(self.extract_archives(archives, findings).await?);
+                chapters
+            }
+            // In recursive mode, find every directory holding pages
+            CollectionDepth::Recursive => self.collect_page_directories().await?,
         };
-
         if let Some(sorter) = custom_sorter {
             chapters.par_sort_by(sorter);
+        } else if self.chapter_name_regex.is_some()
+            || !matches!(self.chapter_sort, SortSpec::Numeric | SortSpec::ExifDate)
+        {
+            let sorter = Self::comparator(&self.chapter_sort, self.chapter_name_regex)?;
+            chapters.par_sort_by(sorter.as_ref());
+        } else if self.collection_depth == CollectionDepth::Recursive {
+            // Nested chapters share names ("Volume 1/Chapter 1", "Volume 2/Chapter 1")
+            chapters.par_sort_by(|a, b| Self::sort_nested_paths(a, b));
         } else {
             // Default sort for chapters if no custom sorter provided
             chapters.par_sort_by(&Collector::sort_name_by_number_default);
@@ -111,12 +331,127 @@ impl<'a> Collector<'a> {
         Ok(chapters)
     }
 
+    /// Extracts `archives`, skipping the ones that can't be read and reporting them in
+    /// `findings`. Only failing to write the extraction is an error.
+    async fn extract_archives(
+        &self,
+        archives: Vec<PathBuf>,
+        findings: &mut Vec<AnalyzeFinding>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut directories = Vec::with_capacity(archives.len());
+        for archive in archives {
+            let password = self.archive_password.as_deref();
+            match self.archive_extractions.extract(&archive, password).await {
+                Ok(directory) => directories.push(directory),
+                Err(Error::Io(e)) => return Err(Error::Io(e)),
+                Err(e) => {
+                    log::warn!(
+                        "Skipping archive '{}': {}",
+                        path_to_string_lossy(&archive),
+                        e
+                    );
+                    findings.push(AnalyzeFinding::UnreadableArchive {
+                        path: archive,
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(directories)
+    }
+
+    /// Returns the comparator for `sort`, numbering names with `name_regex` (if given)
+    /// where `sort` orders by number.
+    fn comparator(sort: &SortSpec, name_regex: Option<&Regex>) -> Result<PathComparator> {
+        match (sort, name_regex) {
+            (SortSpec::Numeric | SortSpec::ExifDate, Some(regex)) => {
+                let regex = regex.clone();
+                Ok(Arc::new(move |a: &PathBuf, b: &PathBuf| {
+                    compare_paths_by_number_safe(a, b, &regex)
+                }))
+            }
+            _ => sort_spec_comparator(sort),
+        }
+    }
+
+    /// The `.cbz`/`.zip` archives directly in `directory`, which are chapters of a
+    /// [`CollectionDepth::Deep`] source.
+    async fn source_archives(directory: &PathBuf) -> Result<Vec<PathBuf>> {
+        let files = Self::collect_all_files(directory).await?;
+        Ok(files
+            .into_iter()
+            .filter(|path| source_archive::is_source_archive(path))
+            .collect())
+    }
+
+    /// Finds every directory below the base directory (including it) that directly
+    /// contains supported images, up to [`MAX_RECURSION_DEPTH`] levels deep.
+    async fn collect_page_directories(&self) -> Result<Vec<PathBuf>> {
+        let mut pending = vec![(self.base_directory.clone(), 0)];
+        let mut found = Vec::new();
+        while let Some((directory, depth)) = pending.pop() {
+            if !Self::collect_parallel(&directory, false).await?.is_empty() {
+                found.push(directory.clone());
+            }
+            if depth < MAX_RECURSION_DEPTH {
+                let subdirectories = Self::collect_parallel(&directory, true).await?;
+                pending.extend(subdirectories.into_iter().map(|dir| (dir, depth + 1)));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Orders nested paths component by component, numerically where components hold
+    /// numbers, so a directory comes before its subdirectories.
+    fn sort_nested_paths(a: &Path, b: &Path) -> Ordering {
+        for (a, b) in a.components().zip(b.components()) {
+            let (a, b) = (PathBuf::from(a.as_os_str()), PathBuf::from(b.as_os_str()));
+            let ordering = Collector::sort_name_by_number_default(&a, &b).then_with(|| a.cmp(&b));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a.components().count().cmp(&b.components().count())
+    }
+
+    /// Determines which [`CollectionDepth`] matches the layout of the base directory:
+    /// `Shallow` if only the base directory holds pages, `Deep` if only its immediate
+    /// subdirectories do, and `Recursive` otherwise. An archive as the source is
+    /// `Shallow`, a directory of only `.cbz`/`.zip` archives `Deep`. Other sources without
+    /// any pages keep the configured depth.
+    pub async fn detect_collection_depth(&self) -> Result<CollectionDepth> {
+        if source_archive::is_source_archive(self.base_directory) {
+            return Ok(CollectionDepth::Shallow);
+        }
+        let page_directories = self.collect_page_directories().await?;
+        let depth_of = |dir: &PathBuf| {
+            dir.strip_prefix(self.base_directory)
+                .map_or(0, |relative| relative.components().count())
+        };
+
+        let depths: Vec<usize> = page_directories.iter().map(depth_of).collect();
+        Ok(if depths.is_empty() {
+            if Self::source_archives(self.base_directory).await?.is_empty() {
+                self.collection_depth
+            } else {
+                CollectionDepth::Deep
+            }
+        } else if depths.iter().all(|&depth| depth == 0) {
+            CollectionDepth::Shallow
+        } else if depths.iter().all(|&depth| depth == 1) {
+            CollectionDepth::Deep
+        } else {
+            CollectionDepth::Recursive
+        })
+    }
+
     /// Collects page images from each chapter directory
     ///
     /// # Arguments
     ///
     /// * `chapters` - Vector of chapter directory paths
-    /// * `custom_sorter` - Optional function to sort the collected pages
+    /// * `custom_sorter` - Optional function to sort the collected pages, overriding the
+    ///   page sort strategy
     ///
     /// # Returns
     ///
@@ -124,24 +459,38 @@ impl<'a> Collector<'a> {
     pub async fn collect_pages(
         &self,
         chapters: Vec<PathBuf>,
-        custom_sorter: Option<Arc<dyn Fn(&PathBuf, &PathBuf) -> Ordering + Sync + Send + 'static>>,
+        custom_sorter: Option<PathComparator>,
     ) -> Result<Vec<Vec<PathBuf>>> {
-        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DIRS));
+        let semaphore = Arc::new(Semaphore::new(
+            self.resolved_storage().max_concurrent_dirs(),
+        ));
+        // Capture times only apply to the built-in ordering
+        let by_capture_time = custom_sorter.is_none() && self.page_sort == SortSpec::ExifDate;
+        let page_sorter = match custom_sorter {
+            Some(sorter) => sorter,
+            None => Self::comparator(&self.page_sort, self.page_name_regex)?,
+        };
         let mut handles: Vec<JoinHandle<Result<(usize, Vec<PathBuf>)>>> = Vec::new();
 
         for (index, chapter_dir) in chapters.into_iter().enumerate() {
             let semaphore = Arc::clone(&semaphore);
-            let page_sorter = custom_sorter.clone();
+            let page_sorter = Arc::clone(&page_sorter);
 
             handles.push(spawn(async move {
                 let _permit = semaphore.acquire().await?;
 
                 let mut chapter_images = Self::collect_parallel(&chapter_dir, false).await?;
 
-                if let Some(sorter) = page_sorter.as_ref() {
-                    chapter_images.par_sort_by(sorter.as_ref());
-                } else {
-                    chapter_images.par_sort_by(&Collector::sort_name_by_number_default);
+                chapter_images.par_sort_by(page_sorter.as_ref());
+                if by_capture_time {
+                    // Stable, so undated pages stay in number order
+                    chapter_images = spawn_blocking(move || {
+                        sort_by_capture_time(chapter_images)
+                            .into_iter()
+                            .map(|(_, page)| page)
+                            .collect()
+                    })
+                    .await?;
                 }
                 Ok((index, chapter_images))
             }));
@@ -180,34 +529,164 @@ impl<'a> Collector<'a> {
             return Ok(Vec::new());
         }
 
+        let breaks = self
+            .detect_cover_breaks(images_per_chapter, sensibility)
+            .await?;
+        Ok(Self::volume_starts(&breaks))
+    }
+
+    /// Scores the cover (first image) of every chapter after the first that
+    /// [`Collector::determine_volume_start_chapters`] would start a volume with. Breaks
+    /// below the [minimum confidence](Collector::with_min_break_confidence) are returned
+    /// too, but not applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `images_per_chapter` - Nested vector of image paths organized by chapter
+    /// * `sensibility` - Custom sensibility override (0.0 to 1.0), or None to use instance setting
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<CoverBreak>>` - The volume breaks found, in chapter order
+    pub async fn detect_cover_breaks(
+        &self,
+        images_per_chapter: Vec<Vec<PathBuf>>,
+        sensibility: Option<f64>,
+    ) -> Result<Vec<CoverBreak>> {
         let effective_sensibility =
             sensibility.unwrap_or(self.image_analysis_sensibility as f64 / 100.0);
 
-        let semaphore = Arc::new(Semaphore::new(num_cpus::get().min(8)));
-        let mut handles: Vec<JoinHandle<Result<Option<usize>>>> = Vec::new();
+        let embedded_thumbnails = self.embedded_thumbnails;
+        let cache = self.analysis_cache.clone();
+        let results = self
+            .analyze_covers(images_per_chapter, move |cover_path| match &cache {
+                Some(cache) => Ok(cache.analyze(cover_path, embedded_thumbnails)?.gray_share),
+                None => Ok(Collector::grayscale_share(&Collector::load_cover(
+                    cover_path,
+                    embedded_thumbnails,
+                )?)),
+            })
+            .await?;
+
+        // Grayscale covers are likely not volume starts; unreadable covers are skipped
+        let mut breaks: Vec<CoverBreak> = results
+            .into_iter()
+            .filter_map(|(index, share)| Some((index, share.ok()?)))
+            .filter(|&(index, gray_share)| index > 0 && gray_share <= effective_sensibility)
+            .map(|(index, gray_share)| {
+                let confidence = break_confidence(gray_share, effective_sensibility);
+                CoverBreak {
+                    index,
+                    gray_share,
+                    confidence,
+                    applied: confidence >= self.min_break_confidence,
+                }
+            })
+            .collect();
+        breaks.sort_unstable_by_key(|cover_break| cover_break.index);
+        Ok(breaks)
+    }
+
+    /// The chapters starting a volume: the first one, and those of the applied `breaks`.
+    pub(crate) fn volume_starts(breaks: &[CoverBreak]) -> Vec<usize> {
+        std::iter::once(0)
+            .chain(
+                breaks
+                    .iter()
+                    .filter(|cover_break| cover_break.applied)
+                    .map(|cover_break| cover_break.index),
+            )
+            .collect()
+    }
+
+    /// Counts the volumes [`Collector::determine_volume_start_chapters`] finds at each of
+    /// the given sensibilities (0-100), so a sensibility yielding the expected number of
+    /// volumes can be picked without structuring the source again and again. Every cover
+    /// is decoded only once; breaks below the
+    /// [minimum confidence](Collector::with_min_break_confidence) are not counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `images_per_chapter` - Nested vector of image paths organized by chapter
+    /// * `sensibilities` - The sensibilities to try, e.g. `(50..=95).step_by(5)`
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(u8, usize)>>` - Each sensibility with the number of volumes found
+    pub async fn sweep_sensibility(
+        &self,
+        images_per_chapter: Vec<Vec<PathBuf>>,
+        sensibilities: impl IntoIterator<Item = u8>,
+    ) -> Result<Vec<(u8, usize)>> {
+        let has_chapters = !images_per_chapter.is_empty();
+        let embedded_thumbnails = self.embedded_thumbnails;
+        let cache = self.analysis_cache.clone();
+        let results = self
+            .analyze_covers(images_per_chapter, move |cover_path| match &cache {
+                Some(cache) => Ok(cache.analyze(cover_path, embedded_thumbnails)?.gray_share),
+                None => Ok(Collector::grayscale_share(&Collector::load_cover(
+                    cover_path,
+                    embedded_thumbnails,
+                )?)),
+            })
+            .await?;
+        let shares: Vec<(usize, f64)> = results
+            .into_iter()
+            .filter_map(|(index, share)| Some((index, share.ok()?)))
+            .collect();
+
+        Ok(sensibilities
+            .into_iter()
+            .map(|sensibility| {
+                let threshold = sensibility.min(100) as f64 / 100.0;
+                // The first chapter always starts a volume
+                let later_starts = shares
+                    .iter()
+                    .filter(|(index, share)| {
+                        *index > 0
+                            && *share <= threshold
+                            && break_confidence(*share, threshold) >= self.min_break_confidence
+                    })
+                    .count();
+                let volumes = if has_chapters { 1 + later_starts } else { 0 };
+                (sensibility, volumes)
+            })
+            .collect())
+    }
+
+    /// Runs `analyze` on the cover (first image) of every non-empty chapter on blocking
+    /// threads, with concurrency capped by the storage and the shared CPU permits.
+    /// Returns each chapter index with its result; the analysis cache is saved afterwards.
+    async fn analyze_covers<T: Send + 'static>(
+        &self,
+        images_per_chapter: Vec<Vec<PathBuf>>,
+        analyze: impl Fn(&Path) -> Result<T> + Clone + Send + Sync + 'static,
+    ) -> Result<Vec<(usize, Result<T>)>> {
+        let max_reads = self
+            .resolved_storage()
+            .max_concurrent_reads()
+            .unwrap_or(usize::MAX);
+        let semaphore = Arc::new(Semaphore::new(num_cpus::get().min(8).min(max_reads)));
+        let mut handles = Vec::new();
 
         for (i, images_in_chapter) in images_per_chapter.into_iter().enumerate() {
-            if images_in_chapter.is_empty() {
+            let Some(cover_path) = images_in_chapter.into_iter().next() else {
                 continue;
-            }
-
-            let cover_path = images_in_chapter[0].clone();
+            };
             let semaphore = Arc::clone(&semaphore);
+            let cpu_limit = self.cpu_limit.clone();
+            let analyze = analyze.clone();
 
             handles.push(spawn(async move {
                 let _permit = semaphore.acquire().await?;
-                // image::open is blocking, so move it to a blocking thread
-                spawn_blocking(move || {
-                    let cover_image = image::open(&cover_path)?;
-                    Ok(
-                        if Collector::is_grayscale(&cover_image, effective_sensibility) {
-                            None // Is grayscale, likely not a cover
-                        } else {
-                            Some(i) // Not grayscale, likely a cover/volume start
-                        },
-                    )
-                })
-                .await?
+                let _cpu_permit = match cpu_limit {
+                    Some(cpu) => Some(cpu.acquire_owned().await?),
+                    None => None,
+                };
+                let _handle = acquire_file_handle().await?;
+                // Decoding is blocking, so move it to a blocking thread
+                let result = spawn_blocking(move || analyze(&cover_path)).await?;
+                Ok((i, result))
             }));
         }
 
@@ -215,19 +694,12 @@ impl<'a> Collector<'a> {
             Error::AsyncTaskError(format!("Failed to join volume detection tasks: {}", e))
         })?;
 
-        let mut volume_start_chapters: Vec<usize> = results
-            .into_iter()
-            .filter_map(|result| result.ok().flatten())
-            .collect();
-
-        if !volume_start_chapters.contains(&0) {
-            volume_start_chapters.insert(0, 0);
+        if let Some(cache) = self.analysis_cache.clone() {
+            spawn_blocking(move || cache.save()).await??;
         }
 
-        volume_start_chapters.par_sort_unstable();
-        volume_start_chapters.dedup();
-
-        Ok(volume_start_chapters)
+        // Failing to acquire permits fails the analysis; failing to decode a cover doesn't
+        results.into_iter().collect()
     }
 
     /// Calculates how many chapters belong to each volume given start indices.
@@ -283,28 +755,63 @@ impl<'a> Collector<'a> {
     /// * `Result<CollectedContent>` - The collected chapters and pages along with an analysis
     pub async fn analyze_source_content(&self) -> Result<CollectedContent> {
         let mut findings = Vec::new();
+        let recommended_depth = self.detect_collection_depth().await?;
+        let depth_mismatch = |findings: &mut Vec<AnalyzeFinding>| {
+            if recommended_depth != self.collection_depth {
+                log::warn!(
+                    "Collection depth {:?} finds no pages in {:?}; its layout matches {:?}",
+                    self.collection_depth,
+                    self.base_directory,
+                    recommended_depth
+                );
+                findings.push(AnalyzeFinding::CollectionDepthMismatch {
+                    configured: self.collection_depth,
+                    recommended: recommended_depth,
+                });
+            }
+        };
 
         // 1. Collect chapters and pages
         let chapters = self
-            .collect_chapters(None::<fn(&PathBuf, &PathBuf) -> Ordering>)
+            .collect_chapters_reporting(None::<fn(&PathBuf, &PathBuf) -> Ordering>, &mut findings)
             .await?;
         if chapters.is_empty() {
+            // Typically a directory of already archived volumes
+            let base = [self.base_directory.to_path_buf()];
+            let ignored = Self::ignored_file_findings(&base, &[Vec::new()]).await;
+            // Archives that failed to extract are already reported as unreadable
+            let unreadable: Vec<PathBuf> = findings
+                .iter()
+                .filter_map(|finding| match finding {
+                    AnalyzeFinding::UnreadableArchive { path, .. } => Some(path.clone()),
+                    _ => None,
+                })
+                .collect();
+            findings.extend(ignored.into_iter().filter(|finding| {
+                !matches!(finding, AnalyzeFinding::NestedArchive { path, .. }
+                    if unreadable.contains(path))
+            }));
             findings.push(AnalyzeFinding::NoChaptersFound);
+            depth_mismatch(&mut findings);
             return Ok(CollectedContent {
                 chapters_with_pages: Vec::new(),
                 report: AnalyzeReport {
                     findings,
+                    recommended_depth,
                     ..Default::default()
                 },
             });
         }
         let pages_per_chapter = self.collect_pages(chapters.clone(), None).await?;
         if pages_per_chapter.par_iter().all(Vec::is_empty) {
+            findings.extend(Self::ignored_file_findings(&chapters, &pages_per_chapter).await);
             findings.push(AnalyzeFinding::NoPagesFound);
+            depth_mismatch(&mut findings);
             return Ok(CollectedContent {
                 chapters_with_pages: pages_per_chapter,
                 report: AnalyzeReport {
                     findings,
+                    recommended_depth,
                     ..Default::default()
                 },
             });
@@ -333,20 +840,36 @@ impl<'a> Collector<'a> {
         // Additional analysis checks
 
         // Check for unsupported file types by comparing raw directory contents with collected pages
-        for (chapter_idx, chapter_pages) in pages_per_chapter.iter().enumerate() {
-            if chapter_idx < chapters.len() {
-                // Get all files in this chapter directory (without filtering)
-                if let Ok(all_files) = Self::collect_all_files(&chapters[chapter_idx]).await {
-                    // Find files that were in the directory but not collected (i.e., unsupported)
-                    for file_path in &all_files {
-                        if !chapter_pages.contains(file_path) {
-                            if let Err(_) = crate::types::get_file_info(file_path) {
-                                findings.push(AnalyzeFinding::UnsupportedFileIgnored {
-                                    path: file_path.clone(),
-                                });
-                            }
-                        }
-                    }
+        findings.extend(Self::ignored_file_findings(&chapters, &pages_per_chapter).await);
+
+        // Check for pages split into parts (`12a`, `12b`), which numeric sorting keeps together
+        if self.page_sort == SortSpec::Numeric {
+            let regex = self.page_name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX);
+            for (chapter, pages) in chapters.iter().zip(&pages_per_chapter) {
+                let count = pages
+                    .iter()
+                    .filter(|page| extract_number_suffix_safe(page, regex).is_some())
+                    .count();
+                if count > 0 {
+                    findings.push(AnalyzeFinding::SplitPagesFound {
+                        chapter_path: chapter.clone(),
+                        count,
+                    });
+                }
+            }
+        }
+
+        // Check for missing page numbers (`1, 2, 4, 5`), e.g. from an incomplete download
+        if self.page_gap_check {
+            let regex = self.page_name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX);
+            for (chapter, pages) in chapters.iter().zip(&pages_per_chapter) {
+                let gaps = Self::missing_page_numbers(pages, regex);
+                if !gaps.is_empty() {
+                    log::warn!("Chapter {:?} misses pages {:?}", chapter, gaps);
+                    findings.push(AnalyzeFinding::MissingPages {
+                        chapter: chapter.clone(),
+                        gaps,
+                    });
                 }
             }
         }
@@ -373,6 +896,19 @@ impl<'a> Collector<'a> {
             }
         }
 
+        // Check for chapters without a number (extras, oneshots), which can't be ordered
+        if self.collection_depth != CollectionDepth::Shallow {
+            for chapter in &chapters {
+                if chapter != self.base_directory
+                    && chapter_number(chapter, self.chapter_name_regex).is_none()
+                {
+                    findings.push(AnalyzeFinding::UnnumberedChapter {
+                        chapter_path: chapter.clone(),
+                    });
+                }
+            }
+        }
+
         // Check for file permissions by attempting to read metadata
         for chapter_pages in &pages_per_chapter {
             for page_path in chapter_pages {
@@ -436,6 +972,7 @@ impl<'a> Collector<'a> {
         let report = AnalyzeReport {
             findings,
             recommended_strategy,
+            recommended_depth,
         };
 
         Ok(CollectedContent {
@@ -446,7 +983,8 @@ impl<'a> Collector<'a> {
 
     // Helper methods
 
-    /// Determines whether an image is predominantly grayscale
+    /// Determines whether an image is predominantly grayscale. Sampling stops as soon as
+    /// the verdict can no longer change.
     ///
     /// # Arguments
     ///
@@ -457,67 +995,100 @@ impl<'a> Collector<'a> {
     ///
     /// * `bool` - True if the image is predominantly grayscale
     pub fn is_grayscale(img: &DynamicImage, sensibility: f64) -> bool {
-        // Downsample image if it's too large to improve performance
-        let working_img;
-        let img_to_use =
-            if img.width() > GRAYSCALE_MAX_DIMENSION || img.height() > GRAYSCALE_MAX_DIMENSION {
-                let scale = GRAYSCALE_MAX_DIMENSION as f32 / img.width().max(img.height()) as f32;
-                let new_width = (img.width() as f32 * scale) as u32;
-                let new_height = (img.height() as f32 * scale) as u32;
-                working_img = img.thumbnail(new_width, new_height);
-                &working_img
+        let img_to_use = Self::downsample_for_analysis(img);
+        let sample_count = Self::grayscale_sample_count(&img_to_use);
+        if sample_count == 0 {
+            return false; // Cannot determine grayscale for empty image/samples
+        }
+
+        // More than this share of the samples must be gray
+        let gray_threshold = sample_count as f64 * sensibility;
+        let (mut gray_pixels, mut color_pixels) = (0, 0);
+        for is_gray in Self::grayscale_samples(&img_to_use) {
+            if is_gray {
+                gray_pixels += 1;
             } else {
-                img
-            };
+                color_pixels += 1;
+            }
 
-        let total_pixels = (img_to_use.width() * img_to_use.height()) as f64;
-        let gray_threshold = total_pixels * sensibility;
+            // Stop as soon as the remaining samples can't change the verdict
+            if gray_pixels as f64 > gray_threshold {
+                return true;
+            }
+            if ((sample_count - color_pixels) as f64) <= gray_threshold {
+                return false;
+            }
+        }
+        false
+    }
 
-        // Create chunks of pixels to process in parallel
-        let width = img_to_use.width();
-        let height = img_to_use.height();
+    /// Returns the share (0.0-1.0) of sampled pixels that are gray, the value
+    /// [`Collector::is_grayscale`] compares against the sensibility. Samples the whole
+    /// image, so the result can be reused for any sensibility.
+    pub fn grayscale_share(img: &DynamicImage) -> f64 {
+        let img_to_use = Self::downsample_for_analysis(img);
+        let sample_count = Self::grayscale_sample_count(&img_to_use);
+        if sample_count == 0 {
+            return 0.0;
+        }
+        let gray_pixels = Self::grayscale_samples(&img_to_use)
+            .filter(|&is_gray| is_gray)
+            .count();
+        gray_pixels as f64 / sample_count as f64
+    }
 
-        // Consider only every Nth pixel to speed up processing
-        let samples = (0..height)
-            .step_by(GRAYSCALE_SAMPLE_RATE as usize)
-            .flat_map(|y| {
-                (0..width)
-                    .step_by(GRAYSCALE_SAMPLE_RATE as usize)
-                    .map(move |x| (x, y))
-            })
-            .collect::<Vec<_>>();
+    /// Opens a cover for analysis, preferring its embedded EXIF preview with
+    /// `embedded_thumbnails`. Blocking.
+    pub(crate) fn load_cover(path: &Path, embedded_thumbnails: bool) -> Result<DynamicImage> {
+        let thumbnail = embedded_thumbnails
+            .then(|| embedded_thumbnail(path))
+            .flatten()
+            .and_then(|preview| image::load_from_memory(&preview).ok());
+        match thumbnail {
+            Some(thumbnail) => Ok(thumbnail),
+            None => Ok(image::open(path)?),
+        }
+    }
 
-        if samples.is_empty() {
-            return false; // Cannot determine grayscale for empty image/samples
+    /// Downsamples an image if it's too large to improve analysis performance.
+    fn downsample_for_analysis(img: &DynamicImage) -> Cow<'_, DynamicImage> {
+        if img.width() > GRAYSCALE_MAX_DIMENSION || img.height() > GRAYSCALE_MAX_DIMENSION {
+            let scale = GRAYSCALE_MAX_DIMENSION as f32 / img.width().max(img.height()) as f32;
+            let new_width = (img.width() as f32 * scale) as u32;
+            let new_height = (img.height() as f32 * scale) as u32;
+            Cow::Owned(img.thumbnail(new_width, new_height))
+        } else {
+            Cow::Borrowed(img)
         }
+    }
 
-        let sample_count = samples.len();
+    /// Number of pixels sampled by [`Collector::grayscale_samples`].
+    fn grayscale_sample_count(img: &DynamicImage) -> usize {
+        (img.width().div_ceil(GRAYSCALE_SAMPLE_RATE) * img.height().div_ceil(GRAYSCALE_SAMPLE_RATE))
+            as usize
+    }
 
-        let gray_pixels = samples
-            .par_iter()
+    /// Whether each sampled pixel is gray. Only every Nth pixel is considered to speed up
+    /// processing.
+    fn grayscale_samples(img: &DynamicImage) -> impl Iterator<Item = bool> + '_ {
+        (0..img.height())
+            .step_by(GRAYSCALE_SAMPLE_RATE as usize)
+            .flat_map(move |y| {
+                (0..img.width())
+                    .step_by(GRAYSCALE_SAMPLE_RATE as usize)
+                    .map(move |x| (x, y))
+            })
             .map(|(x, y)| {
-                let pixel = img_to_use.get_pixel(*x, *y);
-                let rgb = pixel.to_rgb();
+                let rgb = img.get_pixel(x, y).to_rgb();
                 let r = rgb.0[0];
                 let g = rgb.0[1];
                 let b = rgb.0[2];
 
                 // Check if the RGB values are close to each other
-                let r_diff = r.abs_diff(g);
-                let g_diff = g.abs_diff(b);
-                let b_diff = b.abs_diff(r);
-
-                r_diff <= RGB_GRAYSCALE_THRESHOLD
-                    && g_diff <= RGB_GRAYSCALE_THRESHOLD
-                    && b_diff <= RGB_GRAYSCALE_THRESHOLD
+                r.abs_diff(g) <= RGB_GRAYSCALE_THRESHOLD
+                    && g.abs_diff(b) <= RGB_GRAYSCALE_THRESHOLD
+                    && b.abs_diff(r) <= RGB_GRAYSCALE_THRESHOLD
             })
-            .filter(|&is_gray| is_gray)
-            .count();
-
-        // Scale back to estimate the full image
-        let estimated_gray_pixels = (gray_pixels as f64 * total_pixels) / sample_count as f64;
-
-        estimated_gray_pixels > gray_threshold
     }
 
     /// Collects directory contents in parallel with filtering options
@@ -534,7 +1105,10 @@ impl<'a> Collector<'a> {
         let mut entries: Vec<PathBuf> = Vec::new();
 
         // Read directory contents
-        let mut paths: ReadDir = read_dir(directory).await.map_err(|e| Error::Io(e))?;
+        let _handle = acquire_file_handle().await?;
+        let mut paths: ReadDir = read_dir(directory)
+            .await
+            .map_err(|e| open_error(e, directory))?;
 
         while let Some(entry) = paths.next_entry().await.map_err(|e| Error::Io(e))? {
             let path = entry.path();
@@ -552,7 +1126,7 @@ impl<'a> Collector<'a> {
 
             // For files (when only_dirs is false), also filter by supported image formats
             if !only_dirs && !is_dir {
-                if let Err(_) = crate::types::get_file_info(&path) {
+                if ImageFormat::from_path(&path).is_err() {
                     continue; // Skip unsupported file formats
                 }
             }
@@ -563,6 +1137,41 @@ impl<'a> Collector<'a> {
         Ok(entries)
     }
 
+    /// Reports the files in `chapters` that aren't among their collected `pages`:
+    /// archives as [`AnalyzeFinding::NestedArchive`], other files that aren't images as
+    /// [`AnalyzeFinding::UnsupportedFileIgnored`]. Unreadable directories are skipped.
+    async fn ignored_file_findings(
+        chapters: &[PathBuf],
+        pages: &[Vec<PathBuf>],
+    ) -> Vec<AnalyzeFinding> {
+        let mut findings = Vec::new();
+        for (chapter, chapter_pages) in chapters.iter().zip(pages) {
+            let Ok(all_files) = Self::collect_all_files(chapter).await else {
+                continue;
+            };
+            for file_path in all_files {
+                if chapter_pages.contains(&file_path) || ImageFormat::from_path(&file_path).is_ok()
+                {
+                    continue;
+                }
+                if is_archive(&file_path) {
+                    log::warn!(
+                        "'{}' is an archive and won't be converted. {}",
+                        path_to_string_lossy(&file_path),
+                        NESTED_ARCHIVE_GUIDANCE
+                    );
+                    findings.push(AnalyzeFinding::NestedArchive {
+                        path: file_path,
+                        alongside_images: !chapter_pages.is_empty(),
+                    });
+                } else {
+                    findings.push(AnalyzeFinding::UnsupportedFileIgnored { path: file_path });
+                }
+            }
+        }
+        findings
+    }
+
     /// Collects all files in a directory without any filtering (used for analysis)
     ///
     /// # Arguments
@@ -576,7 +1185,10 @@ impl<'a> Collector<'a> {
         let mut entries: Vec<PathBuf> = Vec::new();
 
         // Read directory contents
-        let mut paths: ReadDir = read_dir(directory).await.map_err(|e| Error::Io(e))?;
+        let _handle = acquire_file_handle().await?;
+        let mut paths: ReadDir = read_dir(directory)
+            .await
+            .map_err(|e| open_error(e, directory))?;
 
         while let Some(entry) = paths.next_entry().await.map_err(|e| Error::Io(e))? {
             let path = entry.path();
@@ -649,48 +1261,82 @@ impl<'a> Collector<'a> {
         let an = self.regex_parser(a, false); // Assuming this is for pages or chapters where a single number is expected
         let bn = self.regex_parser(b, false);
 
-        an.partial_cmp(&bn).unwrap_or(Ordering::Equal)
+        // Split pages (`12a`, `12b`) follow their base page
+        let regex = self.page_name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX);
+        an.partial_cmp(&bn)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                extract_number_suffix_safe(a, regex).cmp(&extract_number_suffix_safe(b, regex))
+            })
     }
 
-    /// Sorts paths by volume and chapter numbers in filenames.
-    /// Expects filenames in format "volume-chapter" (e.g., "1-15.jpg") or similar pattern.
-    /// Uses the default grouping regex for volume/chapter identification.
-    pub fn sort_by_name_volume_chapter_default(a: &PathBuf, b: &PathBuf) -> Ordering {
-        fn parse_numbers(path: &PathBuf) -> (Option<f64>, Option<f64>) {
-            let file_name = get_file_name_lossy(path);
-            if let Some(caps) = DEFAULT_NAME_GROUPING_REGEX.captures(&file_name) {
-                let full_match = caps.get(0).unwrap().as_str(); // e.g., "01-23.5"
-                let parts: Vec<&str> = full_match.split('-').collect();
-                let volume_part = parts.first().unwrap_or(&"0");
-                let chapter_part_with_ext = parts.get(1).unwrap_or(&"0");
-
-                let volume = volume_part.trim_start_matches('0').parse::<f64>().ok();
-                let chapter = chapter_part_with_ext
-                    .split('.')
-                    .next() // "23.5" -> "23"
-                    .unwrap_or("0")
-                    .trim_start_matches('0')
+    /// Returns the page numbers missing between the lowest and highest page number of a
+    /// chapter, e.g. `[3]` for pages `01.jpg, 02.jpg, 04.jpg, 05.jpg`. Pages without a whole
+    /// number (`credits.jpg`, `12.5.jpg`) are left out. Returns nothing when there are more
+    /// gaps than pages, as such names rarely count pages (dates, IDs).
+    pub(crate) fn missing_page_numbers(pages: &[PathBuf], regex: &Regex) -> Vec<u32> {
+        let mut numbers: Vec<u32> = pages
+            .iter()
+            .filter_map(|page| extract_number_from_filename_safe(page, regex))
+            .filter(|number| number.fract() == 0.0 && *number <= u32::MAX as f64)
+            .map(|number| number as u32)
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup(); // Split pages (`12a`, `12b`) share a number
+
+        let (Some(&first), Some(&last)) = (numbers.first(), numbers.last()) else {
+            return Vec::new();
+        };
+        let missing = (last - first) as usize + 1 - numbers.len();
+        if missing == 0 || missing > numbers.len() {
+            return Vec::new();
+        }
+        (first..=last)
+            .filter(|number| numbers.binary_search(number).is_err())
+            .collect()
+    }
+
+    /// Parses the volume and chapter numbers from a "volume-chapter" name (e.g. `01-23.5`),
+    /// as used by [`VolumeGroupingStrategy::Name`].
+    pub(crate) fn volume_and_chapter_numbers(path: &PathBuf) -> (Option<f64>, Option<f64>) {
+        let file_name = get_file_name_lossy(path);
+        if let Some(caps) = DEFAULT_NAME_GROUPING_REGEX.captures(&file_name) {
+            let full_match = caps.get(0).unwrap().as_str(); // e.g., "01-23.5"
+            let parts: Vec<&str> = full_match.split('-').collect();
+            let volume_part = parts.first().unwrap_or(&"0");
+            let chapter_part_with_ext = parts.get(1).unwrap_or(&"0");
+
+            let volume = volume_part.trim_start_matches('0').parse::<f64>().ok();
+            let chapter = chapter_part_with_ext
+                .split('.')
+                .next() // "23.5" -> "23"
+                .unwrap_or("0")
+                .trim_start_matches('0')
+                .parse::<f64>()
+                .ok();
+
+            // For the decimal part, try to append it if present
+            let decimal_part = chapter_part_with_ext.split('.').nth(1);
+            let chapter = if let (Some(c), Some(d_str)) = (chapter, decimal_part) {
+                d_str
                     .parse::<f64>()
-                    .ok();
-
-                // For the decimal part, try to append it if present
-                let decimal_part = chapter_part_with_ext.split('.').nth(1);
-                let chapter = if let (Some(c), Some(d_str)) = (chapter, decimal_part) {
-                    d_str
-                        .parse::<f64>()
-                        .ok()
-                        .map(|d| c + d / (10_f64.powi(d_str.len() as i32)))
-                } else {
-                    chapter
-                };
+                    .ok()
+                    .map(|d| c + d / (10_f64.powi(d_str.len() as i32)))
+            } else {
+                chapter
+            };
 
-                return (volume, chapter);
-            }
-            (None, None)
+            return (volume, chapter);
         }
+        (None, None)
+    }
 
-        let (a_vol, a_chap) = parse_numbers(a);
-        let (b_vol, b_chap) = parse_numbers(b);
+    /// Sorts paths by volume and chapter numbers in filenames.
+    /// Expects filenames in format "volume-chapter" (e.g., "1-15.jpg") or similar pattern.
+    /// Uses the default grouping regex for volume/chapter identification.
+    pub fn sort_by_name_volume_chapter_default(a: &PathBuf, b: &PathBuf) -> Ordering {
+        let (a_vol, a_chap) = Self::volume_and_chapter_numbers(a);
+        let (b_vol, b_chap) = Self::volume_and_chapter_numbers(b);
 
         match a_vol.partial_cmp(&b_vol) {
             Some(Ordering::Equal) => a_chap.partial_cmp(&b_chap).unwrap_or(Ordering::Equal),
@@ -0,0 +1,355 @@
+//! Explanations of how a source is ordered and grouped, for debugging wrong output order.
+//!
+//! [`HozonConfig::explain_sort`] lists the numbers extracted from every chapter and page
+//! name, and [`HozonConfig::explain_grouping`] lists where volumes break and why. Both
+//! return structured data whose [`Display`](std::fmt::Display) output is meant for humans:
+//!
+//! ```text
+//! Grouping strategy: Name
+//!   [0] '02-011' → vol 2, ch 11 (volume 1)
+//!   [1] '03-012' → vol 3, ch 12 (volume 2)
+//! Break before index 1 because volume changed 2→3
+//! ```
+//!
+//! [`HozonConfig::sweep_sensibility`] helps tuning
+//! [`VolumeGroupingStrategy::ImageAnalysis`] by counting the volumes found at several
+//! sensibilities.
+
+use regex::Regex;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::HozonConfig;
+use crate::collector::{Collector, DEFAULT_NUMBER_REGEX, chapter_number};
+use crate::error::Result;
+use crate::path_utils::{extract_number_from_filename_safe, get_file_name_lossy};
+use crate::types::{SortSpec, VolumeGroupingStrategy};
+
+/// The sort key of one page, see [`HozonConfig::explain_sort`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageSortKey {
+    pub path: PathBuf,
+    pub number: Option<f64>, // None if the rule extracts no number (or none was found)
+}
+
+/// The sort key of one chapter and its pages, in collection order.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChapterSortKey {
+    pub path: PathBuf,
+    pub number: Option<f64>,
+    pub pages: Vec<PageSortKey>,
+}
+
+/// How the chapters and pages of a source were ordered during collection.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SortExplanation {
+    pub chapter_rule: String, // e.g. "by number, pattern `\d+\.?\d*`"
+    pub page_rule: String,
+    pub chapters: Vec<ChapterSortKey>,
+}
+
+/// One chapter in the order it was grouped, with the volume it ended up in.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChapterGrouping {
+    pub path: PathBuf,
+    pub volume_number: usize,        // 1-based output volume
+    pub parsed_volume: Option<f64>,  // Volume number in the name, for the `Name` strategy
+    pub parsed_chapter: Option<f64>, // Chapter number in the name, for `Name` and `Mapping`
+}
+
+/// A volume starting at chapter `index` (0-based, in grouping order).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VolumeBreak {
+    pub index: usize,
+    pub reason: String,
+}
+
+/// How the chapters of a source were grouped into volumes.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupingExplanation {
+    pub strategy: VolumeGroupingStrategy,
+    pub chapters: Vec<ChapterGrouping>,
+    pub breaks: Vec<VolumeBreak>, // Every volume after the first starts with a break
+}
+
+/// Describes the ordering applied by `sort`.
+fn describe_sort(sort: &SortSpec, name_regex: Option<&Regex>) -> String {
+    let number_pattern = name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX).as_str();
+    match sort {
+        SortSpec::Numeric => format!("by number, pattern `{}`", number_pattern),
+        SortSpec::Natural => "natural order".to_string(),
+        SortSpec::Lexicographic => "by name".to_string(),
+        SortSpec::RegexCapture { pattern, group } => {
+            format!("by number in group {} of `{}`", group, pattern)
+        }
+        SortSpec::ExifDate => format!(
+            "by EXIF capture time, then by number, pattern `{}`",
+            number_pattern
+        ),
+    }
+}
+
+/// The number `sort` orders `path` by, if it orders by number.
+fn sort_number(sort: &SortSpec, name_regex: Option<&Regex>, path: &Path) -> Option<f64> {
+    match sort {
+        SortSpec::Numeric | SortSpec::ExifDate => {
+            extract_number_from_filename_safe(path, name_regex.unwrap_or(&DEFAULT_NUMBER_REGEX))
+        }
+        SortSpec::RegexCapture { pattern, group } => Regex::new(pattern)
+            .ok()?
+            .captures(&get_file_name_lossy(path))?
+            .get(*group)?
+            .as_str()
+            .parse()
+            .ok(),
+        SortSpec::Natural | SortSpec::Lexicographic => None,
+    }
+}
+
+fn format_number(number: Option<f64>) -> String {
+    number.map_or_else(|| "no number".to_string(), |n| n.to_string())
+}
+
+fn format_name(path: &Path) -> String {
+    format!("'{}'", get_file_name_lossy(path))
+}
+
+/// The directory of a chapter, derived from its first page.
+fn chapter_directory(pages: &[PathBuf]) -> PathBuf {
+    pages
+        .first()
+        .and_then(|page| page.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+impl HozonConfig {
+    /// Collects the source and explains the resulting order: the rule applied to chapters
+    /// and pages, and the number extracted from every name.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the source can't be analyzed, see [`analyze_source`](HozonConfig::analyze_source).
+    pub async fn explain_sort(&self) -> Result<SortExplanation> {
+        let collected = self.analyze_source().await?;
+        let chapter_regex = self.compiled_chapter_name_regex.as_ref();
+        let page_regex = self.compiled_page_name_regex.as_ref();
+        let page_sort = self.effective_page_sort();
+
+        let chapters = collected
+            .chapters_with_pages
+            .iter()
+            .map(|pages| {
+                let path = chapter_directory(pages);
+                ChapterSortKey {
+                    number: sort_number(&self.chapter_sort, chapter_regex, &path),
+                    path,
+                    pages: pages
+                        .iter()
+                        .map(|page| PageSortKey {
+                            path: page.clone(),
+                            number: sort_number(&page_sort, page_regex, page),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Ok(SortExplanation {
+            chapter_rule: describe_sort(&self.chapter_sort, chapter_regex),
+            page_rule: describe_sort(&page_sort, page_regex),
+            chapters,
+        })
+    }
+
+    /// Collects the source and counts the volumes
+    /// [`VolumeGroupingStrategy::ImageAnalysis`] finds at each of `sensibilities` (0-100),
+    /// decoding every cover only once. See [`Collector::sweep_sensibility`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the source can't be analyzed, see [`analyze_source`](HozonConfig::analyze_source).
+    pub async fn sweep_sensibility(
+        &self,
+        sensibilities: impl IntoIterator<Item = u8>,
+    ) -> Result<Vec<(u8, usize)>> {
+        let collected = self.analyze_source().await?;
+        self.structuring_collector()
+            .await?
+            .sweep_sensibility(collected.chapters_with_pages, sensibilities)
+            .await
+    }
+
+    /// Collects and structures the source and explains the volume breaks: the volume of
+    /// every chapter and the reason each volume starts where it does.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the source can't be analyzed or structured, see
+    /// [`structure_from_source`](HozonConfig::structure_from_source).
+    pub async fn explain_grouping(&self) -> Result<GroupingExplanation> {
+        let collected = self.analyze_source().await?;
+        let structured =
+            Self::perform_structuring(self, collected.chapters_with_pages.clone()).await?;
+        let strategy = self.volume_grouping_strategy;
+        let extras_volume = structured
+            .volumes_with_chapters_and_pages
+            .last()
+            .filter(|volume| self.is_extras_volume(volume))
+            .map(|_| structured.volumes_with_chapters_and_pages.len());
+
+        let mut chapters = Vec::new();
+        if strategy == VolumeGroupingStrategy::Flat {
+            // The single volume holds one merged chapter; list the chapters it came from
+            for pages in &collected.chapters_with_pages {
+                chapters.push((chapter_directory(pages), 1));
+            }
+        } else {
+            for (volume_index, volume) in structured
+                .volumes_with_chapters_and_pages
+                .iter()
+                .enumerate()
+            {
+                for pages in volume {
+                    chapters.push((chapter_directory(pages), volume_index + 1));
+                }
+            }
+        }
+
+        let chapters: Vec<ChapterGrouping> = chapters
+            .into_iter()
+            .map(|(path, volume_number)| {
+                let (parsed_volume, parsed_chapter) = match strategy {
+                    VolumeGroupingStrategy::Name => Collector::volume_and_chapter_numbers(&path),
+                    VolumeGroupingStrategy::Mapping => (
+                        None,
+                        chapter_number(&path, self.compiled_chapter_name_regex.as_ref()),
+                    ),
+                    _ => (None, None),
+                };
+                ChapterGrouping {
+                    path,
+                    volume_number,
+                    parsed_volume,
+                    parsed_chapter,
+                }
+            })
+            .collect();
+
+        let mut breaks = Vec::new();
+        for index in 1..chapters.len() {
+            let (previous, current) = (&chapters[index - 1], &chapters[index]);
+            if previous.volume_number == current.volume_number {
+                continue;
+            }
+            let reason = match strategy {
+                _ if Some(current.volume_number) == extras_volume => {
+                    "unnumbered chapters are collected in the Extras volume".to_string()
+                }
+                VolumeGroupingStrategy::Name => format!(
+                    "volume changed {}→{}",
+                    format_number(previous.parsed_volume),
+                    format_number(current.parsed_volume)
+                ),
+                VolumeGroupingStrategy::ImageAnalysis => {
+                    let confidence = structured
+                        .report
+                        .cover_breaks
+                        .iter()
+                        .find(|cover_break| cover_break.index == index)
+                        .map_or(String::new(), |cover_break| {
+                            format!(", confidence {:.2}", cover_break.confidence)
+                        });
+                    format!(
+                        "the first page of {} looks like a cover (grayscale at sensibility {}{})",
+                        format_name(&current.path),
+                        self.image_analysis_sensibility,
+                        confidence
+                    )
+                }
+                VolumeGroupingStrategy::Manual => format!(
+                    "volume {} is limited to {} chapters by volume_sizes_override",
+                    previous.volume_number,
+                    structured.report.chapter_counts_per_volume[previous.volume_number - 1]
+                ),
+                VolumeGroupingStrategy::PerChapter => "every chapter is its own volume".to_string(),
+                VolumeGroupingStrategy::Mapping => format!(
+                    "volume_mapping_file maps chapter {} to a later volume than chapter {}",
+                    format_number(current.parsed_chapter),
+                    format_number(previous.parsed_chapter)
+                ),
+                VolumeGroupingStrategy::Flat => continue,
+            };
+            breaks.push(VolumeBreak { index, reason });
+        }
+
+        Ok(GroupingExplanation {
+            strategy,
+            chapters,
+            breaks,
+        })
+    }
+}
+
+impl fmt::Display for SortExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Chapters ordered {}", self.chapter_rule)?;
+        writeln!(f, "Pages ordered {}", self.page_rule)?;
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            writeln!(
+                f,
+                "  [{}] {} → {}",
+                index,
+                format_name(&chapter.path),
+                format_number(chapter.number)
+            )?;
+            for page in &chapter.pages {
+                writeln!(
+                    f,
+                    "      {} → {}",
+                    format_name(&page.path),
+                    format_number(page.number)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GroupingExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Grouping strategy: {:?}", self.strategy)?;
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            write!(f, "  [{}] {}", index, format_name(&chapter.path))?;
+            if self.strategy == VolumeGroupingStrategy::Name {
+                write!(
+                    f,
+                    " → vol {}, ch {}",
+                    format_number(chapter.parsed_volume),
+                    format_number(chapter.parsed_chapter)
+                )?;
+            }
+            writeln!(f, " (volume {})", chapter.volume_number)?;
+        }
+        for volume_break in &self.breaks {
+            writeln!(
+                f,
+                "Break before index {} because {}",
+                volume_break.index, volume_break.reason
+            )?;
+        }
+        Ok(())
+    }
+}
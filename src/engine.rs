@@ -0,0 +1,153 @@
+//! Shared conversion settings for long-running processes.
+//!
+//! A [`HozonEngine`] is created once from a [`HozonConfig`] holding the settings common to
+//! all conversions (format, grouping strategy, regexes, processing options, ...). Its
+//! settings are validated and its regexes compiled up front; every conversion then only
+//! supplies a [`ConversionRequest`] with its own metadata and paths. Cloning the engine is
+//! cheap, and all clones draw from the same [`RuntimeLimits`] pools, so a server can hand
+//! a clone to every request handler.
+//!
+//! ```rust,no_run
+//! # use hozon::prelude::*;
+//! # use hozon::engine::{ConversionRequest, HozonEngine};
+//! # #[tokio::main]
+//! # async fn main() -> hozon::error::Result<()> {
+//! let engine = HozonEngine::new(
+//!     HozonConfig::builder()
+//!         .output_format(FileFormat::Epub)
+//!         .runtime_limits(RuntimeLimits::new(4, 16)?)
+//!         .build()?,
+//! )?;
+//!
+//! let request = ConversionRequest {
+//!     metadata: EbookMetadata::default_with_title("My Comic".to_string()),
+//!     source_path: PathBuf::from("./uploads/1234"),
+//!     target_path: PathBuf::from("./output/1234"),
+//! };
+//! let report = engine.convert_from_source(request, CoverOptions::None).await?;
+//! println!("Wrote {} files", report.files.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::HozonConfig;
+use crate::error::Result;
+use crate::pipeline::HozonPipeline;
+use crate::runtime::RuntimeLimits;
+use crate::types::{CollectedContent, ConversionReport, CoverOptions, EbookMetadata};
+
+/// The per-conversion part of a configuration run by a [`HozonEngine`].
+#[derive(Debug, Clone)]
+pub struct ConversionRequest {
+    pub metadata: EbookMetadata,
+    pub source_path: PathBuf, // Only used by conversions starting from the source directory
+    pub target_path: PathBuf,
+}
+
+/// A validated, cheap-to-clone conversion configuration, see the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct HozonEngine {
+    config: Arc<HozonConfig>,
+}
+
+impl HozonEngine {
+    /// Validates the settings of `config` and wraps them in an engine.
+    ///
+    /// The metadata and paths of `config` are ignored; each conversion takes them from its
+    /// [`ConversionRequest`]. Without [`runtime_limits`](HozonConfig::runtime_limits), the
+    /// engine uses [`RuntimeLimits::default`] for all its conversions together.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the same errors as [`HozonConfig::preflight_check`] for invalid settings.
+    pub fn new(mut config: HozonConfig) -> Result<Self> {
+        config.validate_settings()?;
+        if config.runtime_limits.is_none() {
+            config.runtime_limits = Some(RuntimeLimits::default());
+        }
+        Ok(Self {
+            config: Arc::new(config),
+        })
+    }
+
+    /// The shared settings of this engine.
+    pub fn config(&self) -> &HozonConfig {
+        &self.config
+    }
+
+    /// The concurrency limits shared by all conversions of this engine.
+    pub fn runtime_limits(&self) -> &RuntimeLimits {
+        self.config
+            .runtime_limits
+            .as_ref()
+            .expect("set by HozonEngine::new")
+    }
+
+    /// Returns the full configuration for `request`. Compiled regexes are shared, not
+    /// recompiled.
+    pub fn configure(&self, request: ConversionRequest) -> HozonConfig {
+        HozonConfig {
+            metadata: request.metadata,
+            source_path: request.source_path,
+            target_path: request.target_path,
+            ..HozonConfig::clone(&self.config)
+        }
+    }
+
+    /// Starts a staged conversion for `request`, see [`HozonPipeline`].
+    pub fn pipeline(&self, request: ConversionRequest) -> HozonPipeline {
+        HozonPipeline::prevalidated(self.configure(request))
+    }
+
+    /// Analyzes the source directory of `request`, see [`HozonConfig::analyze_source`].
+    pub async fn analyze_source(&self, request: ConversionRequest) -> Result<CollectedContent> {
+        self.configure(request).analyze_source().await
+    }
+
+    /// Converts the source directory of `request`, see [`HozonConfig::convert_from_source`].
+    pub async fn convert_from_source(
+        &self,
+        request: ConversionRequest,
+        cover_options: CoverOptions,
+    ) -> Result<ConversionReport> {
+        self.pipeline(request)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(cover_options)
+            .await
+    }
+
+    /// Converts pre-collected chapters, see [`HozonConfig::convert_from_collected_data`].
+    pub async fn convert_from_collected_data(
+        &self,
+        request: ConversionRequest,
+        collected_data: Vec<Vec<PathBuf>>,
+        cover_options: CoverOptions,
+    ) -> Result<ConversionReport> {
+        self.pipeline(request)
+            .from_chapters(collected_data)?
+            .structure()
+            .await?
+            .generate(cover_options)
+            .await
+    }
+
+    /// Converts pre-structured volumes, see [`HozonConfig::convert_from_structured_data`].
+    pub async fn convert_from_structured_data(
+        &self,
+        request: ConversionRequest,
+        structured_data: Vec<Vec<Vec<PathBuf>>>,
+        cover_options: CoverOptions,
+    ) -> Result<ConversionReport> {
+        self.pipeline(request)
+            .from_volumes(structured_data)?
+            .generate(cover_options)
+            .await
+    }
+}
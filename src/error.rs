@@ -138,6 +138,58 @@ pub enum Error {
     /// or when paths don't meet expected criteria.
     #[error("The given path '{0:?}' is invalid: {1}")]
     InvalidPath(PathBuf, String),
+    /// Error for source content that changed while a conversion was running.
+    ///
+    /// Raised when a chapter directory gained, lost, or modified pages between
+    /// analysis and generation and the source change policy is `Fail`.
+    #[error("Source changed during conversion in '{0:?}': {1}")]
+    SourceChanged(PathBuf, String),
+    /// Error for chapters that structuring couldn't place in any volume.
+    ///
+    /// Raised when the volumes hold fewer chapters (second field) than were collected
+    /// (first field) and the lost chapter policy is `Fail`.
+    #[error("Structuring placed only {1} of {0} collected chapters in volumes")]
+    LostChapters(usize, usize),
+    /// Error for output directories locked by another running conversion.
+    ///
+    /// Raised when output directory locking is enabled and a live lock file
+    /// from another process is present in the output directory.
+    #[error("Target directory '{0:?}' is locked: {1}")]
+    TargetLocked(PathBuf, String),
+    /// Error for output directories holding outputs of another series.
+    ///
+    /// Raised when the output directory already contains outputs whose fingerprint or
+    /// series manifest names a different series and the foreign output policy is `Fail`.
+    #[error("Output directory '{0:?}' holds outputs of another series: {1}")]
+    ForeignOutputs(PathBuf, String),
+    /// Error for EPUB files rejected by the built-in validator.
+    ///
+    /// Raised when strict EPUB output is enabled and the generated book has
+    /// problems that epubcheck would report; the message lists all of them.
+    #[error("EPUB '{0:?}' failed validation: {1}")]
+    InvalidEpub(PathBuf, String),
+    /// Error for generated files that fail verification before source cleanup.
+    ///
+    /// Raised when [`HozonConfig::source_cleanup`](crate::HozonConfig::source_cleanup)
+    /// would move or delete the source, but a generated file can't be read back intact.
+    /// The source is left untouched.
+    #[error("Output '{0:?}' failed verification, the source was kept: {1}")]
+    InvalidOutput(PathBuf, String),
+    /// Error for output files locked by another process.
+    ///
+    /// Raised on Windows when a file stays locked (sharing violation) after several
+    /// retries, typically because an antivirus scanner or the search indexer holds a
+    /// freshly written archive open.
+    #[error("File '{0:?}' is locked by another process (e.g. an antivirus scanner): {1}")]
+    FileLocked(PathBuf, String),
+    /// Error for running out of file handles.
+    ///
+    /// Raised when the operating system refuses to open another file because the
+    /// process or system limit on open files was reached (EMFILE/ENFILE).
+    #[error(
+        "Too many open files while opening '{0:?}'; raise the open file limit (e.g. `ulimit -n`) or run fewer conversions at once"
+    )]
+    TooManyOpenFiles(PathBuf),
     /// Error for paths that exceed system limitations.
     ///
     /// Indicates that a file path is too long for the current system
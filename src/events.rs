@@ -0,0 +1,373 @@
+//! Pipeline events for automation around conversions.
+//!
+//! Every sink in [`HozonConfig::event_sinks`](crate::HozonConfig::event_sinks) receives a
+//! [`PipelineEvent`] when a stage finishes, a volume starts or finishes, and when the
+//! conversion ends. Built-in sinks cover the common integrations:
+//!
+//! - [`LogSink`]: Logs a one-line summary of every event
+//! - [`ChannelSink`]: Forwards events into a Tokio channel, e.g. to drive a progress bar
+//! - [`JsonLinesSink`]: Appends one JSON object per event to a file
+//! - [`WebhookSink`]: POSTs events as JSON to a URL. Requires the `webhook` feature
+//!
+//! Sinks are called from the conversion tasks and must return quickly; slow work (like
+//! HTTP requests) belongs on a separate thread, as [`WebhookSink`] does. Sinks can't
+//! fail a conversion: errors are logged and the event is dropped.
+//!
+//! ```rust,no_run
+//! # use hozon::prelude::*;
+//! # use hozon::events::{ChannelSink, LogSink, PipelineEvent};
+//! # use std::sync::Arc;
+//! # #[tokio::main]
+//! # async fn main() -> hozon::error::Result<()> {
+//! let (sink, mut events) = ChannelSink::new();
+//! let config = HozonConfig::builder()
+//!     .metadata(EbookMetadata::default_with_title("My Comic".to_string()))
+//!     .source_path(PathBuf::from("./source"))
+//!     .target_path(PathBuf::from("./output"))
+//!     .event_sinks(vec![Arc::new(sink) as Arc<dyn EventSink>, Arc::new(LogSink)])
+//!     .build()?;
+//!
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         if let PipelineEvent::VolumeFinished { path, .. } = event {
+//!             println!("Wrote {:?}", path);
+//!         }
+//!     }
+//! });
+//! config.convert_from_source(CoverOptions::None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::{Value, json};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::error::Result;
+use crate::path_utils::{path_to_string_lossy, prepare_long_path};
+
+/// Something that happened during a conversion run.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PipelineEvent {
+    /// The source was scanned and analyzed.
+    AnalysisFinished { chapters: usize, pages: usize },
+    /// Chapters were grouped into volumes.
+    StructureFinished { volumes: usize },
+    /// Generation of a volume started.
+    VolumeStarted {
+        volume_number: usize,       // 1-based
+        part_number: Option<usize>, // 1-based, if the volume is split into several files
+    },
+    /// A volume was written completely.
+    VolumeFinished {
+        volume_number: usize,
+        part_number: Option<usize>,
+        path: PathBuf,
+        page_count: usize,
+        duration: Duration,
+    },
+    /// Every volume was written.
+    ConversionFinished { files: usize, duration: Duration },
+    /// The conversion failed with `error`.
+    ConversionFailed { error: String },
+}
+
+impl PipelineEvent {
+    /// The name of the event, e.g. `volume_finished`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PipelineEvent::AnalysisFinished { .. } => "analysis_finished",
+            PipelineEvent::StructureFinished { .. } => "structure_finished",
+            PipelineEvent::VolumeStarted { .. } => "volume_started",
+            PipelineEvent::VolumeFinished { .. } => "volume_finished",
+            PipelineEvent::ConversionFinished { .. } => "conversion_finished",
+            PipelineEvent::ConversionFailed { .. } => "conversion_failed",
+        }
+    }
+
+    /// A one-line, human-readable description, e.g. for chat notifications.
+    pub fn summary(&self) -> String {
+        let volume = |number: &usize, part: &Option<usize>| match part {
+            Some(part) => format!("Volume {} (part {})", number, part),
+            None => format!("Volume {}", number),
+        };
+        match self {
+            PipelineEvent::AnalysisFinished { chapters, pages } => {
+                format!("Found {} chapters with {} pages", chapters, pages)
+            }
+            PipelineEvent::StructureFinished { volumes } => {
+                format!("Planned {} volumes", volumes)
+            }
+            PipelineEvent::VolumeStarted {
+                volume_number,
+                part_number,
+            } => format!("Generating {}", volume(volume_number, part_number)),
+            PipelineEvent::VolumeFinished {
+                volume_number,
+                part_number,
+                path,
+                page_count,
+                duration,
+            } => format!(
+                "Finished {} with {} pages in {:.1}s: '{}'",
+                volume(volume_number, part_number),
+                page_count,
+                duration.as_secs_f64(),
+                path_to_string_lossy(path)
+            ),
+            PipelineEvent::ConversionFinished { files, duration } => format!(
+                "Conversion finished: {} files in {:.1}s",
+                files,
+                duration.as_secs_f64()
+            ),
+            PipelineEvent::ConversionFailed { error } => format!("Conversion failed: {}", error),
+        }
+    }
+
+    /// The event as a JSON object with an `event` field holding its [`name`](Self::name).
+    /// Durations are given in milliseconds.
+    pub fn to_json(&self) -> Value {
+        let details = match self {
+            PipelineEvent::AnalysisFinished { chapters, pages } => {
+                json!({ "chapters": chapters, "pages": pages })
+            }
+            PipelineEvent::StructureFinished { volumes } => json!({ "volumes": volumes }),
+            PipelineEvent::VolumeStarted {
+                volume_number,
+                part_number,
+            } => json!({ "volume_number": volume_number, "part_number": part_number }),
+            PipelineEvent::VolumeFinished {
+                volume_number,
+                part_number,
+                path,
+                page_count,
+                duration,
+            } => json!({
+                "volume_number": volume_number,
+                "part_number": part_number,
+                "path": path_to_string_lossy(path),
+                "page_count": page_count,
+                "duration_ms": duration.as_millis() as u64,
+            }),
+            PipelineEvent::ConversionFinished { files, duration } => {
+                json!({ "files": files, "duration_ms": duration.as_millis() as u64 })
+            }
+            PipelineEvent::ConversionFailed { error } => json!({ "error": error }),
+        };
+        let mut object = json!({ "event": self.name() });
+        if let (Some(object), Value::Object(details)) = (object.as_object_mut(), details) {
+            object.extend(details);
+        }
+        object
+    }
+}
+
+/// Receives the events of conversion runs, see the [module documentation](self).
+pub trait EventSink: Send + Sync {
+    /// Handles `event`. Called from the conversion tasks, so it must not block for long.
+    fn send(&self, event: &PipelineEvent);
+}
+
+/// Sends `event` to every sink in `sinks`.
+pub(crate) fn emit(sinks: &[Arc<dyn EventSink>], event: PipelineEvent) {
+    for sink in sinks {
+        sink.send(&event);
+    }
+}
+
+/// Logs the [summary](PipelineEvent::summary) of every event at info level (warn level
+/// for failures).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl EventSink for LogSink {
+    fn send(&self, event: &PipelineEvent) {
+        match event {
+            PipelineEvent::ConversionFailed { .. } => log::warn!("{}", event.summary()),
+            _ => log::info!("{}", event.summary()),
+        }
+    }
+}
+
+/// Forwards every event into an unbounded Tokio channel.
+#[derive(Debug, Clone)]
+pub struct ChannelSink(UnboundedSender<PipelineEvent>);
+
+impl ChannelSink {
+    /// Creates a sink and the receiver its events arrive at.
+    pub fn new() -> (Self, UnboundedReceiver<PipelineEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self(sender), receiver)
+    }
+
+    /// Creates a sink sending into an existing channel, e.g. one shared by several
+    /// conversions.
+    pub fn from_sender(sender: UnboundedSender<PipelineEvent>) -> Self {
+        Self(sender)
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn send(&self, event: &PipelineEvent) {
+        // A dropped receiver just means nobody listens anymore
+        let _ = self.0.send(event.clone());
+    }
+}
+
+/// Appends every event as one line of [JSON](PipelineEvent::to_json) to a file.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl JsonLinesSink {
+    /// Opens `path` for appending, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be opened.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(prepare_long_path(&path)?)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn send(&self, event: &PipelineEvent) {
+        let line = format!("{}\n", event.to_json());
+        let result = match self.file.lock() {
+            Ok(mut file) => file.write_all(line.as_bytes()),
+            Err(_) => return,
+        };
+        if let Err(e) = result {
+            log::warn!(
+                "Failed to write event to '{}': {}",
+                path_to_string_lossy(&self.path),
+                e
+            );
+        }
+    }
+}
+
+/// Builds the request body of a [`WebhookSink`] for an event; `None` skips the event.
+pub type WebhookBody = Arc<dyn Fn(&PipelineEvent) -> Option<Value> + Send + Sync>;
+
+/// POSTs every event as JSON to a URL. Requires the `webhook` feature.
+///
+/// Requests are sent one at a time from a background thread, so slow endpoints don't
+/// hold up the conversion; failed requests are logged and not retried. The body defaults
+/// to [`PipelineEvent::to_json`]; [`with_body`](Self::with_body) adapts it to services
+/// expecting their own format, e.g. for a Discord webhook:
+///
+/// ```rust,no_run
+/// # use hozon::events::{PipelineEvent, WebhookSink};
+/// # use serde_json::json;
+/// # use std::sync::Arc;
+/// let sink = WebhookSink::new("https://discord.com/api/webhooks/...").with_body(Arc::new(
+///     |event: &PipelineEvent| match event {
+///         PipelineEvent::VolumeFinished { .. } | PipelineEvent::ConversionFailed { .. } => {
+///             Some(json!({ "content": event.summary() }))
+///         }
+///         _ => None,
+///     },
+/// ));
+/// ```
+#[derive(Clone)]
+pub struct WebhookSink {
+    url: String,
+    body: WebhookBody,
+    #[cfg_attr(not(feature = "webhook"), allow(dead_code))]
+    requests: Arc<Mutex<Option<std::sync::mpsc::Sender<Value>>>>,
+}
+
+impl std::fmt::Debug for WebhookSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookSink")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebhookSink {
+    /// Timeout of a single request.
+    pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates a sink posting to `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            body: Arc::new(|event| Some(event.to_json())),
+            requests: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Replaces how request bodies are built from events.
+    pub fn with_body(mut self, body: WebhookBody) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// The URL events are posted to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Queues `body` for the background thread, starting it on first use.
+    #[cfg(feature = "webhook")]
+    fn post(&self, body: Value) {
+        let Ok(mut requests) = self.requests.lock() else {
+            return;
+        };
+        let sender = requests.get_or_insert_with(|| {
+            let (sender, receiver) = std::sync::mpsc::channel::<Value>();
+            let url = self.url.clone();
+            std::thread::spawn(move || {
+                let agent = ureq::Agent::config_builder()
+                    .timeout_global(Some(Self::REQUEST_TIMEOUT))
+                    .build()
+                    .new_agent();
+                // Ends once every clone of the sink is dropped
+                for body in receiver {
+                    let result = agent
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .send(body.to_string());
+                    if let Err(e) = result {
+                        log::warn!("Failed to post event to webhook '{}': {}", url, e);
+                    }
+                }
+            });
+            sender
+        });
+        let _ = sender.send(body);
+    }
+
+    #[cfg(not(feature = "webhook"))]
+    fn post(&self, _body: Value) {
+        log::warn!(
+            "Can't post event to webhook '{}': requires the `webhook` feature",
+            self.url
+        );
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn send(&self, event: &PipelineEvent) {
+        if let Some(body) = (self.body)(event) {
+            self.post(body);
+        }
+    }
+}
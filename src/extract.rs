@@ -0,0 +1,498 @@
+//! Unpacking CBZ and EPUB files back into page directories, see
+//! [`HozonConfig::extract_to_directory`](crate::HozonConfig::extract_to_directory).
+//!
+//! An ebook is extracted into one directory per chapter, numbered in reading order and
+//! named after the chapter (`001 Chapter 1/`), holding the pages as `001.jpg`, `002.png`,
+//! and so on. A separate cover is written as `cover.<ext>` next to the chapters, and the
+//! metadata document of the ebook as it is: `ComicInfo.xml` for CBZ files and
+//! `metadata.opf` (the Calibre convention) for EPUB files.
+//!
+//! Chapters are recovered from the page bookmarks of the ComicInfo.xml, or the folders
+//! of the archive, in CBZ files, and from the table of contents in EPUB files. Ebooks
+//! without either are extracted as a single chapter.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::error::{Error, Result};
+use crate::generator::epub_check::{directory_of, resolve};
+use crate::generator::epub_zip::OPF_PATH;
+use crate::metadata::{parse_comicinfo, parse_opf, series_position, unescape_xml};
+use crate::path_utils::{compare_names_natural, path_to_string_lossy, sanitize_filename};
+use crate::source_archive::is_page_entry;
+use crate::types::{EbookMetadata, ExtractedEbook, ImageFormat, VolumeLabel};
+
+/// Name of the metadata document written for EPUB files.
+const OPF_FILE_NAME: &str = "metadata.opf";
+
+lazy_static! {
+    /// A `<Page>` element of the ComicInfo.xml page list.
+    static ref COMIC_INFO_PAGE: Regex = Regex::new(r"<Page\s([^>]*?)/?>").unwrap();
+    /// A manifest item of an OPF package document.
+    static ref OPF_ITEM: Regex = Regex::new(r"<item\s([^>]*?)/?>").unwrap();
+    /// A spine entry of an OPF package document.
+    static ref OPF_ITEMREF: Regex = Regex::new(r#"<itemref\s[^>]*?idref="([^"]*)""#).unwrap();
+    /// The image shown by an XHTML page, as `<img src>` or SVG `<image href>`.
+    static ref PAGE_IMAGE: Regex =
+        Regex::new(r#"<(?:img|image)\s[^>]*?(?:src|xlink:href|href)="([^"]*)""#).unwrap();
+    /// A link of an EPUB 3 navigation document.
+    static ref NAV_LINK: Regex = Regex::new(r#"(?s)<a\s[^>]*?href="([^"]*)"[^>]*>(.*?)</a>"#).unwrap();
+    /// A navigation point of an EPUB 2 NCX document.
+    static ref NCX_POINT: Regex =
+        Regex::new(r#"(?s)<text>(.*?)</text>\s*</navLabel>\s*<content\s[^>]*?src="([^"]*)""#).unwrap();
+    /// The page suffix of table of contents entries written with `TocStyle::Pages`.
+    static ref PAGE_SUFFIX: Regex = Regex::new(r" - Page \d+$").unwrap();
+    /// Markup inside a table of contents label.
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+/// The contents of an ebook, by archive entry name.
+struct Book {
+    metadata: EbookMetadata,
+    volume: Option<usize>,
+    metadata_file: Option<(&'static str, String)>, // File name and contents of the metadata document
+    cover: Option<String>,
+    chapters: Vec<(String, Vec<String>)>, // Title and pages of each chapter
+}
+
+/// Extracts the CBZ or EPUB file `ebook` into the new directory `directory`, decrypting
+/// CBZ entries with `password` if needed. EPUB titles lose the volume number rendered with
+/// `volume_label`. Blocking.
+pub(crate) fn extract(
+    ebook: &Path,
+    directory: &Path,
+    password: Option<&str>,
+    volume_label: &VolumeLabel,
+) -> Result<ExtractedEbook> {
+    let invalid = |reason: String| Error::InvalidPath(ebook.to_path_buf(), reason);
+    let name = path_to_string_lossy(ebook).to_lowercase();
+    let is_epub = name.ends_with(".epub");
+    if !is_epub && !name.ends_with(".cbz") && !name.ends_with(".zip") {
+        return Err(Error::Unsupported(format!(
+            "Extracting '{}'; only CBZ and EPUB files can be extracted",
+            path_to_string_lossy(ebook)
+        )));
+    }
+    if directory.exists() {
+        return Err(Error::InvalidPath(
+            directory.to_path_buf(),
+            "the extraction directory already exists".to_string(),
+        ));
+    }
+
+    let mut archive = ZipArchive::new(File::open(ebook).map_err(|e| invalid(e.to_string()))?)
+        .map_err(|e| invalid(format!("Failed to open archive: {}", e)))?;
+    let title = ebook
+        .file_stem()
+        .map(|stem| {
+            stem.to_string_lossy()
+                .trim_end_matches(".kepub")
+                .to_string()
+        })
+        .unwrap_or_default();
+    let book = if is_epub {
+        read_epub(&mut archive, &title, volume_label)?
+    } else {
+        read_cbz(&mut archive, password, &title)?
+    };
+    if book.chapters.is_empty() {
+        return Err(invalid("the ebook holds no pages".to_string()));
+    }
+
+    std::fs::create_dir_all(directory)?;
+    let result = write_book(&mut archive, password, &book, directory);
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(directory);
+    }
+    let (cover, chapters) = result?;
+    Ok(ExtractedEbook {
+        directory: directory.to_path_buf(),
+        metadata: book.metadata,
+        volume: book.volume,
+        cover,
+        chapter_titles: book.chapters.into_iter().map(|(title, _)| title).collect(),
+        chapters,
+    })
+}
+
+/// Writes the cover, chapters and metadata document of `book` into `directory`.
+fn write_book(
+    archive: &mut ZipArchive<File>,
+    password: Option<&str>,
+    book: &Book,
+    directory: &Path,
+) -> Result<(Option<PathBuf>, Vec<Vec<PathBuf>>)> {
+    if let Some((name, content)) = &book.metadata_file {
+        std::fs::write(directory.join(name), content)?;
+    }
+
+    let mut write_entry = |entry: &str, path: PathBuf| -> Result<PathBuf> {
+        std::fs::write(&path, read_entry(archive, entry, password)?)?;
+        Ok(path)
+    };
+    let cover = match &book.cover {
+        Some(entry) => Some(write_entry(
+            entry,
+            directory.join(format!("cover.{}", extension(entry))),
+        )?),
+        None => None,
+    };
+
+    let mut chapters = Vec::with_capacity(book.chapters.len());
+    for (index, (title, pages)) in book.chapters.iter().enumerate() {
+        let chapter_dir = directory.join(format!("{:03} {}", index + 1, sanitize_filename(title)));
+        std::fs::create_dir_all(&chapter_dir)?;
+        let mut chapter = Vec::with_capacity(pages.len());
+        for (page_index, entry) in pages.iter().enumerate() {
+            let path = chapter_dir.join(format!("{:03}.{}", page_index + 1, extension(entry)));
+            chapter.push(write_entry(entry, path)?);
+        }
+        chapters.push(chapter);
+    }
+    Ok((cover, chapters))
+}
+
+/// Reads the pages, chapters and ComicInfo.xml of a CBZ file.
+fn read_cbz(archive: &mut ZipArchive<File>, password: Option<&str>, title: &str) -> Result<Book> {
+    let mut pages: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && is_page_entry(Path::new(name)))
+        .map(str::to_string)
+        .collect();
+    pages.sort_by(|a, b| compare_names_natural(a, b));
+
+    let comic_info_xml = match archive.index_for_name("ComicInfo.xml") {
+        Some(_) => Some(
+            String::from_utf8_lossy(&read_entry(archive, "ComicInfo.xml", password)?).into_owned(),
+        ),
+        None => None,
+    };
+    let comic_info = comic_info_xml
+        .as_deref()
+        .and_then(|xml| parse_comicinfo(xml).ok());
+
+    // `Image` is the 0-based index of the page, cover included
+    let mut cover = pages
+        .first()
+        .filter(|page| file_stem(page) == "000_cover")
+        .map(|_| 0);
+    let mut starts = Vec::new();
+    for captures in COMIC_INFO_PAGE.captures_iter(comic_info_xml.as_deref().unwrap_or_default()) {
+        let attributes = &captures[1];
+        let Some(image) = attribute(attributes, "Image").and_then(|image| image.parse().ok())
+        else {
+            continue;
+        };
+        if attribute(attributes, "Type").as_deref() == Some("FrontCover") {
+            cover = Some(image);
+        }
+        if let Some(bookmark) = attribute(attributes, "Bookmark") {
+            starts.push((image, bookmark));
+        }
+    }
+
+    // Without bookmarks, the folders of the archive are the chapters
+    let folder = |page: &str| {
+        page.rfind('/')
+            .map_or("", |index| &page[..index])
+            .to_string()
+    };
+    if starts.is_empty() && pages.iter().any(|page| folder(page) != folder(&pages[0])) {
+        for (index, page) in pages.iter().enumerate() {
+            let page_folder = folder(page);
+            let is_new = index == 0 || page_folder != folder(&pages[index - 1]);
+            if is_new && Some(index) != cover {
+                let name = page_folder.rsplit('/').next().unwrap_or_default();
+                starts.push((index, name.to_string()));
+            }
+        }
+    }
+
+    let (metadata, volume, listed_chapters) = match comic_info {
+        Some(info) => (info.metadata, info.volume, info.chapters),
+        None => (
+            EbookMetadata::default_with_title(title.to_string()),
+            None,
+            Vec::new(),
+        ),
+    };
+    let default_title = match listed_chapters.as_slice() {
+        [chapter] => chapter.clone(),
+        _ => metadata.title.clone(),
+    };
+    let (cover, chapters) = split_chapters(pages, cover, starts, &default_title);
+    Ok(Book {
+        metadata,
+        volume,
+        metadata_file: comic_info_xml.map(|xml| ("ComicInfo.xml", xml)),
+        cover,
+        chapters,
+    })
+}
+
+/// Reads the pages, table of contents and package document of an EPUB file.
+fn read_epub(
+    archive: &mut ZipArchive<File>,
+    title: &str,
+    volume_label: &VolumeLabel,
+) -> Result<Book> {
+    let text = |archive: &mut ZipArchive<File>, name: &str| {
+        read_entry(archive, name, None).map(|data| String::from_utf8_lossy(&data).into_owned())
+    };
+    let opf_path = text(archive, "META-INF/container.xml")
+        .ok()
+        .and_then(|container| attribute(&container, "full-path"))
+        .unwrap_or_else(|| OPF_PATH.to_string());
+    let opf = text(archive, &opf_path)?;
+    let opf_dir = directory_of(&opf_path);
+
+    // Manifest items by id: archive path and properties
+    let items: Vec<(String, String, String)> = OPF_ITEM
+        .captures_iter(&opf)
+        .filter_map(|captures| {
+            let attributes = &captures[1];
+            Some((
+                attribute(attributes, "id")?,
+                resolve(opf_dir, &attribute(attributes, "href")?),
+                attribute(attributes, "properties").unwrap_or_default(),
+            ))
+        })
+        .collect();
+    let item_path = |id: &str| {
+        items
+            .iter()
+            .find(|(item_id, _, _)| item_id == id)
+            .map(|(_, path, _)| path.clone())
+    };
+    let cover = items
+        .iter()
+        .find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "cover-image"))
+        .map(|(_, path, _)| path.clone())
+        .or_else(|| {
+            let id = Regex::new(r#"<meta name="cover" content="([^"]*)""#)
+                .unwrap()
+                .captures(&opf)?[1]
+                .to_string();
+            item_path(&id)
+        })
+        .filter(|path| archive.index_for_name(path).is_some());
+
+    // The first image of each spine document is a page; documents without one (chapter
+    // intros, the navigation document) are skipped
+    let spine: Vec<String> = OPF_ITEMREF
+        .captures_iter(&opf)
+        .filter_map(|captures| item_path(&captures[1]))
+        .collect();
+    let mut pages = Vec::new();
+    let mut first_page_of_document = Vec::with_capacity(spine.len());
+    for document in &spine {
+        first_page_of_document.push(pages.len());
+        let Ok(xhtml) = text(archive, document) else {
+            continue;
+        };
+        let image = PAGE_IMAGE
+            .captures(&xhtml)
+            .map(|captures| archive_path(archive, directory_of(document), &captures[1]))
+            .filter(|image| ImageFormat::from_path(Path::new(image)).is_ok());
+        if let Some(image) = image {
+            pages.push(image);
+        }
+    }
+
+    // Chapters start at the pages the table of contents links to
+    let nav = items
+        .iter()
+        .find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "nav"))
+        .map(|(_, path, _)| path.clone());
+    let ncx = Regex::new(r#"<spine\s[^>]*?toc="([^"]*)""#)
+        .unwrap()
+        .captures(&opf)
+        .and_then(|captures| item_path(&captures[1]));
+    let mut links: Vec<(String, String)> = Vec::new();
+    if let Some(nav) = nav.as_deref()
+        && let Ok(document) = text(archive, nav)
+    {
+        // Only the table of contents, not the page list or landmarks
+        let toc = document
+            .find("epub:type=\"toc\"")
+            .map(|start| &document[start..])
+            .map(|rest| &rest[..rest.find("</nav>").unwrap_or(rest.len())])
+            .unwrap_or(&document);
+        links = NAV_LINK
+            .captures_iter(toc)
+            .map(|captures| {
+                (
+                    resolve(directory_of(nav), &captures[1]),
+                    label(&captures[2]),
+                )
+            })
+            .collect();
+    }
+    if links.is_empty()
+        && let Some(ncx) = ncx.as_deref()
+        && let Ok(document) = text(archive, ncx)
+    {
+        links = NCX_POINT
+            .captures_iter(&document)
+            .map(|captures| {
+                (
+                    resolve(directory_of(ncx), &captures[2]),
+                    label(&captures[1]),
+                )
+            })
+            .collect();
+    }
+    let mut starts: Vec<(usize, String)> = Vec::new();
+    for (href, title) in links {
+        let document = href.split('#').next().unwrap_or_default();
+        let Some(position) = spine.iter().position(|path| path == document) else {
+            continue;
+        };
+        // Hozon's per-page entries (`Chapter 1 - Page 2`) name the chapter once
+        let title = PAGE_SUFFIX.replace(&title, "").into_owned();
+        if starts.last().is_some_and(|(_, last)| *last == title) {
+            continue;
+        }
+        starts.push((first_page_of_document[position], title));
+    }
+
+    let volume = series_position(&opf);
+    let mut metadata =
+        parse_opf(&opf).unwrap_or_else(|_| EbookMetadata::default_with_title(title.to_string()));
+    if let Some(volume) = volume {
+        let label = format!(" {}", volume_label.format_short(volume, &metadata.language));
+        if let Some(title) = metadata.title.strip_suffix(&label) {
+            metadata.title = title.to_string();
+        }
+    }
+    let cover_index = pages
+        .first()
+        .filter(|page| Some(*page) == cover.as_ref())
+        .map(|_| 0);
+    let (first_page_cover, chapters) = split_chapters(pages, cover_index, starts, &metadata.title);
+    Ok(Book {
+        volume,
+        metadata,
+        metadata_file: Some((OPF_FILE_NAME, opf)),
+        cover: first_page_cover.or(cover),
+        chapters,
+    })
+}
+
+/// Splits `pages` into chapters at the page indices of `starts`, leaving out the page at
+/// `cover`. Pages before the first start form a chapter titled `default_title`.
+///
+/// # Returns
+///
+/// The cover page, if any, and the title and pages of each chapter with pages.
+fn split_chapters(
+    pages: Vec<String>,
+    cover: Option<usize>,
+    mut starts: Vec<(usize, String)>,
+    default_title: &str,
+) -> (Option<String>, Vec<(String, Vec<String>)>) {
+    starts.sort_by_key(|(index, _)| *index);
+    let mut starts = starts.into_iter().peekable();
+    let mut cover_page = None;
+    let mut chapters: Vec<(String, Vec<String>)> = Vec::new();
+    for (index, page) in pages.into_iter().enumerate() {
+        if Some(index) == cover {
+            cover_page = Some(page);
+            continue;
+        }
+        while let Some((_, title)) = starts.next_if(|(start, _)| *start <= index) {
+            // A chapter whose pages were all taken by the next one is dropped
+            if chapters.last().is_some_and(|(_, pages)| pages.is_empty()) {
+                chapters.pop();
+            }
+            chapters.push((title, Vec::new()));
+        }
+        match chapters.last_mut() {
+            Some((_, chapter_pages)) => chapter_pages.push(page),
+            None => chapters.push((default_title.to_string(), vec![page])),
+        }
+    }
+    chapters.retain(|(_, pages)| !pages.is_empty());
+    (cover_page, chapters)
+}
+
+/// Reads the entry `name` of `archive`, decrypting it with `password` if it's encrypted.
+fn read_entry(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+    password: Option<&str>,
+) -> Result<Vec<u8>> {
+    let index = archive
+        .index_for_name(name)
+        .ok_or_else(|| Error::NotFound(format!("'{}' in the archive", name)))?;
+    let encrypted = archive.by_index_raw(index)?.encrypted();
+    let mut entry = match password {
+        Some(password) if encrypted => archive.by_index_decrypt(index, password.as_bytes())?,
+        _ => archive.by_index(index)?,
+    };
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Resolves the link `href` of a document in `base` to an archive path, percent-decoding
+/// it if only the decoded path exists.
+fn archive_path(archive: &ZipArchive<File>, base: &str, href: &str) -> String {
+    let path = resolve(base, href.split('#').next().unwrap_or_default());
+    if archive.index_for_name(&path).is_some() {
+        return path;
+    }
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).unwrap_or(path)
+}
+
+/// The value of the attribute `name` in the attribute list (or document) `attributes`.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!(r#"(?:^|\s){}="([^"]*)""#, regex::escape(name));
+    Regex::new(&pattern)
+        .unwrap()
+        .captures(attributes)
+        .map(|captures| unescape_xml(&captures[1]))
+}
+
+/// A table of contents label without markup and surrounding whitespace.
+fn label(text: &str) -> String {
+    unescape_xml(TAG.replace_all(text, "").trim())
+}
+
+/// The lowercase extension of an archive entry name.
+fn extension(entry: &str) -> String {
+    Path::new(entry)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// The file name of an archive entry without its extension.
+fn file_stem(entry: &str) -> String {
+    Path::new(entry)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
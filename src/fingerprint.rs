@@ -0,0 +1,380 @@
+//! Source fingerprints embedded into generated outputs.
+//!
+//! A fingerprint combines a content hash of the source pages that make up one output
+//! file with the Hozon version and a digest of the output-affecting configuration.
+//! It is embedded into the ComicInfo.xml `Notes` (CBZ) and as an OPF `<meta>` entry
+//! (EPUB), so later runs can tell whether an existing output file is still up-to-date
+//! without regenerating it. It also records a digest of the series the output belongs to,
+//! so a run can tell outputs of another series apart from earlier outputs of its own.
+
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::task::spawn_blocking;
+
+use crate::error::{Error, Result};
+use crate::generator::mobi;
+use crate::hozon::HozonConfig;
+use crate::path_utils::{get_file_name_lossy, path_to_string_lossy};
+use crate::types::{EbookMetadata, FileFormat};
+
+/// Name of the OPF `<meta>` entry holding the fingerprint in EPUB files.
+pub const FINGERPRINT_KEY: &str = "hozon:fingerprint";
+
+/// Label of the ComicInfo.xml `Notes` line holding the fingerprint in CBZ files.
+pub const FINGERPRINT_NOTES_LABEL: &str = "Fingerprint";
+
+/// Format version of the serialized fingerprint string.
+const FINGERPRINT_FORMAT_VERSION: &str = "v1";
+
+/// Buffer size used when streaming page contents into the hasher.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Fingerprint of the source content and configuration used to generate one output file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceFingerprint {
+    /// Version of Hozon that generated the output.
+    pub hozon_version: String,
+    /// Digest of the configuration options that influence the generated file.
+    pub config_digest: String,
+    /// SHA-256 over the names and contents of all source pages (and cover) of the file.
+    pub content_hash: String,
+    /// [`series_digest`] of the metadata; `None` in fingerprints of older versions.
+    pub series_digest: Option<String>,
+}
+
+impl SourceFingerprint {
+    /// Computes the fingerprint for one output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration used for generation
+    /// * `chapters` - The chapters (with their page paths) that make up the output file
+    /// * `cover` - Optional custom cover image added to the output file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<SourceFingerprint>` - The computed fingerprint, or an I/O error if a page can't be read
+    pub async fn compute(
+        config: &HozonConfig,
+        chapters: &[Vec<PathBuf>],
+        cover: Option<&Path>,
+    ) -> Result<Self> {
+        let config_digest = config_digest(config);
+        let series_digest = series_digest(&config.metadata);
+        let chapters = chapters.to_vec();
+        let cover = cover.map(Path::to_path_buf);
+
+        let content_hash = spawn_blocking(move || hash_source_content(&chapters, cover.as_deref()))
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+
+        Ok(Self {
+            hozon_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_digest,
+            content_hash,
+            series_digest: Some(series_digest),
+        })
+    }
+
+    /// Parses a fingerprint previously produced by the `Display` implementation.
+    ///
+    /// Returns `None` if the string is not a recognized fingerprint.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split(';');
+        if parts.next()? != FINGERPRINT_FORMAT_VERSION {
+            return None;
+        }
+
+        let mut hozon_version = None;
+        let mut config_digest = None;
+        let mut content_hash = None;
+        let mut series_digest = None;
+        for part in parts {
+            let (key, val) = part.split_once('=')?;
+            match key {
+                "hozon" => hozon_version = Some(val.to_string()),
+                "config" => config_digest = Some(val.to_string()),
+                "content" => content_hash = Some(val.to_string()),
+                "series" => series_digest = Some(val.to_string()),
+                _ => {} // Unknown keys are ignored for forward compatibility
+            }
+        }
+
+        Some(Self {
+            hozon_version: hozon_version?,
+            config_digest: config_digest?,
+            content_hash: content_hash?,
+            series_digest,
+        })
+    }
+
+    /// Returns `true` if an output with this fingerprint was generated from the same
+    /// source content and configuration as `other`. The Hozon version and series digest
+    /// are informational and are not compared.
+    pub fn is_up_to_date_with(&self, other: &SourceFingerprint) -> bool {
+        self.config_digest == other.config_digest && self.content_hash == other.content_hash
+    }
+}
+
+impl fmt::Display for SourceFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{};hozon={};config={};content={}",
+            FINGERPRINT_FORMAT_VERSION, self.hozon_version, self.config_digest, self.content_hash
+        )?;
+        if let Some(series_digest) = &self.series_digest {
+            write!(f, ";series={}", series_digest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes a short digest of all configuration options that influence the bytes
+/// of a generated file (metadata, format and naming settings).
+pub fn config_digest(config: &HozonConfig) -> String {
+    let metadata = &config.metadata;
+
+    // Custom fields are sorted so the digest doesn't depend on HashMap iteration order
+    let mut custom_fields: Vec<(&String, &String)> = metadata.custom_fields.iter().collect();
+    custom_fields.sort();
+
+    let canonical = format!(
+        "title={:?}\nseries={:?}\nauthors={:?}\npublisher={:?}\ndescription={:?}\ntags={:?}\n\
+         language={:?}\nrights={:?}\nidentifier={:?}\nidentifiers={:?}\nrelease_date={:?}\ngenre={:?}\nweb={:?}\n\
+         custom_fields={:?}\nformat={:?}\ndirection={:?}\nseparator={:?}\nvolume_label={:?}\nencrypted={}\n\
+         processing={:?}\nnotes={:?}\nchapter_map={}\nvolume_links={}\nalt_text={:?}\nanimated={:?}\nepub_max_file_size={:?}\ntoc={:?}\nepub_version={:?}\nunicode_normalization={:?}\nphoto_album={:?}\nduplicate_pages={:?}\nmissing_pages={:?}\nchapter_intro={:?}\nfirst_page_side={:?}\nepub_compression={:?}\nepub_image_format={:?}\nepub_layout={:?}\nepub_image_fit={:?}\nepub_page_style={:?}",
+        metadata.title,
+        metadata.series,
+        metadata.authors,
+        metadata.publisher,
+        metadata.description,
+        metadata.tags,
+        metadata.language,
+        metadata.rights,
+        metadata.identifier,
+        metadata.identifiers,
+        metadata.release_date.map(|d| d.to_rfc3339()),
+        metadata.genre,
+        metadata.web,
+        custom_fields,
+        config.output_format,
+        config.reading_direction,
+        config.volume_separator,
+        config.volume_label,
+        config.output_password.is_some(),
+        config.image_processing.as_ref().map(|p| (
+            p.max_width,
+            p.max_height,
+            p.output_format,
+            p.jpeg_quality,
+            p.color_profile,
+            p.flatten_alpha,
+            p.auto_levels,
+            p.deskew
+        )),
+        config.comic_info_notes,
+        config.comic_info_chapter_map,
+        config.volume_links,
+        config.alt_text,
+        config.animated_images,
+        config.epub_max_file_size,
+        config.toc,
+        config.epub_version,
+        config.unicode_normalization,
+        config.photo_album,
+        config.duplicate_pages,
+        config.missing_pages,
+        config.chapter_intro,
+        config.first_page_side,
+        config.epub_compression,
+        config.epub_image_format,
+        config.epub_layout,
+        config.epub_image_fit,
+        config.epub_page_style,
+    );
+
+    let digest = format!("{:x}", Sha256::digest(canonical.as_bytes()));
+    digest[..16].to_string()
+}
+
+/// Computes a short digest identifying the series of `metadata`: its series name (the
+/// title if it has none) and authors. Outputs with different digests belong to different
+/// series, even if their titles sanitize to the same directory name.
+pub fn series_digest(metadata: &EbookMetadata) -> String {
+    series_digest_of(
+        metadata.series.as_deref().unwrap_or(&metadata.title),
+        &metadata.authors,
+    )
+}
+
+/// [`series_digest`] of a series name and authors read back from an output directory.
+pub(crate) fn series_digest_of(series: &str, authors: &[String]) -> String {
+    let canonical = format!("series={:?}\nauthors={:?}", series, authors);
+    let digest = format!("{:x}", Sha256::digest(canonical.as_bytes()));
+    digest[..16].to_string()
+}
+
+/// Reads the fingerprint embedded into an existing output file.
+///
+/// This is a blocking operation; call it from a blocking thread in async contexts.
+///
+/// # Arguments
+///
+/// * `path` - Path to the generated CBZ, CB7, EPUB or MOBI file; directory outputs
+///   carry no fingerprint
+/// * `format` - The format of the file
+/// * `password` - Password for encrypted CBZ archives, if any
+///
+/// # Returns
+///
+/// * `Result<Option<SourceFingerprint>>` - The fingerprint, `None` if the file carries none,
+///   or an error if the file is not a readable archive
+pub fn read_embedded_fingerprint(
+    path: &Path,
+    format: FileFormat,
+    password: Option<&str>,
+) -> Result<Option<SourceFingerprint>> {
+    if format == FileFormat::Directory {
+        return Ok(None);
+    }
+    if format == FileFormat::Mobi {
+        // The EXTH source records also hold the identifier from the metadata
+        let values = mobi::read_exth(&std::fs::read(path)?, mobi::EXTH_SOURCE)?;
+        return Ok(values
+            .iter()
+            .find_map(|value| SourceFingerprint::parse(&String::from_utf8_lossy(value))));
+    }
+
+    let entry_name = match format {
+        FileFormat::Cbz | FileFormat::Cb7 => "ComicInfo.xml",
+        FileFormat::Epub | FileFormat::Kepub => "OEBPS/content.opf",
+        FileFormat::Mobi | FileFormat::Directory => unreachable!("handled above"),
+    };
+    let content = if format == FileFormat::Cb7 {
+        read_seven_zip_entry(path, entry_name)?
+    } else {
+        read_zip_entry(path, entry_name, password)?
+    };
+    let Some(content) = content else {
+        return Ok(None);
+    };
+
+    let value = match format {
+        FileFormat::Cbz | FileFormat::Cb7 => {
+            // Depending on the notes format the fingerprint sits on a labelled line, inside
+            // a JSON value or anywhere in a custom template, so look for the value itself.
+            let marker = format!("{};hozon=", FINGERPRINT_FORMAT_VERSION);
+            content.find(&marker).map(|start| {
+                content[start..]
+                    .split(|c: char| c.is_whitespace() || matches!(c, '"' | '&' | '<'))
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+        }
+        FileFormat::Mobi | FileFormat::Directory => None,
+        FileFormat::Epub | FileFormat::Kepub => {
+            let marker = format!("name=\"{}\" content=\"", FINGERPRINT_KEY);
+            content.find(&marker).and_then(|start| {
+                let rest = &content[start + marker.len()..];
+                rest.find('"').map(|end| rest[..end].to_string())
+            })
+        }
+    };
+
+    Ok(value.and_then(|v| SourceFingerprint::parse(&v)))
+}
+
+/// Reads the zip entry `name` of the archive at `path`, `None` if it doesn't exist.
+fn read_zip_entry(path: &Path, name: &str, password: Option<&str>) -> Result<Option<String>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let entry = match password {
+        Some(password) => archive.by_name_decrypt(name, password.as_bytes()),
+        None => archive.by_name(name),
+    };
+    let mut entry = match entry {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Reads the 7z entry `name` of the archive at `path`, `None` if it doesn't exist.
+fn read_seven_zip_entry(path: &Path, name: &str) -> Result<Option<String>> {
+    #[cfg(feature = "cb7")]
+    {
+        let mut content = None;
+        crate::generator::archive::for_each_seven_zip_entry(path, |entry, data| {
+            if entry != name {
+                return Ok(true);
+            }
+            let mut text = String::new();
+            data.read_to_string(&mut text)?;
+            content = Some(text);
+            Ok(false)
+        })?;
+        Ok(content)
+    }
+    #[cfg(not(feature = "cb7"))]
+    {
+        let _ = (path, name);
+        Err(Error::Unsupported(
+            "Reading CB7 files requires the `cb7` feature".to_string(),
+        ))
+    }
+}
+
+/// Hashes page file names and contents in order. Blocking; run on a blocking thread.
+fn hash_source_content(chapters: &[Vec<PathBuf>], cover: Option<&Path>) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+
+    if let Some(cover) = cover {
+        hasher.update(b"cover\0");
+        hash_file(&mut hasher, cover, &mut buffer)?;
+    }
+
+    for (chapter_index, chapter_pages) in chapters.iter().enumerate() {
+        hasher.update(format!("chapter:{}\0", chapter_index).as_bytes());
+        for page in chapter_pages {
+            hash_file(&mut hasher, page, &mut buffer)?;
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Feeds a file's name, length and contents into the hasher.
+fn hash_file(hasher: &mut Sha256, path: &Path, buffer: &mut [u8]) -> Result<()> {
+    let mut file = std::fs::File::open(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to open '{}' for fingerprinting: {}",
+                path_to_string_lossy(path),
+                e
+            ),
+        ))
+    })?;
+
+    hasher.update(get_file_name_lossy(path).as_bytes());
+    hasher.update(b"\0");
+    hasher.update(file.metadata()?.len().to_le_bytes());
+
+    loop {
+        let read = file.read(buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(())
+}
@@ -0,0 +1,338 @@
+//! Archive backends for container formats.
+//!
+//! [`Cbz`](super::cbz::Cbz) decides which entries an archive holds and in which order;
+//! writing them is delegated to an [`ArchiveWriter`]. [`ZipArchiveWriter`] is the default
+//! backend. Other containers (tar, 7z, custom encrypted stores) can be plugged in through
+//! [`Cbz::with_writer`](super::cbz::Cbz::with_writer) without touching page or metadata
+//! handling.
+//!
+//! With the `async-zip` feature, `AsyncZipArchiveWriter` writes zip archives on the
+//! async runtime instead (see [`ArchiveBackend::Async`](crate::types::ArchiveBackend::Async)).
+//! With the `cb7` feature, `SevenZipArchiveWriter` writes the 7z archives of
+//! [`FileFormat::Cb7`](crate::types::FileFormat::Cb7).
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+use crate::error::{Error, Result};
+use crate::path_utils::retry_while_locked;
+
+/// Unix permission bits of archive entries unless set otherwise.
+pub const DEFAULT_ENTRY_PERMISSIONS: u32 = 0o755;
+
+/// A sink for the entries of an archive, written one after another.
+///
+/// Data written through [`Write`] belongs to the entry started last. All methods block;
+/// generators call them from blocking threads where large amounts of data are involved.
+pub trait ArchiveWriter: Write + Send {
+    /// Starts a new entry named `name` (a `/`-separated, already sanitized path).
+    fn start_entry(&mut self, name: &str) -> Result<()>;
+
+    /// Starts a new entry like [`ArchiveWriter::start_entry`], stored with the
+    /// modification time `modified` if given.
+    ///
+    /// Backends without per-entry times keep the default, which ignores `modified`.
+    fn start_entry_at(&mut self, name: &str, modified: Option<NaiveDateTime>) -> Result<()> {
+        let _ = modified;
+        self.start_entry(name)
+    }
+
+    /// Stores the Unix permission bits `mode` (e.g. `0o644`) with every entry started
+    /// afterwards.
+    ///
+    /// Backends without permission bits keep the default, which ignores `mode`.
+    fn set_entry_permissions(&mut self, mode: u32) {
+        let _ = mode;
+    }
+
+    /// Encrypts every entry started afterwards with `password`.
+    ///
+    /// Backends without encryption support keep the default, which returns
+    /// [`Error::Unsupported`].
+    fn set_password(&mut self, _password: &str) -> Result<()> {
+        Err(Error::Unsupported(
+            "This archive backend does not support encryption".to_string(),
+        ))
+    }
+
+    /// Writes the archive trailer and flushes the underlying storage.
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Writes a zip archive, optionally AES-256 encrypted.
+pub struct ZipArchiveWriter<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    options: SimpleFileOptions,
+    password: Option<String>, // AES-256 password applied to every entry, if set
+}
+
+impl ZipArchiveWriter<File> {
+    /// Creates (or truncates) the archive file at `path`.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = retry_while_locked(path, "create archive file", || File::create(path))?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write + Seek> ZipArchiveWriter<W> {
+    /// Writes a zip archive into `inner`, deflating every entry.
+    pub fn new(inner: W) -> Self {
+        Self {
+            zip: ZipWriter::new(inner),
+            options: SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .unix_permissions(DEFAULT_ENTRY_PERMISSIONS),
+            password: None,
+        }
+    }
+}
+
+impl<W: Write + Seek> Write for ZipArchiveWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.zip.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.zip.flush()
+    }
+}
+
+/// Converts `time` to a zip timestamp, or `None` if zip can't represent it (before 1980).
+pub(crate) fn zip_time(time: NaiveDateTime) -> Option<zip::DateTime> {
+    zip::DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
+
+impl<W: Write + Seek + Send> ArchiveWriter for ZipArchiveWriter<W> {
+    fn start_entry(&mut self, name: &str) -> Result<()> {
+        self.start_entry_at(name, None)
+    }
+
+    fn start_entry_at(&mut self, name: &str, modified: Option<NaiveDateTime>) -> Result<()> {
+        let mut options = self.options;
+        if let Some(time) = modified.and_then(zip_time) {
+            options = options.last_modified_time(time);
+        }
+        match &self.password {
+            Some(password) => self
+                .zip
+                .start_file(name, options.with_aes_encryption(AesMode::Aes256, password))?,
+            None => self.zip.start_file(name, options)?,
+        }
+        Ok(())
+    }
+
+    fn set_entry_permissions(&mut self, mode: u32) {
+        self.options = self.options.unix_permissions(mode);
+    }
+
+    fn set_password(&mut self, password: &str) -> Result<()> {
+        self.password = Some(password.to_string());
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.zip.finish()?;
+        Ok(())
+    }
+}
+
+/// Writes a zip archive through Tokio's file I/O, deflating every entry as it is written.
+///
+/// Unlike [`ArchiveWriter`] implementations, no blocking thread or memory map is held
+/// while writing. Encryption is not supported.
+#[cfg(feature = "async-zip")]
+pub struct AsyncZipArchiveWriter {
+    zip: async_zip::tokio::write::ZipFileWriter<tokio::fs::File>,
+    permissions: u32, // Unix permission bits of every entry
+}
+
+#[cfg(feature = "async-zip")]
+fn async_zip_error(error: async_zip::error::ZipError) -> Error {
+    Error::Other(format!("Failed to write zip archive: {}", error))
+}
+
+#[cfg(feature = "async-zip")]
+impl AsyncZipArchiveWriter {
+    /// Creates (or truncates) the archive file at `path`.
+    pub async fn create(path: &Path) -> Result<Self> {
+        let file = retry_while_locked(path, "create archive file", || std::fs::File::create(path))?;
+        let file = tokio::fs::File::from_std(file);
+        Ok(Self {
+            zip: async_zip::base::write::ZipFileWriter::with_tokio(file),
+            permissions: DEFAULT_ENTRY_PERMISSIONS,
+        })
+    }
+
+    /// Stores the Unix permission bits `mode` with every entry written afterwards.
+    pub fn set_entry_permissions(&mut self, mode: u32) {
+        self.permissions = mode;
+    }
+
+    /// Writes a complete entry named `name`.
+    pub async fn write_entry(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.write_entry_at(name, data, None).await
+    }
+
+    /// Writes a complete entry named `name`, stored with the modification time `modified`
+    /// if given.
+    pub async fn write_entry_at(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        modified: Option<NaiveDateTime>,
+    ) -> Result<()> {
+        let mut entry = async_zip::ZipEntryBuilder::new(
+            name.to_string().into(),
+            async_zip::Compression::Deflate,
+        )
+        .unix_permissions(self.permissions as u16);
+        if let Some(time) = modified.filter(|time| (1980..=2107).contains(&time.year())) {
+            entry = entry.last_modification_date(
+                async_zip::ZipDateTimeBuilder::new()
+                    .year(time.year())
+                    .month(time.month())
+                    .day(time.day())
+                    .hour(time.hour())
+                    .minute(time.minute())
+                    .second(time.second())
+                    .build(),
+            );
+        }
+        self.zip
+            .write_entry_whole(entry, data)
+            .await
+            .map_err(async_zip_error)
+    }
+
+    /// Writes the central directory and flushes the file.
+    pub async fn finish(self) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut file = self
+            .zip
+            .close()
+            .await
+            .map_err(async_zip_error)?
+            .into_inner();
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Writes a 7z archive, compressing every entry with LZMA2. Times are stored as UTC.
+///
+/// 7z entries are written from a reader, so each entry is buffered in memory until the
+/// next one starts or the archive is finished.
+#[cfg(feature = "cb7")]
+pub struct SevenZipArchiveWriter {
+    archive: Option<sevenz_rust::SevenZWriter<File>>, // Taken by `finish`
+    entry: Option<(sevenz_rust::SevenZArchiveEntry, Vec<u8>)>, // The entry being written
+}
+
+#[cfg(feature = "cb7")]
+fn seven_zip_error(action: &str, error: sevenz_rust::Error) -> Error {
+    Error::Other(format!("Failed to {} 7z archive: {}", action, error))
+}
+
+#[cfg(feature = "cb7")]
+impl SevenZipArchiveWriter {
+    /// Creates (or truncates) the archive file at `path`.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = retry_while_locked(path, "create archive file", || File::create(path))?;
+        let archive =
+            sevenz_rust::SevenZWriter::new(file).map_err(|e| seven_zip_error("write", e))?;
+        Ok(Self {
+            archive: Some(archive),
+            entry: None,
+        })
+    }
+
+    /// Compresses the buffered entry into the archive.
+    fn push_entry(&mut self) -> Result<()> {
+        let (Some(archive), Some((entry, data))) = (self.archive.as_mut(), self.entry.take())
+        else {
+            return Ok(());
+        };
+        archive
+            .push_archive_entry(entry, Some(data.as_slice()))
+            .map_err(|e| seven_zip_error("write", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cb7")]
+impl Write for SevenZipArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.entry {
+            Some((_, data)) => data.write(buf),
+            None => Err(std::io::Error::other("no 7z entry was started")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cb7")]
+impl ArchiveWriter for SevenZipArchiveWriter {
+    fn start_entry(&mut self, name: &str) -> Result<()> {
+        self.start_entry_at(name, None)
+    }
+
+    fn start_entry_at(&mut self, name: &str, modified: Option<NaiveDateTime>) -> Result<()> {
+        self.push_entry()?;
+        let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+        entry.name = name.to_string();
+        entry.has_stream = true;
+        let time = modified.and_then(|time| {
+            sevenz_rust::nt_time::FileTime::from_unix_time(time.and_utc().timestamp()).ok()
+        });
+        if let Some(time) = time {
+            entry.last_modified_date = time;
+            entry.has_last_modified_date = true;
+        }
+        self.entry = Some((entry, Vec::new()));
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.push_entry()?;
+        if let Some(archive) = self.archive.take() {
+            archive.finish()?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Calls `visit` with the name and contents of every file in the 7z archive at `path`,
+/// until it returns `false`. Reading an entry to the end checks its CRC.
+#[cfg(feature = "cb7")]
+pub(crate) fn for_each_seven_zip_entry(
+    path: &Path,
+    mut visit: impl FnMut(&str, &mut dyn std::io::Read) -> std::io::Result<bool>,
+) -> Result<()> {
+    let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+        .map_err(|e| seven_zip_error("read", e))?;
+    reader
+        .for_each_entries(|entry, data| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            Ok(visit(entry.name(), data)?)
+        })
+        .map_err(|e| seven_zip_error("read", e))
+}
@@ -1,31 +1,491 @@
 use crate::error::{Error, Result};
-use crate::generator::Generator;
-use crate::path_utils::{normalize_path, path_to_string_lossy};
-use crate::types::{EbookMetadata, get_file_info};
+use crate::fingerprint::{FINGERPRINT_NOTES_LABEL, SourceFingerprint};
+#[cfg(feature = "async-zip")]
+use crate::generator::archive::AsyncZipArchiveWriter;
+#[cfg(feature = "cb7")]
+use crate::generator::archive::SevenZipArchiveWriter;
+use crate::generator::archive::{ArchiveWriter, ZipArchiveWriter};
+use crate::generator::{Generator, PageReadOptions, PrefetchedPage, escape_xml, prefetch_pages};
+use crate::path_utils::{normalize_path, path_to_string_lossy, sanitize_entry_name};
+use crate::processing::{AnimatedImagePolicy, ImageProcessing, PageProcessor};
+use crate::runtime::{ConversionControl, RuntimeLimits, ThrottleProfile};
+use crate::types::{
+    EbookMetadata, EntryTimestamps, IdentifierScheme, ImageFormat, NotesFormat, VolumeLink,
+};
 use async_trait::async_trait;
 use chrono::prelude::*;
+use futures::StreamExt;
 use memmap2::MmapOptions;
-use rayon::prelude::*;
-use std::fs::File;
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
+use tokio::sync::{Semaphore, mpsc};
 use tokio::task::spawn_blocking;
-use zip::write::SimpleFileOptions;
-use zip::{CompressionMethod, ZipWriter};
 
 /// A generator for creating CBZ (Comic Book ZIP) files.
 ///
 /// This struct implements the `Generator` trait to package images into
-/// a properly formatted CBZ archive with optional metadata (ComicInfo.xml).
+/// a properly formatted CBZ archive with optional metadata (ComicInfo.xml). Entries
+/// are written through an [`ArchiveWriter`], a zip archive unless another backend is
+/// passed to [`Cbz::with_writer`] or the async backend is selected with [`Cbz::new_async`].
 pub struct Cbz {
-    zip: Option<ZipWriter<File>>,
-    options: SimpleFileOptions,
-    page_index: usize, // 0-based index for pages added
-    has_cover: bool,   // Track if a custom cover has been added
+    writer: Option<Box<dyn ArchiveWriter>>,
+    #[cfg(feature = "async-zip")]
+    async_writer: Option<AsyncZipArchiveWriter>, // Replaces `writer` with the async backend
+    page_index: usize,                      // 0-based index for pages added
+    has_cover: bool,                        // Track if a custom cover has been added
+    fingerprint: Option<SourceFingerprint>, // Embedded into ComicInfo.xml Notes, if set
+    notes_format: NotesFormat,              // How ComicInfo.xml Notes are rendered
+    io_limit: Option<Arc<Semaphore>>,       // Shared cap on concurrent page reads, if set
+    cpu_limit: Option<Arc<Semaphore>>,      // Shared cap on concurrent page processing, if set
+    throttle: ThrottleProfile,              // Pacing of page reads, from the runtime limits
+    control: ConversionControl,             // Pauses page reads of a spawned conversion
+    processor: Option<Arc<PageProcessor>>,  // Resize/transcode applied to pages, if set
+    animated_images: AnimatedImagePolicy,
+    chapter_map: Option<ChapterMap>, // Chapter numbers and start pages, if recorded
+    previous_volume: Option<VolumeLink>, // Linked from `Notes`, if set
+    next_volume: Option<VolumeLink>,
+    entry_timestamps: EntryTimestamps,
+}
+
+/// Where the chapters of an archive start, for ComicInfo.xml bookmarks and `Notes`.
+#[derive(Debug, Clone)]
+struct ChapterMap {
+    first_number: usize,     // 1-based number of the first chapter across all outputs
+    total_chapters: usize,   // Chapters across all outputs
+    page_counts: Vec<usize>, // Pages of each chapter in the archive
+}
+
+impl ChapterMap {
+    /// `Chapters 11–20 of 40`
+    fn range(&self) -> String {
+        let last = self.first_number + self.page_counts.len().saturating_sub(1);
+        if last == self.first_number {
+            format!("Chapter {} of {}", self.first_number, self.total_chapters)
+        } else {
+            format!(
+                "Chapters {}\u{2013}{} of {}",
+                self.first_number, last, self.total_chapters
+            )
+        }
+    }
+
+    /// Number, title, 1-based start page (as in the `page_NNN` entry names) and page count
+    /// of each chapter with pages in the archive.
+    fn starts<'a>(
+        &'a self,
+        titles: &'a [String],
+    ) -> impl Iterator<Item = (usize, &'a str, usize, usize)> + 'a {
+        self.page_counts
+            .iter()
+            .scan(1, |next_page, &count| {
+                let start = *next_page;
+                *next_page += count;
+                Some((start, count))
+            })
+            .enumerate()
+            .filter(|(_, (_, count))| *count > 0)
+            .map(|(index, (start, count))| {
+                let title = titles.get(index).map_or("Untitled Chapter", |t| t.as_str());
+                (self.first_number + index, title, start, count)
+            })
+    }
+}
+
+/// Returns the normalized path of the archive with the given extension, creating the
+/// output directory if needed.
+fn output_file_path(output_dir: &Path, base_filename: &str, extension: &str) -> Result<PathBuf> {
+    // Normalize the output directory path to handle long paths
+    let normalized_output_dir = normalize_path(output_dir)?;
+
+    // Ensure output directory exists
+    if !normalized_output_dir.exists() {
+        std::fs::create_dir_all(&normalized_output_dir)?;
+    }
+
+    let output_file_path = normalized_output_dir.join(format!("{}.{}", base_filename, extension));
+
+    // Normalize the output file path as well
+    normalize_path(&output_file_path)
+}
+
+/// Reads the rest of `file` for the async backend.
+#[cfg(feature = "async-zip")]
+async fn read_to_end(mut file: fs::File) -> Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// Custom fields as `key: value` lines, sorted so the output doesn't depend on HashMap
+/// iteration order.
+fn custom_field_lines(metadata: &EbookMetadata, indent: &str) -> String {
+    let custom_fields: BTreeMap<&String, &String> = metadata.custom_fields.iter().collect();
+    custom_fields
+        .iter()
+        .map(|(key, value)| format!("{}{}: {}", indent, key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The (unescaped) `Notes` lines of [`NotesFormat::Lines`] taken from the metadata:
+/// tags, identifier, rights and custom fields, each line ending in a newline.
+pub(crate) fn metadata_note_lines(metadata: &EbookMetadata) -> String {
+    format!(
+        "    Tags: {}\n    Identifier: {}\n    Rights: {}\n    Custom Fields:\n{}\n",
+        metadata.tags.join(", "),
+        metadata.identifier.as_deref().unwrap_or(""),
+        metadata.rights.as_deref().unwrap_or(""),
+        custom_field_lines(metadata, "    "),
+    )
+}
+
+/// The keys of [`NotesFormat::Json`] notes taken from the metadata.
+pub(crate) fn metadata_notes_json(metadata: &EbookMetadata) -> serde_json::Value {
+    let custom_fields: BTreeMap<&String, &String> = metadata.custom_fields.iter().collect();
+    serde_json::json!({
+        "tags": metadata.tags,
+        "identifier": metadata.identifier,
+        "identifiers": metadata
+            .identifiers
+            .iter()
+            .map(|id| serde_json::json!({ "scheme": id.scheme.name(), "value": id.value }))
+            .collect::<Vec<_>>(),
+        "rights": metadata.rights,
+        "custom_fields": custom_fields,
+    })
+}
+
+/// The ComicInfo.xml `GTIN` element (ComicInfo v2.1) with its line break, or an empty
+/// string: an explicit GTIN wins, otherwise an ISBN (which is a GTIN-13).
+pub(crate) fn gtin_element(metadata: &EbookMetadata) -> String {
+    [IdentifierScheme::Gtin, IdentifierScheme::Isbn]
+        .iter()
+        .find_map(|scheme| metadata.identifiers.iter().find(|id| &id.scheme == scheme))
+        .map(|id| format!("  <GTIN>{}</GTIN>\n", escape_xml(&id.value)))
+        .unwrap_or_default()
+}
+
+/// `Series Vol. 2 (Series Vol. 2.cbz)`
+fn volume_link_label(link: &VolumeLink) -> String {
+    format!("{} ({})", link.title, link.file_name)
+}
+
+/// The `previous_volume`/`next_volume` value of [`NotesFormat::Json`] notes.
+fn volume_link_json(link: Option<&VolumeLink>) -> serde_json::Value {
+    link.map_or(serde_json::Value::Null, |link| {
+        serde_json::json!({
+            "volume_number": link.volume_number,
+            "part_number": link.part_number,
+            "title": link.title,
+            "file_name": link.file_name,
+        })
+    })
+}
+
+/// Renders the (unescaped) contents of the ComicInfo.xml `Notes` field.
+fn render_notes(
+    format: &NotesFormat,
+    metadata: &EbookMetadata,
+    chapter_titles: &[String],
+    chapter_map: Option<&ChapterMap>,
+    previous_volume: Option<&VolumeLink>,
+    next_volume: Option<&VolumeLink>,
+    fingerprint: Option<&SourceFingerprint>,
+) -> String {
+    let fingerprint = fingerprint.map(|fp| fp.to_string());
+    let chapter_range = chapter_map.map(ChapterMap::range).unwrap_or_default();
+    let chapter_offsets = chapter_map
+        .map(|map| {
+            map.starts(chapter_titles)
+                .map(|(number, title, page, _)| format!("{} {}: page {}", number, title, page))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
+    match format {
+        NotesFormat::Lines => {
+            let fingerprint_line = fingerprint
+                .map(|fp| format!("    {}: {}", FINGERPRINT_NOTES_LABEL, fp))
+                .unwrap_or_default();
+            let chapter_map_lines = if chapter_map.is_some() {
+                format!(
+                    "    Chapter range: {}\n    Chapter offsets: {}\n",
+                    chapter_range, chapter_offsets
+                )
+            } else {
+                String::new()
+            };
+            let volume_lines: String = [("Previous", previous_volume), ("Next", next_volume)]
+                .into_iter()
+                .filter_map(|(label, link)| {
+                    link.map(|link| format!("    {} volume: {}\n", label, volume_link_label(link)))
+                })
+                .collect();
+            format!(
+                "\n{}    Chapters included: {}\n{}{}{}\n  ",
+                metadata_note_lines(metadata),
+                chapter_titles.join(", "),
+                chapter_map_lines,
+                volume_lines,
+                fingerprint_line,
+            )
+        }
+        NotesFormat::Json => {
+            let mut notes = metadata_notes_json(metadata);
+            notes["chapters"] = serde_json::json!(chapter_titles);
+            notes["fingerprint"] = serde_json::json!(fingerprint);
+            if let Some(map) = chapter_map {
+                notes["chapter_range"] = serde_json::json!({
+                    "first": map.first_number,
+                    "last": map.first_number + map.page_counts.len().saturating_sub(1),
+                    "total": map.total_chapters,
+                });
+                notes["chapter_offsets"] = map
+                    .starts(chapter_titles)
+                    .map(|(number, title, page, page_count)| {
+                        serde_json::json!({
+                            "number": number,
+                            "title": title,
+                            "page": page,
+                            "page_count": page_count,
+                        })
+                    })
+                    .collect();
+            }
+            if previous_volume.is_some() || next_volume.is_some() {
+                notes["previous_volume"] = volume_link_json(previous_volume);
+                notes["next_volume"] = volume_link_json(next_volume);
+            }
+            notes.to_string()
+        }
+        NotesFormat::Template(template) => template
+            .replace("{tags}", &metadata.tags.join(", "))
+            .replace("{identifier}", metadata.identifier.as_deref().unwrap_or(""))
+            .replace("{rights}", metadata.rights.as_deref().unwrap_or(""))
+            .replace("{custom_fields}", &custom_field_lines(metadata, ""))
+            .replace("{chapters}", &chapter_titles.join(", "))
+            .replace("{chapter_range}", &chapter_range)
+            .replace("{chapter_offsets}", &chapter_offsets)
+            .replace(
+                "{previous_volume}",
+                &previous_volume.map(volume_link_label).unwrap_or_default(),
+            )
+            .replace(
+                "{next_volume}",
+                &next_volume.map(volume_link_label).unwrap_or_default(),
+            )
+            .replace("{fingerprint}", fingerprint.as_deref().unwrap_or("")),
+        NotesFormat::Omit => String::new(),
+    }
 }
 
 impl Cbz {
+    /// Creates a generator writing its entries through `writer` instead of a zip file.
+    pub fn with_writer(writer: Box<dyn ArchiveWriter>) -> Self {
+        Cbz {
+            writer: Some(writer),
+            ..Cbz::without_writer()
+        }
+    }
+
+    fn without_writer() -> Self {
+        Cbz {
+            writer: None,
+            #[cfg(feature = "async-zip")]
+            async_writer: None,
+            page_index: 0,
+            has_cover: false,
+            fingerprint: None,
+            notes_format: NotesFormat::default(),
+            io_limit: None,
+            cpu_limit: None,
+            throttle: ThrottleProfile::Normal,
+            control: ConversionControl::default(),
+            processor: None,
+            animated_images: AnimatedImagePolicy::default(),
+            chapter_map: None,
+            previous_volume: None,
+            next_volume: None,
+            entry_timestamps: EntryTimestamps::default(),
+        }
+    }
+
+    /// Creates a generator writing a zip archive through Tokio's file I/O
+    /// ([`ArchiveBackend::Async`](crate::types::ArchiveBackend::Async)). Requires the
+    /// `async-zip` feature; encryption is not supported.
+    pub async fn new_async(output_dir: &Path, base_filename: &str) -> Result<Self> {
+        #[cfg(feature = "async-zip")]
+        {
+            let path = output_file_path(output_dir, base_filename, "cbz")?;
+            Ok(Cbz {
+                async_writer: Some(AsyncZipArchiveWriter::create(&path).await?),
+                ..Cbz::without_writer()
+            })
+        }
+        #[cfg(not(feature = "async-zip"))]
+        {
+            let _ = (output_dir, base_filename);
+            Err(Error::Unsupported(
+                "The async archive backend requires the `async-zip` feature".to_string(),
+            ))
+        }
+    }
+
+    /// Creates a generator writing a CB7 file, a 7z archive with the same entries as a CBZ
+    /// ([`FileFormat::Cb7`](crate::types::FileFormat::Cb7)). Requires the `cb7` feature;
+    /// encryption is not supported.
+    pub fn new_cb7(output_dir: &Path, base_filename: &str) -> Result<Self> {
+        #[cfg(feature = "cb7")]
+        {
+            let path = output_file_path(output_dir, base_filename, "cb7")?;
+            Ok(Cbz::with_writer(Box::new(SevenZipArchiveWriter::create(
+                &path,
+            )?)))
+        }
+        #[cfg(not(feature = "cb7"))]
+        {
+            let _ = (output_dir, base_filename);
+            Err(Error::Unsupported(
+                "CB7 output requires the `cb7` feature".to_string(),
+            ))
+        }
+    }
+
+    fn writer(&mut self) -> Result<&mut Box<dyn ArchiveWriter>> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| Error::Unsupported("Archive writer not available".to_string()))
+    }
+
+    /// Enables encryption (AES-256 for zip archives) for every entry subsequently
+    /// written to the archive. Must be called before adding pages, covers, or metadata.
+    pub fn set_password(&mut self, password: &str) -> Result<&mut Self> {
+        if password.is_empty() {
+            return Err(Error::Other("CBZ password must not be empty".to_string()));
+        }
+        if self.page_index > 0 || self.has_cover {
+            return Err(Error::Unsupported(
+                "Password must be set before any entries are written".to_string(),
+            ));
+        }
+        #[cfg(feature = "async-zip")]
+        if self.async_writer.is_some() {
+            return Err(Error::Unsupported(
+                "The async archive backend does not support encryption".to_string(),
+            ));
+        }
+        self.writer()?.set_password(password)?;
+        Ok(self)
+    }
+
+    /// Sets the source fingerprint that will be embedded into the ComicInfo.xml `Notes`
+    /// field. Must be called before [`Generator::set_metadata`].
+    pub fn set_fingerprint(&mut self, fingerprint: SourceFingerprint) -> &mut Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
+    /// Sets how the ComicInfo.xml `Notes` field is rendered. Must be called before
+    /// [`Generator::set_metadata`].
+    pub fn set_notes_format(&mut self, notes_format: NotesFormat) -> &mut Self {
+        self.notes_format = notes_format;
+        self
+    }
+
+    /// Records where each chapter starts, as ComicInfo.xml page bookmarks and in the
+    /// `Notes` field. `first_chapter_number` is the 1-based number of the archive's first
+    /// chapter counted across all outputs, `page_counts` the pages of each chapter in the
+    /// archive. Must be called before [`Generator::set_metadata`].
+    pub fn set_chapter_map(
+        &mut self,
+        first_chapter_number: usize,
+        total_chapters: usize,
+        page_counts: Vec<usize>,
+    ) -> &mut Self {
+        self.chapter_map = Some(ChapterMap {
+            first_number: first_chapter_number,
+            total_chapters,
+            page_counts,
+        });
+        self
+    }
+
+    /// Records the outputs before and after this one in reading order in the `Notes`
+    /// field. Must be called before [`Generator::set_metadata`].
+    pub fn set_volume_links(
+        &mut self,
+        previous: Option<VolumeLink>,
+        next: Option<VolumeLink>,
+    ) -> &mut Self {
+        self.previous_volume = previous;
+        self.next_volume = next;
+        self
+    }
+
+    /// Makes page reads of [`Cbz::add_pages`] draw from the shared I/O permits of `limits`,
+    /// and page processing from its CPU permits.
+    pub fn set_runtime_limits(&mut self, limits: &RuntimeLimits) -> &mut Self {
+        self.io_limit = Some(limits.io_semaphore());
+        self.cpu_limit = Some(limits.cpu_semaphore());
+        self.throttle = limits.throttle();
+        self
+    }
+
+    /// Makes page reads of [`Cbz::add_pages`] wait while `control` is paused.
+    pub(crate) fn set_control(&mut self, control: ConversionControl) -> &mut Self {
+        self.control = control;
+        self
+    }
+
+    /// Sets the Unix permission bits (e.g. `0o644`) stored for the entries written
+    /// afterwards. Archives default to
+    /// [`DEFAULT_ENTRY_PERMISSIONS`](crate::generator::archive::DEFAULT_ENTRY_PERMISSIONS).
+    pub fn set_entry_permissions(&mut self, mode: u32) -> &mut Self {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.set_entry_permissions(mode);
+        }
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.as_mut() {
+            writer.set_entry_permissions(mode);
+        }
+        self
+    }
+
+    /// Sets the modification times stored for the entries written afterwards.
+    pub fn set_entry_timestamps(&mut self, timestamps: EntryTimestamps) -> &mut Self {
+        self.entry_timestamps = timestamps;
+        self
+    }
+
+    /// Sets how animated pages added through [`Cbz::add_pages`] are handled.
+    ///
+    /// [`AnimatedImagePolicy::PassThrough`] is rejected, since CBZ readers don't play
+    /// animations reliably.
+    pub fn set_animated_image_policy(&mut self, policy: AnimatedImagePolicy) -> Result<&mut Self> {
+        if policy == AnimatedImagePolicy::PassThrough {
+            return Err(Error::Unsupported(
+                "Passing animated images through is only supported for EPUB".to_string(),
+            ));
+        }
+        self.animated_images = policy;
+        Ok(self)
+    }
+
+    /// Enables resizing/transcoding of pages added through [`Cbz::add_pages`].
+    ///
+    /// If spilling is enabled, this creates the spill directory; it is removed once the
+    /// generator is dropped.
+    pub fn set_image_processing(&mut self, options: ImageProcessing) -> Result<&mut Self> {
+        self.processor = Some(Arc::new(PageProcessor::new(options)?));
+        Ok(self)
+    }
+
     /// Adds a custom cover page to the CBZ archive.
     /// This will be added as "000_cover.jpg" and should be called before adding regular pages.
     pub async fn add_cover_page(&mut self, cover_path: &PathBuf) -> Result<&mut Self> {
@@ -41,7 +501,7 @@ impl Cbz {
             )
         })?;
 
-        let (cover_extension, _) = get_file_info(&normalized_path)?;
+        let cover_extension = ImageFormat::from_path(&normalized_path)?.extension();
 
         // Open the file using the normalized path
         let file = fs::File::open(&normalized_path).await.map_err(|e| {
@@ -55,16 +515,20 @@ impl Cbz {
             ))
         })?;
 
-        let file_std = file.into_std().await;
-        let options = self.options;
-        let cover_file_name = format!("000_cover.{}", cover_extension);
+        let modified = file.metadata().await.and_then(|m| m.modified()).ok();
+        let modified = self.entry_timestamps.entry_time(modified);
+        let cover_file_name = sanitize_entry_name(&format!("000_cover.{}", cover_extension));
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.as_mut() {
+            writer
+                .write_entry_at(&cover_file_name, &read_to_end(file).await?, modified)
+                .await?;
+            self.has_cover = true;
+            return Ok(self);
+        }
 
-        let zip = match self.zip.as_mut() {
-            Some(z) => z,
-            None => {
-                return Err(Error::Unsupported("Zip writer not available".to_string()));
-            }
-        };
+        let file_std = file.into_std().await;
+        let writer = self.writer()?;
 
         // Create the read-only memory map
         let mmap = match spawn_blocking(move || unsafe { MmapOptions::new().map(&file_std) })
@@ -77,46 +541,155 @@ impl Cbz {
             }
         };
 
-        // Add cover to zip
-        zip.start_file(cover_file_name.clone(), options)?;
-        zip.write_all(&mmap[..])?;
+        // Add cover to the archive
+        writer.start_entry_at(&cover_file_name, modified)?;
+        writer.write_all(&mmap[..])?;
 
         self.has_cover = true;
 
         Ok(self)
     }
-}
 
-#[async_trait]
-impl Generator for Cbz {
-    fn new(output_dir: &Path, base_filename: &str) -> Result<Self> {
-        let options: SimpleFileOptions = SimpleFileOptions::default()
-            .compression_method(CompressionMethod::Deflated)
-            .unix_permissions(0o755);
+    /// Adds multiple pages in order, overlapping disk reads with compression.
+    ///
+    /// Upcoming pages are read ahead on blocking threads (bounded by
+    /// the page prefetch depth of the generators, or less when
+    /// throttled) while a dedicated blocking task compresses and
+    /// writes the current page into the archive. The resulting archive is identical
+    /// to calling [`Generator::add_page`] for each page in turn.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_paths` - Paths of the page images, in reading order
+    ///
+    /// # Returns
+    ///
+    /// * `Result<&mut Self>` - Self reference for method chaining or an error
+    pub async fn add_pages(&mut self, image_paths: &[PathBuf]) -> Result<&mut Self> {
+        #[cfg(feature = "async-zip")]
+        if self.async_writer.is_some() {
+            return self.add_pages_async(image_paths).await;
+        }
 
-        // Normalize the output directory path to handle long paths
-        let normalized_output_dir = normalize_path(output_dir)?;
+        let mut writer = match self.writer.take() {
+            Some(w) => w,
+            None => {
+                return Err(Error::Unsupported(
+                    "Archive writer not available".to_string(),
+                ));
+            }
+        };
 
-        // Ensure output directory exists
-        if !normalized_output_dir.exists() {
-            std::fs::create_dir_all(&normalized_output_dir)?;
+        let first_page_number = self.page_index + 1;
+        let timestamps = self.entry_timestamps;
+        let (sender, mut receiver) =
+            mpsc::channel::<PrefetchedPage>(self.throttle.prefetch_depth());
+
+        // The writer task owns the archive while pages stream in and hands it back when
+        // done, together with the number of pages it managed to write.
+        let writer_task = spawn_blocking(move || {
+            let mut written = 0;
+            let mut result = Ok(());
+            while let Some(page) = receiver.blocking_recv() {
+                let file_name = sanitize_entry_name(&format!(
+                    "page_{:03}.{}",
+                    first_page_number + written,
+                    page.extension
+                ));
+                if let Err(e) = writer
+                    .start_entry_at(&file_name, timestamps.entry_time(page.modified))
+                    .and_then(|_| page.data.reader())
+                    .and_then(|mut reader| {
+                        std::io::copy(&mut reader, &mut writer).map_err(Error::from)
+                    })
+                {
+                    result = Err(e);
+                    break;
+                }
+                written += 1;
+            }
+            (writer, written, result)
+        });
+
+        let mut pages = prefetch_pages(
+            image_paths.to_vec(),
+            self.io_limit.clone(),
+            self.cpu_limit.clone(),
+            self.processor.clone(),
+            PageReadOptions {
+                animated: self.animated_images,
+                ..Default::default()
+            },
+            self.throttle,
+            self.control.clone(),
+        );
+        let mut read_result = Ok(());
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(page) => {
+                    if sender.send(page).await.is_err() {
+                        break; // Writer stopped early; its error is reported below
+                    }
+                }
+                Err(e) => {
+                    read_result = Err(e);
+                    break;
+                }
+            }
         }
+        drop(sender);
 
-        let output_file_path = normalized_output_dir.join(format!("{}.cbz", base_filename));
-
-        // Normalize the output file path as well
-        let normalized_output_file = normalize_path(&output_file_path)?;
+        let (writer, written, write_result) = writer_task
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))?;
+        self.writer = Some(writer);
+        self.page_index += written;
 
-        let file = File::create(&normalized_output_file)?;
+        write_result?;
+        read_result?;
+        Ok(self)
+    }
 
-        let zip = ZipWriter::new(file);
+    /// [`Cbz::add_pages`] for the async backend: pages are still read ahead, but written
+    /// on the async runtime.
+    #[cfg(feature = "async-zip")]
+    async fn add_pages_async(&mut self, image_paths: &[PathBuf]) -> Result<&mut Self> {
+        let mut pages = prefetch_pages(
+            image_paths.to_vec(),
+            self.io_limit.clone(),
+            self.cpu_limit.clone(),
+            self.processor.clone(),
+            PageReadOptions {
+                animated: self.animated_images,
+                ..Default::default()
+            },
+            self.throttle,
+            self.control.clone(),
+        );
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let file_name = sanitize_entry_name(&format!(
+                "page_{:03}.{}",
+                self.page_index + 1,
+                page.extension
+            ));
+            let modified = self.entry_timestamps.entry_time(page.modified);
+            let bytes = page.data.into_bytes().await?;
+            if let Some(writer) = self.async_writer.as_mut() {
+                writer.write_entry_at(&file_name, &bytes, modified).await?;
+            }
+            self.page_index += 1;
+        }
+        Ok(self)
+    }
+}
 
-        Ok(Cbz {
-            zip: Some(zip),
-            options,
-            page_index: 0,
-            has_cover: false,
-        })
+#[async_trait]
+impl Generator for Cbz {
+    fn new(output_dir: &Path, base_filename: &str) -> Result<Self> {
+        let writer =
+            ZipArchiveWriter::create(&output_file_path(output_dir, base_filename, "cbz")?)?;
+        Ok(Cbz::with_writer(Box::new(writer)))
     }
 
     async fn add_page(&mut self, image_path: &PathBuf) -> Result<&mut Self> {
@@ -128,7 +701,7 @@ impl Generator for Cbz {
             )
         })?;
 
-        let (image_extension, _) = get_file_info(&normalized_path)?;
+        let image_extension = ImageFormat::from_path(&normalized_path)?.extension();
 
         // Open the file using the normalized path
         let file = fs::File::open(&normalized_path).await.map_err(|e| {
@@ -142,8 +715,6 @@ impl Generator for Cbz {
             ))
         })?;
 
-        let file_std = file.into_std().await;
-        let options = self.options;
         // If we have a cover, start numbering pages from 001, otherwise from 001 as well
         // but the cover would be 000_cover if present
         let page_number = if self.has_cover {
@@ -151,14 +722,21 @@ impl Generator for Cbz {
         } else {
             self.page_index + 1
         };
-        let file_name = format!("page_{:03}.{}", page_number, image_extension);
+        let file_name =
+            sanitize_entry_name(&format!("page_{:03}.{}", page_number, image_extension));
+        let modified = file.metadata().await.and_then(|m| m.modified()).ok();
+        let modified = self.entry_timestamps.entry_time(modified);
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.as_mut() {
+            writer
+                .write_entry_at(&file_name, &read_to_end(file).await?, modified)
+                .await?;
+            self.page_index += 1;
+            return Ok(self);
+        }
 
-        let zip = match self.zip.as_mut() {
-            Some(z) => z,
-            None => {
-                return Err(Error::Unsupported("Zip writer not available".to_string()));
-            }
-        };
+        let file_std = file.into_std().await;
+        let writer = self.writer()?;
 
         // Create the read-only memory map
         let mmap = match spawn_blocking(move || unsafe { MmapOptions::new().map(&file_std) })
@@ -171,10 +749,10 @@ impl Generator for Cbz {
             }
         };
 
-        // Add to zip
-        zip.start_file(file_name.clone(), options)?;
+        // Add to the archive
+        writer.start_entry_at(&file_name, modified)?;
 
-        zip.write_all(&mmap[..])?;
+        writer.write_all(&mmap[..])?;
 
         // Increment page index
         self.page_index += 1;
@@ -182,6 +760,30 @@ impl Generator for Cbz {
         Ok(self)
     }
 
+    async fn add_page_from_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<&mut Self> {
+        let image_extension = ImageFormat::from_path(Path::new(name))?.extension();
+        let file_name = sanitize_entry_name(&format!(
+            "page_{:03}.{}",
+            self.page_index + 1,
+            image_extension
+        ));
+
+        let modified = self.entry_timestamps.entry_time(None);
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.as_mut() {
+            writer.write_entry_at(&file_name, &bytes, modified).await?;
+            self.page_index += 1;
+            return Ok(self);
+        }
+
+        let writer = self.writer()?;
+        writer.start_entry_at(&file_name, modified)?;
+        writer.write_all(&bytes)?;
+        self.page_index += 1;
+
+        Ok(self)
+    }
+
     async fn set_metadata(
         &mut self,
         _file_name_base: &str,
@@ -194,15 +796,6 @@ impl Generator for Cbz {
 
         let mut xml = TEMPLATE.to_string();
 
-        // Helper function to escape XML characters
-        let escape_xml = |text: &str| -> String {
-            text.replace('&', "&amp;")
-                .replace('<', "&lt;")
-                .replace('>', "&gt;")
-                .replace('"', "&quot;")
-                .replace('\'', "&apos;")
-        };
-
         // Basic fields (with XML escaping)
         xml = xml.replace("%title%", &escape_xml(&series_metadata.title));
         xml = xml.replace(
@@ -237,6 +830,31 @@ impl Generator for Cbz {
             &escape_xml(series_metadata.genre.as_deref().unwrap_or("")),
         );
 
+        xml = xml.replace("%gtin%\n", &gtin_element(series_metadata));
+
+        // Page bookmarks at chapter starts; `Image` is the 0-based entry index, cover included
+        let pages = self
+            .chapter_map
+            .as_ref()
+            .map(|map| {
+                let offset = usize::from(self.has_cover);
+                let mut pages = String::from("  <Pages>\n");
+                if self.has_cover {
+                    pages.push_str("    <Page Image=\"0\" Type=\"FrontCover\" />\n");
+                }
+                for (_, title, page, _) in map.starts(collected_chapter_titles) {
+                    pages.push_str(&format!(
+                        "    <Page Image=\"{}\" Bookmark=\"{}\" />\n",
+                        page - 1 + offset,
+                        escape_xml(title)
+                    ));
+                }
+                pages.push_str("  </Pages>\n");
+                pages
+            })
+            .unwrap_or_default();
+        xml = xml.replace("%pages%\n", &pages);
+
         // Authors (as one comma-separated string for "Writer" and "Penciller" if applicable)
         let authors_str = escape_xml(&series_metadata.authors.join(", "));
         xml = xml.replace("%writer%", &authors_str);
@@ -245,9 +863,6 @@ impl Generator for Cbz {
         xml = xml.replace("%colorist%", &authors_str);
         xml = xml.replace("%letterer%", &authors_str);
 
-        // Tags
-        xml = xml.replace("%tags%", &escape_xml(&series_metadata.tags.join(", ")));
-
         // Dates
         let now_utc = Utc::now();
         let release_date = series_metadata.release_date.unwrap_or(now_utc);
@@ -255,64 +870,62 @@ impl Generator for Cbz {
         xml = xml.replace("%month%", &release_date.month().to_string());
         xml = xml.replace("%day%", &release_date.day().to_string());
 
-        // Custom fields are safely embedded in the Notes section as key-value pairs
-        // This follows ComicInfo.xml best practices for custom metadata
-        let custom_fields_xml: String = if series_metadata.custom_fields.is_empty() {
-            String::new()
-        } else {
-            series_metadata
-                .custom_fields
-                .par_iter()
-                .map(|(key, value)| {
-                    // Escape XML characters in key and value to prevent XML parsing issues
-                    let escaped_key = escape_xml(key);
-                    let escaped_value = escape_xml(value);
-                    format!("    {}: {}", escaped_key, escaped_value)
-                })
-                .collect::<Vec<_>>()
-                .join("\n")
-        };
-        xml = xml.replace("%customfields%", &custom_fields_xml);
-
-        // Chapter titles (can be added as a comment or custom tag)
-        let chapter_titles_str = escape_xml(&collected_chapter_titles.join(", "));
-        xml = xml.replace("%chaptertitles%", &chapter_titles_str);
+        // Tags, identifier, rights, custom fields, chapter titles and the source fingerprint
+        // go into Notes. Substituted last so user values can't inject template placeholders.
+        let notes = render_notes(
+            &self.notes_format,
+            series_metadata,
+            collected_chapter_titles,
+            self.chapter_map.as_ref(),
+            self.previous_volume.as_ref(),
+            self.next_volume.as_ref(),
+            self.fingerprint.as_ref(),
+        );
+        xml = xml.replace("%notes%", &escape_xml(&notes));
 
         let xml_bytes = spawn_blocking(move || xml.as_bytes().to_vec())
             .await
             .map_err(|e| Error::AsyncTaskError(e.to_string()))?;
 
-        let zip = match self.zip.as_mut() {
-            Some(z) => z,
-            None => {
-                return Err(Error::Unsupported("Zip writer not available".to_string()));
-            }
-        };
+        let modified = self.entry_timestamps.entry_time(None);
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.as_mut() {
+            writer
+                .write_entry_at("ComicInfo.xml", &xml_bytes, modified)
+                .await?;
+            return Ok(self);
+        }
 
-        // Add the metadata file to zip
-        zip.start_file("ComicInfo.xml", self.options)?;
+        let writer = self.writer()?;
 
-        zip.write_all(&xml_bytes)?;
+        // Add the metadata file to the archive
+        writer.start_entry_at("ComicInfo.xml", modified)?;
+
+        writer.write_all(&xml_bytes)?;
 
         Ok(self)
     }
 
     async fn save(mut self) -> Result<()> {
-        // Take ownership of the zip writer
-        let zip = match self.zip.take() {
-            Some(z) => z,
+        #[cfg(feature = "async-zip")]
+        if let Some(writer) = self.async_writer.take() {
+            return writer.finish().await;
+        }
+
+        // Take ownership of the archive writer
+        let writer = match self.writer.take() {
+            Some(w) => w,
             None => {
-                return Err(Error::Unsupported("Zip writer not available".to_string()));
+                return Err(Error::Unsupported(
+                    "Archive writer not available".to_string(),
+                ));
             }
         };
 
-        // Finish writing the zip file in a blocking task
-        spawn_blocking(move || match zip.finish() {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Error::Zip(e)),
-        })
-        .await
-        .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+        // Finish writing the archive in a blocking task
+        spawn_blocking(move || writer.finish())
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
 
         Ok(())
     }
@@ -0,0 +1,323 @@
+//! Directory output: the pages of a volume as plain image files.
+//!
+//! Instead of packing a volume into an archive, every page is written to
+//! `<output dir>/<file name>/`, numbered in reading order (`001.jpg`, `002.png`, …) with a
+//! custom cover as `000_cover.jpg`, so other tools can pick up the structured and
+//! processed pages. Pages are written into a hidden staging directory that replaces the
+//! volume directory only once complete, so an interrupted conversion never leaves a
+//! partial volume behind.
+//!
+//! With [`Directory::set_link_pages`], pages that are used as they are get hard linked to
+//! their source file instead of copied, so runs that only reorganize pages write almost
+//! nothing. Where the output is on another filesystem or doesn't support links, the pages
+//! are copied.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::generator::{Generator, PageReadOptions, prefetch_pages};
+use crate::path_utils::{
+    normalize_path, path_to_string_lossy, retry_while_locked, sanitize_entry_name,
+};
+use crate::processing::{AnimatedImagePolicy, ImageProcessing, PageProcessor, is_animated_file};
+use crate::runtime::{ConversionControl, RuntimeLimits, ThrottleProfile, acquire_file_handle};
+use crate::types::{EbookMetadata, ImageFormat};
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::sync::Semaphore;
+
+/// A generator writing the pages of a volume into a directory.
+pub struct Directory {
+    output_path: PathBuf,                  // The volume directory
+    staging_path: PathBuf,                 // Pages are written here until `save`
+    io_limit: Option<Arc<Semaphore>>,      // Shared cap on concurrent page reads, if set
+    cpu_limit: Option<Arc<Semaphore>>,     // Shared cap on concurrent page processing, if set
+    throttle: ThrottleProfile,             // Pacing of page reads, from the runtime limits
+    control: ConversionControl,            // Pauses page reads of a spawned conversion
+    processor: Option<Arc<PageProcessor>>, // Resize/transcode applied to pages, if set
+    animated_images: AnimatedImagePolicy,
+    link_pages: bool, // Hard link unmodified pages, until the filesystem refuses
+    has_cover: bool,
+    page_index: usize,
+    saved: bool,
+}
+
+impl Directory {
+    /// Makes page reads of [`Directory::add_pages`] draw from the shared I/O permits of
+    /// `limits`, and page processing from its CPU permits.
+    pub fn set_runtime_limits(&mut self, limits: &RuntimeLimits) -> &mut Self {
+        self.io_limit = Some(limits.io_semaphore());
+        self.cpu_limit = Some(limits.cpu_semaphore());
+        self.throttle = limits.throttle();
+        self
+    }
+
+    /// Makes page reads of [`Directory::add_pages`] wait while `control` is paused.
+    pub(crate) fn set_control(&mut self, control: ConversionControl) -> &mut Self {
+        self.control = control;
+        self
+    }
+
+    /// Sets how animated pages added through [`Directory::add_pages`] are handled.
+    pub fn set_animated_image_policy(&mut self, policy: AnimatedImagePolicy) -> Result<&mut Self> {
+        if policy == AnimatedImagePolicy::PassThrough {
+            return Err(Error::Unsupported(
+                "Passing animated images through is only supported for EPUB".to_string(),
+            ));
+        }
+        self.animated_images = policy;
+        Ok(self)
+    }
+
+    /// Enables resizing/transcoding of pages added through [`Directory::add_pages`].
+    ///
+    /// If spilling is enabled, this creates the spill directory; it is removed once the
+    /// generator is dropped.
+    pub fn set_image_processing(&mut self, options: ImageProcessing) -> Result<&mut Self> {
+        self.processor = Some(Arc::new(PageProcessor::new(options)?));
+        Ok(self)
+    }
+
+    /// Makes [`Directory::add_pages`] hard link pages to their source file instead of
+    /// copying them, unless image processing is enabled or the page is animated. Linked
+    /// pages share their data with the source, so changing one changes the other.
+    pub fn set_link_pages(&mut self, enabled: bool) -> &mut Self {
+        self.link_pages = enabled;
+        self
+    }
+
+    /// Copies the custom cover into the directory as `000_cover.<ext>`, in front of the
+    /// pages.
+    pub async fn add_cover_page(&mut self, cover_path: &Path) -> Result<&mut Self> {
+        if self.has_cover {
+            return Err(Error::Unsupported("Cover already set".to_string()));
+        }
+        let normalized_path = normalize_path(cover_path).map_err(|e| {
+            Error::InvalidPath(
+                cover_path.to_path_buf(),
+                format!("Failed to normalize cover path: {}", e),
+            )
+        })?;
+        let extension = ImageFormat::from_path(&normalized_path)?.extension();
+        let bytes = tokio::fs::read(&normalized_path).await.map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read cover file '{}': {}",
+                    path_to_string_lossy(&normalized_path),
+                    e
+                ),
+            ))
+        })?;
+        self.write_file(&format!("000_cover.{}", extension), &bytes)
+            .await?;
+        self.has_cover = true;
+        Ok(self)
+    }
+
+    /// Adds pages in reading order; reads are pipelined with writing the files.
+    pub async fn add_pages(&mut self, image_paths: &[PathBuf]) -> Result<&mut Self> {
+        let mut remaining = image_paths;
+        while self.link_pages && self.processor.is_none() {
+            let Some((image_path, rest)) = remaining.split_first() else {
+                break;
+            };
+            if !self.link_page(image_path).await? {
+                self.copy_pages(std::slice::from_ref(image_path)).await?;
+            }
+            remaining = rest;
+        }
+        self.copy_pages(remaining).await?;
+        Ok(self)
+    }
+
+    /// Writes the pages read (and processed) from `image_paths`.
+    async fn copy_pages(&mut self, image_paths: &[PathBuf]) -> Result<()> {
+        let mut pages = prefetch_pages(
+            image_paths.to_vec(),
+            self.io_limit.clone(),
+            self.cpu_limit.clone(),
+            self.processor.clone(),
+            PageReadOptions {
+                animated: self.animated_images,
+                ..Default::default()
+            },
+            self.throttle,
+            self.control.clone(),
+        );
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let bytes = page.data.into_bytes().await?;
+            self.write_page(page.extension, &bytes).await?;
+        }
+        Ok(())
+    }
+
+    /// Hard links the page at `image_path` as the next page. Returns `false` if it has to
+    /// be copied instead: it is animated, or the filesystem can't link it, which turns
+    /// linking off for the remaining pages. Paced and limited like the page reads of
+    /// [`prefetch_pages`].
+    async fn link_page(&mut self, image_path: &Path) -> Result<bool> {
+        self.control.wait_while_paused().await;
+        self.throttle.pace_page().await;
+        let _permit = match &self.io_limit {
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await?),
+            None => None,
+        };
+        let _handle = acquire_file_handle().await?;
+
+        let source = normalize_path(image_path).map_err(|e| {
+            Error::InvalidPath(
+                image_path.to_path_buf(),
+                format!("Failed to normalize image path: {}", e),
+            )
+        })?;
+        let extension = ImageFormat::from_path(&source)?.extension();
+        let target = self
+            .staging_path
+            .join(format!("{:03}.{}", self.page_index + 1, extension));
+        // Animated pages are changed according to the animated image policy
+        if matches!(extension, "png" | "webp") {
+            let path = source.clone();
+            let animated =
+                tokio::task::spawn_blocking(move || is_animated_file(&path, extension)).await??;
+            if animated {
+                return Ok(false);
+            }
+        }
+        match tokio::fs::hard_link(&source, &target).await {
+            Ok(()) => {
+                self.page_index += 1;
+                Ok(true)
+            }
+            // E.g. `EXDEV` across filesystems, or a filesystem without hard links
+            Err(e) => {
+                log::info!(
+                    "Copying pages instead of linking them, '{}' can't be linked: {}",
+                    path_to_string_lossy(&source),
+                    e
+                );
+                self.link_pages = false;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Writes the next page with the given image extension.
+    async fn write_page(&mut self, extension: &str, bytes: &[u8]) -> Result<()> {
+        let file_name = format!("{:03}.{}", self.page_index + 1, extension);
+        self.write_file(&file_name, bytes).await?;
+        self.page_index += 1;
+        Ok(())
+    }
+
+    /// Writes `bytes` as file `name` into the staging directory.
+    async fn write_file(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.staging_path.join(sanitize_entry_name(name));
+        tokio::fs::write(&path, bytes).await.map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to write '{}': {}", path_to_string_lossy(&path), e),
+            ))
+        })
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        if !self.saved
+            && self.staging_path.exists()
+            && let Err(e) = std::fs::remove_dir_all(&self.staging_path)
+        {
+            log::warn!(
+                "Failed to remove staging directory {:?}: {}",
+                self.staging_path,
+                e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl Generator for Directory {
+    fn new(output_dir: &Path, filename_base: &str) -> Result<Self> {
+        let normalized_output_dir = normalize_path(output_dir)?;
+        let staging_path = normalized_output_dir.join(format!(".{}.partial", filename_base));
+
+        // Left over by a conversion that didn't finish
+        if staging_path.exists() {
+            std::fs::remove_dir_all(&staging_path)?;
+        }
+        std::fs::create_dir_all(&staging_path)?;
+
+        Ok(Directory {
+            output_path: normalized_output_dir.join(filename_base),
+            staging_path,
+            io_limit: None,
+            cpu_limit: None,
+            throttle: ThrottleProfile::Normal,
+            control: ConversionControl::default(),
+            processor: None,
+            animated_images: AnimatedImagePolicy::default(),
+            link_pages: false,
+            has_cover: false,
+            page_index: 0,
+            saved: false,
+        })
+    }
+
+    async fn add_page(&mut self, image_path: &PathBuf) -> Result<&mut Self> {
+        self.add_pages(std::slice::from_ref(image_path)).await
+    }
+
+    async fn add_page_from_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<&mut Self> {
+        let extension = ImageFormat::from_path(Path::new(name))?.extension();
+        self.write_page(extension, &bytes).await?;
+        Ok(self)
+    }
+
+    /// Directories carry no metadata; only the directory name identifies the volume.
+    async fn set_metadata(
+        &mut self,
+        _file_name_base: &str,
+        _file_volume_number: Option<usize>,
+        _series_metadata: &EbookMetadata,
+        _total_pages_in_file: usize,
+        _collected_chapter_titles: &[String],
+    ) -> Result<&mut Self> {
+        Ok(self)
+    }
+
+    async fn save(mut self) -> Result<()> {
+        let (staging_path, output_path) = (self.staging_path.clone(), self.output_path.clone());
+        tokio::task::spawn_blocking(move || {
+            // A regenerated volume replaces the previous one as a whole
+            if output_path.is_dir() {
+                retry_while_locked(&output_path, "replace volume directory", || {
+                    std::fs::remove_dir_all(&output_path)
+                })?;
+            }
+            retry_while_locked(&output_path, "create volume directory", || {
+                std::fs::rename(&staging_path, &output_path)
+            })
+        })
+        .await
+        .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+        self.saved = true;
+        Ok(())
+    }
+}
+
+/// Total size in bytes of the files in the volume directory at `path`.
+pub(crate) fn directory_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
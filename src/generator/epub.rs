@@ -1,34 +1,346 @@
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use crate::alt_text::AltTextSource;
 use crate::error::{Error, Result};
-use crate::generator::Generator;
-use crate::path_utils::{normalize_path, path_to_string_lossy};
-use crate::types::{Direction, EbookMetadata, get_file_info};
+use crate::fingerprint::{FINGERPRINT_KEY, SourceFingerprint};
+use crate::generator::{
+    Generator, PageReadOptions, epub_check, epub_zip, escape_xml, prefetch_pages,
+};
+use crate::path_utils::{
+    normalize_path, path_to_string_lossy, retry_while_locked, sanitize_entry_name,
+};
+use crate::processing::{
+    AnimatedImagePolicy, ImageProcessing, PageData, PageProcessor, ProcessedImageFormat, transcode,
+};
+use crate::runtime::{ConversionControl, RuntimeLimits, ThrottleProfile};
+use crate::types::{
+    ChapterIntro, Direction, EbookMetadata, EntryTimestamps, EpubCompression, EpubPathLayout,
+    EpubVersion, FileFormat, Identifier, IdentifierScheme, ImageFit, ImageFormat, PageStyle,
+    TocOptions, TocStyle, VolumeLabel, VolumeLink,
+};
 use async_trait::async_trait;
-use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ZipLibrary};
+use epub_builder::{EpubBuilder, EpubContent, MetadataOpf, ZipLibrary};
+use futures::StreamExt;
+use lazy_static::lazy_static;
 use memmap2::MmapOptions;
+use regex::{Captures, Regex};
+use tokio::sync::Semaphore;
 use tokio::task::spawn_blocking;
 
 /// Generates XHTML content for an image to be included in the EPUB.
 ///
 /// # Arguments
 ///
+/// * `page_path` - Path of the XHTML file itself relative to the EPUB root, used to
+///   make the image and stylesheet references relative to the page
 /// * `image_source` - Path to the image file relative to the EPUB root
+/// * `page_title` - Title of the page
+/// * `alt_text` - Description of the image; the page title is used if `None`
+/// * `version` - EPUB version; EPUB 2 pages use the XHTML 1.1 doctype
 ///
 /// # Returns
 ///
 /// * `Result<String>` - The generated XHTML content or an error
-fn generate_xhtml(image_source: &str, page_title: &str) -> Result<String> {
-    const TEMPLATE: &str = include_str!("../../templates/Epub.xhtml");
-    let xhtml = TEMPLATE
-        .replace("%title%", page_title)
-        .replace("%src%", image_source)
-        .replace("%alt%", page_title); // Use page title as alt text
+fn generate_xhtml(
+    page_path: &str,
+    image_source: &str,
+    page_title: &str,
+    alt_text: Option<&str>,
+    version: EpubVersion,
+) -> Result<String> {
+    let template = match version {
+        EpubVersion::V2 => include_str!("../../templates/Epub2.xhtml"),
+        EpubVersion::V3 => include_str!("../../templates/Epub.xhtml"),
+    };
+    let to_root = "../".repeat(page_path.matches('/').count());
+    let xhtml = template
+        .replace("%title%", &escape_xml(page_title))
+        .replace("%stylesheet%", &format!("{}stylesheet.css", to_root))
+        .replace(
+            "%src%",
+            &escape_xml(&format!("{}{}", to_root, image_source)),
+        )
+        .replace("%alt%", &escape_xml(alt_text.unwrap_or(page_title)));
     Ok(xhtml)
 }
 
+/// Generates the XHTML of a chapter intro page (see [`ChapterIntro`]): the first line as
+/// heading, the others as paragraphs. Without lines, the page is blank.
+fn generate_intro_xhtml(
+    page_path: &str,
+    page_title: &str,
+    lines: &[String],
+    version: EpubVersion,
+) -> String {
+    let template = match version {
+        EpubVersion::V2 => include_str!("../../templates/EpubIntro2.xhtml"),
+        EpubVersion::V3 => include_str!("../../templates/EpubIntro.xhtml"),
+    };
+    let to_root = "../".repeat(page_path.matches('/').count());
+    let body = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let tag = if index == 0 { "h1" } else { "p" };
+            format!("        <{0}>{1}</{0}>", tag, escape_xml(line))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    template
+        .replace("%title%", &escape_xml(page_title))
+        .replace("%stylesheet%", &format!("{}stylesheet.css", to_root))
+        .replace("%body%", &body)
+}
+
+/// The `dc:title` of an output file: the title, prefixed by the series and followed by
+/// the short volume label and part number, e.g. `Series - Title Vol 2 Part 1`.
+pub(crate) fn epub_title(
+    metadata: &EbookMetadata,
+    volume_number: Option<usize>,
+    part_number: Option<usize>,
+    volume_label: &VolumeLabel,
+) -> String {
+    let mut full_title = metadata.title.clone();
+    if let Some(series) = &metadata.series {
+        full_title = format!("{} - {}", series, metadata.title);
+    }
+    if let Some(vol_num) = volume_number {
+        full_title = format!(
+            "{} {}",
+            full_title,
+            volume_label.format_short(vol_num, &metadata.language)
+        );
+    }
+    if let Some(part_number) = part_number {
+        full_title = format!("{} Part {}", full_title, part_number);
+    }
+    full_title
+}
+
+/// Renders the OPF metadata elements epub-builder can't express: the series, publisher,
+/// identifiers and custom fields.
+pub(crate) fn metadata_elements(
+    metadata: &EbookMetadata,
+    volume_number: Option<usize>,
+    version: EpubVersion,
+) -> Vec<String> {
+    let mut elements = Vec::new();
+
+    // Series, as EPUB 3 collection or in the Calibre convention EPUB 2 readers use
+    if let Some(series_title) = &metadata.series {
+        match version {
+            EpubVersion::V3 => {
+                elements.push(format!(
+                    "<meta property=\"belongs-to-collection\" id=\"hozon-series\">{}</meta>",
+                    escape_xml(series_title)
+                ));
+                elements.push(
+                    "<meta refines=\"#hozon-series\" property=\"collection-type\">series</meta>"
+                        .to_string(),
+                );
+                if let Some(vol_num) = volume_number {
+                    elements.push(format!(
+                        "<meta refines=\"#hozon-series\" property=\"group-position\">{}</meta>",
+                        vol_num
+                    ));
+                }
+            }
+            EpubVersion::V2 => {
+                elements.extend(calibre_series_elements(series_title, volume_number))
+            }
+        }
+    }
+
+    // Publisher
+    if let Some(publisher) = &metadata.publisher {
+        elements.push(format!(
+            "<dc:publisher>{}</dc:publisher>",
+            escape_xml(publisher)
+        ));
+    }
+    // Identifiers (the generated UUID stays the unique identifier of the package)
+    if let Some(identifier) = &metadata.identifier {
+        elements.push(format!(
+            "<dc:identifier id=\"hozon-id-0\">{}</dc:identifier>",
+            escape_xml(identifier)
+        ));
+    }
+    for (index, identifier) in metadata.identifiers.iter().enumerate() {
+        elements.extend(identifier_elements(index, identifier, version));
+    }
+
+    // Custom fields (EPUB doesn't have a direct "custom field" area like ComicInfo.xml),
+    // written as `<meta name content>` pairs, which both EPUB versions allow
+    let mut custom_fields: Vec<_> = metadata.custom_fields.iter().collect();
+    custom_fields.sort();
+    for (key, value) in custom_fields {
+        elements.push(format!(
+            "<meta name=\"{}\" content=\"{}\"/>",
+            escape_xml(key),
+            escape_xml(value)
+        ));
+    }
+    elements
+}
+
+/// Renders the series in the Calibre convention, which EPUB 2 readers and Kobo devices
+/// read.
+fn calibre_series_elements(series_title: &str, volume_number: Option<usize>) -> Vec<String> {
+    let mut elements = vec![format!(
+        "<meta name=\"calibre:series\" content=\"{}\"/>",
+        escape_xml(series_title)
+    )];
+    if let Some(vol_num) = volume_number {
+        elements.push(format!(
+            "<meta name=\"calibre:series_index\" content=\"{}\"/>",
+            vol_num
+        ));
+    }
+    elements
+}
+
+/// Wraps a page document in the markup of Kobo's kepub format: the body content in the
+/// `book-columns` and `book-inner` containers, and every image and text block in a
+/// numbered `koboSpan`, which Kobo's reader uses for positions and reading statistics.
+fn add_kobo_spans(xhtml: &str) -> String {
+    lazy_static! {
+        static ref SPAN_TARGETS: Regex =
+            Regex::new(r"(<img[^>]*/>)|<(h1|p)>(.*?)</(?:h1|p)>").unwrap();
+    }
+    let mut paragraph = 0;
+    let spanned = SPAN_TARGETS.replace_all(xhtml, |captures: &Captures| {
+        paragraph += 1;
+        let span = |content: &str| {
+            format!(
+                "<span class=\"koboSpan\" id=\"kobo.{}.1\">{}</span>",
+                paragraph, content
+            )
+        };
+        match captures.get(1) {
+            Some(image) => span(image.as_str()),
+            None => format!("<{0}>{1}</{0}>", &captures[2], span(&captures[3])),
+        }
+    });
+    spanned
+        .replacen(
+            "<body>\n",
+            "<body>\n<div id=\"book-columns\">\n<div id=\"book-inner\">\n",
+            1,
+        )
+        .replacen("</body>", "</div>\n</div>\n</body>", 1)
+}
+
+/// Renders a typed identifier as `dc:identifier` element, plus an ONIX
+/// `identifier-type` refinement for ISBN and GTIN.
+///
+/// ISBNs use the `urn:isbn:` URN; other schemes are marked with a `<scheme>:` prefix
+/// (e.g. `anilist:30013`), as is common practice for identifiers without a registered URN.
+/// EPUB 2 has no refinements; there the scheme goes into the `opf:scheme` attribute.
+fn identifier_elements(index: usize, identifier: &Identifier, version: EpubVersion) -> Vec<String> {
+    let id = format!("hozon-id-{}", index + 1);
+    let value = escape_xml(&identifier.value);
+    if version == EpubVersion::V2 {
+        return vec![format!(
+            "<dc:identifier id=\"{}\" opf:scheme=\"{}\">{}</dc:identifier>",
+            id,
+            escape_xml(&identifier.scheme.name().to_uppercase()),
+            value
+        )];
+    }
+    let (text, onix_code) = match &identifier.scheme {
+        IdentifierScheme::Isbn => (format!("urn:isbn:{}", value), Some("15")),
+        IdentifierScheme::Gtin => (value, Some("03")),
+        scheme => (format!("{}:{}", escape_xml(scheme.name()), value), None),
+    };
+
+    let mut elements = vec![format!(
+        "<dc:identifier id=\"{}\">{}</dc:identifier>",
+        id, text
+    )];
+    if let Some(code) = onix_code {
+        elements.push(format!(
+            "<meta refines=\"#{}\" property=\"identifier-type\" scheme=\"onix:codelist5\">{}</meta>",
+            id, code
+        ));
+    }
+    elements
+}
+
+/// Renders the schema.org accessibility metadata of an image-based publication.
+///
+/// Unless every page has alternative text, the content is only perceivable visually;
+/// the fixed page order (and the table of contents, if it has entries) are the
+/// accessibility features the book has either way. EPUB 2 lacks `property` metadata, so
+/// the same values are written as `name`/`content` pairs there.
+fn accessibility_elements(has_alt_text: bool, has_toc: bool, version: EpubVersion) -> Vec<String> {
+    let meta = |property: &str, value: &str| match version {
+        EpubVersion::V2 => format!(
+            "<meta name=\"schema:{}\" content=\"{}\"/>",
+            property,
+            escape_xml(value)
+        ),
+        EpubVersion::V3 => format!("<meta property=\"schema:{}\">{}</meta>", property, value),
+    };
+    let mut elements = vec![meta("accessMode", "visual")];
+    if has_alt_text {
+        elements.push(meta("accessMode", "textual"));
+    }
+    elements.push(meta("accessModeSufficient", "visual"));
+    if has_alt_text {
+        elements.push(meta("accessModeSufficient", "textual"));
+        elements.push(meta("accessibilityFeature", "alternativeText"));
+    }
+    if has_toc {
+        elements.push(meta("accessibilityFeature", "tableOfContents"));
+    }
+    elements.extend([
+        meta("accessibilityFeature", "readingOrder"),
+        meta("accessibilityHazard", "unknown"),
+    ]);
+    let content = if has_alt_text {
+        "This publication consists of page images, each with a text description."
+    } else {
+        "This publication consists of page images without text alternatives; its \
+         content is not accessible to readers who cannot see the images."
+    };
+    let navigation = if has_toc {
+        " It is navigable through its table of contents."
+    } else {
+        " Its pages are meant to be read in order and have no table of contents."
+    };
+    let summary = format!("{}{}", content, navigation);
+    elements.push(meta("accessibilitySummary", &summary));
+    elements
+}
+
+/// Returns the format of the encoded image `bytes`, judged by their content and by
+/// `extension_format` (the format their file name claims) if the content isn't
+/// recognized. With a `forced` format, the bytes are re-encoded into it first, unless
+/// they're in that format already. Blocking.
+fn normalize_image(
+    bytes: Vec<u8>,
+    extension_format: ImageFormat,
+    forced: Option<ProcessedImageFormat>,
+) -> Result<(ImageFormat, Vec<u8>)> {
+    let format = ImageFormat::from_bytes(&bytes).unwrap_or(extension_format);
+    match forced {
+        Some(target) if ImageFormat::from(target) != format => {
+            if !format.can_decode() {
+                return Err(Error::Unsupported(format!(
+                    "Converting {:?} images to {:?}",
+                    format, target
+                )));
+            }
+            Ok((target.into(), transcode(&bytes, target)?))
+        }
+        _ => Ok((format, bytes)),
+    }
+}
+
 /// A generator for creating EPUB files with images.
 ///
 /// This struct wraps the `EpubBuilder` functionality and implements the `Generator` trait
@@ -37,10 +349,228 @@ pub struct EPub {
     epub: EpubBuilder<ZipLibrary>,
     output_path: PathBuf,
     filename_base: String,
+    opf_extras: Vec<String>, // Raw OPF metadata elements epub-builder can't express
     reading_direction: Direction,
+    io_limit: Option<Arc<Semaphore>>, // Shared cap on concurrent page reads, if set
+    cpu_limit: Option<Arc<Semaphore>>, // Shared cap on concurrent page processing, if set
+    throttle: ThrottleProfile,        // Pacing of page reads, from the runtime limits
+    control: ConversionControl,       // Pauses page reads of a spawned conversion
+    processor: Option<Arc<PageProcessor>>, // Resize/transcode applied to pages, if set
+    image_format: Option<ProcessedImageFormat>, // Format forced on every image, if set
+    animated_images: AnimatedImagePolicy,
+    toc: TocOptions,
+    version: EpubVersion,
+    volume_label: VolumeLabel,
+    strict: bool,                      // Validate the finished book before writing it
+    entry_timestamps: EntryTimestamps, // Only `Fixed` applies; entries aren't tied to files
+    part_number: Option<usize>,        // Part of a volume split by size, if any
+    alt_text: Option<AltTextSource>,   // Source of page descriptions, if set
+    share_duplicates: bool,            // Store repeated page images only once
+    shared_images: HashMap<[u8; 32], String>, // Page image digest -> resource path
+    compression: EpubCompression,
+    layout: EpubPathLayout,
+    image_fit: ImageFit,
+    page_style: PageStyle,
+    chapter_intro: Option<(ChapterIntro, usize)>, // Intro pages and the first chapter number
+    kobo: bool,                                   // Write a kepub, see `FileFormat::Kepub`
+    deferred_entries: HashMap<String, PageData>,  // Entry name -> content written on save
+    page_documents: Vec<String>, // Archive paths of the page XHTML, in reading order
+    open_chapter: Option<OpenChapter>, // Chapter receiving pages, see `EPub::add_chapter`
+    pages_added: usize,
+    pages_with_alt_text: usize,
+}
+
+/// The chapter of an [`EPub`] that pages are added to.
+struct OpenChapter {
+    index: usize,    // 1-based chapter index
+    label: String,   // Label in the table of contents
+    pages: usize,    // Pages added so far
+    has_intro: bool, // Whether the intro page took the table of contents entry
 }
 
 impl EPub {
+    /// Makes page reads of [`EPub::add_chapter`] draw from the shared I/O permits of
+    /// `limits`, and page processing from its CPU permits.
+    pub fn set_runtime_limits(&mut self, limits: &RuntimeLimits) -> &mut Self {
+        self.io_limit = Some(limits.io_semaphore());
+        self.cpu_limit = Some(limits.cpu_semaphore());
+        self.throttle = limits.throttle();
+        self
+    }
+
+    /// Makes page reads of [`EPub::add_chapter`] wait while `control` is paused.
+    pub(crate) fn set_control(&mut self, control: ConversionControl) -> &mut Self {
+        self.control = control;
+        self
+    }
+
+    /// Sets how animated pages added through [`EPub::add_chapter`] are handled.
+    pub fn set_animated_image_policy(&mut self, policy: AnimatedImagePolicy) -> &mut Self {
+        self.animated_images = policy;
+        self
+    }
+
+    /// Enables resizing/transcoding of pages added through [`EPub::add_chapter`].
+    ///
+    /// If spilling is enabled, this creates the spill directory; it is removed once the
+    /// generator is dropped.
+    pub fn set_image_processing(&mut self, mut options: ImageProcessing) -> Result<&mut Self> {
+        if let Some(format) = self.image_format {
+            options.output_format = Some(format);
+        }
+        self.processor = Some(Arc::new(PageProcessor::new(options)?));
+        Ok(self)
+    }
+
+    /// Re-encodes every image of the book, the cover included, into `format`, since
+    /// some strict reading systems reject books mixing image formats. Images already in
+    /// `format` are kept as they are. Takes precedence over
+    /// [`ImageProcessing::output_format`]; set it before adding the cover and pages.
+    ///
+    /// Pages in a [registered format](crate::types::register_image_format) can't be
+    /// re-encoded; adding one fails with [`Error::Unsupported`].
+    pub fn set_image_format(&mut self, format: ProcessedImageFormat) -> Result<&mut Self> {
+        let mut options = self
+            .processor
+            .as_ref()
+            .map(|processor| processor.options().clone())
+            .unwrap_or_default();
+        options.output_format = Some(format);
+        self.processor = Some(Arc::new(PageProcessor::new(options)?));
+        self.image_format = Some(format);
+        Ok(self)
+    }
+
+    /// Sets the EPUB version of the generated file. Affects the page XHTML written by
+    /// later [`EPub::add_chapter`] calls.
+    pub fn set_epub_version(&mut self, version: EpubVersion) -> &mut Self {
+        self.epub.epub_version(match version {
+            EpubVersion::V2 => epub_builder::EpubVersion::V20,
+            EpubVersion::V3 => epub_builder::EpubVersion::V30,
+        });
+        self.version = version;
+        self
+    }
+
+    /// Sets the label of the volume number in the book title set by
+    /// [`Generator::set_metadata`].
+    pub fn set_volume_label(&mut self, label: VolumeLabel) -> &mut Self {
+        self.volume_label = label;
+        self
+    }
+
+    /// Enables validation of the finished book in [`Generator::save`]; saving fails with
+    /// [`Error::InvalidEpub`] instead of writing a file epubcheck would reject.
+    pub fn set_strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the modification times stored for the entries of the book. Only
+    /// [`EntryTimestamps::Fixed`] has an effect, since entries are generated rather than
+    /// copied from source files.
+    pub fn set_entry_timestamps(&mut self, timestamps: EntryTimestamps) -> &mut Self {
+        self.entry_timestamps = timestamps;
+        self
+    }
+
+    /// Sets how entries are compressed, per resource type. Applies to images added
+    /// afterwards, so set it before adding pages.
+    pub fn set_compression(&mut self, compression: EpubCompression) -> Result<&mut Self> {
+        compression.validate()?;
+        self.compression = compression;
+        Ok(self)
+    }
+
+    /// Sets where page documents and images are stored inside the book. Applies to the
+    /// cover and pages added afterwards.
+    pub fn set_path_layout(&mut self, layout: EpubPathLayout) -> &mut Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets how pages scale on the screen, selecting the stylesheet of the book.
+    pub fn set_image_fit(&mut self, fit: ImageFit) -> &mut Self {
+        self.image_fit = fit;
+        self
+    }
+
+    /// Inserts an intro page with the chapter title before the pages of each chapter
+    /// added through [`EPub::add_chapter`]. `first_chapter` is the 1-based number of the
+    /// first chapter of this file counted across the series.
+    pub fn set_chapter_intro(&mut self, intro: ChapterIntro, first_chapter: usize) -> &mut Self {
+        self.chapter_intro = Some((intro, first_chapter));
+        self
+    }
+
+    /// Writes a Kobo kepub (see [`FileFormat::Kepub`])
+    /// instead of a plain EPUB: pages get Kobo's span markup, the series is also recorded
+    /// the way Kobo devices read it, and the file is named `.kepub.epub`. Set it before
+    /// adding pages.
+    pub fn set_kobo(&mut self, kobo: bool) -> &mut Self {
+        self.kobo = kobo;
+        self
+    }
+
+    /// Returns the page document `xhtml` as written into the book, with Kobo's span markup
+    /// if writing a kepub.
+    fn page_document(&self, xhtml: String) -> String {
+        if self.kobo {
+            add_kobo_spans(&xhtml)
+        } else {
+            xhtml
+        }
+    }
+
+    /// Sets the page background and margins, added to the stylesheet.
+    pub fn set_page_style(&mut self, style: PageStyle) -> Result<&mut Self> {
+        style.validate()?;
+        self.page_style = style;
+        Ok(self)
+    }
+
+    /// Adds an image resource at `path` (relative to `OEBPS/`). Images that aren't to be
+    /// deflated at the default level are added empty and written by
+    /// [`epub_zip::finish_epub`], so epub-builder doesn't compress them in vain.
+    fn add_image_resource(&mut self, path: &str, data: PageData, mime: &str) -> Result<()> {
+        let entry_name = format!("OEBPS/{}", path);
+        if epub_zip::rewrites_entry(&self.compression, &entry_name) {
+            self.epub.add_resource(path, std::io::empty(), mime)?;
+            self.deferred_entries.insert(entry_name, data);
+        } else {
+            self.epub.add_resource(path, data.reader()?, mime)?;
+        }
+        Ok(())
+    }
+
+    /// Sets which entries the table of contents gets for chapters added through
+    /// [`EPub::add_chapter`], and how chapters are labeled.
+    pub fn set_toc_options(&mut self, options: TocOptions) -> &mut Self {
+        self.toc = options;
+        self
+    }
+
+    /// Marks this EPUB as one part of a volume that was split into several files; the
+    /// part number is appended to the title.
+    pub fn set_part_number(&mut self, part_number: usize) -> &mut Self {
+        self.part_number = Some(part_number);
+        self
+    }
+
+    /// Reads the `alt` text of pages added through [`EPub::add_chapter`] from `source`
+    /// instead of reusing the page title.
+    pub fn set_alt_text_source(&mut self, source: AltTextSource) -> &mut Self {
+        self.alt_text = Some(source);
+        self
+    }
+
+    /// Stores page images that occur on several pages added through [`EPub::add_chapter`]
+    /// only once, referenced by every page showing them.
+    pub fn set_share_duplicate_images(&mut self, share: bool) -> &mut Self {
+        self.share_duplicates = share;
+        self
+    }
+
     /// Sets the cover image for the EPUB file.
     ///
     /// # Arguments
@@ -59,9 +589,9 @@ impl EPub {
             )
         })?;
 
-        let (cover_extension, cover_mime) = get_file_info(&normalized_path)?;
+        let extension_format = ImageFormat::from_path(&normalized_path)?;
 
-        let cover_file = File::open(&normalized_path).map_err(|e| {
+        let mut cover_file = File::open(&normalized_path).map_err(|e| {
             Error::Io(std::io::Error::new(
                 e.kind(),
                 format!(
@@ -71,16 +601,80 @@ impl EPub {
                 ),
             ))
         })?;
+        let mut bytes = Vec::new();
+        cover_file.read_to_end(&mut bytes)?;
+        let (cover_format, bytes) = normalize_image(bytes, extension_format, self.image_format)?;
+        let (cover_extension, cover_mime) = (cover_format.extension(), cover_format.mime());
 
-        // Add cover image as `cover.ext` inside `images/` directory
-        let internal_cover_path = format!("images/cover.{}", cover_extension);
+        // Add cover image as `cover.ext`, inside `images/` unless the layout is flat
+        let internal_cover_path =
+            sanitize_entry_name(&format!("{}.{}", self.layout.cover_path(), cover_extension));
+        let entry_name = format!("OEBPS/{}", internal_cover_path);
+        if epub_zip::rewrites_entry(&self.compression, &entry_name) {
+            self.epub
+                .add_cover_image(internal_cover_path, std::io::empty(), cover_mime)?;
+            self.deferred_entries
+                .insert(entry_name, PageData::Memory(bytes));
+        } else {
+            self.epub
+                .add_cover_image(internal_cover_path, Cursor::new(bytes), cover_mime)?;
+        }
+        Ok(self)
+    }
+
+    /// Adds a blank page without a table of contents entry, shifting the following pages
+    /// to the other side of their spreads. See [`PageSide`](crate::types::PageSide).
+    pub fn add_blank_page(&mut self) -> Result<&mut Self> {
+        let path = self.layout.blank_page_path();
+        let xhtml = self.page_document(generate_intro_xhtml(path, "Blank page", &[], self.version));
         self.epub
-            .add_cover_image(internal_cover_path, cover_file, cover_mime)?;
+            .add_content(EpubContent::new(path, xhtml.as_bytes()))?;
+        self.page_documents.push(format!("OEBPS/{}", path));
         Ok(self)
     }
 
+    /// Embeds the source fingerprint as a `<meta name="hozon:fingerprint">` entry in the OPF.
+    pub fn set_fingerprint(&mut self, fingerprint: &SourceFingerprint) -> &mut Self {
+        self.epub.add_metadata_opf(MetadataOpf {
+            name: FINGERPRINT_KEY.to_string(),
+            content: fingerprint.to_string(),
+        });
+        self
+    }
+
+    /// Links the outputs before and after this one in reading order as
+    /// `<meta name="hozon:previous_volume">` and `hozon:next_volume` entries in the OPF,
+    /// holding the title, with the file name in `hozon:previous_volume_file` and
+    /// `hozon:next_volume_file`.
+    pub fn set_volume_links(
+        &mut self,
+        previous: Option<VolumeLink>,
+        next: Option<VolumeLink>,
+    ) -> &mut Self {
+        for (key, link) in [("previous_volume", previous), ("next_volume", next)] {
+            let Some(link) = link else {
+                continue;
+            };
+            self.opf_extras.push(format!(
+                "<meta name=\"hozon:{}\" content=\"{}\"/>",
+                key,
+                escape_xml(&link.title)
+            ));
+            self.opf_extras.push(format!(
+                "<meta name=\"hozon:{}_file\" content=\"{}\"/>",
+                key,
+                escape_xml(&link.file_name)
+            ));
+        }
+        self
+    }
+
     /// Adds a chapter containing multiple image pages to the EPUB.
     ///
+    /// Calling it again with the same `chapter_index` adds further pages to the chapter,
+    /// and pages added through [`Generator::add_page`] and
+    /// [`Generator::add_page_from_image`] in between belong to it as well.
+    ///
     /// # Arguments
     ///
     /// * `chapter_index` - 1-based chapter index for ordering
@@ -96,35 +690,175 @@ impl EPub {
         chapter_title: &str,
         image_paths: &[PathBuf],
     ) -> Result<&mut Self> {
-        let mut page_xhtml_files = Vec::new(); // To build chapter content in TOC
-        let chapter_base_path = format!("chapters/chapter_{:03}", chapter_index);
-
-        for (i, path) in image_paths.iter().enumerate() {
-            let (image_extension, _image_mime) = get_file_info(path)?;
-
-            // Internal path for the image within the EPUB
-            let image_name_in_epub = format!(
-                "{}/page_{:03}.{}",
-                chapter_base_path,
-                i + 1,
-                image_extension
-            );
-            let page_title = format!("{} - Page {}", chapter_title, i + 1);
-            let xhtml_content = generate_xhtml(&image_name_in_epub, &page_title)?;
+        if self
+            .open_chapter
+            .as_ref()
+            .is_none_or(|chapter| chapter.index != chapter_index)
+        {
+            self.open_chapter(chapter_index, chapter_title)?;
+        }
+
+        let alt_texts = match self.alt_text.clone() {
+            Some(source) => {
+                let paths = image_paths.to_vec();
+                spawn_blocking(move || source.resolve_chapter(&paths))
+                    .await
+                    .map_err(|e| Error::AsyncTaskError(e.to_string()))??
+            }
+            None => Vec::new(),
+        };
 
-            // Add the image resource to the EPUB
-            self.add_resource_mmap(&image_name_in_epub, path).await?;
+        // Upcoming pages are read ahead while the current one is added to the archive
+        let mut pages = prefetch_pages(
+            image_paths.to_vec(),
+            self.io_limit.clone(),
+            self.cpu_limit.clone(),
+            self.processor.clone(),
+            PageReadOptions {
+                animated: self.animated_images,
+                detect_format: true,
+            },
+            self.throttle,
+            self.control.clone(),
+        )
+        .enumerate();
 
-            // Add XHTML content for the page
-            let xhtml_file_name = format!("{}/page_{:03}.xhtml", chapter_base_path, i + 1);
+        while let Some((i, page)) = pages.next().await {
+            let page = page?;
+            if let Some(format) = self.image_format
+                && page.extension != format.file_info().0
+            {
+                return Err(Error::Unsupported(format!(
+                    "Converting page '{}' to {:?}",
+                    path_to_string_lossy(&image_paths[i]),
+                    format
+                )));
+            }
+            let alt_text = alt_texts.get(i).cloned().flatten();
+            self.add_chapter_page(page.data, page.extension, page.mime, alt_text.as_deref())?;
+        }
+        Ok(self)
+    }
+
+    /// Starts chapter `chapter_index`, adding its intro page if one is set.
+    fn open_chapter(&mut self, chapter_index: usize, chapter_title: &str) -> Result<()> {
+        let chapter_label = self.toc.chapter_label(chapter_index, chapter_title);
+
+        // The intro page takes the chapter's table of contents entry
+        let has_intro = self.chapter_intro.is_some();
+        if let Some((intro, first_chapter)) = &self.chapter_intro {
+            let lines = intro.lines(first_chapter + chapter_index - 1, chapter_title);
+            let intro_path = sanitize_entry_name(&self.layout.intro_path(chapter_index));
+            let xhtml = self.page_document(generate_intro_xhtml(
+                &intro_path,
+                &chapter_label,
+                &lines,
+                self.version,
+            ));
+            let toc_title = match self.toc.style {
+                TocStyle::Pages | TocStyle::Chapters => chapter_label.as_str(),
+                TocStyle::None => "",
+            };
             self.epub.add_content(
-                EpubContent::new(xhtml_file_name.clone(), xhtml_content.as_bytes())
-                    .title(&page_title), // Title for TOC
+                EpubContent::new(intro_path.clone(), xhtml.as_bytes()).title(toc_title),
             )?;
+            self.page_documents.push(format!("OEBPS/{}", intro_path));
+        }
+
+        self.open_chapter = Some(OpenChapter {
+            index: chapter_index,
+            label: chapter_label,
+            pages: 0,
+            has_intro,
+        });
+        Ok(())
+    }
+
+    /// Adds a page image to the open chapter, numbered after its pages so far.
+    fn add_chapter_page(
+        &mut self,
+        data: PageData,
+        image_extension: &str,
+        mime: &str,
+        alt_text: Option<&str>,
+    ) -> Result<()> {
+        let Some(chapter) = self.open_chapter.as_mut() else {
+            return Err(Error::Other("No chapter to add the page to".to_string()));
+        };
+        chapter.pages += 1;
+        let (chapter_index, page_number) = (chapter.index, chapter.pages);
+        let chapter_label = chapter.label.clone();
+        let has_intro = chapter.has_intro;
+
+        // Internal paths of the page, per the layout; repeated images point at the
+        // resource of their first occurrence when sharing is enabled
+        let (xhtml_file_name, image_stem) = self.layout.page_paths(chapter_index, page_number);
+        let xhtml_file_name = sanitize_entry_name(&xhtml_file_name);
+        let digest = if self.share_duplicates {
+            Some(data.digest()?)
+        } else {
+            None
+        };
+        let shared_image = digest
+            .as_ref()
+            .and_then(|digest| self.shared_images.get(digest))
+            .cloned();
+        let image_name_in_epub = shared_image
+            .clone()
+            .unwrap_or_else(|| sanitize_entry_name(&format!("{}.{}", image_stem, image_extension)));
+        let page_title = format!("{} - Page {}", chapter_label, page_number);
+        let xhtml_content = self.page_document(generate_xhtml(
+            &xhtml_file_name,
+            &image_name_in_epub,
+            &page_title,
+            alt_text,
+            self.version,
+        )?);
 
-            page_xhtml_files.push(xhtml_file_name);
+        self.pages_added += 1;
+        if alt_text.is_some() {
+            self.pages_with_alt_text += 1;
         }
-        Ok(self)
+
+        // Add the image resource to the EPUB
+        if shared_image.is_none() {
+            self.add_image_resource(&image_name_in_epub, data, mime)?;
+            if let Some(digest) = digest {
+                self.shared_images
+                    .insert(digest, image_name_in_epub.clone());
+            }
+        }
+
+        // Add XHTML content for the page; only titled content gets a TOC entry
+        let toc_title = match self.toc.style {
+            TocStyle::Pages => page_title.as_str(),
+            TocStyle::Chapters if page_number == 1 && !has_intro => chapter_label.as_str(),
+            TocStyle::Chapters | TocStyle::None => "",
+        };
+        self.epub.add_content(
+            EpubContent::new(xhtml_file_name.clone(), xhtml_content.as_bytes()).title(toc_title),
+        )?;
+        self.page_documents
+            .push(format!("OEBPS/{}", xhtml_file_name));
+        Ok(())
+    }
+
+    /// Returns the image path, XHTML path and title of the next page added outside of
+    /// chapters through [`Generator::add_page`] or [`Generator::add_page_from_bytes`].
+    fn loose_page_paths(&self, image_extension: &str) -> (String, String, String) {
+        let page_number = self.pages_added + 1;
+        let (content_path, image_stem) = match self.layout {
+            EpubPathLayout::Nested => (
+                format!("chapter_1/page_{:03}.xhtml", page_number),
+                format!("images/1/page_{:03}", page_number),
+            ),
+            layout => layout.page_paths(1, page_number),
+        };
+        (
+            format!("{}.{}", image_stem, image_extension),
+            content_path,
+            format!("Page {}", page_number),
+        )
     }
 
     /// Adds a resource to the EPUB using memory mapping for efficient handling of large files.
@@ -150,7 +884,7 @@ impl EPub {
             )
         })?;
 
-        let (_, image_mime) = get_file_info(&normalized_path)?;
+        let extension_format = ImageFormat::from_path(&normalized_path)?;
 
         // Open the file asynchronously using the normalized path
         let file = tokio::fs::File::open(&normalized_path).await.map_err(|e| {
@@ -165,16 +899,22 @@ impl EPub {
         })?;
 
         let file_std = file.into_std().await;
-        let epub_ref = &mut self.epub;
         let path = resource_path.to_string();
-        let mime = image_mime.to_string();
 
         let mmap = spawn_blocking(move || unsafe { MmapOptions::new().map(&file_std) })
             .await
             .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+        let mime = ImageFormat::from_bytes(&mmap)
+            .unwrap_or(extension_format)
+            .mime();
 
-        // Add resource directly from memory-mapped data
-        epub_ref.add_resource(&path, Cursor::new(&mmap[..]), &mime)?;
+        // Add resource directly from memory-mapped data, unless it is written on save
+        if epub_zip::rewrites_entry(&self.compression, &format!("OEBPS/{}", path)) {
+            self.add_image_resource(&path, PageData::Memory(mmap.to_vec()), mime)?;
+        } else {
+            self.epub
+                .add_resource(&path, Cursor::new(&mmap[..]), mime)?;
+        }
 
         Ok(self)
     }
@@ -185,9 +925,10 @@ impl Generator for EPub {
     fn new(output_dir: &Path, filename_base: &str) -> Result<Self> {
         let mut epub = EpubBuilder::new(ZipLibrary::new()?)?;
 
-        epub.epub_version(EpubVersion::V30);
+        epub.epub_version(epub_builder::EpubVersion::V30);
 
-        epub.stylesheet(include_bytes!("../../templates/Epub.css").as_slice())?;
+        // Replaced on save unless the default image fit and page style are kept
+        epub.stylesheet(ImageFit::default().stylesheet().as_bytes())?;
 
         // Normalize the output directory path to handle long paths
         let normalized_output_dir = normalize_path(output_dir)?;
@@ -201,33 +942,102 @@ impl Generator for EPub {
             epub,
             output_path: normalized_output_dir,
             filename_base: filename_base.to_string(),
+            opf_extras: Vec::new(),
             reading_direction: Direction::Ltr, // Default, will be updated by set_metadata
+            io_limit: None,
+            cpu_limit: None,
+            throttle: ThrottleProfile::Normal,
+            control: ConversionControl::default(),
+            processor: None,
+            image_format: None,
+            animated_images: AnimatedImagePolicy::default(),
+            toc: TocOptions::default(),
+            version: EpubVersion::default(),
+            volume_label: VolumeLabel::default(),
+            strict: false,
+            entry_timestamps: EntryTimestamps::default(),
+            part_number: None,
+            alt_text: None,
+            share_duplicates: false,
+            shared_images: HashMap::new(),
+            compression: EpubCompression::default(),
+            layout: EpubPathLayout::default(),
+            image_fit: ImageFit::default(),
+            page_style: PageStyle::default(),
+            chapter_intro: None,
+            kobo: false,
+            deferred_entries: HashMap::new(),
+            page_documents: Vec::new(),
+            open_chapter: None,
+            pages_added: 0,
+            pages_with_alt_text: 0,
         })
     }
 
     async fn add_page(&mut self, image_path: &PathBuf) -> Result<&mut Self> {
-        let (image_extension, _) = get_file_info(image_path)?;
+        // Pages re-encoded into a forced format can't be memory-mapped; pages joining a
+        // chapter are added from memory like those of `add_chapter`
+        if self.image_format.is_some() || self.open_chapter.is_some() {
+            let bytes = tokio::fs::read(image_path).await?;
+            return self
+                .add_page_from_bytes(bytes, &path_to_string_lossy(image_path))
+                .await;
+        }
+        let path = image_path.clone();
+        let image_extension = spawn_blocking(move || ImageFormat::from_file(&path))
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))??
+            .extension();
 
-        // This `add_page` is for flat content where each page is its own "chapter" in EPUB context
-        let page_index = 0; // Simplified index for this
-        let chapter_idx = 1;
+        // This `add_page` is for flat content outside of chapters, numbered in order of addition
+        let (image_name, content_path, page_title) = self.loose_page_paths(image_extension);
+        let xhtml_content = self.page_document(generate_xhtml(
+            &content_path,
+            &image_name,
+            &page_title,
+            None,
+            self.version,
+        )?);
+        self.pages_added += 1;
 
-        let image_name = format!(
-            "images/{}/page_{:03}.{}",
-            chapter_idx,
-            page_index + 1,
-            image_extension
-        );
+        self.add_resource_mmap(&image_name, image_path).await?;
 
-        let page_title = format!("Page {}", page_index + 1);
-        let xhtml_content = generate_xhtml(&image_name, &page_title)?;
+        self.epub.add_content(
+            EpubContent::new(content_path.clone(), xhtml_content.as_bytes()).title(&page_title),
+        )?;
+        self.page_documents.push(format!("OEBPS/{}", content_path));
 
-        self.add_resource_mmap(&image_name, image_path).await?;
+        Ok(self)
+    }
+
+    async fn add_page_from_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<&mut Self> {
+        let extension_format = ImageFormat::from_path(Path::new(name))?;
+        let forced = self.image_format;
+        let (image_format, bytes) =
+            spawn_blocking(move || normalize_image(bytes, extension_format, forced))
+                .await
+                .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+        let (image_extension, image_mime) = (image_format.extension(), image_format.mime());
+        if self.open_chapter.is_some() {
+            self.add_chapter_page(PageData::Memory(bytes), image_extension, image_mime, None)?;
+            return Ok(self);
+        }
+
+        let (image_name, content_path, page_title) = self.loose_page_paths(image_extension);
+        let xhtml_content = self.page_document(generate_xhtml(
+            &content_path,
+            &image_name,
+            &page_title,
+            None,
+            self.version,
+        )?);
+        self.pages_added += 1;
 
-        let content_path = format!("chapter_1/page_{:03}.xhtml", page_index + 1);
+        self.add_image_resource(&image_name, PageData::Memory(bytes), image_mime)?;
         self.epub.add_content(
             EpubContent::new(content_path.clone(), xhtml_content.as_bytes()).title(&page_title),
         )?;
+        self.page_documents.push(format!("OEBPS/{}", content_path));
 
         Ok(self)
     }
@@ -240,26 +1050,17 @@ impl Generator for EPub {
         _total_pages_in_file: usize,
         _collected_chapter_titles: &[String],
     ) -> Result<&mut Self> {
-        // Main Title (use the specific title for this output file)
-        let mut full_title = series_metadata.title.clone();
-        if let Some(series) = &series_metadata.series {
-            full_title = format!("{} - {}", series, series_metadata.title);
-        }
-        if let Some(vol_num) = file_volume_number {
-            full_title = format!("{} Vol {}", full_title, vol_num);
-        }
+        let full_title = epub_title(
+            series_metadata,
+            file_volume_number,
+            self.part_number,
+            &self.volume_label,
+        );
         self.epub.metadata("title", &full_title)?;
 
-        // Series Title (if different from main title)
-        if let Some(series_title) = &series_metadata.series {
-            if series_title != &series_metadata.title {
-                self.epub.metadata("series", series_title)?;
-            }
-        }
-
         // Creators/Authors
         for author in &series_metadata.authors {
-            self.epub.metadata("creator", author)?;
+            self.epub.add_author(author);
         }
         self.epub.set_lang(&series_metadata.language);
 
@@ -270,56 +1071,92 @@ impl Generator for EPub {
         if let Some(description) = &series_metadata.description {
             self.epub.metadata("description", description)?;
         }
-        // Publisher
-        if let Some(publisher) = &series_metadata.publisher {
-            self.epub.metadata("publisher", publisher)?;
-        }
         // Rights
         if let Some(rights) = &series_metadata.rights {
-            self.epub.metadata("rights", rights)?;
-        }
-        // Identifier
-        if let Some(identifier) = &series_metadata.identifier {
-            self.epub.metadata("identifier", identifier)?;
+            self.epub.set_license(rights);
         }
         // Release Date
-        if let Some(release_date) = &series_metadata.release_date {
-            self.epub.metadata("date", &release_date.to_rfc3339())?;
+        if let Some(release_date) = series_metadata.release_date {
+            self.epub.set_publication_date(release_date);
         }
         // Tags
         for tag in &series_metadata.tags {
-            self.epub.metadata("subject", tag)?;
+            self.epub.add_subject(tag);
         }
 
-        // Custom fields (EPUB doesn't have a direct "custom field" area like ComicInfo.xml,
-        // but we can add them as meta properties or additional subjects if meaningful)
-        for (key, value) in &series_metadata.custom_fields {
-            self.epub.metadata(key, value)?; // Attempt to add as generic metadata
+        // Series, publisher, identifiers and custom fields
+        self.opf_extras.extend(metadata_elements(
+            series_metadata,
+            file_volume_number,
+            self.version,
+        ));
+        if self.kobo
+            && self.version == EpubVersion::V3
+            && let Some(series_title) = &series_metadata.series
+        {
+            self.opf_extras
+                .extend(calibre_series_elements(series_title, file_volume_number));
         }
 
         Ok(self)
     }
 
     async fn save(mut self) -> Result<()> {
-        let output_file_path = self
-            .output_path
-            .join(format!("{}.epub", self.filename_base));
+        let format = if self.kobo {
+            FileFormat::Kepub
+        } else {
+            FileFormat::Epub
+        };
+        let output_file_path =
+            self.output_path
+                .join(format!("{}.{}", self.filename_base, format.extension()));
 
         // Normalize the output file path as well
         let normalized_output_file = normalize_path(&output_file_path)?;
 
-        let file = File::create(&normalized_output_file).map_err(|e| {
-            Error::Io(std::io::Error::new(
-                e.kind(),
-                format!(
-                    "Failed to create EPUB file '{}': {}",
-                    path_to_string_lossy(&normalized_output_file),
-                    e
-                ),
-            ))
-        })?;
+        let has_alt_text = self.pages_added > 0 && self.pages_with_alt_text == self.pages_added;
+        let has_toc = self.toc.style != TocStyle::None;
+        self.opf_extras
+            .extend(accessibility_elements(has_alt_text, has_toc, self.version));
 
-        self.epub.generate(file)?;
-        Ok(())
+        if self.image_fit != ImageFit::default() || self.page_style != PageStyle::default() {
+            let stylesheet = format!("{}{}", self.image_fit.stylesheet(), self.page_style.css());
+            // epub-builder stores the stylesheet under this name
+            self.deferred_entries.insert(
+                "OEBPS/stylesheet.css".to_string(),
+                PageData::Memory(stylesheet.into_bytes()),
+            );
+        }
+
+        let mut book = Vec::new();
+        self.epub.generate(&mut book)?;
+        let book = epub_zip::finish_epub(
+            book,
+            &self.opf_extras,
+            self.entry_timestamps.entry_time(None),
+            &self.compression,
+            std::mem::take(&mut self.deferred_entries),
+        )?;
+
+        // Pages are read and processed concurrently, but the spine must list them in the
+        // order they were added; a deviation is a bug, never a book worth writing
+        let spine = epub_check::spine_documents(&book)?;
+        if let Some(problem) = epub_check::check_spine_order(&spine, &self.page_documents) {
+            return Err(Error::InvalidEpub(normalized_output_file, problem));
+        }
+
+        if self.strict {
+            let problems = epub_check::validate(&book)?;
+            if !problems.is_empty() {
+                return Err(Error::InvalidEpub(
+                    normalized_output_file,
+                    problems.join("; "),
+                ));
+            }
+        }
+
+        retry_while_locked(&normalized_output_file, "create EPUB file", || {
+            std::fs::write(&normalized_output_file, &book)
+        })
     }
 }
@@ -0,0 +1,613 @@
+//! Lightweight EPUB validation for [`HozonConfig::strict_epub`](crate::HozonConfig::strict_epub).
+//!
+//! This is not a replacement for epubcheck, but it covers the problems epubcheck (and
+//! store ingestion pipelines built on it) most often reject generated books for:
+//!
+//! - `mimetype` must be the first entry, stored uncompressed, with the exact media type
+//! - every XML document (package, NCX, XHTML) must be well-formed, without HTML-only
+//!   entities like `&nbsp;`, and use unique `id` values
+//! - the package document needs a title, language and unique identifier, and EPUB 3
+//!   packages a `dcterms:modified` date and only known metadata properties
+//! - every manifest item must exist, with a media type matching the image data, every
+//!   spine entry must reference the manifest, and every file must be declared
+//! - links and image sources in XHTML pages must point at existing files
+//! - entry names must be portable (see [`sanitize_entry_name`])
+//!
+//! Independently of strict mode, [`check_spine_order`] verifies before every save that
+//! the spine lists the pages in the order they were added to the generator.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::error::{Error, Result};
+use crate::generator::epub_zip::OPF_PATH;
+use crate::path_utils::sanitize_entry_name;
+
+/// Prefixes reserved by the EPUB 3 specification, usable without declaration.
+const RESERVED_PREFIXES: &[&str] = &[
+    "a11y",
+    "dcterms",
+    "marc",
+    "media",
+    "msv",
+    "onix",
+    "prism",
+    "rendition",
+    "schema",
+    "xsd",
+];
+
+/// Properties of the default EPUB 3 metadata vocabulary.
+const DEFAULT_VOCABULARY: &[&str] = &[
+    "alternate-script",
+    "authority",
+    "belongs-to-collection",
+    "collection-type",
+    "display-seq",
+    "file-as",
+    "group-position",
+    "identifier-type",
+    "meta-auth",
+    "role",
+    "source-of",
+    "term",
+    "title-type",
+];
+
+/// A start (or empty) tag of an XML document.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String, // Text directly following the start tag, for simple metadata elements
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Checks that entity references in `text` are the predefined XML ones or numeric.
+fn check_entities(text: &str) -> std::result::Result<(), String> {
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        rest = &rest[index + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| "unterminated entity reference".to_string())?;
+        let entity = &rest[..end];
+        let valid = match entity.strip_prefix('#') {
+            Some(hex) if hex.starts_with('x') => {
+                hex.len() > 1 && hex[1..].chars().all(|c| c.is_ascii_hexdigit())
+            }
+            Some(decimal) => !decimal.is_empty() && decimal.chars().all(|c| c.is_ascii_digit()),
+            None => matches!(entity, "amp" | "lt" | "gt" | "quot" | "apos"),
+        };
+        if !valid {
+            return Err(format!("undefined entity '&{};'", entity));
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(())
+}
+
+/// Returns the index of the `>` closing the tag that starts `text`, skipping quoted values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses the inside of a start tag (`name attr="value" ...`).
+fn parse_tag(tag: &str) -> std::result::Result<Element, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    if name.is_empty() {
+        return Err("empty tag name".to_string());
+    }
+
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let (key, after_key) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("attribute without value in <{}>", name))?;
+        let key = key.trim();
+        let after_key = after_key.trim_start();
+        let quote = after_key
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("unquoted value of attribute '{}' in <{}>", key, name))?;
+        let value_end = after_key[1..]
+            .find(quote)
+            .ok_or_else(|| format!("unterminated value of attribute '{}' in <{}>", key, name))?;
+        let value = &after_key[1..1 + value_end];
+        if value.contains('<') {
+            return Err(format!("'<' in value of attribute '{}' in <{}>", key, name));
+        }
+        check_entities(value)?;
+        if attributes.iter().any(|(existing, _)| existing == key) {
+            return Err(format!("duplicate attribute '{}' in <{}>", key, name));
+        }
+        attributes.push((key.to_string(), value.to_string()));
+        rest = after_key[value_end + 2..].trim_start();
+    }
+
+    Ok(Element {
+        name: name.to_string(),
+        attributes,
+        text: String::new(),
+    })
+}
+
+/// Checks that `text` is a well-formed XML document and returns its start tags.
+fn parse_xml(text: &str) -> std::result::Result<Vec<Element>, String> {
+    let mut elements: Vec<Element> = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut has_root = false;
+    let mut rest = text;
+
+    loop {
+        let Some(start) = rest.find('<') else {
+            check_entities(rest)?;
+            if !rest.trim().is_empty() {
+                return Err("text after the root element".to_string());
+            }
+            break;
+        };
+        let content = &rest[..start];
+        check_entities(content)?;
+        if open.is_empty() && !content.trim().is_empty() {
+            return Err("text outside the root element".to_string());
+        }
+        if let Some(last) = elements.last_mut()
+            && last.text.is_empty()
+        {
+            last.text = content.trim().to_string();
+        }
+        rest = &rest[start..];
+
+        let skip_to = |rest: &str, prefix: &str, terminator: &str| {
+            rest[prefix.len()..]
+                .find(terminator)
+                .map(|end| prefix.len() + end + terminator.len())
+                .ok_or_else(|| format!("unterminated '{}'", prefix))
+        };
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "<!--", "-->")?..];
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            rest = &rest[skip_to(rest, "<![CDATA[", "]]>")?..];
+            continue;
+        }
+        if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "<?", "?>")?..];
+            continue;
+        }
+        if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, "<!", ">")?..];
+            continue;
+        }
+
+        let end = tag_end(rest).ok_or_else(|| "unterminated tag".to_string())?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            match open.pop() {
+                Some(expected) if expected == name => {}
+                Some(expected) => {
+                    return Err(format!("</{}> closes <{}>", name, expected));
+                }
+                None => return Err(format!("unexpected </{}>", name)),
+            }
+            continue;
+        }
+
+        let (tag, is_empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let element = parse_tag(tag)?;
+        if open.is_empty() && has_root {
+            return Err(format!("second root element <{}>", element.name));
+        }
+        has_root = true;
+        if !is_empty {
+            open.push(element.name.clone());
+        }
+        elements.push(element);
+    }
+
+    if let Some(name) = open.last() {
+        return Err(format!("<{}> is never closed", name));
+    }
+    if !has_root {
+        return Err("no root element".to_string());
+    }
+    Ok(elements)
+}
+
+/// Resolves `href` relative to the directory `base` (both archive paths).
+pub(crate) fn resolve(base: &str, href: &str) -> String {
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Returns the directory part of an archive path, with trailing slash.
+pub(crate) fn directory_of(path: &str) -> &str {
+    path.rfind('/').map_or("", |index| &path[..=index])
+}
+
+/// Returns whether the leading bytes of an image match its declared media type.
+fn image_matches(media_type: &str, data: &[u8]) -> bool {
+    match media_type {
+        "image/jpeg" => data.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "image/png" => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/gif" => data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
+        "image/webp" => data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP",
+        _ => true,
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Validates an EPUB archive.
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - Description of every problem found; empty if the book is valid
+/// * `Err(Error)` - The archive itself could not be read
+pub(crate) fn validate(epub: &[u8]) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut archive = ZipArchive::new(Cursor::new(epub))?;
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let files: HashSet<&str> = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !name.ends_with('/'))
+        .collect();
+
+    for name in &files {
+        if sanitize_entry_name(name) != *name {
+            problems.push(format!("entry name '{}' is not portable", name));
+        }
+    }
+
+    // Container
+    {
+        let first = archive.by_index(0)?;
+        if first.name() != "mimetype" {
+            problems.push(format!("first entry is '{}', not 'mimetype'", first.name()));
+        } else if first.compression() != CompressionMethod::Stored {
+            problems.push("'mimetype' entry is compressed".to_string());
+        }
+    }
+    if files.contains("mimetype")
+        && read_entry(&mut archive, "mimetype")? != b"application/epub+zip"
+    {
+        problems.push("'mimetype' does not contain 'application/epub+zip'".to_string());
+    }
+
+    let container = match files.contains("META-INF/container.xml") {
+        true => String::from_utf8_lossy(&read_entry(&mut archive, "META-INF/container.xml")?)
+            .into_owned(),
+        false => {
+            problems.push("missing META-INF/container.xml".to_string());
+            return Ok(problems);
+        }
+    };
+    let opf_path = match parse_xml(&container) {
+        Ok(elements) => elements
+            .iter()
+            .find(|e| e.name == "rootfile")
+            .and_then(|e| e.attribute("full-path"))
+            .map(str::to_string),
+        Err(e) => {
+            problems.push(format!("META-INF/container.xml: {}", e));
+            None
+        }
+    };
+    let Some(opf_path) = opf_path.filter(|path| files.contains(path.as_str())) else {
+        problems.push("container.xml does not reference an existing package document".to_string());
+        return Ok(problems);
+    };
+
+    // Package document
+    let opf = String::from_utf8_lossy(&read_entry(&mut archive, &opf_path)?).into_owned();
+    let elements = match parse_xml(&opf) {
+        Ok(elements) => elements,
+        Err(e) => {
+            problems.push(format!("{}: {}", opf_path, e));
+            return Ok(problems);
+        }
+    };
+    let mut ids = HashSet::new();
+    for id in elements.iter().filter_map(|e| e.attribute("id")) {
+        if !ids.insert(id) {
+            problems.push(format!("{}: duplicate id '{}'", opf_path, id));
+        }
+    }
+
+    let package = &elements[0];
+    let is_epub3 = package
+        .attribute("version")
+        .is_some_and(|v| v.starts_with('3'));
+    match package.attribute("unique-identifier") {
+        Some(id)
+            if elements
+                .iter()
+                .any(|e| e.name == "dc:identifier" && e.attribute("id") == Some(id)) => {}
+        _ => problems.push(format!(
+            "{}: unique-identifier does not reference a dc:identifier",
+            opf_path
+        )),
+    }
+    for required in ["dc:title", "dc:language"] {
+        if !elements
+            .iter()
+            .any(|e| e.name == required && !e.text.is_empty())
+        {
+            problems.push(format!("{}: missing {}", opf_path, required));
+        }
+    }
+    for meta in elements.iter().filter(|e| e.name == "meta") {
+        let Some(property) = meta.attribute("property") else {
+            continue;
+        };
+        if !is_epub3 {
+            problems.push(format!(
+                "{}: EPUB 3 metadata property '{}' in an EPUB 2 package",
+                opf_path, property
+            ));
+            continue;
+        }
+        let known = match property.split_once(':') {
+            Some((prefix, _)) => RESERVED_PREFIXES.contains(&prefix),
+            None => DEFAULT_VOCABULARY.contains(&property),
+        };
+        if !known {
+            problems.push(format!(
+                "{}: unknown metadata property '{}'",
+                opf_path, property
+            ));
+        }
+    }
+    if is_epub3
+        && !elements
+            .iter()
+            .any(|e| e.name == "meta" && e.attribute("property") == Some("dcterms:modified"))
+    {
+        problems.push(format!("{}: missing dcterms:modified", opf_path));
+    }
+
+    // Manifest and spine
+    let opf_dir = directory_of(&opf_path);
+    let mut declared = HashSet::new();
+    let mut manifest_ids = HashSet::new();
+    let mut documents = Vec::new();
+    for item in elements.iter().filter(|e| e.name == "item") {
+        let (Some(id), Some(href), Some(media_type)) = (
+            item.attribute("id"),
+            item.attribute("href"),
+            item.attribute("media-type"),
+        ) else {
+            problems.push(format!("{}: incomplete manifest item", opf_path));
+            continue;
+        };
+        manifest_ids.insert(id);
+        let path = resolve(opf_dir, href);
+        if !files.contains(path.as_str()) {
+            problems.push(format!("manifest item '{}' does not exist", path));
+            continue;
+        }
+        if media_type.starts_with("image/")
+            && !image_matches(media_type, &read_entry(&mut archive, &path)?)
+        {
+            problems.push(format!("'{}' is not a {} image", path, media_type));
+        }
+        if media_type == "application/xhtml+xml" || media_type == "application/x-dtbncx+xml" {
+            documents.push(path.clone());
+        }
+        declared.insert(path);
+    }
+    for itemref in elements.iter().filter(|e| e.name == "itemref") {
+        if let Some(idref) = itemref.attribute("idref")
+            && !manifest_ids.contains(idref)
+        {
+            problems.push(format!("spine references unknown item '{}'", idref));
+        }
+    }
+    for name in &files {
+        if *name != "mimetype"
+            && !name.starts_with("META-INF/")
+            && *name != opf_path
+            && !declared.contains(*name)
+        {
+            problems.push(format!("'{}' is not declared in the manifest", name));
+        }
+    }
+
+    // Content documents
+    for path in documents {
+        let text = String::from_utf8_lossy(&read_entry(&mut archive, &path)?).into_owned();
+        let elements = match parse_xml(&text) {
+            Ok(elements) => elements,
+            Err(e) => {
+                problems.push(format!("{}: {}", path, e));
+                continue;
+            }
+        };
+        let mut ids = HashSet::new();
+        for id in elements.iter().filter_map(|e| e.attribute("id")) {
+            if !ids.insert(id) {
+                problems.push(format!("{}: duplicate id '{}'", path, id));
+            }
+        }
+        let base = directory_of(&path);
+        for element in &elements {
+            for attribute in ["src", "href"] {
+                let Some(reference) = element.attribute(attribute) else {
+                    continue;
+                };
+                let target = reference.split('#').next().unwrap_or_default();
+                if target.is_empty() || target.contains(':') {
+                    continue; // Fragment-only or external links
+                }
+                let resolved = resolve(base, target);
+                if !files.contains(resolved.as_str()) {
+                    problems.push(format!(
+                        "{}: <{} {}=\"{}\"> points at missing '{}'",
+                        path, element.name, attribute, reference, resolved
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Returns the archive paths of the documents in the spine of an EPUB generated by
+/// epub-builder, in reading order.
+pub(crate) fn spine_documents(epub: &[u8]) -> Result<Vec<String>> {
+    let mut archive = ZipArchive::new(Cursor::new(epub))?;
+    let opf = String::from_utf8_lossy(&read_entry(&mut archive, OPF_PATH)?).into_owned();
+    let elements = parse_xml(&opf).map_err(|e| Error::Other(format!("{}: {}", OPF_PATH, e)))?;
+    let hrefs: HashMap<&str, &str> = elements
+        .iter()
+        .filter(|e| e.name == "item")
+        .filter_map(|item| Some((item.attribute("id")?, item.attribute("href")?)))
+        .collect();
+    Ok(elements
+        .iter()
+        .filter(|e| e.name == "itemref")
+        .filter_map(|itemref| hrefs.get(itemref.attribute("idref")?))
+        .map(|href| resolve(directory_of(OPF_PATH), href))
+        .collect())
+}
+
+/// Checks that every document of `pages` appears in `spine` exactly once, in the same
+/// order. Other spine documents (cover, navigation) are ignored.
+///
+/// # Returns
+///
+/// A description of the first deviation, or `None` if the order holds.
+pub(crate) fn check_spine_order(spine: &[String], pages: &[String]) -> Option<String> {
+    let expected: HashSet<&str> = pages.iter().map(String::as_str).collect();
+    let listed: Vec<&str> = spine
+        .iter()
+        .map(String::as_str)
+        .filter(|document| expected.contains(document))
+        .collect();
+    let deviation = pages
+        .iter()
+        .zip(&listed)
+        .position(|(page, document)| page != document);
+    match deviation {
+        Some(index) => Some(format!(
+            "spine position {} of the pages holds '{}' instead of '{}'",
+            index + 1,
+            listed[index],
+            pages[index]
+        )),
+        None if listed.len() != pages.len() => Some(format!(
+            "spine lists {} of {} pages",
+            listed.len(),
+            pages.len()
+        )),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_well_formed_documents_are_accepted() {
+        let xhtml = "<?xml version=\"1.0\"?>\n<!DOCTYPE html>\n<html><head><title>A &amp; B</title></head>\
+                     <body><!-- note --><img alt='x' src=\"a.jpg\"/></body></html>";
+        let elements = parse_xml(xhtml).unwrap();
+        assert_eq!(elements.len(), 5);
+        assert_eq!(elements[2].text, "A &amp; B");
+        assert_eq!(elements[4].attribute("src"), Some("a.jpg"));
+    }
+
+    #[test]
+    fn test_malformed_documents_are_rejected() {
+        assert!(parse_xml("<p>&nbsp;</p>").is_err());
+        assert!(parse_xml("<p><b></p></b>").is_err());
+        assert!(parse_xml("<p>").is_err());
+        assert!(parse_xml("<p a=\"1\" a=\"2\"/>").is_err());
+        assert!(parse_xml("<p a=1/>").is_err());
+        assert!(parse_xml("<p/><p/>").is_err());
+    }
+
+    #[test]
+    fn test_spine_order_deviations_are_reported() {
+        let pages: Vec<String> = ["a.xhtml", "b.xhtml", "c.xhtml"]
+            .iter()
+            .map(|page| page.to_string())
+            .collect();
+        let spine = |documents: &[&str]| -> Vec<String> {
+            documents.iter().map(|page| page.to_string()).collect()
+        };
+
+        assert_eq!(
+            check_spine_order(
+                &spine(&["nav.xhtml", "a.xhtml", "b.xhtml", "c.xhtml"]),
+                &pages
+            ),
+            None
+        );
+        assert!(check_spine_order(&spine(&["a.xhtml", "c.xhtml", "b.xhtml"]), &pages).is_some());
+        assert!(check_spine_order(&spine(&["a.xhtml", "b.xhtml"]), &pages).is_some());
+        assert!(
+            check_spine_order(
+                &spine(&["a.xhtml", "b.xhtml", "c.xhtml", "a.xhtml"]),
+                &pages
+            )
+            .is_some()
+        );
+    }
+
+    #[test]
+    fn test_references_resolve_against_the_document_directory() {
+        assert_eq!(
+            resolve("OEBPS/chapters/chapter_001/", "../../stylesheet.css"),
+            "OEBPS/stylesheet.css"
+        );
+        assert_eq!(resolve("OEBPS/", "./nav.xhtml"), "OEBPS/nav.xhtml");
+    }
+}
@@ -0,0 +1,123 @@
+//! Zip-level finishing of EPUBs built by `epub-builder`.
+//!
+//! epub-builder only knows a fixed set of metadata keys and keeps its zip backend
+//! private. To add OPF metadata elements it cannot express on its own (such as multiple
+//! identifiers and their refinements), the generated book is rewritten entry by entry:
+//! every entry is copied as-is (without recompressing), except
+//! `content.opf`, which gets the extra elements inserted before `</metadata>`. The same
+//! pass stamps every entry with a fixed modification time, if one is requested.
+//!
+//! epub-builder deflates every entry at the default level. Entries an [`EpubCompression`]
+//! wants otherwise are rewritten in the same pass; images are added to the builder empty
+//! and only written here, so they are never deflated just to be stored afterwards.
+
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::error::Result;
+use crate::generator::archive::zip_time;
+use crate::processing::PageData;
+use crate::types::{EntryCompression, EpubCompression};
+
+/// Path of the package document inside EPUBs generated by epub-builder.
+pub(crate) const OPF_PATH: &str = "OEBPS/content.opf";
+
+/// Inserts `extras` (raw XML elements) right before `</metadata>` of `opf`.
+fn patch_opf(opf: &str, extras: &[String]) -> String {
+    let block: String = extras
+        .iter()
+        .map(|element| format!("    {}\n", element))
+        .collect();
+    match opf.find("  </metadata>") {
+        Some(index) => {
+            let mut patched = opf.to_string();
+            patched.insert_str(index, &block);
+            patched
+        }
+        None => opf.to_string(),
+    }
+}
+
+/// Whether the entry `name` must be written differently than epub-builder does, i.e. not
+/// deflated at the default level.
+pub(crate) fn rewrites_entry(compression: &EpubCompression, name: &str) -> bool {
+    name != "mimetype"
+        && match compression.for_entry(name) {
+            EntryCompression::Stored => true,
+            EntryCompression::Deflated => compression.level.is_some(),
+        }
+}
+
+/// Returns the zip options writing the entry `name` as `compression` asks for.
+fn entry_options(compression: &EpubCompression, name: &str) -> SimpleFileOptions {
+    match compression.for_entry(name) {
+        EntryCompression::Stored => {
+            SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+        }
+        EntryCompression::Deflated => SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(compression.level.map(i64::from)),
+    }
+}
+
+/// Returns the EPUB archive in `epub` with `extras` added to its OPF metadata, entries
+/// compressed according to `compression`, and, if given, every entry's modification time
+/// set to `modified`. `deferred` holds the contents of entries (by full entry name, e.g.
+/// `OEBPS/images/cover.jpg`) that were added to the builder empty.
+///
+/// Entry order is preserved, so the uncompressed `mimetype` entry stays first as
+/// required by the EPUB container specification.
+pub(crate) fn finish_epub(
+    epub: Vec<u8>,
+    extras: &[String],
+    modified: Option<NaiveDateTime>,
+    compression: &EpubCompression,
+    mut deferred: HashMap<String, PageData>,
+) -> Result<Vec<u8>> {
+    let modified = modified.and_then(zip_time);
+    let rewrites_documents = rewrites_entry(compression, OPF_PATH);
+    if extras.is_empty() && modified.is_none() && deferred.is_empty() && !rewrites_documents {
+        return Ok(epub);
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(epub.as_slice()))?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer.set_comment(""); // Fix issues with some readers
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let mut options = entry_options(compression, &name);
+        if let Some(time) = modified {
+            options = options.last_modified_time(time);
+        }
+        if let Some(data) = deferred.remove(&name) {
+            drop(entry);
+            writer.start_file(name, options)?;
+            std::io::copy(&mut data.reader()?, &mut writer)?;
+        } else if name == OPF_PATH {
+            let mut opf = String::new();
+            entry.read_to_string(&mut opf)?;
+            writer.start_file(OPF_PATH, options)?;
+            writer.write_all(patch_opf(&opf, extras).as_bytes())?;
+        } else if rewrites_entry(compression, &name) {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            writer.start_file(name, options)?;
+            writer.write_all(&content)?;
+        } else {
+            let mode = entry.unix_mode();
+            drop(entry);
+            match modified {
+                Some(time) => writer.raw_copy_file_touch(archive.by_index_raw(i)?, time, mode)?,
+                None => writer.raw_copy_file(archive.by_index_raw(i)?)?,
+            }
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
@@ -0,0 +1,733 @@
+//! MOBI (Mobipocket 6) output for Kindle devices.
+//!
+//! Older Kindles open neither CBZ nor EPUB files, so books otherwise have to be converted
+//! with Calibre before sideloading. A MOBI file is a Palm database: record 0 holds the
+//! headers and metadata (EXTH), followed by the book's HTML in uncompressed text records
+//! and one record per image. Every page becomes a full-screen image followed by a page
+//! break; the table of contents is a page at the end of the book, linked from the guide.
+//!
+//! The HTML is pure ASCII (other characters are written as character references), so
+//! text records never split a character. Older Kindles can't decode WebP and don't show
+//! images larger than 127 KiB: WebP pages are re-encoded to JPEG, and oversized images
+//! are reported in the log; limit page sizes with [`ImageProcessing`] for such devices.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::fingerprint::SourceFingerprint;
+use crate::generator::epub::epub_title;
+use crate::generator::{Generator, PageReadOptions, prefetch_pages};
+use crate::path_utils::{normalize_path, path_to_string_lossy, retry_while_locked};
+use crate::processing::{
+    AnimatedImagePolicy, ImageProcessing, PageProcessor, ProcessedImageFormat, transcode,
+};
+use crate::runtime::{ConversionControl, RuntimeLimits, ThrottleProfile};
+use crate::types::{
+    EbookMetadata, EntryTimestamps, IdentifierScheme, ImageFormat, TocOptions, TocStyle,
+    VolumeLabel,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+use tokio::task::spawn_blocking;
+
+/// Size of the text records holding the HTML.
+const TEXT_RECORD_SIZE: usize = 4096;
+
+/// Largest image record older Kindles display.
+const MAX_IMAGE_RECORD_SIZE: usize = 127 * 1024;
+
+/// Length of the PalmDOC header at the start of record 0.
+const PALMDOC_HEADER_LENGTH: usize = 16;
+
+/// Length of the MOBI header following the PalmDOC header, from its `MOBI` identifier on.
+const MOBI_HEADER_LENGTH: usize = 232;
+
+/// Offset of the record list in the Palm database header.
+const RECORD_LIST_OFFSET: usize = 78;
+
+/// Width of the zero-padded byte offsets in `filepos` links, patched in once known.
+const FILEPOS_WIDTH: usize = 10;
+
+/// EXTH record types written by [`Mobi`].
+const EXTH_AUTHOR: u32 = 100;
+const EXTH_PUBLISHER: u32 = 101;
+const EXTH_DESCRIPTION: u32 = 103;
+const EXTH_ISBN: u32 = 104;
+const EXTH_SUBJECT: u32 = 105;
+const EXTH_PUBLISHED: u32 = 106;
+const EXTH_RIGHTS: u32 = 109;
+const EXTH_COVER_OFFSET: u32 = 201;
+const EXTH_HAS_FAKE_COVER: u32 = 203;
+const EXTH_CDE_TYPE: u32 = 501;
+const EXTH_UPDATED_TITLE: u32 = 503;
+const EXTH_LANGUAGE: u32 = 524;
+
+/// EXTH record type of the source identifiers, which include the source fingerprint.
+pub(crate) const EXTH_SOURCE: u32 = 112;
+
+/// FLIS record as written by kindlegen; readers expect it although it carries no data.
+const FLIS_RECORD: &[u8] = b"FLIS\0\0\0\x08\0\x41\0\0\0\0\0\0\xff\xff\xff\xff\0\x01\0\x03\0\0\0\x03\0\0\0\x01\xff\xff\xff\xff";
+
+/// End of file record.
+const EOF_RECORD: &[u8] = b"\xe9\x8e\x0d\x0a";
+
+/// A generator for creating MOBI files for Kindle devices.
+///
+/// Pages are kept in memory until [`Generator::save`] writes the file, since the
+/// headers in front of them depend on the complete book.
+pub struct Mobi {
+    output_file: PathBuf,
+    io_limit: Option<Arc<Semaphore>>, // Shared cap on concurrent page reads, if set
+    cpu_limit: Option<Arc<Semaphore>>, // Shared cap on concurrent page processing, if set
+    throttle: ThrottleProfile,        // Pacing of page reads, from the runtime limits
+    control: ConversionControl,       // Pauses page reads of a spawned conversion
+    processor: Option<Arc<PageProcessor>>, // Resize/transcode applied to pages, if set
+    animated_images: AnimatedImagePolicy,
+    toc: TocOptions,
+    volume_label: VolumeLabel,
+    entry_timestamps: EntryTimestamps, // Only `Fixed` applies, as the creation date
+    title: String,
+    exth: Vec<(u32, Vec<u8>)>, // Metadata records, in order
+    fingerprint: Option<SourceFingerprint>,
+    cover: Option<Vec<u8>>, // Encoded custom cover; the first page otherwise
+    images: Vec<Vec<u8>>,   // Encoded page images, in reading order
+    chapters: Vec<(String, usize)>, // Label and index of the first page of each chapter
+    open_chapter: Option<(usize, Option<String>)>, // Chapter receiving pages; label until its first page
+}
+
+impl Mobi {
+    /// Makes page reads of [`Mobi::add_chapter`] draw from the shared I/O permits of
+    /// `limits`, and page processing from its CPU permits.
+    pub fn set_runtime_limits(&mut self, limits: &RuntimeLimits) -> &mut Self {
+        self.io_limit = Some(limits.io_semaphore());
+        self.cpu_limit = Some(limits.cpu_semaphore());
+        self.throttle = limits.throttle();
+        self
+    }
+
+    /// Makes page reads of [`Mobi::add_chapter`] wait while `control` is paused.
+    pub(crate) fn set_control(&mut self, control: ConversionControl) -> &mut Self {
+        self.control = control;
+        self
+    }
+
+    /// Sets how animated pages added through [`Mobi::add_chapter`] are handled.
+    ///
+    /// [`AnimatedImagePolicy::PassThrough`] is rejected, since Kindles don't play
+    /// animations.
+    pub fn set_animated_image_policy(&mut self, policy: AnimatedImagePolicy) -> Result<&mut Self> {
+        if policy == AnimatedImagePolicy::PassThrough {
+            return Err(Error::Unsupported(
+                "Passing animated images through is only supported for EPUB".to_string(),
+            ));
+        }
+        self.animated_images = policy;
+        Ok(self)
+    }
+
+    /// Enables resizing/transcoding of pages added through [`Mobi::add_chapter`].
+    ///
+    /// If spilling is enabled, this creates the spill directory; it is removed once the
+    /// generator is dropped.
+    pub fn set_image_processing(&mut self, options: ImageProcessing) -> Result<&mut Self> {
+        self.processor = Some(Arc::new(PageProcessor::new(options)?));
+        Ok(self)
+    }
+
+    /// Sets how chapters are labeled in the table of contents; [`TocStyle::None`] leaves
+    /// it out. Chapters get one entry each, also with [`TocStyle::Pages`].
+    pub fn set_toc_options(&mut self, options: TocOptions) -> &mut Self {
+        self.toc = options;
+        self
+    }
+
+    /// Sets the label of the volume number in the book title set by
+    /// [`Generator::set_metadata`].
+    pub fn set_volume_label(&mut self, label: VolumeLabel) -> &mut Self {
+        self.volume_label = label;
+        self
+    }
+
+    /// Sets the creation date of the file. Only [`EntryTimestamps::Fixed`] has an effect;
+    /// the write time is used otherwise.
+    pub fn set_entry_timestamps(&mut self, timestamps: EntryTimestamps) -> &mut Self {
+        self.entry_timestamps = timestamps;
+        self
+    }
+
+    /// Embeds the source fingerprint as an EXTH source record. Must be called before
+    /// [`Generator::set_metadata`].
+    pub fn set_fingerprint(&mut self, fingerprint: &SourceFingerprint) -> &mut Self {
+        self.fingerprint = Some(fingerprint.clone());
+        self
+    }
+
+    /// Sets the cover image shown in the Kindle library. Without a cover, the first page
+    /// is used.
+    pub async fn set_cover(&mut self, cover_image_path: &Path) -> Result<&mut Self> {
+        let normalized_path = normalize_path(cover_image_path).map_err(|e| {
+            Error::InvalidPath(
+                cover_image_path.to_path_buf(),
+                format!("Failed to normalize cover image path: {}", e),
+            )
+        })?;
+        let extension_format = ImageFormat::from_path(&normalized_path)?;
+        let bytes = tokio::fs::read(&normalized_path).await.map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read cover image '{}': {}",
+                    path_to_string_lossy(&normalized_path),
+                    e
+                ),
+            ))
+        })?;
+        self.cover = Some(kindle_image(bytes, extension_format).await?);
+        Ok(self)
+    }
+
+    /// Adds a chapter containing multiple image pages, with an entry in the table of
+    /// contents. Calling it again with the same `chapter_index` adds further pages to the
+    /// chapter, and pages added through [`Generator::add_page`] and
+    /// [`Generator::add_page_from_image`] in between belong to it as well. Chapters
+    /// without pages get no entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `chapter_index` - 1-based chapter index, used by chapter label templates
+    /// * `chapter_title` - The title of the chapter (for the table of contents)
+    /// * `image_paths` - Paths of the page images, in reading order
+    ///
+    /// # Returns
+    ///
+    /// * `Result<&mut Self>` - Self reference for method chaining or an error
+    pub async fn add_chapter(
+        &mut self,
+        chapter_index: usize,
+        chapter_title: &str,
+        image_paths: &[PathBuf],
+    ) -> Result<&mut Self> {
+        if self
+            .open_chapter
+            .as_ref()
+            .is_none_or(|(index, _)| *index != chapter_index)
+        {
+            let label = self.toc.chapter_label(chapter_index, chapter_title);
+            self.open_chapter = Some((chapter_index, Some(label)));
+        }
+
+        // Upcoming pages are read ahead while the current one is converted
+        let mut pages = prefetch_pages(
+            image_paths.to_vec(),
+            self.io_limit.clone(),
+            self.cpu_limit.clone(),
+            self.processor.clone(),
+            PageReadOptions {
+                animated: self.animated_images,
+                detect_format: true,
+            },
+            self.throttle,
+            self.control.clone(),
+        );
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let format = ImageFormat::from_extension(page.extension)
+                .ok_or_else(|| Error::Unsupported(format!("Image format '{}'", page.extension)))?;
+            let bytes = page.data.into_bytes().await?;
+            let image = kindle_image(bytes, format).await?;
+            self.push_image(image);
+        }
+        Ok(self)
+    }
+
+    /// Appends a converted page, starting the open chapter at it if it's the first.
+    fn push_image(&mut self, image: Vec<u8>) {
+        if let Some(label) = self
+            .open_chapter
+            .as_mut()
+            .and_then(|(_, label)| label.take())
+        {
+            self.chapters.push((label, self.images.len()));
+        }
+        self.images.push(image);
+    }
+
+    /// Renders the HTML of the book: the pages, then the table of contents.
+    fn render_html(&self) -> String {
+        let cover_records = usize::from(self.cover.is_some());
+        let has_toc = self.toc.style != TocStyle::None && !self.chapters.is_empty();
+        let placeholder = "0".repeat(FILEPOS_WIDTH);
+        let mut links = Vec::new(); // Offset of each `filepos` value -> index of its target
+
+        let mut html = String::from("<html><head>");
+        if has_toc {
+            html.push_str("<guide><reference type=\"toc\" title=\"Table of Contents\" filepos=");
+            links.push((html.len(), self.images.len()));
+            html.push_str(&placeholder);
+            html.push_str(" /></guide>");
+        }
+        html.push_str("</head><body>");
+
+        let mut targets = Vec::with_capacity(self.images.len() + 1);
+        for index in 0..self.images.len() {
+            targets.push(html.len());
+            html.push_str(&format!(
+                "<p align=\"center\"><img recindex=\"{:05}\" /></p><mbp:pagebreak />",
+                cover_records + index + 1
+            ));
+        }
+        targets.push(html.len()); // The table of contents
+
+        if has_toc {
+            html.push_str("<h2>Table of Contents</h2>");
+            for (label, first_page) in &self.chapters {
+                html.push_str("<p><a filepos=");
+                links.push((html.len(), *first_page));
+                html.push_str(&placeholder);
+                html.push('>');
+                html.push_str(&ascii_html(label));
+                html.push_str("</a></p>");
+            }
+        }
+        html.push_str("</body></html>");
+
+        for (offset, target) in links {
+            let position = format!("{:0width$}", targets[target], width = FILEPOS_WIDTH);
+            html.replace_range(offset..offset + FILEPOS_WIDTH, &position);
+        }
+        html
+    }
+
+    /// Builds record 0: the PalmDOC and MOBI headers, the EXTH metadata and the title.
+    fn header_record(
+        &self,
+        text_length: usize,
+        text_records: usize,
+        image_records: usize,
+    ) -> Vec<u8> {
+        let first_image = 1 + text_records;
+        let flis = first_image + image_records;
+
+        let mut exth = self.exth.clone();
+        if let Some(fingerprint) = &self.fingerprint {
+            exth.push((EXTH_SOURCE, fingerprint.to_string().into_bytes()));
+        }
+        if image_records > 0 {
+            // The custom cover is the first image record, the first page otherwise
+            exth.push((EXTH_COVER_OFFSET, 0u32.to_be_bytes().to_vec()));
+            exth.push((EXTH_HAS_FAKE_COVER, 0u32.to_be_bytes().to_vec()));
+        }
+
+        let mut record = Vec::new();
+        // PalmDOC header: no compression, no encryption
+        put_u16(&mut record, 1);
+        put_u16(&mut record, 0);
+        put_u32(&mut record, text_length as u32);
+        put_u16(&mut record, text_records as u16);
+        put_u16(&mut record, TEXT_RECORD_SIZE as u16);
+        put_u32(&mut record, 0);
+
+        // MOBI header
+        record.extend_from_slice(b"MOBI");
+        put_u32(&mut record, MOBI_HEADER_LENGTH as u32);
+        put_u32(&mut record, 2); // Mobipocket book
+        put_u32(&mut record, 65001); // UTF-8
+        put_u32(&mut record, unique_id(&self.title));
+        put_u32(&mut record, 6); // File version
+        for _ in 0..10 {
+            put_u32(&mut record, u32::MAX); // No indexes
+        }
+        put_u32(&mut record, first_image as u32); // First non-book record
+        let full_name_offset = record.len();
+        put_u32(&mut record, 0); // Patched below
+        put_u32(&mut record, self.title.len() as u32);
+        put_u32(&mut record, 0); // Locale; the EXTH language record is authoritative
+        put_u32(&mut record, 0);
+        put_u32(&mut record, 0);
+        put_u32(&mut record, 6); // Minimum reader version
+        put_u32(&mut record, first_image as u32);
+        record.extend_from_slice(&[0; 16]); // No Huffman compression
+        put_u32(&mut record, 0x40); // Has EXTH
+        record.extend_from_slice(&[0; 32]);
+        put_u32(&mut record, u32::MAX);
+        put_u32(&mut record, u32::MAX); // No DRM
+        record.extend_from_slice(&[0; 20]);
+        put_u16(&mut record, 1); // First content record
+        put_u16(&mut record, (flis - 1) as u16); // Last content record
+        put_u32(&mut record, 1);
+        put_u32(&mut record, (flis + 1) as u32); // FCIS record
+        put_u32(&mut record, 1);
+        put_u32(&mut record, flis as u32); // FLIS record
+        put_u32(&mut record, 1);
+        record.extend_from_slice(&[0; 8]);
+        put_u32(&mut record, u32::MAX);
+        put_u32(&mut record, 0);
+        put_u32(&mut record, u32::MAX);
+        put_u32(&mut record, u32::MAX);
+        put_u32(&mut record, 0); // No trailing entries in text records
+        put_u32(&mut record, u32::MAX); // No INDX record
+        debug_assert_eq!(record.len(), PALMDOC_HEADER_LENGTH + MOBI_HEADER_LENGTH);
+
+        // EXTH header, padded to a multiple of four bytes
+        let records_length: usize = exth.iter().map(|(_, data)| 8 + data.len()).sum();
+        record.extend_from_slice(b"EXTH");
+        put_u32(&mut record, (12 + records_length) as u32);
+        put_u32(&mut record, exth.len() as u32);
+        for (record_type, data) in &exth {
+            put_u32(&mut record, *record_type);
+            put_u32(&mut record, (8 + data.len()) as u32);
+            record.extend_from_slice(data);
+        }
+        record.resize(record.len().next_multiple_of(4), 0);
+
+        let offset = record.len() as u32;
+        record[full_name_offset..full_name_offset + 4].copy_from_slice(&offset.to_be_bytes());
+        record.extend_from_slice(self.title.as_bytes());
+        record.resize((record.len() + 2).next_multiple_of(4), 0);
+        record
+    }
+
+    /// Returns the creation date of the file in seconds since the Unix epoch.
+    fn creation_time(&self) -> u32 {
+        match self.entry_timestamps {
+            EntryTimestamps::Fixed(time) => time.timestamp().max(0) as u32,
+            _ => chrono::Utc::now().timestamp() as u32,
+        }
+    }
+}
+
+#[async_trait]
+impl Generator for Mobi {
+    fn new(output_dir: &Path, filename_base: &str) -> Result<Self> {
+        // Normalize the output directory path to handle long paths
+        let normalized_output_dir = normalize_path(output_dir)?;
+        if !normalized_output_dir.exists() {
+            std::fs::create_dir_all(&normalized_output_dir)?;
+        }
+        let output_file =
+            normalize_path(&normalized_output_dir.join(format!("{}.mobi", filename_base)))?;
+
+        Ok(Mobi {
+            output_file,
+            io_limit: None,
+            cpu_limit: None,
+            throttle: ThrottleProfile::Normal,
+            control: ConversionControl::default(),
+            processor: None,
+            animated_images: AnimatedImagePolicy::default(),
+            toc: TocOptions::default(),
+            volume_label: VolumeLabel::default(),
+            entry_timestamps: EntryTimestamps::default(),
+            title: filename_base.to_string(),
+            exth: Vec::new(),
+            fingerprint: None,
+            cover: None,
+            images: Vec::new(),
+            chapters: Vec::new(),
+            open_chapter: None,
+        })
+    }
+
+    async fn add_page(&mut self, image_path: &PathBuf) -> Result<&mut Self> {
+        let bytes = tokio::fs::read(image_path).await.map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read image file '{}': {}",
+                    path_to_string_lossy(image_path),
+                    e
+                ),
+            ))
+        })?;
+        self.add_page_from_bytes(bytes, &path_to_string_lossy(image_path))
+            .await
+    }
+
+    async fn add_page_from_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<&mut Self> {
+        let extension_format = ImageFormat::from_path(Path::new(name))?;
+        let image = kindle_image(bytes, extension_format).await?;
+        self.push_image(image);
+        Ok(self)
+    }
+
+    async fn set_metadata(
+        &mut self,
+        _file_name_base: &str, // Passed to `new`, used for filename already
+        file_volume_number: Option<usize>,
+        series_metadata: &EbookMetadata,
+        _total_pages_in_file: usize,
+        _collected_chapter_titles: &[String],
+    ) -> Result<&mut Self> {
+        self.title = epub_title(
+            series_metadata,
+            file_volume_number,
+            None,
+            &self.volume_label,
+        );
+
+        let mut exth: Vec<(u32, String)> = Vec::new();
+        for author in &series_metadata.authors {
+            exth.push((EXTH_AUTHOR, author.clone()));
+        }
+        if let Some(publisher) = &series_metadata.publisher {
+            exth.push((EXTH_PUBLISHER, publisher.clone()));
+        }
+        if let Some(description) = &series_metadata.description {
+            exth.push((EXTH_DESCRIPTION, description.clone()));
+        }
+        for isbn in series_metadata
+            .identifiers
+            .iter()
+            .filter(|id| id.scheme == IdentifierScheme::Isbn)
+        {
+            exth.push((EXTH_ISBN, isbn.value.clone()));
+        }
+        for tag in &series_metadata.tags {
+            exth.push((EXTH_SUBJECT, tag.clone()));
+        }
+        if let Some(release_date) = series_metadata.release_date {
+            exth.push((EXTH_PUBLISHED, release_date.format("%Y-%m-%d").to_string()));
+        }
+        if let Some(rights) = &series_metadata.rights {
+            exth.push((EXTH_RIGHTS, rights.clone()));
+        }
+        if let Some(identifier) = &series_metadata.identifier {
+            exth.push((EXTH_SOURCE, identifier.clone()));
+        }
+        exth.push((EXTH_CDE_TYPE, "EBOK".to_string())); // Listed under books, not documents
+        exth.push((EXTH_UPDATED_TITLE, self.title.clone()));
+        exth.push((EXTH_LANGUAGE, series_metadata.language.clone()));
+
+        self.exth = exth
+            .into_iter()
+            .map(|(record_type, value)| (record_type, value.into_bytes()))
+            .collect();
+        Ok(self)
+    }
+
+    async fn save(mut self) -> Result<()> {
+        let oversized = self
+            .cover
+            .iter()
+            .chain(&self.images)
+            .filter(|image| image.len() > MAX_IMAGE_RECORD_SIZE)
+            .count();
+        if oversized > 0 {
+            log::warn!(
+                "{} images of '{}' exceed 127 KiB and may not display on older Kindles; limit the page size with image processing",
+                oversized,
+                path_to_string_lossy(&self.output_file)
+            );
+        }
+
+        let html = self.render_html();
+        let text_records: Vec<&[u8]> = html.as_bytes().chunks(TEXT_RECORD_SIZE).collect();
+        let cover = self.cover.take();
+        let images: Vec<&[u8]> = cover
+            .iter()
+            .chain(&self.images)
+            .map(Vec::as_slice)
+            .collect();
+        let fcis = fcis_record(html.len());
+        let header = self.header_record(html.len(), text_records.len(), images.len());
+
+        let mut records: Vec<&[u8]> = vec![&header];
+        records.extend(&text_records);
+        records.extend(&images);
+        records.extend([FLIS_RECORD, &fcis, EOF_RECORD]);
+        let book = palm_database(&self.title, self.creation_time(), &records);
+
+        retry_while_locked(&self.output_file, "create MOBI file", || {
+            std::fs::write(&self.output_file, &book)
+        })
+    }
+}
+
+/// Converts a page into a format older Kindles display: WebP pages are re-encoded to
+/// JPEG, JPEG and PNG pages are kept. `extension_format` is the format the file name
+/// claims, used if the content isn't recognized.
+async fn kindle_image(bytes: Vec<u8>, extension_format: ImageFormat) -> Result<Vec<u8>> {
+    match ImageFormat::from_bytes(&bytes).unwrap_or(extension_format) {
+        ImageFormat::Jpeg | ImageFormat::Png => Ok(bytes),
+        ImageFormat::WebP => spawn_blocking(move || transcode(&bytes, ProcessedImageFormat::Jpeg))
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))?,
+        format => Err(Error::Unsupported(format!(
+            "{:?} images in MOBI output",
+            format
+        ))),
+    }
+}
+
+/// Escapes `text` for the HTML of the book, writing every non-ASCII character as a
+/// character reference.
+fn ascii_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c if c.is_ascii() => escaped.push(c),
+            c => escaped.push_str(&format!("&#{};", c as u32)),
+        }
+    }
+    escaped
+}
+
+/// A stable identifier of the book, derived from its title.
+fn unique_id(title: &str) -> u32 {
+    let digest = Sha256::digest(title.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+/// FCIS record as written by kindlegen, referring to the length of the text.
+fn fcis_record(text_length: usize) -> Vec<u8> {
+    let mut record = b"FCIS\0\0\0\x14\0\0\0\x10\0\0\0\x01".to_vec();
+    put_u32(&mut record, text_length as u32);
+    record.extend_from_slice(b"\0\0\0\0\0\0\0\x20\0\0\0\x08\0\x01\0\x01\0\0\0\0");
+    record
+}
+
+/// Assembles a Palm database of type `BOOK`/`MOBI` from its records.
+fn palm_database(title: &str, created: u32, records: &[&[u8]]) -> Vec<u8> {
+    // The database name is limited to 31 bytes; Kindles show the full name from record 0
+    let mut name: Vec<u8> = title
+        .bytes()
+        .map(|b| if b.is_ascii_alphanumeric() { b } else { b'_' })
+        .take(31)
+        .collect();
+    name.resize(32, 0);
+
+    let mut book = name;
+    put_u16(&mut book, 0); // Attributes
+    put_u16(&mut book, 0); // Version
+    put_u32(&mut book, created);
+    put_u32(&mut book, created); // Modified
+    put_u32(&mut book, 0); // Last backup
+    put_u32(&mut book, 0); // Modification number
+    put_u32(&mut book, 0); // App info
+    put_u32(&mut book, 0); // Sort info
+    book.extend_from_slice(b"BOOKMOBI");
+    put_u32(&mut book, (2 * records.len()).saturating_sub(1) as u32); // Unique ID seed
+    put_u32(&mut book, 0); // Next record list
+    put_u16(&mut book, records.len() as u16);
+    debug_assert_eq!(book.len(), RECORD_LIST_OFFSET);
+
+    let mut offset = RECORD_LIST_OFFSET + 8 * records.len() + 2;
+    for (index, record) in records.iter().enumerate() {
+        put_u32(&mut book, offset as u32);
+        put_u32(&mut book, (2 * index) as u32 & 0x00FF_FFFF); // No attributes, unique ID
+        offset += record.len();
+    }
+    put_u16(&mut book, 0); // Gap to the data
+    for record in records {
+        book.extend_from_slice(record);
+    }
+    book
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<usize> {
+    Some(u16::from_be_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?) as usize)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?) as usize)
+}
+
+/// Splits a MOBI file into its records, checking the Palm database header and that the
+/// records are in order and within the file.
+fn records(bytes: &[u8]) -> std::result::Result<Vec<&[u8]>, String> {
+    if bytes.get(60..68) != Some(b"BOOKMOBI".as_slice()) {
+        return Err("not a MOBI file".to_string());
+    }
+    let count = read_u16(bytes, 76).ok_or("truncated header")?;
+    let offsets = (0..count)
+        .map(|index| read_u32(bytes, RECORD_LIST_OFFSET + 8 * index))
+        .collect::<Option<Vec<_>>>()
+        .ok_or("truncated record list")?;
+    let mut records = Vec::with_capacity(count);
+    for (index, &start) in offsets.iter().enumerate() {
+        let end = offsets.get(index + 1).copied().unwrap_or(bytes.len());
+        if start > end || end > bytes.len() {
+            return Err(format!("record {} lies outside the file", index));
+        }
+        records.push(&bytes[start..end]);
+    }
+    Ok(records)
+}
+
+/// Checks that the MOBI file `bytes` can be read: the records are in order, record 0
+/// carries a MOBI header, and the text records hold the declared text length.
+pub(crate) fn verify(bytes: &[u8]) -> std::result::Result<(), String> {
+    let records = records(bytes)?;
+    let header = records.first().ok_or("the file has no records")?;
+    if header.get(16..20) != Some(b"MOBI".as_slice()) {
+        return Err("record 0 has no MOBI header".to_string());
+    }
+    let text_length = read_u32(header, 4).ok_or("truncated PalmDOC header")?;
+    let text_records = read_u16(header, 8).ok_or("truncated PalmDOC header")?;
+    let stored: usize = records
+        .get(1..=text_records)
+        .ok_or("text records are missing")?
+        .iter()
+        .map(|record| record.len())
+        .sum();
+    if stored != text_length {
+        return Err(format!(
+            "text records hold {} bytes instead of {}",
+            stored, text_length
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the data of every EXTH record of type `record_type` in the MOBI file `bytes`.
+///
+/// # Errors
+///
+/// [`Error::Other`] if `bytes` isn't a readable MOBI file.
+pub(crate) fn read_exth(bytes: &[u8], record_type: u32) -> Result<Vec<Vec<u8>>> {
+    let records = records(bytes).map_err(Error::Other)?;
+    let header = records
+        .first()
+        .filter(|header| header.get(16..20) == Some(b"MOBI".as_slice()))
+        .ok_or_else(|| Error::Other("MOBI file without MOBI header".to_string()))?;
+    let has_exth = read_u32(header, 0x80).is_some_and(|flags| flags & 0x40 != 0);
+    let Some(start) = read_u32(header, 20).map(|length| PALMDOC_HEADER_LENGTH + length) else {
+        return Ok(Vec::new());
+    };
+    if !has_exth || header.get(start..start + 4) != Some(b"EXTH".as_slice()) {
+        return Ok(Vec::new());
+    }
+
+    let count = read_u32(header, start + 8).unwrap_or(0);
+    let mut values = Vec::new();
+    let mut offset = start + 12;
+    for _ in 0..count {
+        let (Some(kind), Some(length)) = (read_u32(header, offset), read_u32(header, offset + 4))
+        else {
+            break;
+        };
+        let Some(data) = header.get(offset + 8..offset + length.max(8)) else {
+            break;
+        };
+        if kind == record_type as usize {
+            values.push(data.to_vec());
+        }
+        offset += length.max(8);
+    }
+    Ok(values)
+}
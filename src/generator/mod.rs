@@ -3,13 +3,182 @@
 //! This module contains the common interface for document generators and specific
 //! implementations for different file formats.
 
-use crate::error::Result;
-use crate::types::EbookMetadata;
+use crate::error::{Error, Result};
+use crate::path_utils::{normalize_path, path_to_string_lossy};
+use crate::processing::{
+    AnimatedImagePolicy, PageData, PageProcessor, encode_page, first_frame, is_animated,
+};
+use crate::runtime::{
+    ConversionControl, ThrottleProfile, acquire_file_handle, is_too_many_open_files,
+};
+use crate::types::{EbookMetadata, ImageFormat};
 use async_trait::async_trait;
+use futures::stream::{self, Stream, StreamExt};
+use image::DynamicImage;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::Semaphore;
+use tokio::task::spawn_blocking;
 
+pub mod archive;
 pub mod cbz;
+pub mod directory;
 pub mod epub;
+pub(crate) mod epub_check;
+pub(crate) mod epub_zip;
+pub mod mobi;
+
+/// Number of pages read ahead of the archive writer within a single volume, unless a
+/// [`ThrottleProfile`] other than `Normal` is in effect.
+///
+/// Bounds the memory held by in-flight pages while letting disk reads overlap
+/// with compression of the current page.
+pub(crate) const PAGE_PREFETCH_DEPTH: usize = 8;
+
+/// How [`prefetch_pages`] treats the pages it reads, before any processing.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PageReadOptions {
+    pub animated: AnimatedImagePolicy,
+    pub detect_format: bool, // Name and type pages after their content, not their extension
+}
+
+/// A page image that has been read (and processed, if enabled) ahead of being written.
+pub(crate) struct PrefetchedPage {
+    pub extension: &'static str,
+    pub mime: &'static str,
+    pub data: PageData,
+    pub modified: Option<SystemTime>, // Modification time of the source file
+}
+
+/// Reads the given pages concurrently on blocking threads, yielding them in their
+/// original order. At most [`ThrottleProfile::prefetch_depth`] pages are in flight at
+/// once, reads wait while `control` is paused and are paced by
+/// [`ThrottleProfile::pace_page`], and
+/// each read additionally holds a permit from `io_limit` if one is given and a handle
+/// from the process-wide [open file budget](crate::runtime::open_file_budget). Animated
+/// pages are handled according to `options`, then pages are run through `processor`
+/// when image processing is enabled, holding a permit from `cpu_limit` if one is given.
+pub(crate) fn prefetch_pages(
+    paths: Vec<PathBuf>,
+    io_limit: Option<Arc<Semaphore>>,
+    cpu_limit: Option<Arc<Semaphore>>,
+    processor: Option<Arc<PageProcessor>>,
+    options: PageReadOptions,
+    throttle: ThrottleProfile,
+    control: ConversionControl,
+) -> impl Stream<Item = Result<PrefetchedPage>> {
+    stream::iter(paths)
+        .map(move |path| {
+            let io_limit = io_limit.clone();
+            let cpu_limit = cpu_limit.clone().filter(|_| processor.is_some());
+            let processor = processor.clone();
+            let control = control.clone();
+            async move {
+                control.wait_while_paused().await;
+                throttle.pace_page().await;
+                let _permit = match io_limit {
+                    Some(semaphore) => Some(semaphore.acquire_owned().await?),
+                    None => None,
+                };
+                // Plain reads are I/O bound; only decoding pages needs CPU permits
+                let _cpu_permit = match cpu_limit {
+                    Some(semaphore) => Some(semaphore.acquire_owned().await?),
+                    None => None,
+                };
+                let _handle = acquire_file_handle().await?;
+                spawn_blocking(move || read_page(path, processor.as_deref(), options))
+                    .await
+                    .map_err(|e| Error::AsyncTaskError(e.to_string()))?
+            }
+        })
+        .buffered(throttle.prefetch_depth())
+}
+
+/// Escapes the XML special characters in `text` for use in element content and attributes.
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Reads a single page from disk. Blocking; run on a blocking thread.
+fn read_page(
+    image_path: PathBuf,
+    processor: Option<&PageProcessor>,
+    options: PageReadOptions,
+) -> Result<PrefetchedPage> {
+    // Normalize the image path to handle long paths and special characters
+    let normalized_path = normalize_path(&image_path).map_err(|e| {
+        Error::InvalidPath(
+            image_path.clone(),
+            format!("Failed to normalize image path: {}", e),
+        )
+    })?;
+
+    let format = ImageFormat::from_path(&normalized_path)?;
+    let modified = std::fs::metadata(&normalized_path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    let bytes = std::fs::read(&normalized_path).map_err(|e| {
+        if is_too_many_open_files(&e) {
+            return Error::TooManyOpenFiles(normalized_path.clone());
+        }
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to read image file '{}': {}",
+                path_to_string_lossy(&normalized_path),
+                e
+            ),
+        ))
+    })?;
+
+    // Some sources save e.g. PNG pages with a `.jpg` extension, which strict readers refuse
+    let format = match ImageFormat::from_bytes(&bytes).filter(|_| options.detect_format) {
+        Some(actual) if actual != format => {
+            log::debug!(
+                "Page '{}' is {:?} despite its extension",
+                path_to_string_lossy(&normalized_path),
+                actual
+            );
+            actual
+        }
+        _ => format,
+    };
+    let (extension, mime) = (format.extension(), format.mime());
+
+    let bytes = if is_animated(&bytes, extension) {
+        match options.animated {
+            AnimatedImagePolicy::FirstFrame => first_frame(&bytes, extension)?,
+            AnimatedImagePolicy::Reject => {
+                return Err(Error::Unsupported(format!(
+                    "Animated image '{}' (rejected by the animated image policy)",
+                    path_to_string_lossy(&normalized_path)
+                )));
+            }
+            AnimatedImagePolicy::PassThrough => bytes,
+        }
+    } else {
+        bytes
+    };
+
+    // Registered formats can't be decoded and are always copied as they are
+    let (extension, mime, data) = match processor {
+        Some(processor) if format.can_decode() => processor.process(bytes, extension, mime)?,
+        _ => (extension, mime, PageData::Memory(bytes)),
+    };
+
+    Ok(PrefetchedPage {
+        extension,
+        mime,
+        data,
+        modified,
+    })
+}
 
 /// Common interface for all file generators.
 ///
@@ -41,6 +210,51 @@ pub trait Generator {
     where
         Self: Sized;
 
+    /// Adds a page from an encoded image held in memory, without a file on disk.
+    ///
+    /// The default implementation returns [`Error::Unsupported`]; generators that can
+    /// take pages from memory override it, as all built-in generators do.
+    ///
+    /// # Parameters
+    /// * `bytes` - The encoded image
+    /// * `name` - File name of the page (e.g. `"title.png"`); its extension (`jpg`, `png`
+    ///   or `webp`) determines the image type
+    ///
+    /// # Returns
+    /// * `Result<&mut Self>` - Self reference for method chaining, or an error if failed
+    async fn add_page_from_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<&mut Self>
+    where
+        Self: Sized,
+    {
+        let _ = bytes;
+        Err(Error::Unsupported(format!(
+            "Adding page '{}' from memory to this generator",
+            name
+        )))
+    }
+
+    /// Adds a decoded image as a page, e.g. a modified page or a synthesized title page
+    /// or placeholder.
+    ///
+    /// # Parameters
+    /// * `image` - The image to add
+    /// * `name` - File name of the page (e.g. `"title.png"`); the image is encoded in the
+    ///   format given by its extension (`jpg`, `png` or `webp`)
+    ///
+    /// # Returns
+    /// * `Result<&mut Self>` - Self reference for method chaining, or an error if failed
+    async fn add_page_from_image(&mut self, image: &DynamicImage, name: &str) -> Result<&mut Self>
+    where
+        Self: Sized + Send,
+    {
+        let image = image.clone();
+        let file_name = name.to_string();
+        let bytes = spawn_blocking(move || encode_page(&image, &file_name))
+            .await
+            .map_err(|e| Error::AsyncTaskError(e.to_string()))??;
+        self.add_page_from_bytes(bytes, name).await
+    }
+
     /// Sets comprehensive metadata for the generated document.
     ///
     /// # Parameters
@@ -1,20 +1,68 @@
-use num_cpus;
+use chrono::Datelike;
 use rayon::prelude::*;
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs;
-use tokio::sync::Semaphore;
 
-use crate::collector::{Collector, DEFAULT_NAME_GROUPING_REGEX};
+use crate::alt_text::AltTextSource;
+use crate::analysis_cache::AnalysisCache;
+use crate::collector::{
+    Collector, DEFAULT_NAME_GROUPING_REGEX, DEFAULT_NUMBER_REGEX, chapter_number,
+    sort_spec_comparator,
+};
 use crate::error::{Error, Result};
-use crate::generator::{Generator, cbz::Cbz, epub::EPub};
-use crate::path_utils::sanitize_filename;
+use crate::events::{EventSink, PipelineEvent, emit};
+use crate::fingerprint::{
+    SourceFingerprint, read_embedded_fingerprint, series_digest, series_digest_of,
+};
+use crate::generator::archive::DEFAULT_ENTRY_PERMISSIONS;
+use crate::generator::{
+    Generator, cbz::Cbz, directory, directory::Directory, epub::EPub, mobi::Mobi,
+};
+use crate::lock::OutputLock;
+use crate::metadata::retag_output;
+use crate::path_utils::{
+    get_file_name_lossy, get_file_name_safe, normalize_path, path_to_string_lossy,
+    sanitize_filename, set_file_permissions,
+};
+use crate::pdf::PdfPages;
+use crate::photo::PhotoAlbum;
+use crate::pipeline::{ConversionHandle, HozonPipeline};
+use crate::placeholder::{PageRun, SyntheticPages};
+use crate::presets::{NAMING_PRESETS, naming_preset};
+use crate::processing::{AnimatedImagePolicy, ImageProcessing, ProcessedImageFormat};
+use crate::report::{GeneratedOutput, WarningLog};
+use crate::runtime::{
+    ConversionControl, RuntimeLimits, ThrottleProfile, acquire_file_handle,
+    acquire_output_file_handle,
+};
+use crate::sidecar::{
+    CoverSidecars, ManifestEntry, SERIES_MANIFEST_FILE_NAME, read_series_manifest,
+    write_series_manifest,
+};
+use crate::snapshot::{SourceSnapshot, page_directories};
+use crate::source_archive::{ArchiveExtractions, is_source_archive};
+use crate::storage::StorageKind;
+use crate::telemetry;
 use crate::types::{
-    CollectedContent, CollectionDepth, CoverOptions, Direction, EbookMetadata, FileFormat,
-    HozonExecutionMode, StructuredContent, VolumeGroupingStrategy, VolumeStructureReport,
+    AnalyzeFinding, ArchiveBackend, ChapterIntro, CollectedContent, CollectionDepth, CoverOptions,
+    Direction, DuplicatePagePolicy, EbookMetadata, EntryTimestamps, EpubCompression,
+    EpubPathLayout, EpubVersion, ExtraChapters, ExtractedEbook, FileFormat, ForeignOutputPolicy,
+    HozonExecutionMode, IgnoredOption, ImageFit, LostChapterPolicy, MissingPagePolicy, NotesFormat,
+    OutputCheckReport, OutputState, OutputStatus, PageMapping, PageSide, PageStyle, SkippedVolume,
+    SortSpec, SortStrategy, SourceChangePolicy, SourceCleanup, StructuredContent, TocOptions,
+    TocStyle, UnicodeNormalization, VolumeCountAdjustment, VolumeGroupingStrategy, VolumeLabel,
+    VolumeLink, VolumeStructureReport,
 };
+use crate::volume_mapping::VolumeMapping;
+
+/// Default for [`HozonConfig::image_analysis_sensibility`].
+const DEFAULT_IMAGE_ANALYSIS_SENSIBILITY: u8 = 75;
 
 /// The main Hozon conversion configuration, built declaratively using the builder pattern.
 ///
@@ -27,6 +75,10 @@ use crate::types::{
 /// - [`convert_from_structured_data`](HozonConfig::convert_from_structured_data): From pre-structured volume data
 /// - [`analyze_source`](HozonConfig::analyze_source): Analysis only, no conversion
 ///
+/// None of them consume the configuration, so one config can be reused for retries or
+/// several conversions. To inspect intermediate results between stages, run the conversion through a
+/// [`HozonPipeline`] instead.
+///
 /// ## Builder Pattern
 ///
 /// Use [`HozonConfig::builder()`](HozonConfig::builder) to create a new configuration:
@@ -60,7 +112,11 @@ pub struct HozonConfig {
     ///
     /// Required for [`convert_from_source`](HozonConfig::convert_from_source) and
     /// [`analyze_source`](HozonConfig::analyze_source) methods. The directory structure
-    /// depends on the [`collection_depth`](HozonConfig::collection_depth) setting.
+    /// depends on the [`collection_depth`](HozonConfig::collection_depth) setting. A
+    /// `.cbz`/`.zip` file (or `.cbr`/`.rar` with the `cbr` feature, which extracts them
+    /// with `unrar` or `bsdtar` from the `PATH`) is converted as a single chapter, and
+    /// with [`CollectionDepth::Deep`] such archives in the directory are chapters too;
+    /// their images are extracted into the system temp dir first.
     #[builder(default)]
     pub source_path: PathBuf,
 
@@ -75,6 +131,10 @@ pub struct HozonConfig {
     ///
     /// - [`FileFormat::Cbz`]: Comic Book Archive (ZIP-based) with ComicInfo.xml metadata
     /// - [`FileFormat::Epub`]: EPUB format with full metadata and reading direction support
+    /// - [`FileFormat::Mobi`]: Mobipocket format for Kindle devices, with a table of contents
+    /// - [`FileFormat::Cb7`]: CBZ contents in a 7z archive; requires the `cb7` feature
+    /// - [`FileFormat::Kepub`]: EPUB with Kobo's extensions, written as `.kepub.epub`
+    /// - [`FileFormat::Directory`]: A directory of plain page images per volume
     #[builder(default = "FileFormat::Cbz")]
     pub output_format: FileFormat,
 
@@ -94,10 +154,32 @@ pub struct HozonConfig {
     #[builder(default = "true")]
     pub create_output_directory: bool,
 
+    /// Template for the output directory relative to the target path, overriding
+    /// [`create_output_directory`](HozonConfig::create_output_directory). Missing
+    /// directories are created during generation.
+    ///
+    /// Segments are separated by `/` and may start with `{target}/`, e.g.
+    /// `{target}/{author}/{series}/` or `{format}/{series}`. Placeholders:
+    /// - `{title}`: The ebook title
+    /// - `{series}`: The [`series`](EbookMetadata::series), or the title if there is none
+    /// - `{author}`: The first author
+    /// - `{publisher}`: The publisher
+    /// - `{language}`: The language code
+    /// - `{year}`: The year of the release date
+    /// - `{format}`: The file extension of the output format, e.g. `cbz`
+    ///
+    /// Missing values become [`UNKNOWN_DIRECTORY_VALUE`], and every segment is sanitized
+    /// like file names, so a value containing `/` never creates extra directories.
+    /// Absolute templates and `..` segments are rejected.
+    #[builder(default)]
+    pub output_directory_template: Option<String>,
+
     /// Directory scanning depth for collecting chapters and pages.
     ///
     /// - [`CollectionDepth::Deep`]: Expects `source/chapter/page.jpg` structure
     /// - [`CollectionDepth::Shallow`]: Expects `source/page.jpg` structure (single chapter)
+    /// - [`CollectionDepth::Recursive`]: Every directory holding pages is a chapter, at any depth
+    ///   (e.g. `source/volume/chapter/page.jpg`)
     #[builder(default = "CollectionDepth::Deep")]
     pub collection_depth: CollectionDepth,
 
@@ -106,9 +188,33 @@ pub struct HozonConfig {
     /// Higher values mean stricter requirements for detecting grayscale "cover" pages
     /// when using [`VolumeGroupingStrategy::ImageAnalysis`]. A value of 90 means 90%
     /// of pixels must be grayscale for a page to be considered a volume break.
-    #[builder(default = "75")]
+    #[builder(default = "DEFAULT_IMAGE_ANALYSIS_SENSIBILITY")]
     pub image_analysis_sensibility: u8,
 
+    /// Whether [`VolumeGroupingStrategy::ImageAnalysis`] analyzes the JPEG preview that
+    /// cameras and phones embed in the EXIF data of a cover instead of decoding the full
+    /// image. Cuts analysis time on photographed libraries considerably, but misjudges
+    /// covers edited without updating their preview. Covers without a preview are
+    /// decoded as usual.
+    #[builder(default)]
+    pub image_analysis_thumbnails: bool,
+
+    /// Minimum confidence (0.0-1.0) of a cover found by
+    /// [`VolumeGroupingStrategy::ImageAnalysis`] to start a volume: 0.0 for a cover right at
+    /// the sensibility, 1.0 for one without gray pixels. Raising it keeps borderline pages
+    /// from splitting off spurious one-chapter volumes. Every break is listed with its
+    /// confidence in [`VolumeStructureReport::cover_breaks`]. Defaults to 0.0.
+    #[builder(default)]
+    pub image_analysis_min_confidence: f64,
+
+    /// Optional JSON file caching the cover analysis of
+    /// [`VolumeGroupingStrategy::ImageAnalysis`], keyed by file contents, so structuring
+    /// the same source again (e.g. while tuning the sensibility) doesn't decode every
+    /// cover again. Created if missing; use [`AnalysisCache::clear`] to reset it. See the
+    /// [`analysis_cache`](crate::analysis_cache) module.
+    #[builder(default)]
+    pub analysis_cache: Option<PathBuf>,
+
     // --- Customization for Collection & Structuring Logic ---
     /// Strategy for grouping chapters into logical volumes.
     ///
@@ -116,13 +222,18 @@ pub struct HozonConfig {
     /// - [`VolumeGroupingStrategy::ImageAnalysis`]: Detects volume breaks using cover page analysis
     /// - [`VolumeGroupingStrategy::Manual`]: Uses explicit sizes or single volume
     /// - [`VolumeGroupingStrategy::Flat`]: All pages in one chapter, one volume
+    /// - [`VolumeGroupingStrategy::PerChapter`]: One output file per chapter, named by
+    ///   [`file_name_template`](HozonConfig::file_name_template)
+    /// - [`VolumeGroupingStrategy::Mapping`]: Groups by the chapter-to-volume list in
+    ///   [`volume_mapping_file`](HozonConfig::volume_mapping_file)
     #[builder(default = "VolumeGroupingStrategy::Manual")]
     pub volume_grouping_strategy: VolumeGroupingStrategy,
 
     /// Separator character(s) used between series title and volume number.
     ///
     /// When multiple volumes are generated, the filename format will be:
-    /// `{title}{separator}Volume {number}.{extension}`
+    /// `{title}{separator}Volume {number}.{extension}` (see
+    /// [`volume_label`](HozonConfig::volume_label) for the `Volume {number}` part)
     ///
     /// Examples:
     /// - `" - "` → "My Series - Volume 1.cbz"
@@ -131,6 +242,31 @@ pub struct HozonConfig {
     #[builder(default = "\" - \".to_string()")]
     pub volume_separator: String,
 
+    /// The text naming the volume number in file names and EPUB titles, e.g. `Tome` or
+    /// `巻` for non-English libraries. See [`VolumeLabel`].
+    #[builder(default)]
+    pub volume_label: VolumeLabel,
+
+    /// Template for output file names (without extension), overriding the
+    /// `{title}{separator}{volume label}` default.
+    ///
+    /// Placeholders:
+    /// - `{series}`: The series title
+    /// - `{volume}`: The 1-based volume number
+    /// - `{volume_label}`: The volume number with its [`volume_label`](HozonConfig::volume_label),
+    ///   e.g. `Volume 2`
+    /// - `{chapter}`: The 1-based position of the first chapter of the file among all chapters
+    /// - `{chapter_title}`: The directory name of the first chapter of the file
+    ///
+    /// Numbers can be zero-padded with a width, e.g. `{chapter:03}` → `007`. The template
+    /// is applied even when only one file is generated. `None` uses the default naming,
+    /// or [`DEFAULT_CHAPTER_FILE_NAME_TEMPLATE`] with [`VolumeGroupingStrategy::PerChapter`].
+    ///
+    /// Files whose names collide after sanitizing (ignoring case) get a ` (2)`, ` (3)`, …
+    /// suffix with a warning, whatever the naming.
+    #[builder(default)]
+    pub file_name_template: Option<String>,
+
     /// Custom regex pattern for extracting chapter numbers from directory names.
     ///
     /// If not provided, uses the default pattern that matches common numbering schemes
@@ -149,6 +285,15 @@ pub struct HozonConfig {
     #[builder(default)]
     pub page_name_regex_str: Option<String>,
 
+    /// Name of a [naming preset](crate::presets) supplying the chapter and page regexes for
+    /// a common downloader, e.g. `"tachiyomi"`, `"hakuneko"`, `"fanbox"` or `"calibre"`.
+    ///
+    /// [`chapter_name_regex_str`](HozonConfig::chapter_name_regex_str) and
+    /// [`page_name_regex_str`](HozonConfig::page_name_regex_str) take precedence over the
+    /// regexes of the preset. Unknown names fail the build.
+    #[builder(default)]
+    pub naming_preset: Option<String>,
+
     /// Custom sorting function for chapter directories.
     ///
     /// Provides full control over chapter ordering. If not provided, uses the default
@@ -173,6 +318,34 @@ pub struct HozonConfig {
     pub custom_page_path_sorter:
         Option<Arc<dyn Fn(&PathBuf, &PathBuf) -> Ordering + Sync + Send + 'static>>,
 
+    /// How pages are ordered within each chapter when neither
+    /// [`custom_page_path_sorter`](HozonConfig::custom_page_path_sorter) nor
+    /// [`page_sort`](HozonConfig::page_sort) is set.
+    ///
+    /// - [`SortStrategy::Number`]: By the numbers in the file names (default)
+    /// - [`SortStrategy::ExifDate`]: By EXIF capture time, for camera-generated file names
+    #[builder(default)]
+    pub page_sort_strategy: SortStrategy,
+
+    /// How chapters are ordered when no
+    /// [`custom_chapter_path_sorter`](HozonConfig::custom_chapter_path_sorter) is set.
+    /// Defaults to [`SortSpec::Numeric`]; [`SortSpec::ExifDate`] is not supported for chapters.
+    #[builder(default)]
+    pub chapter_sort: SortSpec,
+
+    /// How pages are ordered when no
+    /// [`custom_page_path_sorter`](HozonConfig::custom_page_path_sorter) is set. Takes
+    /// precedence over [`page_sort_strategy`](HozonConfig::page_sort_strategy).
+    #[builder(default)]
+    pub page_sort: Option<SortSpec>,
+
+    /// Whether [`analyze_source`](HozonConfig::analyze_source) compares the page numbers in
+    /// the file names of each chapter and reports missing ones (pages `1, 2, 4, 5`) as
+    /// [`AnalyzeFinding::MissingPages`], catching incomplete chapters before they end up in
+    /// a volume. Only looks at file names, not the page contents.
+    #[builder(default)]
+    pub check_page_gaps: bool,
+
     /// Explicit volume sizes for [`VolumeGroupingStrategy::Manual`].
     ///
     /// Specifies how many chapters should be in each volume. For example, `vec![10, 8, 5]`
@@ -183,19 +356,418 @@ pub struct HozonConfig {
     #[builder(default)]
     pub volume_sizes_override: Vec<usize>,
 
+    /// The number of volumes the source is known to have, e.g. from the publisher.
+    ///
+    /// When [`VolumeGroupingStrategy::Name`] or [`VolumeGroupingStrategy::ImageAnalysis`]
+    /// detect a different number, image analysis is first repeated at the sensibility
+    /// coming closest, then the smallest neighboring volumes are merged or the largest
+    /// volumes split in half until the count matches (or no volume is left to split).
+    /// Every change is listed in [`VolumeStructureReport::adjustments`].
+    #[builder(default)]
+    pub expected_volume_count: Option<usize>,
+
+    /// CSV or JSON file mapping chapter numbers to volume numbers, required by
+    /// [`VolumeGroupingStrategy::Mapping`]. See [`volume_mapping`](crate::volume_mapping)
+    /// for the format.
+    ///
+    /// Volumes are created in ascending volume number, each holding its chapters in
+    /// collected order. Chapters missing from the mapping are reported as
+    /// [`AnalyzeFinding::UnmappedChapter`] by [`analyze_source`](HozonConfig::analyze_source)
+    /// and fail structuring.
+    #[builder(default)]
+    pub volume_mapping_file: Option<PathBuf>,
+
+    /// Where chapters without a number in their name (omake, extras, oneshots) go, see
+    /// [`ExtraChapters`]. They are reported as [`AnalyzeFinding::UnnumberedChapter`] and
+    /// ordered by name among themselves. Only chapter directories are considered: not
+    /// [`CollectionDepth::Shallow`] sources or [`photo_album`](HozonConfig::photo_album)
+    /// chapters.
+    #[builder(default)]
+    pub extra_chapters: ExtraChapters,
+
+    /// Optional photo album mode for camera photos without meaningful file names.
+    ///
+    /// When set, collected pages are ordered by their EXIF capture time and regrouped into
+    /// chapters per day or event (see [`PhotoAlbum`]) before volumes are structured, and
+    /// chapters are titled by date. Not applied to
+    /// [`convert_from_structured_data`](HozonConfig::convert_from_structured_data). See
+    /// [`HozonConfigBuilder::photo_album_preset`] for a complete photo setup.
+    #[builder(default)]
+    pub photo_album: Option<PhotoAlbum>,
+
+    /// Optional password used to AES-256 encrypt every entry of generated CBZ archives.
+    ///
+    /// Only supported for [`FileFormat::Cbz`]; EPUB reading systems cannot open encrypted
+    /// containers, so combining a password with EPUB output fails the preflight check.
+    /// The password is never serialized or printed in debug output.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "specta", specta(skip))]
+    pub output_password: Option<String>,
+
+    /// Optional password decrypting encrypted entries of CBZ/ZIP archives read as source
+    /// chapters (see [`source_path`](HozonConfig::source_path)) and of CBZ files passed to
+    /// [`extract_to_directory`](HozonConfig::extract_to_directory); unencrypted entries are
+    /// read as they are. Archives that can't be decrypted are skipped and reported as
+    /// [`AnalyzeFinding::UnreadableArchive`]. The password is never serialized or printed
+    /// in debug output.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "specta", specta(skip))]
+    pub input_password: Option<String>,
+
+    /// How CBZ archives are written.
+    ///
+    /// - [`ArchiveBackend::Blocking`]: The `zip` crate on blocking threads (default)
+    /// - [`ArchiveBackend::Async`]: An async zip writer on Tokio's file I/O, which keeps
+    ///   blocking threads free when many volumes are generated concurrently. Requires the
+    ///   `async-zip` feature and can't be combined with
+    ///   [`output_password`](HozonConfig::output_password).
+    ///
+    /// Ignored for EPUB output.
+    #[builder(default)]
+    pub archive_backend: ArchiveBackend,
+
+    /// The modification times stored for entries of the output archive.
+    ///
+    /// - [`EntryTimestamps::WriteTime`]: The time each entry is written (default)
+    /// - [`EntryTimestamps::SourceModified`]: The modification time of the source page,
+    ///   for provenance. Only applies to CBZ output.
+    /// - [`EntryTimestamps::Fixed`]: One timestamp for every entry, so repeated
+    ///   conversions produce identical archives
+    #[builder(default)]
+    pub entry_timestamps: EntryTimestamps,
+
+    /// Unix permission bits stored for every entry of generated CBZ archives (default
+    /// `0o755`). Strict extraction tools and shared servers may expect `0o644` for
+    /// image files. Must not exceed `0o777`; ignored for EPUB output.
+    #[builder(default = "DEFAULT_ENTRY_PERMISSIONS")]
+    pub entry_permissions: u32,
+
+    /// Unix permission bits set on every generated CBZ or EPUB file, regardless of the
+    /// process umask (sidecars and manifests keep the defaults).
+    ///
+    /// By default (`None`) output files are created with the usual `0o666` masked by the
+    /// umask. Must not exceed `0o7777`; ignored on platforms without Unix permissions.
+    #[builder(default)]
+    pub output_permissions: Option<u32>,
+
+    /// Whether [`FileFormat::Directory`] output hard links pages to their source file
+    /// instead of copying them, so runs that only reorganize pages write almost nothing.
+    ///
+    /// Falls back to copying when the output is on another filesystem or links aren't
+    /// supported. Pages changed by [`image_processing`](HozonConfig::image_processing) or
+    /// the [`animated_images`](HozonConfig::animated_images) policy are always written,
+    /// and linking is off with [`output_permissions`](HozonConfig::output_permissions).
+    /// Linked pages share their data with the source, so editing one edits the other.
+    #[builder(default = "false")]
+    pub link_pages: bool,
+
+    /// Whether to embed a [`SourceFingerprint`] into each output.
+    ///
+    /// The fingerprint hashes the source pages of the output file together with the
+    /// Hozon version and a digest of the output-affecting configuration, and is stored in
+    /// the ComicInfo.xml `Notes` (CBZ) or as OPF metadata (EPUB). Computing it requires
+    /// reading every page once more, so it is disabled by default.
+    #[builder(default = "false")]
+    pub embed_source_fingerprint: bool,
+
+    /// How the ComicInfo.xml `Notes` field of CBZ output is rendered.
+    ///
+    /// - [`NotesFormat::Lines`]: `Key: value` lines (default)
+    /// - [`NotesFormat::Json`]: A single JSON object, for tools that parse Notes
+    /// - [`NotesFormat::Template`]: A custom template with `{placeholder}` substitution
+    /// - [`NotesFormat::Omit`]: An empty Notes field
+    ///
+    /// Ignored for EPUB output.
+    #[builder(default)]
+    pub comic_info_notes: NotesFormat,
+
+    /// Whether to record where each chapter starts in the ComicInfo.xml of CBZ output.
+    ///
+    /// Adds a `<Pages>` element bookmarking the first page of every chapter, and the
+    /// chapter number range (e.g. `Chapters 11–20 of 40`, numbered continuously across
+    /// volumes) and start pages to the `Notes` field, so readers and downstream tools can
+    /// map volume pages back to chapters. Ignored for EPUB output.
+    #[builder(default = "false")]
+    pub comic_info_chapter_map: bool,
+
+    /// Whether to link every output to the ones before and after it in reading order.
+    ///
+    /// When a conversion generates more than one file, the title and file name of the
+    /// previous and next volume (or part) are written as `hozon:previous_volume` and
+    /// `hozon:next_volume` entries into the OPF of EPUB output, and into the ComicInfo.xml
+    /// `Notes` of CBZ and CB7 output, so reading apps and tools can chain the volumes.
+    /// Ignored for MOBI and directory output.
+    #[builder(default = "false")]
+    pub volume_links: bool,
+
+    /// Whether to guard the output directory with a lock file while generating.
+    ///
+    /// When enabled, a [`LOCK_FILE_NAME`](crate::lock::LOCK_FILE_NAME) file is created in
+    /// the output directory before any volume is written and removed afterwards. A second
+    /// run targeting the same directory fails with [`Error::TargetLocked`] instead of
+    /// interleaving writes. Locks left behind by crashed runs are detected and replaced.
+    #[builder(default = "false")]
+    pub lock_output_directory: bool,
+
+    /// How to react when the output directory already holds outputs of another series,
+    /// e.g. because two series titles sanitize to the same directory name. Only checked
+    /// for the directories created by [`create_output_directory`](HozonConfig::create_output_directory)
+    /// and [`output_directory_template`](HozonConfig::output_directory_template).
+    ///
+    /// The series of existing outputs is read from their
+    /// [embedded fingerprint](HozonConfig::embed_source_fingerprint) and the
+    /// [series manifest](HozonConfig::series_manifest); outputs without either can't be
+    /// attributed and are left alone.
+    ///
+    /// - [`ForeignOutputPolicy::Warn`]: Log a warning and write the outputs anyway
+    /// - [`ForeignOutputPolicy::Fail`]: Abort with [`Error::ForeignOutputs`]
+    /// - [`ForeignOutputPolicy::Ignore`]: Skip the check
+    #[builder(default)]
+    pub foreign_output_policy: ForeignOutputPolicy,
+
+    /// How to react when a chapter changes between analysis and generation.
+    ///
+    /// Only applies to [`convert_from_source`](HozonConfig::convert_from_source). Before a
+    /// volume is generated, each of its chapter directories is compared (page count, size
+    /// and modification time) against the state recorded during analysis.
+    ///
+    /// - [`SourceChangePolicy::Fail`]: Abort with [`Error::SourceChanged`]
+    /// - [`SourceChangePolicy::Rescan`]: Re-collect the chapter's pages and continue
+    /// - [`SourceChangePolicy::Ignore`]: Skip the check
+    #[builder(default = "SourceChangePolicy::Fail")]
+    pub source_change_policy: SourceChangePolicy,
+
+    /// What to do with [`source_path`](HozonConfig::source_path) after a successful
+    /// conversion, e.g. to keep the inbox of an automated pipeline clean.
+    ///
+    /// Only applies to [`convert_from_source`](HozonConfig::convert_from_source) and
+    /// pipelines started with [`collect`](HozonPipeline::collect). The source is touched
+    /// only after every generated file was read back and all entry checksums verified;
+    /// otherwise the conversion fails with [`Error::InvalidOutput`] and the source stays.
+    ///
+    /// - [`SourceCleanup::KeepSource`]: Leave the source in place (the default)
+    /// - [`SourceCleanup::MoveSourceTo`]: Move the source directory into the given
+    ///   directory, which is created if needed
+    /// - [`SourceCleanup::DeleteSource`]: Delete the source directory, or send it to the
+    ///   trash with [`use_trash`](HozonConfig::use_trash)
+    #[builder(default)]
+    pub source_cleanup: SourceCleanup,
+
+    /// Whether [`SourceCleanup::DeleteSource`] sends the source to the OS trash (recycle
+    /// bin) instead of deleting it permanently, so a misconfigured watch pipeline can be
+    /// undone. Requires the `trash` feature.
+    #[builder(default)]
+    pub use_trash: bool,
+
+    /// How to react when structuring places fewer chapters in volumes than were
+    /// collected, e.g. a chapter without pages under [`VolumeGroupingStrategy::Name`].
+    ///
+    /// - [`LostChapterPolicy::Fail`]: Abort with [`Error::LostChapters`]
+    /// - [`LostChapterPolicy::Warn`]: Log a warning and continue without them
+    #[builder(default)]
+    pub lost_chapter_policy: LostChapterPolicy,
+
+    /// Optional resizing/transcoding applied to every page during generation.
+    ///
+    /// `None` (the default) copies source images into the output unchanged. See
+    /// [`ImageProcessing`] for size limits, target format and the disk spill option that
+    /// keeps memory usage low for very large volumes.
+    #[builder(default)]
+    pub image_processing: Option<ImageProcessing>,
+
+    /// Optional source of alternative text for page images.
+    ///
+    /// `None` (the default) uses each page's title as `alt` text. See [`AltTextSource`]
+    /// for sidecar files and OCR output. Ignored for CBZ output.
+    #[builder(default)]
+    pub alt_text: Option<AltTextSource>,
+
+    /// How animated pages (APNG, animated WebP) are handled.
+    ///
+    /// - [`AnimatedImagePolicy::FirstFrame`]: Keep only the first frame (default)
+    /// - [`AnimatedImagePolicy::Reject`]: Fail the conversion
+    /// - [`AnimatedImagePolicy::PassThrough`]: Keep the animation (EPUB only)
+    #[builder(default)]
+    pub animated_images: AnimatedImagePolicy,
+
+    /// Optional maximum size in bytes of a single EPUB file.
+    ///
+    /// Volumes whose pages add up to more than this are split at chapter boundaries into
+    /// several files named `{volume name}{separator}Part {n}`, since Send-to-Kindle and
+    /// some stores reject large files. Sizes are estimated from the source pages (before
+    /// image processing); a single chapter larger than the limit gets a part of its own.
+    /// Ignored for CBZ output.
+    #[builder(default)]
+    pub epub_max_file_size: Option<u64>,
+
+    /// What to do with page images that occur more than once, e.g. a disclaimer page
+    /// printed in every volume of a batch.
+    ///
+    /// - [`DuplicatePagePolicy::Keep`]: Keep every page (default)
+    /// - [`DuplicatePagePolicy::StripRepeated`]: Remove pages already seen in an earlier volume
+    /// - [`DuplicatePagePolicy::Share`]: Store repeated images once per EPUB file
+    #[builder(default)]
+    pub duplicate_pages: DuplicatePagePolicy,
+
+    /// What to do with gaps in the page numbering of a chapter, found the same way as by
+    /// [`check_page_gaps`](HozonConfig::check_page_gaps).
+    ///
+    /// - [`MissingPagePolicy::Ignore`]: Convert the pages that exist (default)
+    /// - [`MissingPagePolicy::Placeholder`]: Insert a generated "page missing" page for each gap
+    #[builder(default)]
+    pub missing_pages: MissingPagePolicy,
+
+    /// Inserts an intro page with the chapter number and title before the pages of each
+    /// chapter, see [`ChapterIntro`]. EPUB output gets an XHTML page that takes the
+    /// chapter's table of contents entry; archive and MOBI output get a rendered image.
+    /// `None` (the default) adds no intro pages.
+    #[builder(default)]
+    pub chapter_intro: Option<ChapterIntro>,
+
+    /// Side of the spread the content of each volume starts on, counting a custom cover
+    /// where it is a page of its own (CBZ, CB7 and directory output). A blank page is
+    /// inserted in front of the content if it would start on the other side, so
+    /// two-page spreads line up as in print, e.g. `PageSide::Verso` for right-to-left
+    /// volumes whose first page faces the cover. Without a custom cover, readers of CBZ
+    /// and CB7 files may show the blank page as thumbnail. `None` (the default) inserts
+    /// nothing.
+    #[builder(default)]
+    pub first_page_side: Option<PageSide>,
+
+    /// Table of contents options for EPUB output: per-page or per-chapter entries (or
+    /// none at all) and an optional chapter label template. See [`TocOptions`].
+    /// Ignored for CBZ output.
+    #[builder(default)]
+    pub toc: TocOptions,
+
+    /// EPUB specification version of EPUB output. [`EpubVersion::V2`] targets legacy
+    /// readers that can't open EPUB 3 files. Ignored for CBZ output.
+    #[builder(default)]
+    pub epub_version: EpubVersion,
+
+    /// Validate every generated EPUB before it is written and fail instead of producing
+    /// a file that epubcheck (and store ingestion) would reject: container layout,
+    /// well-formed XHTML, unique ids, known metadata properties and resolvable links.
+    /// Also rejects configurations known to produce such files. Ignored for CBZ output.
+    #[builder(default)]
+    pub strict_epub: bool,
+
+    /// How the entries of EPUB output are compressed, per resource type. By default, page
+    /// images (already compressed) are stored and XHTML/CSS deflated, which saves a lot of
+    /// CPU time on large jobs; [`EpubCompression::deflate_all`] gives the smallest files.
+    /// Ignored for CBZ output.
+    #[builder(default)]
+    pub epub_compression: EpubCompression,
+
+    /// Single format every image of EPUB output is re-encoded into, the cover included.
+    /// Some strict reading systems reject books mixing image formats. `None` (the
+    /// default) keeps each page's format; EPUB resources are always named and typed
+    /// after their actual content either way. Takes the place of
+    /// [`ImageProcessing::output_format`], which must be unset or agree. Ignored for
+    /// CBZ output.
+    #[builder(default)]
+    pub epub_image_format: Option<ProcessedImageFormat>,
+
+    /// Where page documents and images are stored inside EPUB output: a directory per
+    /// chapter (the default), one directory per resource type, or flat. Some downstream
+    /// tools mishandle deeply nested paths. Ignored for CBZ output.
+    #[builder(default)]
+    pub epub_layout: EpubPathLayout,
+
+    /// How pages scale on the screen in EPUB output: fitting the width (the default) or
+    /// the height, filling the screen without margins, or at their original size. Only
+    /// applies to EPUB output.
+    #[builder(default)]
+    pub epub_image_fit: ImageFit,
+
+    /// Page background color and margins of EPUB output, see [`PageStyle`]. Only applies
+    /// to EPUB output.
+    #[builder(default)]
+    pub epub_page_style: PageStyle,
+
+    /// Unicode normalization applied to the metadata, chapter names and output file
+    /// names, so sources from macOS (NFD) and other systems produce identical names.
+    /// See [`UnicodeNormalization`].
+    #[builder(default)]
+    pub unicode_normalization: UnicodeNormalization,
+
+    /// Optional cover image files written next to each generated archive, for servers
+    /// and file browsers that read sidecar covers. See [`CoverSidecars`].
+    #[builder(default)]
+    pub cover_sidecars: Option<CoverSidecars>,
+
+    /// Write an OPDS 2.0 feed named `series.json` into the output directory once all
+    /// volumes are generated, describing each file (title, volume number, identifiers,
+    /// path and sidecar covers) so static comic servers can index the directory
+    /// without opening every archive.
+    #[builder(default)]
+    pub series_manifest: bool,
+
+    /// Write a machine-readable `hozon-report.json` into the output directory after each
+    /// conversion, with the analysis and structure reports, the generated files, stage
+    /// timings and warnings. Also written for failed conversions once the output
+    /// directory exists. See the [`report`](crate::report) module.
+    #[builder(default)]
+    pub result_bundle: bool,
+
+    /// Sinks receiving [`PipelineEvent`]s as the conversion progresses, e.g. to
+    /// notify a chat when a volume finishes. See the [`events`](crate::events) module.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "specta", specta(skip))]
+    pub event_sinks: Vec<Arc<dyn EventSink>>,
+
+    /// Optional concurrency limits shared with other conversions in the same process.
+    ///
+    /// When set, volume generation, page reads, and image decoding in both the analysis
+    /// and generation phases draw permits from the shared [`RuntimeLimits`] pools instead
+    /// of this config's own caps, so running several conversions at once keeps total CPU
+    /// and disk pressure bounded.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "specta", specta(skip))]
+    pub runtime_limits: Option<RuntimeLimits>,
+
+    /// The storage the source lives on, which caps concurrent directory scans and page
+    /// reads. [`StorageKind::Auto`] (the default) detects it; set it explicitly when
+    /// detection gets it wrong. Page read caps don't apply when
+    /// [`runtime_limits`](HozonConfig::runtime_limits) is set. See the
+    /// [`storage`](crate::storage) module.
+    #[builder(default)]
+    pub storage_kind: StorageKind,
+
+    /// How hard the conversion may use the machine, e.g. [`ThrottleProfile::Low`] for
+    /// background conversions on a desktop. Fewer volumes, page reads and image workers run
+    /// at once, fewer pages are read ahead, and pages are paced; see
+    /// [`RuntimeLimits::throttled`].
+    ///
+    /// Ignored when [`runtime_limits`](HozonConfig::runtime_limits) is set; throttle the
+    /// shared limits instead.
+    #[builder(default)]
+    pub throttle: ThrottleProfile,
+
     // --- Internal Fields (Auto-Generated, Hidden from Builder) ---
     // Note: These are compiled from the above regex strings in the builder's validate() method.
-    /// Compiled regex from `chapter_name_regex_str`. Internal use only.
-    #[builder(setter(skip), default)]
+    /// Compiled regex from `chapter_name_regex_str` or the naming preset. Internal use only.
+    #[builder(setter(skip), default = "self.compile_name_regex(true)")]
     #[cfg_attr(feature = "serde", serde(skip))]
     #[cfg_attr(feature = "specta", specta(skip))]
     pub(crate) compiled_chapter_name_regex: Option<Regex>,
 
-    /// Compiled regex from `page_name_regex_str`. Internal use only.
-    #[builder(setter(skip), default)]
+    /// Compiled regex from `page_name_regex_str` or the naming preset. Internal use only.
+    #[builder(setter(skip), default = "self.compile_name_regex(false)")]
     #[cfg_attr(feature = "serde", serde(skip))]
     #[cfg_attr(feature = "specta", specta(skip))]
     pub(crate) compiled_page_name_regex: Option<Regex>,
+
+    /// Source archives extracted by this config and its clones, removed once they're all
+    /// dropped. Internal use only.
+    #[builder(setter(skip))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "specta", specta(skip))]
+    pub(crate) archive_extractions: Arc<ArchiveExtractions>,
 }
 impl std::fmt::Debug for HozonConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -206,15 +778,25 @@ impl std::fmt::Debug for HozonConfig {
             .field("output_format", &self.output_format)
             .field("reading_direction", &self.reading_direction)
             .field("create_output_directory", &self.create_output_directory)
+            .field("output_directory_template", &self.output_directory_template)
             .field("collection_depth", &self.collection_depth)
             .field(
                 "image_analysis_sensibility",
                 &self.image_analysis_sensibility,
             )
+            .field("image_analysis_thumbnails", &self.image_analysis_thumbnails)
+            .field(
+                "image_analysis_min_confidence",
+                &self.image_analysis_min_confidence,
+            )
+            .field("analysis_cache", &self.analysis_cache)
             .field("volume_grouping_strategy", &self.volume_grouping_strategy)
             .field("volume_separator", &self.volume_separator)
+            .field("volume_label", &self.volume_label)
+            .field("file_name_template", &self.file_name_template)
             .field("chapter_name_regex_str", &self.chapter_name_regex_str)
             .field("page_name_regex_str", &self.page_name_regex_str)
+            .field("naming_preset", &self.naming_preset)
             .field(
                 "custom_chapter_path_sorter",
                 if self.custom_chapter_path_sorter.is_some() {
@@ -231,7 +813,76 @@ impl std::fmt::Debug for HozonConfig {
                     &"None"
                 },
             )
+            .field("page_sort_strategy", &self.page_sort_strategy)
+            .field("chapter_sort", &self.chapter_sort)
+            .field("page_sort", &self.page_sort)
+            .field("check_page_gaps", &self.check_page_gaps)
             .field("volume_sizes_override", &self.volume_sizes_override)
+            .field("volume_mapping_file", &self.volume_mapping_file)
+            .field("extra_chapters", &self.extra_chapters)
+            .field("expected_volume_count", &self.expected_volume_count)
+            .field("photo_album", &self.photo_album)
+            .field("embed_source_fingerprint", &self.embed_source_fingerprint)
+            .field("comic_info_notes", &self.comic_info_notes)
+            .field("comic_info_chapter_map", &self.comic_info_chapter_map)
+            .field("volume_links", &self.volume_links)
+            .field("lock_output_directory", &self.lock_output_directory)
+            .field("foreign_output_policy", &self.foreign_output_policy)
+            .field("source_change_policy", &self.source_change_policy)
+            .field("source_cleanup", &self.source_cleanup)
+            .field("use_trash", &self.use_trash)
+            .field("lost_chapter_policy", &self.lost_chapter_policy)
+            .field("image_processing", &self.image_processing)
+            .field("alt_text", &self.alt_text)
+            .field("animated_images", &self.animated_images)
+            .field("epub_max_file_size", &self.epub_max_file_size)
+            .field("duplicate_pages", &self.duplicate_pages)
+            .field("missing_pages", &self.missing_pages)
+            .field("chapter_intro", &self.chapter_intro)
+            .field("first_page_side", &self.first_page_side)
+            .field("toc", &self.toc)
+            .field("epub_version", &self.epub_version)
+            .field("strict_epub", &self.strict_epub)
+            .field("epub_compression", &self.epub_compression)
+            .field("epub_image_format", &self.epub_image_format)
+            .field("epub_layout", &self.epub_layout)
+            .field("epub_image_fit", &self.epub_image_fit)
+            .field("epub_page_style", &self.epub_page_style)
+            .field("unicode_normalization", &self.unicode_normalization)
+            .field("cover_sidecars", &self.cover_sidecars)
+            .field("series_manifest", &self.series_manifest)
+            .field("result_bundle", &self.result_bundle)
+            .field("event_sinks", &format!("{} sinks", self.event_sinks.len()))
+            .field("runtime_limits", &self.runtime_limits)
+            .field("storage_kind", &self.storage_kind)
+            .field("throttle", &self.throttle)
+            .field(
+                "output_password",
+                if self.output_password.is_some() {
+                    &"Some(<redacted>)"
+                } else {
+                    &"None"
+                },
+            )
+            .field(
+                "input_password",
+                if self.input_password.is_some() {
+                    &"Some(<redacted>)"
+                } else {
+                    &"None"
+                },
+            )
+            .field("archive_backend", &self.archive_backend)
+            .field("entry_timestamps", &self.entry_timestamps)
+            .field(
+                "entry_permissions",
+                &format!("{:o}", self.entry_permissions),
+            )
+            .field(
+                "output_permissions",
+                &self.output_permissions.map(|mode| format!("{:o}", mode)),
+            )
+            .field("link_pages", &self.link_pages)
             // Skip compiled regexes in debug output
             .finish()
     }
@@ -293,6 +944,146 @@ impl HozonConfig {
     /// # }
     /// ```
     pub fn preflight_check(&self, mode: HozonExecutionMode) -> Result<&Self> {
+        self.validate_settings()?;
+        self.validate_request(mode)?;
+        Ok(self)
+    }
+
+    /// Validates the settings that don't depend on the converted content: everything
+    /// except metadata, paths and the execution mode. Logs [`ignored_options`](Self::ignored_options).
+    pub(crate) fn validate_settings(&self) -> Result<()> {
+        if self.image_analysis_sensibility > 100 {
+            return Err(Error::Other(
+                "Image analysis sensibility must be between 0 and 100.".to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.image_analysis_min_confidence) {
+            return Err(Error::Other(
+                "Image analysis minimum confidence must be between 0.0 and 1.0.".to_string(),
+            ));
+        }
+        if self.output_password.is_some() && self.output_format != FileFormat::Cbz {
+            return Err(Error::Unsupported(
+                "Password-protected output is only supported for CBZ".to_string(),
+            ));
+        }
+        if self.output_format == FileFormat::Cb7 && !cfg!(feature = "cb7") {
+            return Err(Error::Unsupported(
+                "CB7 output requires the `cb7` feature".to_string(),
+            ));
+        }
+        if self.use_trash && !cfg!(feature = "trash") {
+            return Err(Error::Unsupported(
+                "`use_trash` requires the `trash` feature".to_string(),
+            ));
+        }
+        if self.archive_backend == ArchiveBackend::Async {
+            if !cfg!(feature = "async-zip") {
+                return Err(Error::Unsupported(
+                    "The async archive backend requires the `async-zip` feature".to_string(),
+                ));
+            }
+            if self.output_password.is_some() {
+                return Err(Error::Unsupported(
+                    "Password-protected output is not supported by the async archive backend"
+                        .to_string(),
+                ));
+            }
+        }
+        if self.volume_grouping_strategy == VolumeGroupingStrategy::Mapping
+            && self.volume_mapping_file.is_none()
+        {
+            return Err(Error::Other(
+                "The mapping grouping strategy requires a volume mapping file".to_string(),
+            ));
+        }
+        if self.expected_volume_count == Some(0) {
+            return Err(Error::Other(
+                "Expected volume count must be greater than zero".to_string(),
+            ));
+        }
+        if self.entry_permissions > 0o777 {
+            return Err(Error::Other(format!(
+                "Entry permissions {:o} must not exceed 777",
+                self.entry_permissions
+            )));
+        }
+        if let Some(mode) = self.output_permissions
+            && mode > 0o7777
+        {
+            return Err(Error::Other(format!(
+                "Output permissions {:o} must not exceed 7777",
+                mode
+            )));
+        }
+        if self.chapter_sort == SortSpec::ExifDate {
+            return Err(Error::Unsupported(
+                "`SortSpec::ExifDate` only applies to pages, not chapters".to_string(),
+            ));
+        }
+        sort_spec_comparator(&self.chapter_sort)?;
+        sort_spec_comparator(&self.effective_page_sort())?;
+        if let Some(processing) = &self.image_processing {
+            processing.validate()?;
+        }
+        if let Some(sidecars) = &self.cover_sidecars {
+            sidecars.validate()?;
+        }
+        self.epub_compression.validate()?;
+        self.epub_page_style.validate()?;
+        if let (Some(forced), Some(processing)) = (self.epub_image_format, &self.image_processing)
+            && processing
+                .output_format
+                .is_some_and(|format| format != forced)
+            && self.output_format.is_epub()
+        {
+            return Err(Error::Unsupported(format!(
+                "`epub_image_format` {:?} conflicts with the image processing output format {:?}",
+                forced, processing.output_format
+            )));
+        }
+        if self.strict_epub && self.output_format.is_epub() && self.toc.style == TocStyle::None {
+            return Err(Error::Unsupported(
+                "`strict_epub` requires a table of contents; epubcheck rejects empty navigation documents"
+                    .to_string(),
+            ));
+        }
+        if let Some(album) = &self.photo_album {
+            album.validate()?;
+            if self.source_change_policy == SourceChangePolicy::Rescan {
+                return Err(Error::Unsupported(
+                    "`SourceChangePolicy::Rescan` cannot be combined with `photo_album`, whose chapters span directories"
+                        .to_string(),
+                ));
+            }
+        }
+        if let Some(template) = &self.file_name_template {
+            render_file_name_template(template, &FileNameFields::default())?;
+        }
+        if let Some(template) = &self.output_directory_template {
+            render_directory_template(template, &DirectoryFields::default())?;
+        }
+        if self.epub_max_file_size == Some(0) {
+            return Err(Error::Other(
+                "`epub_max_file_size` must be greater than zero".to_string(),
+            ));
+        }
+        if self.animated_images == AnimatedImagePolicy::PassThrough && !self.output_format.is_epub()
+        {
+            return Err(Error::Unsupported(
+                "Passing animated images through is only supported for EPUB".to_string(),
+            ));
+        }
+        // Compiled regexes are already validated during build.
+
+        for ignored in self.ignored_options() {
+            log::warn!("{}", ignored);
+        }
+        Ok(())
+    }
+
+    /// Validates the per-conversion parts of the configuration (metadata and paths) for `mode`.
+    pub(crate) fn validate_request(&self, mode: HozonExecutionMode) -> Result<()> {
         // --- Basic config validation (redundant with Builder::build, but good as a sanity check) ---
         if self.metadata.title.is_empty() {
             return Err(Error::Other("Ebook title is required".to_string()));
@@ -300,12 +1091,6 @@ impl HozonConfig {
         if self.target_path.as_os_str().is_empty() {
             return Err(Error::Other("Target path is required".to_string()));
         }
-        if self.image_analysis_sensibility > 100 {
-            return Err(Error::Other(
-                "Image analysis sensibility must be between 0 and 100.".to_string(),
-            ));
-        }
-        // Compiled regexes are already validated during build.
 
         // --- Mode-specific checks ---
         match mode {
@@ -321,12 +1106,29 @@ impl HozonConfig {
                         self.source_path
                     )));
                 }
-                if !self.source_path.is_dir() {
+                if !self.source_path.is_dir() && !is_source_archive(&self.source_path) {
                     return Err(Error::InvalidPath(
                         self.source_path.clone(),
-                        "Source path is not a directory.".to_string(),
+                        "Source path is not a directory or supported archive.".to_string(),
                     ));
                 }
+                if self.source_cleanup != SourceCleanup::KeepSource {
+                    // Lexical check; the paths may not exist yet
+                    let absolute =
+                        |path: &Path| std::path::absolute(path).unwrap_or(path.to_path_buf());
+                    let source = absolute(&self.source_path);
+                    let mut kept = vec![("target_path", absolute(&self.target_path))];
+                    if let SourceCleanup::MoveSourceTo(directory) = &self.source_cleanup {
+                        kept.push(("the `MoveSourceTo` directory", absolute(directory)));
+                    }
+                    if let Some((name, _)) = kept.iter().find(|(_, path)| path.starts_with(&source))
+                    {
+                        return Err(Error::Unsupported(format!(
+                            "`source_cleanup` can't move or delete a source that contains {}",
+                            name
+                        )));
+                    }
+                }
             }
             HozonExecutionMode::FromCollectedData => {
                 // No specific config checks here related to data itself, as data is passed to `convert_from_collected_data`
@@ -337,17 +1139,262 @@ impl HozonConfig {
             }
         }
 
-        Ok(self)
+        Ok(())
     }
 
-    /// Validates only the source-related parts of the configuration.
-    fn validate_source(&self) -> Result<()> {
-        if self.source_path.as_os_str().is_empty() {
-            return Err(Error::Other(
-                "`source_path` must be set for analysis.".to_string(),
-            ));
-        }
-
+    /// Lists the settings that have no effect for the configured [`FileFormat`] and
+    /// [`VolumeGroupingStrategy`], e.g. `reading_direction` for CBZ output.
+    ///
+    /// Settings left at their defaults are never reported. [`preflight_check`](Self::preflight_check)
+    /// logs every entry as a warning, and the result bundle lists them under
+    /// `ignored_options`.
+    pub fn ignored_options(&self) -> Vec<IgnoredOption> {
+        let capabilities = self.output_format.capabilities();
+        let format = match self.output_format {
+            FileFormat::Cbz => "CBZ",
+            FileFormat::Epub => "EPUB",
+            FileFormat::Mobi => "MOBI",
+            FileFormat::Cb7 => "CB7",
+            FileFormat::Kepub => "KEPUB",
+            FileFormat::Directory => "Directory",
+        };
+        let is_cbz = self.output_format == FileFormat::Cbz;
+        let has_comic_info = matches!(self.output_format, FileFormat::Cbz | FileFormat::Cb7);
+        let is_epub = self.output_format.is_epub();
+
+        let checks = [
+            (
+                "reading_direction",
+                self.reading_direction != Direction::Ltr && !capabilities.supports_rtl,
+                format!("{} output has no reading direction", format),
+            ),
+            (
+                "toc",
+                self.toc != TocOptions::default() && !capabilities.supports_toc,
+                format!("{} output has no table of contents", format),
+            ),
+            (
+                "alt_text",
+                self.alt_text.is_some() && !capabilities.supports_alt_text,
+                format!("{} output has no alternative text for pages", format),
+            ),
+            (
+                "epub_max_file_size",
+                self.epub_max_file_size.is_some() && !capabilities.supports_size_split,
+                format!("{} volumes are never split by size", format),
+            ),
+            (
+                "epub_version",
+                self.epub_version != EpubVersion::default() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "strict_epub",
+                self.strict_epub && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "epub_compression",
+                self.epub_compression != EpubCompression::default() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "epub_image_format",
+                self.epub_image_format.is_some() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "epub_layout",
+                self.epub_layout != EpubPathLayout::default() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "epub_image_fit",
+                self.epub_image_fit != ImageFit::default() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "epub_page_style",
+                self.epub_page_style != PageStyle::default() && !is_epub,
+                "only applies to EPUB output".to_string(),
+            ),
+            (
+                "duplicate_pages",
+                self.duplicate_pages == DuplicatePagePolicy::Share && !is_epub,
+                "`DuplicatePagePolicy::Share` only applies to EPUB output".to_string(),
+            ),
+            (
+                "comic_info_notes",
+                self.comic_info_notes != NotesFormat::default() && !has_comic_info,
+                "only applies to the ComicInfo.xml of CBZ and CB7 output".to_string(),
+            ),
+            (
+                "comic_info_chapter_map",
+                self.comic_info_chapter_map && !has_comic_info,
+                "only applies to the ComicInfo.xml of CBZ and CB7 output".to_string(),
+            ),
+            (
+                "volume_links",
+                self.volume_links && !has_comic_info && !is_epub,
+                format!("{} output has no metadata linking volumes", format),
+            ),
+            (
+                "archive_backend",
+                self.archive_backend != ArchiveBackend::default() && !is_cbz,
+                "only applies to CBZ output".to_string(),
+            ),
+            (
+                "entry_timestamps",
+                self.entry_timestamps == EntryTimestamps::SourceModified && !has_comic_info,
+                format!("{} entries aren't copied from source files", format),
+            ),
+            (
+                "entry_permissions",
+                self.entry_permissions != DEFAULT_ENTRY_PERMISSIONS && !is_cbz,
+                "only applies to CBZ output".to_string(),
+            ),
+            (
+                "link_pages",
+                self.link_pages && self.output_format != FileFormat::Directory,
+                "only applies to Directory output".to_string(),
+            ),
+            (
+                "link_pages",
+                self.link_pages && self.image_processing.is_some(),
+                "processed pages are always written".to_string(),
+            ),
+            (
+                "link_pages",
+                self.link_pages && self.output_permissions.is_some(),
+                "linked pages would change the permissions of the source files".to_string(),
+            ),
+            (
+                "embed_source_fingerprint",
+                self.embed_source_fingerprint && self.output_format == FileFormat::Directory,
+                "directory output has no metadata to hold the fingerprint".to_string(),
+            ),
+            (
+                "output_permissions",
+                self.output_permissions.is_some() && !cfg!(unix),
+                "file permissions only exist on Unix".to_string(),
+            ),
+            (
+                "image_analysis_sensibility",
+                self.image_analysis_sensibility != DEFAULT_IMAGE_ANALYSIS_SENSIBILITY
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::ImageAnalysis,
+                "only used by `VolumeGroupingStrategy::ImageAnalysis`".to_string(),
+            ),
+            (
+                "image_analysis_thumbnails",
+                self.image_analysis_thumbnails
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::ImageAnalysis,
+                "only used by `VolumeGroupingStrategy::ImageAnalysis`".to_string(),
+            ),
+            (
+                "image_analysis_min_confidence",
+                self.image_analysis_min_confidence > 0.0
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::ImageAnalysis,
+                "only used by `VolumeGroupingStrategy::ImageAnalysis`".to_string(),
+            ),
+            (
+                "analysis_cache",
+                self.analysis_cache.is_some()
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::ImageAnalysis,
+                "only used by `VolumeGroupingStrategy::ImageAnalysis`".to_string(),
+            ),
+            (
+                "expected_volume_count",
+                self.expected_volume_count.is_some()
+                    && !matches!(
+                        self.volume_grouping_strategy,
+                        VolumeGroupingStrategy::Name | VolumeGroupingStrategy::ImageAnalysis
+                    ),
+                "only used by `VolumeGroupingStrategy::Name` and `ImageAnalysis`".to_string(),
+            ),
+            (
+                "volume_sizes_override",
+                !self.volume_sizes_override.is_empty()
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::Manual,
+                "only used by `VolumeGroupingStrategy::Manual`".to_string(),
+            ),
+            (
+                "volume_mapping_file",
+                self.volume_mapping_file.is_some()
+                    && self.volume_grouping_strategy != VolumeGroupingStrategy::Mapping,
+                "only used by `VolumeGroupingStrategy::Mapping`".to_string(),
+            ),
+            (
+                "throttle",
+                self.throttle != ThrottleProfile::Normal && self.runtime_limits.is_some(),
+                "the shared `runtime_limits` decide; use `RuntimeLimits::throttled`".to_string(),
+            ),
+            (
+                "use_trash",
+                self.use_trash && self.source_cleanup != SourceCleanup::DeleteSource,
+                "only used by `SourceCleanup::DeleteSource`".to_string(),
+            ),
+            (
+                "create_output_directory",
+                !self.create_output_directory && self.output_directory_template.is_some(),
+                "`output_directory_template` decides the output directory".to_string(),
+            ),
+            (
+                "foreign_output_policy",
+                self.foreign_output_policy != ForeignOutputPolicy::Warn
+                    && !self.create_output_directory
+                    && self.output_directory_template.is_none(),
+                "only checked for created output directories".to_string(),
+            ),
+            (
+                "extra_chapters",
+                self.extra_chapters != ExtraChapters::Sorted
+                    && (self.collection_depth == CollectionDepth::Shallow
+                        || self.photo_album.is_some()),
+                "only applies to chapter directories".to_string(),
+            ),
+        ];
+
+        let metadata_fields = [
+            ("genre", self.metadata.genre.is_some()),
+            ("web", self.metadata.web.is_some()),
+        ];
+        let unwritten_metadata = metadata_fields.into_iter().filter(|(field, set)| {
+            *set && !capabilities
+                .supports_metadata_fields
+                .iter()
+                .any(|supported| supported == field)
+        });
+
+        checks
+            .into_iter()
+            .filter(|(_, ignored, _)| *ignored)
+            .map(|(option, _, reason)| IgnoredOption {
+                option: option.to_string(),
+                reason,
+            })
+            .chain(unwritten_metadata.map(|(field, _)| IgnoredOption {
+                option: format!("metadata.{}", field),
+                reason: format!("{} output has no field for it", format),
+            }))
+            .collect()
+    }
+
+    /// The page ordering in effect: [`page_sort`](HozonConfig::page_sort) if set, otherwise
+    /// [`page_sort_strategy`](HozonConfig::page_sort_strategy).
+    pub(crate) fn effective_page_sort(&self) -> SortSpec {
+        self.page_sort
+            .clone()
+            .unwrap_or_else(|| self.page_sort_strategy.into())
+    }
+
+    /// Validates only the source-related parts of the configuration.
+    fn validate_source(&self) -> Result<()> {
+        if self.source_path.as_os_str().is_empty() {
+            return Err(Error::Other(
+                "`source_path` must be set for analysis.".to_string(),
+            ));
+        }
+
         // Validate path format and characters
         crate::path_utils::validate_path(&self.source_path)?;
 
@@ -357,10 +1404,10 @@ impl HozonConfig {
                 self.source_path
             )));
         }
-        if !self.source_path.is_dir() {
+        if !self.source_path.is_dir() && !is_source_archive(&self.source_path) {
             return Err(Error::InvalidPath(
                 self.source_path.clone(),
-                "Source path is not a directory.".to_string(),
+                "Source path is not a directory or supported archive.".to_string(),
             ));
         }
 
@@ -428,6 +1475,46 @@ impl HozonConfig {
         Self::perform_structuring(self, collected_data).await
     }
 
+    /// Performs the analysis and structuring steps on the source directory, without
+    /// generating any files.
+    ///
+    /// This is [`analyze_source`](HozonConfig::analyze_source) followed by
+    /// [`structure_from_collected_data`](HozonConfig::structure_from_collected_data), and
+    /// yields the volumes [`convert_from_source`](HozonConfig::convert_from_source) would
+    /// generate. Use it to preview the volume layout of a directory source.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(StructuredContent)` - The volumes, the structuring report and the applied strategy
+    /// * `Err(Error)` - Source validation, analysis or structuring failed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// let config = HozonConfig::builder()
+    ///     .metadata(EbookMetadata::default_with_title("Preview".to_string()))
+    ///     .source_path(PathBuf::from("./manga_source"))
+    ///     .target_path(PathBuf::from("./output"))
+    ///     .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+    ///     .build()?;
+    ///
+    /// let structured = config.structure_from_source().await?;
+    /// for (i, volume) in structured.volumes_with_chapters_and_pages.iter().enumerate() {
+    ///     println!("Volume {}: {} chapters", i + 1, volume.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn structure_from_source(&self) -> Result<StructuredContent> {
+        self.preflight_check(HozonExecutionMode::FromSource)?;
+        let collected = self.analyze_source().await?;
+        Self::perform_structuring(self, collected.chapters_with_pages).await
+    }
+
     /// Analyzes the source directory structure and content without performing conversion.
     ///
     /// This method performs the initial analysis phase of the conversion pipeline,
@@ -486,9 +1573,444 @@ impl HozonConfig {
             self.compiled_chapter_name_regex.as_ref(),
             self.compiled_page_name_regex.as_ref(),
             self.image_analysis_sensibility,
-        );
+        )
+        .with_chapter_sort(self.chapter_sort.clone())
+        .with_page_sort(self.effective_page_sort())
+        .with_storage_kind(self.storage_kind)
+        .with_embedded_thumbnails(self.image_analysis_thumbnails)
+        .with_page_gap_check(self.check_page_gaps)
+        .with_archive_extractions(self.archive_extractions.clone())
+        .with_archive_password(self.input_password.clone());
+        let collector = match self.analysis_limits() {
+            Some(limits) => collector.with_runtime_limits(&limits),
+            None => collector,
+        };
+
+        let mut collected = collector.analyze_source_content().await?;
+        if let Some(mapping) = self.volume_mapping().await? {
+            // Extras are placed by `extra_chapters` rather than the mapping
+            let chapters: Vec<Vec<PathBuf>> = collected
+                .chapters_with_pages
+                .iter()
+                .filter(|pages| {
+                    self.extra_chapters == ExtraChapters::Sorted || !self.is_extra_chapter(pages)
+                })
+                .cloned()
+                .collect();
+            let unmapped =
+                mapping.unmapped_chapters(&chapters, self.compiled_chapter_name_regex.as_ref());
+            collected.report.findings.extend(unmapped.into_iter().map(
+                |(chapter_path, chapter_number)| AnalyzeFinding::UnmappedChapter {
+                    chapter_path,
+                    chapter_number,
+                },
+            ));
+        }
+        Ok(collected)
+    }
+
+    /// Loads [`volume_mapping_file`](HozonConfig::volume_mapping_file) if the
+    /// [`VolumeGroupingStrategy::Mapping`] strategy uses it.
+    async fn volume_mapping(&self) -> Result<Option<VolumeMapping>> {
+        match &self.volume_mapping_file {
+            Some(path) if self.volume_grouping_strategy == VolumeGroupingStrategy::Mapping => {
+                let path = path.clone();
+                let mapping =
+                    tokio::task::spawn_blocking(move || VolumeMapping::load(&path)).await??;
+                Ok(Some(mapping))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The limits cover analysis draws from: the shared [`runtime_limits`](HozonConfig::runtime_limits),
+    /// or the default ones scaled by [`throttle`](HozonConfig::throttle) unless it is
+    /// [`ThrottleProfile::Normal`].
+    fn analysis_limits(&self) -> Option<RuntimeLimits> {
+        match (&self.runtime_limits, self.throttle) {
+            (Some(limits), _) => Some(limits.clone()),
+            (None, ThrottleProfile::Normal) => None,
+            (None, throttle) => Some(RuntimeLimits::default().throttled(throttle)),
+        }
+    }
+
+    /// Returns the directory generated files are written to.
+    ///
+    /// This is the rendered [`output_directory_template`](HozonConfig::output_directory_template)
+    /// below `target_path` if one is set, `target_path/<sanitized title>` when
+    /// [`create_output_directory`](HozonConfig::create_output_directory) is set, and
+    /// `target_path` otherwise. The directory is not created by this method.
+    pub fn output_directory(&self) -> PathBuf {
+        // Templates are validated when building, so rendering only fails for configs
+        // modified afterwards; those fall back to the title directory
+        let templated = self
+            .output_directory_template
+            .as_ref()
+            .and_then(|template| {
+                render_directory_template(template, &self.directory_fields()).ok()
+            });
+        if let Some(relative) = templated {
+            self.target_path.join(relative)
+        } else if self.create_output_directory || self.output_directory_template.is_some() {
+            self.target_path.join(sanitize_filename(
+                &self.unicode_normalization.apply(&self.metadata.title),
+            ))
+        } else {
+            self.target_path.clone()
+        }
+    }
+
+    /// The values substituted into the [`output_directory_template`](HozonConfig::output_directory_template).
+    fn directory_fields(&self) -> DirectoryFields {
+        let text = |value: Option<&String>| match value {
+            Some(value) if !value.trim().is_empty() => self.unicode_normalization.apply(value),
+            _ => UNKNOWN_DIRECTORY_VALUE.to_string(),
+        };
+        let metadata = &self.metadata;
+        DirectoryFields {
+            title: text(Some(&metadata.title)),
+            series: text(metadata.series.as_ref().or(Some(&metadata.title))),
+            author: text(metadata.authors.first()),
+            publisher: text(metadata.publisher.as_ref()),
+            language: text(Some(&metadata.language)),
+            format: self.output_format.extension().to_string(),
+            year: metadata.release_date.map(|date| date.year() as usize),
+        }
+    }
+
+    /// Returns the base file name (without extension) of a generated volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `volume_number` - The 1-based volume number
+    /// * `total_volumes` - The total number of volumes generated in the task
+    pub fn volume_file_name_base(&self, volume_number: usize, total_volumes: usize) -> String {
+        let label = self
+            .volume_label
+            .format(volume_number, &self.metadata.language);
+        self.labeled_file_name_base(&label, total_volumes)
+    }
+
+    /// Returns the base file name of a volume labeled `label`.
+    fn labeled_file_name_base(&self, label: &str, total_volumes: usize) -> String {
+        let title = self.unicode_normalization.apply(&self.metadata.title);
+        if total_volumes > 1 {
+            sanitize_filename(&format!(
+                "{}{}{}",
+                title,
+                self.volume_separator,
+                self.unicode_normalization.apply(label)
+            ))
+        } else {
+            sanitize_filename(&title)
+        }
+    }
+
+    /// Whether `pages` belong to a chapter directory without a number in its name, which
+    /// [`extra_chapters`](HozonConfig::extra_chapters) applies to.
+    fn is_extra_chapter(&self, pages: &[PathBuf]) -> bool {
+        if self.collection_depth == CollectionDepth::Shallow || self.photo_album.is_some() {
+            return false;
+        }
+        pages
+            .first()
+            .and_then(|page| page.parent())
+            .is_some_and(|chapter_dir| {
+                chapter_dir != self.source_path
+                    && chapter_number(chapter_dir, self.compiled_chapter_name_regex.as_ref())
+                        .is_none()
+            })
+    }
+
+    /// Whether `chapters` form the volume collecting extra chapters with
+    /// [`ExtraChapters::SeparateVolume`].
+    pub(crate) fn is_extras_volume(&self, chapters: &[Vec<PathBuf>]) -> bool {
+        self.extra_chapters == ExtraChapters::SeparateVolume
+            && !chapters.is_empty()
+            && chapters.iter().all(|pages| self.is_extra_chapter(pages))
+    }
+
+    /// Splits the extra chapters off `chapters` unless they stay where they were sorted,
+    /// ordering them by name.
+    fn separate_extra_chapters(
+        &self,
+        chapters: Vec<Vec<PathBuf>>,
+    ) -> (Vec<Vec<PathBuf>>, Vec<Vec<PathBuf>>) {
+        if self.extra_chapters == ExtraChapters::Sorted {
+            return (chapters, Vec::new());
+        }
+        let (mut extras, numbered): (Vec<_>, Vec<_>) = chapters
+            .into_iter()
+            .partition(|pages| self.is_extra_chapter(pages));
+        extras.sort_by_cached_key(|pages| {
+            pages
+                .first()
+                .and_then(|page| page.parent())
+                .map(get_file_name_lossy)
+                .unwrap_or_default()
+        });
+        (numbered, extras)
+    }
+
+    /// Returns the file name base (without extension) of one part of a volume that was
+    /// split because of [`epub_max_file_size`](HozonConfig::epub_max_file_size).
+    pub fn part_file_name_base(
+        &self,
+        volume_number: usize,
+        total_volumes: usize,
+        part_number: usize,
+    ) -> String {
+        self.part_name(
+            &self.volume_file_name_base(volume_number, total_volumes),
+            part_number,
+        )
+    }
+
+    fn part_name(&self, file_name_base: &str, part_number: usize) -> String {
+        sanitize_filename(&format!(
+            "{}{}Part {}",
+            file_name_base, self.volume_separator, part_number
+        ))
+    }
+
+    /// Returns the title of a chapter: its date in [`photo_album`](HozonConfig::photo_album)
+    /// mode, else the name of the directory of its first page. Blocking in photo album mode.
+    fn chapter_title(&self, pages: &[PathBuf]) -> Option<String> {
+        let title = match self
+            .photo_album
+            .as_ref()
+            .and_then(|album| album.chapter_title(pages))
+        {
+            Some(title) => title,
+            None => get_file_name_safe(pages.first()?.parent()?).ok()?,
+        };
+        Some(self.unicode_normalization.apply(&title))
+    }
+
+    /// Returns the file name base of a volume, applying the
+    /// [`file_name_template`](HozonConfig::file_name_template) if one is in effect.
+    fn planned_file_name_base(
+        &self,
+        volume_number: usize,
+        total_volumes: usize,
+        chapter_number: usize,
+        chapters: &[Vec<PathBuf>],
+    ) -> Result<String> {
+        let template = match (&self.file_name_template, self.volume_grouping_strategy) {
+            (Some(template), _) => template.as_str(),
+            (None, VolumeGroupingStrategy::PerChapter) => DEFAULT_CHAPTER_FILE_NAME_TEMPLATE,
+            (None, _) if self.is_extras_volume(chapters) => {
+                return Ok(self.labeled_file_name_base(EXTRAS_VOLUME_LABEL, total_volumes));
+            }
+            (None, _) => return Ok(self.volume_file_name_base(volume_number, total_volumes)),
+        };
+        let chapter_title = chapters
+            .first()
+            .and_then(|pages| self.chapter_title(pages))
+            .unwrap_or_default();
+        let fields = FileNameFields {
+            series: self.unicode_normalization.apply(&self.metadata.title),
+            volume: volume_number,
+            volume_label: if self.is_extras_volume(chapters) {
+                EXTRAS_VOLUME_LABEL.to_string()
+            } else {
+                self.unicode_normalization.apply(
+                    &self
+                        .volume_label
+                        .format(volume_number, &self.metadata.language),
+                )
+            },
+            chapter: chapter_number,
+            chapter_title,
+        };
+        Ok(sanitize_filename(&render_file_name_template(
+            template, &fields,
+        )?))
+    }
+
+    /// Compares existing output files against the current source content.
+    ///
+    /// Runs analysis and structuring (without generating anything), then inspects the
+    /// [`output_directory`](HozonConfig::output_directory) and classifies each expected
+    /// volume as up-to-date, stale, missing, or unverifiable using the fingerprint embedded
+    /// by [`embed_source_fingerprint`](HozonConfig::embed_source_fingerprint). Files of the
+    /// configured output format that no current volume maps to are reported as orphaned.
+    ///
+    /// # Arguments
+    ///
+    /// * `cover_options` - The cover options that would be used for conversion, since
+    ///   custom covers are part of the fingerprint
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OutputCheckReport)` - Status of every expected output plus orphaned files
+    /// * `Err(Error)` - Source validation, analysis, or structuring failed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// let config = HozonConfig::builder()
+    ///     .metadata(EbookMetadata::default_with_title("My Series".to_string()))
+    ///     .source_path(PathBuf::from("./source"))
+    ///     .target_path(PathBuf::from("./output"))
+    ///     .embed_source_fingerprint(true)
+    ///     .build()?;
+    ///
+    /// let report = config.check_outputs(&CoverOptions::None).await?;
+    /// if !report.is_up_to_date() {
+    ///     config.convert_from_source(CoverOptions::None).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn check_outputs(&self, cover_options: &CoverOptions) -> Result<OutputCheckReport> {
+        let structured = self.structure_from_source().await?;
+
+        let output_dir = self.output_directory();
+        let planned = self
+            .plan_outputs(
+                structured.volumes_with_chapters_and_pages,
+                &WarningLog::default(),
+            )
+            .await?;
+
+        let mut outputs = Vec::with_capacity(planned.len());
+        let mut expected_paths = Vec::with_capacity(planned.len());
+
+        for PlannedOutput {
+            volume_index: i,
+            part_number,
+            file_name_base,
+            chapters: volume,
+            ..
+        } in &planned
+        {
+            let volume_number = i + 1;
+            let path = output_dir.join(self.output_format.output_name(file_name_base));
+            expected_paths.push(path.clone());
+
+            let state = if !path.exists() {
+                OutputState::Missing
+            } else {
+                let format = self.output_format;
+                let password = self.output_password.clone();
+                let existing_path = path.clone();
+                let embedded = tokio::task::spawn_blocking(move || {
+                    read_embedded_fingerprint(&existing_path, format, password.as_deref())
+                })
+                .await?;
+
+                match embedded {
+                    Ok(Some(embedded)) => {
+                        let cover = cover_options.cover_for_volume(*i);
+                        let current =
+                            SourceFingerprint::compute(self, volume, cover.as_deref()).await?;
+                        if embedded.is_up_to_date_with(&current) {
+                            OutputState::UpToDate
+                        } else {
+                            OutputState::Stale
+                        }
+                    }
+                    Ok(None) => OutputState::Unverifiable,
+                    Err(e) => {
+                        log::warn!("Could not read fingerprint from {:?}: {}", path, e);
+                        OutputState::Unverifiable
+                    }
+                }
+            };
+
+            outputs.push(OutputStatus {
+                volume_number,
+                part_number: *part_number,
+                path,
+                state,
+            });
+        }
+
+        let mut orphaned = Vec::new();
+        if output_dir.is_dir() {
+            let mut entries = fs::read_dir(&output_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.is_file()
+                    && self.output_format.matches_path(&path)
+                    && !expected_paths.contains(&path)
+                {
+                    orphaned.push(path);
+                }
+            }
+            orphaned.sort();
+        }
+
+        Ok(OutputCheckReport { outputs, orphaned })
+    }
+
+    /// Rewrites the metadata of existing output files in `dir` from the current
+    /// [`metadata`](HozonConfig::metadata), without regenerating them.
+    ///
+    /// Only the ComicInfo.xml (CBZ) or OPF package document (EPUB) of every file of the
+    /// configured output format is replaced; pages are copied over without being decoded
+    /// or recompressed, which makes fixing a typo across a whole series a matter of
+    /// seconds. Structural information is kept: volume and part numbers, page counts,
+    /// bookmarks, the table of contents and the chapters recorded in the notes. Custom
+    /// `Notes` templates are kept as they are, and so is the release date if the metadata
+    /// has none. An embedded [source fingerprint](HozonConfig::embed_source_fingerprint)
+    /// is updated to the current configuration, so [`check_outputs`](HozonConfig::check_outputs)
+    /// reports retagged files as up-to-date.
+    ///
+    /// File names are not changed, even if they were derived from a title that changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory holding the output files, usually the
+    ///   [`output_directory`](HozonConfig::output_directory)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<PathBuf>)` - The rewritten files, sorted by path
+    /// * `Err(Error)` - A file couldn't be read or written; files before it are rewritten
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// let mut metadata = EbookMetadata::default_with_title("My Series".to_string());
+    /// metadata.series = Some("My Series (fixed)".to_string());
+    /// let config = HozonConfig::builder()
+    ///     .metadata(metadata)
+    ///     .target_path(PathBuf::from("./output"))
+    ///     .build()?;
+    ///
+    /// let retagged = config.retag_outputs(&config.output_directory()).await?;
+    /// println!("Retagged {} files", retagged.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn retag_outputs(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.is_file() && self.output_format.matches_path(&path) {
+                files.push(path);
+            }
+        }
+        files.sort();
 
-        collector.analyze_source_content().await
+        let config = Arc::new(self.clone());
+        for path in &files {
+            let config = Arc::clone(&config);
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || retag_output(&path, &config)).await??;
+        }
+        Ok(files)
     }
 
     // --- Core conversion entry points ---
@@ -530,12 +2052,15 @@ impl HozonConfig {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn convert_from_source(self, cover_options: CoverOptions) -> Result<()> {
-        self.preflight_check(HozonExecutionMode::FromSource)?;
-        let collected_content = self.analyze_source().await?;
-
-        self.convert_from_collected_data(collected_content.chapters_with_pages, cover_options)
-            .await
+    pub async fn convert_from_source(&self, cover_options: CoverOptions) -> Result<()> {
+        HozonPipeline::new(self.clone())
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(cover_options)
+            .await?;
+        Ok(())
     }
 
     /// Starts the conversion pipeline from pre-collected chapter/page data.
@@ -580,19 +2105,138 @@ impl HozonConfig {
     /// # }
     /// ```
     pub async fn convert_from_collected_data(
-        self,
+        &self,
         collected_data: Vec<Vec<PathBuf>>,
         cover_options: CoverOptions,
     ) -> Result<()> {
-        self.preflight_check(HozonExecutionMode::FromCollectedData)?;
-        let structured_content = Self::perform_structuring(&self, collected_data).await?;
+        HozonPipeline::new(self.clone())
+            .from_chapters(collected_data)?
+            .structure()
+            .await?
+            .generate(cover_options)
+            .await?;
+        Ok(())
+    }
 
-        Self::perform_generation(
-            &self,
-            structured_content.volumes_with_chapters_and_pages,
-            &cover_options, // Pass CoverOptions by reference
-        )
-        .await
+    /// Unpacks an existing CBZ or EPUB file, generated by Hozon or not, back into a
+    /// directory of chapter directories holding the pages.
+    ///
+    /// The ebook is extracted into `target_path/<file name>/`, which must not exist yet.
+    /// Chapters are numbered in reading order and named after their title (`001 Chapter 1/`),
+    /// pages are numbered within their chapter (`001.jpg`), and a separate cover is written
+    /// as `cover.<ext>`. The metadata document is copied along (`ComicInfo.xml` or
+    /// `metadata.opf`) and returned parsed, with the volume number rendered by the
+    /// [`volume_label`](HozonConfig::volume_label) removed from EPUB titles, so the result can be fed into
+    /// [`convert_from_collected_data`](HozonConfig::convert_from_collected_data) again.
+    /// Encrypted CBZ entries are decrypted with the
+    /// [`input_password`](HozonConfig::input_password), or without one with the
+    /// [`output_password`](HozonConfig::output_password), so files this configuration
+    /// wrote can be read back.
+    ///
+    /// Chapters come from the page bookmarks or folders of CBZ files and from the table of
+    /// contents of EPUB files; ebooks without either are extracted as one chapter.
+    ///
+    /// # Arguments
+    ///
+    /// * `ebook` - Path to the `.cbz`, `.zip`, `.epub` or `.kepub.epub` file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ExtractedEbook)` - The written directory, metadata, cover and pages
+    /// * `Err(Error)` - The ebook can't be read or holds no pages, the directory exists,
+    ///   or writing failed; a partially written directory is removed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::{Path, PathBuf};
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// let config = HozonConfig::builder()
+    ///     .target_path(PathBuf::from("./extracted"))
+    ///     .build()?;
+    ///
+    /// let extracted = config
+    ///     .extract_to_directory(Path::new("./output/My Comic/My Comic - Volume 1.epub"))
+    ///     .await?;
+    /// println!(
+    ///     "{}: {} chapters in {:?}",
+    ///     extracted.metadata.title,
+    ///     extracted.chapters.len(),
+    ///     extracted.directory
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn extract_to_directory(&self, ebook: &Path) -> Result<ExtractedEbook> {
+        let name = get_file_name_lossy(ebook);
+        let base = name
+            .strip_suffix(".kepub.epub")
+            .or_else(|| name.rsplit_once('.').map(|(base, _)| base))
+            .unwrap_or(&name);
+        let directory = self.target_path.join(sanitize_filename(base));
+        let ebook = ebook.to_path_buf();
+        let password = self
+            .input_password
+            .clone()
+            .or_else(|| self.output_password.clone());
+        let volume_label = self.volume_label.clone();
+        tokio::task::spawn_blocking(move || {
+            crate::extract::extract(&ebook, &directory, password.as_deref(), &volume_label)
+        })
+        .await?
+    }
+
+    /// Converts scanned PDFs, each PDF being a chapter titled after its file name.
+    ///
+    /// The page images are extracted into the system temp dir (see the [`pdf`](crate::pdf)
+    /// module) and converted like [`convert_from_collected_data`](HozonConfig::convert_from_collected_data);
+    /// they are removed once the conversion is done. Requires the `pdf` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `pdf_paths` - The PDFs, in reading order
+    /// * `cover_options` - Specifies how to handle cover images (see [`CoverOptions`] for details)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Conversion completed successfully
+    /// * `Err(Error)` - A PDF can't be read, or the conversion failed
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// let config = HozonConfig::builder()
+    ///     .metadata(EbookMetadata::default_with_title("My Comic".to_string()))
+    ///     .target_path(PathBuf::from("./output"))
+    ///     .build()?;
+    ///
+    /// config
+    ///     .convert_from_pdfs(&[PathBuf::from("./My Comic 01.pdf")], CoverOptions::None)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn convert_from_pdfs(
+        &self,
+        pdf_paths: &[PathBuf],
+        cover_options: CoverOptions,
+    ) -> Result<()> {
+        let mut extracted = Vec::with_capacity(pdf_paths.len());
+        for pdf in pdf_paths {
+            extracted.push(PdfPages::extract(pdf).await?);
+        }
+        let chapters = extracted
+            .iter()
+            .map(|pages| pages.pages().to_vec())
+            .collect();
+        self.convert_from_collected_data(chapters, cover_options)
+            .await
     }
 
     /// Executes only the generation step from pre-structured volume data.
@@ -643,22 +2287,143 @@ impl HozonConfig {
     /// # }
     /// ```
     pub async fn convert_from_structured_data(
-        self,
+        &self,
         structured_data: Vec<Vec<Vec<PathBuf>>>,
         cover_options: CoverOptions,
     ) -> Result<()> {
-        self.preflight_check(HozonExecutionMode::FromStructuredData)?;
-        Self::perform_generation(&self, structured_data, &cover_options).await
+        HozonPipeline::new(self.clone())
+            .from_volumes(structured_data)?
+            .generate(cover_options)
+            .await?;
+        Ok(())
     }
 
-    // --- Private helper methods for pipeline steps ---
-
-    /// Internal method to perform the volume structuring logic.
+    /// Runs [`convert_from_source`](Self::convert_from_source) in a background task,
+    /// returning a handle to pause, resume or shut down the conversion. Errors are
+    /// returned by [`ConversionHandle::join`].
     ///
-    /// This method takes collected chapters and groups them into logical volumes
-    /// based on the configured [`VolumeGroupingStrategy`]. It handles all the
-    /// complex logic for different grouping strategies including name-based grouping,
-    /// image analysis, manual grouping, and flat organization.
+    /// Must be called within a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// # #[tokio::main]
+    /// # async fn main() -> hozon::error::Result<()> {
+    /// # let config = HozonConfig::builder()
+    /// #     .metadata(EbookMetadata::default_with_title("My Comic".to_string()))
+    /// #     .source_path(PathBuf::from("./source"))
+    /// #     .target_path(PathBuf::from("./output"))
+    /// #     .build()?;
+    /// let handle = config.spawn_from_source(CoverOptions::None);
+    ///
+    /// // The user started something latency-sensitive
+    /// handle.pause();
+    /// // ...and is done with it
+    /// handle.resume();
+    ///
+    /// let report = handle.join().await?;
+    /// println!("Wrote {} files", report.files.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_from_source(&self, cover_options: CoverOptions) -> ConversionHandle {
+        HozonPipeline::new(self.clone()).spawn(cover_options)
+    }
+
+    /// Runs [`convert_from_collected_data`](Self::convert_from_collected_data) in a
+    /// background task, see [`spawn_from_source`](Self::spawn_from_source).
+    pub fn spawn_from_collected_data(
+        &self,
+        collected_data: Vec<Vec<PathBuf>>,
+        cover_options: CoverOptions,
+    ) -> ConversionHandle {
+        HozonPipeline::new(self.clone()).spawn_stages(|pipeline| async move {
+            pipeline
+                .from_chapters(collected_data)?
+                .structure()
+                .await?
+                .generate(cover_options)
+                .await
+        })
+    }
+
+    /// Runs [`convert_from_structured_data`](Self::convert_from_structured_data) in a
+    /// background task, see [`spawn_from_source`](Self::spawn_from_source).
+    pub fn spawn_from_structured_data(
+        &self,
+        structured_data: Vec<Vec<Vec<PathBuf>>>,
+        cover_options: CoverOptions,
+    ) -> ConversionHandle {
+        HozonPipeline::new(self.clone()).spawn_stages(|pipeline| async move {
+            pipeline
+                .from_volumes(structured_data)?
+                .generate(cover_options)
+                .await
+        })
+    }
+
+    // --- Private helper methods for pipeline steps ---
+
+    /// Creates the collector structuring the source: storage, CPU limits, embedded
+    /// thumbnails, the minimum break confidence, and the analysis cache applied.
+    pub(crate) async fn structuring_collector(&self) -> Result<Collector<'_>> {
+        let collector = Collector::new(
+            &self.source_path, // Still need source_path for collector context
+            self.collection_depth,
+            self.compiled_chapter_name_regex.as_ref(),
+            self.compiled_page_name_regex.as_ref(),
+            self.image_analysis_sensibility,
+        )
+        .with_storage_kind(self.storage_kind)
+        .with_embedded_thumbnails(self.image_analysis_thumbnails)
+        .with_min_break_confidence(self.image_analysis_min_confidence)
+        .with_archive_extractions(self.archive_extractions.clone())
+        .with_archive_password(self.input_password.clone());
+        let collector = match self.analysis_limits() {
+            Some(limits) => collector.with_runtime_limits(&limits),
+            None => collector,
+        };
+        let collector = match &self.analysis_cache {
+            Some(path)
+                if self.volume_grouping_strategy == VolumeGroupingStrategy::ImageAnalysis =>
+            {
+                let path = path.clone();
+                let cache =
+                    tokio::task::spawn_blocking(move || AnalysisCache::open(&path)).await??;
+                collector.with_analysis_cache(Arc::new(cache))
+            }
+            _ => collector,
+        };
+        Ok(collector)
+    }
+
+    /// Applies the [`lost_chapter_policy`](HozonConfig::lost_chapter_policy) if only
+    /// `placed` of `collected` chapters ended up in volumes.
+    fn check_placed_chapters(&self, collected: usize, placed: usize) -> Result<()> {
+        if placed >= collected {
+            return Ok(());
+        }
+        match self.lost_chapter_policy {
+            LostChapterPolicy::Fail => Err(Error::LostChapters(collected, placed)),
+            LostChapterPolicy::Warn => {
+                log::warn!(
+                    "{} of {} collected chapters couldn't be placed in a volume and are left out",
+                    collected - placed,
+                    collected
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Internal method to perform the volume structuring logic.
+    ///
+    /// This method takes collected chapters and groups them into logical volumes
+    /// based on the configured [`VolumeGroupingStrategy`]. It handles all the
+    /// complex logic for different grouping strategies including name-based grouping,
+    /// image analysis, manual grouping, and flat organization.
     ///
     /// # Arguments
     ///
@@ -669,22 +2434,27 @@ impl HozonConfig {
     ///
     /// * `Ok(StructuredContent)` - Successfully structured volumes with detailed report
     /// * `Err(Error)` - Structuring failed due to configuration or processing errors
-    async fn perform_structuring(
+    pub(crate) async fn perform_structuring(
         config: &HozonConfig,
         collected_chapters_pages: Vec<Vec<PathBuf>>,
     ) -> Result<StructuredContent> {
-        let collector = Collector::new(
-            &config.source_path, // Still need source_path for collector context
-            config.collection_depth,
-            config.compiled_chapter_name_regex.as_ref(),
-            config.compiled_page_name_regex.as_ref(),
-            config.image_analysis_sensibility,
-        );
+        let collector = config.structuring_collector().await?;
+
+        let collected_chapters_pages = match config.photo_album.clone() {
+            Some(album) => {
+                tokio::task::spawn_blocking(move || album.arrange(collected_chapters_pages)).await?
+            }
+            None => collected_chapters_pages,
+        };
+        let (collected_chapters_pages, extra_chapters) =
+            config.separate_extra_chapters(collected_chapters_pages);
 
         let total_chapters_processed = collected_chapters_pages.len();
         let mut total_volumes_created: usize = 0;
         let mut chapter_counts_per_volume: Vec<usize> = Vec::new();
         let mut final_volume_structures: Vec<Vec<Vec<PathBuf>>> = Vec::new(); // Vec<Volume: Vec<Chapter: Vec<PagePath>>>
+        let mut adjustments = Vec::new();
+        let mut cover_breaks = Vec::new();
 
         match config.volume_grouping_strategy {
             VolumeGroupingStrategy::Flat => {
@@ -696,6 +2466,14 @@ impl HozonConfig {
                     chapter_counts_per_volume.push(total_chapters_processed); // Represents the count of original chapters if needed
                 }
             }
+            VolumeGroupingStrategy::PerChapter => {
+                total_volumes_created = total_chapters_processed;
+                chapter_counts_per_volume = vec![1; total_chapters_processed];
+                final_volume_structures = collected_chapters_pages
+                    .into_iter()
+                    .map(|chapter| vec![chapter])
+                    .collect();
+            }
             VolumeGroupingStrategy::Manual => {
                 let chapters_for_manual_grouping = collected_chapters_pages; // This is the Vec<Vec<PathBuf>> of chapters with their pages
                 let actual_total_chapters = chapters_for_manual_grouping.len();
@@ -720,6 +2498,13 @@ impl HozonConfig {
                         );
                         current_chapter_idx += num_chapters_in_volume;
                     }
+                    // Chapters beyond the given sizes form one last volume
+                    if current_chapter_idx < actual_total_chapters {
+                        chapter_counts_per_volume.push(actual_total_chapters - current_chapter_idx);
+                        total_volumes_created += 1;
+                        final_volume_structures
+                            .push(chapters_for_manual_grouping[current_chapter_idx..].to_vec());
+                    }
                 } else {
                     // Default manual: one volume containing all chapters
                     if actual_total_chapters > 0 {
@@ -765,6 +2550,9 @@ impl HozonConfig {
                             .cloned()
                     })
                     .collect::<Vec<Vec<PathBuf>>>();
+                // Chapters without pages have no directory to match; the count of placed
+                // chapters is checked against the lost chapter policy below
+                let total_chapters_processed = sorted_collected_chapters_pages.len();
 
                 // Now determine volume start indices based on the sorted chapter paths
                 let mut volume_start_indices = Vec::new();
@@ -808,6 +2596,13 @@ impl HozonConfig {
                         }
                     }
                 }
+                if let Some(expected) = config.expected_volume_count {
+                    adjustments.extend(fit_volume_count(
+                        &mut volume_start_indices,
+                        total_chapters_processed,
+                        expected,
+                    ));
+                }
 
                 total_volumes_created = volume_start_indices.len();
                 chapter_counts_per_volume = collector
@@ -823,15 +2618,80 @@ impl HozonConfig {
                     current_chapter_offset += num_chapters_in_vol;
                 }
             }
-            VolumeGroupingStrategy::ImageAnalysis => {
-                let sensibility_f64 = config.image_analysis_sensibility as f64 / 100.0;
+            VolumeGroupingStrategy::Mapping => {
+                let mapping = config.volume_mapping().await?.ok_or_else(|| {
+                    Error::Other(
+                        "The mapping grouping strategy requires a volume mapping file".to_string(),
+                    )
+                })?;
+                let chapter_regex = config.compiled_chapter_name_regex.as_ref();
+                let unmapped = mapping.unmapped_chapters(&collected_chapters_pages, chapter_regex);
+                if !unmapped.is_empty() {
+                    let names: Vec<String> = unmapped
+                        .iter()
+                        .map(|(path, _)| get_file_name_lossy(path))
+                        .collect();
+                    return Err(Error::Other(format!(
+                        "Chapters missing from the volume mapping: {}",
+                        names.join(", ")
+                    )));
+                }
 
-                let volume_start_indices = collector
-                    .determine_volume_start_chapters(
+                let mut volumes: BTreeMap<u32, Vec<Vec<PathBuf>>> = BTreeMap::new();
+                for chapter in collected_chapters_pages {
+                    let volume = chapter
+                        .first()
+                        .and_then(|page| page.parent())
+                        .and_then(|dir| mapping.volume_of_chapter(dir, chapter_regex))
+                        .unwrap_or_default(); // Only chapters without pages lack a directory
+                    volumes.entry(volume).or_default().push(chapter);
+                }
+                for chapters in volumes.into_values() {
+                    chapter_counts_per_volume.push(chapters.len());
+                    final_volume_structures.push(chapters);
+                }
+                total_volumes_created = final_volume_structures.len();
+            }
+            VolumeGroupingStrategy::ImageAnalysis => {
+                let mut sensibility = config.image_analysis_sensibility;
+                cover_breaks = collector
+                    .detect_cover_breaks(
                         collected_chapters_pages.clone(),
-                        Some(sensibility_f64),
+                        Some(sensibility as f64 / 100.0),
                     )
                     .await?;
+                let mut volume_start_indices = Collector::volume_starts(&cover_breaks);
+
+                if let Some(expected) = config.expected_volume_count
+                    && volume_start_indices.len() != expected
+                {
+                    // Prefer the sensibility coming closest, and among those the nearest one
+                    let sweep = collector
+                        .sweep_sensibility(collected_chapters_pages.clone(), 0..=100)
+                        .await?;
+                    if let Some(&(best, _)) = sweep.iter().min_by_key(|(candidate, volumes)| {
+                        (volumes.abs_diff(expected), candidate.abs_diff(sensibility))
+                    }) && best != sensibility
+                    {
+                        adjustments.push(VolumeCountAdjustment::Sensibility {
+                            from: sensibility,
+                            to: best,
+                        });
+                        sensibility = best;
+                        cover_breaks = collector
+                            .detect_cover_breaks(
+                                collected_chapters_pages.clone(),
+                                Some(sensibility as f64 / 100.0),
+                            )
+                            .await?;
+                        volume_start_indices = Collector::volume_starts(&cover_breaks);
+                    }
+                    adjustments.extend(fit_volume_count(
+                        &mut volume_start_indices,
+                        total_chapters_processed,
+                        expected,
+                    ));
+                }
 
                 total_volumes_created = volume_start_indices.len();
                 chapter_counts_per_volume = collector
@@ -849,12 +2709,54 @@ impl HozonConfig {
             }
         }
 
+        if config.volume_grouping_strategy != VolumeGroupingStrategy::Flat {
+            let placed = final_volume_structures.iter().map(Vec::len).sum();
+            config.check_placed_chapters(total_chapters_processed, placed)?;
+        }
+
+        let extra_chapter_count = extra_chapters.len();
+        if extra_chapter_count > 0 && config.extra_chapters != ExtraChapters::Skip {
+            // Flat output keeps every page in one chapter per volume
+            let extra_chapters = if config.volume_grouping_strategy == VolumeGroupingStrategy::Flat
+            {
+                vec![extra_chapters.into_iter().flatten().collect()]
+            } else {
+                extra_chapters
+            };
+            match final_volume_structures.last_mut() {
+                Some(last_volume) if config.extra_chapters == ExtraChapters::AppendToLastVolume => {
+                    match last_volume.first_mut() {
+                        Some(pages)
+                            if config.volume_grouping_strategy == VolumeGroupingStrategy::Flat =>
+                        {
+                            pages.extend(extra_chapters.into_iter().flatten());
+                        }
+                        _ => last_volume.extend(extra_chapters),
+                    }
+                    if let Some(count) = chapter_counts_per_volume.last_mut() {
+                        *count += extra_chapter_count;
+                    }
+                }
+                _ => {
+                    final_volume_structures.push(extra_chapters);
+                    chapter_counts_per_volume.push(extra_chapter_count);
+                    total_volumes_created += 1;
+                }
+            }
+        }
+        let total_chapters_processed = match config.extra_chapters {
+            ExtraChapters::Skip => total_chapters_processed,
+            _ => total_chapters_processed + extra_chapter_count,
+        };
+
         Ok(StructuredContent {
             volumes_with_chapters_and_pages: final_volume_structures,
             report: VolumeStructureReport {
                 total_chapters_processed,
                 total_volumes_created,
                 chapter_counts_per_volume,
+                adjustments,
+                cover_breaks,
             },
             grouping_strategy_applied: config.volume_grouping_strategy,
         })
@@ -871,32 +2773,41 @@ impl HozonConfig {
     /// * `config` - The configuration containing metadata, target paths, and format settings
     /// * `volumes_to_generate` - The structured volume data ready for generation
     /// * `cover_options` - Cover image options for the generated volumes
+    /// * `warnings` - Receives warnings raised while planning and generating
+    /// * `control` - Pauses page processing; once a shutdown is requested, volumes that
+    ///   haven't started yet are skipped
     ///
     /// # Returns
     ///
-    /// * `Ok(())` - All volumes generated successfully
+    /// * `Ok((outputs, skipped))` - All started volumes generated successfully, and the
+    ///   volumes skipped because of a shutdown
     /// * `Err(Error)` - Generation failed due to I/O, format, or processing errors
-    async fn perform_generation(
+    pub(crate) async fn perform_generation(
         config: &HozonConfig,
         volumes_to_generate: Vec<Vec<Vec<PathBuf>>>,
         cover_options: &CoverOptions,
-    ) -> Result<()> {
-        let target_directory_path = if config.create_output_directory {
-            let path =
-                PathBuf::from(&config.target_path).join(&sanitize_filename(&config.metadata.title));
-            if !path.exists() {
-                fs::create_dir_all(&path).await?;
-            }
-            path
-        } else {
-            let path = PathBuf::from(&config.target_path);
-            if !path.exists() {
-                return Err(Error::NotFound(
-                    "Target directory does not exist".to_string(),
-                ));
-            }
-            path
-        };
+        source_snapshot: Option<SourceSnapshot>,
+        warnings: &WarningLog,
+        control: &ConversionControl,
+    ) -> Result<(Vec<GeneratedOutput>, Vec<SkippedVolume>)> {
+        let target_directory_path =
+            if config.create_output_directory || config.output_directory_template.is_some() {
+                let path = config.output_directory();
+                if !path.exists() {
+                    fs::create_dir_all(&path).await?;
+                } else if config.foreign_output_policy != ForeignOutputPolicy::Ignore {
+                    config.check_foreign_outputs(&path, warnings).await?;
+                }
+                path
+            } else {
+                let path = PathBuf::from(&config.target_path);
+                if !path.exists() {
+                    return Err(Error::NotFound(
+                        "Target directory does not exist".to_string(),
+                    ));
+                }
+                path
+            };
 
         if volumes_to_generate.is_empty()
             || volumes_to_generate
@@ -906,64 +2817,236 @@ impl HozonConfig {
             return Err(Error::Other("No volumes found for generation.".to_string()));
         }
 
-        let max_concurrent = num_cpus::get().min(4); // Cap concurrent conversions to reasonable number
-        let semaphore = Arc::new(Semaphore::new(max_concurrent));
+        // Held until all volumes are written; released on drop, including on errors
+        let _output_lock = if config.lock_output_directory {
+            let _handle = acquire_file_handle().await?;
+            Some(OutputLock::acquire(&target_directory_path)?)
+        } else {
+            None
+        };
+
+        // Volumes and page reads are capped per config unless shared limits were injected
+        let limits = config.runtime_limits.clone().unwrap_or_else(|| {
+            // Pages may live outside the source directory when passed in directly
+            let first_page_dir = volumes_to_generate
+                .iter()
+                .flatten()
+                .flatten()
+                .next()
+                .and_then(|page| page.parent());
+            let storage = config
+                .storage_kind
+                .resolve(first_page_dir.unwrap_or(&config.source_path));
+            RuntimeLimits::for_storage(storage).throttled(config.throttle)
+        });
+        let shared_config = Arc::new(config.clone());
+        let source_snapshot = source_snapshot.map(Arc::new);
 
         let mut tasks = Vec::new();
-        let total_volumes_to_create = volumes_to_generate.len();
+        let mut manifest_entries = Vec::new();
+        let planned_outputs = config.plan_outputs(volumes_to_generate, warnings).await?;
+        let total_chapters: usize = planned_outputs.iter().map(|o| o.chapters.len()).sum();
+        let links: Vec<VolumeLink> = if config.volume_links && planned_outputs.len() > 1 {
+            planned_outputs
+                .iter()
+                .map(|output| VolumeLink {
+                    volume_number: output.volume_index + 1,
+                    part_number: output.part_number,
+                    title: output.file_name_base.clone(),
+                    file_name: config.output_format.output_name(&output.file_name_base),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
 
-        for (i, volume_chapters_and_pages) in volumes_to_generate.into_iter().enumerate() {
+        for (
+            output_index,
+            PlannedOutput {
+                volume_index: i,
+                part_number,
+                file_name_base,
+                first_chapter,
+                chapters: mut volume_chapters_and_pages,
+                chapter_titles: collected_chapter_titles,
+            },
+        ) in planned_outputs.into_iter().enumerate()
+        {
             let current_volume_number = i + 1;
-            let file_name_base = if total_volumes_to_create > 1 {
-                sanitize_filename(&format!(
-                    "{}{}Volume {}",
-                    config.metadata.title, config.volume_separator, current_volume_number
-                ))
-            } else {
-                sanitize_filename(&config.metadata.title)
-            };
+            let previous_volume = output_index
+                .checked_sub(1)
+                .and_then(|index| links.get(index))
+                .cloned();
+            let next_volume = links.get(output_index + 1).cloned();
+            if config.series_manifest {
+                manifest_entries.push(ManifestEntry {
+                    volume_number: current_volume_number,
+                    part_number,
+                    file_name_base: file_name_base.clone(),
+                    page_count: volume_chapters_and_pages.iter().map(|c| c.len()).sum(),
+                });
+            }
             let target_dir_clone = target_directory_path.clone();
             let format_clone = config.output_format;
-            let semaphore_clone = Arc::clone(&semaphore);
-            let series_metadata_clone = config.metadata.clone();
-            let cover_path_for_this_volume = match cover_options {
-                CoverOptions::None => None,
-                CoverOptions::Single(path) => Some(path.clone()),
-                CoverOptions::PerVolume(map) => map.get(&i).cloned(),
-            };
+            let limits_clone = limits.clone();
+            let series_metadata_clone = config.metadata.normalized(config.unicode_normalization);
+            let output_password = config.output_password.clone();
+            let image_processing = config.image_processing.clone();
+            let cover_path_for_this_volume = cover_options.cover_for_volume(i);
+            let config_clone = Arc::clone(&shared_config);
+            let snapshot_clone = source_snapshot.clone();
+            let warnings_clone = warnings.clone();
+            let control_clone = control.clone();
 
-            // Extract chapter titles for metadata (from first page's parent folder name, or dummy name)
-            let collected_chapter_titles: Vec<String> = volume_chapters_and_pages
-                .iter()
-                .filter_map(|chapter_pages| {
-                    chapter_pages
-                        .first()
-                        .and_then(|p| p.parent()) // Get chapter folder path
-                        .and_then(|p| p.file_name()) // Get folder name
-                        .and_then(|n| n.to_str())
-                        .map(|s| s.to_string())
-                        .or_else(|| Some("Untitled Chapter".to_string()))
-                })
-                .collect();
+            let task = tokio::spawn(async move {
+                let _permit = limits_clone.acquire_volume().await?;
+                // Volumes already being written finish; the ones still waiting are skipped
+                if control_clone.is_shutdown_requested() {
+                    log::info!("Shutdown requested, skipping '{}'", file_name_base);
+                    return Ok(None);
+                }
+                let _active = telemetry::ActiveTask::start();
+                let started = Instant::now();
+                emit(
+                    &config_clone.event_sinks,
+                    PipelineEvent::VolumeStarted {
+                        volume_number: current_volume_number,
+                        part_number,
+                    },
+                );
 
-            let total_pages_in_volume: usize =
-                volume_chapters_and_pages.iter().map(|c| c.len()).sum();
+                // Earlier volumes may have taken a while; make sure the source is still as analyzed
+                if let Some(snapshot) = &snapshot_clone {
+                    config_clone
+                        .reconcile_source_changes(
+                            snapshot,
+                            &mut volume_chapters_and_pages,
+                            &warnings_clone,
+                        )
+                        .await?;
+                }
 
-            let task = tokio::spawn(async move {
-                let _permit = semaphore_clone.acquire().await?;
+                // Sidecar covers show the custom cover, or else the first page
+                let sidecar_cover = cover_path_for_this_volume.clone().or_else(|| {
+                    volume_chapters_and_pages
+                        .iter()
+                        .find_map(|chapter| chapter.first().cloned())
+                });
+
+                let fingerprint = if config_clone.embed_source_fingerprint {
+                    Some(
+                        SourceFingerprint::compute(
+                            &config_clone,
+                            &volume_chapters_and_pages,
+                            cover_path_for_this_volume.as_deref(),
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
+                };
+
+                // Pages rendered in memory, added to the generator between the source pages
+                let mut synthetic = SyntheticPages::new(image_processing.as_ref());
+                if config_clone.missing_pages == MissingPagePolicy::Placeholder {
+                    config_clone
+                        .insert_placeholders(
+                            &mut volume_chapters_and_pages,
+                            &mut synthetic,
+                            first_chapter,
+                            &warnings_clone,
+                        )
+                        .await?;
+                }
 
+                // EPUBs get XHTML intro pages; other formats count rendered ones as pages
+                if let Some(intro) = &config_clone.chapter_intro
+                    && !format_clone.is_epub()
+                {
+                    insert_chapter_intros(
+                        intro,
+                        &mut volume_chapters_and_pages,
+                        &mut synthetic,
+                        first_chapter,
+                        &collected_chapter_titles,
+                    )
+                    .await?;
+                }
+
+                // A custom cover is only a page of its own in archives and directories
+                let cover_pages = usize::from(
+                    cover_path_for_this_volume.is_some()
+                        && matches!(
+                            format_clone,
+                            FileFormat::Cbz | FileFormat::Cb7 | FileFormat::Directory
+                        ),
+                );
+                let needs_blank_page = config_clone
+                    .first_page_side
+                    .is_some_and(|side| side.needs_blank_page(cover_pages));
+                if needs_blank_page && !format_clone.is_epub() {
+                    insert_blank_page(&mut volume_chapters_and_pages, &mut synthetic).await?;
+                }
+
+                let total_pages_in_volume: usize =
+                    volume_chapters_and_pages.iter().map(|c| c.len()).sum();
+                let page_mappings = map_pages(&volume_chapters_and_pages, first_chapter);
+
+                // Held until the volume is written, on top of the handles of its page reads
+                let _output_handle = acquire_output_file_handle().await?;
                 match format_clone {
-                    FileFormat::Cbz => {
-                        let mut generator = Cbz::new(&target_dir_clone, &file_name_base)?;
+                    FileFormat::Cbz | FileFormat::Cb7 => {
+                        let mut generator = match (format_clone, config_clone.archive_backend) {
+                            (FileFormat::Cb7, _) => {
+                                Cbz::new_cb7(&target_dir_clone, &file_name_base)?
+                            }
+                            (_, ArchiveBackend::Blocking) => {
+                                Cbz::new(&target_dir_clone, &file_name_base)?
+                            }
+                            (_, ArchiveBackend::Async) => {
+                                Cbz::new_async(&target_dir_clone, &file_name_base).await?
+                            }
+                        };
+                        generator.set_runtime_limits(&limits_clone);
+                        generator.set_control(control_clone.clone());
+                        generator.set_notes_format(config_clone.comic_info_notes.clone());
+                        generator.set_entry_timestamps(config_clone.entry_timestamps);
+                        generator.set_entry_permissions(config_clone.entry_permissions);
+                        if config_clone.comic_info_chapter_map {
+                            generator.set_chapter_map(
+                                first_chapter,
+                                total_chapters,
+                                volume_chapters_and_pages.iter().map(Vec::len).collect(),
+                            );
+                        }
+                        generator.set_volume_links(previous_volume, next_volume);
+                        generator.set_animated_image_policy(config_clone.animated_images)?;
+                        if let Some(processing) = image_processing {
+                            generator.set_image_processing(processing)?;
+                        }
+
+                        if let Some(password) = &output_password {
+                            generator.set_password(password)?;
+                        }
+                        if let Some(fingerprint) = fingerprint {
+                            generator.set_fingerprint(fingerprint);
+                        }
 
                         // Add custom cover if provided
                         if let Some(cover_path) = &cover_path_for_this_volume {
                             generator.add_cover_page(cover_path).await?;
                         }
 
-                        for chapter_pages in volume_chapters_and_pages.into_iter().flatten() {
-                            // Flatten all pages in the volume
-                            generator.add_page(&chapter_pages).await?;
+                        // Flatten all pages in the volume; reads are pipelined with compression
+                        let pages: Vec<PathBuf> =
+                            volume_chapters_and_pages.into_iter().flatten().collect();
+                        for run in synthetic.runs(&pages) {
+                            match run {
+                                PageRun::Files(paths) => generator.add_pages(paths).await?,
+                                PageRun::Image(image, name) => {
+                                    generator.add_page_from_image(image, name).await?
+                                }
+                            };
                         }
                         generator
                             .set_metadata(
@@ -976,26 +3059,123 @@ impl HozonConfig {
                             .await?;
                         generator.save().await?;
                     }
-                    FileFormat::Epub => {
+                    FileFormat::Epub | FileFormat::Kepub => {
                         let mut generator = EPub::new(&target_dir_clone, &file_name_base)?;
+                        generator.set_runtime_limits(&limits_clone);
+                        generator.set_kobo(format_clone == FileFormat::Kepub);
+                        generator.set_control(control_clone.clone());
+                        generator.set_epub_version(config_clone.epub_version);
+                        generator.set_volume_label(config_clone.volume_label.clone());
+                        generator.set_strict(config_clone.strict_epub);
+                        generator.set_compression(config_clone.epub_compression)?;
+                        generator.set_path_layout(config_clone.epub_layout);
+                        generator.set_image_fit(config_clone.epub_image_fit);
+                        generator.set_page_style(config_clone.epub_page_style.clone())?;
+                        if let Some(intro) = &config_clone.chapter_intro {
+                            generator.set_chapter_intro(intro.clone(), first_chapter);
+                        }
+                        generator.set_entry_timestamps(config_clone.entry_timestamps);
+                        generator.set_toc_options(config_clone.toc.clone());
+                        if let Some(part_number) = part_number {
+                            generator.set_part_number(part_number);
+                        }
+                        if let Some(processing) = image_processing {
+                            generator.set_image_processing(processing)?;
+                        }
+                        if let Some(format) = config_clone.epub_image_format {
+                            generator.set_image_format(format)?;
+                        }
+                        generator.set_animated_image_policy(config_clone.animated_images);
+                        generator.set_share_duplicate_images(
+                            config_clone.duplicate_pages == DuplicatePagePolicy::Share,
+                        );
+                        if let Some(source) = config_clone.alt_text.clone() {
+                            generator.set_alt_text_source(source);
+                        }
+
+                        if let Some(fingerprint) = &fingerprint {
+                            generator.set_fingerprint(fingerprint);
+                        }
+                        generator.set_volume_links(previous_volume, next_volume);
 
                         // Use custom cover if provided, otherwise use first page of first chapter
                         if let Some(cover_path) = &cover_path_for_this_volume {
                             generator.set_cover(cover_path)?;
                         } else {
-                            if volume_chapters_and_pages.is_empty()
-                                || volume_chapters_and_pages
-                                    .first()
-                                    .map_or(true, |c| c.is_empty())
-                            {
+                            // EPUB generator takes the first source page as cover
+                            let Some(first_page) = &sidecar_cover else {
                                 return Err(Error::Unsupported(
                                     "Cannot create EPUB without a cover image (first page of first chapter)".to_string(),
                                 ));
+                            };
+                            generator.set_cover(first_page)?;
+                        }
+
+                        generator
+                            .set_metadata(
+                                &file_name_base,
+                                Some(current_volume_number),
+                                &series_metadata_clone,
+                                total_pages_in_volume,
+                                &collected_chapter_titles,
+                            )
+                            .await?;
+
+                        if needs_blank_page {
+                            generator.add_blank_page()?;
+                        }
+                        // Chapters whose pages were all stripped as repeated keep their number, not an entry
+                        for (chapter_idx, chapter_pages) in volume_chapters_and_pages
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, pages)| !pages.is_empty())
+                        {
+                            let chapter_title = collected_chapter_titles
+                                .get(chapter_idx)
+                                .map_or("Untitled Chapter", |s| s.as_str());
+                            // Opened first, so that a synthesized first page belongs to it
+                            generator
+                                .add_chapter(chapter_idx + 1, chapter_title, &[])
+                                .await?;
+                            for run in synthetic.runs(chapter_pages) {
+                                match run {
+                                    PageRun::Files(paths) => {
+                                        generator
+                                            .add_chapter(chapter_idx + 1, chapter_title, paths)
+                                            .await?
+                                    }
+                                    PageRun::Image(image, name) => {
+                                        generator.add_page_from_image(image, name).await?
+                                    }
+                                };
                             }
-                            // EPUB generator takes the first page of the first chapter as cover
-                            generator.set_cover(
-                                volume_chapters_and_pages.first().unwrap().first().unwrap(),
-                            )?;
+                        }
+                        generator.save().await?;
+                    }
+                    FileFormat::Mobi => {
+                        let mut generator = Mobi::new(&target_dir_clone, &file_name_base)?;
+                        generator.set_runtime_limits(&limits_clone);
+                        generator.set_control(control_clone.clone());
+                        generator.set_volume_label(config_clone.volume_label.clone());
+                        generator.set_entry_timestamps(config_clone.entry_timestamps);
+                        generator.set_toc_options(config_clone.toc.clone());
+                        if let Some(processing) = image_processing {
+                            generator.set_image_processing(processing)?;
+                        }
+                        generator.set_animated_image_policy(config_clone.animated_images)?;
+                        if let Some(fingerprint) = &fingerprint {
+                            generator.set_fingerprint(fingerprint);
+                        }
+
+                        // Without a custom cover, Kindles show the first page, unless that
+                        // is an inserted blank page
+                        let cover_path = if needs_blank_page {
+                            &sidecar_cover
+                        } else {
+                            &cover_path_for_this_volume
+                        };
+                        if let Some(cover_path) = cover_path {
+                            generator.set_cover(cover_path).await?;
                         }
 
                         generator
@@ -1008,32 +3188,888 @@ impl HozonConfig {
                             )
                             .await?;
 
-                        for (chapter_idx, chapter_pages) in
-                            volume_chapters_and_pages.iter().enumerate()
+                        // Chapters whose pages were all stripped as repeated keep their number, not an entry
+                        for (chapter_idx, chapter_pages) in volume_chapters_and_pages
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, pages)| !pages.is_empty())
                         {
                             let chapter_title = collected_chapter_titles
                                 .get(chapter_idx)
                                 .map_or("Untitled Chapter", |s| s.as_str());
+                            // Opened first, so that a synthesized first page belongs to it
                             generator
-                                .add_chapter(chapter_idx + 1, chapter_title, chapter_pages)
+                                .add_chapter(chapter_idx + 1, chapter_title, &[])
                                 .await?;
+                            for run in synthetic.runs(chapter_pages) {
+                                match run {
+                                    PageRun::Files(paths) => {
+                                        generator
+                                            .add_chapter(chapter_idx + 1, chapter_title, paths)
+                                            .await?
+                                    }
+                                    PageRun::Image(image, name) => {
+                                        generator.add_page_from_image(image, name).await?
+                                    }
+                                };
+                            }
+                        }
+                        generator.save().await?;
+                    }
+                    FileFormat::Directory => {
+                        let mut generator = Directory::new(&target_dir_clone, &file_name_base)?;
+                        generator.set_runtime_limits(&limits_clone);
+                        generator.set_control(control_clone.clone());
+                        generator.set_animated_image_policy(config_clone.animated_images)?;
+                        // Permissions set on linked pages would change the source files
+                        generator.set_link_pages(
+                            config_clone.link_pages && config_clone.output_permissions.is_none(),
+                        );
+                        if let Some(processing) = image_processing {
+                            generator.set_image_processing(processing)?;
+                        }
+                        if let Some(cover_path) = &cover_path_for_this_volume {
+                            generator.add_cover_page(cover_path).await?;
+                        }
+                        let pages: Vec<PathBuf> =
+                            volume_chapters_and_pages.into_iter().flatten().collect();
+                        for run in synthetic.runs(&pages) {
+                            match run {
+                                PageRun::Files(paths) => generator.add_pages(paths).await?,
+                                PageRun::Image(image, name) => {
+                                    generator.add_page_from_image(image, name).await?
+                                }
+                            };
                         }
                         generator.save().await?;
                     }
                 }
-                Result::Ok(())
+
+                let output_path = target_dir_clone.join(format_clone.output_name(&file_name_base));
+                if let Some(mode) = config_clone.output_permissions {
+                    if format_clone == FileFormat::Directory {
+                        // The mode is meant for files; the directory keeps the default
+                        let directory = output_path.clone();
+                        tokio::task::spawn_blocking(move || {
+                            for entry in std::fs::read_dir(&directory)? {
+                                set_file_permissions(&entry?.path(), mode)?;
+                            }
+                            Result::Ok(())
+                        })
+                        .await??;
+                    } else {
+                        set_file_permissions(&normalize_path(&output_path)?, mode)?;
+                    }
+                }
+                if let (Some(sidecars), Some(cover)) =
+                    (config_clone.cover_sidecars.clone(), sidecar_cover)
+                {
+                    let directory = target_dir_clone.clone();
+                    tokio::task::spawn_blocking(move || {
+                        sidecars.write(&cover, &directory, &file_name_base)
+                    })
+                    .await??;
+                }
+                let bytes = if format_clone == FileFormat::Directory {
+                    let directory = output_path.clone();
+                    tokio::task::spawn_blocking(move || directory::directory_size(&directory))
+                        .await?
+                } else {
+                    tokio::fs::metadata(&output_path)
+                        .await
+                        .map_or(0, |m| m.len())
+                };
+                telemetry::record_output(format_clone, total_pages_in_volume, bytes);
+                emit(
+                    &config_clone.event_sinks,
+                    PipelineEvent::VolumeFinished {
+                        volume_number: current_volume_number,
+                        part_number,
+                        path: output_path.clone(),
+                        page_count: total_pages_in_volume,
+                        duration: started.elapsed(),
+                    },
+                );
+                Result::Ok(Some(GeneratedOutput {
+                    volume_number: current_volume_number,
+                    part_number,
+                    path: output_path,
+                    page_count: total_pages_in_volume,
+                    pages: page_mappings,
+                    duration: started.elapsed(),
+                }))
+            });
+            let volume = SkippedVolume {
+                volume_number: current_volume_number,
+                part_number,
+            };
+            tasks.push((volume, task));
+        }
+
+        // Wait for every volume before returning so no task is still writing once the
+        // output lock is released; the first error is reported.
+        let mut first_error = None;
+        let mut generated_outputs = Vec::with_capacity(tasks.len());
+        let mut skipped = Vec::new();
+        for (volume, task) in tasks.into_iter() {
+            match task.await.map_err(Error::from).and_then(|r| r) {
+                Ok(Some(output)) => generated_outputs.push(output),
+                Ok(None) => skipped.push(volume),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+
+        // Only list complete output sets, so servers never index missing files
+        if config.series_manifest && skipped.is_empty() {
+            let metadata = config.metadata.normalized(config.unicode_normalization);
+            let format = config.output_format;
+            let cover_sidecars = config.cover_sidecars.clone();
+            let _handle = acquire_file_handle().await?;
+            tokio::task::spawn_blocking(move || {
+                write_series_manifest(
+                    &target_directory_path,
+                    &metadata,
+                    format,
+                    cover_sidecars.as_ref(),
+                    &manifest_entries,
+                )
+            })
+            .await??;
+        }
+        Ok((generated_outputs, skipped))
+    }
+}
+
+/// Merges or splits the volumes starting at `starts` (sorted chapter indices, beginning
+/// with 0) until there are `expected` of them, returning the changes made. Neighbors
+/// with the fewest chapters together are merged first; the volume with the most chapters
+/// is split first. Stops early once no volume has more than one chapter left to split.
+fn fit_volume_count(
+    starts: &mut Vec<usize>,
+    total_chapters: usize,
+    expected: usize,
+) -> Vec<VolumeCountAdjustment> {
+    let size = |starts: &[usize], volume: usize| {
+        starts.get(volume + 1).copied().unwrap_or(total_chapters) - starts[volume]
+    };
+
+    let mut adjustments = Vec::new();
+    while starts.len() > expected.max(1) {
+        let volume = (0..starts.len() - 1)
+            .min_by_key(|&volume| size(starts, volume) + size(starts, volume + 1))
+            .expect("at least two volumes");
+        starts.remove(volume + 1);
+        adjustments.push(VolumeCountAdjustment::Merged { volume: volume + 1 });
+    }
+    while starts.len() < expected {
+        let volume = (0..starts.len())
+            .rev() // Earlier volumes first among equally sized ones
+            .max_by_key(|&volume| size(starts, volume))
+            .filter(|&volume| size(starts, volume) > 1);
+        let Some(volume) = volume else {
+            break;
+        };
+        starts.insert(volume + 1, starts[volume] + size(starts, volume) / 2);
+        adjustments.push(VolumeCountAdjustment::Split { volume: volume + 1 });
+    }
+    adjustments
+}
+
+/// Inserts a [`ChapterIntro`] page rendered into `synthetic` before the pages of each
+/// chapter of a volume that has any.
+/// `first_chapter` is the 1-based number of the volume's first chapter across the series.
+async fn insert_chapter_intros(
+    intro: &ChapterIntro,
+    chapters: &mut Vec<Vec<PathBuf>>,
+    synthetic: &mut SyntheticPages,
+    first_chapter: usize,
+    titles: &[String],
+) -> Result<()> {
+    let lines: Vec<Vec<String>> = titles
+        .iter()
+        .enumerate()
+        .map(|(index, title)| intro.lines(first_chapter + index, title))
+        .collect();
+    let mut volume = std::mem::take(chapters);
+    let mut intros = std::mem::take(synthetic);
+    let (intros, volume) = tokio::task::spawn_blocking(move || {
+        for (index, (pages, lines)) in volume.iter_mut().zip(&lines).enumerate() {
+            if !pages.is_empty() {
+                intros.insert_intro(pages, first_chapter + index, lines);
+            }
+        }
+        (intros, volume)
+    })
+    .await?;
+    *chapters = volume;
+    *synthetic = intros;
+    Ok(())
+}
+
+/// Inserts a blank page rendered into `synthetic` in front of the first chapter of a volume
+/// with pages, see [`HozonConfig::first_page_side`].
+async fn insert_blank_page(
+    chapters: &mut Vec<Vec<PathBuf>>,
+    synthetic: &mut SyntheticPages,
+) -> Result<()> {
+    let mut volume = std::mem::take(chapters);
+    let mut blank = std::mem::take(synthetic);
+    let (blank, volume) = tokio::task::spawn_blocking(move || {
+        if let Some(first_chapter) = volume.iter_mut().find(|pages| !pages.is_empty()) {
+            blank.insert_blank(first_chapter);
+        }
+        (blank, volume)
+    })
+    .await?;
+    *chapters = volume;
+    *synthetic = blank;
+    Ok(())
+}
+
+/// Numbers the pages of a file's `chapters` in reading order, see [`PageMapping`].
+fn map_pages(chapters: &[Vec<PathBuf>], first_chapter: usize) -> Vec<PageMapping> {
+    let mut volume_page = 0;
+    let mut pages = Vec::with_capacity(chapters.iter().map(Vec::len).sum());
+    for (chapter_index, chapter) in chapters.iter().enumerate() {
+        for (page_index, source) in chapter.iter().enumerate() {
+            volume_page += 1;
+            pages.push(PageMapping {
+                source: source.clone(),
+                chapter: chapter_index + 1,
+                series_chapter: first_chapter + chapter_index,
+                chapter_page: page_index + 1,
+                volume_page,
             });
-            tasks.push(task);
         }
+    }
+    pages
+}
 
-        for task in tasks.into_iter() {
-            task.await??;
+/// One output file to generate: a whole volume, or one part of a volume that was split
+/// by [`HozonConfig::epub_max_file_size`].
+struct PlannedOutput {
+    volume_index: usize,         // 0-based index of the volume the file belongs to
+    part_number: Option<usize>,  // 1-based part number, if the volume was split
+    file_name_base: String,      // File name without extension
+    first_chapter: usize,        // 1-based number of the first chapter, counted across files
+    chapters: Vec<Vec<PathBuf>>, // Pages of each chapter in the file
+    chapter_titles: Vec<String>, // Title of each chapter, from its pages as collected
+}
+
+impl HozonConfig {
+    /// Maps structured volumes to the output files to generate, splitting EPUB volumes
+    /// that exceed [`epub_max_file_size`](HozonConfig::epub_max_file_size) into parts.
+    async fn plan_outputs(
+        &self,
+        volumes: Vec<Vec<Vec<PathBuf>>>,
+        warnings: &WarningLog,
+    ) -> Result<Vec<PlannedOutput>> {
+        let total_volumes = volumes.len();
+        // Named after the chapters as collected; stripping may empty some of them
+        let mut names = Vec::with_capacity(total_volumes);
+        let mut chapter_offset = 0;
+        for (volume_index, chapters) in volumes.iter().enumerate() {
+            let file_name_base = self.planned_file_name_base(
+                volume_index + 1,
+                total_volumes,
+                chapter_offset + 1,
+                chapters,
+            )?;
+            let titles: Vec<String> = chapters
+                .iter()
+                .map(|pages| {
+                    self.chapter_title(pages)
+                        .unwrap_or_else(|| "Untitled Chapter".to_string())
+                })
+                .collect();
+            names.push((file_name_base, titles));
+            chapter_offset += chapters.len();
         }
+
+        let volumes = if self.duplicate_pages == DuplicatePagePolicy::StripRepeated {
+            let warnings = warnings.clone();
+            tokio::task::spawn_blocking(move || strip_repeated_pages(volumes, &warnings)).await??
+        } else {
+            volumes
+        };
+        let max_size = match (self.output_format, self.epub_max_file_size) {
+            (format, Some(max_size)) if format.is_epub() => Some(max_size),
+            _ => None,
+        };
+
+        let mut planned = Vec::with_capacity(total_volumes);
+        let mut chapter_offset = 0;
+        for (volume_index, (chapters, (file_name_base, mut chapter_titles))) in
+            volumes.into_iter().zip(names).enumerate()
+        {
+            let first_chapter = chapter_offset + 1;
+            chapter_offset += chapters.len();
+            let parts = match max_size {
+                Some(max_size) => {
+                    let warnings = warnings.clone();
+                    tokio::task::spawn_blocking(move || {
+                        split_by_size(chapters, max_size, &warnings)
+                    })
+                    .await?
+                }
+                None => vec![chapters],
+            };
+
+            if parts.len() == 1 {
+                planned.extend(parts.into_iter().map(|chapters| PlannedOutput {
+                    volume_index,
+                    part_number: None,
+                    file_name_base: file_name_base.clone(),
+                    first_chapter,
+                    chapters,
+                    chapter_titles: std::mem::take(&mut chapter_titles),
+                }));
+                continue;
+            }
+            let mut first_chapter = first_chapter;
+            for (part_index, chapters) in parts.into_iter().enumerate() {
+                let chapter_count = chapters.len();
+                let rest = chapter_titles.split_off(chapter_count);
+                planned.push(PlannedOutput {
+                    volume_index,
+                    part_number: Some(part_index + 1),
+                    file_name_base: self.part_name(&file_name_base, part_index + 1),
+                    first_chapter,
+                    chapters,
+                    chapter_titles: std::mem::replace(&mut chapter_titles, rest),
+                });
+                first_chapter += chapter_count;
+            }
+        }
+        self.disambiguate_file_names(&mut planned, warnings);
+        Ok(planned)
+    }
+
+    /// Applies the [`foreign_output_policy`](HozonConfig::foreign_output_policy) to the
+    /// existing output directory `directory`.
+    async fn check_foreign_outputs(&self, directory: &Path, warnings: &WarningLog) -> Result<()> {
+        let config = self.clone();
+        let path = directory.to_path_buf();
+        let foreign =
+            tokio::task::spawn_blocking(move || config.find_foreign_outputs(&path)).await?;
+        if foreign.is_empty() {
+            return Ok(());
+        }
+        let message = format!(
+            "{} belong to another series than '{}'",
+            foreign.join(", "),
+            self.metadata
+                .series
+                .as_deref()
+                .unwrap_or(&self.metadata.title)
+        );
+        match self.foreign_output_policy {
+            ForeignOutputPolicy::Fail => {
+                Err(Error::ForeignOutputs(directory.to_path_buf(), message))
+            }
+            ForeignOutputPolicy::Warn | ForeignOutputPolicy::Ignore => {
+                warnings.warn(format!(
+                    "Output directory '{}' already holds outputs of another series: {}",
+                    path_to_string_lossy(directory),
+                    message
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    /// Lists the series manifest and the outputs in `directory` whose recorded series
+    /// differs from the series of this config. Blocking.
+    fn find_foreign_outputs(&self, directory: &Path) -> Vec<String> {
+        let expected = series_digest(&self.metadata);
+        let mut foreign = Vec::new();
+        if let Some((series, authors)) = read_series_manifest(directory)
+            && series_digest_of(&series, &authors) != expected
+        {
+            foreign.push(format!(
+                "'{}' (series '{}')",
+                SERIES_MANIFEST_FILE_NAME, series
+            ));
+        }
+
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return foreign;
+        };
+        let extension = format!(".{}", self.output_format.extension());
+        let mut outputs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && get_file_name_lossy(path)
+                        .to_lowercase()
+                        .ends_with(&extension)
+            })
+            .collect();
+        outputs.sort();
+        for output in outputs {
+            let fingerprint = read_embedded_fingerprint(
+                &output,
+                self.output_format,
+                self.output_password.as_deref(),
+            );
+            match fingerprint {
+                Ok(Some(SourceFingerprint {
+                    series_digest: Some(digest),
+                    ..
+                })) if digest != expected => {
+                    foreign.push(format!("'{}'", get_file_name_lossy(&output)));
+                }
+                Ok(_) => {}
+                Err(e) => log::debug!("Could not read fingerprint from {:?}: {}", output, e),
+            }
+        }
+        foreign
+    }
+
+    /// Renames outputs whose file name is already taken by an earlier output, ignoring
+    /// case, to `{name} (2)`, `{name} (3)`, …, so sanitized names that collide (e.g.
+    /// titles differing only in forbidden characters) can't overwrite each other.
+    fn disambiguate_file_names(&self, planned: &mut [PlannedOutput], warnings: &WarningLog) {
+        let key = |base: &str| self.output_format.output_name(base).to_lowercase();
+        let mut taken = std::collections::HashSet::new();
+        for output in planned.iter_mut() {
+            if taken.insert(key(&output.file_name_base)) {
+                continue;
+            }
+            let mut suffix = 2;
+            let renamed = loop {
+                let renamed = format!("{} ({})", output.file_name_base, suffix);
+                if !taken.contains(&key(&renamed)) {
+                    break renamed;
+                }
+                suffix += 1;
+            };
+            warnings.warn(format!(
+                "Volume {} would overwrite '{}', which another volume is written to; \
+                 writing it to '{}' instead",
+                output.volume_index + 1,
+                self.output_format.output_name(&output.file_name_base),
+                self.output_format.output_name(&renamed)
+            ));
+            taken.insert(key(&renamed));
+            output.file_name_base = renamed;
+        }
+    }
+
+    /// Fills the gaps in the page numbering of each chapter of a volume with placeholder
+    /// pages rendered into `synthetic`, see [`MissingPagePolicy::Placeholder`].
+    /// `first_chapter` is the 1-based number of the volume's first chapter across the
+    /// series.
+    async fn insert_placeholders(
+        &self,
+        chapters: &mut Vec<Vec<PathBuf>>,
+        synthetic: &mut SyntheticPages,
+        first_chapter: usize,
+        warnings: &WarningLog,
+    ) -> Result<()> {
+        let regex = self
+            .compiled_page_name_regex
+            .clone()
+            .unwrap_or_else(|| DEFAULT_NUMBER_REGEX.clone());
+        let mut volume = std::mem::take(chapters);
+        let mut placeholders = std::mem::take(synthetic);
+        let warnings = warnings.clone();
+        let (placeholders, volume) = tokio::task::spawn_blocking(move || {
+            for (index, pages) in volume.iter_mut().enumerate() {
+                let chapter_dir = pages.first().and_then(|page| page.parent());
+                let chapter_dir = chapter_dir.map(Path::to_path_buf).unwrap_or_default();
+                let gaps = placeholders.fill_gaps(pages, first_chapter + index, &regex)?;
+                if !gaps.is_empty() {
+                    warnings.warn(format!(
+                        "Chapter {:?} misses pages {:?}; inserted placeholders",
+                        chapter_dir, gaps
+                    ));
+                }
+            }
+            Result::Ok((placeholders, volume))
+        })
+        .await??;
+        *chapters = volume;
+        *synthetic = placeholders;
         Ok(())
     }
+
+    /// Compares each chapter of a volume against the analysis-time snapshot and applies
+    /// the configured [`SourceChangePolicy`] to chapters that changed.
+    async fn reconcile_source_changes(
+        &self,
+        snapshot: &SourceSnapshot,
+        volume_chapters_and_pages: &mut [Vec<PathBuf>],
+        warnings: &WarningLog,
+    ) -> Result<()> {
+        for chapter_pages in volume_chapters_and_pages.iter_mut() {
+            let Some((chapter_dir, reason)) = snapshot.detect_change(chapter_pages).await? else {
+                continue;
+            };
+
+            match self.source_change_policy {
+                SourceChangePolicy::Fail | SourceChangePolicy::Ignore => {
+                    // `Ignore` never captures a snapshot, so only `Fail` can get here
+                    return Err(Error::SourceChanged(chapter_dir, reason));
+                }
+                SourceChangePolicy::Rescan => {
+                    warnings.warn(format!(
+                        "Chapter {:?} changed since analysis ({}); re-scanning its pages",
+                        chapter_dir, reason
+                    ));
+                    // Every directory of the chapter, keeping the files that belong to it
+                    let belongs = snapshot.membership(chapter_pages);
+                    let mut rescanned = Vec::new();
+                    for directory in page_directories(chapter_pages) {
+                        let collector = Collector::new(
+                            &directory,
+                            CollectionDepth::Shallow,
+                            self.compiled_chapter_name_regex.as_ref(),
+                            self.compiled_page_name_regex.as_ref(),
+                            self.image_analysis_sensibility,
+                        )
+                        .with_page_sort(self.effective_page_sort())
+                        .with_storage_kind(self.storage_kind);
+                        let pages = collector
+                            .collect_pages(
+                                vec![directory.clone()],
+                                self.custom_page_path_sorter.clone(),
+                            )
+                            .await?;
+                        rescanned.extend(pages.into_iter().flatten().filter(|page| belongs(page)));
+                    }
+                    *chapter_pages = rescanned;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Label of the volume collecting extra chapters with [`ExtraChapters::SeparateVolume`],
+/// used instead of the volume number in its file name.
+pub const EXTRAS_VOLUME_LABEL: &str = "Extras";
+
+/// File name template used by [`VolumeGroupingStrategy::PerChapter`] when no
+/// [`HozonConfig::file_name_template`] is set.
+pub const DEFAULT_CHAPTER_FILE_NAME_TEMPLATE: &str = "{series} - c{chapter:03} - {chapter_title}";
+
+/// Values substituted into a [`HozonConfig::file_name_template`].
+#[derive(Debug, Default)]
+struct FileNameFields {
+    series: String,
+    volume: usize,
+    volume_label: String,
+    chapter: usize,
+    chapter_title: String,
+}
+
+impl FileNameFields {
+    fn value(&self, name: &str) -> Option<TemplateValue> {
+        Some(match name {
+            "series" => TemplateValue::Text(self.series.clone()),
+            "volume" => TemplateValue::Number(self.volume),
+            "volume_label" => TemplateValue::Text(self.volume_label.clone()),
+            "chapter" => TemplateValue::Number(self.chapter),
+            "chapter_title" => TemplateValue::Text(self.chapter_title.clone()),
+            _ => return None,
+        })
+    }
+}
+
+/// Values substituted into a [`HozonConfig::output_directory_template`].
+#[derive(Debug, Default)]
+struct DirectoryFields {
+    title: String,
+    series: String,
+    author: String,
+    publisher: String,
+    language: String,
+    format: String,
+    year: Option<usize>,
+}
+
+impl DirectoryFields {
+    fn value(&self, name: &str) -> Option<TemplateValue> {
+        let text = |value: &str| TemplateValue::Text(value.to_string());
+        Some(match name {
+            "title" => text(&self.title),
+            "series" => text(&self.series),
+            "author" => text(&self.author),
+            "publisher" => text(&self.publisher),
+            "language" => text(&self.language),
+            "format" => text(&self.format),
+            "year" => match self.year {
+                Some(year) => TemplateValue::Number(year),
+                None => text(UNKNOWN_DIRECTORY_VALUE),
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Directory name used for metadata an [`HozonConfig::output_directory_template`] refers
+/// to but the metadata doesn't have, e.g. `{publisher}` without a publisher.
+pub const UNKNOWN_DIRECTORY_VALUE: &str = "Unknown";
+
+/// Renders an output directory template into a path relative to the target path, see
+/// [`HozonConfig::output_directory_template`].
+fn render_directory_template(template: &str, fields: &DirectoryFields) -> Result<PathBuf> {
+    let invalid = |reason: &str| {
+        Error::Other(format!(
+            "Invalid output directory template '{}': {}",
+            template, reason
+        ))
+    };
+    if template.starts_with(['/', '\\']) || Path::new(template).is_absolute() {
+        return Err(invalid("must be relative to the target path"));
+    }
+    let mut segments = template.split(['/', '\\']).peekable();
+    if segments.peek() == Some(&"{target}") {
+        segments.next();
+    }
+    let mut path = PathBuf::new();
+    for segment in segments.filter(|segment| !segment.is_empty()) {
+        if segment == "." || segment == ".." {
+            return Err(invalid("'.' and '..' segments are not allowed"));
+        }
+        let rendered = sanitize_filename(
+            render_template("output directory", segment, |name| fields.value(name))?.trim(),
+        );
+        // A title like ".." must not leave the target directory
+        if rendered.is_empty() || rendered.chars().all(|c| c == '.') {
+            path.push(UNKNOWN_DIRECTORY_VALUE);
+        } else {
+            path.push(rendered);
+        }
+    }
+    Ok(path)
+}
+
+/// A value substituted into a name template.
+enum TemplateValue {
+    Number(usize), // Can be zero-padded
+    Text(String),
+}
+
+/// Substitutes `{placeholder}` and `{placeholder:0N}` occurrences in a file name template.
+fn render_file_name_template(template: &str, fields: &FileNameFields) -> Result<String> {
+    render_template("file name", template, |name| fields.value(name))
+}
+
+/// Substitutes `{placeholder}` and `{placeholder:0N}` occurrences in a `kind` template,
+/// looking up placeholders with `value`.
+fn render_template(
+    kind: &str,
+    template: &str,
+    value: impl Fn(&str) -> Option<TemplateValue>,
+) -> Result<String> {
+    let invalid = |reason: String| {
+        Error::Other(format!(
+            "Invalid {} template '{}': {}",
+            kind, template, reason
+        ))
+    };
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid("unclosed '{'".to_string()))?
+            + start;
+        let (name, width) = match rest[start + 1..end].split_once(':') {
+            Some((name, spec)) => {
+                let width = spec
+                    .strip_prefix('0')
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .ok_or_else(|| invalid(format!("unsupported format '{}'", spec)))?;
+                (name, Some(width))
+            }
+            None => (&rest[start + 1..end], None),
+        };
+        match (value(name), width) {
+            (Some(TemplateValue::Number(number)), width) => {
+                rendered.push_str(&format!("{:0width$}", number, width = width.unwrap_or(0)))
+            }
+            (Some(TemplateValue::Text(text)), None) => rendered.push_str(&text),
+            (Some(TemplateValue::Text(_)), Some(_)) => {
+                return Err(invalid(format!("'{{{}}}' is not a number", name)));
+            }
+            (None, _) => return Err(invalid(format!("unknown placeholder '{{{}}}'", name))),
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Removes pages whose content already appeared in an earlier volume, keeping volumes
+/// that would end up empty unchanged. Chapters losing all of their pages stay in place
+/// without pages, so the chapters after them keep their numbers. Blocking; reads every
+/// page.
+fn strip_repeated_pages(
+    volumes: Vec<Vec<Vec<PathBuf>>>,
+    warnings: &WarningLog,
+) -> Result<Vec<Vec<Vec<PathBuf>>>> {
+    let mut seen: HashSet<[u8; 32]> = HashSet::new();
+    let mut stripped_volumes = Vec::with_capacity(volumes.len());
+
+    for (volume_index, chapters) in volumes.into_iter().enumerate() {
+        let digests: Vec<Vec<[u8; 32]>> = chapters
+            .iter()
+            .map(|pages| {
+                pages
+                    .par_iter()
+                    .map(|page| {
+                        let mut hasher = Sha256::new();
+                        std::io::copy(&mut std::fs::File::open(page)?, &mut hasher)?;
+                        Ok(hasher.finalize().into())
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<_>>()?;
+
+        let kept: Vec<Vec<PathBuf>> = chapters
+            .iter()
+            .zip(&digests)
+            .map(|(pages, digests)| {
+                pages
+                    .iter()
+                    .zip(digests)
+                    .filter(|(_, digest)| !seen.contains(*digest))
+                    .map(|(page, _)| page.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        seen.extend(digests.into_iter().flatten());
+
+        let total: usize = chapters.iter().map(Vec::len).sum();
+        let remaining: usize = kept.iter().map(Vec::len).sum();
+        if remaining == 0 {
+            warnings.warn(format!(
+                "Volume {} only contains pages of earlier volumes; keeping them",
+                volume_index + 1
+            ));
+            stripped_volumes.push(chapters);
+            continue;
+        }
+        if remaining < total {
+            log::info!(
+                "Stripped {} repeated pages from volume {}",
+                total - remaining,
+                volume_index + 1
+            );
+        }
+        stripped_volumes.push(kept);
+    }
+    Ok(stripped_volumes)
+}
+
+/// Groups consecutive chapters into parts whose page sizes add up to at most `max_size`
+/// bytes. Blocking; reads file metadata. Always returns at least one part.
+fn split_by_size(
+    chapters: Vec<Vec<PathBuf>>,
+    max_size: u64,
+    warnings: &WarningLog,
+) -> Vec<Vec<Vec<PathBuf>>> {
+    let mut parts: Vec<Vec<Vec<PathBuf>>> = vec![Vec::new()];
+    let mut part_size = 0u64;
+
+    for chapter in chapters {
+        let chapter_size: u64 = chapter
+            .iter()
+            .filter_map(|page| std::fs::metadata(page).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        if chapter_size > max_size {
+            warnings.warn(format!(
+                "Chapter {:?} alone exceeds the maximum EPUB size ({} > {} bytes)",
+                chapter.first().and_then(|p| p.parent()),
+                chapter_size,
+                max_size
+            ));
+        }
+
+        let current = parts.last_mut().expect("parts is never empty");
+        if !current.is_empty() && part_size + chapter_size > max_size {
+            parts.push(vec![chapter]);
+            part_size = chapter_size;
+        } else {
+            current.push(chapter);
+            part_size += chapter_size;
+        }
+    }
+    parts
 }
 
 impl HozonConfigBuilder {
+    /// Configures the builder for photo collections: pages ordered by capture date
+    /// ([`SortStrategy::ExifDate`]) and grouped into one chapter per day ([`PhotoAlbum`]), captions embedded in the photos
+    /// as alt text ([`AltTextSource::EmbeddedCaptions`]) and a table of contents listing
+    /// the days in chronological order ([`TocStyle::Chapters`]).
+    ///
+    /// Options already set on the builder are kept, so the preset can be refined before
+    /// or after calling it. Use [`CollectionDepth::Shallow`] for a single folder of photos.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use hozon::prelude::*;
+    /// # use std::path::PathBuf;
+    /// let config = HozonConfig::builder()
+    ///     .photo_album_preset()
+    ///     .metadata(EbookMetadata::default_with_title("Holidays 2024".to_string()))
+    ///     .source_path(PathBuf::from("./DCIM"))
+    ///     .target_path(PathBuf::from("./output"))
+    ///     .collection_depth(CollectionDepth::Shallow)
+    ///     .output_format(FileFormat::Epub)
+    ///     .build()
+    ///     .expect("Invalid configuration");
+    /// ```
+    pub fn photo_album_preset(&mut self) -> &mut Self {
+        if self.photo_album.is_none() {
+            self.photo_album(PhotoAlbum::default());
+        }
+        if self.page_sort_strategy.is_none() {
+            self.page_sort_strategy(SortStrategy::ExifDate);
+        }
+        if self.alt_text.is_none() {
+            self.alt_text(AltTextSource::EmbeddedCaptions);
+        }
+        if self.toc.is_none() {
+            self.toc(TocOptions {
+                style: TocStyle::Chapters,
+                chapter_label: None,
+            });
+        }
+        self
+    }
+
+    /// Compiles the chapter or page name regex, preferring the explicit pattern over the
+    /// one of the naming preset. Patterns are checked in `validate`.
+    fn compile_name_regex(&self, for_chapter: bool) -> Option<Regex> {
+        let explicit = if for_chapter {
+            &self.chapter_name_regex_str
+        } else {
+            &self.page_name_regex_str
+        };
+        let pattern = explicit.clone().flatten().or_else(|| {
+            let preset = naming_preset(self.naming_preset.as_ref()?.as_ref()?)?;
+            Some(if for_chapter {
+                preset.chapter_name_regex.to_string()
+            } else {
+                preset.page_name_regex.to_string()
+            })
+        })?;
+        Regex::new(&pattern).ok()
+    }
+
     fn validate(&self) -> std::result::Result<(), String> {
         // Validate custom regexes if they are provided
         if let Some(Some(s)) = &self.chapter_name_regex_str {
@@ -1049,6 +4085,17 @@ impl HozonConfigBuilder {
             }
         }
 
+        if let Some(Some(name)) = &self.naming_preset
+            && naming_preset(name).is_none()
+        {
+            let known: Vec<&str> = NAMING_PRESETS.iter().map(|preset| preset.name).collect();
+            return Err(format!(
+                "Unknown naming preset '{}', expected one of: {}",
+                name,
+                known.join(", ")
+            ));
+        }
+
         // Validate image analysis sensibility
         if let Some(sensibility) = self.image_analysis_sensibility {
             if sensibility > 100 {
@@ -2,8 +2,8 @@
 //!
 //! Hozon is a Rust library that provides a fast, asynchronous, and feature-rich API
 //! for converting image-based content (manga, comics, photo collections) into
-//! standardized ebook formats (CBZ and EPUB). It offers intelligent content analysis,
-//! flexible volume grouping strategies, and comprehensive metadata support.
+//! standardized ebook formats (CBZ, CB7, EPUB, Kobo KEPUB and MOBI). It offers intelligent
+//! content analysis, flexible volume grouping strategies, and comprehensive metadata support.
 //!
 //! ## Features
 //!
@@ -11,6 +11,7 @@
 //! - **Smart Analysis**: Automatic content analysis with configurable sensitivity for optimal grouping
 //! - **Flexible Volume Strategies**: Name-based, image analysis, manual, or flat grouping options
 //! - **Rich Metadata Support**: Complete ebook metadata including custom fields and multilingual support
+//! - **Photo Albums**: EXIF date ordering, grouping by day or event, and embedded captions as alt text
 //! - **High Performance**: Async/parallel processing with configurable concurrency limits
 //! - **Robust Error Handling**: Comprehensive error reporting and validation
 //! - **Cross-Platform**: Works on Windows, macOS, and Linux
@@ -194,25 +195,77 @@
 //! - **`VolumeGroupingStrategy::ImageAnalysis`**: Detects volume breaks by analyzing cover pages (grayscale detection)
 //! - **`VolumeGroupingStrategy::Manual`**: Uses explicit volume sizes or treats all content as one volume
 //! - **`VolumeGroupingStrategy::Flat`**: Combines all pages into a single chapter in one volume
+//! - **`VolumeGroupingStrategy::PerChapter`**: Emits one file per chapter, named by `file_name_template`
+//! - **`VolumeGroupingStrategy::Mapping`**: Groups chapters by a chapter-to-volume list from a CSV or JSON file
 //!
 //! For detailed examples and API documentation, see the individual module documentation.
 
+pub mod alt_text;
+pub mod analysis_cache;
+mod cleanup;
 pub mod collector;
+pub mod diagnostics;
+pub mod engine;
 pub mod error;
+pub mod events;
+mod extract;
+pub mod fingerprint;
 pub mod generator;
 pub mod hozon;
+pub mod lock;
+pub mod metadata;
 pub mod path_utils;
+pub mod pdf;
+pub mod photo;
+pub mod pipeline;
+mod placeholder;
+pub mod presets;
+pub mod processing;
+pub mod report;
+pub mod runtime;
+pub mod sidecar;
+mod snapshot;
+mod source_archive;
+pub mod storage;
+pub mod telemetry;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 pub mod types;
+pub mod volume_mapping;
 
 // Publicly expose the main `HozonConfig` struct and its builder
 pub use hozon::HozonConfig;
 pub use hozon::HozonConfigBuilder;
 
+pub use alt_text::AltTextSource;
+pub use analysis_cache::{AnalysisCache, CoverAnalysis};
+pub use diagnostics::{GroupingExplanation, SortExplanation};
+pub use engine::{ConversionRequest, HozonEngine};
+pub use events::{EventSink, PipelineEvent};
+pub use fingerprint::SourceFingerprint;
+pub use metadata::ComicInfo;
+pub use photo::{PhotoAlbum, PhotoGrouping};
+pub use pipeline::{ConversionHandle, HozonPipeline};
+pub use processing::{
+    AnimatedImagePolicy, ColorProfilePolicy, ImageProcessing, ProcessedImageFormat,
+};
+pub use runtime::{RuntimeLimits, ThrottleProfile};
+pub use sidecar::CoverSidecars;
+pub use storage::StorageKind;
+pub use volume_mapping::VolumeMapping;
+
 // Re-export error and core types for direct access
 pub use types::{
-    AnalyzeFinding, AnalyzeReport, CollectedContent, CollectionDepth, CoverOptions, Direction,
-    EbookMetadata, FileFormat, HozonExecutionMode, StructuredContent, VolumeGroupingStrategy,
-    VolumeStructureReport,
+    AnalyzeFinding, AnalyzeReport, ArchiveBackend, ChapterIntro, CollectedContent, CollectionDepth,
+    ConversionReport, CoverBreak, CoverOptions, CustomImageFormat, Direction, DuplicatePagePolicy,
+    EbookMetadata, EntryCompression, EntryTimestamps, EpubCompression, EpubPathLayout, EpubVersion,
+    ExtraChapters, ExtractedEbook, FileFormat, ForeignOutputPolicy, GeneratedFile,
+    GeneratorCapabilities, HozonExecutionMode, Identifier, IdentifierScheme, IgnoredOption,
+    ImageFit, ImageFormat, LostChapterPolicy, MissingPagePolicy, NotesFormat, OutputCheckReport,
+    OutputState, OutputStatus, PageMapping, PageSide, PageStyle, SizeBucket, SkippedVolume,
+    SortSpec, SortStrategy, SourceChangePolicy, SourceCleanup, SourceStats, StructuredContent,
+    TocOptions, TocStyle, UnicodeNormalization, VolumeCountAdjustment, VolumeGroupingStrategy,
+    VolumeLabel, VolumeLink, VolumeStructureReport,
 };
 
 /// Prelude module for convenient imports.
@@ -223,18 +276,50 @@ pub use types::{
 /// ## Included Types
 ///
 /// - **Core Config**: `HozonConfig`, `HozonConfigBuilder`
-/// - **Metadata**: `EbookMetadata`
+/// - **Pipeline**: `HozonPipeline`, `ConversionHandle`, `ConversionReport`, `GeneratedFile`,
+///   `PageMapping`, `SkippedVolume`
+/// - **Servers**: `HozonEngine`, `ConversionRequest`
+/// - **Events**: `EventSink`, `PipelineEvent`
+/// - **Metadata**: `EbookMetadata`, `Identifier`, `IdentifierScheme`, `AltTextSource`, `VolumeLink`
 /// - **Data Structures**: `CollectedContent`, `StructuredContent`
-/// - **Enums**: `FileFormat`, `Direction`, `VolumeGroupingStrategy`, `CollectionDepth`
-/// - **Analysis**: `AnalyzeReport`, `AnalyzeFinding`, `VolumeStructureReport`
+/// - **Enums**: `FileFormat`, `ArchiveBackend`, `Direction`, `VolumeGroupingStrategy`, `CollectionDepth`,
+///   `SortStrategy`, `SortSpec`, `SourceChangePolicy`, `NotesFormat`, `TocStyle`, `EpubVersion`,
+///   `UnicodeNormalization`, `VolumeLabel`, `DuplicatePagePolicy`, `EntryTimestamps`,
+///   `ExtraChapters`, `LostChapterPolicy`, `SourceCleanup`, `MissingPagePolicy`, `PageSide`,
+///   `ForeignOutputPolicy`
+/// - **EPUB Layout**: `TocOptions`, `EpubCompression`, `EntryCompression`, `EpubPathLayout`,
+///   `ImageFit`, `PageStyle`, `ChapterIntro`
+/// - **Formats**: `GeneratorCapabilities`, `IgnoredOption`
+/// - **Analysis**: `AnalyzeReport`, `AnalyzeFinding`, `VolumeStructureReport`, `SourceFingerprint`,
+///   `SourceStats`, `SortExplanation`, `GroupingExplanation`, `AnalysisCache`, `CoverAnalysis`,
+///   `VolumeCountAdjustment`, `VolumeMapping`, `CoverBreak`
+/// - **Maintenance**: `OutputCheckReport`, `OutputStatus`, `OutputState`, `ComicInfo`,
+///   `ExtractedEbook`
+/// - **Processing**: `ImageFormat`, `ImageProcessing`, `ProcessedImageFormat`, `ColorProfilePolicy`,
+///   `AnimatedImagePolicy`
+/// - **Concurrency**: `RuntimeLimits`, `StorageKind`, `ThrottleProfile`
+/// - **Sidecars**: `CoverSidecars`
+/// - **Photo Albums**: `PhotoAlbum`, `PhotoGrouping`
 /// - **Utilities**: `Collector`, `Regex`, `PathBuf`, `Path`, `Arc`
 /// - **Error Handling**: `error` module
 /// - **Execution Modes**: `HozonExecutionMode`
 pub mod prelude {
     pub use super::{
-        AnalyzeFinding, AnalyzeReport, CollectedContent, CollectionDepth, CoverOptions, Direction,
-        EbookMetadata, FileFormat, HozonConfig, HozonConfigBuilder, HozonExecutionMode,
-        StructuredContent, VolumeGroupingStrategy, VolumeStructureReport, error, generator, types,
+        AltTextSource, AnalysisCache, AnalyzeFinding, AnalyzeReport, AnimatedImagePolicy,
+        ArchiveBackend, ChapterIntro, CollectedContent, CollectionDepth, ColorProfilePolicy,
+        ComicInfo, ConversionHandle, ConversionReport, ConversionRequest, CoverAnalysis,
+        CoverBreak, CoverOptions, CoverSidecars, Direction, DuplicatePagePolicy, EbookMetadata,
+        EntryCompression, EntryTimestamps, EpubCompression, EpubPathLayout, EpubVersion, EventSink,
+        ExtraChapters, ExtractedEbook, FileFormat, ForeignOutputPolicy, GeneratedFile,
+        GeneratorCapabilities, GroupingExplanation, HozonConfig, HozonConfigBuilder, HozonEngine,
+        HozonExecutionMode, HozonPipeline, Identifier, IdentifierScheme, IgnoredOption, ImageFit,
+        ImageFormat, ImageProcessing, LostChapterPolicy, MissingPagePolicy, NotesFormat,
+        OutputCheckReport, OutputState, OutputStatus, PageMapping, PageSide, PageStyle, PhotoAlbum,
+        PhotoGrouping, PipelineEvent, ProcessedImageFormat, RuntimeLimits, SkippedVolume,
+        SortExplanation, SortSpec, SortStrategy, SourceChangePolicy, SourceCleanup,
+        SourceFingerprint, SourceStats, StorageKind, StructuredContent, ThrottleProfile,
+        TocOptions, TocStyle, UnicodeNormalization, VolumeCountAdjustment, VolumeGroupingStrategy,
+        VolumeLabel, VolumeLink, VolumeMapping, VolumeStructureReport, error, generator, types,
     };
     pub use crate::collector::Collector;
     pub use regex::Regex;
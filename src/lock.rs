@@ -0,0 +1,288 @@
+//! Single-writer guard for output directories.
+//!
+//! When enabled, generation creates a lock file in the output directory before writing
+//! any volume and removes it when done, so two concurrent Hozon runs (e.g. a cron job and
+//! a manual run) don't interleave writes to the same files. Locks left behind by crashed
+//! runs are detected as stale and taken over.
+//!
+//! Lock files are written to a temporary file and moved into place, so a lock never
+//! exists without its owner, and stale locks are replaced by renaming over them rather
+//! than removed, so the lock file never goes missing while it changes hands.
+
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+/// Name of the lock file created in the output directory.
+pub const LOCK_FILE_NAME: &str = ".hozon.lock";
+
+/// Locks older than this are considered stale even if their owner can't be verified
+/// (e.g. when they were created on another machine).
+const STALE_LOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Lock files without a readable owner (not written by Hozon, or by a version writing
+/// them in place) are considered held until they're unchanged for this long.
+const UNREADABLE_LOCK_GRACE: Duration = Duration::from_secs(30);
+
+/// File created next to the lock while a run takes over a stale one, so that only one
+/// run replaces it.
+const TAKEOVER_FILE_NAME: &str = ".hozon.lock.takeover";
+
+/// Owner information stored in the lock file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LockOwner {
+    pid: u32,
+    host: String,
+    created: u64, // Seconds since the Unix epoch
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            host: host_name(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut pid = None;
+        let mut host = None;
+        let mut created = None;
+        for line in content.lines() {
+            match line.split_once('=') {
+                Some(("pid", v)) => pid = v.trim().parse().ok(),
+                Some(("host", v)) => host = Some(v.trim().to_string()),
+                Some(("created", v)) => created = v.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            pid: pid?,
+            host: host?,
+            created: created?,
+        })
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "pid={}\nhost={}\ncreated={}\n",
+            self.pid, self.host, self.created
+        )
+    }
+
+    /// A lock is stale if its owner process is known to be gone, or it is very old.
+    fn is_stale(&self) -> bool {
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+            .saturating_sub(self.created);
+        if age > STALE_LOCK_AGE.as_secs() {
+            return true;
+        }
+        self.host == host_name() && process_is_gone(self.pid)
+    }
+}
+
+/// Held lock on an output directory. The lock file is removed on drop.
+#[derive(Debug)]
+pub(crate) struct OutputLock {
+    path: PathBuf,
+    content: String, // Written owner, telling our lock from a later one
+}
+
+impl OutputLock {
+    /// Acquires the lock for `directory`, taking over stale locks.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OutputLock)` - The lock is held until the returned guard is dropped
+    /// * `Err(Error::TargetLocked)` - Another live run holds the lock
+    pub(crate) fn acquire(directory: &Path) -> Result<Self> {
+        let path = directory.join(LOCK_FILE_NAME);
+        let content = LockOwner::current().serialize();
+
+        // One retry: the lock may be released between failing to create it and reading it
+        for _ in 0..2 {
+            match write_temporary(directory, &content)?.persist_noclobber(&path) {
+                Ok(_) => return Ok(Self { path, content }),
+                Err(e) if e.error.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.error.into()),
+            }
+            let existing = match std::fs::read_to_string(&path) {
+                Ok(existing) => existing,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            return match LockOwner::parse(&existing) {
+                Some(owner) if !owner.is_stale() => Err(Error::TargetLocked(
+                    directory.to_path_buf(),
+                    format!("locked by process {} on '{}'", owner.pid, owner.host),
+                )),
+                None if !unchanged_for(&path, UNREADABLE_LOCK_GRACE) => Err(Error::TargetLocked(
+                    directory.to_path_buf(),
+                    "locked by a run that hasn't recorded itself yet".to_string(),
+                )),
+                _ => Self::take_over(directory, path, &existing, content),
+            };
+        }
+
+        Err(Error::TargetLocked(
+            directory.to_path_buf(),
+            "lock was re-acquired by another run".to_string(),
+        ))
+    }
+
+    /// Replaces the stale lock at `path`, which held `stale`, with one holding `content`.
+    fn take_over(directory: &Path, path: PathBuf, stale: &str, content: String) -> Result<Self> {
+        let takeover = directory.join(TAKEOVER_FILE_NAME);
+        let locked =
+            |reason: &str| Error::TargetLocked(directory.to_path_buf(), reason.to_string());
+        // Only left behind by a run that crashed while taking over
+        if unchanged_for(&takeover, UNREADABLE_LOCK_GRACE) {
+            let _ = std::fs::remove_file(&takeover);
+        }
+        match write_temporary(directory, &content)?.persist_noclobber(&takeover) {
+            Ok(_) => {}
+            Err(e) if e.error.kind() == ErrorKind::AlreadyExists => {
+                return Err(locked("another run is taking over a stale lock"));
+            }
+            Err(e) => return Err(e.error.into()),
+        }
+
+        // Another run may have taken the lock over and released it in the meantime
+        let result = if std::fs::read_to_string(&path).is_ok_and(|current| current == stale) {
+            log::warn!("Replacing stale lock file {:?}", path);
+            write_temporary(directory, &content)?
+                .persist(&path)
+                .map(|_| Self { path, content })
+                .map_err(|e| e.error.into())
+        } else {
+            Err(locked("lock was re-acquired by another run"))
+        };
+        let _ = std::fs::remove_file(&takeover);
+        result
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        // Left alone if another run took it over as stale in the meantime
+        if std::fs::read_to_string(&self.path).is_ok_and(|current| current != self.content) {
+            log::warn!("Lock file {:?} was taken over by another run", self.path);
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove lock file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Writes `content` to a new temporary file in `directory`, to be moved into place.
+fn write_temporary(directory: &Path, content: &str) -> Result<tempfile::NamedTempFile> {
+    let mut file = tempfile::Builder::new()
+        .prefix(".hozon.lock.")
+        .tempfile_in(directory)?;
+    file.write_all(content.as_bytes())?;
+    Ok(file)
+}
+
+/// Whether the file at `path` exists and wasn't modified for `duration`.
+fn unchanged_for(path: &Path, duration: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed > duration)
+}
+
+/// Best-effort host name used to tell local locks from remote ones.
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|h| h.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Returns `true` only if the process is known not to exist. Where this can't be
+/// determined, the lock is treated as live and only the age check applies.
+fn process_is_gone(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        !Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    fn lock_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("hozon-lock-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn acquire_concurrently(dir: &Path, runs: usize) -> Vec<Result<OutputLock>> {
+        let barrier = Arc::new(Barrier::new(runs));
+        let handles: Vec<_> = (0..runs)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let dir = dir.to_path_buf();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    OutputLock::acquire(&dir)
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_concurrent_runs_acquire_once() {
+        for stale in [false, true] {
+            let dir = lock_dir(if stale { "stale" } else { "fresh" });
+            if stale {
+                let crashed = "pid=1\nhost=elsewhere\ncreated=0\n";
+                std::fs::write(dir.join(LOCK_FILE_NAME), crashed).unwrap();
+            }
+
+            let results = acquire_concurrently(&dir, 8);
+            assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+            assert!(
+                results
+                    .iter()
+                    .all(|result| matches!(result, Ok(_) | Err(Error::TargetLocked(..))))
+            );
+            drop(results);
+            // Neither the lock nor any temporary file is left behind
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_unwritten_lock_is_held() {
+        let dir = lock_dir("unwritten");
+        std::fs::write(dir.join(LOCK_FILE_NAME), "").unwrap();
+        assert!(matches!(
+            OutputLock::acquire(&dir),
+            Err(Error::TargetLocked(..))
+        ));
+    }
+}
@@ -0,0 +1,809 @@
+//! Reading back and rewriting the metadata of generated files.
+//!
+//! [`read_comicinfo`] parses the ComicInfo.xml of a CBZ file into the same
+//! [`EbookMetadata`] that is used for writing, so tools can verify or bulk-edit archives
+//! generated earlier:
+//!
+//! ```rust,no_run
+//! use hozon::metadata::read_comicinfo;
+//! # use std::path::Path;
+//! # fn main() -> hozon::error::Result<()> {
+//!
+//! let info = read_comicinfo(Path::new("./output/My Comic/My Comic - Volume 2.cbz"))?;
+//! println!(
+//!     "{} volume {:?}, {:?} pages",
+//!     info.metadata.title, info.volume, info.page_count
+//! );
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ComicInfo.xml holds less than [`EbookMetadata`], so some values don't survive the round
+//! trip:
+//!
+//! - Authors are joined with `", "` when writing and split at it when reading.
+//! - Tags, the identifier, rights and custom fields are only recovered from `Notes` written
+//!   as [`NotesFormat::Lines`](crate::NotesFormat::Lines) or
+//!   [`NotesFormat::Json`](crate::NotesFormat::Json).
+//! - Typed identifiers are only recovered from JSON notes. Otherwise the `GTIN` element is
+//!   read as ISBN if it has an ISBN prefix, and as GTIN if not.
+//! - The release date is only kept to the day. Files written without one carry the date
+//!   they were generated on.
+//!
+//! [`parse_opf`] does the same for the package document of EPUB files, which
+//! [`HozonConfig::extract_to_directory`](crate::HozonConfig::extract_to_directory) uses to
+//! unpack ebooks together with their metadata.
+//!
+//! [`HozonConfig::retag_outputs`](crate::HozonConfig::retag_outputs) goes the other way and
+//! rewrites the metadata of existing files from updated [`EbookMetadata`], copying the
+//! pages over unchanged.
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, ZipArchive, ZipWriter};
+
+use crate::error::{Error, Result};
+use crate::fingerprint::{
+    FINGERPRINT_KEY, SourceFingerprint, config_digest, read_embedded_fingerprint, series_digest,
+};
+use crate::generator::cbz::{gtin_element, metadata_note_lines, metadata_notes_json};
+use crate::generator::epub::{epub_title, metadata_elements};
+use crate::generator::epub_zip::OPF_PATH;
+use crate::generator::escape_xml;
+use crate::hozon::HozonConfig;
+use crate::path_utils::{path_to_string_lossy, retry_while_locked};
+use crate::types::{EbookMetadata, EpubVersion, FileFormat, Identifier, IdentifierScheme};
+
+/// Name of the metadata entry in CBZ files.
+const COMIC_INFO_ENTRY: &str = "ComicInfo.xml";
+
+lazy_static! {
+    /// The predefined entities and character references of XML text.
+    static ref XML_ENTITY: Regex = Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-z]+);").unwrap();
+}
+
+/// The contents of a ComicInfo.xml, as read by [`read_comicinfo`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComicInfo {
+    pub metadata: EbookMetadata,
+    pub volume: Option<usize>,     // `Number`, the volume the file holds
+    pub page_count: Option<usize>, // `PageCount`, the cover included
+    pub chapters: Vec<String>,     // Titles of the included chapters, from the notes
+}
+
+/// Reads the ComicInfo.xml of the CBZ file at `cbz_path`. Blocking.
+///
+/// # Errors
+///
+/// Fails if the file isn't a readable archive, has no ComicInfo.xml, or the ComicInfo.xml
+/// has no `Title`.
+pub fn read_comicinfo(cbz_path: &Path) -> Result<ComicInfo> {
+    read_comicinfo_with_password(cbz_path, None)
+}
+
+/// Reads the ComicInfo.xml of the CBZ file at `cbz_path`, decrypting it with `password`
+/// if given. Blocking.
+///
+/// # Errors
+///
+/// See [`read_comicinfo`].
+pub fn read_comicinfo_with_password(cbz_path: &Path, password: Option<&str>) -> Result<ComicInfo> {
+    let file = std::fs::File::open(cbz_path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    let entry = match password {
+        Some(password) => archive.by_name_decrypt(COMIC_INFO_ENTRY, password.as_bytes()),
+        None => archive.by_name(COMIC_INFO_ENTRY),
+    };
+    let mut entry = match entry {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => {
+            return Err(Error::NotFound(format!(
+                "{} in '{}'",
+                COMIC_INFO_ENTRY,
+                path_to_string_lossy(cbz_path)
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    parse_comicinfo(&content)
+}
+
+/// Parses the contents of a ComicInfo.xml.
+///
+/// # Errors
+///
+/// Fails if the document has no `Title`.
+pub fn parse_comicinfo(xml: &str) -> Result<ComicInfo> {
+    let field = |name: &str| element_text(xml, name).filter(|text| !text.is_empty());
+    let number = |name: &str| field(name).and_then(|text| text.parse::<u32>().ok());
+
+    let title =
+        field("Title").ok_or_else(|| Error::Other(format!("{} has no Title", COMIC_INFO_ENTRY)))?;
+    let mut metadata = EbookMetadata {
+        series: field("Series"),
+        authors: field("Writer")
+            .map(|writers| writers.split(", ").map(str::to_string).collect())
+            .unwrap_or_default(),
+        publisher: field("Publisher"),
+        description: field("Summary"),
+        language: field("LanguageISO")
+            .or_else(|| field("Language"))
+            .unwrap_or_default(),
+        release_date: number("Year").and_then(|year| {
+            release_date(
+                year,
+                number("Month").unwrap_or(1),
+                number("Day").unwrap_or(1),
+            )
+        }),
+        genre: field("Genre"),
+        web: field("Web"),
+        ..EbookMetadata::default_with_title(title)
+    };
+    if metadata.language.is_empty() {
+        metadata.language = EbookMetadata::default().language;
+    }
+
+    let mut chapters = Vec::new();
+    if let Some(notes) = element_text(xml, "Notes") {
+        chapters = read_notes(&notes, &mut metadata);
+    }
+    if metadata.identifiers.is_empty()
+        && let Some(gtin) = field("GTIN")
+    {
+        let is_isbn = gtin.len() == 13 && (gtin.starts_with("978") || gtin.starts_with("979"));
+        let scheme = if is_isbn {
+            IdentifierScheme::Isbn
+        } else {
+            IdentifierScheme::Gtin
+        };
+        metadata.identifiers.push(Identifier::new(scheme, gtin));
+    }
+
+    Ok(ComicInfo {
+        metadata,
+        volume: field("Number").and_then(|text| text.parse().ok()),
+        page_count: field("PageCount").and_then(|text| text.parse().ok()),
+        chapters,
+    })
+}
+
+/// Reads tags, identifiers, rights and custom fields from `Notes` into `metadata` and
+/// returns the included chapter titles.
+fn read_notes(notes: &str, metadata: &mut EbookMetadata) -> Vec<String> {
+    let notes = notes.trim();
+    if notes.starts_with('{') {
+        return serde_json::from_str::<Value>(notes)
+            .map(|notes| read_json_notes(&notes, metadata))
+            .unwrap_or_default();
+    }
+
+    let list = |text: &str| -> Vec<String> {
+        text.split(", ")
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let mut chapters = Vec::new();
+    let mut in_custom_fields = false;
+    for line in notes.lines().map(str::trim) {
+        let Some((label, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match label {
+            "Tags" => metadata.tags = list(value),
+            "Identifier" => metadata.identifier = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "Rights" => metadata.rights = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "Custom Fields" => {
+                in_custom_fields = true;
+                continue;
+            }
+            "Chapters included" => chapters = list(value),
+            key if in_custom_fields => {
+                metadata
+                    .custom_fields
+                    .insert(key.to_string(), value.to_string());
+                continue;
+            }
+            _ => {}
+        }
+        in_custom_fields = false;
+    }
+    chapters
+}
+
+/// Reads JSON notes, see [`read_notes`].
+fn read_json_notes(notes: &Value, metadata: &mut EbookMetadata) -> Vec<String> {
+    let string = |value: &Value| value.as_str().map(str::to_string);
+    let strings = |value: Option<&Value>| -> Vec<String> {
+        value
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(string).collect())
+            .unwrap_or_default()
+    };
+
+    metadata.tags = strings(notes.get("tags"));
+    metadata.identifier = notes.get("identifier").and_then(string);
+    metadata.rights = notes.get("rights").and_then(string);
+    metadata.identifiers = notes
+        .get("identifiers")
+        .and_then(Value::as_array)
+        .map(|identifiers| {
+            identifiers
+                .iter()
+                .filter_map(|id| {
+                    let scheme = id.get("scheme")?.as_str()?;
+                    let value = id.get("value")?.as_str()?;
+                    Some(Identifier::new(IdentifierScheme::from_name(scheme), value))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    metadata.custom_fields = notes
+        .get("custom_fields")
+        .and_then(Value::as_object)
+        .map(|fields| {
+            fields
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), string(value)?)))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+    strings(notes.get("chapters"))
+}
+
+/// Parses the metadata of an EPUB package document (`content.opf`).
+///
+/// Reads the Dublin Core elements, the series in its EPUB 3 and Calibre forms and the
+/// `<meta name content>` pairs Hozon writes custom fields as. The series prefix and part
+/// number Hozon adds to the `dc:title` are removed; the volume label is kept, as its
+/// rendering depends on the [`VolumeLabel`](crate::VolumeLabel) it was written with.
+///
+/// # Errors
+///
+/// Fails if the document has no `dc:title`.
+pub fn parse_opf(opf: &str) -> Result<EbookMetadata> {
+    let title = element_text(opf, "dc:title")
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| Error::Other("content.opf has no dc:title".to_string()))?;
+    let meta_content = |name: &str| {
+        let pattern = format!(r#"<meta name="{}" content="([^"]*)""#, regex::escape(name));
+        Regex::new(&pattern)
+            .unwrap()
+            .captures(opf)
+            .map(|captures| unescape_xml(&captures[1]))
+    };
+    let series = Regex::new(r#"(?s)<meta property="belongs-to-collection"[^>]*>(.*?)</meta>"#)
+        .unwrap()
+        .captures(opf)
+        .map(|captures| unescape_xml(captures[1].trim()))
+        .or_else(|| meta_content("calibre:series"));
+
+    let title = Regex::new(r" Part \d+$")
+        .unwrap()
+        .replace(&title, "")
+        .into_owned();
+    let title = match &series {
+        Some(series) => title
+            .strip_prefix(&format!("{} - ", series))
+            .map_or(title.clone(), str::to_string),
+        None => title,
+    };
+    let mut metadata = EbookMetadata {
+        series,
+        authors: element_texts(opf, "dc:creator"),
+        publisher: element_text(opf, "dc:publisher"),
+        description: element_text(opf, "dc:description"),
+        tags: element_texts(opf, "dc:subject"),
+        rights: element_text(opf, "dc:rights"),
+        release_date: element_text(opf, "dc:date").and_then(|date| {
+            DateTime::parse_from_rfc3339(&date)
+                .map(|date| date.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    let mut parts = date.get(..10)?.split('-').map(str::parse::<u32>);
+                    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+                    release_date(year.ok()?, month.ok()?, day.ok()?)
+                })
+        }),
+        ..EbookMetadata::default_with_title(title)
+    };
+    if let Some(language) = element_text(opf, "dc:language").filter(|l| !l.is_empty()) {
+        metadata.language = language;
+    }
+
+    // Hozon numbers its identifiers `hozon-id-0` (the untyped one) and up; the package's
+    // own `urn:uuid:` identifier is generated and not part of the metadata
+    let identifiers = Regex::new(r#"(?s)<dc:identifier([^>]*)>(.*?)</dc:identifier>"#).unwrap();
+    let hozon_id = Regex::new(r#"id="hozon-id-(\d+)""#).unwrap();
+    let opf_scheme = Regex::new(r#"opf:scheme="([^"]*)""#).unwrap();
+    for captures in identifiers.captures_iter(opf) {
+        let attributes = &captures[1];
+        let value = unescape_xml(captures[2].trim());
+        let hozon_index = hozon_id
+            .captures(attributes)
+            .and_then(|id| id[1].parse::<usize>().ok());
+        let epub2_scheme = opf_scheme
+            .captures(attributes)
+            .map(|scheme| unescape_xml(&scheme[1]).to_lowercase());
+        match (hozon_index, epub2_scheme) {
+            (Some(0), _) => metadata.identifier = Some(value),
+            (Some(_), Some(scheme)) => metadata
+                .identifiers
+                .push(Identifier::new(IdentifierScheme::from_name(&scheme), value)),
+            (Some(_), None) => {
+                let identifier = match value.strip_prefix("urn:isbn:") {
+                    Some(isbn) => Identifier::new(IdentifierScheme::Isbn, isbn),
+                    None if value.chars().all(|c| c.is_ascii_digit()) => {
+                        Identifier::new(IdentifierScheme::Gtin, value)
+                    }
+                    None => match value.split_once(':') {
+                        Some((scheme, id)) => {
+                            Identifier::new(IdentifierScheme::from_name(scheme), id)
+                        }
+                        None => Identifier::new(IdentifierScheme::Other(String::new()), value),
+                    },
+                };
+                metadata.identifiers.push(identifier);
+            }
+            (None, _) if metadata.identifier.is_none() && !value.starts_with("urn:uuid:") => {
+                metadata.identifier = Some(value);
+            }
+            (None, _) => {}
+        }
+    }
+
+    // `<meta name content>` pairs are custom fields, unless Hozon, Calibre or epub-builder
+    // wrote them
+    let pairs = Regex::new(r#"<meta name="([^"]*)" content="([^"]*)"\s*/>"#).unwrap();
+    for captures in pairs.captures_iter(opf) {
+        let name = unescape_xml(&captures[1]);
+        let is_reserved = name == "cover"
+            || name.starts_with("schema:")
+            || name.starts_with("calibre:")
+            || name.starts_with("hozon:");
+        if !is_reserved {
+            metadata
+                .custom_fields
+                .insert(name, unescape_xml(&captures[2]));
+        }
+    }
+    Ok(metadata)
+}
+
+/// The volume number recorded as series position of an EPUB, in its EPUB 3 or Calibre form.
+pub(crate) fn series_position(opf: &str) -> Option<usize> {
+    Regex::new(r#"(?:property="group-position">|name="calibre:series_index" content=")(\d+)"#)
+        .unwrap()
+        .captures(opf)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+/// The unescaped and trimmed texts of all `<name>` elements.
+fn element_texts(xml: &str, name: &str) -> Vec<String> {
+    let pattern = format!(r"(?s)<{0}(?:\s[^>]*)?>(.*?)</{0}\s*>", regex::escape(name));
+    Regex::new(&pattern)
+        .unwrap()
+        .captures_iter(xml)
+        .map(|captures| unescape_xml(captures[1].trim()))
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+/// The unescaped and trimmed text of the first `<name>` element, if there is one.
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    element_raw_text(xml, name).map(|text| text.trim().to_string())
+}
+
+/// The unescaped text of the first `<name>` element including surrounding whitespace.
+fn element_raw_text(xml: &str, name: &str) -> Option<String> {
+    let pattern = format!(r"(?s)<{0}(?:\s[^>]*)?>(.*?)</{0}\s*>", regex::escape(name));
+    let captures = Regex::new(&pattern).ok()?.captures(xml)?;
+    Some(unescape_xml(captures.get(1)?.as_str()))
+}
+
+/// Resolves the predefined entities and character references of XML text.
+pub(crate) fn unescape_xml(text: &str) -> String {
+    XML_ENTITY
+        .replace_all(text, |captures: &Captures| {
+            let entity = &captures[1];
+            let character = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            character.map_or_else(|| captures[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Midnight UTC of the given day, if it exists.
+fn release_date(year: u32, month: u32, day: u32) -> Option<DateTime<Utc>> {
+    Utc.with_ymd_and_hms(i32::try_from(year).ok()?, month, day, 0, 0, 0)
+        .single()
+}
+
+/// Rewrites the metadata of the generated file at `path` from the metadata of `config`,
+/// see [`HozonConfig::retag_outputs`]. Blocking.
+pub(crate) fn retag_output(path: &Path, config: &HozonConfig) -> Result<()> {
+    let format = config.output_format;
+    let password = config.output_password.as_deref();
+
+    // Keep the content hash of an embedded fingerprint, but record the new configuration
+    let fingerprints = read_embedded_fingerprint(path, format, password)?.map(|old| {
+        let new = SourceFingerprint {
+            hozon_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_digest: config_digest(config),
+            content_hash: old.content_hash.clone(),
+            series_digest: Some(series_digest(&config.metadata)),
+        };
+        (old.to_string(), new.to_string())
+    });
+    let refresh_fingerprint = |text: String| match &fingerprints {
+        Some((old, new)) => text.replace(old, new),
+        None => text,
+    };
+
+    match format {
+        FileFormat::Cbz => rewrite_entry(path, COMIC_INFO_ENTRY, password, |xml| {
+            refresh_fingerprint(retag_comic_info(xml, &config.metadata))
+        }),
+        FileFormat::Epub | FileFormat::Kepub => rewrite_entry(path, OPF_PATH, None, |opf| {
+            refresh_fingerprint(retag_opf(opf, config))
+        }),
+        FileFormat::Mobi => Err(Error::Unsupported("Retagging MOBI output".to_string())),
+        FileFormat::Cb7 => Err(Error::Unsupported("Retagging CB7 output".to_string())),
+        FileFormat::Directory => Err(Error::Unsupported(
+            "Retagging directory output, which has no metadata".to_string(),
+        )),
+    }
+}
+
+/// Replaces the descriptive fields of a ComicInfo.xml with `metadata`. The volume number,
+/// page count, page bookmarks and the chapters recorded in the notes are kept; so is the
+/// release date if `metadata` has none.
+fn retag_comic_info(xml: &str, metadata: &EbookMetadata) -> String {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    let authors = metadata.authors.join(", ");
+    let mut fields = vec![
+        ("Title", metadata.title.clone()),
+        ("Series", optional(&metadata.series)),
+        ("Writer", authors.clone()),
+        ("Penciller", authors.clone()),
+        ("Inker", authors.clone()),
+        ("Colorist", authors.clone()),
+        ("Letterer", authors.clone()),
+        ("CoverArtist", authors),
+        ("Publisher", optional(&metadata.publisher)),
+        ("Genre", optional(&metadata.genre)),
+        ("Web", optional(&metadata.web)),
+        ("Language", metadata.language.clone()),
+        ("Summary", optional(&metadata.description)),
+    ];
+    if let Some(release_date) = metadata.release_date {
+        fields.push(("Year", release_date.year().to_string()));
+        fields.push(("Month", release_date.month().to_string()));
+        fields.push(("Day", release_date.day().to_string()));
+    }
+
+    let mut xml = xml.to_string();
+    for (name, value) in fields {
+        xml = set_element_text(&xml, name, &escape_xml(&value));
+    }
+    if let Some(notes) = element_raw_text(&xml, "Notes") {
+        xml = set_element_text(&xml, "Notes", &escape_xml(&retag_notes(&notes, metadata)));
+    }
+
+    let without_gtin = Regex::new(r"(?s)[ \t]*<GTIN>.*?</GTIN>\r?\n?")
+        .unwrap()
+        .replace_all(&xml, "")
+        .into_owned();
+    insert_before(&without_gtin, "</ComicInfo>", &gtin_element(metadata))
+}
+
+/// Replaces tags, identifiers, rights and custom fields in the (unescaped) notes of a
+/// ComicInfo.xml. Notes in another format than [`NotesFormat::Lines`] or
+/// [`NotesFormat::Json`](crate::NotesFormat::Json) are kept.
+///
+/// [`NotesFormat::Lines`]: crate::NotesFormat::Lines
+fn retag_notes(notes: &str, metadata: &EbookMetadata) -> String {
+    if notes.trim_start().starts_with('{') {
+        let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(notes.trim()) else {
+            return notes.to_string();
+        };
+        if let Value::Object(fields) = metadata_notes_json(metadata) {
+            object.extend(fields);
+        }
+        return Value::Object(object).to_string();
+    }
+
+    // The metadata lines run from `Tags` up to the chapter list
+    match (
+        notes.find("    Tags:"),
+        notes.find("    Chapters included:"),
+    ) {
+        (Some(start), Some(end)) if start < end => format!(
+            "{}{}{}",
+            &notes[..start],
+            metadata_note_lines(metadata),
+            &notes[end..]
+        ),
+        _ => notes.to_string(),
+    }
+}
+
+/// Replaces the metadata elements of an OPF package document with the metadata of
+/// `config`. The package identifier, cover, accessibility metadata and the volume and
+/// part numbers of the title are kept; so is the publication date if the metadata has
+/// none. The modification date is set to now.
+fn retag_opf(opf: &str, config: &HozonConfig) -> String {
+    let metadata = &config.metadata;
+    let version = if opf.contains("<package version=\"2.0\"") {
+        EpubVersion::V2
+    } else {
+        EpubVersion::V3
+    };
+    let old_title = element_text(opf, "dc:title").unwrap_or_default();
+    let old_language = element_text(opf, "dc:language").unwrap_or_default();
+    let (volume_number, part_number) = title_numbers(opf, &old_title, &old_language, config);
+
+    // Elements rendered from the metadata, by epub-builder or Hozon
+    let mut removed = vec![
+        r"<dc:(?:title|language|creator|description|subject|rights|publisher)\b[^>]*>.*?</dc:\w+>"
+            .to_string(),
+        r#"<dc:identifier id="hozon-id-\d+"[^>]*>.*?</dc:identifier>"#.to_string(),
+        r##"<meta refines="#(?:epub-creator-\d+|hozon-series|hozon-id-\d+)"[^>]*>.*?</meta>"##
+            .to_string(),
+        r#"<meta property="belongs-to-collection" id="hozon-series">.*?</meta>"#.to_string(),
+        r#"<meta property="dcterms:modified">.*?</meta>"#.to_string(),
+    ];
+    if metadata.release_date.is_some() {
+        removed.push(r"<dc:date\b[^>]*>.*?</dc:date>".to_string());
+    }
+    let mut retagged = opf.to_string();
+    for pattern in removed {
+        retagged = Regex::new(&format!(r"(?s)\n[ \t]*{}", pattern))
+            .unwrap()
+            .replace_all(&retagged, "")
+            .into_owned();
+    }
+    // `<meta name content>` pairs are custom fields, unless Hozon or epub-builder wrote them
+    retagged = Regex::new(r#"\n[ \t]*<meta name="([^"]*)" content="[^"]*"\s*/>"#)
+        .unwrap()
+        .replace_all(&retagged, |captures: &Captures| {
+            let name = unescape_xml(&captures[1]);
+            let is_kept = name == "cover" || name == FINGERPRINT_KEY || name.starts_with("schema:");
+            if is_kept {
+                captures[0].to_string()
+            } else {
+                String::new()
+            }
+        })
+        .into_owned();
+
+    let mut elements = vec![
+        format!(
+            "<dc:title>{}</dc:title>",
+            escape_xml(&epub_title(
+                metadata,
+                volume_number,
+                part_number,
+                &config.volume_label
+            ))
+        ),
+        format!(
+            "<dc:language>{}</dc:language>",
+            escape_xml(&metadata.language)
+        ),
+    ];
+    for (index, author) in metadata.authors.iter().enumerate() {
+        match version {
+            EpubVersion::V2 => elements.push(format!(
+                "<dc:creator opf:role=\"aut\">{}</dc:creator>",
+                escape_xml(author)
+            )),
+            EpubVersion::V3 => {
+                elements.push(format!(
+                    "<dc:creator id=\"epub-creator-{}\">{}</dc:creator>",
+                    index,
+                    escape_xml(author)
+                ));
+                elements.push(format!(
+                    "<meta refines=\"#epub-creator-{}\" property=\"role\" scheme=\"marc:relators\">aut</meta>",
+                    index
+                ));
+            }
+        }
+    }
+    if version == EpubVersion::V3 {
+        elements.push(format!(
+            "<meta property=\"dcterms:modified\">{}</meta>",
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        elements.push(format!(
+            "<dc:description>{}</dc:description>",
+            escape_xml(description)
+        ));
+    }
+    for tag in &metadata.tags {
+        elements.push(format!("<dc:subject>{}</dc:subject>", escape_xml(tag)));
+    }
+    if let Some(rights) = &metadata.rights {
+        elements.push(format!("<dc:rights>{}</dc:rights>", escape_xml(rights)));
+    }
+    if let Some(release_date) = metadata.release_date {
+        elements.push(format!(
+            "<dc:date>{}</dc:date>",
+            release_date.format("%Y-%m-%dT%H:%M:%SZ")
+        ));
+    }
+    elements.extend(metadata_elements(metadata, volume_number, version));
+
+    let block: String = elements
+        .iter()
+        .map(|element| format!("    {}\n", element))
+        .collect();
+    insert_before(&retagged, "  </metadata>", &block)
+}
+
+/// The volume and part number of an EPUB, from its series position or the end of its
+/// title (`... Vol 2 Part 1`) as rendered with the volume label of `config`.
+fn title_numbers(
+    opf: &str,
+    title: &str,
+    language: &str,
+    config: &HozonConfig,
+) -> (Option<usize>, Option<usize>) {
+    let (title, part_number) = match Regex::new(r" Part (\d+)$").unwrap().captures(title) {
+        Some(captures) => (
+            &title[..captures.get(0).unwrap().start()],
+            captures[1].parse().ok(),
+        ),
+        None => (title, None),
+    };
+    let volume_number = series_position(opf).or_else(|| {
+        Regex::new(r"\d+")
+            .unwrap()
+            .find_iter(title)
+            .filter_map(|number| number.as_str().parse::<usize>().ok())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .find(|&number| {
+                let label = config.volume_label.format_short(number, language);
+                title.ends_with(&format!(" {}", label))
+            })
+    });
+    (volume_number, part_number)
+}
+
+/// Replaces the text of the first `<name>` element with `text` (already escaped), or
+/// adds the element if there is none.
+fn set_element_text(xml: &str, name: &str, text: &str) -> String {
+    let pattern = format!(
+        r"(?s)(<{0}(?:\s[^>]*)?>).*?(</{0}\s*>)",
+        regex::escape(name)
+    );
+    let element = Regex::new(&pattern).unwrap();
+    match element.captures(xml) {
+        Some(captures) => {
+            let range = captures.get(0).unwrap().range();
+            format!(
+                "{}{}{}{}{}",
+                &xml[..range.start],
+                &captures[1],
+                text,
+                &captures[2],
+                &xml[range.end..]
+            )
+        }
+        None if text.is_empty() => xml.to_string(),
+        None => insert_before(
+            xml,
+            "</ComicInfo>",
+            &format!("  <{0}>{1}</{0}>\n", name, text),
+        ),
+    }
+}
+
+/// Inserts `text` right before the last occurrence of `marker`, or returns `xml`
+/// unchanged if there is none.
+fn insert_before(xml: &str, marker: &str, text: &str) -> String {
+    match xml.rfind(marker) {
+        Some(index) => format!("{}{}{}", &xml[..index], text, &xml[index..]),
+        None => xml.to_string(),
+    }
+}
+
+/// Rewrites the text entry `entry_name` of the zip archive at `path` with `rewrite`,
+/// copying every other entry without recompressing it. The entry keeps its compression,
+/// modification time, permissions and encryption. Blocking.
+fn rewrite_entry(
+    path: &Path,
+    entry_name: &str,
+    password: Option<&str>,
+    rewrite: impl FnOnce(&str) -> String,
+) -> Result<()> {
+    let mut archive = ZipArchive::new(std::fs::File::open(path)?)?;
+    let Some(index) = archive.index_for_name(entry_name) else {
+        return Err(Error::NotFound(format!(
+            "{} in '{}'",
+            entry_name,
+            path_to_string_lossy(path)
+        )));
+    };
+
+    let (content, options, encrypted) = {
+        let is_encrypted = archive.by_index_raw(index)?.encrypted();
+        let mut entry = match password {
+            Some(password) if is_encrypted => {
+                archive.by_index_decrypt(index, password.as_bytes())?
+            }
+            _ => archive.by_index(index)?,
+        };
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        let mut options = SimpleFileOptions::default().compression_method(entry.compression());
+        if let Some(time) = entry.last_modified() {
+            options = options.last_modified_time(time);
+        }
+        if let Some(mode) = entry.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+        (content, options, is_encrypted)
+    };
+    let content = rewrite(&content);
+
+    // Write a sibling first so the original stays intact if anything fails
+    let temporary = path.with_extension("retag.tmp");
+    let written = (|| -> Result<()> {
+        let file = retry_while_locked(&temporary, "create retagged archive", || {
+            std::fs::File::create(&temporary)
+        })?;
+        let mut writer = ZipWriter::new(file);
+        for i in 0..archive.len() {
+            if i != index {
+                writer.raw_copy_file(archive.by_index_raw(i)?)?;
+                continue;
+            }
+            match password {
+                Some(password) if encrypted => writer.start_file(
+                    entry_name,
+                    options.with_aes_encryption(AesMode::Aes256, password),
+                )?,
+                _ => writer.start_file(entry_name, options)?,
+            }
+            writer.write_all(content.as_bytes())?;
+        }
+        writer.finish()?;
+        Ok(())
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e);
+    }
+    retry_while_locked(path, "replace retagged archive", || {
+        std::fs::rename(&temporary, path)
+    })
+}
@@ -2,11 +2,14 @@
 //!
 //! This module provides utilities for handling file paths safely, especially on Windows
 //! where long paths and special characters can cause issues. It includes functions for
-//! path validation, UTF-8 conversion, and Windows long path support.
+//! path validation, UTF-8 conversion, Windows long path support, and retrying writes to
+//! files another process briefly holds open.
 
 use crate::error::{Error, Result};
 
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
 
 /// Maximum path length for Windows without long path support
 const WINDOWS_MAX_PATH: usize = 260;
@@ -14,6 +17,12 @@ const WINDOWS_MAX_PATH: usize = 260;
 /// Windows long path prefix
 const WINDOWS_LONG_PATH_PREFIX: &str = r"\\?\";
 
+/// Number of attempts [`retry_while_locked`] makes before giving up
+const LOCKED_FILE_ATTEMPTS: u32 = 6;
+
+/// Delay before the first retry of a locked file, doubled for every further retry
+const LOCKED_FILE_INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+
 /// Safely converts a path to a string, handling UTF-8 conversion errors gracefully.
 ///
 /// # Arguments
@@ -116,6 +125,10 @@ pub fn validate_path(path: &Path) -> Result<()> {
 
 /// Prepares a path for Windows long path support if needed.
 ///
+/// Long paths are made absolute and given the `\\?\` prefix (`\\?\UNC\` for network
+/// shares). The path doesn't need to exist, so this also works for output files about to
+/// be created.
+///
 /// # Arguments
 ///
 /// * `path` - The path to prepare
@@ -131,16 +144,25 @@ pub fn prepare_long_path(path: &Path) -> Result<PathBuf> {
         && path_str.len() > WINDOWS_MAX_PATH
         && !path_str.starts_with(WINDOWS_LONG_PATH_PREFIX)
     {
-        // Convert to absolute path first
-        let absolute_path = path.canonicalize().map_err(|e| {
-            Error::InvalidPath(
-                path.to_path_buf(),
-                format!("Cannot canonicalize path: {}", e),
-            )
-        })?;
+        // Convert to absolute path first; output files don't exist yet
+        let absolute_path = path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .map_err(|e| {
+                Error::InvalidPath(
+                    path.to_path_buf(),
+                    format!("Cannot make path absolute: {}", e),
+                )
+            })?;
 
         let absolute_str = path_to_string_safe(&absolute_path)?;
-        let long_path = format!("{}{}", WINDOWS_LONG_PATH_PREFIX, absolute_str);
+        let long_path = if absolute_str.starts_with(WINDOWS_LONG_PATH_PREFIX) {
+            absolute_str // `canonicalize` already returns verbatim paths
+        } else if let Some(share) = absolute_str.strip_prefix(r"\\") {
+            format!(r"{}UNC\{}", WINDOWS_LONG_PATH_PREFIX, share)
+        } else {
+            format!("{}{}", WINDOWS_LONG_PATH_PREFIX, absolute_str)
+        };
         Ok(PathBuf::from(long_path))
     } else {
         Ok(path.to_path_buf())
@@ -169,12 +191,48 @@ pub fn extract_number_from_filename_safe(path: &Path, regex: &regex::Regex) -> O
             if capture.contains('.') {
                 capture.parse::<f64>().ok()
             } else {
-                capture.trim_start_matches('0').parse::<f64>().ok()
+                match capture.trim_start_matches('0') {
+                    "" if !capture.is_empty() => Some(0.0),
+                    digits => digits.parse::<f64>().ok(),
+                }
             }
         })
 }
 
-/// Safely compares two paths by their numeric content.
+/// Extracts the letter right after the number [`extract_number_from_filename_safe`]
+/// finds, marking a page split into parts (`12a.jpg`, `12b.jpg`). The letter must end the
+/// word, so `12_cover.jpg` or `12page.jpg` have no suffix.
+///
+/// # Arguments
+///
+/// * `path` - The path to extract the suffix from
+/// * `regex` - The regex pattern used for number extraction
+///
+/// # Returns
+///
+/// * `Option<char>` - The lowercased suffix letter, or None if the number has none
+pub fn extract_number_suffix_safe(path: &Path, regex: &regex::Regex) -> Option<char> {
+    let file_name = get_file_name_lossy(path);
+    let captures = regex.captures_iter(&file_name).last()?;
+    let number = captures.get(1).or_else(|| captures.get(0))?;
+    // A trailing dot belongs to the extension (`12.jpg`), not to the number
+    if number.as_str().ends_with('.') {
+        return None;
+    }
+    let mut rest = file_name[number.end()..].chars();
+    match (rest.next(), rest.next()) {
+        (Some(letter), next)
+            if letter.is_ascii_alphabetic() && !next.is_some_and(|c| c.is_alphanumeric()) =>
+        {
+            Some(letter.to_ascii_lowercase())
+        }
+        _ => None,
+    }
+}
+
+/// Safely compares two paths by their numeric content. Equal numbers are ordered by
+/// their [suffix](extract_number_suffix_safe), so split pages sort as `12`, `12a`,
+/// `12b`, `13`.
 ///
 /// # Arguments
 ///
@@ -196,6 +254,62 @@ pub fn compare_paths_by_number_safe(
     a_num
         .partial_cmp(&b_num)
         .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| {
+            extract_number_suffix_safe(a, regex).cmp(&extract_number_suffix_safe(b, regex))
+        })
+}
+
+/// Compares two names the way file managers do: runs of digits compare by their numeric
+/// value, everything else character by character, ignoring case.
+///
+/// # Arguments
+///
+/// * `a` - First name to compare
+/// * `b` - Second name to compare
+///
+/// # Returns
+///
+/// * `std::cmp::Ordering` - The comparison result
+pub fn compare_names_natural(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let x_digits = take_digits(&mut a_chars);
+                let y_digits = take_digits(&mut b_chars);
+                let (x_trimmed, y_trimmed) = (
+                    x_digits.trim_start_matches('0'),
+                    y_digits.trim_start_matches('0'),
+                );
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
 }
 
 /// Checks if a filename starts with a dot (hidden file) using safe conversion.
@@ -235,6 +349,64 @@ pub fn sanitize_filename(filename: &str) -> String {
         .collect()
 }
 
+/// Maximum length in bytes of one segment of an archive entry name. Longer names are
+/// truncated, since many extraction tools and file systems reject them.
+const MAX_ENTRY_SEGMENT_BYTES: usize = 255;
+
+/// Sanitizes the name of an entry inside a generated archive (CBZ or EPUB).
+///
+/// Entry names are independent of host paths: they always use `/` as separator, may not
+/// escape the archive root, and are read by many different readers. The name is
+/// normalized to Unicode NFC, backslashes become separators, empty, `.` and `..`
+/// segments are dropped, characters that are invalid in Windows file names are replaced
+/// with `_`, trailing dots and spaces are trimmed, and every segment is truncated to 255
+/// bytes, keeping its extension.
+///
+/// # Arguments
+///
+/// * `name` - The entry name to sanitize
+///
+/// # Returns
+///
+/// * `String` - The sanitized entry name
+pub fn sanitize_entry_name(name: &str) -> String {
+    let normalized: String = name.nfc().collect();
+    normalized
+        .split(['/', '\\'])
+        .filter(|segment| !matches!(*segment, "" | "." | ".."))
+        .map(|segment| {
+            let cleaned: String = segment
+                .chars()
+                .map(|c| match c {
+                    '<' | '>' | '"' | '|' | '?' | '*' | ':' => '_',
+                    c if c.is_control() => '_',
+                    c => c,
+                })
+                .collect();
+            truncate_segment(cleaned.trim_end_matches(['.', ' ']))
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Truncates an entry name segment to [`MAX_ENTRY_SEGMENT_BYTES`], keeping the extension.
+fn truncate_segment(segment: &str) -> String {
+    if segment.len() <= MAX_ENTRY_SEGMENT_BYTES {
+        return segment.to_string();
+    }
+    let extension = segment
+        .rfind('.')
+        .map(|index| &segment[index..])
+        .filter(|extension| extension.len() <= 16)
+        .unwrap_or("");
+    let mut end = MAX_ENTRY_SEGMENT_BYTES - extension.len();
+    while !segment.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &segment[..end], extension)
+}
+
 /// Normalizes a path for consistent handling across platforms.
 ///
 /// # Arguments
@@ -245,32 +417,124 @@ pub fn sanitize_filename(filename: &str) -> String {
 ///
 /// * `Result<PathBuf>` - The normalized path
 pub fn normalize_path(path: &Path) -> Result<PathBuf> {
-    // First validate the path
-    validate_path(path)?;
-
     // Try to canonicalize the path to resolve any relative components
     // and get the absolute path
-    match path.canonicalize() {
+    let normalized = match path.canonicalize() {
         Ok(canonical) => {
             // If canonicalization succeeds, prepare for long path support if needed
-            prepare_long_path(&canonical)
+            prepare_long_path(&canonical)?
+        }
+        Err(e) if path.exists() => {
+            return Err(Error::InvalidPath(
+                path.to_path_buf(),
+                format!("Cannot access path: {}", e),
+            ));
         }
-        Err(e) => {
-            // If canonicalization fails (e.g., path doesn't exist),
-            // just return the original path if it's valid
-            if path.exists() {
-                Err(Error::InvalidPath(
+        // Non-existent paths (e.g. output files) still need the long path prefix to be
+        // creatable
+        Err(_) => prepare_long_path(path)?,
+    };
+
+    // Validate after preparing, since prefixed paths are exempt from the length limit
+    validate_path(&normalized)?;
+    Ok(normalized)
+}
+
+/// Whether `error` is a Windows sharing or lock violation, raised while another process
+/// (typically an antivirus scanner or the search indexer) has the file open.
+pub fn is_sharing_violation(error: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(error.raw_os_error(), Some(32 | 33))
+}
+
+/// Runs a blocking file operation on `path`, retrying with exponential backoff (about
+/// 1.5 seconds in total) while the file is locked by another process.
+///
+/// # Arguments
+///
+/// * `path` - The file the operation works on, for error messages
+/// * `action` - What the operation does, e.g. `"create CBZ file"`
+/// * `operation` - The operation to run
+///
+/// # Returns
+///
+/// * `Result<T>` - The result of the operation, [`Error::FileLocked`] if the file stayed
+///   locked, or an I/O error naming `action` and `path`
+pub(crate) fn retry_while_locked<T>(
+    path: &Path,
+    action: &str,
+    mut operation: impl FnMut() -> std::io::Result<T>,
+) -> Result<T> {
+    let mut backoff = LOCKED_FILE_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if is_sharing_violation(&e) && attempt < LOCKED_FILE_ATTEMPTS => {
+                log::debug!(
+                    "'{}' is locked by another process, retrying in {:?}",
+                    path_to_string_lossy(path),
+                    backoff
+                );
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) if is_sharing_violation(&e) => {
+                return Err(Error::FileLocked(
                     path.to_path_buf(),
-                    format!("Cannot access path: {}", e),
-                ))
-            } else {
-                // For non-existent paths (e.g., output paths), just validate and return
-                Ok(path.to_path_buf())
+                    format!("failed to {} after {} attempts: {}", action, attempt, e),
+                ));
+            }
+            Err(e) => {
+                return Err(Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to {} '{}': {}",
+                        action,
+                        path_to_string_lossy(path),
+                        e
+                    ),
+                )));
             }
         }
     }
 }
 
+/// Sets the Unix permission bits of the file at `path` to exactly `mode`, regardless of
+/// the process umask. Does nothing on platforms without Unix permissions.
+///
+/// # Arguments
+///
+/// * `path` - The file to change
+/// * `mode` - The permission bits, e.g. `0o644`
+///
+/// # Returns
+///
+/// * `Result<()>` - Ok, or an I/O error naming `path`
+pub fn set_file_permissions(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to set permissions of '{}': {}",
+                    path_to_string_lossy(path),
+                    e
+                ),
+            ))
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,6 +574,48 @@ mod tests {
         assert_eq!(result, Some(123.0));
     }
 
+    #[test]
+    fn test_number_suffixes() {
+        use std::cmp::Ordering;
+
+        let regex = Regex::new(r"\d+\.?\d*").unwrap();
+        let suffix = |name: &str| extract_number_suffix_safe(Path::new(name), &regex);
+        assert_eq!(suffix("12a.jpg"), Some('a'));
+        assert_eq!(suffix("page_12B.png"), Some('b'));
+        assert_eq!(suffix("12.jpg"), None);
+        assert_eq!(suffix("12_cover.jpg"), None);
+        assert_eq!(suffix("12page.jpg"), None);
+        assert_eq!(suffix("cover.jpg"), None);
+
+        let mut pages: Vec<&Path> = ["13.jpg", "12b.jpg", "12.jpg", "12a.jpg"]
+            .into_iter()
+            .map(Path::new)
+            .collect();
+        pages.sort_by(|a, b| compare_paths_by_number_safe(a, b, &regex));
+        assert_eq!(
+            pages,
+            ["12.jpg", "12a.jpg", "12b.jpg", "13.jpg"].map(Path::new)
+        );
+        assert_eq!(
+            compare_paths_by_number_safe(Path::new("12.jpg"), Path::new("012.png"), &regex),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_compare_names_natural() {
+        use std::cmp::Ordering;
+
+        assert_eq!(compare_names_natural("page_2", "page_10"), Ordering::Less);
+        assert_eq!(compare_names_natural("a10b2", "a2b10"), Ordering::Greater);
+        assert_eq!(
+            compare_names_natural("Chapter 1", "chapter 2"),
+            Ordering::Less
+        );
+        assert_eq!(compare_names_natural("007", "7a"), Ordering::Less);
+        assert_eq!(compare_names_natural("abc", "abc"), Ordering::Equal);
+    }
+
     #[test]
     fn test_validate_path_with_invalid_chars() {
         let path = Path::new("test<invalid>path");
@@ -329,4 +635,63 @@ mod tests {
         assert_eq!(sanitize_filename("test\\file"), "test-file");
         assert_eq!(sanitize_filename("normal_file.txt"), "normal_file.txt");
     }
+
+    #[test]
+    fn test_sanitize_entry_name() {
+        assert_eq!(sanitize_entry_name("chapters\\001.jpg"), "chapters/001.jpg");
+        assert_eq!(sanitize_entry_name("/../a/./b.png"), "a/b.png");
+        assert_eq!(sanitize_entry_name("Ch: 1?/p.jpg"), "Ch_ 1_/p.jpg");
+        assert_eq!(sanitize_entry_name("Chapter 1. /p.jpg"), "Chapter 1/p.jpg");
+        // NFD "é" becomes the single NFC code point
+        assert_eq!(sanitize_entry_name("Cafe\u{301}/p.jpg"), "Caf\u{e9}/p.jpg");
+
+        let long = format!("{}.jpg", "\u{e9}".repeat(200));
+        let sanitized = sanitize_entry_name(&long);
+        assert!(sanitized.len() <= 255);
+        assert!(sanitized.ends_with(".jpg"));
+    }
+
+    #[test]
+    fn test_retry_while_locked() {
+        let path = Path::new("out/Volume 01.cbz");
+        let mut attempts = 0;
+        let result: Result<()> = retry_while_locked(path, "create archive file", || {
+            attempts += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+        });
+        assert_eq!(attempts, 1); // Only sharing violations are retried
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("Failed to create archive file"));
+
+        // A lock released before the attempts run out only delays the operation
+        let mut attempts = 0;
+        let result = retry_while_locked(path, "create archive file", || {
+            attempts += 1;
+            if cfg!(windows) && attempts < 3 {
+                Err(std::io::Error::from_raw_os_error(32))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), if cfg!(windows) { 3 } else { 1 });
+    }
+
+    #[test]
+    fn test_normalize_long_output_path() {
+        // Deep enough to exceed MAX_PATH, with every component well below 255 bytes
+        let mut dir = std::env::temp_dir().join(format!("hozon-long-{}", std::process::id()));
+        for _ in 0..4 {
+            dir.push("A Very Long Series Title That Keeps Going And Going".repeat(2));
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let output = dir.join("A Very Long Series Title - Volume 01.cbz");
+        assert!(path_to_string_lossy(&output).len() > WINDOWS_MAX_PATH);
+        let normalized = normalize_path(&output).unwrap();
+        if cfg!(windows) {
+            assert!(path_to_string_lossy(&normalized).starts_with(WINDOWS_LONG_PATH_PREFIX));
+        }
+        std::fs::write(&normalized, b"archive").unwrap();
+        assert!(output.exists());
+    }
 }
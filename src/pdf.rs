@@ -0,0 +1,355 @@
+//! Page images of scanned PDFs. Requires the `pdf` feature.
+//!
+//! Scanned comics and manga are often distributed as PDFs holding one image per page.
+//! [`PdfPages::extract`] writes the image of every page into a temporary directory, so
+//! the pages pass through the pipeline like any collected chapter;
+//! [`HozonConfig::convert_from_pdfs`](crate::HozonConfig::convert_from_pdfs) converts
+//! PDFs in one call, each PDF being a chapter.
+//!
+//! PDFs aren't rendered: pages without an image (e.g. text pages) are skipped with a
+//! warning, and a page holding several images is represented by the largest one. JPEG
+//! images are copied as they are, uncompressed and Flate-compressed gray, RGB and CMYK
+//! images are written as PNG. Other encodings (JPEG 2000, JBIG2, CCITT) are skipped.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+#[cfg(feature = "pdf")]
+use crate::path_utils::path_to_string_lossy;
+
+/// Page images extracted from a PDF into a temporary directory, which is removed together
+/// with the images on drop.
+#[derive(Debug)]
+pub struct PdfPages {
+    _directory: tempfile::TempDir, // Removed on drop
+    pages: Vec<PathBuf>,           // In page order, inside `_directory/<PDF name>/`
+}
+
+impl PdfPages {
+    /// Extracts the page images of the PDF at `pdf` into a fresh directory in the system
+    /// temp dir, named after the PDF so chapter titles are taken from it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(PdfPages)` - The page images, in page order
+    /// * `Err(Error::InvalidPath)` - The PDF can't be read or holds no usable page image
+    /// * `Err(Error::Unsupported)` - The `pdf` feature is disabled
+    pub async fn extract(pdf: &Path) -> Result<Self> {
+        let name = pdf
+            .file_stem()
+            .ok_or_else(|| Error::InvalidPath(pdf.to_path_buf(), "has no file name".to_string()))?
+            .to_os_string();
+        let pdf = pdf.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let directory = tempfile::Builder::new().prefix("hozon-pdf-").tempdir()?;
+            let chapter_dir = directory.path().join(name);
+            std::fs::create_dir_all(&chapter_dir)?;
+            let pages = extract_page_images(&pdf, &chapter_dir)?;
+            Ok(PdfPages {
+                _directory: directory,
+                pages,
+            })
+        })
+        .await
+        .map_err(|e| Error::AsyncTaskError(e.to_string()))?
+    }
+
+    /// The page images, in page order.
+    pub fn pages(&self) -> &[PathBuf] {
+        &self.pages
+    }
+}
+
+/// Writes the image of every page of `pdf` into `directory` as `0001.jpg`, `0002.png`, …
+#[cfg(feature = "pdf")]
+fn extract_page_images(pdf: &Path, directory: &Path) -> Result<Vec<PathBuf>> {
+    let invalid = |reason: String| Error::InvalidPath(pdf.to_path_buf(), reason);
+    let document =
+        lopdf::Document::load(pdf).map_err(|e| invalid(format!("Failed to read PDF: {}", e)))?;
+
+    let mut pages = Vec::new();
+    for (page_number, page_id) in document.get_pages() {
+        let skip = |reason: &str| {
+            log::warn!(
+                "Skipping page {} of '{}': {}",
+                page_number,
+                path_to_string_lossy(pdf),
+                reason
+            );
+        };
+        let images = match document.get_page_images(page_id) {
+            Ok(images) => images,
+            Err(e) => {
+                skip(&e.to_string());
+                continue;
+            }
+        };
+        let Some(image) = images
+            .iter()
+            .max_by_key(|image| image.width.saturating_mul(image.height))
+        else {
+            skip("it holds no image");
+            continue;
+        };
+        match encode_page_image(&document, image) {
+            Ok((extension, bytes)) => {
+                let path = directory.join(format!("{:04}.{}", page_number, extension));
+                std::fs::write(&path, bytes)?;
+                pages.push(path);
+            }
+            Err(reason) => skip(&reason),
+        }
+    }
+
+    if pages.is_empty() {
+        return Err(invalid("the PDF holds no page images".to_string()));
+    }
+    Ok(pages)
+}
+
+#[cfg(not(feature = "pdf"))]
+fn extract_page_images(pdf: &Path, directory: &Path) -> Result<Vec<PathBuf>> {
+    let _ = (pdf, directory);
+    Err(Error::Unsupported(
+        "PDF input requires the `pdf` feature".to_string(),
+    ))
+}
+
+/// Returns the file extension and bytes of `image`, or why it can't be used.
+#[cfg(feature = "pdf")]
+fn encode_page_image(
+    document: &lopdf::Document,
+    image: &lopdf::xobject::PdfImage,
+) -> std::result::Result<(&'static str, Vec<u8>), String> {
+    let filters = image.filters.clone().unwrap_or_default();
+    match filters
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["DCTDecode"] => return Ok(("jpg", image.content.to_vec())),
+        [] | ["FlateDecode"] => {}
+        other => return Err(format!("unsupported image encoding {:?}", other)),
+    }
+
+    let (width, height) = match (u32::try_from(image.width), u32::try_from(image.height)) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+        _ => return Err("invalid image size".to_string()),
+    };
+    let channels = match image.color_space.as_deref() {
+        Some("DeviceGray") | Some("CalGray") => 1,
+        Some("DeviceRGB") | Some("CalRGB") => 3,
+        Some("DeviceCMYK") => 4,
+        other => return Err(format!("unsupported color space {:?}", other)),
+    };
+    let bits = image.bits_per_component.unwrap_or(8);
+    if bits != 8 && !(bits == 1 && channels == 1) {
+        return Err(format!("unsupported {} bits per component", bits));
+    }
+
+    // The expected size bounds decompression
+    let row_bytes = (width as usize * channels * bits as usize).div_ceil(8);
+    let expected = row_bytes * height as usize;
+    let samples = document
+        .get_object(image.id)
+        .and_then(lopdf::Object::as_stream)
+        .and_then(|stream| stream.get_plain_content_with_limit(expected))
+        .map_err(|e| e.to_string())?;
+    if samples.len() < expected {
+        return Err("truncated image data".to_string());
+    }
+
+    let pixels = match (channels, bits) {
+        (1, 1) => {
+            let pixels = samples
+                .chunks(row_bytes)
+                .take(height as usize)
+                .flat_map(|row| {
+                    (0..width as usize).map(move |x| {
+                        if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                            255
+                        } else {
+                            0
+                        }
+                    })
+                })
+                .collect();
+            image::DynamicImage::ImageLuma8(gray_buffer(width, height, pixels)?)
+        }
+        (1, _) => image::DynamicImage::ImageLuma8(gray_buffer(
+            width,
+            height,
+            samples[..expected].to_vec(),
+        )?),
+        (3, _) => {
+            image::DynamicImage::ImageRgb8(rgb_buffer(width, height, samples[..expected].to_vec())?)
+        }
+        _ => {
+            let rgb = samples[..expected]
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - u16::from(cmyk[3]);
+                    let channel = move |c: u8| ((255 - u16::from(c)) * k / 255) as u8;
+                    [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2])]
+                })
+                .collect();
+            image::DynamicImage::ImageRgb8(rgb_buffer(width, height, rgb)?)
+        }
+    };
+
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    pixels
+        .write_to(&mut bytes, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(("png", bytes.into_inner()))
+}
+
+#[cfg(feature = "pdf")]
+fn gray_buffer(
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+) -> std::result::Result<image::GrayImage, String> {
+    image::GrayImage::from_raw(width, height, pixels).ok_or_else(|| "invalid image data".into())
+}
+
+#[cfg(feature = "pdf")]
+fn rgb_buffer(
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+) -> std::result::Result<image::RgbImage, String> {
+    image::RgbImage::from_raw(width, height, pixels).ok_or_else(|| "invalid image data".into())
+}
+
+#[cfg(all(test, feature = "pdf"))]
+mod tests {
+    use super::*;
+    use lopdf::{Document, Object, Stream, dictionary};
+
+    /// Writes a PDF with a JPEG page, an uncompressed RGB page and a page without images.
+    fn write_pdf(path: &Path) {
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(4, 6))
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let images = [
+            Some(Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => 4,
+                    "Height" => 6,
+                    "ColorSpace" => "DeviceRGB",
+                    "BitsPerComponent" => 8,
+                    "Filter" => "DCTDecode",
+                },
+                jpeg.into_inner(),
+            )),
+            Some(Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => 2,
+                    "Height" => 3,
+                    "ColorSpace" => "DeviceRGB",
+                    "BitsPerComponent" => 8,
+                },
+                vec![200; 2 * 3 * 3],
+            )),
+            None,
+        ];
+        let kids: Vec<Object> = images
+            .into_iter()
+            .map(|image| {
+                let resources = match image {
+                    Some(image) => {
+                        let image_id = document.add_object(image);
+                        dictionary! { "XObject" => dictionary! { "Im0" => image_id } }
+                    }
+                    None => dictionary! {},
+                };
+                let content_id = document.add_object(Stream::new(
+                    dictionary! {},
+                    b"q 4 0 0 6 0 0 cm /Im0 Do Q".to_vec(),
+                ));
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "MediaBox" => vec![0.into(), 0.into(), 4.into(), 6.into()],
+                        "Resources" => resources,
+                        "Contents" => content_id,
+                    })
+                    .into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document.save(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_images_are_extracted_in_order() {
+        let dir = std::env::temp_dir().join(format!("hozon-pdf-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("Chapter 1.pdf");
+        write_pdf(&pdf);
+
+        let extracted = PdfPages::extract(&pdf).await.unwrap();
+        let names: Vec<_> = extracted
+            .pages()
+            .iter()
+            .map(|page| page.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["0001.jpg", "0002.png"]);
+        assert!(
+            extracted.pages()[0]
+                .parent()
+                .unwrap()
+                .ends_with("Chapter 1")
+        );
+        let png = image::open(&extracted.pages()[1]).unwrap();
+        assert_eq!((png.width(), png.height()), (2, 3));
+
+        let directory = extracted.pages()[0]
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        drop(extracted);
+        assert!(!directory.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_pdf_is_rejected() {
+        let dir =
+            std::env::temp_dir().join(format!("hozon-pdf-test-invalid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("broken.pdf");
+        std::fs::write(&pdf, b"not a pdf").unwrap();
+
+        assert!(matches!(
+            PdfPages::extract(&pdf).await,
+            Err(Error::InvalidPath(..))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,464 @@
+//! Photo album support: ordering and grouping pages by capture date.
+//!
+//! Manga and comic sources encode the reading order in their file names, but camera
+//! files (`IMG_4711.JPG`, `DSC00012.jpg`) don't. With [`PhotoAlbum`] configured, pages
+//! are ordered by the capture time recorded in their EXIF data and regrouped into
+//! chapters per day or per event, titled by date. Together with
+//! [`AltTextSource::EmbeddedCaptions`](crate::AltTextSource::EmbeddedCaptions), which
+//! turns captions written by photo managers into alt text, this makes photo collections
+//! convert without any renaming.
+//!
+//! Embedded metadata is read from JPEG, PNG (`eXIf` chunk) and WebP (`EXIF` chunk) files.
+//! Pages without a capture date keep their collected order after all dated pages.
+
+use chrono::{DateTime, NaiveDateTime};
+use rayon::prelude::*;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// How photos are grouped into chapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PhotoGrouping {
+    /// One chapter per calendar day (default).
+    #[default]
+    Day,
+    /// A new chapter starts whenever more than `gap_minutes` pass between two
+    /// consecutive photos, so a trip or a party ends up in one chapter.
+    Event { gap_minutes: u32 },
+    /// Keep the collected chapters (e.g. one folder per event). Photos are sorted within
+    /// each chapter, and chapters by their earliest photo.
+    Folder,
+}
+
+/// Options for converting photo collections, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhotoAlbum {
+    /// How photos are grouped into chapters.
+    pub grouping: PhotoGrouping,
+    /// [`chrono` format string](chrono::format::strftime) for chapter titles, e.g.
+    /// `"%A, %B %-d, %Y"`. `None` uses `%Y-%m-%d` for [`PhotoGrouping::Day`] and
+    /// `%Y-%m-%d %H:%M` for [`PhotoGrouping::Event`]. [`PhotoGrouping::Folder`] keeps
+    /// the directory names unless a format is set.
+    pub chapter_title_format: Option<String>,
+}
+
+/// Title of the chapter holding photos without a capture date.
+const UNDATED_CHAPTER_TITLE: &str = "Undated";
+
+/// JPEG metadata segments precede the image data, so reading a prefix is enough.
+const JPEG_METADATA_PREFIX: u64 = 256 * 1024;
+
+/// Placeholders some cameras write into `ImageDescription` instead of leaving it empty.
+const CAMERA_PLACEHOLDER_CAPTIONS: &[&str] = &[
+    "OLYMPUS DIGITAL CAMERA",
+    "SONY DSC",
+    "DIGITAL CAMERA",
+    "KONICA MINOLTA DIGITAL CAMERA",
+];
+
+impl PhotoAlbum {
+    /// Checks that the chapter title format is a valid `chrono` format string.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(format) = &self.chapter_title_format {
+            let mut probe = String::new();
+            write!(probe, "{}", DateTime::UNIX_EPOCH.naive_utc().format(format)).map_err(|_| {
+                Error::Other(format!("Invalid photo chapter title format '{}'", format))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Orders the collected pages by capture time and regroups them into chapters
+    /// according to [`PhotoAlbum::grouping`]. Blocking; reads the page files.
+    pub(crate) fn arrange(&self, chapters: Vec<Vec<PathBuf>>) -> Vec<Vec<PathBuf>> {
+        if self.grouping == PhotoGrouping::Folder {
+            let mut chapters: Vec<(Option<NaiveDateTime>, Vec<PathBuf>)> = chapters
+                .into_iter()
+                .map(|pages| {
+                    let pages = sort_by_capture_time(pages);
+                    let earliest = pages.first().and_then(|(time, _)| *time);
+                    (earliest, pages.into_iter().map(|(_, page)| page).collect())
+                })
+                .collect();
+            chapters.sort_by_key(|(time, _)| (time.is_none(), *time));
+            return chapters.into_iter().map(|(_, pages)| pages).collect();
+        }
+
+        let pages = sort_by_capture_time(chapters.into_iter().flatten().collect());
+        let mut grouped: Vec<Vec<PathBuf>> = Vec::new();
+        let mut previous: Option<Option<NaiveDateTime>> = None;
+        for (time, page) in pages {
+            let starts_chapter = match (previous, time) {
+                (None, _) => true,
+                (Some(Some(previous)), Some(time)) => match self.grouping {
+                    PhotoGrouping::Event { gap_minutes } => {
+                        (time - previous).num_minutes() > i64::from(gap_minutes)
+                    }
+                    _ => time.date() != previous.date(),
+                },
+                (Some(previous), time) => previous.is_some() != time.is_some(),
+            };
+            if starts_chapter {
+                grouped.push(Vec::new());
+            }
+            if let Some(chapter) = grouped.last_mut() {
+                chapter.push(page);
+            }
+            previous = Some(time);
+        }
+        grouped
+    }
+
+    /// Returns the title of a chapter arranged by [`PhotoAlbum::arrange`], or `None` if
+    /// the directory name should be used. Blocking; reads the first page.
+    pub(crate) fn chapter_title(&self, pages: &[PathBuf]) -> Option<String> {
+        let format = match (&self.chapter_title_format, self.grouping) {
+            (Some(format), _) => format.as_str(),
+            (None, PhotoGrouping::Day) => "%Y-%m-%d",
+            (None, PhotoGrouping::Event { .. }) => "%Y-%m-%d %H:%M",
+            (None, PhotoGrouping::Folder) => return None,
+        };
+        let title = match pages.first().and_then(|page| capture_time(page)) {
+            Some(time) => time.format(format).to_string(),
+            None => UNDATED_CHAPTER_TITLE.to_string(),
+        };
+        Some(title)
+    }
+}
+
+/// Sorts pages by capture time; undated pages keep their order after all dated ones.
+pub(crate) fn sort_by_capture_time(pages: Vec<PathBuf>) -> Vec<(Option<NaiveDateTime>, PathBuf)> {
+    let mut timed: Vec<(Option<NaiveDateTime>, PathBuf)> = pages
+        .into_par_iter()
+        .map(|page| (capture_time(&page), page))
+        .collect();
+    timed.sort_by_key(|(time, _)| (time.is_none(), *time));
+    timed
+}
+
+/// Returns the capture time of a photo (EXIF `DateTimeOriginal`, falling back to
+/// `DateTime`). Blocking; `None` if the file has no readable date.
+pub(crate) fn capture_time(path: &Path) -> Option<NaiveDateTime> {
+    let bytes = read_metadata_bytes(path)?;
+    let exif = find_segments(&bytes).exif?;
+    let tiff = Tiff::new(exif)?;
+    let exif_ifd = tiff.ifd_entry(tiff.first_ifd()?, TAG_EXIF_IFD);
+    let original = exif_ifd
+        .and_then(|entry| tiff.entry_u32(&entry))
+        .and_then(|offset| tiff.ifd_entry(offset as usize, TAG_DATE_TIME_ORIGINAL));
+    [original, tiff.ifd_entry(tiff.first_ifd()?, TAG_DATE_TIME)]
+        .into_iter()
+        .flatten()
+        .find_map(|entry| parse_exif_date(&tiff.entry_text(&entry)?))
+}
+
+/// Returns the caption of a photo: IPTC `Caption-Abstract`, EXIF `ImageDescription`,
+/// Windows `XPComment` or EXIF `UserComment`, whichever is found first. Blocking.
+pub(crate) fn embedded_caption(path: &Path) -> Option<String> {
+    let bytes = read_metadata_bytes(path)?;
+    let segments = find_segments(&bytes);
+
+    let iptc = segments.iptc.and_then(iptc_caption);
+    let exif = segments.exif.and_then(Tiff::new);
+    let from_exif = || {
+        let tiff = exif.as_ref()?;
+        let ifd0 = tiff.first_ifd()?;
+        let description = tiff
+            .ifd_entry(ifd0, TAG_IMAGE_DESCRIPTION)
+            .and_then(|entry| tiff.entry_text(&entry))
+            .filter(|text| !CAMERA_PLACEHOLDER_CAPTIONS.contains(&text.as_str()));
+        let xp_comment = || {
+            let entry = tiff.ifd_entry(ifd0, TAG_XP_COMMENT)?;
+            Some(decode_utf16(tiff.entry_bytes(&entry)?, true))
+        };
+        let user_comment = || {
+            let offset = tiff.entry_u32(&tiff.ifd_entry(ifd0, TAG_EXIF_IFD)?)?;
+            let entry = tiff.ifd_entry(offset as usize, TAG_USER_COMMENT)?;
+            let bytes = tiff.entry_bytes(&entry)?;
+            let (charset, text) = bytes.split_at_checked(8)?;
+            Some(match charset {
+                b"UNICODE\0" => decode_utf16(text, tiff.little_endian),
+                _ => String::from_utf8_lossy(text).into_owned(),
+            })
+        };
+        description
+            .or_else(|| xp_comment().and_then(clean_text))
+            .or_else(|| user_comment().and_then(clean_text))
+    };
+    iptc.and_then(clean_text).or_else(from_exif)
+}
+
+/// Returns the JPEG preview cameras and phones embed in the EXIF data of a photo (the
+/// IFD1 thumbnail), if there is one. Blocking.
+pub(crate) fn embedded_thumbnail(path: &Path) -> Option<Vec<u8>> {
+    let bytes = read_metadata_bytes(path)?;
+    let tiff = Tiff::new(find_segments(&bytes).exif?)?;
+    let ifd1 = tiff.next_ifd(tiff.first_ifd()?)?;
+    let offset = tiff.entry_u32(&tiff.ifd_entry(ifd1, TAG_JPEG_INTERCHANGE_FORMAT)?)? as usize;
+    let length = tiff.entry_u32(&tiff.ifd_entry(ifd1, TAG_JPEG_INTERCHANGE_FORMAT_LENGTH)?)?;
+    let thumbnail = tiff
+        .data
+        .get(offset..offset.checked_add(length as usize)?)?;
+    thumbnail
+        .starts_with(&[0xFF, 0xD8])
+        .then(|| thumbnail.to_vec())
+}
+
+/// Reads the part of a file that can hold metadata: a prefix for JPEG, else everything.
+fn read_metadata_bytes(path: &Path) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(JPEG_METADATA_PREFIX)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if !bytes.starts_with(&[0xFF, 0xD8]) && bytes.len() as u64 == JPEG_METADATA_PREFIX {
+        bytes = std::fs::read(path).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Metadata blocks found in an image file.
+#[derive(Default)]
+struct Segments<'a> {
+    exif: Option<&'a [u8]>, // TIFF structure, starting with the byte order mark
+    iptc: Option<&'a [u8]>, // IPTC-IIM records
+}
+
+fn find_segments(bytes: &[u8]) -> Segments<'_> {
+    let mut segments = Segments::default();
+
+    // JPEG: APP1 holds EXIF, APP13 the Photoshop resources with IPTC
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut offset = 2;
+        while let Some(&[0xFF, marker]) = bytes.get(offset..offset + 2) {
+            match marker {
+                0xFF => offset += 1, // Fill byte
+                0xD0..=0xD8 | 0x01 => offset += 2,
+                0xD9 | 0xDA => break, // End of image, start of scan
+                _ => {
+                    let Some(length) = read_u16(bytes, offset + 2, false) else {
+                        break;
+                    };
+                    let end = (offset + 2 + length as usize).min(bytes.len());
+                    let data = bytes.get(offset + 4..end).unwrap_or_default();
+                    match marker {
+                        0xE1 => {
+                            if let Some(exif) = data.strip_prefix(b"Exif\0\0") {
+                                segments.exif.get_or_insert(exif);
+                            }
+                        }
+                        0xED => {
+                            if let Some(resources) = data.strip_prefix(b"Photoshop 3.0\0") {
+                                segments.iptc = photoshop_iptc(resources).or(segments.iptc);
+                            }
+                        }
+                        _ => {}
+                    }
+                    offset = offset + 2 + length as usize;
+                }
+            }
+        }
+    // PNG: an `eXIf` chunk
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut offset = 8;
+        while let Some(length) = read_u32(bytes, offset, false) {
+            let data = offset + 8..offset + 8 + length as usize;
+            if bytes.get(offset + 4..offset + 8) == Some(b"eXIf") {
+                segments.exif = bytes.get(data);
+                break;
+            }
+            offset = data.end + 4; // CRC
+        }
+    // WebP: an `EXIF` chunk, sometimes with the JPEG-style header
+    } else if bytes.len() > 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        let mut offset = 12;
+        while let Some(length) = read_u32(bytes, offset + 4, true) {
+            let data = offset + 8..offset + 8 + length as usize;
+            if bytes.get(offset..offset + 4) == Some(b"EXIF") {
+                segments.exif = bytes
+                    .get(data)
+                    .map(|exif| exif.strip_prefix(b"Exif\0\0").unwrap_or(exif));
+                break;
+            }
+            offset = data.end + (length as usize & 1); // Chunks are padded to even sizes
+        }
+    }
+    segments
+}
+
+/// Finds the IPTC-NAA resource (id 0x0404) among Photoshop image resources.
+fn photoshop_iptc(resources: &[u8]) -> Option<&[u8]> {
+    let mut offset = 0;
+    while resources.get(offset..offset + 4) == Some(b"8BIM") {
+        let id = read_u16(resources, offset + 4, false)?;
+        let name_length = *resources.get(offset + 6)? as usize;
+        let size_offset = offset + 6 + ((name_length + 2) & !1); // Pascal string, padded to even
+        let size = read_u32(resources, size_offset, false)? as usize;
+        let data = resources.get(size_offset + 4..size_offset + 4 + size)?;
+        if id == 0x0404 {
+            return Some(data);
+        }
+        offset = size_offset + 4 + ((size + 1) & !1);
+    }
+    None
+}
+
+/// Returns the `Caption-Abstract` (record 2, dataset 120) of IPTC-IIM data.
+fn iptc_caption(iptc: &[u8]) -> Option<String> {
+    let mut offset = 0;
+    while iptc.get(offset) == Some(&0x1C) {
+        let record = *iptc.get(offset + 1)?;
+        let dataset = *iptc.get(offset + 2)?;
+        let length = read_u16(iptc, offset + 3, false)?;
+        if length & 0x8000 != 0 {
+            return None; // Extended datasets are not used for captions
+        }
+        let data = iptc.get(offset + 5..offset + 5 + length as usize)?;
+        if (record, dataset) == (2, 120) {
+            return Some(String::from_utf8_lossy(data).into_owned());
+        }
+        offset += 5 + length as usize;
+    }
+    None
+}
+
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010E;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_XP_COMMENT: u16 = 0x9C9C;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_JPEG_INTERCHANGE_FORMAT: u16 = 0x0201;
+const TAG_JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 0x0202;
+const TAG_USER_COMMENT: u16 = 0x9286;
+
+/// A TIFF structure as embedded in EXIF data.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// One entry of a TIFF image file directory.
+struct IfdEntry {
+    field_type: u16,
+    count: u32,
+    position: usize, // Offset of the entry within the TIFF data
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(0..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        read_u32(self.data, 4, self.little_endian).map(|offset| offset as usize)
+    }
+
+    /// Returns the directory following `ifd`, if any.
+    fn next_ifd(&self, ifd: usize) -> Option<usize> {
+        let count = read_u16(self.data, ifd, self.little_endian)? as usize;
+        let next = read_u32(self.data, ifd + 2 + count * 12, self.little_endian)? as usize;
+        (next != 0).then_some(next)
+    }
+
+    fn ifd_entry(&self, ifd: usize, tag: u16) -> Option<IfdEntry> {
+        let count = read_u16(self.data, ifd, self.little_endian)? as usize;
+        (0..count).find_map(|index| {
+            let position = ifd + 2 + index * 12;
+            (read_u16(self.data, position, self.little_endian)? == tag).then_some(())?;
+            Some(IfdEntry {
+                field_type: read_u16(self.data, position + 2, self.little_endian)?,
+                count: read_u32(self.data, position + 4, self.little_endian)?,
+                position,
+            })
+        })
+    }
+
+    /// Returns the raw value bytes of an entry, stored inline if they fit in four bytes.
+    fn entry_bytes(&self, entry: &IfdEntry) -> Option<&'a [u8]> {
+        let unit = match entry.field_type {
+            1 | 2 | 6 | 7 => 1, // BYTE, ASCII, SBYTE, UNDEFINED
+            3 | 8 => 2,         // SHORT, SSHORT
+            4 | 9 => 4,         // LONG, SLONG
+            5 | 10 => 8,        // RATIONAL, SRATIONAL
+            _ => return None,
+        };
+        let size = unit * entry.count as usize;
+        let start = if size <= 4 {
+            entry.position + 8
+        } else {
+            read_u32(self.data, entry.position + 8, self.little_endian)? as usize
+        };
+        self.data.get(start..start + size)
+    }
+
+    fn entry_u32(&self, entry: &IfdEntry) -> Option<u32> {
+        read_u32(self.entry_bytes(entry)?, 0, self.little_endian)
+    }
+
+    fn entry_text(&self, entry: &IfdEntry) -> Option<String> {
+        // Declared ASCII, but photo managers commonly write UTF-8
+        clean_text(String::from_utf8_lossy(self.entry_bytes(entry)?).into_owned())
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let raw: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if little_endian {
+        u16::from_le_bytes(raw)
+    } else {
+        u16::from_be_bytes(raw)
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let raw: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if little_endian {
+        u32::from_le_bytes(raw)
+    } else {
+        u32::from_be_bytes(raw)
+    })
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Trims NUL padding and whitespace; `None` if nothing is left.
+fn clean_text(text: String) -> Option<String> {
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Parses an EXIF date (`2024:05:01 14:30:00`). Unset dates (`0000:00:00 ...`) fail.
+fn parse_exif_date(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, "%Y:%m:%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S"))
+        .ok()
+}
@@ -0,0 +1,438 @@
+//! Staged conversion API.
+//!
+//! [`HozonPipeline`] runs a conversion one stage at a time. Each stage consumes the
+//! previous one, so stages can't be skipped or run out of order:
+//!
+//! ```text
+//! HozonPipeline ──collect()──▶ Collected ──structure()──▶ Structured ──generate()──▶ ConversionReport
+//! ```
+//!
+//! Between stages, the intermediate results can be inspected (e.g. to preview volumes in
+//! a GUI) before committing to the next stage. Runs can also start from pre-collected
+//! chapters ([`HozonPipeline::from_chapters`]) or pre-structured volumes
+//! ([`HozonPipeline::from_volumes`]). The `convert_from_*` methods of [`HozonConfig`] run
+//! all remaining stages in one call.
+//!
+//! [`HozonPipeline::spawn`] and [`Structured::spawn`] run the remaining stages in a
+//! background task and return a [`ConversionHandle`]. Its
+//! [`shutdown`](ConversionHandle::shutdown) stops a long conversion at the next volume
+//! boundary, e.g. when a service restarts: volumes being written are finished and saved,
+//! and the report lists the volumes that were skipped. [`pause`](ConversionHandle::pause)
+//! and [`resume`](ConversionHandle::resume) hold page processing, e.g. while an
+//! interactive app needs the machine.
+//!
+//! ```rust,no_run
+//! # use hozon::prelude::*;
+//! # use hozon::pipeline::HozonPipeline;
+//! # #[tokio::main]
+//! # async fn main() -> hozon::error::Result<()> {
+//! let config = HozonConfig::builder()
+//!     .metadata(EbookMetadata::default_with_title("My Comic".to_string()))
+//!     .source_path(PathBuf::from("./source"))
+//!     .target_path(PathBuf::from("./output"))
+//!     .build()?;
+//!
+//! let collected = HozonPipeline::new(config).collect().await?;
+//! println!("Found {} chapters", collected.chapters().len());
+//!
+//! let structured = collected.structure().await?;
+//! println!("Planned {} volumes", structured.volumes().len());
+//!
+//! let report = structured.generate(CoverOptions::None).await?;
+//! for file in &report.files {
+//!     println!("Wrote {:?} ({} pages)", file.path, file.page_count);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::PathBuf;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+use crate::HozonConfig;
+use crate::cleanup::clean_up_source;
+use crate::error::Result;
+use crate::events::{PipelineEvent, emit};
+use crate::report::ResultBundle;
+use crate::runtime::ConversionControl;
+use crate::snapshot::SourceSnapshot;
+use crate::types::{
+    AnalyzeReport, ConversionReport, CoverOptions, GeneratedFile, HozonExecutionMode,
+    SourceChangePolicy, VolumeStructureReport,
+};
+
+/// Entry point of a staged conversion, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct HozonPipeline {
+    config: HozonConfig,
+    settings_validated: bool, // Set by `HozonEngine`, which validates its settings once
+    control: ConversionControl,
+}
+
+/// Chapters collected from the source or provided by the caller, ready to be structured.
+#[derive(Debug)]
+pub struct Collected {
+    config: HozonConfig,
+    bundle: ResultBundle,
+    chapters: Vec<Vec<PathBuf>>, // Vec<Chapter: Vec<PagePath>>
+    analysis: Option<AnalyzeReport>,
+    snapshot: Option<SourceSnapshot>,
+    control: ConversionControl,
+}
+
+/// Volumes ready to be generated.
+#[derive(Debug)]
+pub struct Structured {
+    config: HozonConfig,
+    bundle: ResultBundle,
+    volumes: Vec<Vec<Vec<PathBuf>>>, // Vec<Volume: Vec<Chapter: Vec<PagePath>>>
+    analysis: Option<AnalyzeReport>,
+    structure: Option<VolumeStructureReport>,
+    snapshot: Option<SourceSnapshot>,
+    control: ConversionControl,
+}
+
+/// A conversion running in the background, returned by [`HozonPipeline::spawn`] and
+/// [`Structured::spawn`].
+///
+/// Dropping the handle doesn't stop the conversion.
+#[derive(Debug)]
+pub struct ConversionHandle {
+    control: ConversionControl,
+    task: JoinHandle<Result<ConversionReport>>,
+}
+
+impl ConversionHandle {
+    /// Stops scheduling new volumes. Volumes already being written are finished and
+    /// saved; the others end up in [`ConversionReport::skipped`]. A shutdown requested
+    /// before generation starts skips every volume, and a paused conversion is resumed.
+    ///
+    /// Source cleanup and the series manifest are skipped unless every volume was
+    /// written.
+    pub fn shutdown(&self) {
+        self.control.request_shutdown();
+    }
+
+    /// Whether [`shutdown`](Self::shutdown) was called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.control.is_shutdown_requested()
+    }
+
+    /// Pauses page processing until [`resume`](Self::resume) is called, e.g. while the
+    /// user does something latency-sensitive. Pages already being read or encoded are
+    /// finished; later pages wait without holding any [`RuntimeLimits`](crate::RuntimeLimits)
+    /// permits, so other conversions sharing them keep running. Source analysis isn't
+    /// paused.
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    /// Resumes a paused conversion.
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Whether the conversion is paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// Whether the conversion has ended.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the conversion to end and returns its report.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any stage fails, or with [`Error::Join`](crate::error::Error::Join) if the
+    /// conversion task panicked.
+    pub async fn join(self) -> Result<ConversionReport> {
+        self.task.await?
+    }
+}
+
+impl HozonPipeline {
+    /// Starts a pipeline for `config`.
+    pub fn new(config: HozonConfig) -> Self {
+        Self {
+            config,
+            settings_validated: false,
+            control: ConversionControl::default(),
+        }
+    }
+
+    /// Starts a pipeline for `config`, whose settings were already validated.
+    pub(crate) fn prevalidated(config: HozonConfig) -> Self {
+        Self {
+            config,
+            settings_validated: true,
+            control: ConversionControl::default(),
+        }
+    }
+
+    /// Runs the preflight checks for `mode`, skipping settings that were already validated.
+    fn check(&self, mode: HozonExecutionMode) -> Result<()> {
+        if !self.settings_validated {
+            self.config.validate_settings()?;
+        }
+        self.config.validate_request(mode)
+    }
+
+    /// The configuration the pipeline runs with.
+    pub fn config(&self) -> &HozonConfig {
+        &self.config
+    }
+
+    /// Scans and analyzes [`source_path`](HozonConfig::source_path).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration is invalid for [`HozonExecutionMode::FromSource`] or the
+    /// source can't be analyzed.
+    pub async fn collect(self) -> Result<Collected> {
+        self.check(HozonExecutionMode::FromSource)?;
+        let config = self.config;
+        let mut bundle = ResultBundle::new(HozonExecutionMode::FromSource);
+
+        let result = async {
+            let started = Instant::now();
+            let collected = config.analyze_source().await?;
+            bundle.set_analysis(&collected.report, started);
+            emit(
+                &config.event_sinks,
+                PipelineEvent::AnalysisFinished {
+                    chapters: collected.chapters_with_pages.len(),
+                    pages: collected.chapters_with_pages.iter().map(Vec::len).sum(),
+                },
+            );
+
+            let snapshot = if config.source_change_policy == SourceChangePolicy::Ignore {
+                None
+            } else {
+                let chapters = collected.chapters_with_pages.clone();
+                Some(tokio::task::spawn_blocking(move || SourceSnapshot::capture(&chapters)).await?)
+            };
+            Ok((collected, snapshot))
+        }
+        .await;
+
+        let (collected, snapshot) = match result {
+            Ok(collected) => collected,
+            Err(e) => return bundle.finish(&config, Err(e)),
+        };
+        Ok(Collected {
+            config,
+            bundle,
+            chapters: collected.chapters_with_pages,
+            analysis: Some(collected.report),
+            snapshot,
+            control: self.control,
+        })
+    }
+
+    /// Runs every stage, starting from [`source_path`](HozonConfig::source_path), in a
+    /// background task.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn spawn(self, cover_options: CoverOptions) -> ConversionHandle {
+        self.spawn_stages(|pipeline| async move {
+            pipeline
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(cover_options)
+                .await
+        })
+    }
+
+    /// Runs `stages` on this pipeline in a background task.
+    pub(crate) fn spawn_stages<F>(self, stages: impl FnOnce(Self) -> F) -> ConversionHandle
+    where
+        F: Future<Output = Result<ConversionReport>> + Send + 'static,
+    {
+        ConversionHandle {
+            control: self.control.clone(),
+            task: tokio::spawn(stages(self)),
+        }
+    }
+
+    /// Skips collection, continuing with the given chapters (`Vec<Chapter: Vec<PagePath>>`).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration is invalid for [`HozonExecutionMode::FromCollectedData`].
+    pub fn from_chapters(self, chapters: Vec<Vec<PathBuf>>) -> Result<Collected> {
+        self.check(HozonExecutionMode::FromCollectedData)?;
+        Ok(Collected {
+            config: self.config,
+            bundle: ResultBundle::new(HozonExecutionMode::FromCollectedData),
+            chapters,
+            analysis: None,
+            snapshot: None,
+            control: self.control,
+        })
+    }
+
+    /// Skips collection and structuring, continuing with the given volumes
+    /// (`Vec<Volume: Vec<Chapter: Vec<PagePath>>>`).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the configuration is invalid for [`HozonExecutionMode::FromStructuredData`].
+    pub fn from_volumes(self, volumes: Vec<Vec<Vec<PathBuf>>>) -> Result<Structured> {
+        self.check(HozonExecutionMode::FromStructuredData)?;
+        Ok(Structured {
+            config: self.config,
+            bundle: ResultBundle::new(HozonExecutionMode::FromStructuredData),
+            volumes,
+            analysis: None,
+            structure: None,
+            snapshot: None,
+            control: self.control,
+        })
+    }
+}
+
+impl Collected {
+    /// The configuration the pipeline runs with.
+    pub fn config(&self) -> &HozonConfig {
+        &self.config
+    }
+
+    /// The collected chapters, each a list of page paths.
+    pub fn chapters(&self) -> &[Vec<PathBuf>] {
+        &self.chapters
+    }
+
+    /// The analysis report, if the chapters were collected from the source directory.
+    pub fn analysis(&self) -> Option<&AnalyzeReport> {
+        self.analysis.as_ref()
+    }
+
+    /// Groups the chapters into volumes according to the configured
+    /// [`VolumeGroupingStrategy`](crate::types::VolumeGroupingStrategy).
+    pub async fn structure(self) -> Result<Structured> {
+        let Collected {
+            config,
+            mut bundle,
+            chapters,
+            analysis,
+            snapshot,
+            control,
+        } = self;
+
+        let started = Instant::now();
+        let structured = match HozonConfig::perform_structuring(&config, chapters).await {
+            Ok(structured) => structured,
+            Err(e) => return bundle.finish(&config, Err(e)),
+        };
+        bundle.set_structure(&structured.report, started);
+        emit(
+            &config.event_sinks,
+            PipelineEvent::StructureFinished {
+                volumes: structured.volumes_with_chapters_and_pages.len(),
+            },
+        );
+
+        Ok(Structured {
+            config,
+            bundle,
+            volumes: structured.volumes_with_chapters_and_pages,
+            analysis,
+            structure: Some(structured.report),
+            snapshot,
+            control,
+        })
+    }
+}
+
+impl Structured {
+    /// The configuration the pipeline runs with.
+    pub fn config(&self) -> &HozonConfig {
+        &self.config
+    }
+
+    /// The volumes to generate, each a list of chapters with their page paths.
+    pub fn volumes(&self) -> &[Vec<Vec<PathBuf>>] {
+        &self.volumes
+    }
+
+    /// The structuring report, unless the pipeline started from structured volumes.
+    pub fn report(&self) -> Option<&VolumeStructureReport> {
+        self.structure.as_ref()
+    }
+
+    /// Writes the output files, with covers chosen by `cover_options`.
+    ///
+    /// The result bundle, if enabled, is written once this stage finishes, or as soon as
+    /// any stage fails.
+    pub async fn generate(self, cover_options: CoverOptions) -> Result<ConversionReport> {
+        let Structured {
+            config,
+            mut bundle,
+            volumes,
+            analysis,
+            structure,
+            snapshot,
+            control,
+        } = self;
+
+        let started = Instant::now();
+        let result = HozonConfig::perform_generation(
+            &config,
+            volumes,
+            &cover_options,
+            snapshot,
+            bundle.warnings(),
+            &control,
+        )
+        .await;
+        // Only sources the pipeline collected itself and converted completely are cleaned up
+        let result = match result {
+            Ok((outputs, skipped)) if analysis.is_some() && skipped.is_empty() => {
+                let paths: Vec<PathBuf> =
+                    outputs.iter().map(|output| output.path.clone()).collect();
+                clean_up_source(&config, &paths)
+                    .await
+                    .map(|_| (outputs, skipped))
+            }
+            result => result,
+        };
+        let result = result.map(|(outputs, skipped)| {
+            let files = outputs
+                .iter()
+                .map(|output| GeneratedFile {
+                    volume_number: output.volume_number,
+                    part_number: output.part_number,
+                    path: output.path.clone(),
+                    page_count: output.page_count,
+                    pages: output.pages.clone(),
+                })
+                .collect();
+            bundle.set_outputs(outputs, skipped.clone(), started);
+            ConversionReport {
+                analysis,
+                structure,
+                skipped,
+                files,
+            }
+        });
+
+        bundle.finish(&config, result)
+    }
+
+    /// Runs [`generate`](Self::generate) in a background task.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn spawn(self, cover_options: CoverOptions) -> ConversionHandle {
+        let control = self.control.clone();
+        ConversionHandle {
+            control,
+            task: tokio::spawn(self.generate(cover_options)),
+        }
+    }
+}
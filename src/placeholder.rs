@@ -0,0 +1,319 @@
+//! Placeholder pages for gaps in the page numbering of a chapter.
+//!
+//! With [`MissingPagePolicy::Placeholder`](crate::types::MissingPagePolicy::Placeholder),
+//! every page number missing from a chapter (see
+//! [`AnalyzeFinding::MissingPages`](crate::types::AnalyzeFinding::MissingPages)) is filled
+//! with a generated image reading "PAGE MISSING" together with the chapter and page
+//! number. Pages after the gap keep their position, so double-page spreads stay aligned
+//! and the gap is visible to readers instead of silently shifting the following pages.
+//!
+//! Placeholders are rendered in memory into [`SyntheticPages`]. They take their place in
+//! a chapter's page list under a name of their own and are handed to the generator with
+//! [`Generator::add_page_from_image`](crate::generator::Generator::add_page_from_image)
+//! when the volume is written, without ever touching the disk. Archive and MOBI outputs
+//! get their [`ChapterIntro`](crate::types::ChapterIntro) pages and the blank pages
+//! aligning content to a [`PageSide`](crate::types::PageSide) the same way.
+
+use image::{DynamicImage, ImageBuffer, Luma};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::collector::Collector;
+use crate::error::Result;
+use crate::path_utils::extract_number_from_filename_safe;
+use crate::processing::ImageProcessing;
+
+/// Size of placeholders in a chapter whose pages can't be measured.
+const DEFAULT_SIZE: (u32, u32) = (800, 1200);
+
+/// Characters per line of a chapter intro before its words wrap.
+const INTRO_COLUMNS: usize = 20;
+
+/// Width and height of a glyph of [`glyph`], in font pixels.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Directory of the names synthesized pages take in page lists. Nothing is read from or
+/// written to it; the names only mark the places of the pages.
+const SYNTHETIC_PAGE_ROOT: &str = "<synthesized>";
+
+/// Pages of one volume rendered in memory, each standing in its chapter's page list under
+/// a name of its own until [`SyntheticPages::runs`] hands it to the generator.
+#[derive(Default)]
+pub(crate) struct SyntheticPages {
+    images: HashMap<PathBuf, DynamicImage>,
+    max_size: (Option<u32>, Option<u32>), // Width and height limits of image processing
+}
+
+/// Consecutive pages of a page list that are added to a generator the same way.
+pub(crate) enum PageRun<'a> {
+    /// Source pages, read from disk by the generator.
+    Files(&'a [PathBuf]),
+    /// A synthesized page and its file name.
+    Image(&'a DynamicImage, &'a str),
+}
+
+impl SyntheticPages {
+    /// Creates an empty set. Pages are rendered no larger than `processing` makes the
+    /// source pages next to them.
+    pub(crate) fn new(processing: Option<&ImageProcessing>) -> Self {
+        Self {
+            images: HashMap::new(),
+            max_size: processing.map_or((None, None), |processing| {
+                (processing.max_width, processing.max_height)
+            }),
+        }
+    }
+
+    /// Inserts a placeholder for every page number missing from `pages`, right before the
+    /// first page numbered higher. `chapter_number` is the 1-based chapter number counted
+    /// across the series, printed on the placeholders. Blocking; reads the size of the
+    /// pages and renders the placeholder images.
+    ///
+    /// # Returns
+    ///
+    /// The page numbers that got a placeholder.
+    pub(crate) fn fill_gaps(
+        &mut self,
+        pages: &mut Vec<PathBuf>,
+        chapter_number: usize,
+        regex: &Regex,
+    ) -> Result<Vec<u32>> {
+        let gaps = Collector::missing_page_numbers(pages, regex);
+        if gaps.is_empty() {
+            return Ok(gaps);
+        }
+
+        let mut filled = Vec::with_capacity(pages.len() + gaps.len());
+        let mut pending = gaps.iter().copied().peekable();
+        let mut previous: Option<&Path> = None;
+        for page in pages.iter() {
+            let number = extract_number_from_filename_safe(page, regex)
+                .filter(|number| number.fract() == 0.0);
+            while let (Some(number), Some(&gap)) = (number, pending.peek()) {
+                if f64::from(gap) > number {
+                    break;
+                }
+                let (width, height) = self.size_after(previous);
+                let image = render(
+                    &[
+                        "PAGE MISSING".to_string(),
+                        format!("CHAPTER {}", chapter_number),
+                        format!("PAGE {}", gap),
+                    ],
+                    width,
+                    height,
+                );
+                let name = format!("chapter_{:04}_page_{:04}.png", chapter_number, gap);
+                filled.push(self.insert(name, DynamicImage::ImageLuma8(image)));
+                pending.next();
+            }
+            filled.push(page.clone());
+            previous = Some(page);
+        }
+        *pages = filled;
+        Ok(gaps)
+    }
+
+    /// Splits `pages` into runs of source pages and the synthesized pages between them.
+    pub(crate) fn runs<'a>(&'a self, pages: &'a [PathBuf]) -> Vec<PageRun<'a>> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for (index, page) in pages.iter().enumerate() {
+            let Some(image) = self.images.get(page) else {
+                continue;
+            };
+            if start < index {
+                runs.push(PageRun::Files(&pages[start..index]));
+            }
+            let name = page
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            runs.push(PageRun::Image(image, name));
+            start = index + 1;
+        }
+        if start < pages.len() {
+            runs.push(PageRun::Files(&pages[start..]));
+        }
+        runs
+    }
+
+    /// Inserts the intro page of chapter `chapter` in front of `pages`, sized like the
+    /// chapter's first page. `lines` are printed in capitals, long lines wrapped at word
+    /// boundaries; characters without a glyph stay blank. Blocking; reads the size of the
+    /// first page and renders the intro image.
+    pub(crate) fn insert_intro(
+        &mut self,
+        pages: &mut Vec<PathBuf>,
+        chapter: usize,
+        lines: &[String],
+    ) {
+        let (width, height) = self.size_after(pages.first().map(PathBuf::as_path));
+        let lines: Vec<String> = lines
+            .iter()
+            .flat_map(|line| wrap(&line.to_uppercase(), INTRO_COLUMNS))
+            .collect();
+        let image = render(&lines, width, height);
+        let name = format!("chapter_{:04}_intro.png", chapter);
+        pages.insert(0, self.insert(name, DynamicImage::ImageLuma8(image)));
+    }
+
+    /// Inserts a white page in front of `pages`, sized like the first of them. Blocking;
+    /// reads the size of the first page and renders the image.
+    pub(crate) fn insert_blank(&mut self, pages: &mut Vec<PathBuf>) {
+        let (width, height) = self.size_after(pages.first().map(PathBuf::as_path));
+        let image = ImageBuffer::from_pixel(width.max(1), height.max(1), Luma([255u8]));
+        pages.insert(
+            0,
+            self.insert("blank.png".to_string(), DynamicImage::ImageLuma8(image)),
+        );
+    }
+
+    /// Adds `image` under the file name `name`, returning the name it takes in page lists.
+    fn insert(&mut self, name: String, image: DynamicImage) -> PathBuf {
+        let path = Path::new(SYNTHETIC_PAGE_ROOT).join(name);
+        self.images.insert(path.clone(), image);
+        path
+    }
+
+    /// Size of a page synthesized after `page`: the size `page` ends up with in the
+    /// volume, or [`DEFAULT_SIZE`] if there is none or it can't be measured.
+    fn size_after(&self, page: Option<&Path>) -> (u32, u32) {
+        let size = page.and_then(|page| match self.images.get(page) {
+            Some(image) => Some((image.width(), image.height())),
+            None => image::image_dimensions(page).ok(),
+        });
+        let Some((width, height)) = size else {
+            return DEFAULT_SIZE;
+        };
+        // Downscaled to fit the limits like the source pages, keeping the aspect ratio
+        let scale = [(self.max_size.0, width), (self.max_size.1, height)]
+            .into_iter()
+            .filter_map(|(max, size)| max.map(|max| f64::from(max) / f64::from(size.max(1))))
+            .fold(1.0, f64::min);
+        (
+            (f64::from(width) * scale).round() as u32,
+            (f64::from(height) * scale).round() as u32,
+        )
+    }
+}
+
+/// Renders `lines` centered on a light gray page of the given size, framed by a border.
+fn render(lines: &[String], width: u32, height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = (width.max(1), height.max(1));
+    let border = (width.min(height) / 40).max(1);
+    let mut image = ImageBuffer::from_fn(width, height, |x, y| {
+        let edge = x < border || y < border || x >= width - border || y >= height - border;
+        Luma([if edge { 96 } else { 230 }])
+    });
+
+    // Scale the font so the longest line fills about two thirds of the width
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(1) as u32;
+    let scale = (width * 2 / 3 / (columns * (GLYPH_WIDTH + 1))).max(1);
+    let line_height = (GLYPH_HEIGHT + 4) * scale;
+    let mut top = height.saturating_sub(line_height * lines.len() as u32) / 2;
+    for line in lines {
+        let line_width = line.chars().count() as u32 * (GLYPH_WIDTH + 1) * scale;
+        let mut left = width.saturating_sub(line_width) / 2;
+        for character in line.chars() {
+            for (row, bits) in glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let x = left + column * scale + dx;
+                            let y = top + row as u32 * scale + dy;
+                            if x < width && y < height {
+                                image.put_pixel(x, y, Luma([32]));
+                            }
+                        }
+                    }
+                }
+            }
+            left += (GLYPH_WIDTH + 1) * scale;
+        }
+        top += line_height;
+    }
+    image
+}
+
+/// Splits `line` at spaces into lines of at most `columns` characters. Words longer than
+/// that get a line of their own.
+fn wrap(line: &str, columns: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in line.split_whitespace() {
+        match lines.last_mut() {
+            Some(last) if last.chars().count() + 1 + word.chars().count() <= columns => {
+                last.push(' ');
+                last.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// Rows of a 5x7 pixel glyph, most significant of the five bits on the left. Covers the
+/// capital letters, digits and common punctuation; anything else is blank.
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match character {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        _ => [0; GLYPH_HEIGHT as usize],
+    }
+}
@@ -0,0 +1,58 @@
+//! Chapter and page name regexes for the naming schemes of common downloaders.
+//!
+//! Select a preset with [`HozonConfig::naming_preset`](crate::HozonConfig::naming_preset)
+//! instead of writing [`chapter_name_regex_str`](crate::HozonConfig::chapter_name_regex_str)
+//! and [`page_name_regex_str`](crate::HozonConfig::page_name_regex_str) by hand. An explicit
+//! regex takes precedence over the one of the preset.
+//!
+//! | Preset      | Chapter directories                       | Pages               |
+//! |-------------|-------------------------------------------|---------------------|
+//! | `tachiyomi` | `Scanlator_Vol.2 Ch.15.5 - Title`         | `001.jpg`           |
+//! | `hakuneko`  | `Chapter 12 - Title`, `#012 Title`        | `001.jpg`           |
+//! | `fanbox`    | `1234567 Post title` (post ID)            | `1234567_p0.jpg`    |
+//! | `calibre`   | `Series [3] Title` (series index)         | `page_0001.jpg`     |
+
+/// A named pair of chapter and page name regexes. Both extract the sort number from
+/// capture group 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamingPreset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub chapter_name_regex: &'static str,
+    pub page_name_regex: &'static str,
+}
+
+/// All built-in presets.
+pub const NAMING_PRESETS: &[NamingPreset] = &[
+    NamingPreset {
+        name: "tachiyomi",
+        description: "Tachiyomi and Mihon downloads: chapter number after `Ch.`",
+        chapter_name_regex: r"(?i)ch(?:apter)?\.?\s*(\d+(?:\.\d+)?)",
+        page_name_regex: r"^(\d+)",
+    },
+    NamingPreset {
+        name: "hakuneko",
+        description: "HakuNeko downloads: chapter number after `Chapter`, `Ch.` or `#`",
+        chapter_name_regex: r"(?i)(?:chapter|ch\.|#)\s*(\d+(?:\.\d+)?)",
+        page_name_regex: r"^(\d+)",
+    },
+    NamingPreset {
+        name: "fanbox",
+        description: "pixivFANBOX downloads: posts by ID, pages by `_p` index",
+        chapter_name_regex: r"^(\d+)",
+        page_name_regex: r"_p(\d+)",
+    },
+    NamingPreset {
+        name: "calibre",
+        description: "Calibre exports: series index in brackets",
+        chapter_name_regex: r"\[(\d+(?:\.\d+)?)\]",
+        page_name_regex: r"(\d+)",
+    },
+];
+
+/// Looks up a preset by name, ignoring case.
+pub fn naming_preset(name: &str) -> Option<&'static NamingPreset> {
+    NAMING_PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
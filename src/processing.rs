@@ -0,0 +1,805 @@
+//! Optional per-page image processing applied during generation.
+//!
+//! By default Hozon copies source images into the output byte-for-byte. When
+//! [`ImageProcessing`] is configured, each page is decoded, downscaled and/or
+//! re-encoded before being written. Embedded ICC color profiles are kept by default,
+//! also on re-encoded pages; [`ColorProfilePolicy`] can strip them or convert the pages
+//! to sRGB instead. Processed pages can optionally be spilled to a temporary directory
+//! instead of being held in memory until the archive writer consumes them; the spill
+//! directory is removed automatically when generation ends.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageReader};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+use crate::path_utils::path_to_string_lossy;
+use crate::types::ImageFormat;
+
+/// Image format pages are re-encoded to when processing is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProcessedImageFormat {
+    /// Lossy JPEG using [`ImageProcessing::jpeg_quality`]. Alpha channels are dropped.
+    Jpeg,
+    /// Lossless PNG.
+    Png,
+    /// Lossless WebP.
+    WebP,
+}
+
+impl ProcessedImageFormat {
+    /// Returns the file extension and MIME type used for pages in this format.
+    pub fn file_info(&self) -> (&'static str, &'static str) {
+        let format = ImageFormat::from(*self);
+        (format.extension(), format.mime())
+    }
+
+    /// The encoder for pages in `format`, if it [can be encoded](ImageFormat::can_encode).
+    pub fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Jpeg => Some(ProcessedImageFormat::Jpeg),
+            ImageFormat::Png => Some(ProcessedImageFormat::Png),
+            ImageFormat::WebP => Some(ProcessedImageFormat::WebP),
+            _ => None,
+        }
+    }
+
+    /// Maps a page extension (as returned by [`ImageFormat::extension`]) to its format.
+    fn from_extension(extension: &str) -> Option<Self> {
+        ImageFormat::from_extension(extension).and_then(Self::from_image_format)
+    }
+}
+
+impl From<ProcessedImageFormat> for ImageFormat {
+    fn from(format: ProcessedImageFormat) -> Self {
+        match format {
+            ProcessedImageFormat::Jpeg => ImageFormat::Jpeg,
+            ProcessedImageFormat::Png => ImageFormat::Png,
+            ProcessedImageFormat::WebP => ImageFormat::WebP,
+        }
+    }
+}
+
+/// How embedded ICC color profiles are treated when pages are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorProfilePolicy {
+    /// Keep each page's profile, also when the page is re-encoded (default).
+    #[default]
+    Preserve,
+    /// Remove profiles, so readers render every page as sRGB. Pages carrying a profile
+    /// are re-encoded even if they need no other processing.
+    Strip,
+    /// Convert the pixels of pages carrying a profile to sRGB and remove the profile.
+    /// Pages carrying a profile are re-encoded even if they need no other processing.
+    ConvertToSrgb,
+}
+
+/// How animated pages (APNG, animated WebP) are handled.
+///
+/// Comic readers generally expect still images; an animated page in a CBZ is shown as
+/// its first frame by some readers and rejected or mis-rendered by others. GIF is not a
+/// supported page format and is never collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AnimatedImagePolicy {
+    /// Replace the animation with its first frame, re-encoded in the page's format (default).
+    #[default]
+    FirstFrame,
+    /// Fail the conversion with [`Error::Unsupported`] naming the animated page.
+    Reject,
+    /// Keep the animation as-is. Only supported for EPUB output.
+    PassThrough,
+}
+
+/// Returns `true` if the page bytes hold an animated PNG or WebP.
+pub(crate) fn is_animated(bytes: &[u8], extension: &str) -> bool {
+    match extension {
+        // APNG: an `acTL` chunk precedes the first `IDAT` chunk
+        "png" => {
+            let mut offset = 8; // PNG signature
+            while let Some(header) = bytes.get(offset..offset + 8) {
+                let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+                match &header[4..8] {
+                    b"acTL" => return true,
+                    b"IDAT" | b"IEND" => return false,
+                    _ => offset += 12 + length as usize, // Header, data and CRC
+                }
+            }
+            false
+        }
+        // Extended WebP: the VP8X chunk carries an animation flag
+        "webp" => {
+            bytes.len() > 20
+                && &bytes[0..4] == b"RIFF"
+                && &bytes[8..12] == b"WEBP"
+                && &bytes[12..16] == b"VP8X"
+                && bytes[20] & 0x02 != 0
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the image file at `path` is an animated PNG or WebP, like
+/// [`is_animated`] but reading only the headers in front of the image data. Blocking.
+pub(crate) fn is_animated_file(path: &Path, extension: &str) -> std::io::Result<bool> {
+    let mut file = File::open(path)?;
+    match extension {
+        "png" => {
+            file.seek(SeekFrom::Start(8))?; // PNG signature
+            let mut header = [0u8; 8];
+            loop {
+                match file.read_exact(&mut header) {
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+                    result => result?,
+                }
+                let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+                match &header[4..8] {
+                    b"acTL" => return Ok(true),
+                    b"IDAT" | b"IEND" => return Ok(false),
+                    _ => file.seek(SeekFrom::Current(i64::from(length) + 4))?, // Data and CRC
+                };
+            }
+        }
+        "webp" => {
+            let mut header = Vec::with_capacity(21); // Up to the VP8X flags
+            file.take(21).read_to_end(&mut header)?;
+            Ok(is_animated(&header, extension))
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Decodes the first frame of an animated page and re-encodes it as a still image
+/// in the same format.
+pub(crate) fn first_frame(bytes: &[u8], extension: &str) -> Result<Vec<u8>> {
+    let format = ProcessedImageFormat::from_extension(extension)
+        .ok_or_else(|| Error::Unsupported(format!("Animated image format '{}'", extension)))?;
+    let (image, icc_profile) = decode(bytes)?;
+    encode(&image, format, 100, icc_profile)
+}
+
+/// Options for resizing and transcoding pages during generation.
+///
+/// Pages that already fit within the size limits and are already in the target
+/// format are passed through unchanged, so enabling processing never re-encodes
+/// pages needlessly.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageProcessing {
+    /// Maximum page width in pixels. Larger pages are downscaled, keeping the aspect ratio.
+    pub max_width: Option<u32>,
+    /// Maximum page height in pixels. Larger pages are downscaled, keeping the aspect ratio.
+    pub max_height: Option<u32>,
+    /// Format to re-encode every page to. `None` keeps each page's original format.
+    pub output_format: Option<ProcessedImageFormat>,
+    /// JPEG quality (1-100) used when encoding JPEG pages.
+    pub jpeg_quality: u8,
+    /// How embedded ICC color profiles are handled.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color_profile: ColorProfilePolicy,
+    /// Background color (RGB) that pages with transparent regions are flattened onto.
+    /// `None` keeps transparency. Several e-ink readers display transparent regions as
+    /// black, so `Some([255, 255, 255])` is a common choice for such devices.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flatten_alpha: Option<[u8; 3]>,
+    /// Strength (0.0-1.0) of the auto-levels contrast enhancement for faded scans.
+    /// `None` disables it. Every page is stretched so its darkest and brightest 0.5% of
+    /// pixels reach black and white, blended with the original by this strength; the
+    /// same strength applies to all pages, so well-exposed pages barely change.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub auto_levels: Option<f32>,
+    /// Whether to straighten tilted pages (e.g. photographed with a camera). The
+    /// dominant angle of text lines and panel borders is detected within ±5° and the
+    /// page is rotated back; uncovered corners are filled with white.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deskew: bool,
+    /// Whether processed pages are written to a temporary spill directory instead of
+    /// being kept in memory until they are added to the archive.
+    pub spill_to_disk: bool,
+    /// Directory in which the spill directory is created. Defaults to the system temp dir.
+    pub spill_directory: Option<PathBuf>,
+}
+
+impl Default for ImageProcessing {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            max_height: None,
+            output_format: None,
+            jpeg_quality: 90,
+            color_profile: ColorProfilePolicy::default(),
+            flatten_alpha: None,
+            auto_levels: None,
+            deskew: false,
+            spill_to_disk: false,
+            spill_directory: None,
+        }
+    }
+}
+
+impl ImageProcessing {
+    /// Validates the processing options.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_width == Some(0) || self.max_height == Some(0) {
+            return Err(Error::Other(
+                "Image processing size limits must be greater than zero".to_string(),
+            ));
+        }
+        if self.jpeg_quality == 0 || self.jpeg_quality > 100 {
+            return Err(Error::Other(
+                "JPEG quality must be between 1 and 100".to_string(),
+            ));
+        }
+        if self
+            .auto_levels
+            .is_some_and(|strength| !(0.0..=1.0).contains(&strength))
+        {
+            return Err(Error::Other(
+                "Auto-levels strength must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if a page with the given dimensions must be downscaled.
+    fn needs_resize(&self, width: u32, height: u32) -> bool {
+        self.max_width.is_some_and(|max| width > max)
+            || self.max_height.is_some_and(|max| height > max)
+    }
+}
+
+/// The bytes of a page, either held in memory or spilled to a temporary file.
+pub(crate) enum PageData {
+    Memory(Vec<u8>),
+    Spilled(SpilledPage),
+}
+
+impl PageData {
+    /// Returns the SHA-256 digest of the page bytes.
+    pub(crate) fn digest(&self) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        match self {
+            PageData::Memory(bytes) => hasher.update(bytes),
+            PageData::Spilled(page) => {
+                std::io::copy(&mut File::open(&page.path)?, &mut hasher)?;
+            }
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Returns the page bytes, reading spilled pages through Tokio's file I/O.
+    pub(crate) async fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            PageData::Memory(bytes) => Ok(bytes),
+            PageData::Spilled(page) => Ok(tokio::fs::read(&page.path).await?),
+        }
+    }
+
+    /// Returns a reader over the page bytes.
+    pub(crate) fn reader(self) -> Result<Box<dyn Read + Send>> {
+        match self {
+            PageData::Memory(bytes) => Ok(Box::new(Cursor::new(bytes))),
+            PageData::Spilled(page) => Ok(Box::new(page.open()?)),
+        }
+    }
+}
+
+/// A processed page stored in the spill directory. The file is deleted on drop.
+pub(crate) struct SpilledPage {
+    path: PathBuf,
+}
+
+impl SpilledPage {
+    fn open(self) -> Result<SpilledReader> {
+        let file = File::open(&self.path)?;
+        Ok(SpilledReader { file, _page: self })
+    }
+}
+
+impl Drop for SpilledPage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reader over a spilled page that deletes the file once reading is done.
+struct SpilledReader {
+    file: File,
+    _page: SpilledPage,
+}
+
+impl Read for SpilledReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+/// Temporary directory holding spilled pages, removed together with its contents on drop.
+pub(crate) struct SpillStore {
+    directory: tempfile::TempDir,
+    next_id: AtomicU64,
+}
+
+impl SpillStore {
+    /// Creates a fresh, randomly named spill directory below `base` (or the system temp
+    /// dir), creating `base` if it doesn't exist.
+    pub(crate) fn new(base: Option<&Path>) -> Result<Self> {
+        let base = base.map_or_else(std::env::temp_dir, Path::to_path_buf);
+        let directory = std::fs::create_dir_all(&base)
+            .and_then(|()| {
+                tempfile::Builder::new()
+                    .prefix("hozon-spill-")
+                    .tempdir_in(&base)
+            })
+            .map_err(|e| {
+                Error::Io(std::io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to create spill directory in '{}': {}",
+                        path_to_string_lossy(&base),
+                        e
+                    ),
+                ))
+            })?;
+
+        Ok(Self {
+            directory,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Writes page bytes to a new file in the spill directory.
+    fn spill(&self, bytes: &[u8], extension: &str) -> Result<SpilledPage> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let path = self
+            .directory
+            .path()
+            .join(format!("{:08}.{}", id, extension));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(bytes)?;
+        writer.flush()?;
+        Ok(SpilledPage { path })
+    }
+}
+
+/// Applies [`ImageProcessing`] to pages. Shared by all page reads of one generator.
+pub(crate) struct PageProcessor {
+    options: ImageProcessing,
+    spill: Option<SpillStore>,
+}
+
+impl PageProcessor {
+    /// Creates a processor, setting up a spill directory if spilling is enabled.
+    pub(crate) fn new(options: ImageProcessing) -> Result<Self> {
+        options.validate()?;
+        let spill = if options.spill_to_disk {
+            Some(SpillStore::new(options.spill_directory.as_deref())?)
+        } else {
+            None
+        };
+        Ok(Self { options, spill })
+    }
+
+    /// The options this processor applies.
+    pub(crate) fn options(&self) -> &ImageProcessing {
+        &self.options
+    }
+
+    /// Processes one page. Blocking; run on a blocking thread.
+    ///
+    /// Returns the (possibly new) extension and MIME type together with the page bytes.
+    pub(crate) fn process(
+        &self,
+        bytes: Vec<u8>,
+        extension: &'static str,
+        mime: &'static str,
+    ) -> Result<(&'static str, &'static str, PageData)> {
+        let source_format = ProcessedImageFormat::from_extension(extension);
+        let target_format = self.options.output_format.or(source_format);
+
+        let (image, icc_profile) = decode(&bytes)?;
+        let needs_resize = self.options.needs_resize(image.width(), image.height());
+        let changes_profile =
+            icc_profile.is_some() && self.options.color_profile != ColorProfilePolicy::Preserve;
+        let needs_flatten = self.options.flatten_alpha.is_some() && has_transparency(&image);
+        let needs_levels = self
+            .options
+            .auto_levels
+            .is_some_and(|strength| strength > 0.0);
+        let skew = if self.options.deskew {
+            detect_skew(&image)
+        } else {
+            0.0
+        };
+        let needs_deskew = skew.abs() >= MIN_DESKEW_ANGLE;
+
+        let (extension, mime, bytes) = match target_format {
+            Some(target)
+                if needs_resize
+                    || changes_profile
+                    || needs_flatten
+                    || needs_levels
+                    || needs_deskew
+                    || Some(target) != source_format =>
+            {
+                let image = if needs_resize {
+                    image.resize(
+                        self.options.max_width.unwrap_or(u32::MAX),
+                        self.options.max_height.unwrap_or(u32::MAX),
+                        FilterType::Lanczos3,
+                    )
+                } else {
+                    image
+                };
+                let image = if needs_deskew {
+                    rotate(&image, skew)
+                } else {
+                    image
+                };
+                let (image, icc_profile) = match (self.options.color_profile, icc_profile) {
+                    (ColorProfilePolicy::Preserve, profile) => (image, profile),
+                    (ColorProfilePolicy::Strip, _) | (_, None) => (image, None),
+                    (ColorProfilePolicy::ConvertToSrgb, Some(profile)) => {
+                        (convert_to_srgb(image, &profile), None)
+                    }
+                };
+                let image = match self.options.auto_levels {
+                    Some(strength) if needs_levels => auto_levels(image, strength),
+                    _ => image,
+                };
+                let image = match self.options.flatten_alpha {
+                    Some(background) if needs_flatten => flatten(&image, background),
+                    _ => image,
+                };
+                let encoded = encode(&image, target, self.options.jpeg_quality, icc_profile)?;
+                let (extension, mime) = target.file_info();
+                (extension, mime, encoded)
+            }
+            // Already within limits and in the requested format: keep the original bytes
+            _ => (extension, mime, bytes),
+        };
+
+        let data = match &self.spill {
+            Some(store) => PageData::Spilled(store.spill(&bytes, extension)?),
+            None => PageData::Memory(bytes),
+        };
+        Ok((extension, mime, data))
+    }
+}
+
+/// Returns `true` if the image has an alpha channel with at least one non-opaque pixel.
+fn has_transparency(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < u8::MAX)
+}
+
+/// Composites the image onto an opaque `background` color, removing the alpha channel.
+fn flatten(image: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let flattened = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = pixel[3] as u16;
+        image::Rgb(std::array::from_fn(|c| {
+            ((pixel[c] as u16 * alpha + background[c] as u16 * (255 - alpha) + 127) / 255) as u8
+        }))
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
+
+/// Largest page tilt, in degrees, that deskewing corrects.
+const MAX_DESKEW_ANGLE: f32 = 5.0;
+
+/// Smallest detected tilt, in degrees, worth rotating a page for.
+const MIN_DESKEW_ANGLE: f32 = 0.2;
+
+/// Detects the tilt of a page in degrees (positive: lines descend to the right).
+///
+/// Uses the projection profile method on a downscaled copy: dark pixels are projected
+/// onto the vertical axis of each candidate rotation, and the angle at which they pile
+/// up into the sharpest rows (text lines, panel borders) wins.
+fn detect_skew(image: &DynamicImage) -> f32 {
+    const ANALYSIS_SIZE: u32 = 600;
+    const STEP: f32 = 0.1;
+
+    let luma = image.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).to_luma8();
+    let dark: Vec<(f32, f32)> = luma
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p[0] < 128)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if dark.is_empty() {
+        return 0.0;
+    }
+
+    let width = luma.width() as f32;
+    let offset = width * MAX_DESKEW_ANGLE.to_radians().sin(); // Keep row indices positive
+    let rows = (luma.height() as f32 + 2.0 * offset) as usize + 1;
+    let steps = (MAX_DESKEW_ANGLE / STEP).round() as i32;
+
+    let score = |angle: f32| {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut profile = vec![0u64; rows];
+        for &(x, y) in &dark {
+            let row = (y * cos - x * sin + offset) as usize;
+            profile[row.min(rows - 1)] += 1;
+        }
+        profile.iter().map(|count| count * count).sum::<u64>()
+    };
+
+    let mut best = (0.0, score(0.0));
+    for step in (-steps..=steps).filter(|&step| step != 0) {
+        let angle = step as f32 * STEP;
+        let score = score(angle);
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+    best.0
+}
+
+/// Rotates the page so that lines tilted by `angle` degrees become horizontal, using
+/// bilinear sampling around the center. Uncovered areas are filled with white; grayscale
+/// pages stay grayscale.
+fn rotate(image: &DynamicImage, angle: f32) -> DynamicImage {
+    let has_alpha = image.color().has_alpha();
+    if image.color().has_color() {
+        let white = image::Rgba([255, 255, 255, 255]);
+        let rotated = rotate_pixels(&image.to_rgba8(), angle, white);
+        if has_alpha {
+            DynamicImage::ImageRgba8(rotated)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rotated).to_rgb8())
+        }
+    } else {
+        let white = image::LumaA([255, 255]);
+        let rotated = rotate_pixels(&image.to_luma_alpha8(), angle, white);
+        if has_alpha {
+            DynamicImage::ImageLumaA8(rotated)
+        } else {
+            DynamicImage::ImageLuma8(DynamicImage::ImageLumaA8(rotated).to_luma8())
+        }
+    }
+}
+
+/// Rotates the pixels of `source` for [`rotate`], filling uncovered areas with `white`.
+fn rotate_pixels<P: image::Pixel<Subpixel = u8>>(
+    source: &image::ImageBuffer<P, Vec<u8>>,
+    angle: f32,
+    white: P,
+) -> image::ImageBuffer<P, Vec<u8>> {
+    let (width, height) = source.dimensions();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    let sample = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            white
+        } else {
+            *source.get_pixel(x as u32, y as u32)
+        }
+    };
+
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let sx = cx + dx * cos - dy * sin - 0.5;
+        let sy = cy + dx * sin + dy * cos - 0.5;
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let corners = [
+            (sample(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (sample(x0 + 1, y0), fx * (1.0 - fy)),
+            (sample(x0, y0 + 1), (1.0 - fx) * fy),
+            (sample(x0 + 1, y0 + 1), fx * fy),
+        ];
+        let mut pixel = white;
+        for (c, channel) in pixel.channels_mut().iter_mut().enumerate() {
+            *channel = corners
+                .iter()
+                .map(|(pixel, weight)| pixel.channels()[c] as f32 * weight)
+                .sum::<f32>()
+                .round() as u8;
+        }
+        pixel
+    })
+}
+
+/// Stretches the luminance range of the image to full black and white, blended with
+/// the original by `strength`. The same curve is applied to all color channels, so hues
+/// are kept and grayscale pages stay grayscale; alpha is left untouched.
+fn auto_levels(image: DynamicImage, strength: f32) -> DynamicImage {
+    const CLIP_FRACTION: f64 = 0.005; // Ignore the darkest/brightest 0.5% (noise, specks)
+
+    let luma = image.to_luma8();
+    let mut histogram = [0u64; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let clip = (luma.pixels().len() as f64 * CLIP_FRACTION) as u64;
+    fn percentile(
+        histogram: &[u64; 256],
+        clip: u64,
+        mut levels: impl Iterator<Item = usize>,
+    ) -> f32 {
+        let mut seen = 0;
+        levels
+            .find(|&level| {
+                seen += histogram[level];
+                seen > clip
+            })
+            .unwrap_or_default() as f32
+    }
+    let low = percentile(&histogram, clip, 0..256);
+    let high = percentile(&histogram, clip, (0..256).rev());
+    if high - low < 1.0 {
+        return image; // Blank page, nothing to stretch
+    }
+
+    let lut: [u8; 256] = std::array::from_fn(|level| {
+        let level = level as f32;
+        let stretched = ((level - low) * 255.0 / (high - low)).clamp(0.0, 255.0);
+        (level + (stretched - level) * strength).round() as u8
+    });
+
+    match (image.color().has_color(), image.color().has_alpha()) {
+        (false, false) => {
+            let mut pixels = luma;
+            for channel in pixels.iter_mut() {
+                *channel = lut[*channel as usize];
+            }
+            DynamicImage::ImageLuma8(pixels)
+        }
+        (false, true) => {
+            let mut pixels = image.to_luma_alpha8();
+            for pixel in pixels.pixels_mut() {
+                pixel.0[0] = lut[pixel.0[0] as usize];
+            }
+            DynamicImage::ImageLumaA8(pixels)
+        }
+        (true, true) => {
+            let mut pixels = image.to_rgba8();
+            for pixel in pixels.pixels_mut() {
+                for channel in &mut pixel.0[..3] {
+                    *channel = lut[*channel as usize];
+                }
+            }
+            DynamicImage::ImageRgba8(pixels)
+        }
+        (true, false) => {
+            let mut pixels = image.to_rgb8();
+            for channel in pixels.iter_mut() {
+                *channel = lut[*channel as usize];
+            }
+            DynamicImage::ImageRgb8(pixels)
+        }
+    }
+}
+
+/// Decodes an image together with its embedded ICC profile, if any.
+fn decode(bytes: &[u8]) -> Result<(DynamicImage, Option<Vec<u8>>)> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()?;
+    // A malformed profile shouldn't make the page unusable
+    let icc_profile = decoder.icc_profile().unwrap_or_else(|e| {
+        log::warn!("Ignoring unreadable ICC profile: {}", e);
+        None
+    });
+    let image = DynamicImage::from_decoder(decoder)?;
+    Ok((image, icc_profile))
+}
+
+/// Converts the pixels of `image` from the color space described by `icc_profile` to
+/// sRGB. Images whose profile can't be parsed or transformed are returned unchanged.
+fn convert_to_srgb(image: DynamicImage, icc_profile: &[u8]) -> DynamicImage {
+    let Some(input) = qcms::Profile::new_from_slice(icc_profile, false) else {
+        log::warn!("Unsupported ICC profile; page colors are kept as they are");
+        return image;
+    };
+    if input.is_sRGB() {
+        return image;
+    }
+    let mut output = qcms::Profile::new_sRGB();
+    output.precache_output_transform();
+    let intent = qcms::Intent::default();
+
+    if image.color().has_alpha() {
+        let mut pixels = image.to_rgba8();
+        if let Some(transform) =
+            qcms::Transform::new(&input, &output, qcms::DataType::RGBA8, intent)
+        {
+            transform.apply(&mut pixels);
+            return DynamicImage::ImageRgba8(pixels);
+        }
+    } else if let Some(transform) =
+        qcms::Transform::new(&input, &output, qcms::DataType::RGB8, intent)
+    {
+        let mut pixels = image.to_rgb8();
+        transform.apply(&mut pixels);
+        return DynamicImage::ImageRgb8(pixels);
+    } else if let Some(transform) = qcms::Transform::new_to(
+        &input,
+        &output,
+        qcms::DataType::Gray8,
+        qcms::DataType::RGB8,
+        intent,
+    ) {
+        // Gray profiles only transform grayscale pixels
+        let gray = image.to_luma8();
+        let mut pixels = image::RgbImage::new(gray.width(), gray.height());
+        transform.convert(&gray, &mut pixels);
+        return DynamicImage::ImageRgb8(pixels);
+    }
+
+    log::warn!("Cannot convert ICC profile to sRGB; page colors are kept as they are");
+    image
+}
+
+/// Encodes an image handed to a generator directly, in the format given by the
+/// extension of `name` (JPEG at the default quality). Blocking.
+pub(crate) fn encode_page(image: &DynamicImage, name: &str) -> Result<Vec<u8>> {
+    let format = ImageFormat::from_path(Path::new(name))?;
+    let format = ProcessedImageFormat::from_image_format(format)
+        .ok_or_else(|| Error::Unsupported(format!("Encoding {:?}", format)))?;
+    encode(image, format, ImageProcessing::default().jpeg_quality, None)
+}
+
+/// Re-encodes the encoded image `bytes` into `format` (JPEG at the default quality),
+/// keeping an embedded ICC profile. Blocking.
+pub(crate) fn transcode(bytes: &[u8], format: ProcessedImageFormat) -> Result<Vec<u8>> {
+    let (image, icc_profile) = decode(bytes)?;
+    encode(
+        &image,
+        format,
+        ImageProcessing::default().jpeg_quality,
+        icc_profile,
+    )
+}
+
+/// Encodes an image into the given format, embedding `icc_profile` if given.
+fn encode(
+    image: &DynamicImage,
+    format: ProcessedImageFormat,
+    jpeg_quality: u8,
+    icc_profile: Option<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    fn embed_profile(encoder: &mut impl ImageEncoder, icc_profile: Option<Vec<u8>>) {
+        if let Some(profile) = icc_profile
+            && let Err(e) = encoder.set_icc_profile(profile)
+        {
+            log::warn!("Failed to embed ICC profile: {}", e);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    match format {
+        ProcessedImageFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut buffer, jpeg_quality);
+            embed_profile(&mut encoder, icc_profile);
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+        }
+        ProcessedImageFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut buffer);
+            embed_profile(&mut encoder, icc_profile);
+            image.write_with_encoder(encoder)?;
+        }
+        ProcessedImageFormat::WebP => {
+            let mut encoder = WebPEncoder::new_lossless(&mut buffer);
+            embed_profile(&mut encoder, icc_profile);
+            DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder)?;
+        }
+    }
+    Ok(buffer)
+}
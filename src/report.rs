@@ -0,0 +1,375 @@
+//! Machine-readable summary of a conversion run.
+//!
+//! With [`HozonConfig::result_bundle`](crate::HozonConfig::result_bundle) enabled, every
+//! conversion writes [`RESULT_BUNDLE_FILE_NAME`] into the output directory. It combines
+//! the analysis and structure reports, the generated files, stage timings, settings
+//! without effect and the warnings raised during the run, so CI pipelines can assert on
+//! conversion quality without parsing logs. The bundle is also written when the conversion fails (as long as
+//! the output directory exists), with `status` set to `"failed"` and the error message.
+//! Conversions that were [shut down](crate::pipeline::ConversionHandle::shutdown) have
+//! `status` `"shut_down"` and list the volumes they didn't generate.
+
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::HozonConfig;
+use crate::collector::NESTED_ARCHIVE_GUIDANCE;
+use crate::error::{Error, Result};
+use crate::events::{PipelineEvent, emit};
+use crate::path_utils::{path_to_string_lossy, retry_while_locked};
+use crate::telemetry;
+use crate::types::{
+    AnalyzeFinding, AnalyzeReport, HozonExecutionMode, PageMapping, SkippedVolume,
+    VolumeStructureReport,
+};
+
+/// Name of the result bundle written into the output directory.
+pub const RESULT_BUNDLE_FILE_NAME: &str = "hozon-report.json";
+
+/// Warnings raised during generation. Messages are logged and kept for the result bundle.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WarningLog(Arc<Mutex<Vec<String>>>);
+
+impl WarningLog {
+    pub(crate) fn warn(&self, message: String) {
+        log::warn!("{}", message);
+        if let Ok(mut warnings) = self.0.lock() {
+            warnings.push(message);
+        }
+    }
+
+    fn messages(&self) -> Vec<String> {
+        self.0.lock().map(|w| w.clone()).unwrap_or_default()
+    }
+}
+
+/// One file written by the generation stage.
+#[derive(Debug, Clone)]
+pub(crate) struct GeneratedOutput {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume was split
+    pub path: PathBuf,
+    pub page_count: usize,
+    pub pages: Vec<PageMapping>,
+    pub duration: Duration,
+}
+
+/// Collects the reports of one conversion run until it is written.
+#[derive(Debug)]
+pub(crate) struct ResultBundle {
+    mode: HozonExecutionMode,
+    started: Instant,
+    analysis: Option<AnalyzeReport>,
+    structure: Option<VolumeStructureReport>,
+    outputs: Vec<GeneratedOutput>,
+    skipped: Vec<SkippedVolume>,
+    timings: Vec<(&'static str, Duration)>,
+    warnings: WarningLog,
+}
+
+/// Returns the name, severity and details of an analysis finding.
+fn describe_finding(finding: &AnalyzeFinding) -> (&'static str, &'static str, Value) {
+    match finding {
+        AnalyzeFinding::ConsistentNamingFound { count, pattern } => (
+            "ConsistentNamingFound",
+            "info",
+            json!({ "count": count, "pattern": pattern }),
+        ),
+        AnalyzeFinding::ConsistentImageFormat { format } => {
+            ("ConsistentImageFormat", "info", json!({ "format": format }))
+        }
+        AnalyzeFinding::SplitPagesFound {
+            chapter_path,
+            count,
+        } => (
+            "SplitPagesFound",
+            "info",
+            json!({ "chapter_path": path_to_string_lossy(chapter_path), "count": count }),
+        ),
+        AnalyzeFinding::InconsistentPageCount {
+            chapter_path,
+            expected,
+            found,
+        } => (
+            "InconsistentPageCount",
+            "warning",
+            json!({
+                "chapter_path": path_to_string_lossy(chapter_path),
+                "expected": expected,
+                "found": found,
+            }),
+        ),
+        AnalyzeFinding::UnnumberedChapter { chapter_path } => (
+            "UnnumberedChapter",
+            "warning",
+            json!({ "chapter_path": path_to_string_lossy(chapter_path) }),
+        ),
+        AnalyzeFinding::UnusualFileSize {
+            file_path,
+            size_kb,
+            average_kb,
+        } => (
+            "UnusualFileSize",
+            "warning",
+            json!({
+                "file_path": path_to_string_lossy(file_path),
+                "size_kb": size_kb,
+                "average_kb": average_kb,
+            }),
+        ),
+        AnalyzeFinding::SpecialCharactersInPath { path } => (
+            "SpecialCharactersInPath",
+            "warning",
+            json!({ "path": path_to_string_lossy(path) }),
+        ),
+        AnalyzeFinding::CollectionDepthMismatch {
+            configured,
+            recommended,
+        } => (
+            "CollectionDepthMismatch",
+            "warning",
+            json!({
+                "configured": format!("{:?}", configured),
+                "recommended": format!("{:?}", recommended),
+            }),
+        ),
+        AnalyzeFinding::MissingPages { chapter, gaps } => (
+            "MissingPages",
+            "warning",
+            json!({ "chapter": path_to_string_lossy(chapter), "gaps": gaps }),
+        ),
+        AnalyzeFinding::UnsupportedFileIgnored { path } => (
+            "UnsupportedFileIgnored",
+            "error",
+            json!({ "path": path_to_string_lossy(path) }),
+        ),
+        AnalyzeFinding::NestedArchive {
+            path,
+            alongside_images,
+        } => (
+            "NestedArchive",
+            "error",
+            json!({
+                "path": path_to_string_lossy(path),
+                "alongside_images": alongside_images,
+                "guidance": NESTED_ARCHIVE_GUIDANCE,
+            }),
+        ),
+        AnalyzeFinding::UnreadableArchive { path, reason } => (
+            "UnreadableArchive",
+            "error",
+            json!({ "path": path_to_string_lossy(path), "reason": reason }),
+        ),
+        AnalyzeFinding::UnmappedChapter {
+            chapter_path,
+            chapter_number,
+        } => (
+            "UnmappedChapter",
+            "fatal",
+            json!({
+                "chapter_path": path_to_string_lossy(chapter_path),
+                "chapter_number": chapter_number,
+            }),
+        ),
+        AnalyzeFinding::SourcePathNotFound { path } => (
+            "SourcePathNotFound",
+            "fatal",
+            json!({ "path": path_to_string_lossy(path) }),
+        ),
+        AnalyzeFinding::PermissionDenied { path } => (
+            "PermissionDenied",
+            "fatal",
+            json!({ "path": path_to_string_lossy(path) }),
+        ),
+        AnalyzeFinding::NoChaptersFound => ("NoChaptersFound", "fatal", json!({})),
+        AnalyzeFinding::NoPagesFound => ("NoPagesFound", "fatal", json!({})),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+impl ResultBundle {
+    pub(crate) fn new(mode: HozonExecutionMode) -> Self {
+        Self {
+            mode,
+            started: Instant::now(),
+            analysis: None,
+            structure: None,
+            outputs: Vec::new(),
+            skipped: Vec::new(),
+            timings: Vec::new(),
+            warnings: WarningLog::default(),
+        }
+    }
+
+    /// Handle for recording warnings from generation tasks.
+    pub(crate) fn warnings(&self) -> &WarningLog {
+        &self.warnings
+    }
+
+    pub(crate) fn set_analysis(&mut self, report: &AnalyzeReport, started: Instant) {
+        self.analysis = Some(report.clone());
+        self.timings.push(("analysis", started.elapsed()));
+    }
+
+    pub(crate) fn set_structure(&mut self, report: &VolumeStructureReport, started: Instant) {
+        self.structure = Some(report.clone());
+        self.timings.push(("structuring", started.elapsed()));
+    }
+
+    pub(crate) fn set_outputs(
+        &mut self,
+        outputs: Vec<GeneratedOutput>,
+        skipped: Vec<SkippedVolume>,
+        started: Instant,
+    ) {
+        self.outputs = outputs;
+        self.skipped = skipped;
+        self.timings.push(("generation", started.elapsed()));
+    }
+
+    fn to_json(&self, config: &HozonConfig, error: Option<&Error>) -> Value {
+        let mut warnings = Vec::new();
+        let analysis = self.analysis.as_ref().map(|report| {
+            let findings: Vec<Value> = report
+                .findings
+                .iter()
+                .map(|finding| {
+                    let (kind, severity, details) = describe_finding(finding);
+                    if severity != "info" {
+                        warnings.push(format!("{}: {}", kind, details));
+                    }
+                    json!({ "kind": kind, "severity": severity, "details": details })
+                })
+                .collect();
+            json!({
+                "recommended_strategy": format!("{:?}", report.recommended_strategy),
+                "recommended_depth": format!("{:?}", report.recommended_depth),
+                "findings": findings,
+            })
+        });
+        warnings.extend(self.warnings.messages());
+
+        let ignored_options: Vec<Value> = config
+            .ignored_options()
+            .into_iter()
+            .map(|ignored| {
+                warnings.push(ignored.to_string());
+                json!({ "option": ignored.option, "reason": ignored.reason })
+            })
+            .collect();
+
+        let structure = self.structure.as_ref().map(|report| {
+            json!({
+                "grouping_strategy": format!("{:?}", config.volume_grouping_strategy),
+                "total_chapters_processed": report.total_chapters_processed,
+                "total_volumes_created": report.total_volumes_created,
+                "chapter_counts_per_volume": report.chapter_counts_per_volume,
+            })
+        });
+
+        let outputs: Vec<Value> = self
+            .outputs
+            .iter()
+            .map(|output| {
+                json!({
+                    "volume_number": output.volume_number,
+                    "part_number": output.part_number,
+                    "path": path_to_string_lossy(&output.path),
+                    "page_count": output.page_count,
+                    "pages": output.pages.iter().map(|page| json!({
+                        "source": path_to_string_lossy(&page.source),
+                        "chapter": page.chapter,
+                        "series_chapter": page.series_chapter,
+                        "chapter_page": page.chapter_page,
+                        "volume_page": page.volume_page,
+                    })).collect::<Vec<_>>(),
+                    "size_bytes": std::fs::metadata(&output.path).map(|m| m.len()).ok(),
+                    "duration_ms": millis(output.duration),
+                })
+            })
+            .collect();
+
+        let mut timings = serde_json::Map::new();
+        for (stage, duration) in &self.timings {
+            timings.insert(format!("{}_ms", stage), json!(millis(*duration)));
+        }
+        timings.insert("total_ms".into(), json!(millis(self.started.elapsed())));
+
+        json!({
+            "hozon_version": env!("CARGO_PKG_VERSION"),
+            "generated_at": chrono::Utc::now().to_rfc3339(),
+            "status": match (error, self.skipped.is_empty()) {
+                (Some(_), _) => "failed",
+                (None, false) => "shut_down",
+                (None, true) => "succeeded",
+            },
+            "error": error.map(|e| e.to_string()),
+            "mode": format!("{:?}", self.mode),
+            "title": config.metadata.title,
+            "format": config.output_format.extension(),
+            "analysis": analysis,
+            "structure": structure,
+            "conversion": {
+                "outputs": outputs,
+                "skipped": self.skipped.iter().map(|volume| json!({
+                    "volume_number": volume.volume_number,
+                    "part_number": volume.part_number,
+                })).collect::<Vec<_>>(),
+                "total_pages": self.outputs.iter().map(|o| o.page_count).sum::<usize>(),
+            },
+            "timings": timings,
+            "ignored_options": ignored_options,
+            "warnings": warnings,
+        })
+    }
+
+    fn write(&self, config: &HozonConfig, directory: &Path, error: Option<&Error>) -> Result<()> {
+        let path = directory.join(RESULT_BUNDLE_FILE_NAME);
+        let content = serde_json::to_string_pretty(&self.to_json(config, error))
+            .map_err(|e| Error::Other(format!("Failed to serialize result bundle: {}", e)))?;
+        retry_while_locked(&path, "write result bundle", || {
+            std::fs::write(&path, &content)
+        })
+    }
+
+    /// Writes the bundle if enabled and passes `result` through. Failing to write the
+    /// bundle fails a successful run; after a failed run it is only logged.
+    pub(crate) fn finish<T>(self, config: &HozonConfig, result: Result<T>) -> Result<T> {
+        let event = match &result {
+            Ok(_) => PipelineEvent::ConversionFinished {
+                files: self.outputs.len(),
+                duration: self.started.elapsed(),
+            },
+            Err(e) => {
+                telemetry::record_error(e);
+                PipelineEvent::ConversionFailed {
+                    error: e.to_string(),
+                }
+            }
+        };
+        emit(&config.event_sinks, event);
+        if !config.result_bundle {
+            return result;
+        }
+        let directory = config.output_directory();
+        match result {
+            Ok(value) => {
+                self.write(config, &directory, None)?;
+                Ok(value)
+            }
+            Err(e) => {
+                if directory.is_dir()
+                    && let Err(write_error) = self.write(config, &directory, Some(&e))
+                {
+                    log::warn!("Failed to write result bundle: {}", write_error);
+                }
+                Err(e)
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! Process-wide concurrency limits shared between conversions.
+//!
+//! Every [`HozonConfig`](crate::hozon::HozonConfig) caps its own parallelism by default.
+//! When several conversions run in the same process (e.g. a batch of series), those
+//! per-config caps add up. A [`RuntimeLimits`] handle can be cloned into each config so
+//! that all of them draw from the same pool of volume and disk I/O permits instead.
+//!
+//! Independently of any limits, all conversions in the process share one budget of open
+//! file handles for directory scans, page reads and the output, lock and manifest files,
+//! sized from the operating system's open file limit (see [`open_file_budget`]). When
+//! it's used up, further opens wait for a handle instead of failing with "too many open
+//! files". Output files take at most half of it, so page reads always find a handle.
+//!
+//! A [`ThrottleProfile`] trades speed for responsiveness, e.g. to keep a desktop usable
+//! while conversions run in the background. For short bursts of latency-sensitive work,
+//! a spawned conversion can also be paused entirely with
+//! [`ConversionHandle::pause`](crate::pipeline::ConversionHandle::pause).
+
+use lazy_static::lazy_static;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+
+use crate::error::{Error, Result};
+use crate::generator::PAGE_PREFETCH_DEPTH;
+use crate::storage::StorageKind;
+
+/// Upper bound of the budget; more concurrent reads don't speed anything up
+const MAX_FILE_HANDLE_BUDGET: usize = 1024;
+
+lazy_static! {
+    static ref FILE_HANDLE_BUDGET: AtomicUsize = AtomicUsize::new(compute_file_handle_budget());
+    static ref FILE_HANDLES: Arc<Semaphore> = Arc::new(Semaphore::new(open_file_budget()));
+    /// Caps the output files open at once, which stay open while a whole volume is
+    /// written and would otherwise starve its page reads
+    static ref OUTPUT_FILE_SLOTS: Arc<Semaphore> =
+        Arc::new(Semaphore::new(output_file_slots(open_file_budget())));
+}
+
+/// Number of output files that may be open at once with `budget` file handles.
+fn output_file_slots(budget: usize) -> usize {
+    (budget / 2).max(1)
+}
+
+/// Half of the soft open file limit, leaving room for everything else the process has open.
+#[cfg(unix)]
+fn compute_file_handle_budget() -> usize {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit only writes to the struct it is given
+    let soft_limit = if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0 {
+        usize::try_from(limit.rlim_cur).unwrap_or(usize::MAX)
+    } else {
+        256 // The lowest common default (macOS)
+    };
+    (soft_limit / 2).clamp(8, MAX_FILE_HANDLE_BUDGET)
+}
+
+/// Windows has no small per-process limit on file handles.
+#[cfg(not(unix))]
+fn compute_file_handle_budget() -> usize {
+    MAX_FILE_HANDLE_BUDGET
+}
+
+/// Returns how many files Hozon keeps open at once, across all conversions in the process.
+pub fn open_file_budget() -> usize {
+    FILE_HANDLE_BUDGET.load(Ordering::SeqCst)
+}
+
+/// Replaces the open file budget, e.g. to leave more handles to the rest of a program
+/// with a low open file limit. The budget is at least 2, one output file and one read.
+///
+/// Meant to be called before conversions start; lowering the budget while files are
+/// open takes effect as they close.
+pub fn set_open_file_budget(budget: usize) {
+    let budget = budget.max(2);
+    // Created from the previous budget, then resized
+    lazy_static::initialize(&FILE_HANDLES);
+    lazy_static::initialize(&OUTPUT_FILE_SLOTS);
+    let previous = FILE_HANDLE_BUDGET.swap(budget, Ordering::SeqCst);
+    resize(&FILE_HANDLES, previous, budget);
+    resize(
+        &OUTPUT_FILE_SLOTS,
+        output_file_slots(previous),
+        output_file_slots(budget),
+    );
+}
+
+/// Grows or shrinks `semaphore` from `previous` to `permits` permits.
+fn resize(semaphore: &Arc<Semaphore>, previous: usize, permits: usize) {
+    if permits >= previous {
+        semaphore.add_permits(permits - previous);
+        return;
+    }
+    let excess = previous - permits;
+    let in_use = u32::try_from(excess - semaphore.forget_permits(excess)).unwrap_or(u32::MAX);
+    if in_use == 0 {
+        return;
+    }
+    // Permits in use are forgotten as they come back
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        let semaphore = Arc::clone(semaphore);
+        runtime.spawn(async move {
+            if let Ok(permits) = semaphore.acquire_many_owned(in_use).await {
+                permits.forget();
+            }
+        });
+    }
+}
+
+/// Waits for a handle from the process-wide open file budget. Hold the permit while the
+/// file or directory is open.
+pub(crate) async fn acquire_file_handle() -> Result<OwnedSemaphorePermit> {
+    Ok(Arc::clone(&FILE_HANDLES).acquire_owned().await?)
+}
+
+/// A handle from the open file budget for an output file, held until the file is written.
+pub(crate) struct OutputFileHandle {
+    _slot: OwnedSemaphorePermit,
+    _handle: OwnedSemaphorePermit,
+}
+
+/// Waits for a handle for an output file. Only half of the budget goes to output files,
+/// so volumes waiting for one never hold up the page reads of those being written.
+pub(crate) async fn acquire_output_file_handle() -> Result<OutputFileHandle> {
+    let slot = Arc::clone(&OUTPUT_FILE_SLOTS).acquire_owned().await?;
+    Ok(OutputFileHandle {
+        _slot: slot,
+        _handle: acquire_file_handle().await?,
+    })
+}
+
+/// Converts an error from opening `path`, reporting exhausted file handles as
+/// [`Error::TooManyOpenFiles`].
+pub(crate) fn open_error(error: std::io::Error, path: &Path) -> Error {
+    if is_too_many_open_files(&error) {
+        Error::TooManyOpenFiles(path.to_path_buf())
+    } else {
+        Error::Io(error)
+    }
+}
+
+/// Whether `error` means the process or system is out of file handles.
+pub(crate) fn is_too_many_open_files(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    let codes = [libc::EMFILE, libc::ENFILE];
+    #[cfg(windows)]
+    let codes = [4]; // ERROR_TOO_MANY_OPEN_FILES
+    #[cfg(not(any(unix, windows)))]
+    let codes: [i32; 0] = [];
+    error
+        .raw_os_error()
+        .is_some_and(|code| codes.contains(&code))
+}
+
+/// How hard a conversion may use the machine.
+///
+/// Applied through [`RuntimeLimits::throttled`], or with
+/// [`HozonConfig::throttle`](crate::hozon::HozonConfig::throttle) for conversions
+/// without shared limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThrottleProfile {
+    /// For background conversions: one volume at once, few concurrent reads and image
+    /// workers, fewer pages read ahead, and a short pause before every page.
+    Low,
+    /// The default caps.
+    #[default]
+    Normal,
+    /// For dedicated machines: one volume per core and more reads in flight.
+    High,
+}
+
+/// Pause before every page read under [`ThrottleProfile::Low`], giving interactive
+/// programs a chance to run.
+const LOW_THROTTLE_PAGE_PAUSE: Duration = Duration::from_millis(5);
+
+impl ThrottleProfile {
+    /// Number of pages read ahead of the archive writer within a single volume.
+    pub(crate) fn prefetch_depth(self) -> usize {
+        match self {
+            ThrottleProfile::Low => 2,
+            ThrottleProfile::Normal => PAGE_PREFETCH_DEPTH,
+            ThrottleProfile::High => 16,
+        }
+    }
+
+    /// Called before every page read; pauses briefly under [`ThrottleProfile::Low`] and
+    /// returns immediately otherwise.
+    pub(crate) async fn pace_page(self) {
+        if self == ThrottleProfile::Low {
+            tokio::time::sleep(LOW_THROTTLE_PAGE_PAUSE).await;
+        }
+    }
+}
+
+/// Shutdown and pause requests for one conversion, shared between its
+/// [`ConversionHandle`](crate::pipeline::ConversionHandle) and its volume tasks.
+#[derive(Debug, Clone)]
+pub(crate) struct ConversionControl {
+    shutdown: Arc<AtomicBool>,
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ConversionControl {
+    fn default() -> Self {
+        Self {
+            shutdown: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl ConversionControl {
+    /// Stops volumes that haven't started yet; resumes a paused conversion so the
+    /// volumes being written can finish.
+    pub(crate) fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.resume();
+    }
+
+    pub(crate) fn is_shutdown_requested(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub(crate) fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Called before every page read; waits while the conversion is paused. Waiting
+    /// happens before any permit is acquired, so paused conversions don't hold back
+    /// others sharing the same [`RuntimeLimits`].
+    pub(crate) async fn wait_while_paused(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives as long as `self`, so waiting can't fail
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
+
+/// Shared limits for concurrent volume generation, disk reads, and CPU-heavy image work.
+///
+/// Cloning a `RuntimeLimits` is cheap and yields a handle to the *same* permit pools.
+/// The CPU pool is drawn from by every phase doing image decoding: cover analysis for
+/// [`VolumeGroupingStrategy::ImageAnalysis`](crate::VolumeGroupingStrategy::ImageAnalysis)
+/// as well as resizing and transcoding pages during generation, so conversions sharing a
+/// handle never decode more images at once than the budget allows.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use hozon::prelude::*;
+/// # use std::path::PathBuf;
+/// # #[tokio::main]
+/// # async fn main() -> hozon::error::Result<()> {
+/// let limits = RuntimeLimits::new(4, 16)?;
+///
+/// let mut configs = Vec::new();
+/// for series in ["Series A", "Series B"] {
+///     configs.push(
+///         HozonConfig::builder()
+///             .metadata(EbookMetadata::default_with_title(series.to_string()))
+///             .source_path(PathBuf::from(format!("./source/{}", series)))
+///             .target_path(PathBuf::from("./output"))
+///             .runtime_limits(limits.clone())
+///             .build()?,
+///     );
+/// }
+/// let conversions = configs
+///     .iter()
+///     .map(|config| config.convert_from_source(CoverOptions::None));
+/// futures::future::try_join_all(conversions).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RuntimeLimits {
+    volumes: Arc<Semaphore>,
+    io: Arc<Semaphore>,
+    cpu: Arc<Semaphore>,
+    max_concurrent_volumes: usize,
+    max_concurrent_io: usize,
+    max_concurrent_cpu: usize,
+    throttle: ThrottleProfile,
+}
+
+impl RuntimeLimits {
+    /// Creates a new set of shared limits. CPU-heavy image work is capped at the number
+    /// of cores; see [`RuntimeLimits::with_max_concurrent_cpu`].
+    ///
+    /// # Arguments
+    ///
+    /// * `max_concurrent_volumes` - Maximum number of volumes generated at once across all
+    ///   conversions using this handle
+    /// * `max_concurrent_io` - Maximum number of page files read from disk at once across
+    ///   all conversions using this handle
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RuntimeLimits>` - The limits, or an error if either limit is zero
+    pub fn new(max_concurrent_volumes: usize, max_concurrent_io: usize) -> Result<Self> {
+        if max_concurrent_volumes == 0 || max_concurrent_io == 0 {
+            return Err(Error::Other(
+                "Runtime limits must allow at least one concurrent operation".to_string(),
+            ));
+        }
+        let max_concurrent_cpu = num_cpus::get().max(1);
+        Ok(Self {
+            volumes: Arc::new(Semaphore::new(max_concurrent_volumes)),
+            io: Arc::new(Semaphore::new(max_concurrent_io)),
+            cpu: Arc::new(Semaphore::new(max_concurrent_cpu)),
+            max_concurrent_volumes,
+            max_concurrent_io,
+            max_concurrent_cpu,
+            throttle: ThrottleProfile::Normal,
+        })
+    }
+
+    /// Rescales the caps for `throttle` and makes generators using these limits pace
+    /// their page reads accordingly:
+    ///
+    /// - [`ThrottleProfile::Low`]: One volume, at most two page reads and a quarter of the
+    ///   image workers at once
+    /// - [`ThrottleProfile::Normal`]: The caps stay as they are
+    /// - [`ThrottleProfile::High`]: At least one volume per core and eight page reads per
+    ///   volume
+    ///
+    /// Replaces the permit pools, so call it before handing out clones.
+    pub fn throttled(self, throttle: ThrottleProfile) -> Self {
+        let cores = num_cpus::get().max(1);
+        let (volumes, io, cpu) = match throttle {
+            ThrottleProfile::Low => (
+                1,
+                self.max_concurrent_io.min(2),
+                (self.max_concurrent_cpu / 4).max(1),
+            ),
+            ThrottleProfile::Normal => (
+                self.max_concurrent_volumes,
+                self.max_concurrent_io,
+                self.max_concurrent_cpu,
+            ),
+            ThrottleProfile::High => {
+                let volumes = self.max_concurrent_volumes.max(cores);
+                (
+                    volumes,
+                    self.max_concurrent_io.max(volumes * 8),
+                    self.max_concurrent_cpu.max(cores),
+                )
+            }
+        };
+        let mut limits = Self::new(volumes, io)
+            .and_then(|limits| limits.with_max_concurrent_cpu(cpu))
+            .expect("throttled caps are non-zero");
+        limits.throttle = throttle;
+        limits
+    }
+
+    /// Caps CPU-heavy image work (cover analysis, resizing, transcoding) at
+    /// `max_concurrent_cpu` images at once across all conversions using this handle.
+    ///
+    /// Replaces the CPU pool, so call it before handing out clones.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RuntimeLimits>` - The limits, or an error if the limit is zero
+    pub fn with_max_concurrent_cpu(mut self, max_concurrent_cpu: usize) -> Result<Self> {
+        if max_concurrent_cpu == 0 {
+            return Err(Error::Other(
+                "Runtime limits must allow at least one concurrent operation".to_string(),
+            ));
+        }
+        self.cpu = Arc::new(Semaphore::new(max_concurrent_cpu));
+        self.max_concurrent_cpu = max_concurrent_cpu;
+        Ok(self)
+    }
+
+    /// Returns the maximum number of volumes generated concurrently.
+    pub fn max_concurrent_volumes(&self) -> usize {
+        self.max_concurrent_volumes
+    }
+
+    /// Returns the maximum number of concurrent page reads.
+    pub fn max_concurrent_io(&self) -> usize {
+        self.max_concurrent_io
+    }
+
+    /// Returns the maximum number of images decoded or encoded concurrently.
+    pub fn max_concurrent_cpu(&self) -> usize {
+        self.max_concurrent_cpu
+    }
+
+    /// Returns the profile set with [`RuntimeLimits::throttled`].
+    pub fn throttle(&self) -> ThrottleProfile {
+        self.throttle
+    }
+
+    /// Returns the caps a single conversion uses on its own when reading from `storage`:
+    /// the [default](RuntimeLimits::default) caps, with page reads lowered to
+    /// [`StorageKind::max_concurrent_reads`].
+    pub fn for_storage(storage: StorageKind) -> Self {
+        let defaults = Self::default();
+        match storage.max_concurrent_reads() {
+            Some(reads) if reads < defaults.max_concurrent_io => {
+                Self::new(defaults.max_concurrent_volumes, reads)
+                    .expect("storage caps are non-zero")
+            }
+            _ => defaults,
+        }
+    }
+
+    /// Waits for a volume generation permit.
+    pub(crate) async fn acquire_volume(&self) -> Result<OwnedSemaphorePermit> {
+        Ok(Arc::clone(&self.volumes).acquire_owned().await?)
+    }
+
+    /// Returns the semaphore bounding concurrent disk reads.
+    pub(crate) fn io_semaphore(&self) -> Arc<Semaphore> {
+        Arc::clone(&self.io)
+    }
+
+    /// Returns the semaphore bounding concurrent image decoding and encoding.
+    pub(crate) fn cpu_semaphore(&self) -> Arc<Semaphore> {
+        Arc::clone(&self.cpu)
+    }
+}
+
+impl Default for RuntimeLimits {
+    /// Matches the caps a single conversion uses on its own: up to four volumes at once
+    /// (fewer on machines with fewer cores) and four page reads per permitted volume.
+    fn default() -> Self {
+        let volumes = num_cpus::get().clamp(1, 4);
+        Self::new(volumes, volumes * 4).expect("default limits are non-zero")
+    }
+}
+
+impl fmt::Debug for RuntimeLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeLimits")
+            .field("max_concurrent_volumes", &self.max_concurrent_volumes)
+            .field("available_volumes", &self.volumes.available_permits())
+            .field("max_concurrent_io", &self.max_concurrent_io)
+            .field("available_io", &self.io.available_permits())
+            .field("max_concurrent_cpu", &self.max_concurrent_cpu)
+            .field("available_cpu", &self.cpu.available_permits())
+            .field("throttle", &self.throttle)
+            .finish()
+    }
+}
@@ -0,0 +1,287 @@
+//! Files written next to generated archives.
+//!
+//! Some self-hosted comic servers and file browsers display covers from image files
+//! lying next to an archive instead of opening it. [`CoverSidecars`] makes generation
+//! write those files directly, so no separate extraction step is needed.
+//!
+//! Static comic servers can also index a directory from a single manifest instead of
+//! opening every archive: with [`HozonConfig::series_manifest`](crate::HozonConfig::series_manifest)
+//! enabled, all generated files are described in an [OPDS 2.0](https://drafts.opds.io/opds-2.0)
+//! feed named [`SERIES_MANIFEST_FILE_NAME`].
+
+use image::DynamicImage;
+use image::codecs::jpeg::JpegEncoder;
+use serde_json::{Value, json};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::path_utils::{prepare_long_path, retry_while_locked};
+use crate::types::{EbookMetadata, FileFormat, IdentifierScheme};
+
+/// Name of the series manifest written into the output directory.
+pub const SERIES_MANIFEST_FILE_NAME: &str = "series.json";
+
+/// Cover image files written next to each generated archive.
+///
+/// For an archive `My Series - Volume 1.cbz`, the full cover is written to
+/// `My Series - Volume 1.cover.jpg` and the thumbnail to
+/// `My Series - Volume 1.cover_thumb.jpg`. The cover is the custom cover of the volume,
+/// or its first page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverSidecars {
+    /// Whether to write the full-size cover (`<name>.cover.jpg`).
+    pub full_cover: bool,
+    /// Longest edge in pixels of the thumbnail (`<name>.cover_thumb.jpg`). `None`
+    /// disables the thumbnail.
+    pub thumbnail_size: Option<u32>,
+    /// JPEG quality (1-100) of the written images.
+    pub jpeg_quality: u8,
+}
+
+impl Default for CoverSidecars {
+    fn default() -> Self {
+        Self {
+            full_cover: true,
+            thumbnail_size: Some(300),
+            jpeg_quality: 90,
+        }
+    }
+}
+
+impl CoverSidecars {
+    /// Validates the sidecar options.
+    pub fn validate(&self) -> Result<()> {
+        if self.thumbnail_size == Some(0) {
+            return Err(Error::Other(
+                "Cover thumbnail size must be greater than zero".to_string(),
+            ));
+        }
+        if self.jpeg_quality == 0 || self.jpeg_quality > 100 {
+            return Err(Error::Other(
+                "Cover sidecar JPEG quality must be between 1 and 100".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the configured cover files for the archive named `file_name_base` into
+    /// `directory`. Blocking; decodes and encodes images.
+    pub(crate) fn write(&self, cover: &Path, directory: &Path, file_name_base: &str) -> Result<()> {
+        if !self.full_cover && self.thumbnail_size.is_none() {
+            return Ok(());
+        }
+
+        let image = image::open(cover).map_err(|e| {
+            Error::InvalidPath(
+                cover.to_path_buf(),
+                format!("Failed to read cover for sidecar files: {}", e),
+            )
+        })?;
+
+        if self.full_cover {
+            let path = directory.join(format!("{}.cover.jpg", file_name_base));
+            self.write_jpeg(&image, &path)?;
+        }
+        if let Some(size) = self.thumbnail_size {
+            let path = directory.join(format!("{}.cover_thumb.jpg", file_name_base));
+            self.write_jpeg(&image.thumbnail(size, size), &path)?;
+        }
+        Ok(())
+    }
+
+    fn write_jpeg(&self, image: &DynamicImage, path: &Path) -> Result<()> {
+        let mut buffer = Vec::new();
+        let encoder = JpegEncoder::new_with_quality(&mut buffer, self.jpeg_quality);
+        DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+        let path = prepare_long_path(path)?;
+        retry_while_locked(&path, "write cover sidecar", || {
+            std::fs::write(&path, &buffer)
+        })
+    }
+}
+
+/// One generated file listed in the series manifest.
+#[derive(Debug, Clone)]
+pub(crate) struct ManifestEntry {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume was split
+    pub file_name_base: String,     // File name without extension
+    pub page_count: usize,
+}
+
+/// Percent-encodes a file name for use as relative link in the manifest.
+fn encode_href(file_name: &str) -> String {
+    let mut encoded = String::with_capacity(file_name.len());
+    for byte in file_name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Returns the identifier of the work as URI, preferring an ISBN.
+fn primary_identifier(metadata: &EbookMetadata) -> Option<String> {
+    metadata
+        .identifiers
+        .iter()
+        .find(|id| id.scheme == IdentifierScheme::Isbn)
+        .map(|id| format!("urn:isbn:{}", id.value))
+        .or_else(|| metadata.identifier.clone())
+        .or_else(|| {
+            metadata
+                .identifiers
+                .first()
+                .map(|id| format!("{}:{}", id.scheme.name(), id.value))
+        })
+}
+
+/// Builds the OPDS publication object of one generated file.
+fn publication(
+    entry: &ManifestEntry,
+    metadata: &EbookMetadata,
+    format: FileFormat,
+    cover_sidecars: Option<&CoverSidecars>,
+) -> Value {
+    let series_name = metadata.series.as_deref().unwrap_or(&metadata.title);
+    let mut publication_metadata = json!({
+        "@type": "http://schema.org/Book",
+        "title": entry.file_name_base,
+        "language": metadata.language,
+        "numberOfPages": entry.page_count,
+        "belongsTo": {
+            "series": { "name": series_name, "position": entry.volume_number },
+        },
+    });
+    let fields = publication_metadata.as_object_mut().unwrap();
+    if let Some(identifier) = primary_identifier(metadata) {
+        fields.insert("identifier".into(), json!(identifier));
+    }
+    if !metadata.identifiers.is_empty() {
+        let identifiers: Vec<Value> = metadata
+            .identifiers
+            .iter()
+            .map(|id| json!({ "scheme": id.scheme.name(), "value": id.value }))
+            .collect();
+        fields.insert("identifiers".into(), json!(identifiers));
+    }
+    if let Some(part_number) = entry.part_number {
+        fields.insert("part".into(), json!(part_number));
+    }
+    if !metadata.authors.is_empty() {
+        fields.insert("author".into(), json!(metadata.authors));
+    }
+    if let Some(publisher) = &metadata.publisher {
+        fields.insert("publisher".into(), json!(publisher));
+    }
+    if let Some(description) = &metadata.description {
+        fields.insert("description".into(), json!(description));
+    }
+    if !metadata.tags.is_empty() {
+        fields.insert("subject".into(), json!(metadata.tags));
+    }
+    if let Some(date) = metadata.release_date {
+        fields.insert(
+            "published".into(),
+            json!(date.format("%Y-%m-%d").to_string()),
+        );
+    }
+
+    let media_type = match format {
+        FileFormat::Epub => "application/epub+zip",
+        FileFormat::Cbz => "application/vnd.comicbook+zip",
+        FileFormat::Mobi => "application/x-mobipocket-ebook",
+        FileFormat::Cb7 => "application/x-cb7",
+        FileFormat::Kepub => "application/x-kobo-epub+zip",
+        FileFormat::Directory => "inode/directory",
+    };
+    let file_name = format.output_name(&entry.file_name_base);
+
+    let mut images = Vec::new();
+    if let Some(sidecars) = cover_sidecars {
+        if sidecars.full_cover {
+            images.push(json!({
+                "href": encode_href(&format!("{}.cover.jpg", entry.file_name_base)),
+                "type": "image/jpeg",
+                "rel": "http://opds-spec.org/image",
+            }));
+        }
+        if let Some(size) = sidecars.thumbnail_size {
+            images.push(json!({
+                "href": encode_href(&format!("{}.cover_thumb.jpg", entry.file_name_base)),
+                "type": "image/jpeg",
+                "rel": "http://opds-spec.org/image/thumbnail",
+                "width": size,
+            }));
+        }
+    }
+
+    json!({
+        "metadata": publication_metadata,
+        "links": [{
+            "rel": "http://opds-spec.org/acquisition",
+            "href": encode_href(&file_name),
+            "type": media_type,
+        }],
+        "images": images,
+    })
+}
+
+/// Reads the series name and authors from the series manifest in `directory`, `None` if
+/// there is no readable manifest. Blocking.
+pub(crate) fn read_series_manifest(directory: &Path) -> Option<(String, Vec<String>)> {
+    let content = std::fs::read_to_string(directory.join(SERIES_MANIFEST_FILE_NAME)).ok()?;
+    let manifest: Value = serde_json::from_str(&content).ok()?;
+    let series = manifest["metadata"]["title"].as_str()?.to_string();
+    // Every publication lists the authors of the series, if there are any
+    let authors = manifest["publications"][0]["metadata"]["author"]
+        .as_array()
+        .map(|authors| {
+            authors
+                .iter()
+                .filter_map(|author| author.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some((series, authors))
+}
+
+/// Writes the series manifest listing `entries` into `directory`. Covers are linked
+/// when `cover_sidecars` are written. Blocking; writes a file.
+pub(crate) fn write_series_manifest(
+    directory: &Path,
+    metadata: &EbookMetadata,
+    format: FileFormat,
+    cover_sidecars: Option<&CoverSidecars>,
+    entries: &[ManifestEntry],
+) -> Result<()> {
+    let publications: Vec<Value> = entries
+        .iter()
+        .map(|entry| publication(entry, metadata, format, cover_sidecars))
+        .collect();
+    let manifest = json!({
+        "metadata": {
+            "title": metadata.series.as_deref().unwrap_or(&metadata.title),
+            "numberOfItems": publications.len(),
+            "modified": chrono::Utc::now().to_rfc3339(),
+        },
+        "links": [{
+            "rel": "self",
+            "href": SERIES_MANIFEST_FILE_NAME,
+            "type": "application/opds+json",
+        }],
+        "publications": publications,
+    });
+
+    let path = directory.join(SERIES_MANIFEST_FILE_NAME);
+    let content = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| Error::Other(format!("Failed to serialize series manifest: {}", e)))?;
+    retry_while_locked(&path, "write series manifest", || {
+        std::fs::write(&path, &content)
+    })
+}
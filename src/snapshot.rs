@@ -0,0 +1,205 @@
+//! Detection of source modifications between analysis and generation.
+//!
+//! When converting straight from a source directory, the page lists produced by
+//! analysis are used much later, once a volume's turn to be generated comes up. If a
+//! downloader is still writing into the source, a chapter may have gained, lost, or
+//! rewritten pages in the meantime. A [`SourceSnapshot`] records the pages of every
+//! chapter at analysis time so that each chapter can be re-checked right before its
+//! volume is generated.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::collector::Collector;
+use crate::error::Result;
+
+/// Size and modification time of a single page when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PageStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl PageStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Page stamps of a directory, and its last page in chapter order.
+#[derive(Debug, Clone, Default)]
+struct DirectoryStamps {
+    pages: HashMap<PathBuf, Option<PageStamp>>,
+    last: Option<PathBuf>,
+}
+
+/// Page stamps of every directory holding pages, keyed by directory. A chapter may span
+/// several directories (e.g. with [`VolumeGroupingStrategy::Flat`]), and a directory may
+/// be split across chapters.
+///
+/// [`VolumeGroupingStrategy::Flat`]: crate::types::VolumeGroupingStrategy::Flat
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SourceSnapshot {
+    directories: HashMap<PathBuf, DirectoryStamps>,
+}
+
+impl SourceSnapshot {
+    /// Records the current state of the given chapters. Blocking; stats every page.
+    pub(crate) fn capture(chapters_with_pages: &[Vec<PathBuf>]) -> Self {
+        let mut directories: HashMap<PathBuf, DirectoryStamps> = HashMap::new();
+        for page in chapters_with_pages.iter().flatten() {
+            let Some(directory) = page.parent() else {
+                continue;
+            };
+            let stamps = directories.entry(directory.to_path_buf()).or_default();
+            stamps.pages.insert(page.clone(), PageStamp::read(page));
+            stamps.last = Some(page.clone());
+        }
+        Self { directories }
+    }
+
+    /// Returns whether a file in one of the directories of the chapter `pages` belongs
+    /// to that chapter: the pages recorded for it, and new files of the directories
+    /// whose last recorded page it holds.
+    pub(crate) fn membership(&self, pages: &[PathBuf]) -> impl Fn(&Path) -> bool + '_ {
+        let chapter: HashSet<PathBuf> = pages.iter().cloned().collect();
+        move |file: &Path| match file.parent().and_then(|d| self.directories.get(d)) {
+            Some(recorded) if recorded.pages.contains_key(file) => chapter.contains(file),
+            Some(recorded) => recorded
+                .last
+                .as_ref()
+                .is_some_and(|last| chapter.contains(last)),
+            None => chapter.contains(file),
+        }
+    }
+
+    /// Checks whether the chapter containing `pages` changed since the snapshot, in any
+    /// of its directories.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(None)` - The chapter is unchanged (or was not part of the snapshot)
+    /// * `Ok(Some((directory, reason)))` - The changed directory and a description of the change
+    pub(crate) async fn detect_change(
+        &self,
+        pages: &[PathBuf],
+    ) -> Result<Option<(PathBuf, String)>> {
+        let belongs = self.membership(pages);
+        for directory in page_directories(pages) {
+            let Some(recorded) = self.directories.get(&directory) else {
+                continue;
+            };
+            let expected = pages
+                .iter()
+                .filter(|page| page.parent() == Some(directory.as_path()))
+                .count();
+            let current: Vec<PathBuf> = Collector::collect_parallel(&directory, false)
+                .await?
+                .into_iter()
+                .filter(|page| belongs(page))
+                .collect();
+            if current.len() != expected {
+                return Ok(Some((
+                    directory,
+                    format!("page count changed from {} to {}", expected, current.len()),
+                )));
+            }
+
+            for page in &current {
+                match recorded.pages.get(page) {
+                    None => {
+                        return Ok(Some((directory, format!("new page {:?} appeared", page))));
+                    }
+                    Some(stamp) if *stamp != PageStamp::read(page) => {
+                        return Ok(Some((directory, format!("page {:?} was modified", page))));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// The distinct directories holding `pages`, in page order.
+pub(crate) fn page_directories(pages: &[PathBuf]) -> Vec<PathBuf> {
+    let mut directories: Vec<PathBuf> = Vec::new();
+    for directory in pages.iter().filter_map(|page| page.parent()) {
+        if !directories.iter().any(|known| known == directory) {
+            directories.push(directory.to_path_buf());
+        }
+    }
+    directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("hozon-snapshot-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_chapter_is_not_reported() {
+        let dir = chapter_dir("unchanged");
+        let page = dir.join("001.jpg");
+        std::fs::write(&page, b"page").unwrap();
+
+        let pages = vec![page];
+        let snapshot = SourceSnapshot::capture(std::slice::from_ref(&pages));
+        assert!(snapshot.detect_change(&pages).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_added_and_modified_pages_are_reported() {
+        let dir = chapter_dir("changed");
+        let page = dir.join("001.jpg");
+        std::fs::write(&page, b"page").unwrap();
+
+        let pages = vec![page.clone()];
+        let snapshot = SourceSnapshot::capture(std::slice::from_ref(&pages));
+
+        std::fs::write(dir.join("002.jpg"), b"late page").unwrap();
+        let (changed_dir, reason) = snapshot.detect_change(&pages).await.unwrap().unwrap();
+        assert_eq!(changed_dir, dir);
+        assert!(reason.contains("page count"));
+
+        std::fs::remove_file(dir.join("002.jpg")).unwrap();
+        std::fs::write(&page, b"rewritten page").unwrap();
+        let (_, reason) = snapshot.detect_change(&pages).await.unwrap().unwrap();
+        assert!(reason.contains("modified"));
+    }
+
+    #[tokio::test]
+    async fn test_split_directory_belongs_to_its_chapters() {
+        let dir = chapter_dir("split");
+        let pages: Vec<PathBuf> = (1..=4).map(|i| dir.join(format!("{:03}.jpg", i))).collect();
+        for page in &pages {
+            std::fs::write(page, b"page").unwrap();
+        }
+        let (first, second) = pages.split_at(2);
+        let snapshot = SourceSnapshot::capture(&[first.to_vec(), second.to_vec()]);
+        assert!(snapshot.detect_change(first).await.unwrap().is_none());
+        assert!(snapshot.detect_change(second).await.unwrap().is_none());
+
+        // A new page of the directory belongs to the chapter holding its last page
+        let late = dir.join("005.jpg");
+        std::fs::write(&late, b"late page").unwrap();
+        assert!(snapshot.detect_change(first).await.unwrap().is_none());
+        assert!(snapshot.detect_change(second).await.unwrap().is_some());
+        assert!(!snapshot.membership(first)(&late));
+        assert!(snapshot.membership(second)(&late));
+    }
+}
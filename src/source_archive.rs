@@ -0,0 +1,302 @@
+//! CBZ/ZIP archives read as chapters of a source.
+//!
+//! A `source_path` that is a `.cbz`/`.zip` file is read as a single chapter, and with
+//! [`CollectionDepth::Deep`](crate::types::CollectionDepth::Deep) every such archive
+//! directly in the source directory is a chapter next to the chapter directories. The
+//! images of an archive are extracted into a temporary directory and collected from there
+//! like any chapter directory; the extracted directory is named after the archive, so
+//! chapter numbers, sorting and titles work as for directories. An archive that fails to
+//! extract is skipped and reported as an
+//! [`AnalyzeFinding::UnreadableArchive`](crate::types::AnalyzeFinding::UnreadableArchive).
+//!
+//! With the `cbr` feature, `.cbr`/`.rar` archives are read too. They are extracted by
+//! running the `unrar` or `bsdtar` command, whichever is found on the `PATH` first, so the
+//! `PATH` decides which program reads them. Symlinks in the extracted archive are skipped.
+//!
+//! The extractions belong to an [`ArchiveExtractions`] shared by a config and its clones,
+//! so analyzing and then converting a source extracts it once. Its directory is created
+//! with a random name readable only by the current user, and removed with the last
+//! handle.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::path_utils::{is_hidden_file, path_to_string_lossy};
+use crate::types::ImageFormat;
+
+/// Extensions of archives read as chapters.
+#[cfg(not(feature = "cbr"))]
+const SOURCE_ARCHIVE_EXTENSIONS: [&str; 2] = ["cbz", "zip"];
+#[cfg(feature = "cbr")]
+const SOURCE_ARCHIVE_EXTENSIONS: [&str; 4] = ["cbz", "zip", "cbr", "rar"];
+
+/// Commands extracting RAR archives into a directory, tried in order.
+#[cfg(feature = "cbr")]
+const RAR_EXTRACTORS: [(&str, &[&str]); 2] =
+    [("unrar", &["x", "-y", "-idq"]), ("bsdtar", &["-xf"])];
+
+/// Whether `path` is a file with one of the [`SOURCE_ARCHIVE_EXTENSIONS`].
+pub(crate) fn is_source_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SOURCE_ARCHIVE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+        && path.is_file()
+}
+
+/// The source archives extracted for a conversion, in a private temporary directory
+/// removed when the last handle is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct ArchiveExtractions {
+    state: Mutex<ExtractionState>,
+}
+
+#[derive(Debug, Default)]
+struct ExtractionState {
+    root: Option<tempfile::TempDir>, // Created by the first extraction
+    directories: HashMap<String, PathBuf>, // Extracted pages by archive key
+}
+
+impl ArchiveExtractions {
+    /// Extracts the images of the archive at `archive`, or reuses a previous extraction
+    /// of the unchanged archive. Encrypted CBZ/ZIP entries are decrypted with `password`.
+    ///
+    /// # Returns
+    ///
+    /// The directory holding the pages, named after the archive without its extension.
+    /// It lives as long as `self`.
+    pub(crate) async fn extract(
+        self: &Arc<Self>,
+        archive: &Path,
+        password: Option<&str>,
+    ) -> Result<PathBuf> {
+        let extractions = Arc::clone(self);
+        let archive = archive.to_path_buf();
+        let password = password.map(str::to_string);
+        tokio::task::spawn_blocking(move || {
+            extractions.extract_blocking(&archive, password.as_deref())
+        })
+        .await
+        .map_err(|e| Error::AsyncTaskError(e.to_string()))?
+    }
+
+    fn extract_blocking(&self, archive: &Path, password: Option<&str>) -> Result<PathBuf> {
+        let invalid = |reason: String| Error::InvalidPath(archive.to_path_buf(), reason);
+        let metadata = std::fs::metadata(archive).map_err(|e| invalid(e.to_string()))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos());
+        let absolute = std::path::absolute(archive).unwrap_or(archive.to_path_buf());
+        let key = format!(
+            "{}\n{}\n{}",
+            path_to_string_lossy(&absolute),
+            metadata.len(),
+            modified
+        );
+        let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+        let name = archive
+            .file_stem()
+            .ok_or_else(|| invalid("has no file name".to_string()))?;
+
+        let root = {
+            let mut state = self.lock();
+            if let Some(directory) = state.directories.get(&digest) {
+                return Ok(directory.clone());
+            }
+            match &state.root {
+                Some(root) => root.path().to_path_buf(),
+                None => {
+                    // A fresh, randomly named `0700` directory nobody else can plant into
+                    let root = tempfile::Builder::new()
+                        .prefix("hozon-archives-")
+                        .tempdir()?;
+                    let path = root.path().to_path_buf();
+                    state.root = Some(root);
+                    path
+                }
+            }
+        };
+        let extraction = root.join(&digest[..16]);
+        let directory = extraction.join(name);
+
+        // Extracted next to the final directory first and moved into place once complete,
+        // so an interrupted extraction is never mistaken for a complete one. The staging
+        // directory removes itself when dropped
+        let staging = tempfile::Builder::new()
+            .prefix(".partial-")
+            .tempdir_in(&root)?;
+        let pages = extract_images(archive, staging.path(), password)?;
+        if pages == 0 {
+            log::warn!(
+                "Archive '{}' contains no supported images",
+                path_to_string_lossy(archive)
+            );
+        }
+        std::fs::create_dir_all(&extraction)?;
+        match std::fs::rename(staging.path(), &directory) {
+            // Extracted by a concurrent conversion in the meantime
+            Err(_) if directory.is_dir() => {}
+            result => result?,
+        }
+        self.lock().directories.insert(digest, directory.clone());
+        Ok(directory)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ExtractionState> {
+        // Entries are plain values; a panicking task can't leave them inconsistent
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether an entry at `entry_path` inside an archive is a page: a supported image
+/// outside of hidden and macOS metadata folders.
+pub(crate) fn is_page_entry(entry_path: &Path) -> bool {
+    !entry_path.components().any(|component| {
+        matches!(component, Component::Normal(name)
+            if name == "__MACOSX" || is_hidden_file(Path::new(name)))
+    }) && ImageFormat::from_path(entry_path).is_ok()
+}
+
+/// Writes the supported images of `archive` into `destination` by file name, ignoring
+/// the folders they are in, decrypting encrypted entries with `password`. Returns the
+/// number of images written.
+fn extract_images(archive: &Path, destination: &Path, password: Option<&str>) -> Result<usize> {
+    #[cfg(feature = "cbr")]
+    if archive
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension.to_ascii_lowercase().as_str(), "cbr" | "rar"))
+    {
+        return extract_rar_images(archive, destination);
+    }
+
+    let invalid = |reason: String| Error::InvalidPath(archive.to_path_buf(), reason);
+    let file = std::fs::File::open(archive).map_err(|e| invalid(e.to_string()))?;
+    let mut zip = zip::ZipArchive::new(file)
+        .map_err(|e| invalid(format!("Failed to open archive: {}", e)))?;
+
+    let mut pages = 0;
+    let mut buffer = Vec::new();
+    for i in 0..zip.len() {
+        let encrypted = zip.by_index_raw(i)?.encrypted();
+        let mut entry = match password {
+            Some(password) if encrypted => zip.by_index_decrypt(i, password.as_bytes())?,
+            _ => zip.by_index(i)?,
+        };
+        // Rejects absolute paths and `..`, which would point outside the archive
+        let Some(entry_path) = entry.enclosed_name() else {
+            continue;
+        };
+        let skipped = entry.is_dir() || !is_page_entry(&entry_path);
+        let Some(file_name) = entry_path.file_name().filter(|_| !skipped) else {
+            continue;
+        };
+        let target = destination.join(file_name);
+        if target.exists() {
+            log::warn!(
+                "Skipping '{}' in '{}': another page has the same file name",
+                path_to_string_lossy(&entry_path),
+                path_to_string_lossy(archive)
+            );
+            continue;
+        }
+        buffer.clear();
+        entry
+            .read_to_end(&mut buffer)
+            .map_err(|e| invalid(format!("Failed to read '{}': {}", entry.name(), e)))?;
+        std::fs::write(&target, &buffer)?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Extracts the RAR archive at `archive` with the first of the [`RAR_EXTRACTORS`] found,
+/// then moves its images into `destination` like [`extract_images`].
+#[cfg(feature = "cbr")]
+fn extract_rar_images(archive: &Path, destination: &Path) -> Result<usize> {
+    let invalid = |reason: String| Error::InvalidPath(archive.to_path_buf(), reason);
+    let raw = destination.join(".raw");
+    std::fs::create_dir_all(&raw)?;
+    // `unrar` wants the destination with a trailing separator, `bsdtar` after `-C`
+    let mut extracted = None;
+    for (command, args) in RAR_EXTRACTORS {
+        let mut process = std::process::Command::new(command);
+        process.args(args).arg(archive);
+        if command == "bsdtar" {
+            process.arg("-C").arg(&raw);
+        } else {
+            process.arg(format!("{}{}", raw.display(), std::path::MAIN_SEPARATOR));
+        }
+        match process.output() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(invalid(format!("Failed to run `{}`: {}", command, e))),
+            Ok(output) => {
+                extracted = Some((command, output));
+                break;
+            }
+        }
+    }
+    let Some((command, output)) = extracted else {
+        return Err(Error::Unsupported(
+            "Reading CBR/RAR archives requires `unrar` or `bsdtar` on the PATH".to_string(),
+        ));
+    };
+    if !output.status.success() {
+        return Err(invalid(format!(
+            "`{}` failed to extract the archive: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Walked without following symlinks, which the archive may point anywhere; whatever
+    // is moved into `destination` must be a regular file inside the extraction
+    let root = raw.canonicalize()?;
+    let mut pages = 0;
+    let mut pending = vec![root.clone()];
+    while let Some(directory) = pending.pop() {
+        for entry in std::fs::read_dir(&directory)? {
+            let path = entry?.path();
+            let entry_path = path.strip_prefix(&root).unwrap_or(&path);
+            let file_type = std::fs::symlink_metadata(&path)?.file_type();
+            if file_type.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if !file_type.is_file() || !path.canonicalize()?.starts_with(&root) {
+                log::warn!(
+                    "Skipping '{}' in '{}': not a regular file",
+                    path_to_string_lossy(entry_path),
+                    path_to_string_lossy(archive)
+                );
+                continue;
+            }
+            let Some(file_name) = path.file_name().filter(|_| is_page_entry(entry_path)) else {
+                continue;
+            };
+            let target = destination.join(file_name);
+            if target.exists() {
+                log::warn!(
+                    "Skipping '{}' in '{}': another page has the same file name",
+                    path_to_string_lossy(entry_path),
+                    path_to_string_lossy(archive)
+                );
+                continue;
+            }
+            std::fs::rename(&path, &target)?;
+            pages += 1;
+        }
+    }
+    std::fs::remove_dir_all(&raw)?;
+    Ok(pages)
+}
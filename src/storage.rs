@@ -0,0 +1,184 @@
+//! Read concurrency tuned to the storage holding the source.
+//!
+//! Scanning chapters and reading pages in parallel is what makes conversions from SSDs
+//! fast, but the same 64-way directory concurrency makes a spinning disk seek constantly
+//! and floods SMB/NFS shares with requests. Hozon therefore detects the kind of storage
+//! the source lives on and caps its reads accordingly; set
+//! [`HozonConfig::storage_kind`](crate::HozonConfig::storage_kind) when detection can't
+//! tell (e.g. a USB hard disk behind a bridge reporting itself as non-rotational).
+//!
+//! Detection reads `/proc/self/mountinfo` and `/sys/dev/block` on Linux and recognizes
+//! UNC paths (`\\server\share`) on Windows. Anything undetected is treated as an SSD,
+//! which matches the caps Hozon always used.
+
+use std::path::Path;
+
+/// The kind of storage pages are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageKind {
+    /// Detect the storage of the source directory (default).
+    #[default]
+    Auto,
+    /// Solid-state or other fast local storage: high read concurrency.
+    Ssd,
+    /// Spinning disks: few concurrent reads, keeping reads mostly sequential.
+    Hdd,
+    /// Network shares (SMB, NFS, ...): moderate concurrency, bounded by round trips.
+    Network,
+}
+
+impl StorageKind {
+    /// Resolves [`StorageKind::Auto`] by detecting the storage of `path`; other kinds are
+    /// returned as is.
+    pub fn resolve(self, path: &Path) -> StorageKind {
+        match self {
+            StorageKind::Auto => detect_storage_kind(path),
+            kind => kind,
+        }
+    }
+
+    /// Maximum number of chapter directories scanned at once.
+    pub fn max_concurrent_dirs(self) -> usize {
+        match self {
+            StorageKind::Auto | StorageKind::Ssd => 64,
+            StorageKind::Hdd => 2,
+            StorageKind::Network => 8,
+        }
+    }
+
+    /// Maximum number of page files read at once by a single conversion, or `None` to
+    /// only bound reads by the number of volumes generated at once.
+    pub fn max_concurrent_reads(self) -> Option<usize> {
+        match self {
+            StorageKind::Auto | StorageKind::Ssd => None,
+            StorageKind::Hdd => Some(2),
+            StorageKind::Network => Some(8),
+        }
+    }
+}
+
+/// Detects the kind of storage `path` lives on, falling back to [`StorageKind::Ssd`].
+pub fn detect_storage_kind(path: &Path) -> StorageKind {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    detect(&path).unwrap_or(StorageKind::Ssd)
+}
+
+#[cfg(target_os = "linux")]
+fn detect(path: &Path) -> Option<StorageKind> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let mount = find_mount(&mountinfo, path)?;
+    if is_network_filesystem(&mount.filesystem) {
+        return Some(StorageKind::Network);
+    }
+
+    // Partitions have no queue of their own; their parent device does
+    let device = Path::new("/sys/dev/block").join(&mount.device);
+    let rotational = std::fs::read_to_string(device.join("queue/rotational"))
+        .or_else(|_| std::fs::read_to_string(device.join("../queue/rotational")))
+        .ok()?;
+    Some(if rotational.trim() == "1" {
+        StorageKind::Hdd
+    } else {
+        StorageKind::Ssd
+    })
+}
+
+#[cfg(windows)]
+fn detect(path: &Path) -> Option<StorageKind> {
+    let path = path.to_string_lossy();
+    let is_unc = path.starts_with(r"\\?\UNC\")
+        || (path.starts_with(r"\\") && !path.starts_with(r"\\?\") && !path.starts_with(r"\\.\"));
+    is_unc.then_some(StorageKind::Network)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn detect(_path: &Path) -> Option<StorageKind> {
+    None
+}
+
+/// A mount point from `/proc/self/mountinfo`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Debug, PartialEq, Eq)]
+struct Mount {
+    device: String, // "major:minor"
+    filesystem: String,
+}
+
+/// Finds the innermost mount containing `path` in the contents of `/proc/self/mountinfo`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn find_mount(mountinfo: &str, path: &Path) -> Option<Mount> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // ID PARENT MAJOR:MINOR ROOT MOUNT_POINT OPTIONS [OPTIONAL...] - FSTYPE SOURCE ...
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let fields: Vec<&str> = mount.split(' ').collect();
+            let mount_point = fields.get(4)?.replace("\\040", " ");
+            let filesystem = filesystem.split(' ').next()?;
+            path.starts_with(&mount_point).then(|| {
+                let mount = Mount {
+                    device: fields[2].to_string(),
+                    filesystem: filesystem.to_string(),
+                };
+                (mount_point.len(), mount)
+            })
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, mount)| mount)
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_network_filesystem(filesystem: &str) -> bool {
+    matches!(
+        filesystem,
+        "nfs"
+            | "nfs4"
+            | "cifs"
+            | "smb3"
+            | "smbfs"
+            | "9p"
+            | "afs"
+            | "ceph"
+            | "glusterfs"
+            | "fuse.sshfs"
+            | "fuse.rclone"
+            | "fuse.s3fs"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+41 22 8:1 / /mnt/archive rw,relatime shared:20 - ext4 /dev/sda1 rw
+57 22 0:52 / /mnt/nas\\040share rw,relatime shared:31 - cifs //nas/comics rw";
+
+    #[test]
+    fn test_innermost_mount_is_found() {
+        let mount = find_mount(MOUNTINFO, Path::new("/mnt/archive/Series/001")).unwrap();
+        assert_eq!(mount.device, "8:1");
+
+        let root = find_mount(MOUNTINFO, Path::new("/mnt/archived")).unwrap();
+        assert_eq!(root.device, "259:2");
+    }
+
+    #[test]
+    fn test_network_shares_are_recognized() {
+        let mount = find_mount(MOUNTINFO, Path::new("/mnt/nas share/Series")).unwrap();
+        assert!(is_network_filesystem(&mount.filesystem));
+        assert!(!is_network_filesystem("ext4"));
+    }
+
+    #[test]
+    fn test_explicit_kind_is_not_detected() {
+        assert_eq!(
+            StorageKind::Hdd.resolve(Path::new("/does/not/exist")),
+            StorageKind::Hdd
+        );
+        assert_eq!(StorageKind::Hdd.max_concurrent_dirs(), 2);
+    }
+}
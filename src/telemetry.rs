@@ -0,0 +1,95 @@
+//! Conversion metrics for monitoring conversion workers. Requires the `metrics` feature.
+//!
+//! Metrics are emitted through the [`metrics`](https://docs.rs/metrics) facade, so any
+//! recorder installed by the application receives them, e.g. the Prometheus exporter of
+//! `metrics-exporter-prometheus`. Without an installed recorder, or without the feature,
+//! recording does nothing. All conversions in the process report into the same metrics:
+//!
+//! | Metric | Type | Labels |
+//! |---|---|---|
+//! | [`VOLUMES_GENERATED`] | counter | `format` (`cbz`, `epub`) |
+//! | [`PAGES_PROCESSED`] | counter | `format` |
+//! | [`BYTES_WRITTEN`] | counter | `format` |
+//! | [`ERRORS`] | counter | `kind`, e.g. `io` or `source_changed` |
+//! | [`ACTIVE_TASKS`] | gauge | |
+//!
+//! Volumes, pages and bytes are counted once a file was written completely. Errors count
+//! failed conversion runs, not preflight failures.
+
+use crate::error::Error;
+use crate::types::FileFormat;
+
+/// Generated output files, counting every part of a split volume.
+pub const VOLUMES_GENERATED: &str = "hozon_volumes_generated_total";
+
+/// Pages written into generated files, excluding custom covers.
+pub const PAGES_PROCESSED: &str = "hozon_pages_processed_total";
+
+/// Size of the generated files in bytes.
+pub const BYTES_WRITTEN: &str = "hozon_bytes_written_total";
+
+/// Failed conversion runs, labeled with the kind of error.
+pub const ERRORS: &str = "hozon_errors_total";
+
+/// Volumes being generated at the moment.
+pub const ACTIVE_TASKS: &str = "hozon_active_tasks";
+
+/// Records a completely written output file.
+pub(crate) fn record_output(format: FileFormat, pages: usize, bytes: u64) {
+    #[cfg(feature = "metrics")]
+    {
+        let format = format.extension();
+        metrics::counter!(VOLUMES_GENERATED, "format" => format).increment(1);
+        metrics::counter!(PAGES_PROCESSED, "format" => format).increment(pages as u64);
+        metrics::counter!(BYTES_WRITTEN, "format" => format).increment(bytes);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (format, pages, bytes);
+}
+
+/// Records a failed conversion run.
+pub(crate) fn record_error(error: &Error) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(ERRORS, "kind" => error_kind(error)).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = error;
+}
+
+/// Counts a volume as active in [`ACTIVE_TASKS`] while alive.
+pub(crate) struct ActiveTask(());
+
+impl ActiveTask {
+    pub(crate) fn start() -> Self {
+        #[cfg(feature = "metrics")]
+        metrics::gauge!(ACTIVE_TASKS).increment(1.0);
+        ActiveTask(())
+    }
+}
+
+impl Drop for ActiveTask {
+    fn drop(&mut self) {
+        #[cfg(feature = "metrics")]
+        metrics::gauge!(ACTIVE_TASKS).decrement(1.0);
+    }
+}
+
+/// The `kind` label of an error.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::Io(_) => "io",
+        Error::Image(_) => "image",
+        Error::Epub(_) | Error::InvalidEpub(..) => "epub",
+        Error::Zip(_) => "zip",
+        Error::InvalidPath(..) | Error::PathTooLong(_) => "invalid_path",
+        Error::SourceChanged(..) => "source_changed",
+        Error::LostChapters(..) => "lost_chapters",
+        Error::TargetLocked(..) | Error::FileLocked(..) => "locked",
+        Error::ForeignOutputs(..) => "foreign_outputs",
+        Error::InvalidOutput(..) => "invalid_output",
+        Error::TooManyOpenFiles(_) => "too_many_open_files",
+        Error::NotFound(_) => "not_found",
+        Error::Unsupported(_) => "unsupported",
+        _ => "other",
+    }
+}
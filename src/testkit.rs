@@ -0,0 +1,222 @@
+//! Synthetic sources for testing applications built on Hozon. Requires the `testkit`
+//! feature.
+//!
+//! [`SyntheticLibrary`] writes a source directory with a chosen number of volumes,
+//! chapters and pages, in any image format Hozon reads and with chapter names matching
+//! the grouping strategy under test. Like in printed manga, the first page of every volume
+//! is a colored cover and all other pages are grayscale, so
+//! [`VolumeGroupingStrategy::ImageAnalysis`] finds the same volumes as
+//! [`VolumeGroupingStrategy::Name`]:
+//!
+//! ```rust,no_run
+//! use hozon::prelude::*;
+//! use hozon::testkit::{ChapterNaming, SyntheticLibrary};
+//! # #[tokio::main]
+//! # async fn main() -> hozon::error::Result<()> {
+//!
+//! let source = PathBuf::from("./synthetic");
+//! SyntheticLibrary::new()
+//!     .with_volumes(2)
+//!     .with_chapters_per_volume(3)
+//!     .with_naming(ChapterNaming::VolumeChapter)
+//!     .generate(&source)
+//!     .await?;
+//!
+//! let config = HozonConfig::builder()
+//!     .metadata(EbookMetadata::default_with_title("Synthetic".to_string()))
+//!     .source_path(source)
+//!     .target_path(PathBuf::from("./output"))
+//!     .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+//!     .build()?;
+//! assert_eq!(config.structure_from_source().await?.report.total_volumes_created, 2);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`VolumeGroupingStrategy::ImageAnalysis`]: crate::VolumeGroupingStrategy::ImageAnalysis
+//! [`VolumeGroupingStrategy::Name`]: crate::VolumeGroupingStrategy::Name
+
+use image::RgbImage;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+pub use image::{ImageFormat, Rgb};
+
+/// How the chapter directories of a [`SyntheticLibrary`] are named. Chapters are numbered
+/// from 1 across all volumes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ChapterNaming {
+    /// `Chapter 001`, `Chapter 002`, ... (the default).
+    #[default]
+    Numbered,
+    /// `01-001`, `01-002`, `02-003`, ..., as grouped by
+    /// [`VolumeGroupingStrategy::Name`](crate::VolumeGroupingStrategy::Name).
+    VolumeChapter,
+    /// A template where `{volume}` and `{chapter}` are replaced by the numbers, e.g.
+    /// `"Vol.{volume} Ch.{chapter}"`.
+    Custom(String),
+}
+
+impl ChapterNaming {
+    /// The directory name of chapter `chapter` in volume `volume` (both 1-based).
+    pub fn format(&self, volume: usize, chapter: usize) -> String {
+        match self {
+            ChapterNaming::Numbered => format!("Chapter {:03}", chapter),
+            ChapterNaming::VolumeChapter => format!("{:02}-{:03}", volume, chapter),
+            ChapterNaming::Custom(template) => template
+                .replace("{volume}", &volume.to_string())
+                .replace("{chapter}", &chapter.to_string()),
+        }
+    }
+}
+
+/// A synthetic source directory, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticLibrary {
+    volumes: usize,
+    chapters_per_volume: usize,
+    pages_per_chapter: usize,
+    format: ImageFormat,
+    naming: ChapterNaming,
+    page_size: (u32, u32),
+}
+
+impl Default for SyntheticLibrary {
+    fn default() -> Self {
+        Self {
+            volumes: 1,
+            chapters_per_volume: 3,
+            pages_per_chapter: 4,
+            format: ImageFormat::Jpeg,
+            naming: ChapterNaming::Numbered,
+            page_size: (100, 100),
+        }
+    }
+}
+
+impl SyntheticLibrary {
+    /// One volume of 3 chapters with 4 JPEG pages of 100x100 pixels each, named
+    /// [`ChapterNaming::Numbered`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of volumes.
+    pub fn with_volumes(mut self, volumes: usize) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    /// Sets the number of chapters in every volume.
+    pub fn with_chapters_per_volume(mut self, chapters: usize) -> Self {
+        self.chapters_per_volume = chapters;
+        self
+    }
+
+    /// Sets the number of pages in every chapter.
+    pub fn with_pages_per_chapter(mut self, pages: usize) -> Self {
+        self.pages_per_chapter = pages;
+        self
+    }
+
+    /// Sets the image format of the pages, e.g. [`ImageFormat::Png`].
+    pub fn with_format(mut self, format: ImageFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets how chapter directories are named.
+    pub fn with_naming(mut self, naming: ChapterNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets the width and height of the pages in pixels.
+    pub fn with_page_size(mut self, width: u32, height: u32) -> Self {
+        self.page_size = (width, height);
+        self
+    }
+
+    /// Writes the library into `source`, creating it if needed, and returns the pages of
+    /// every chapter in reading order, shaped like
+    /// [`CollectedContent::chapters_with_pages`](crate::CollectedContent::chapters_with_pages).
+    ///
+    /// Pages are named `001.<extension>`, `002.<extension>`, ... Every page has a slightly
+    /// different color, so no two pages are duplicates.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a directory or page can't be written, or the format can't be encoded.
+    pub async fn generate(&self, source: &Path) -> Result<Vec<Vec<PathBuf>>> {
+        let extension = self
+            .format
+            .extensions_str()
+            .first()
+            .ok_or_else(|| Error::Unsupported(format!("Image format {:?}", self.format)))?;
+
+        let mut chapters = Vec::with_capacity(self.volumes * self.chapters_per_volume);
+        let mut page_index = 0usize;
+        for volume in 1..=self.volumes {
+            for chapter_in_volume in 0..self.chapters_per_volume {
+                let chapter = (volume - 1) * self.chapters_per_volume + chapter_in_volume + 1;
+                let chapter_dir = source.join(self.naming.format(volume, chapter));
+                let mut pages = Vec::with_capacity(self.pages_per_chapter);
+                for page in 1..=self.pages_per_chapter {
+                    let path = chapter_dir.join(format!("{:03}.{}", page, extension));
+                    let is_cover = chapter_in_volume == 0 && page == 1;
+                    let color = page_color(page_index, is_cover);
+                    create_image(&path, color, self.format, self.page_size).await?;
+                    pages.push(path);
+                    page_index += 1;
+                }
+                chapters.push(pages);
+            }
+        }
+        Ok(chapters)
+    }
+}
+
+/// A distinct color for the `index`-th page: clearly colored for covers, gray otherwise.
+fn page_color(index: usize, is_cover: bool) -> Rgb<u8> {
+    if is_cover {
+        Rgb([255, (index % 200) as u8, 0])
+    } else {
+        let level = 40 + (index % 176) as u8;
+        Rgb([level, level, level])
+    }
+}
+
+/// Writes a single-colored image of `size` (width, height) pixels to `path` in
+/// `format`, creating missing parent directories.
+///
+/// # Errors
+///
+/// Fails if the file can't be written or the format can't be encoded.
+pub async fn create_image(
+    path: &Path,
+    color: Rgb<u8>,
+    format: ImageFormat,
+    size: (u32, u32),
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        RgbImage::from_pixel(size.0, size.1, color).save_with_format(path, format)
+    })
+    .await?
+    .map_err(Error::Image)
+}
+
+/// Writes a 100x100 gray JPEG page to `path`.
+pub async fn create_grayscale_page(path: &Path) -> Result<()> {
+    create_image(path, Rgb([128, 128, 128]), ImageFormat::Jpeg, (100, 100)).await
+}
+
+/// Writes a 100x100 red JPEG page to `path`, which image analysis treats as a volume
+/// cover.
+pub async fn create_color_page(path: &Path) -> Result<()> {
+    create_image(path, Rgb([255, 0, 0]), ImageFormat::Jpeg, (100, 100)).await
+}
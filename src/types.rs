@@ -8,9 +8,13 @@
 //! - Comprehensive metadata (`EbookMetadata`)
 //! - Error detail types (`AnalyzeFinding`)
 
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
 
 use crate::error::{Error, Result};
 
@@ -24,6 +28,70 @@ pub enum VolumeGroupingStrategy {
     #[default]
     Manual, // User provides explicit volume breaks or assumes 1 volume for collected content
     Flat,          // Treats all collected pages as a single chapter in a single output book
+    PerChapter,    // Every chapter becomes its own output file
+    Mapping,       // Group by a chapter-to-volume mapping file (`volume_mapping_file`)
+}
+
+/// Where chapters without a number in their name (omake, extras, oneshots) end up.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExtraChapters {
+    /// Wherever the chapter sort puts them (the default); numeric sorting puts them first.
+    #[default]
+    Sorted,
+    /// After the chapters of the last volume.
+    AppendToLastVolume,
+    /// In an additional last volume, labeled `Extras` instead of a volume number.
+    SeparateVolume,
+    /// Left out of the output.
+    Skip,
+}
+
+/// How pages are ordered within each chapter during collection.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SortStrategy {
+    /// By the numbers in the file names (`page_2.jpg` before `page_10.jpg`), the default.
+    #[default]
+    Number,
+    /// By EXIF capture time, for camera-generated file names that don't reflect the
+    /// order. Pages without a capture date follow, ordered by number.
+    ExifDate,
+}
+
+/// How chapters or pages are ordered during collection.
+///
+/// Unlike the custom sorter closures of [`HozonConfig`](crate::HozonConfig), a `SortSpec`
+/// can be serialized, so configs sent from a GUI or a remote client can express it.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SortSpec {
+    /// By the last number in the name (`page_2.jpg` before `page_10.jpg`), the default.
+    #[default]
+    Numeric,
+    /// Like a file manager: runs of digits compare as numbers, everything else as text,
+    /// ignoring case (`a2b10` before `a10b2`).
+    Natural,
+    /// By the name as a plain string (`page_10.jpg` before `page_2.jpg`).
+    Lexicographic,
+    /// By the number captured by `group` of `pattern` (0 for the whole match). Names
+    /// without a match come first.
+    RegexCapture { pattern: String, group: usize },
+    /// By EXIF capture time, then by number for pages without a capture date. Only
+    /// applies to pages.
+    ExifDate,
+}
+
+impl From<SortStrategy> for SortSpec {
+    fn from(strategy: SortStrategy) -> Self {
+        match strategy {
+            SortStrategy::Number => SortSpec::Numeric,
+            SortStrategy::ExifDate => SortSpec::ExifDate,
+        }
+    }
 }
 
 /// How deeply to scan the source directory for chapters and pages during collection.
@@ -32,8 +100,60 @@ pub enum VolumeGroupingStrategy {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CollectionDepth {
     #[default]
-    Deep, // Expects structure: `source_path/chapter_folder/page.jpg`
+    Deep, // Expects structure: `source_path/chapter_folder/page.jpg`, or `source_path/chapter.cbz`
     Shallow, // Expects structure: `source_path/page.jpg` (all pages in root, treated as one virtual chapter)
+    Recursive, // Every directory at any depth that directly contains pages is a chapter (e.g. `source_path/volume/chapter/page.jpg`)
+}
+
+/// What to do when a chapter's contents changed between analysis and generation
+/// (e.g. because a downloader is still writing into the source directory).
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SourceChangePolicy {
+    #[default]
+    Fail, // Abort with `Error::SourceChanged`
+    Rescan, // Re-collect the affected chapter's pages and continue with the fresh list
+    Ignore, // Don't check; generate from the page lists gathered during analysis
+}
+
+/// What to do with the source directory once a conversion from source succeeded.
+///
+/// Cleanup only happens after every generated file was read back and verified, so a
+/// broken output never costs the source.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SourceCleanup {
+    #[default]
+    KeepSource, // Leave the source directory in place
+    MoveSourceTo(PathBuf), // Move the source directory into this directory, e.g. an archive of converted inboxes
+    DeleteSource,          // Delete the source directory with everything in it
+}
+
+/// What to do when structuring can't place every collected chapter in a volume (e.g. a
+/// chapter without pages, whose directory can't be derived for name grouping).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LostChapterPolicy {
+    #[default]
+    Fail, // Abort with `Error::LostChapters`
+    Warn, // Log a warning and generate the volumes without the lost chapters
+}
+
+/// What to do when the output directory already holds outputs of another series, e.g. of
+/// two series whose titles sanitize to the same directory name. Outputs are told apart by
+/// the series recorded in their [source fingerprint](crate::fingerprint::SourceFingerprint)
+/// and in the series manifest.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ForeignOutputPolicy {
+    #[default]
+    Warn, // Log a warning and write into the directory anyway
+    Fail,   // Abort with `Error::ForeignOutputs` before writing anything
+    Ignore, // Don't check
 }
 
 /// A specific finding from the analysis phase, categorized by severity.
@@ -50,6 +170,10 @@ pub enum AnalyzeFinding {
     ConsistentImageFormat {
         format: String,
     },
+    SplitPagesFound {
+        chapter_path: PathBuf,
+        count: usize, // Pages like `12a.jpg`, sorted right after their base page
+    },
 
     // --- Warnings ---
     InconsistentPageCount {
@@ -57,6 +181,9 @@ pub enum AnalyzeFinding {
         expected: usize,
         found: usize,
     },
+    UnnumberedChapter {
+        chapter_path: PathBuf, // Handled by `HozonConfig::extra_chapters`
+    },
     UnusualFileSize {
         file_path: PathBuf,
         size_kb: u64,
@@ -65,13 +192,33 @@ pub enum AnalyzeFinding {
     SpecialCharactersInPath {
         path: PathBuf,
     },
+    CollectionDepthMismatch {
+        configured: CollectionDepth, // Yields no chapters or pages for this source
+        recommended: CollectionDepth, // Matches the layout of the source
+    },
+    MissingPages {
+        chapter: PathBuf,
+        gaps: Vec<u32>, // Page numbers missing from the file names, see `HozonConfig::check_page_gaps`
+    },
 
     // --- Errors (Non-blocking) ---
     UnsupportedFileIgnored {
         path: PathBuf,
     },
+    NestedArchive {
+        path: PathBuf,          // A `.zip`/`.cbz`/`.rar`/... file that isn't read as a chapter
+        alongside_images: bool, // The directory's images are converted without it
+    },
+    UnreadableArchive {
+        path: PathBuf,  // A source archive that failed to extract; converted without it
+        reason: String, // Why the extraction failed
+    },
 
     // --- Fatals (Blocking) ---
+    UnmappedChapter {
+        chapter_path: PathBuf,
+        chapter_number: Option<f64>, // None if the name contains no number
+    },
     SourcePathNotFound {
         path: PathBuf,
     },
@@ -90,6 +237,207 @@ pub enum FileFormat {
     Epub,
     #[default]
     Cbz,
+    /// Mobipocket, for Kindle devices that read neither CBZ nor EPUB.
+    Mobi,
+    /// A CBZ packed as 7z archive, which compresses PNG-heavy scans better. Requires the
+    /// `cb7` feature.
+    Cb7,
+    /// An EPUB with Kobo's extensions (a "kepub"), written as `.kepub.epub` so Kobo
+    /// devices open it in their native reader with its page turning and statistics.
+    Kepub,
+    /// A directory of plain page images per volume (`001.jpg`, `002.jpg`, …) instead of a
+    /// file, as input for other tools. Carries no metadata.
+    Directory,
+}
+
+impl FileFormat {
+    /// Returns the file extension (without the leading dot) used for this format, empty
+    /// for [`FileFormat::Directory`].
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Epub => "epub",
+            FileFormat::Cbz => "cbz",
+            FileFormat::Mobi => "mobi",
+            FileFormat::Cb7 => "cb7",
+            FileFormat::Kepub => "kepub.epub",
+            FileFormat::Directory => "",
+        }
+    }
+
+    /// Returns the name of the output of this format with the base name `base`: the base
+    /// name with the [`extension`](FileFormat::extension), or the base name alone for
+    /// [`FileFormat::Directory`].
+    pub fn output_name(&self, base: &str) -> String {
+        match self {
+            FileFormat::Directory => base.to_string(),
+            _ => format!("{}.{}", base, self.extension()),
+        }
+    }
+
+    /// Whether the output is an EPUB, i.e. built by the EPUB generator.
+    pub(crate) fn is_epub(&self) -> bool {
+        matches!(self, FileFormat::Epub | FileFormat::Kepub)
+    }
+
+    /// Whether `path` names a file of this format, going by its extension. Never true for
+    /// [`FileFormat::Directory`].
+    pub(crate) fn matches_path(&self, path: &Path) -> bool {
+        *self != FileFormat::Directory
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.to_ascii_lowercase()
+                        .ends_with(&format!(".{}", self.extension()))
+                })
+    }
+
+    /// Returns which options and metadata fields the generator of this format honors,
+    /// so frontends can disable the ones that don't apply.
+    pub fn capabilities(&self) -> GeneratorCapabilities {
+        const COMMON_FIELDS: &[&str] = &[
+            "title",
+            "series",
+            "authors",
+            "publisher",
+            "description",
+            "tags",
+            "language",
+            "rights",
+            "identifier",
+            "identifiers",
+            "release_date",
+        ];
+        let (extra_fields, capabilities): (&[&str], _) = match self {
+            FileFormat::Cbz | FileFormat::Cb7 => (
+                &["genre", "web", "custom_fields"],
+                GeneratorCapabilities {
+                    supports_rtl: false,
+                    supports_toc: false,
+                    supports_cover: true,
+                    supports_encryption: *self == FileFormat::Cbz,
+                    supports_alt_text: false,
+                    supports_size_split: false,
+                    supports_metadata_fields: Vec::new(),
+                },
+            ),
+            FileFormat::Epub | FileFormat::Kepub => (
+                &["custom_fields"],
+                GeneratorCapabilities {
+                    supports_rtl: true,
+                    supports_toc: true,
+                    supports_cover: true,
+                    supports_encryption: false,
+                    supports_alt_text: true,
+                    supports_size_split: true,
+                    supports_metadata_fields: Vec::new(),
+                },
+            ),
+            FileFormat::Mobi => (
+                &[],
+                GeneratorCapabilities {
+                    supports_rtl: false,
+                    supports_toc: true,
+                    supports_cover: true,
+                    supports_encryption: false,
+                    supports_alt_text: false,
+                    supports_size_split: false,
+                    supports_metadata_fields: Vec::new(),
+                },
+            ),
+            // Nothing but the pages
+            FileFormat::Directory => {
+                return GeneratorCapabilities {
+                    supports_cover: true,
+                    ..Default::default()
+                };
+            }
+        };
+        GeneratorCapabilities {
+            supports_metadata_fields: COMMON_FIELDS
+                .iter()
+                .chain(extra_fields)
+                .map(|field| field.to_string())
+                .collect(),
+            ..capabilities
+        }
+    }
+}
+
+/// What the generator of a [`FileFormat`] supports, see [`FileFormat::capabilities`].
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneratorCapabilities {
+    pub supports_rtl: bool,        // Honors `HozonConfig::reading_direction`
+    pub supports_toc: bool,        // Has a table of contents (`HozonConfig::toc`)
+    pub supports_cover: bool,      // Marks a dedicated cover image (`CoverOptions`)
+    pub supports_encryption: bool, // Honors `HozonConfig::output_password`
+    pub supports_alt_text: bool,   // Honors `HozonConfig::alt_text`
+    pub supports_size_split: bool, // Honors `HozonConfig::epub_max_file_size`
+    pub supports_metadata_fields: Vec<String>, // `EbookMetadata` fields written to the output
+}
+
+/// A setting without effect for the chosen output format or volume grouping strategy,
+/// see [`HozonConfig::ignored_options`](crate::HozonConfig::ignored_options).
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IgnoredOption {
+    pub option: String, // `HozonConfig` field, or `metadata.<field>` for metadata
+    pub reason: String,
+}
+
+impl std::fmt::Display for IgnoredOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` has no effect: {}", self.option, self.reason)
+    }
+}
+
+/// How CBZ archives are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArchiveBackend {
+    /// The `zip` crate on blocking threads, with memory-mapped page reads (the default).
+    #[default]
+    Blocking,
+    /// An async zip writer on Tokio's file I/O. Fairer when dozens of volumes are
+    /// generated concurrently, since no blocking threads are held while writing.
+    /// Requires the `async-zip` feature; does not support encryption.
+    Async,
+}
+
+/// Modification times stored for archive entries.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EntryTimestamps {
+    /// The time the archive is written (the default).
+    #[default]
+    WriteTime,
+    /// The modification time of the source page or cover file, for provenance. Generated
+    /// entries like `ComicInfo.xml` keep the write time. CBZ and CB7 only.
+    SourceModified,
+    /// The same time for every entry, so repeated conversions produce identical archives.
+    /// Applies to CBZ, CB7 and EPUB, and to the creation date of MOBI files. Zip timestamps
+    /// can't represent times before 1980.
+    Fixed(DateTime<Utc>),
+}
+
+impl EntryTimestamps {
+    /// The time to store for an entry whose source file was last modified at
+    /// `source_modified`, or `None` to keep the time set by the archive backend.
+    pub(crate) fn entry_time(self, source_modified: Option<SystemTime>) -> Option<NaiveDateTime> {
+        match self {
+            EntryTimestamps::WriteTime => None,
+            // Zip timestamps have no time zone and are read as local time
+            EntryTimestamps::SourceModified => {
+                source_modified.map(|time| DateTime::<Local>::from(time).naive_local())
+            }
+            EntryTimestamps::Fixed(time) => Some(time.naive_utc()),
+        }
+    }
 }
 
 /// Defines the reading direction for content within an EPUB file.
@@ -126,6 +474,8 @@ pub struct EbookMetadata {
     pub language: String,  // e.g., "en", "ja"
     pub rights: Option<String>,
     pub identifier: Option<String>, // e.g., ISBN, UUID, mangaupdates ID
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub identifiers: Vec<Identifier>, // Additional identifiers with an explicit scheme
     pub release_date: Option<DateTime<Utc>>,
     pub genre: Option<String>, // Specific genre (often for ComicInfo.xml)
     pub web: Option<String>,   // Website link (often for ComicInfo.xml)
@@ -144,6 +494,614 @@ impl EbookMetadata {
     }
 }
 
+/// The scheme of an [`Identifier`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdentifierScheme {
+    Isbn,          // ISBN-10 or ISBN-13
+    Gtin,          // GTIN/EAN barcode number
+    Anilist,       // AniList media ID
+    MangaUpdates,  // MangaUpdates series ID
+    Other(String), // Any other scheme, e.g. "mal" or "uuid"
+}
+
+impl IdentifierScheme {
+    /// Returns the lowercase scheme name used as marker in generated metadata.
+    pub fn name(&self) -> &str {
+        match self {
+            IdentifierScheme::Isbn => "isbn",
+            IdentifierScheme::Gtin => "gtin",
+            IdentifierScheme::Anilist => "anilist",
+            IdentifierScheme::MangaUpdates => "mangaupdates",
+            IdentifierScheme::Other(name) => name,
+        }
+    }
+
+    /// The scheme named `name`, the inverse of [`IdentifierScheme::name`].
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "isbn" => IdentifierScheme::Isbn,
+            "gtin" => IdentifierScheme::Gtin,
+            "anilist" => IdentifierScheme::Anilist,
+            "mangaupdates" => IdentifierScheme::MangaUpdates,
+            name => IdentifierScheme::Other(name.to_string()),
+        }
+    }
+}
+
+/// An identifier of the work together with its scheme.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Identifier {
+    pub scheme: IdentifierScheme,
+    pub value: String,
+}
+
+impl Identifier {
+    /// Creates a new identifier.
+    pub fn new(scheme: IdentifierScheme, value: impl Into<String>) -> Self {
+        Self {
+            scheme,
+            value: value.into(),
+        }
+    }
+}
+
+/// How tags, identifier, rights, custom fields, chapter titles and the source fingerprint
+/// are rendered into the ComicInfo.xml `Notes` field of CBZ output.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NotesFormat {
+    /// Human-readable `Key: value` lines (the default).
+    #[default]
+    Lines,
+    /// A single JSON object with the keys `tags`, `identifier`, `identifiers`, `rights`,
+    /// `custom_fields`, `chapters` and `fingerprint` (plus `chapter_range` and
+    /// `chapter_offsets` with [`HozonConfig::comic_info_chapter_map`](crate::HozonConfig::comic_info_chapter_map),
+    /// and `previous_volume` and `next_volume` with [`HozonConfig::volume_links`](crate::HozonConfig::volume_links)),
+    /// for tools that parse Notes programmatically.
+    Json,
+    /// A custom template. The placeholders `{tags}`, `{identifier}`, `{rights}`,
+    /// `{custom_fields}` (one `key: value` per line), `{chapters}`, `{chapter_range}`,
+    /// `{chapter_offsets}`, `{previous_volume}`, `{next_volume}` and `{fingerprint}` are
+    /// substituted. The chapter placeholders are empty unless
+    /// [`HozonConfig::comic_info_chapter_map`](crate::HozonConfig::comic_info_chapter_map)
+    /// is enabled, the volume placeholders unless
+    /// [`HozonConfig::volume_links`](crate::HozonConfig::volume_links) is.
+    Template(String),
+    /// Leave the Notes field empty. Note that outputs then carry no source fingerprint.
+    Omit,
+}
+
+/// An output next to another one in reading order, linked from its metadata with
+/// [`HozonConfig::volume_links`](crate::HozonConfig::volume_links).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VolumeLink {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume was split into parts
+    pub title: String,              // The file name without extension, e.g. `Series Vol. 2`
+    pub file_name: String,          // e.g. `Series Vol. 2.epub`
+}
+
+/// Which entries the table of contents of EPUB output contains.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TocStyle {
+    /// One entry per page, titled `{chapter label} - Page {n}` (the default).
+    #[default]
+    Pages,
+    /// One entry per chapter, pointing at its first page.
+    Chapters,
+    /// No entries, for EPUBs meant to be read like a CBZ. The navigation document
+    /// required by EPUB 3 is still present, but empty.
+    None,
+}
+
+/// What to do with page images that occur more than once, e.g. a disclaimer page
+/// printed in every volume.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicatePagePolicy {
+    /// Keep every page as-is (the default).
+    #[default]
+    Keep,
+    /// Remove pages whose image already appeared in an earlier volume. Pages are compared
+    /// by content; repeats within the same volume are kept, and a volume consisting only
+    /// of repeated pages is left unchanged.
+    StripRepeated,
+    /// Store an image that occurs on several pages of one EPUB only once, referenced by
+    /// every page showing it. Separate files can't share resources, so this reduces the
+    /// size of each file, not the number of pages. Ignored for CBZ output.
+    Share,
+}
+
+/// What to do with gaps in the page numbering of a chapter (pages `1, 2, 4, 5`), e.g. from
+/// an incomplete download. See [`AnalyzeFinding::MissingPages`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MissingPagePolicy {
+    /// Convert the pages that exist (the default).
+    #[default]
+    Ignore,
+    /// Insert a generated page reading "PAGE MISSING" with the chapter and page number
+    /// for every missing page, keeping double-page spreads after the gap aligned.
+    Placeholder,
+}
+
+/// The side of a two-page spread a page falls on. The first page of a book is a recto:
+/// the right-hand page of left-to-right books, the left-hand page of right-to-left ones.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PageSide {
+    /// An odd page: the first, third, ... page of the book.
+    Recto,
+    /// An even page, facing the recto before it.
+    Verso,
+}
+
+impl PageSide {
+    /// Whether content preceded by `leading_pages` pages needs a blank page in front to
+    /// start on this side.
+    pub(crate) fn needs_blank_page(&self, leading_pages: usize) -> bool {
+        let starts_on_recto = leading_pages.is_multiple_of(2);
+        starts_on_recto != (*self == PageSide::Recto)
+    }
+}
+
+/// The EPUB specification version of generated EPUB files.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EpubVersion {
+    /// EPUB 2.0.1, for legacy readers (older Sony Readers, Adobe Digital Editions 2-4).
+    /// Navigation uses the NCX table of contents, pages are XHTML 1.1, and metadata
+    /// avoids EPUB 3-only properties.
+    V2,
+    /// EPUB 3 (the default).
+    #[default]
+    V3,
+}
+
+/// The text naming a volume number in output file names and EPUB titles.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VolumeLabel {
+    /// `Volume 1` in file names and `Vol 1` in EPUB titles (the default).
+    #[default]
+    English,
+    /// Looked up from the language of the metadata, e.g. `Tome 1` for `fr`, `Band 1` for
+    /// `de` or `第1巻` for `ja`. Languages without a known label use English.
+    FromLanguage,
+    /// A custom label: `{n}` is replaced by the volume number; without the placeholder,
+    /// the number is appended after a space (`"Tomo"` → `Tomo 1`).
+    Custom(String),
+}
+
+impl VolumeLabel {
+    /// Returns the label template (with `{n}` placeholder) for a language tag like `fr`
+    /// or `ja-JP`, if one is known.
+    fn for_language(language: &str) -> Option<&'static str> {
+        let primary = language.split(['-', '_']).next().unwrap_or_default();
+        Some(match primary.to_ascii_lowercase().as_str() {
+            "en" | "it" | "pt" => "Volume {n}",
+            "fr" => "Tome {n}",
+            "de" => "Band {n}",
+            "es" => "Tomo {n}",
+            "nl" => "Deel {n}",
+            "pl" => "Tom {n}",
+            "ru" => "Том {n}",
+            "ja" => "第{n}巻",
+            "zh" => "第{n}卷",
+            "ko" => "{n}권",
+            _ => return None,
+        })
+    }
+
+    /// Formats the label of `volume_number` as used in file names.
+    ///
+    /// # Arguments
+    ///
+    /// * `volume_number` - The 1-based volume number
+    /// * `language` - Language tag of the metadata, used by [`VolumeLabel::FromLanguage`]
+    pub fn format(&self, volume_number: usize, language: &str) -> String {
+        let template = match self {
+            VolumeLabel::English => "Volume {n}",
+            VolumeLabel::FromLanguage => Self::for_language(language).unwrap_or("Volume {n}"),
+            VolumeLabel::Custom(label) if label.contains("{n}") => label,
+            VolumeLabel::Custom(label) => return format!("{} {}", label, volume_number),
+        };
+        template.replace("{n}", &volume_number.to_string())
+    }
+
+    /// Formats the label of `volume_number` as used in EPUB titles. Only differs from
+    /// [`VolumeLabel::format`] for the English default, which is abbreviated to `Vol`.
+    pub fn format_short(&self, volume_number: usize, language: &str) -> String {
+        match self {
+            VolumeLabel::English => format!("Vol {}", volume_number),
+            label => label.format(volume_number, language),
+        }
+    }
+}
+
+/// Unicode normalization form applied to titles, chapter names and output file names.
+///
+/// The same title can be encoded differently: macOS file systems store decomposed
+/// characters (NFD, `e` + combining accent), most other sources precomposed ones (NFC).
+/// Both look identical but compare, sort and name files differently, which shows up as
+/// duplicate-looking entries in library apps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnicodeNormalization {
+    /// Keep text as it is (the default).
+    #[default]
+    None,
+    /// Canonical composition (NFC): the same characters, consistently precomposed.
+    Nfc,
+    /// Compatibility composition (NFKC): additionally folds compatibility variants like
+    /// full-width letters and ligatures into their plain forms.
+    Nfkc,
+}
+
+impl UnicodeNormalization {
+    /// Returns `text` in this normalization form.
+    pub fn apply(&self, text: &str) -> String {
+        use unicode_normalization::UnicodeNormalization as _;
+        match self {
+            UnicodeNormalization::None => text.to_string(),
+            UnicodeNormalization::Nfc => text.nfc().collect(),
+            UnicodeNormalization::Nfkc => text.nfkc().collect(),
+        }
+    }
+}
+
+impl EbookMetadata {
+    /// Returns a copy with every free-text field normalized to `form`. Identifiers,
+    /// dates and links are kept as they are.
+    pub fn normalized(&self, form: UnicodeNormalization) -> Self {
+        if form == UnicodeNormalization::None {
+            return self.clone();
+        }
+        let apply = |text: &String| form.apply(text);
+        Self {
+            title: form.apply(&self.title),
+            series: self.series.as_ref().map(apply),
+            authors: self.authors.iter().map(apply).collect(),
+            publisher: self.publisher.as_ref().map(apply),
+            description: self.description.as_ref().map(apply),
+            tags: self.tags.iter().map(apply).collect(),
+            rights: self.rights.as_ref().map(apply),
+            genre: self.genre.as_ref().map(apply),
+            custom_fields: self
+                .custom_fields
+                .iter()
+                .map(|(key, value)| (form.apply(key), form.apply(value)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Where page documents and images are stored inside generated EPUBs. Some downstream
+/// tools (e.g. the Kobo kepub converter or Kindle Previewer) mishandle deeply nested
+/// internal paths.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EpubPathLayout {
+    /// A directory per chapter holding its page documents and images, e.g.
+    /// `chapters/chapter_001/page_001.xhtml` next to `page_001.jpg` (default).
+    #[default]
+    Nested,
+    /// Page documents in `text/` and images in `images/`, e.g.
+    /// `text/chapter_001_page_001.xhtml` and `images/chapter_001_page_001.jpg`.
+    ByType,
+    /// Everything next to the package document, e.g. `chapter_001_page_001.xhtml` and
+    /// `chapter_001_page_001.jpg`.
+    Flat,
+}
+
+impl EpubPathLayout {
+    /// Returns the paths (relative to `OEBPS/`) of the document and of the image, without
+    /// extension, of page `page` of chapter `chapter`, both 1-based.
+    pub(crate) fn page_paths(&self, chapter: usize, page: usize) -> (String, String) {
+        let name = format!("chapter_{:03}_page_{:03}", chapter, page);
+        match self {
+            EpubPathLayout::Nested => {
+                let stem = format!("chapters/chapter_{:03}/page_{:03}", chapter, page);
+                (format!("{}.xhtml", stem), stem)
+            }
+            EpubPathLayout::ByType => (format!("text/{}.xhtml", name), format!("images/{}", name)),
+            EpubPathLayout::Flat => (format!("{}.xhtml", name), name),
+        }
+    }
+
+    /// Returns the path (relative to `OEBPS/`) of the intro page of chapter `chapter`,
+    /// see [`ChapterIntro`].
+    pub(crate) fn intro_path(&self, chapter: usize) -> String {
+        match self {
+            EpubPathLayout::Nested => format!("chapters/chapter_{:03}/intro.xhtml", chapter),
+            EpubPathLayout::ByType => format!("text/chapter_{:03}_intro.xhtml", chapter),
+            EpubPathLayout::Flat => format!("chapter_{:03}_intro.xhtml", chapter),
+        }
+    }
+
+    /// The path (relative to `OEBPS/`) of the blank page inserted for
+    /// [`PageSide`] alignment.
+    pub(crate) fn blank_page_path(&self) -> &'static str {
+        match self {
+            EpubPathLayout::Nested => "chapters/blank.xhtml",
+            EpubPathLayout::ByType => "text/blank.xhtml",
+            EpubPathLayout::Flat => "blank.xhtml",
+        }
+    }
+
+    /// The path (relative to `OEBPS/`) of the cover image, without extension.
+    pub(crate) fn cover_path(&self) -> &'static str {
+        match self {
+            EpubPathLayout::Nested | EpubPathLayout::ByType => "images/cover",
+            EpubPathLayout::Flat => "cover",
+        }
+    }
+}
+
+/// How pages scale on the screen in generated EPUBs, selecting one of the prebuilt
+/// stylesheets.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFit {
+    /// Pages fill the width of the screen, scrolling if they are taller (the default).
+    #[default]
+    FitWidth,
+    /// Pages fill the height of the screen, narrower pages leave space at the sides.
+    FitHeight,
+    /// Pages fill as much of the screen as their aspect ratio allows, without any
+    /// margins; what most comic readers want.
+    FullBleed,
+    /// Pages keep their original size in pixels.
+    OriginalSize,
+}
+
+impl ImageFit {
+    /// The stylesheet of this mode.
+    pub(crate) fn stylesheet(&self) -> &'static str {
+        match self {
+            ImageFit::FitWidth => include_str!("../templates/Epub.css"),
+            ImageFit::FitHeight => include_str!("../templates/EpubFitHeight.css"),
+            ImageFit::FullBleed => include_str!("../templates/EpubFullBleed.css"),
+            ImageFit::OriginalSize => include_str!("../templates/EpubOriginal.css"),
+        }
+    }
+}
+
+/// Page background and margins of generated EPUBs, added to the stylesheet selected by
+/// [`ImageFit`]. White-bordered scans can sit on a matching background instead of the
+/// reader default.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageStyle {
+    /// CSS color of the page background, a hex color like `"#f4f1ea"` or a color name
+    /// like `"black"`. `None` keeps white.
+    pub background_color: Option<String>,
+    /// Margin around each page as one to four CSS lengths in `px`, `pt`, `em`, `rem`,
+    /// `%`, `vw` or `vh`, e.g. `"1em"` or `"0 2%"`. `None` keeps the margins of the
+    /// stylesheet.
+    pub margin: Option<String>,
+}
+
+/// Units accepted in [`PageStyle::margin`].
+const MARGIN_UNITS: &[&str] = &["px", "pt", "rem", "em", "%", "vw", "vh"];
+
+impl PageStyle {
+    /// Validates the style; values are copied into the stylesheet, so only plain colors
+    /// and lengths are accepted.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(color) = &self.background_color {
+            let hex = color.strip_prefix('#').is_some_and(|digits| {
+                matches!(digits.len(), 3 | 4 | 6 | 8)
+                    && digits.chars().all(|c| c.is_ascii_hexdigit())
+            });
+            let named = !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic());
+            if !hex && !named {
+                return Err(Error::Other(format!(
+                    "Page background color '{}' must be a hex color or a color name",
+                    color
+                )));
+            }
+        }
+        if let Some(margin) = &self.margin {
+            let lengths: Vec<&str> = margin.split_whitespace().collect();
+            let valid = (1..=4).contains(&lengths.len())
+                && lengths.iter().all(|length| is_css_length(length));
+            if !valid {
+                return Err(Error::Other(format!(
+                    "Page margin '{}' must be one to four CSS lengths, e.g. \"1em\" or \"0 2%\"",
+                    margin
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The CSS rules of this style, appended to the stylesheet; empty if nothing is set.
+    pub(crate) fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(color) = &self.background_color {
+            css.push_str(&format!(
+                "\n/* Page background */\nhtml,\nbody {{\n    background-color: {};\n}}\n",
+                color
+            ));
+        }
+        if let Some(margin) = &self.margin {
+            css.push_str(&format!(
+                "\n/* Page margins */\nbody {{\n    padding: {};\n    box-sizing: border-box;\n}}\n",
+                margin
+            ));
+        }
+        css
+    }
+}
+
+/// Returns `true` if `value` is `0` or a non-negative number followed by a unit of
+/// [`MARGIN_UNITS`].
+fn is_css_length(value: &str) -> bool {
+    if value == "0" {
+        return true;
+    }
+    MARGIN_UNITS.iter().any(|unit| {
+        value.strip_suffix(unit).is_some_and(|number| {
+            !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && number.matches('.').count() <= 1
+                && number != "."
+        })
+    })
+}
+
+/// How an entry of a generated archive is compressed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EntryCompression {
+    /// Store the entry as-is.
+    Stored,
+    /// Compress the entry with deflate (the default).
+    #[default]
+    Deflated,
+}
+
+/// Compression of the entries of EPUB output, chosen per resource type.
+///
+/// Page images are JPEG, PNG or WebP files that are compressed already; deflating them
+/// again costs a lot of CPU time on large jobs for hardly any size reduction, so they are
+/// stored by default. XHTML, CSS and the package documents compress well and are deflated.
+/// The `mimetype` entry is always stored, as the EPUB specification requires.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EpubCompression {
+    /// Compression of images (pages and cover).
+    pub images: EntryCompression,
+    /// Compression of XHTML pages, stylesheets, and the package and navigation documents.
+    pub documents: EntryCompression,
+    /// Deflate level from 0 (fastest) to 9 (smallest). `None` uses the default level.
+    pub level: Option<u8>,
+}
+
+impl Default for EpubCompression {
+    fn default() -> Self {
+        Self {
+            images: EntryCompression::Stored,
+            documents: EntryCompression::Deflated,
+            level: None,
+        }
+    }
+}
+
+impl EpubCompression {
+    /// Deflates every entry, for the smallest files at the highest CPU cost.
+    pub fn deflate_all() -> Self {
+        Self {
+            images: EntryCompression::Deflated,
+            ..Self::default()
+        }
+    }
+
+    /// Validates the compression settings.
+    pub fn validate(&self) -> Result<()> {
+        if self.level.is_some_and(|level| level > 9) {
+            return Err(Error::Other(
+                "EPUB compression level must be between 0 and 9".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns how the archive entry `name` (e.g. `OEBPS/data/page_001.jpg`) is compressed.
+    pub fn for_entry(&self, name: &str) -> EntryCompression {
+        if ImageFormat::from_path(Path::new(name)).is_ok() {
+            self.images
+        } else {
+            self.documents
+        }
+    }
+}
+
+/// Table of contents options for EPUB output.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TocOptions {
+    /// Which entries the table of contents contains.
+    pub style: TocStyle,
+    /// Template for chapter labels, e.g. `"Chapter {number}: {title}"`. `{number}` is the
+    /// 1-based position of the chapter within the volume, `{title}` the chapter directory
+    /// name. `None` uses the chapter directory name as-is.
+    pub chapter_label: Option<String>,
+}
+
+impl TocOptions {
+    /// Returns the label of a chapter according to [`TocOptions::chapter_label`].
+    pub fn chapter_label(&self, number: usize, title: &str) -> String {
+        match &self.chapter_label {
+            Some(template) => template
+                .replace("{number}", &number.to_string())
+                .replace("{title}", title),
+            None => title.to_string(),
+        }
+    }
+}
+
+/// A generated page with the chapter title, inserted before the pages of each chapter to
+/// ease navigation in volumes merging many chapters. EPUBs get a text page; CBZ, CB7 and
+/// MOBI output get a rendered image of the size of the chapter's first page.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChapterIntro {
+    /// Text of the page, one line per `\n`, e.g. `"Chapter {number}\n{title}"` (the
+    /// default). `{number}` is the 1-based chapter number counted across the series,
+    /// `{title}` the chapter directory name. The first line is the heading.
+    ///
+    /// Rendered images print letters in uppercase; their built-in font covers ASCII
+    /// letters, digits and common punctuation, other characters are left blank.
+    pub template: String,
+}
+
+impl Default for ChapterIntro {
+    fn default() -> Self {
+        Self {
+            template: "Chapter {number}\n{title}".to_string(),
+        }
+    }
+}
+
+impl ChapterIntro {
+    /// Returns the non-empty lines of the page of chapter `number` titled `title`.
+    pub fn lines(&self, number: usize, title: &str) -> Vec<String> {
+        self.template
+            .replace("{number}", &number.to_string())
+            .replace("{title}", title)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
 /// Options for specifying cover images during conversion.
 /// This enum allows for no cover, a single custom cover, or per-volume covers.
 #[cfg_attr(feature = "specta", derive(specta::Type))]
@@ -161,6 +1119,17 @@ pub enum CoverOptions {
     PerVolume(HashMap<usize, PathBuf>),
 }
 
+impl CoverOptions {
+    /// Returns the custom cover image for the volume at the given 0-based index, if any.
+    pub fn cover_for_volume(&self, volume_index: usize) -> Option<PathBuf> {
+        match self {
+            CoverOptions::None => None,
+            CoverOptions::Single(path) => Some(path.clone()),
+            CoverOptions::PerVolume(map) => map.get(&volume_index).cloned(),
+        }
+    }
+}
+
 /// Immutable configuration for a Hozon conversion task, established during `HozonConfigBuilder::build()`.
 /// This holds all the user-defined settings for how the conversion should proceed.
 #[derive(Debug, Clone, Default)]
@@ -187,6 +1156,83 @@ pub struct CollectedContent {
     pub report: AnalyzeReport,                  // Report from the collection/analysis phase
 }
 
+impl CollectedContent {
+    /// Summarizes the collected source: totals, pages per image format and a page size
+    /// histogram, e.g. for display in a frontend. Blocking; reads the metadata of every page.
+    pub fn stats(&self) -> SourceStats {
+        let mut stats = SourceStats {
+            total_chapters: self.chapters_with_pages.len(),
+            size_histogram: SIZE_BUCKET_BOUNDS
+                .iter()
+                .enumerate()
+                .map(|(i, &min_bytes)| SizeBucket {
+                    min_bytes,
+                    max_bytes: SIZE_BUCKET_BOUNDS.get(i + 1).copied(),
+                    pages: 0,
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        for page in self.chapters_with_pages.iter().flatten() {
+            stats.total_pages += 1;
+            let format = ImageFormat::from_path(page).map_or("other", |format| format.extension());
+            *stats
+                .pages_per_format
+                .entry(format.to_string())
+                .or_default() += 1;
+
+            let Ok(size) = std::fs::metadata(page).map(|m| m.len()) else {
+                stats.unreadable_pages += 1;
+                continue;
+            };
+            stats.total_bytes += size;
+            if let Some(bucket) = stats
+                .size_histogram
+                .iter_mut()
+                .rev()
+                .find(|bucket| size >= bucket.min_bytes)
+            {
+                bucket.pages += 1;
+            }
+        }
+
+        if stats.total_chapters > 0 {
+            stats.average_pages_per_chapter =
+                stats.total_pages as f64 / stats.total_chapters as f64;
+        }
+        stats
+    }
+}
+
+/// Lower bounds of the page size histogram buckets in [`SourceStats::size_histogram`]:
+/// under 100 KiB, 100-500 KiB, 500 KiB-1 MiB, 1-5 MiB and 5 MiB or more.
+const SIZE_BUCKET_BOUNDS: [u64; 5] = [0, 100 << 10, 500 << 10, 1 << 20, 5 << 20];
+
+/// Summary of collected source content, see [`CollectedContent::stats`].
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceStats {
+    pub total_chapters: usize,
+    pub total_pages: usize,
+    pub total_bytes: u64, // Sum of the sizes of readable pages
+    pub pages_per_format: BTreeMap<String, usize>, // Keyed by extension: "jpg", "png", "webp"
+    pub average_pages_per_chapter: f64,
+    pub size_histogram: Vec<SizeBucket>, // Ascending, always the same buckets
+    pub unreadable_pages: usize,         // Pages whose size could not be read
+}
+
+/// One bucket of the page size histogram in [`SourceStats`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeBucket {
+    pub min_bytes: u64,         // Inclusive
+    pub max_bytes: Option<u64>, // Exclusive; `None` for the last bucket
+    pub pages: usize,
+}
+
 /// Represents the outcome of the volume structuring (grouping) phase.
 /// This data structure holds the image paths organized into logical volumes
 /// and a `VolumeStructureReport`.
@@ -207,6 +1253,7 @@ pub struct StructuredContent {
 pub struct AnalyzeReport {
     pub findings: Vec<AnalyzeFinding>,
     pub recommended_strategy: VolumeGroupingStrategy,
+    pub recommended_depth: CollectionDepth, // The depth matching the source layout
 }
 
 /// Report from the volume structuring (grouping) stage.
@@ -218,6 +1265,161 @@ pub struct VolumeStructureReport {
     pub total_chapters_processed: usize,
     pub total_volumes_created: usize,
     pub chapter_counts_per_volume: Vec<usize>, // e.g., `[10, 12, 8]` for 3 volumes
+    pub adjustments: Vec<VolumeCountAdjustment>, // Made to reach `expected_volume_count`
+    pub cover_breaks: Vec<CoverBreak>,         // Found by `VolumeGroupingStrategy::ImageAnalysis`
+}
+
+/// A chapter whose cover (first page) [`VolumeGroupingStrategy::ImageAnalysis`] takes for a
+/// volume cover, i.e. its share of gray pixels is at most the sensibility.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverBreak {
+    pub index: usize,    // 0-based chapter index, in grouping order
+    pub gray_share: f64, // 0.0-1.0
+    pub confidence: f64, // 0.0 right at the sensibility, 1.0 for a cover without gray pixels
+    pub applied: bool,   // False below `HozonConfig::image_analysis_min_confidence`
+}
+
+/// A change made to the detected volumes to reach
+/// [`HozonConfig::expected_volume_count`](crate::HozonConfig::expected_volume_count).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VolumeCountAdjustment {
+    /// Cover analysis was repeated at another sensibility (0-100).
+    Sensibility { from: u8, to: u8 },
+    /// Volume `volume` (1-based) was merged with the volume following it.
+    Merged { volume: usize },
+    /// Volume `volume` (1-based) was split in half.
+    Split { volume: usize },
+}
+
+impl std::fmt::Display for VolumeCountAdjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolumeCountAdjustment::Sensibility { from, to } => {
+                write!(
+                    f,
+                    "image analysis sensibility changed from {} to {}",
+                    from, to
+                )
+            }
+            VolumeCountAdjustment::Merged { volume } => {
+                write!(f, "volume {} merged with volume {}", volume, volume + 1)
+            }
+            VolumeCountAdjustment::Split { volume } => write!(f, "volume {} split in two", volume),
+        }
+    }
+}
+
+/// State of a single expected output file, as determined by `HozonConfig::check_outputs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputState {
+    /// The output exists and its embedded fingerprint matches the current source and config.
+    UpToDate,
+    /// The output exists but was generated from different source content or configuration.
+    Stale,
+    /// The output file does not exist yet.
+    Missing,
+    /// The output exists but carries no readable fingerprint, so its freshness is unknown.
+    Unverifiable,
+}
+
+/// Status of one expected output file (one volume, or one part of a split volume).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputStatus {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume is split into several files
+    pub path: PathBuf,
+    pub state: OutputState,
+}
+
+/// Report comparing existing output files against the current source content.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputCheckReport {
+    pub outputs: Vec<OutputStatus>, // One entry per expected volume, in order
+    pub orphaned: Vec<PathBuf>,     // Files of the output format that no current volume maps to
+}
+
+impl OutputCheckReport {
+    /// Returns `true` if every expected output exists and is up-to-date, with no orphans.
+    pub fn is_up_to_date(&self) -> bool {
+        self.orphaned.is_empty()
+            && self
+                .outputs
+                .iter()
+                .all(|o| o.state == OutputState::UpToDate)
+    }
+}
+
+/// An ebook unpacked by [`HozonConfig::extract_to_directory`](crate::HozonConfig::extract_to_directory).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractedEbook {
+    pub directory: PathBuf, // Holds the chapter directories, the cover and the metadata file
+    pub metadata: EbookMetadata,
+    pub volume: Option<usize>, // The volume number recorded in the ebook, if any
+    pub cover: Option<PathBuf>, // `cover.<ext>`, if the ebook has a separate cover
+    pub chapter_titles: Vec<String>, // In reading order
+    pub chapters: Vec<Vec<PathBuf>>, // The pages of each chapter, in reading order
+}
+
+/// One file written by a conversion.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneratedFile {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume was split into several files
+    pub path: PathBuf,
+    pub page_count: usize,
+    pub pages: Vec<PageMapping>, // In reading order, one per page (custom covers excluded)
+}
+
+/// Where a source page ended up in a generated file, so external tools (e.g. translation
+/// trackers) can cross-reference pages after conversion.
+///
+/// Page numbers follow the archive entry names: CBZ pages are named after
+/// `volume_page` (`page_007.jpg`), EPUB pages after `chapter` and `chapter_page`
+/// (`chapter_002/page_003.xhtml`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageMapping {
+    pub source: PathBuf, // Source file; `<synthesized>/<name>` for generated pages
+    pub chapter: usize,  // 1-based chapter number within the file
+    pub series_chapter: usize, // 1-based chapter number counted across all files
+    pub chapter_page: usize, // 1-based page number within the chapter
+    pub volume_page: usize, // 1-based page number within the file
+}
+
+/// Summary of a conversion run through [`HozonPipeline`](crate::pipeline::HozonPipeline).
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConversionReport {
+    pub analysis: Option<AnalyzeReport>, // Only set when the run started from the source directory
+    pub structure: Option<VolumeStructureReport>, // Not set when the run started from structured data
+    pub files: Vec<GeneratedFile>,                // In volume order
+    pub skipped: Vec<SkippedVolume>, // Not generated because the conversion was shut down
+}
+
+/// A volume, or a part of a split volume, that wasn't generated because the conversion
+/// was shut down, see [`ConversionHandle::shutdown`](crate::pipeline::ConversionHandle::shutdown).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedVolume {
+    pub volume_number: usize,       // 1-based
+    pub part_number: Option<usize>, // 1-based, if the volume was split into several files
 }
 
 /// Specifies the intended starting point for a Hozon conversion.
@@ -234,6 +1436,188 @@ pub enum HozonExecutionMode {
     FromStructuredData,
 }
 
+/// An image format pages can be read in.
+///
+/// Downstream code can match on formats instead of comparing extension strings. Besides
+/// the built-in formats, applications can accept further ones with
+/// [`register_image_format`]. Registered formats are copied into outputs unchanged: they
+/// can't be decoded, so image processing skips them and
+/// [`VolumeGroupingStrategy::ImageAnalysis`] can't analyze them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    WebP,
+    /// A format added with [`register_image_format`].
+    Custom(CustomImageFormat),
+}
+
+/// A page format added with [`register_image_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomImageFormat {
+    extension: &'static str, // Lowercase, without the dot
+    mime: &'static str,
+}
+
+lazy_static! {
+    static ref CUSTOM_IMAGE_FORMATS: RwLock<Vec<CustomImageFormat>> = RwLock::new(Vec::new());
+}
+
+impl ImageFormat {
+    /// The formats Hozon reads without registration.
+    pub const BUILT_IN: [ImageFormat; 3] = [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+    /// Returns the format of files with `extension` (case-insensitive, without the dot),
+    /// or `None` if it is neither built in nor registered. `jpeg` is accepted for JPEG.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let custom = CUSTOM_IMAGE_FORMATS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Self::from_extension_in(&extension.to_lowercase(), &custom)
+    }
+
+    /// [`ImageFormat::from_extension`] for a lowercase `extension`, looking up registered
+    /// formats in `custom`, so callers holding the registry lock can use it.
+    fn from_extension_in(extension: &str, custom: &[CustomImageFormat]) -> Option<Self> {
+        match extension {
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "webp" => Some(ImageFormat::WebP),
+            _ => custom
+                .iter()
+                .find(|format| format.extension == extension)
+                .map(|format| ImageFormat::Custom(*format)),
+        }
+    }
+
+    /// Returns the format of the image at `path`, judged by its extension.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Unsupported`] if the path has no extension of a supported format.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        extension
+            .and_then(Self::from_extension)
+            .ok_or_else(|| Error::Unsupported(format!("Image format {:#?}", extension)))
+    }
+
+    /// Returns the built-in format of the encoded image `bytes`, judged by their
+    /// signature, or `None` if they aren't a JPEG, PNG or WebP image. Registered formats
+    /// aren't recognized.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match image::guess_format(bytes).ok()? {
+            image::ImageFormat::Jpeg => Some(ImageFormat::Jpeg),
+            image::ImageFormat::Png => Some(ImageFormat::Png),
+            image::ImageFormat::WebP => Some(ImageFormat::WebP),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the image at `path`, judged by its content. Files whose
+    /// content isn't recognized (e.g. registered formats) are judged by their extension
+    /// like in [`ImageFormat::from_path`], so a PNG saved as `page.jpg` is a PNG.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, or if its content isn't recognized and it has no
+    /// extension of a supported format.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut header = Vec::with_capacity(16);
+        std::fs::File::open(path)?
+            .take(16)
+            .read_to_end(&mut header)?;
+        match Self::from_bytes(&header) {
+            Some(format) => Ok(format),
+            None => Self::from_path(path),
+        }
+    }
+
+    /// The built-in and all registered formats.
+    pub fn all() -> Vec<ImageFormat> {
+        let custom = CUSTOM_IMAGE_FORMATS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|format| ImageFormat::Custom(*format))
+            .collect::<Vec<_>>();
+        Self::BUILT_IN.into_iter().chain(custom).collect()
+    }
+
+    /// The extension used for pages in this format, e.g. `jpg`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Custom(format) => format.extension,
+        }
+    }
+
+    /// The MIME type of this format, e.g. `image/jpeg`.
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Custom(format) => format.mime,
+        }
+    }
+
+    /// Whether pages in this format can be decoded, which image processing and image
+    /// analysis need.
+    pub fn can_decode(&self) -> bool {
+        !matches!(self, ImageFormat::Custom(_))
+    }
+
+    /// Whether pages can be transcoded into this format, see
+    /// [`ProcessedImageFormat`](crate::processing::ProcessedImageFormat).
+    pub fn can_encode(&self) -> bool {
+        !matches!(self, ImageFormat::Custom(_))
+    }
+}
+
+/// Accepts pages with `extension` (without the dot) as `mime`, e.g. `("jxl",
+/// "image/jxl")`, and returns the new format. Registration is process-wide; registering
+/// the same extension and MIME type again returns the existing format.
+///
+/// Readers must support the format themselves; Hozon copies such pages unchanged.
+///
+/// # Errors
+///
+/// Fails if the extension is empty, not alphanumeric, a built-in format's, or already
+/// registered with a different MIME type.
+pub fn register_image_format(extension: &'static str, mime: &'static str) -> Result<ImageFormat> {
+    if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(Error::Other(format!(
+            "Invalid image format extension '{}'",
+            extension
+        )));
+    }
+    if extension.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(Error::Other(format!(
+            "Image format extension '{}' must be lowercase",
+            extension
+        )));
+    }
+    let mut formats = CUSTOM_IMAGE_FORMATS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match ImageFormat::from_extension_in(extension, &formats) {
+        Some(ImageFormat::Custom(format)) if format.mime == mime => Ok(ImageFormat::Custom(format)),
+        Some(existing) => Err(Error::Other(format!(
+            "Image format extension '{}' is already used by {:?}",
+            extension, existing
+        ))),
+        None => {
+            let format = CustomImageFormat { extension, mime };
+            formats.push(format);
+            Ok(ImageFormat::Custom(format))
+        }
+    }
+}
+
 /// Utility function: Determines file type and MIME type from a file path
 ///
 /// # Arguments
@@ -244,22 +1628,9 @@ pub enum HozonExecutionMode {
 ///
 /// * `Ok((&str, &str))` - A tuple containing (file extension, MIME type)
 /// * `Err(Error)` - An error if the file format is unsupported
-///
-/// # Supported formats
-///
-/// - JPEG/JPG: image/jpeg
-/// - PNG: image/png
-/// - WebP: image/webp
+#[deprecated(
+    note = "use `ImageFormat::from_path`, whose `extension` and `mime` give the same values"
+)]
 pub fn get_file_info(image_path: &PathBuf) -> Result<(&'static str, &'static str)> {
-    let path = image_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase());
-
-    match path.as_deref() {
-        Some("jpg") | Some("jpeg") => Ok(("jpg", "image/jpeg")),
-        Some("png") => Ok(("png", "image/png")),
-        Some("webp") => Ok(("webp", "image/webp")),
-        _ => Err(Error::Unsupported(format!("Image format {:#?}", path))),
-    }
+    ImageFormat::from_path(image_path).map(|format| (format.extension(), format.mime()))
 }
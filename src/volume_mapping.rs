@@ -0,0 +1,230 @@
+//! Chapter-to-volume mappings read from CSV or JSON files.
+//!
+//! Fan wikis publish the official contents of every volume as a list of chapter
+//! numbers. [`VolumeGroupingStrategy::Mapping`](crate::VolumeGroupingStrategy::Mapping)
+//! groups chapters by such a list, read from
+//! [`HozonConfig::volume_mapping_file`](crate::HozonConfig::volume_mapping_file). Files
+//! ending in `.json` hold an object from chapter numbers to volume numbers, anything
+//! else is read as CSV with one `chapter,volume` pair per line:
+//!
+//! ```text
+//! # chapter,volume
+//! 1-8,1
+//! 9-17,2
+//! 17.5,2
+//! ```
+//!
+//! ```json
+//! { "1-8": 1, "9-17": 2, "17.5": 2 }
+//! ```
+//!
+//! Chapters are given as single numbers or inclusive ranges (`9-17`). A header line,
+//! empty lines and lines starting with `#` are skipped. The number of a chapter is
+//! extracted from its directory name with
+//! [`HozonConfig::chapter_name_regex_str`](crate::HozonConfig::chapter_name_regex_str), the
+//! same way chapters are sorted.
+
+use regex::Regex;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::collector::chapter_number;
+use crate::error::{Error, Result};
+use crate::path_utils::path_to_string_lossy;
+
+/// One line of a mapping: the chapters `first..=last` belong to `volume`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MappingEntry {
+    first: f64,
+    last: f64,
+    volume: u32,
+}
+
+/// A parsed chapter-to-volume mapping, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VolumeMapping {
+    entries: Vec<MappingEntry>,
+}
+
+impl VolumeMapping {
+    /// Reads the mapping stored at `path`, as JSON if it ends in `.json` and as CSV
+    /// otherwise. Blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or parsed, or maps a chapter to two volumes.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read volume mapping '{}': {}",
+                    path_to_string_lossy(path),
+                    e
+                ),
+            ))
+        })?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let parsed = if is_json {
+            Self::parse_json(&content)
+        } else {
+            Self::parse_csv(&content)
+        };
+        parsed.map_err(|e| {
+            let reason = match e {
+                Error::Other(reason) => reason,
+                e => e.to_string(),
+            };
+            Error::Other(format!(
+                "Invalid volume mapping '{}': {}",
+                path_to_string_lossy(path),
+                reason
+            ))
+        })
+    }
+
+    /// Parses `chapter,volume` lines.
+    ///
+    /// # Errors
+    ///
+    /// Fails on malformed lines (other than a leading header) and conflicting entries.
+    pub fn parse_csv(content: &str) -> Result<Self> {
+        let mut mapping = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line
+                .split_once(',')
+                .ok_or_else(|| "expected `chapter,volume`".to_string())
+                .and_then(|(chapters, volume)| parse_entry(chapters, volume.trim()));
+            match parsed {
+                Ok(entry) => mapping
+                    .insert(entry)
+                    .map_err(|e| Error::Other(format!("line {}: {}", index + 1, e)))?,
+                // A header names the columns instead of numbering them
+                Err(_) if mapping.entries.is_empty() && !line.starts_with(char::is_numeric) => {}
+                Err(e) => {
+                    return Err(Error::Other(format!("line {}: {}", index + 1, e)));
+                }
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// Parses an object from chapter numbers (or ranges) to volume numbers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the content isn't such an object or maps a chapter to two volumes.
+    pub fn parse_json(content: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(content)
+            .map_err(|e| Error::Other(format!("invalid JSON: {}", e)))?;
+        let object = value.as_object().ok_or_else(|| {
+            Error::Other("expected an object from chapters to volumes".to_string())
+        })?;
+
+        let mut mapping = Self::default();
+        for (chapters, volume) in object {
+            let volume = match volume {
+                Value::String(volume) => volume.clone(),
+                volume => volume.to_string(),
+            };
+            parse_entry(chapters, &volume)
+                .and_then(|entry| mapping.insert(entry))
+                .map_err(|e| Error::Other(format!("entry '{}': {}", chapters, e)))?;
+        }
+        Ok(mapping)
+    }
+
+    /// The volume `chapter` is mapped to.
+    pub fn volume_of(&self, chapter: f64) -> Option<u32> {
+        self.entries
+            .iter()
+            .find(|entry| (entry.first..=entry.last).contains(&chapter))
+            .map(|entry| entry.volume)
+    }
+
+    /// Whether the mapping has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The volume the chapter stored in `chapter_dir` is mapped to, with the chapter
+    /// number extracted using `name_regex` (or the default number pattern).
+    pub fn volume_of_chapter(&self, chapter_dir: &Path, name_regex: Option<&Regex>) -> Option<u32> {
+        self.volume_of(chapter_number(chapter_dir, name_regex)?)
+    }
+
+    /// The chapter directories of `chapters` (as collected, pages per chapter) that
+    /// aren't mapped to any volume.
+    pub fn unmapped_chapters(
+        &self,
+        chapters: &[Vec<PathBuf>],
+        name_regex: Option<&Regex>,
+    ) -> Vec<(PathBuf, Option<f64>)> {
+        chapters
+            .iter()
+            .filter_map(|pages| pages.first()?.parent())
+            .filter(|chapter_dir| self.volume_of_chapter(chapter_dir, name_regex).is_none())
+            .map(|chapter_dir| {
+                (
+                    chapter_dir.to_path_buf(),
+                    chapter_number(chapter_dir, name_regex),
+                )
+            })
+            .collect()
+    }
+
+    fn insert(&mut self, entry: MappingEntry) -> std::result::Result<(), String> {
+        let conflict = self.entries.iter().find(|existing| {
+            existing.volume != entry.volume
+                && existing.first <= entry.last
+                && entry.first <= existing.last
+        });
+        if let Some(existing) = conflict {
+            return Err(format!(
+                "chapters {}-{} are mapped to volume {} and {}",
+                entry.first.max(existing.first),
+                entry.last.min(existing.last),
+                existing.volume,
+                entry.volume
+            ));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+/// Parses a chapter number or range and a volume number.
+fn parse_entry(chapters: &str, volume: &str) -> std::result::Result<MappingEntry, String> {
+    let parse_chapter = |number: &str| {
+        number
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .ok_or_else(|| format!("'{}' is not a chapter number", number.trim()))
+    };
+    let (first, last) = match chapters.trim().split_once('-') {
+        Some((first, last)) => (parse_chapter(first)?, parse_chapter(last)?),
+        None => {
+            let chapter = parse_chapter(chapters)?;
+            (chapter, chapter)
+        }
+    };
+    if first > last {
+        return Err(format!("range '{}' is reversed", chapters.trim()));
+    }
+    let volume = volume
+        .parse::<u32>()
+        .map_err(|_| format!("'{}' is not a volume number", volume))?;
+    Ok(MappingEntry {
+        first,
+        last,
+        volume,
+    })
+}
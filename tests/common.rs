@@ -112,6 +112,109 @@ pub async fn create_dummy_color_image(path: &Path) -> Result<()> {
     create_dummy_image(path, Rgb([255, 0, 0])).await // Red
 }
 
+/// Creates a dummy JPEG photo whose EXIF data carries a `DateTimeOriginal` (formatted
+/// `2024:05:01 14:30:00`) and optionally an `ImageDescription` caption.
+#[allow(dead_code)]
+pub async fn create_dummy_photo(path: &Path, taken: &str, caption: Option<&str>) -> Result<()> {
+    create_dummy_color_image(path).await?;
+
+    // Little-endian TIFF: IFD0 (caption, pointer to the Exif IFD), the Exif IFD (date),
+    // then the string values
+    let ifd0_entries: u16 = if caption.is_some() { 2 } else { 1 };
+    let exif_ifd = 8 + 2 + 12 * ifd0_entries as u32 + 4;
+    let date_offset = exif_ifd + 18;
+    let caption_offset = date_offset + taken.len() as u32 + 1;
+    let entry = |tiff: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: u32| {
+        tiff.extend(tag.to_le_bytes());
+        tiff.extend(field_type.to_le_bytes());
+        tiff.extend(count.to_le_bytes());
+        tiff.extend(value.to_le_bytes());
+    };
+
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    tiff.extend(ifd0_entries.to_le_bytes());
+    if let Some(caption) = caption {
+        entry(
+            &mut tiff,
+            0x010E,
+            2,
+            caption.len() as u32 + 1,
+            caption_offset,
+        );
+    }
+    entry(&mut tiff, 0x8769, 4, 1, exif_ifd);
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend(1u16.to_le_bytes());
+    entry(&mut tiff, 0x9003, 2, taken.len() as u32 + 1, date_offset);
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend(taken.as_bytes());
+    tiff.push(0);
+    if let Some(caption) = caption {
+        tiff.extend(caption.as_bytes());
+        tiff.push(0);
+    }
+
+    let jpeg = fs::read(path).await?;
+    let mut photo = jpeg[..2].to_vec(); // SOI
+    photo.extend([0xFF, 0xE1]);
+    photo.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+    photo.extend(b"Exif\0\0");
+    photo.extend(tiff);
+    photo.extend(&jpeg[2..]);
+    fs::write(path, photo).await?;
+    Ok(())
+}
+
+/// Creates a dummy JPEG image in `color` whose EXIF data embeds a JPEG preview in
+/// `preview_color`, like the thumbnails cameras and phones write.
+#[allow(dead_code)]
+pub async fn create_dummy_image_with_preview(
+    path: &Path,
+    color: Rgb<u8>,
+    preview_color: Rgb<u8>,
+) -> Result<()> {
+    create_dummy_image(path, color).await?;
+
+    let mut preview = Vec::new();
+    RgbImage::from_pixel(32, 32, preview_color)
+        .write_to(
+            &mut std::io::Cursor::new(&mut preview),
+            image::ImageFormat::Jpeg,
+        )
+        .map_err(Error::Image)?;
+
+    // Little-endian TIFF: an empty IFD0 linking to IFD1, which points at the preview
+    let ifd1 = 8 + 2 + 4;
+    let preview_offset = ifd1 + 2 + 2 * 12 + 4;
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    tiff.extend(0u16.to_le_bytes());
+    tiff.extend((ifd1 as u32).to_le_bytes());
+    tiff.extend(2u16.to_le_bytes());
+    for (tag, value) in [
+        (0x0201u16, preview_offset as u32),
+        (0x0202, preview.len() as u32),
+    ] {
+        tiff.extend(tag.to_le_bytes());
+        tiff.extend(4u16.to_le_bytes()); // LONG
+        tiff.extend(1u32.to_le_bytes());
+        tiff.extend(value.to_le_bytes());
+    }
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend(preview);
+
+    let jpeg = fs::read(path).await?;
+    let mut photo = jpeg[..2].to_vec(); // SOI
+    photo.extend([0xFF, 0xE1]);
+    photo.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+    photo.extend(b"Exif\0\0");
+    photo.extend(tiff);
+    photo.extend(&jpeg[2..]);
+    fs::write(path, photo).await?;
+    Ok(())
+}
+
 /// Checks if a ZIP file (CBZ or EPUB) exists and contains at least one entry.
 #[allow(dead_code)]
 pub async fn assert_valid_zip_file(path: &Path) {
@@ -135,3 +238,21 @@ pub async fn get_comic_info_xml(cbz_path: &Path) -> String {
     std::io::Read::read_to_string(&mut file, &mut content).unwrap();
     content
 }
+
+/// Reads a text entry from a ZIP file (CBZ or EPUB) and returns its content.
+#[allow(dead_code)]
+pub async fn get_zip_entry(path: &Path, entry_name: &str) -> String {
+    let file = fs::File::open(path).await.unwrap();
+    let file_std = file.into_std().await;
+    let mut archive = zip::ZipArchive::new(file_std).unwrap();
+    let mut file = archive.by_name(entry_name).unwrap();
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut file, &mut content).unwrap();
+    content
+}
+
+/// Reads the OPF package document from an EPUB file and returns its content.
+#[allow(dead_code)]
+pub async fn get_epub_opf(epub_path: &Path) -> String {
+    get_zip_entry(epub_path, "OEBPS/content.opf").await
+}
@@ -0,0 +1,221 @@
+//! Golden-file tests for generated metadata.
+//!
+//! Converts small sources with representative configurations and compares the
+//! ComicInfo.xml, OPF and navigation documents against the files in `tests/golden`,
+//! after replacing values that change on every run (package UUIDs, timestamps). Catches
+//! fields that silently go missing and escaping regressions.
+//!
+//! After an intended change to the output, regenerate the files with
+//! `HOZON_UPDATE_GOLDEN=1 cargo test --test golden` and review the diff.
+
+use chrono::{TimeZone, Utc};
+use hozon::error::Result;
+use hozon::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::time::timeout;
+
+mod common;
+use common::{
+    LONG_TEST_TIMEOUT, create_dummy_color_image, get_comic_info_xml, get_epub_opf, get_zip_entry,
+    setup_test_dirs,
+};
+
+/// Set to regenerate the golden files instead of comparing against them.
+const UPDATE_ENV: &str = "HOZON_UPDATE_GOLDEN";
+
+/// Metadata filling every field, with values that need escaping in XML.
+fn full_metadata() -> EbookMetadata {
+    EbookMetadata {
+        title: "Tom & Jerry's <Big> \"Adventure\"".to_string(),
+        series: Some("Tom & Jerry".to_string()),
+        authors: vec!["Ann O'Brien".to_string(), "Jo <Jr.>".to_string()],
+        publisher: Some("Small & Co.".to_string()),
+        description: Some("Cats > mice?\nNot always.".to_string()),
+        tags: vec!["comedy".to_string(), "cats & mice".to_string()],
+        language: "en".to_string(),
+        rights: Some("© 2024 Small & Co.".to_string()),
+        identifier: Some("legacy-id".to_string()),
+        identifiers: vec![
+            Identifier::new(IdentifierScheme::Isbn, "9781234567897"),
+            Identifier::new(IdentifierScheme::Anilist, "30013"),
+        ],
+        release_date: Some(Utc.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).unwrap()),
+        genre: Some("Comedy".to_string()),
+        web: Some("https://example.com/?a=1&b=2".to_string()),
+        custom_fields: HashMap::from([
+            ("translator".to_string(), "Kim & Lee".to_string()),
+            ("edition".to_string(), "First".to_string()),
+        ]),
+    }
+}
+
+/// Metadata with only the required fields and a fixed release date.
+fn minimal_metadata() -> EbookMetadata {
+    EbookMetadata {
+        release_date: Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+        ..EbookMetadata::default_with_title("Minimal".to_string())
+    }
+}
+
+/// Chapter names of the sources, the first of the full one needing escaping.
+const FULL_CHAPTERS: [&str; 2] = ["01 Cats & Dogs", "02 Finale"];
+const MINIMAL_CHAPTERS: [&str; 2] = ["Chapter 1", "Chapter 2"];
+
+/// Writes `chapters` with two pages each into `source`.
+async fn create_source(source: &Path, chapters: &[&str]) -> Result<()> {
+    for chapter in chapters {
+        for page in ["001.jpg", "002.jpg"] {
+            create_dummy_color_image(&source.join(chapter).join(page)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts a source of `chapters` with `metadata` and `format` into a single file and
+/// returns its path.
+async fn convert(
+    name: &str,
+    chapters: &[&str],
+    metadata: EbookMetadata,
+    format: FileFormat,
+) -> Result<(common::TestDirs, std::path::PathBuf)> {
+    let test_dirs = setup_test_dirs(name).await;
+    create_source(&test_dirs.source_dir, chapters).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(format)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = config.output_directory();
+    let mut outputs: Vec<_> = std::fs::read_dir(&output_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    assert_eq!(outputs.len(), 1, "Expected one output in {:?}", output_dir);
+    let output = outputs.pop().unwrap();
+    Ok((test_dirs, output))
+}
+
+/// Replaces the parts of generated metadata that differ between runs.
+fn normalize(content: &str) -> String {
+    let replacements = [
+        (
+            r"urn:uuid:[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+            "urn:uuid:<uuid>",
+        ),
+        (
+            r#"(<meta property="dcterms:modified">)[^<]*(</meta>)"#,
+            "${1}<timestamp>${2}",
+        ),
+        (
+            r#"(<meta name="generator" content=")[^"]*(")"#,
+            "${1}<generator>${2}",
+        ),
+    ];
+    // Trailing whitespace doesn't matter in XML and is easily lost when editing the files
+    let mut normalized: String = content
+        .lines()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect();
+    for (pattern, replacement) in replacements {
+        normalized = Regex::new(pattern)
+            .unwrap()
+            .replace_all(&normalized, replacement)
+            .into_owned();
+    }
+    normalized
+}
+
+/// Compares `actual` (normalized) against `tests/golden/<name>`, or overwrites the file
+/// when [`UPDATE_ENV`] is set.
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name);
+    let actual = normalize(actual);
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Can't read golden file {:?} ({}); create it with {}=1",
+            path, e, UPDATE_ENV
+        )
+    });
+    assert_eq!(
+        actual,
+        normalize(&expected),
+        "{} differs from its golden file; if the change is intended, rerun with {}=1",
+        name,
+        UPDATE_ENV
+    );
+}
+
+#[tokio::test]
+async fn test_golden_comic_info_full_metadata() -> Result<()> {
+    let (_dirs, cbz) = convert(
+        "golden_cbz_full",
+        &FULL_CHAPTERS,
+        full_metadata(),
+        FileFormat::Cbz,
+    )
+    .await?;
+    assert_golden("comic_info_full.xml", &get_comic_info_xml(&cbz).await);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_comic_info_minimal_metadata() -> Result<()> {
+    let (_dirs, cbz) = convert(
+        "golden_cbz_minimal",
+        &MINIMAL_CHAPTERS,
+        minimal_metadata(),
+        FileFormat::Cbz,
+    )
+    .await?;
+    assert_golden("comic_info_minimal.xml", &get_comic_info_xml(&cbz).await);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_epub_full_metadata() -> Result<()> {
+    let (_dirs, epub) = convert(
+        "golden_epub_full",
+        &FULL_CHAPTERS,
+        full_metadata(),
+        FileFormat::Epub,
+    )
+    .await?;
+    assert_golden("content_full.opf", &get_epub_opf(&epub).await);
+    assert_golden(
+        "nav_full.xhtml",
+        &get_zip_entry(&epub, "OEBPS/nav.xhtml").await,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_epub_minimal_metadata() -> Result<()> {
+    let (_dirs, epub) = convert(
+        "golden_epub_minimal",
+        &MINIMAL_CHAPTERS,
+        minimal_metadata(),
+        FileFormat::Epub,
+    )
+    .await?;
+    assert_golden("content_minimal.opf", &get_epub_opf(&epub).await);
+    Ok(())
+}
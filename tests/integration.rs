@@ -11,7 +11,8 @@ use tokio::time::timeout;
 mod common;
 use common::{
     LONG_TEST_TIMEOUT, assert_valid_zip_file, create_dummy_color_image,
-    create_dummy_grayscale_image, get_comic_info_xml, setup_test_dirs,
+    create_dummy_grayscale_image, create_dummy_image, create_dummy_image_with_preview,
+    create_dummy_photo, get_comic_info_xml, get_epub_opf, get_zip_entry, setup_test_dirs,
 };
 
 #[tokio::test]
@@ -558,3 +559,5033 @@ async fn test_error_on_empty_structured_data() -> Result<()> {
     assert!(error_msg.contains("No volumes found for generation"));
     Ok(())
 }
+
+#[tokio::test]
+async fn test_password_protected_cbz() -> Result<()> {
+    let test_dirs = setup_test_dirs("password_cbz").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title(
+            "Secret Comic".to_string(),
+        ))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .output_password("hunter2".to_string())
+        .build()?;
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs
+        .target_dir
+        .join("Secret Comic")
+        .join("Secret Comic.cbz");
+    let file = std::fs::File::open(&cbz_path)?;
+    let mut archive = zip::ZipArchive::new(file).unwrap();
+
+    // Entries cannot be read without the password
+    assert!(archive.by_name("ComicInfo.xml").is_err());
+
+    let mut entry = archive
+        .by_name_decrypt("ComicInfo.xml", b"hunter2")
+        .unwrap();
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut entry, &mut content)?;
+    assert!(content.contains("<Title>Secret Comic</Title>"));
+
+    // Extracted with the password for files being read
+    let extract_config = HozonConfig::builder()
+        .target_path(test_dirs.target_dir.join("extracted"))
+        .input_password("hunter2".to_string())
+        .build()?;
+    let extracted = extract_config.extract_to_directory(&cbz_path).await?;
+    assert_eq!(extracted.metadata.title, "Secret Comic");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_error_on_password_with_epub() -> Result<()> {
+    let test_dirs = setup_test_dirs("password_epub").await;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Secret Book".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .output_password("hunter2".to_string())
+        .build()?;
+
+    let result = config.preflight_check(HozonExecutionMode::FromSource);
+    assert!(matches!(
+        result.unwrap_err(),
+        hozon::error::Error::Unsupported(_)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_fingerprint_embedded_cbz() -> Result<()> {
+    let test_dirs = setup_test_dirs("fingerprint_cbz").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title(
+            "Fingerprinted".to_string(),
+        ))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .embed_source_fingerprint(true)
+        .build()?;
+
+    let collected = config.analyze_source().await?;
+    let expected =
+        SourceFingerprint::compute(&config, &collected.chapters_with_pages, None).await?;
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs
+        .target_dir
+        .join("Fingerprinted")
+        .join("Fingerprinted.cbz");
+    let comic_info = get_comic_info_xml(&cbz_path).await;
+    let line = comic_info
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Fingerprint: "))
+        .expect("Fingerprint line missing from Notes");
+    let embedded = SourceFingerprint::parse(line).expect("Fingerprint should parse");
+
+    assert_eq!(embedded.hozon_version, env!("CARGO_PKG_VERSION"));
+    assert!(embedded.is_up_to_date_with(&expected));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_check_outputs_detects_stale_missing_and_orphaned() -> Result<()> {
+    let test_dirs = setup_test_dirs("check_outputs").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Checked".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .embed_source_fingerprint(true)
+        .build()?;
+
+    let report = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(report.outputs.len(), 1);
+    assert_eq!(report.outputs[0].state, OutputState::Missing);
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let report = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(report.outputs[0].state, OutputState::UpToDate);
+    assert!(report.is_up_to_date());
+
+    // Changing a page makes the output stale
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("003.jpg")).await?;
+    let orphan = config.output_directory().join("Old Volume.cbz");
+    tokio::fs::write(&orphan, b"not a real archive").await?;
+
+    let report = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(report.outputs[0].state, OutputState::Stale);
+    assert_eq!(report.orphaned, vec![orphan]);
+    assert!(!report.is_up_to_date());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pipelined_cbz_preserves_page_order() -> Result<()> {
+    let test_dirs = setup_test_dirs("pipelined_cbz_order").await;
+
+    // More pages than the prefetch depth, split over two chapters
+    for page in 1..=12 {
+        create_dummy_color_image(
+            &test_dirs
+                .source_dir
+                .join("Chapter 1")
+                .join(format!("{:03}.jpg", page)),
+        )
+        .await?;
+    }
+    for page in 1..=6 {
+        create_dummy_color_image(
+            &test_dirs
+                .source_dir
+                .join("Chapter 2")
+                .join(format!("{:03}.png", page)),
+        )
+        .await?;
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Pipelined".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .build()?;
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs.target_dir.join("Pipelined").join("Pipelined.cbz");
+    let file = std::fs::File::open(&cbz_path)?;
+    let archive = zip::ZipArchive::new(file)?;
+    let page_names: Vec<&str> = archive
+        .file_names()
+        .filter(|name| name.starts_with("page_"))
+        .collect();
+
+    let expected: Vec<String> = (1..=18)
+        .map(|i| format!("page_{:03}.{}", i, if i <= 12 { "jpg" } else { "png" }))
+        .collect();
+    assert_eq!(page_names, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batch_conversions_share_runtime_limits() -> Result<()> {
+    let test_dirs = setup_test_dirs("shared_runtime_limits").await;
+    let limits = RuntimeLimits::new(1, 1)?;
+
+    let mut configs = Vec::new();
+    for series in ["Series A", "Series B"] {
+        let source = test_dirs.source_dir.join(series);
+        for chapter in ["Chapter 1", "Chapter 2"] {
+            create_dummy_color_image(&source.join(chapter).join("001.jpg")).await?;
+            create_dummy_color_image(&source.join(chapter).join("002.jpg")).await?;
+        }
+
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title(series.to_string()))
+            .source_path(source)
+            .target_path(test_dirs.target_dir.clone())
+            .volume_sizes_override(vec![1, 1])
+            .runtime_limits(limits.clone())
+            .build()?;
+        configs.push(config);
+    }
+    let conversions = configs
+        .iter()
+        .map(|config| config.convert_from_source(CoverOptions::None));
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        futures::future::try_join_all(conversions),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    for series in ["Series A", "Series B"] {
+        for volume in 1..=2 {
+            let path = test_dirs
+                .target_dir
+                .join(series)
+                .join(format!("{} - Volume {}.cbz", series, volume));
+            assert_valid_zip_file(&path).await;
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_image_processing_with_disk_spill() -> Result<()> {
+    let test_dirs = setup_test_dirs("image_processing_spill").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let spill_base = test_dirs.target_dir.join("spill");
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Processed".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .image_processing(ImageProcessing {
+            max_width: Some(50),
+            output_format: Some(ProcessedImageFormat::Png),
+            spill_to_disk: true,
+            spill_directory: Some(spill_base.clone()),
+            ..Default::default()
+        })
+        .build()?;
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs.target_dir.join("Processed").join("Processed.cbz");
+    let file = std::fs::File::open(&cbz_path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    for name in ["page_001.png", "page_002.png"] {
+        let mut entry = archive.by_name(name)?;
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut bytes)?;
+        let page = image::load_from_memory(&bytes)?;
+        assert_eq!((page.width(), page.height()), (50, 50));
+    }
+
+    // The spill directory is cleaned up once generation finishes
+    let leftovers = std::fs::read_dir(&spill_base)?.count();
+    assert_eq!(leftovers, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_error_on_invalid_image_processing() -> Result<()> {
+    let test_dirs = setup_test_dirs("invalid_image_processing").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Invalid".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .image_processing(ImageProcessing {
+            max_height: Some(0),
+            ..Default::default()
+        })
+        .build()?;
+
+    let result = config.convert_from_source(CoverOptions::None).await;
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_output_directory_lock() -> Result<()> {
+    let test_dirs = setup_test_dirs("output_directory_lock").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Locked".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .lock_output_directory(true)
+        .build()?;
+
+    // A live lock held by this very process blocks the conversion
+    let output_dir = config.output_directory();
+    tokio::fs::create_dir_all(&output_dir).await?;
+    let lock_path = output_dir.join(hozon::lock::LOCK_FILE_NAME);
+    let created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    tokio::fs::write(
+        &lock_path,
+        format!(
+            "pid={}\nhost=elsewhere\ncreated={}\n",
+            std::process::id(),
+            created
+        ),
+    )
+    .await?;
+
+    let result = config.convert_from_source(CoverOptions::None).await;
+    assert!(matches!(result, Err(hozon::error::Error::TargetLocked(..))));
+
+    // An ancient lock is considered stale and taken over
+    tokio::fs::write(&lock_path, "pid=1\nhost=elsewhere\ncreated=0\n").await?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    assert_valid_zip_file(&output_dir.join("Locked.cbz")).await;
+    assert!(
+        !lock_path.exists(),
+        "Lock file should be removed after conversion"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_comic_info_notes_formats() -> Result<()> {
+    let test_dirs = setup_test_dirs("comic_info_notes_formats").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let mut metadata = EbookMetadata::default_with_title("Notes".to_string());
+    metadata.tags = vec!["Action".to_string(), "Drama".to_string()];
+    metadata
+        .custom_fields
+        .insert("Scanlator".to_string(), "Team <A>".to_string());
+
+    let notes_of = |format: NotesFormat, name: &str| {
+        let metadata = metadata.clone();
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            std::fs::create_dir_all(&target)?;
+            let config = HozonConfig::builder()
+                .metadata(metadata)
+                .source_path(source)
+                .target_path(target.clone())
+                .create_output_directory(false)
+                .comic_info_notes(format)
+                .embed_source_fingerprint(true)
+                .build()?;
+            timeout(
+                LONG_TEST_TIMEOUT,
+                config.convert_from_source(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")?;
+            let xml = get_comic_info_xml(&target.join("Notes.cbz")).await;
+            let notes = xml
+                .split("<Notes>")
+                .nth(1)
+                .and_then(|rest| rest.split("</Notes>").next())
+                .unwrap_or_default()
+                .to_string();
+            let status = config.check_outputs(&CoverOptions::None).await?;
+            Result::Ok((notes, status.outputs[0].state))
+        }
+    };
+
+    let (notes, state) = notes_of(NotesFormat::Json, "json").await?;
+    let json: serde_json::Value = serde_json::from_str(
+        &notes
+            .replace("&quot;", "\"")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+    .expect("Notes should be valid JSON");
+    assert_eq!(json["tags"][1], "Drama");
+    assert_eq!(json["custom_fields"]["Scanlator"], "Team <A>");
+    assert_eq!(json["chapters"][0], "Chapter 1");
+    assert_eq!(state, OutputState::UpToDate);
+
+    let (notes, state) = notes_of(
+        NotesFormat::Template("tags={tags} | {custom_fields} | fp={fingerprint}".to_string()),
+        "template",
+    )
+    .await?;
+    assert!(notes.starts_with("tags=Action, Drama | Scanlator: Team &lt;A&gt; | fp=v1;"));
+    assert_eq!(state, OutputState::UpToDate);
+
+    let (notes, state) = notes_of(NotesFormat::Omit, "omit").await?;
+    assert!(notes.is_empty());
+    assert_eq!(state, OutputState::Unverifiable);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_multiple_identifiers_epub_and_cbz() -> Result<()> {
+    let test_dirs = setup_test_dirs("multiple_identifiers").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let metadata = EbookMetadata {
+        identifier: Some("legacy-id".to_string()),
+        identifiers: vec![
+            Identifier::new(IdentifierScheme::Isbn, "9781234567897"),
+            Identifier::new(IdentifierScheme::Anilist, "30013"),
+            Identifier::new(IdentifierScheme::MangaUpdates, "abc&def"),
+        ],
+        ..EbookMetadata::default_with_title("Identified".to_string())
+    };
+
+    for format in [FileFormat::Epub, FileFormat::Cbz] {
+        let config = HozonConfig::builder()
+            .metadata(metadata.clone())
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(format)
+            .embed_source_fingerprint(true)
+            .build()?;
+        timeout(
+            LONG_TEST_TIMEOUT,
+            config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let status = config.check_outputs(&CoverOptions::None).await?;
+        assert_eq!(status.outputs[0].state, OutputState::UpToDate);
+    }
+
+    let output_dir = test_dirs.target_dir.join("Identified");
+    let opf = get_epub_opf(&output_dir.join("Identified.epub")).await;
+    assert!(opf.contains(">legacy-id</dc:identifier>"));
+    assert!(opf.contains(">urn:isbn:9781234567897</dc:identifier>"));
+    assert!(opf.contains("property=\"identifier-type\" scheme=\"onix:codelist5\">15</meta>"));
+    assert!(opf.contains(">anilist:30013</dc:identifier>"));
+    assert!(opf.contains(">mangaupdates:abc&amp;def</dc:identifier>"));
+    // The generated package UUID plus the four given identifiers
+    assert_eq!(opf.matches("<dc:identifier").count(), 5);
+
+    let xml = get_comic_info_xml(&output_dir.join("Identified.cbz")).await;
+    assert!(xml.contains("<GTIN>9781234567897</GTIN>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_accessibility_metadata() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_accessibility").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Accessible".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let opf = get_epub_opf(
+        &test_dirs
+            .target_dir
+            .join("Accessible")
+            .join("Accessible.epub"),
+    )
+    .await;
+    for property in [
+        "accessMode",
+        "accessModeSufficient",
+        "accessibilityFeature",
+        "accessibilityHazard",
+        "accessibilitySummary",
+    ] {
+        assert!(
+            opf.contains(&format!("<meta property=\"schema:{}\">", property)),
+            "Missing schema:{} in OPF",
+            property
+        );
+    }
+    assert!(opf.contains("<meta property=\"schema:accessModeSufficient\">visual</meta>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_alt_text_from_sidecars() -> Result<()> {
+    let test_dirs = setup_test_dirs("alt_text_sidecars").await;
+
+    let chapter_1 = test_dirs.source_dir.join("Chapter 1");
+    let chapter_2 = test_dirs.source_dir.join("Chapter 2");
+    create_dummy_color_image(&chapter_1.join("001.jpg")).await?;
+    create_dummy_color_image(&chapter_1.join("002.jpg")).await?;
+    create_dummy_color_image(&chapter_2.join("001.jpg")).await?;
+    tokio::fs::write(
+        chapter_1.join("alt.json"),
+        r#"{"001.jpg": "A <tall> tower", "002.jpg": "Two kids on a roof"}"#,
+    )
+    .await?;
+    tokio::fs::write(
+        chapter_2.join("alt.json"),
+        r#"{"001.jpg": "An empty street"}"#,
+    )
+    .await?;
+
+    let read_page = |epub: std::path::PathBuf, chapter: usize| async move {
+        get_zip_entry(
+            &epub,
+            &format!("OEBPS/chapters/chapter_{:03}/page_001.xhtml", chapter),
+        )
+        .await
+    };
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Described".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .alt_text(AltTextSource::ChapterSidecar("alt.json".to_string()))
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let epub = test_dirs
+        .target_dir
+        .join("Described")
+        .join("Described.epub");
+    assert!(
+        read_page(epub.clone(), 1)
+            .await
+            .contains("alt=\"A &lt;tall&gt; tower\"")
+    );
+    assert!(
+        read_page(epub.clone(), 2)
+            .await
+            .contains("alt=\"An empty street\"")
+    );
+    let opf = get_epub_opf(&epub).await;
+    assert!(opf.contains("<meta property=\"schema:accessibilityFeature\">alternativeText</meta>"));
+
+    // A single CSV file addressing a chapter as a whole and one page of another
+    let csv = test_dirs.test_dir.join("alt.csv");
+    tokio::fs::write(
+        &csv,
+        "page,alt\nChapter 1,\"Rooftops, at night\"\nChapter 2/001.jpg,A street\n",
+    )
+    .await?;
+    let target = test_dirs.target_dir.join("csv");
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Described".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(target.clone())
+        .output_format(FileFormat::Epub)
+        .alt_text(AltTextSource::File(csv))
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let epub = target.join("Described").join("Described.epub");
+    assert!(
+        read_page(epub.clone(), 1)
+            .await
+            .contains("alt=\"Rooftops, at night\"")
+    );
+    assert!(
+        read_page(epub.clone(), 2)
+            .await
+            .contains("alt=\"A street\"")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_color_profile_policies() -> Result<()> {
+    use image::{ImageDecoder, ImageEncoder};
+
+    let test_dirs = setup_test_dirs("color_profile_policies").await;
+
+    // A page carrying an embedded ICC profile (the encoder does not validate it)
+    let profile = b"hozon-test-icc-profile".to_vec();
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    std::fs::create_dir_all(&chapter)?;
+    let mut bytes = Vec::new();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new(&mut bytes);
+    encoder.set_icc_profile(profile.clone()).unwrap();
+    image::DynamicImage::new_rgb8(100, 100).write_with_encoder(encoder)?;
+    std::fs::write(chapter.join("001.jpg"), &bytes)?;
+
+    let page_profile = |policy: ColorProfilePolicy, max_width: Option<u32>, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Profiled".to_string()))
+                .source_path(source)
+                .target_path(target.clone())
+                .image_processing(ImageProcessing {
+                    max_width,
+                    color_profile: policy,
+                    ..Default::default()
+                })
+                .build()?;
+            timeout(
+                LONG_TEST_TIMEOUT,
+                config.convert_from_source(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")?;
+
+            let cbz = std::fs::File::open(target.join("Profiled").join("Profiled.cbz"))?;
+            let mut archive = zip::ZipArchive::new(cbz)?;
+            let mut entry = archive.by_name("page_001.jpg")?;
+            let mut page = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut page)?;
+            let mut decoder = image::ImageReader::new(std::io::Cursor::new(page))
+                .with_guessed_format()?
+                .into_decoder()?;
+            Result::Ok(decoder.icc_profile()?)
+        }
+    };
+
+    // Re-encoded because of the resize, but the profile is carried over
+    let kept = page_profile(ColorProfilePolicy::Preserve, Some(50), "preserve").await?;
+    assert_eq!(kept, Some(profile));
+
+    // Re-encoded only to drop the profile
+    let stripped = page_profile(ColorProfilePolicy::Strip, None, "strip").await?;
+    assert_eq!(stripped, None);
+
+    // An unparsable profile leaves the pixels alone but is still removed
+    let converted = page_profile(ColorProfilePolicy::ConvertToSrgb, None, "convert").await?;
+    assert_eq!(converted, None);
+    Ok(())
+}
+
+/// Builds a minimal single-frame APNG by adding animation chunks to a plain PNG.
+fn create_apng(width: u32, height: u32) -> Vec<u8> {
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xFFFF_FFFFu32;
+        for byte in data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        let mut body = kind.to_vec();
+        body.extend_from_slice(data);
+        chunk.extend_from_slice(&body);
+        chunk.extend_from_slice(&crc32(&body).to_be_bytes());
+        chunk
+    }
+
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgb8(width, height)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let actl = [1u32.to_be_bytes(), 0u32.to_be_bytes()].concat(); // 1 frame, loop forever
+    let mut fctl = 0u32.to_be_bytes().to_vec(); // Sequence number
+    for value in [width, height, 0, 0] {
+        fctl.extend_from_slice(&value.to_be_bytes());
+    }
+    fctl.extend_from_slice(&[0, 1, 0, 100, 0, 0]); // Delay 1/100s, no dispose/blend
+
+    // Signature (8) + IHDR chunk (25) come first
+    let mut apng = png[..33].to_vec();
+    apng.extend(chunk(b"acTL", &actl));
+    apng.extend(chunk(b"fcTL", &fctl));
+    apng.extend_from_slice(&png[33..]);
+    apng
+}
+
+#[tokio::test]
+async fn test_animated_image_policies() -> Result<()> {
+    let test_dirs = setup_test_dirs("animated_image_policies").await;
+
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    std::fs::create_dir_all(&chapter)?;
+    let apng = create_apng(40, 60);
+    std::fs::write(chapter.join("001.png"), &apng)?;
+
+    let config_for = |format: FileFormat, policy: AnimatedImagePolicy, name: &str| {
+        HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Animated".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.join(name))
+            .output_format(format)
+            .animated_images(policy)
+            .build()
+    };
+
+    // Default: the animation is replaced by its first frame
+    let config = config_for(FileFormat::Cbz, AnimatedImagePolicy::default(), "first")?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let cbz = std::fs::File::open(
+        test_dirs
+            .target_dir
+            .join("first")
+            .join("Animated")
+            .join("Animated.cbz"),
+    )?;
+    let mut archive = zip::ZipArchive::new(cbz)?;
+    let mut entry = archive.by_name("page_001.png")?;
+    let mut page = Vec::new();
+    std::io::Read::read_to_end(&mut entry, &mut page)?;
+    assert!(!page.windows(4).any(|w| w == b"acTL"));
+    let frame = image::load_from_memory(&page)?;
+    assert_eq!((frame.width(), frame.height()), (40, 60));
+
+    // Reject fails the conversion
+    let config = config_for(FileFormat::Cbz, AnimatedImagePolicy::Reject, "reject")?;
+    let result = timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out");
+    assert!(matches!(result, Err(hozon::error::Error::Unsupported(_))));
+
+    // Pass-through is refused for CBZ, but keeps the animation in EPUB
+    let config = config_for(FileFormat::Cbz, AnimatedImagePolicy::PassThrough, "pass")?;
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+
+    let config = config_for(FileFormat::Epub, AnimatedImagePolicy::PassThrough, "pass")?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let epub = std::fs::File::open(
+        test_dirs
+            .target_dir
+            .join("pass")
+            .join("Animated")
+            .join("Animated.epub"),
+    )?;
+    let mut archive = zip::ZipArchive::new(epub)?;
+    let mut entry = archive.by_name("OEBPS/chapters/chapter_001/page_001.png")?;
+    let mut page = Vec::new();
+    std::io::Read::read_to_end(&mut entry, &mut page)?;
+    assert_eq!(page, apng);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_flatten_transparent_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("flatten_transparent").await;
+
+    // Left half opaque red, right half fully transparent
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    std::fs::create_dir_all(&chapter)?;
+    let page = image::RgbaImage::from_fn(20, 10, |x, _| {
+        if x < 10 {
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            image::Rgba([0, 0, 0, 0])
+        }
+    });
+    page.save(chapter.join("001.png"))?;
+    create_dummy_color_image(&chapter.join("002.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Flattened".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .image_processing(ImageProcessing {
+            flatten_alpha: Some([255, 255, 255]),
+            ..Default::default()
+        })
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz = std::fs::File::open(test_dirs.target_dir.join("Flattened").join("Flattened.cbz"))?;
+    let mut archive = zip::ZipArchive::new(cbz)?;
+    let mut entry = archive.by_name("page_001.png")?;
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut entry, &mut bytes)?;
+    let flattened = image::load_from_memory(&bytes)?;
+    assert!(!flattened.color().has_alpha());
+    let flattened = flattened.to_rgb8();
+    assert_eq!(flattened.get_pixel(0, 0).0, [255, 0, 0]);
+    assert_eq!(flattened.get_pixel(15, 5).0, [255, 255, 255]);
+
+    // Opaque pages are left untouched
+    let original = std::fs::read(chapter.join("002.jpg"))?;
+    drop(entry);
+    let mut entry = archive.by_name("page_002.jpg")?;
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut entry, &mut bytes)?;
+    assert_eq!(bytes, original);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auto_levels_enhances_faded_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("auto_levels").await;
+
+    // A faded gradient using only the gray levels 100-150
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    std::fs::create_dir_all(&chapter)?;
+    let faded = image::RgbImage::from_fn(51, 10, |x, _| image::Rgb([100 + x as u8; 3]));
+    faded.save(chapter.join("001.png"))?;
+
+    let gray_range = |strength: f32, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Levels".to_string()))
+                .source_path(source)
+                .target_path(target.clone())
+                .image_processing(ImageProcessing {
+                    auto_levels: Some(strength),
+                    ..Default::default()
+                })
+                .build()?;
+            timeout(
+                LONG_TEST_TIMEOUT,
+                config.convert_from_source(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")?;
+
+            let cbz = std::fs::File::open(target.join("Levels").join("Levels.cbz"))?;
+            let mut archive = zip::ZipArchive::new(cbz)?;
+            let mut entry = archive.by_name("page_001.png")?;
+            let mut bytes = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut bytes)?;
+            let page = image::load_from_memory(&bytes)?.to_luma8();
+            let min = page.pixels().map(|p| p[0]).min().unwrap();
+            let max = page.pixels().map(|p| p[0]).max().unwrap();
+            Result::Ok((min, max))
+        }
+    };
+
+    assert_eq!(gray_range(1.0, "full").await?, (0, 255));
+    let (min, max) = gray_range(0.5, "half").await?;
+    assert!((45..=55).contains(&min) && (195..=205).contains(&max));
+
+    // Grayscale pages stay grayscale
+    let faded = image::GrayImage::from_fn(51, 10, |x, _| image::Luma([100 + x as u8]));
+    faded.save(chapter.join("001.png"))?;
+    assert_eq!(gray_range(1.0, "gray").await?, (0, 255));
+    let cbz = std::fs::File::open(test_dirs.target_dir.join("gray/Levels/Levels.cbz"))?;
+    let mut archive = zip::ZipArchive::new(cbz)?;
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("page_001.png")?, &mut bytes)?;
+    let page = image::load_from_memory(&bytes)?;
+    assert_eq!(page.color(), image::ColorType::L8);
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Levels".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .image_processing(ImageProcessing {
+            auto_levels: Some(1.5),
+            ..Default::default()
+        })
+        .build()?;
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_deskew_straightens_tilted_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("deskew").await;
+
+    // Black stripes tilted by 3 degrees on a white page
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    std::fs::create_dir_all(&chapter)?;
+    let slope = 3.0f32.to_radians().tan();
+    let tilted = image::RgbImage::from_fn(400, 300, |x, y| {
+        let line = (y as f32 - x as f32 * slope).rem_euclid(30.0);
+        if line < 4.0 {
+            image::Rgb([0, 0, 0])
+        } else {
+            image::Rgb([255, 255, 255])
+        }
+    });
+    tilted.save(chapter.join("001.png"))?;
+
+    // Highest share of dark pixels in any row of the page's central area
+    let straightest_row = |page: &image::GrayImage| {
+        (100..200)
+            .map(|y| {
+                (100..300)
+                    .filter(|&x| page.get_pixel(x, y)[0] < 128)
+                    .count()
+            })
+            .max()
+            .unwrap_or_default() as f32
+            / 200.0
+    };
+    let tilted = image::DynamicImage::ImageRgb8(tilted).to_luma8();
+    assert!(straightest_row(&tilted) < 0.5);
+    // The same page in grayscale
+    let gray_chapter = test_dirs.source_dir.join("Chapter 2");
+    std::fs::create_dir_all(&gray_chapter)?;
+    tilted.save(gray_chapter.join("001.png"))?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Deskewed".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .image_processing(ImageProcessing {
+            deskew: true,
+            ..Default::default()
+        })
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz = std::fs::File::open(test_dirs.target_dir.join("Deskewed").join("Deskewed.cbz"))?;
+    let mut archive = zip::ZipArchive::new(cbz)?;
+    let mut page = |name: &str| {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name(name)?, &mut bytes)?;
+        Result::Ok(image::load_from_memory(&bytes)?)
+    };
+    let color = page("page_001.png")?;
+    assert_eq!((color.width(), color.height()), (400, 300));
+    assert!(straightest_row(&color.to_luma8()) > 0.95);
+    // Grayscale pages stay grayscale
+    let gray = page("page_002.png")?;
+    assert_eq!(gray.color(), image::ColorType::L8);
+    assert!(straightest_row(&gray.to_luma8()) > 0.95);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_split_by_file_size() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_split_by_size").await;
+
+    let mut chapter_sizes = Vec::new();
+    for chapter in ["Chapter 1", "Chapter 2", "Chapter 3"] {
+        let mut size = 0;
+        for page in ["001.jpg", "002.jpg"] {
+            let path = test_dirs.source_dir.join(chapter).join(page);
+            create_dummy_color_image(&path).await?;
+            size += std::fs::metadata(&path)?.len();
+        }
+        chapter_sizes.push(size);
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Split".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .epub_max_file_size(chapter_sizes[0] + chapter_sizes[1])
+        .embed_source_fingerprint(true)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Split");
+    let part_1 = output_dir.join("Split - Part 1.epub");
+    let part_2 = output_dir.join("Split - Part 2.epub");
+    assert_valid_zip_file(&part_1).await;
+    assert_valid_zip_file(&part_2).await;
+    assert!(!output_dir.join("Split.epub").exists());
+
+    // Chapters 1 and 2 fit into the first part, chapter 3 goes into the second
+    let opf = get_epub_opf(&part_1).await;
+    assert!(opf.contains("chapter_002/page_002.xhtml"));
+    assert!(opf.contains("Split Vol 1 Part 1"));
+    let opf = get_epub_opf(&part_2).await;
+    assert!(!opf.contains("chapter_002/"));
+
+    let status = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(status.outputs.len(), 2);
+    assert_eq!(status.outputs[1].part_number, Some(2));
+    assert_eq!(status.outputs[1].path, part_2);
+    assert!(status.is_up_to_date());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_toc_options() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_toc_options").await;
+
+    for chapter in ["01 Intro", "02 Finale"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("002.jpg")).await?;
+    }
+
+    let nav_of = |toc: TocOptions, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Toc".to_string()))
+                .source_path(source)
+                .target_path(target.clone())
+                .output_format(FileFormat::Epub)
+                .toc(toc)
+                .build()?;
+            timeout(
+                LONG_TEST_TIMEOUT,
+                config.convert_from_source(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")?;
+            let epub = target.join("Toc").join("Toc.epub");
+            Result::Ok((
+                get_zip_entry(&epub, "OEBPS/nav.xhtml").await,
+                get_epub_opf(&epub).await,
+            ))
+        }
+    };
+
+    // Default: one entry per page
+    let (nav, _) = nav_of(TocOptions::default(), "pages").await?;
+    assert!(nav.contains("01 Intro - Page 2"));
+
+    let (nav, _) = nav_of(
+        TocOptions {
+            style: TocStyle::Chapters,
+            chapter_label: Some("Chapter {number}: {title}".to_string()),
+        },
+        "chapters",
+    )
+    .await?;
+    assert!(nav.contains("Chapter 1: 01 Intro"));
+    assert!(nav.contains("Chapter 2: 02 Finale"));
+    assert!(!nav.contains("Page 2"));
+
+    let (nav, opf) = nav_of(
+        TocOptions {
+            style: TocStyle::None,
+            ..Default::default()
+        },
+        "none",
+    )
+    .await?;
+    assert!(!nav.contains("Intro"));
+    assert!(!opf.contains("tableOfContents"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cover_sidecars() -> Result<()> {
+    let test_dirs = setup_test_dirs("cover_sidecars").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Sidecars".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .cover_sidecars(CoverSidecars {
+            thumbnail_size: Some(40),
+            ..Default::default()
+        })
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Sidecars");
+    let cover = image::open(output_dir.join("Sidecars.cover.jpg"))?;
+    assert_eq!((cover.width(), cover.height()), (100, 100));
+    let thumbnail = image::open(output_dir.join("Sidecars.cover_thumb.jpg"))?;
+    assert_eq!((thumbnail.width(), thumbnail.height()), (40, 40));
+
+    // Sidecars are not mistaken for orphaned outputs
+    let status = config.check_outputs(&CoverOptions::None).await?;
+    assert!(status.orphaned.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_series_manifest() -> Result<()> {
+    let test_dirs = setup_test_dirs("series_manifest").await;
+
+    for chapter in 1..=2 {
+        create_dummy_color_image(
+            &test_dirs
+                .source_dir
+                .join(format!("Chapter {}", chapter))
+                .join("001.jpg"),
+        )
+        .await?;
+    }
+
+    let metadata = EbookMetadata {
+        identifiers: vec![Identifier::new(IdentifierScheme::Isbn, "9781234567897")],
+        authors: vec!["Manifest Author".to_string()],
+        ..EbookMetadata::default_with_title("Manifest Series".to_string())
+    };
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![1, 1])
+        .cover_sidecars(CoverSidecars::default())
+        .series_manifest(true)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Manifest Series");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&tokio::fs::read_to_string(output_dir.join("series.json")).await?)
+            .expect("Manifest is valid JSON");
+
+    assert_eq!(manifest["metadata"]["title"], "Manifest Series");
+    assert_eq!(manifest["metadata"]["numberOfItems"], 2);
+    let publications = manifest["publications"].as_array().unwrap();
+    assert_eq!(publications.len(), 2);
+
+    let second = &publications[1];
+    assert_eq!(second["metadata"]["title"], "Manifest Series - Volume 2");
+    assert_eq!(second["metadata"]["belongsTo"]["series"]["position"], 2);
+    assert_eq!(second["metadata"]["identifier"], "urn:isbn:9781234567897");
+    assert_eq!(second["metadata"]["author"][0], "Manifest Author");
+    assert_eq!(second["metadata"]["numberOfPages"], 1);
+    assert_eq!(
+        second["links"][0]["href"],
+        "Manifest%20Series%20-%20Volume%202.cbz"
+    );
+    assert_eq!(second["links"][0]["type"], "application/vnd.comicbook+zip");
+    assert_eq!(
+        second["images"][0]["href"],
+        "Manifest%20Series%20-%20Volume%202.cover.jpg"
+    );
+    assert_eq!(
+        second["images"][1]["rel"],
+        "http://opds-spec.org/image/thumbnail"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_result_bundle() -> Result<()> {
+    let test_dirs = setup_test_dirs("result_bundle").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title(
+            "Report Comic".to_string(),
+        ))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![1, 1])
+        .result_bundle(true)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Report Comic");
+    let read_report = || async {
+        let content = tokio::fs::read_to_string(output_dir.join("hozon-report.json")).await?;
+        Result::Ok(serde_json::from_str::<serde_json::Value>(&content).expect("Valid JSON"))
+    };
+
+    let report = read_report().await?;
+    assert_eq!(report["status"], "succeeded");
+    assert_eq!(report["mode"], "FromSource");
+    assert!(report["analysis"]["findings"].is_array());
+    assert_eq!(report["structure"]["total_volumes_created"], 2);
+    let outputs = report["conversion"]["outputs"].as_array().unwrap();
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0]["page_count"], 2);
+    assert_eq!(outputs[0]["pages"][1]["volume_page"], 2);
+    assert!(outputs[0]["size_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(report["conversion"]["total_pages"], 3);
+    assert!(report["timings"]["generation_ms"].is_u64());
+
+    // Failed conversions are reported as well
+    let result = config
+        .convert_from_structured_data(
+            vec![vec![vec![test_dirs.source_dir.join("missing.jpg")]]],
+            CoverOptions::None,
+        )
+        .await;
+    assert!(result.is_err());
+    let report = read_report().await?;
+    assert_eq!(report["status"], "failed");
+    assert!(report["error"].is_string());
+    assert!(report["analysis"].is_null());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub2_output() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub2_output").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let metadata = EbookMetadata {
+        identifiers: vec![Identifier::new(IdentifierScheme::Isbn, "9781234567897")],
+        ..EbookMetadata::default_with_title("Legacy Book".to_string())
+    };
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .epub_version(EpubVersion::V2)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let epub = test_dirs
+        .target_dir
+        .join("Legacy Book")
+        .join("Legacy Book.epub");
+    let opf = get_epub_opf(&epub).await;
+    assert!(opf.contains("<package version=\"2.0\""));
+    assert!(!opf.contains("property="), "EPUB 3 properties in {}", opf);
+    assert!(opf.contains("opf:scheme=\"ISBN\">9781234567897</dc:identifier>"));
+    assert!(opf.contains("<meta name=\"schema:accessMode\" content=\"visual\"/>"));
+    assert!(
+        get_zip_entry(&epub, "OEBPS/toc.ncx")
+            .await
+            .contains("<navMap>")
+    );
+
+    let page = get_zip_entry(&epub, "OEBPS/chapters/chapter_001/page_001.xhtml").await;
+    assert!(page.contains("XHTML 1.1"));
+    assert!(!page.contains("xmlns:epub"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_strict_epub_with_full_metadata() -> Result<()> {
+    let test_dirs = setup_test_dirs("strict_epub").await;
+
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+
+    let mut custom_fields = HashMap::new();
+    custom_fields.insert("source".to_string(), "Scan \"A\" & B".to_string());
+    let metadata = EbookMetadata {
+        series: Some("Strict Series".to_string()),
+        authors: vec!["First Author".to_string(), "Second Author".to_string()],
+        publisher: Some("Publisher & Co".to_string()),
+        rights: Some("All rights reserved".to_string()),
+        description: Some("A <strict> book".to_string()),
+        tags: vec!["Action".to_string()],
+        release_date: Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()),
+        identifiers: vec![Identifier::new(IdentifierScheme::Isbn, "9781234567897")],
+        custom_fields,
+        ..EbookMetadata::default_with_title("Strict Book".to_string())
+    };
+
+    for version in [EpubVersion::V3, EpubVersion::V2] {
+        let config = HozonConfig::builder()
+            .metadata(metadata.clone())
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.join(format!("{:?}", version)))
+            .output_format(FileFormat::Epub)
+            .epub_version(version)
+            .strict_epub(true)
+            .build()?;
+        timeout(
+            LONG_TEST_TIMEOUT,
+            config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let epub = test_dirs
+            .target_dir
+            .join(format!("{:?}", version))
+            .join("Strict Book")
+            .join("Strict Book.epub");
+        let opf = get_epub_opf(&epub).await;
+        assert!(opf.contains("Second Author</dc:creator>"));
+        assert!(opf.contains("<dc:publisher>Publisher &amp; Co</dc:publisher>"));
+        assert!(opf.contains("<dc:rights>All rights reserved</dc:rights>"));
+        assert!(opf.contains("<dc:date>2024-05-01"));
+        assert!(opf.contains("content=\"Scan &quot;A&quot; &amp; B\""));
+        match version {
+            EpubVersion::V3 => assert!(opf.contains(
+                "<meta property=\"belongs-to-collection\" id=\"hozon-series\">Strict Series</meta>"
+            )),
+            EpubVersion::V2 => {
+                assert!(opf.contains("<meta name=\"calibre:series\" content=\"Strict Series\"/>"))
+            }
+        }
+
+        // Page images and the stylesheet are referenced relative to the page
+        let page = get_zip_entry(&epub, "OEBPS/chapters/chapter_001/page_001.xhtml").await;
+        assert!(page.contains("src=\"../../chapters/chapter_001/page_001.jpg\""));
+        assert!(page.contains("href=\"../../stylesheet.css\""));
+    }
+
+    // Configurations epubcheck is known to reject are refused up front
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .toc(TocOptions {
+            style: TocStyle::None,
+            ..Default::default()
+        })
+        .strict_epub(true)
+        .build()?;
+    assert!(
+        config
+            .convert_from_source(CoverOptions::None)
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_entry_names_are_portable() -> Result<()> {
+    let test_dirs = setup_test_dirs("portable_entry_names").await;
+
+    // Decomposed (NFD) "é", as written by macOS, and a trailing dot
+    let chapter = test_dirs
+        .source_dir
+        .join("Chapitre\u{65}\u{301} 1 - D\u{65}\u{301}but.");
+    create_dummy_color_image(&chapter.join("001.JPG")).await?;
+
+    for format in [FileFormat::Cbz, FileFormat::Epub] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Portable".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(format)
+            .build()?;
+        timeout(
+            LONG_TEST_TIMEOUT,
+            config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let path = test_dirs
+            .target_dir
+            .join("Portable")
+            .join(format!("Portable.{}", format.extension()));
+        let archive = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        for name in archive.file_names() {
+            assert_eq!(hozon::path_utils::sanitize_entry_name(name), name);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unicode_normalization_of_names_and_metadata() -> Result<()> {
+    let test_dirs = setup_test_dirs("unicode_normalization").await;
+
+    // Chapter and title in decomposed form (NFD), as found on macOS
+    let chapter = test_dirs.source_dir.join("Chapitre\u{65}\u{301}");
+    create_dummy_color_image(&chapter.join("001.jpg")).await?;
+
+    let metadata = EbookMetadata {
+        authors: vec!["\u{ff21}uthor".to_string()], // Full-width "A"
+        ..EbookMetadata::default_with_title("Caf\u{65}\u{301}".to_string())
+    };
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .unicode_normalization(UnicodeNormalization::Nfkc)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz = test_dirs.target_dir.join("Caf\u{e9}").join("Caf\u{e9}.cbz");
+    assert!(cbz.exists(), "Output name is not normalized");
+    let comic_info = get_comic_info_xml(&cbz).await;
+    assert!(comic_info.contains("<Title>Caf\u{e9}</Title>"));
+    assert!(comic_info.contains("Author"));
+    assert!(comic_info.contains("Chapitre\u{e9}"));
+    assert!(!comic_info.contains('\u{301}'));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_localized_volume_label() -> Result<()> {
+    let test_dirs = setup_test_dirs("localized_volume_label").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+
+    let metadata = EbookMetadata {
+        language: "fr-FR".to_string(),
+        ..EbookMetadata::default_with_title("Série".to_string())
+    };
+    let mut config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![1, 1])
+        .volume_label(VolumeLabel::FromLanguage)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Série");
+    let tome2 = output_dir.join("Série - Tome 2.epub");
+    assert!(tome2.exists(), "Expected localized volume file name");
+    assert!(get_epub_opf(&tome2).await.contains("Série Tome 2"));
+
+    config.volume_label = VolumeLabel::Custom("第{n}巻".to_string());
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    assert!(output_dir.join("Série - 第1巻.epub").exists());
+
+    assert_eq!(VolumeLabel::FromLanguage.format(3, "de"), "Band 3");
+    assert_eq!(VolumeLabel::FromLanguage.format(3, "xx"), "Volume 3");
+    assert_eq!(VolumeLabel::Custom("Tomo".into()).format(3, "en"), "Tomo 3");
+    assert_eq!(VolumeLabel::English.format_short(3, "en"), "Vol 3");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_per_chapter_output_naming() -> Result<()> {
+    let test_dirs = setup_test_dirs("per_chapter_naming").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("01 Arrival").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("02 Departure").join("001.jpg")).await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Chaptered".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .volume_grouping_strategy(VolumeGroupingStrategy::PerChapter)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Chaptered");
+    assert!(
+        output_dir
+            .join("Chaptered - c001 - 01 Arrival.cbz")
+            .exists()
+    );
+    assert!(
+        output_dir
+            .join("Chaptered - c002 - 02 Departure.cbz")
+            .exists()
+    );
+
+    config.file_name_template = Some("{series} {volume_label} [{chapter:02}]".to_string());
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    assert!(output_dir.join("Chaptered Volume 2 [02].cbz").exists());
+
+    config.file_name_template = Some("{series} {unknown}".to_string());
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_photo_album_preset() -> Result<()> {
+    let test_dirs = setup_test_dirs("photo_album").await;
+    let source = &test_dirs.source_dir;
+    // Camera file names don't reflect the capture order
+    create_dummy_photo(
+        &source.join("IMG_0003.jpg"),
+        "2024:05:01 09:00:00",
+        Some("Breakfast"),
+    )
+    .await?;
+    create_dummy_photo(
+        &source.join("IMG_0001.jpg"),
+        "2024:05:02 18:30:00",
+        Some("Sunset at the beach"),
+    )
+    .await?;
+    create_dummy_photo(
+        &source.join("IMG_0002.jpg"),
+        "2024:05:01 08:00:00",
+        Some("Sunrise"),
+    )
+    .await?;
+    create_dummy_color_image(&source.join("IMG_0000.jpg")).await?; // No EXIF data
+
+    let config = HozonConfig::builder()
+        .photo_album_preset()
+        .metadata(EbookMetadata::default_with_title("Holidays".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .collection_depth(CollectionDepth::Shallow)
+        .output_format(FileFormat::Epub)
+        .build()?;
+    assert_eq!(config.toc.style, TocStyle::Chapters);
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let epub = test_dirs.target_dir.join("Holidays").join("Holidays.epub");
+    let nav = get_zip_entry(&epub, "OEBPS/nav.xhtml").await;
+    let day1 = nav.find("2024-05-01").expect("First day in TOC");
+    let day2 = nav.find("2024-05-02").expect("Second day in TOC");
+    let undated = nav.find("Undated").expect("Undated photos in TOC");
+    assert!(day1 < day2 && day2 < undated, "TOC is not chronological");
+
+    // Captions become alt text and show that pages are ordered by capture time
+    for (page, caption) in [
+        ("chapter_001/page_001", "Sunrise"),
+        ("chapter_001/page_002", "Breakfast"),
+        ("chapter_002/page_001", "Sunset at the beach"),
+    ] {
+        let xhtml = get_zip_entry(&epub, &format!("OEBPS/chapters/{}.xhtml", page)).await;
+        assert!(xhtml.contains(&format!("alt=\"{}\"", caption)), "{}", page);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exif_date_page_sorting() -> Result<()> {
+    let test_dirs = setup_test_dirs("exif_date_sorting").await;
+    let chapter = test_dirs.source_dir.join("Trip");
+    create_dummy_photo(&chapter.join("DSC_9.jpg"), "2024:05:01 10:00:00", None).await?;
+    create_dummy_photo(&chapter.join("DSC_10.jpg"), "2024:05:01 08:00:00", None).await?;
+    create_dummy_color_image(&chapter.join("DSC_2.jpg")).await?; // No EXIF data
+    create_dummy_color_image(&chapter.join("DSC_1.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Trip".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .page_sort_strategy(SortStrategy::ExifDate)
+        .build()?;
+    let collected = config.analyze_source().await?;
+
+    let names: Vec<String> = collected.chapters_with_pages[0]
+        .iter()
+        .map(|page| page.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["DSC_10.jpg", "DSC_9.jpg", "DSC_1.jpg", "DSC_2.jpg"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_duplicate_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("duplicate_pages").await;
+    for (chapter, color) in [("Chapter 1", [255, 0, 0]), ("Chapter 2", [0, 0, 255])] {
+        let chapter = test_dirs.source_dir.join(chapter);
+        create_dummy_grayscale_image(&chapter.join("001.jpg")).await?; // Recurring disclaimer
+        create_dummy_image(&chapter.join("002.jpg"), image::Rgb(color)).await?;
+    }
+    let image_names = |path: &std::path::Path| -> Result<Vec<String>> {
+        let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        Ok(archive
+            .file_names()
+            .filter(|name| name.contains("page_") && name.ends_with(".jpg"))
+            .map(str::to_string)
+            .collect())
+    };
+
+    // Stripped from every volume but the first
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Dup".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![1, 1])
+        .duplicate_pages(DuplicatePagePolicy::StripRepeated)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let output_dir = test_dirs.target_dir.join("Dup");
+    assert_eq!(
+        image_names(&output_dir.join("Dup - Volume 1.cbz"))?.len(),
+        2
+    );
+    assert_eq!(
+        image_names(&output_dir.join("Dup - Volume 2.cbz"))?.len(),
+        1
+    );
+
+    // Shared within one EPUB
+    config.output_format = FileFormat::Epub;
+    config.volume_sizes_override = Vec::new();
+    config.duplicate_pages = DuplicatePagePolicy::Share;
+    config.strict_epub = true;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let epub = output_dir.join("Dup.epub");
+    assert_eq!(image_names(&epub)?.len(), 3); // Four pages, one image shared
+    let page = get_zip_entry(&epub, "OEBPS/chapters/chapter_002/page_001.xhtml").await;
+    assert!(page.contains("../../chapters/chapter_001/page_001.jpg"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stripped_chapter_keeps_numbering() -> Result<()> {
+    let test_dirs = setup_test_dirs("stripped_chapter_numbering").await;
+    let source = &test_dirs.source_dir;
+    create_dummy_grayscale_image(&source.join("Chapter 1").join("001.jpg")).await?; // Disclaimer
+    create_dummy_image(
+        &source.join("Chapter 1").join("002.jpg"),
+        image::Rgb([255, 0, 0]),
+    )
+    .await?;
+    create_dummy_grayscale_image(&source.join("Chapter 2").join("001.jpg")).await?; // Only the disclaimer
+    create_dummy_image(
+        &source.join("Chapter 3").join("001.jpg"),
+        image::Rgb([0, 0, 255]),
+    )
+    .await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Stripped".to_string()))
+        .source_path(source.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![1, 2])
+        .duplicate_pages(DuplicatePagePolicy::StripRepeated)
+        .comic_info_chapter_map(true)
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    // Chapter 2 is left without pages, but chapter 3 keeps its number
+    assert_eq!(
+        report.files[1].pages,
+        vec![PageMapping {
+            source: source.join("Chapter 3").join("001.jpg"),
+            chapter: 2,
+            series_chapter: 3,
+            chapter_page: 1,
+            volume_page: 1,
+        }]
+    );
+    let comic_info = get_comic_info_xml(&report.files[1].path).await;
+    assert!(comic_info.contains("<Page Image=\"0\" Bookmark=\"Chapter 3\" />"));
+    assert!(!comic_info.contains("Bookmark=\"Chapter 2\""));
+    assert!(comic_info.contains("Chapter range: Chapters 2\u{2013}3 of 3"));
+    assert!(comic_info.contains("Chapter offsets: 3 Chapter 3: page 1"));
+    assert!(comic_info.contains("Chapters included: Chapter 2, Chapter 3"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_comic_info_chapter_map() -> Result<()> {
+    let test_dirs = setup_test_dirs("comic_info_chapter_map").await;
+    for (chapter, pages) in [("Chapter 1", 2), ("Chapter 2", 3), ("Chapter 3", 1)] {
+        for page in 1..=pages {
+            let path = test_dirs
+                .source_dir
+                .join(chapter)
+                .join(format!("{:03}.jpg", page));
+            create_dummy_color_image(&path).await?;
+        }
+    }
+    let cover_path = test_dirs.source_dir.join("cover.jpg");
+    create_dummy_grayscale_image(&cover_path).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Map".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![2, 1])
+        .comic_info_chapter_map(true)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::Single(cover_path)),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let output_dir = test_dirs.target_dir.join("Map");
+    let first = get_comic_info_xml(&output_dir.join("Map - Volume 1.cbz")).await;
+    assert!(first.contains("<Page Image=\"0\" Type=\"FrontCover\" />"));
+    assert!(first.contains("<Page Image=\"1\" Bookmark=\"Chapter 1\" />"));
+    assert!(first.contains("<Page Image=\"3\" Bookmark=\"Chapter 2\" />"));
+    assert!(first.contains("Chapter range: Chapters 1\u{2013}2 of 3"));
+    assert!(first.contains("Chapter offsets: 1 Chapter 1: page 1, 2 Chapter 2: page 3"));
+
+    let second = get_comic_info_xml(&output_dir.join("Map - Volume 2.cbz")).await;
+    assert!(second.contains("<Page Image=\"1\" Bookmark=\"Chapter 3\" />"));
+    assert!(second.contains("Chapter range: Chapter 3 of 3"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_archive_backend() -> Result<()> {
+    let test_dirs = setup_test_dirs("async_archive_backend").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    let cover_path = test_dirs.source_dir.join("cover.jpg");
+    create_dummy_grayscale_image(&cover_path).await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Async".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .archive_backend(ArchiveBackend::Async)
+        .build()?;
+    if !cfg!(feature = "async-zip") {
+        assert!(
+            config
+                .preflight_check(HozonExecutionMode::FromSource)
+                .is_err()
+        );
+        return Ok(());
+    }
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::Single(cover_path)),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs.target_dir.join("Async").join("Async.cbz");
+    assert_valid_zip_file(&cbz_path).await;
+    let archive = zip::ZipArchive::new(std::fs::File::open(&cbz_path)?).unwrap();
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "000_cover.jpg",
+            "ComicInfo.xml",
+            "page_001.jpg",
+            "page_002.jpg"
+        ]
+    );
+    assert!(
+        get_comic_info_xml(&cbz_path)
+            .await
+            .contains("<PageCount>2</PageCount>")
+    );
+
+    config.output_password = Some("secret".to_string());
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_structure_from_source() -> Result<()> {
+    let test_dirs = setup_test_dirs("structure_from_source").await;
+    for chapter in ["Chapter 1", "Chapter 2", "Chapter 3"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Preview".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![2, 1])
+        .build()?;
+    let structured = timeout(LONG_TEST_TIMEOUT, config.structure_from_source())
+        .await
+        .expect("Test timed out")?;
+
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![2, 1]);
+    assert_eq!(
+        structured.grouping_strategy_applied,
+        VolumeGroupingStrategy::Manual
+    );
+    assert!(!test_dirs.target_dir.join("Preview").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_staged_pipeline() -> Result<()> {
+    let test_dirs = setup_test_dirs("staged_pipeline").await;
+    for chapter in ["Chapter 1", "Chapter 2", "Chapter 3"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("002.jpg")).await?;
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Staged".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+        .volume_sizes_override(vec![2, 1])
+        .build()?;
+
+    let collected = HozonPipeline::new(config.clone()).collect().await?;
+    assert_eq!(collected.chapters().len(), 3);
+    assert!(collected.analysis().is_some());
+
+    let structured = collected.structure().await?;
+    assert_eq!(structured.volumes().len(), 2);
+    assert_eq!(
+        structured.report().unwrap().chapter_counts_per_volume,
+        vec![2, 1]
+    );
+
+    let report = timeout(LONG_TEST_TIMEOUT, structured.generate(CoverOptions::None))
+        .await
+        .expect("Test timed out")?;
+    assert_eq!(report.files.len(), 2);
+    assert_eq!(report.files[0].page_count, 4);
+    assert!(report.files.iter().all(|file| file.path.exists()));
+
+    // Starting from structured volumes skips collection and structuring.
+    let volumes = vec![vec![vec![
+        test_dirs.source_dir.join("Chapter 3").join("001.jpg"),
+    ]]];
+    let report = HozonPipeline::new(config)
+        .from_volumes(volumes)?
+        .generate(CoverOptions::None)
+        .await?;
+    assert!(report.analysis.is_none() && report.structure.is_none());
+    assert_eq!(report.files[0].page_count, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_engine_converts_requests() -> Result<()> {
+    let test_dirs = setup_test_dirs("engine_requests").await;
+    let engine = HozonEngine::new(
+        HozonConfig::builder()
+            .page_name_regex_str(r"(\d+)")
+            .create_output_directory(false)
+            .build()?,
+    )?;
+
+    let mut requests = Vec::new();
+    for series in ["Series A", "Series B"] {
+        let source = test_dirs.source_dir.join(series);
+        create_dummy_color_image(&source.join("Chapter 1").join("001.jpg")).await?;
+        create_dummy_color_image(&source.join("Chapter 1").join("002.jpg")).await?;
+        requests.push(ConversionRequest {
+            metadata: EbookMetadata::default_with_title(series.to_string()),
+            source_path: source,
+            target_path: test_dirs.target_dir.clone(),
+        });
+    }
+
+    let conversions = requests.into_iter().map(|request| {
+        let engine = engine.clone();
+        async move {
+            engine
+                .convert_from_source(request, CoverOptions::None)
+                .await
+        }
+    });
+    let reports = timeout(
+        LONG_TEST_TIMEOUT,
+        futures::future::try_join_all(conversions),
+    )
+    .await
+    .expect("Test timed out")?;
+    for (report, series) in reports.iter().zip(["Series A", "Series B"]) {
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].page_count, 2);
+        assert_eq!(
+            report.files[0].path,
+            test_dirs.target_dir.join(format!("{}.cbz", series))
+        );
+    }
+
+    // Settings are validated once, when the engine is created.
+    let invalid = HozonConfig::builder().epub_max_file_size(0u64).build()?;
+    assert!(HozonEngine::new(invalid).is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sort_specs() -> Result<()> {
+    let test_dirs = setup_test_dirs("sort_specs").await;
+    for chapter in ["Chapter 2", "Chapter 10"] {
+        for page in ["v2_p1.jpg", "v1_p2.jpg"] {
+            create_dummy_color_image(&test_dirs.source_dir.join(chapter).join(page)).await?;
+        }
+    }
+
+    let base = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Sorted".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .build()?;
+    let names = |pages: &[PathBuf]| -> Vec<String> {
+        pages
+            .iter()
+            .map(|page| {
+                let chapter = page.parent().unwrap().file_name().unwrap();
+                let page = page.file_name().unwrap();
+                format!("{}/{}", chapter.to_string_lossy(), page.to_string_lossy())
+            })
+            .collect()
+    };
+
+    let default = base.analyze_source().await?;
+    let default_order: Vec<String> = default
+        .chapters_with_pages
+        .iter()
+        .flat_map(|pages| names(pages))
+        .collect();
+    assert_eq!(
+        default_order,
+        vec![
+            "Chapter 2/v2_p1.jpg",
+            "Chapter 2/v1_p2.jpg",
+            "Chapter 10/v2_p1.jpg",
+            "Chapter 10/v1_p2.jpg"
+        ]
+    );
+
+    let mut config = base.clone();
+    config.chapter_sort = SortSpec::Lexicographic;
+    config.page_sort = Some(SortSpec::RegexCapture {
+        pattern: r"v(\d+)".to_string(),
+        group: 1,
+    });
+    config.preflight_check(HozonExecutionMode::FromSource)?;
+    let sorted = config.analyze_source().await?;
+    let sorted_order: Vec<String> = sorted
+        .chapters_with_pages
+        .iter()
+        .flat_map(|pages| names(pages))
+        .collect();
+    assert_eq!(
+        sorted_order,
+        vec![
+            "Chapter 10/v1_p2.jpg",
+            "Chapter 10/v2_p1.jpg",
+            "Chapter 2/v1_p2.jpg",
+            "Chapter 2/v2_p1.jpg"
+        ]
+    );
+
+    let mut invalid = base.clone();
+    invalid.chapter_sort = SortSpec::ExifDate;
+    assert!(
+        invalid
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    let mut invalid = base;
+    invalid.page_sort = Some(SortSpec::RegexCapture {
+        pattern: r"v(\d+)".to_string(),
+        group: 2,
+    });
+    assert!(
+        invalid
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_naming_preset_orders_chapters() -> Result<()> {
+    let test_dirs = setup_test_dirs("naming_preset").await;
+    for chapter in ["1000 Post 9", "999 Post 10"] {
+        for page in ["1_p0.jpg", "1_p1.jpg"] {
+            create_dummy_color_image(&test_dirs.source_dir.join(chapter).join(page)).await?;
+        }
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Fanbox".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .naming_preset("fanbox")
+        .build()?;
+    let collected = config.analyze_source().await?;
+    let chapters: Vec<String> = collected
+        .chapters_with_pages
+        .iter()
+        .map(|pages| {
+            let chapter = pages[0].parent().unwrap().file_name().unwrap();
+            chapter.to_string_lossy().into_owned()
+        })
+        .collect();
+    assert_eq!(chapters, vec!["999 Post 10", "1000 Post 9"]);
+    assert!(collected.chapters_with_pages[0][0].ends_with("1_p0.jpg"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_explain_sort_and_grouping() -> Result<()> {
+    let test_dirs = setup_test_dirs("explain_grouping").await;
+    for chapter in ["01-001", "01-002", "02-003"] {
+        for page in ["p1.jpg", "p2.jpg"] {
+            create_dummy_color_image(&test_dirs.source_dir.join(chapter).join(page)).await?;
+        }
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Explain".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .build()?;
+
+    let sort = config.explain_sort().await?;
+    assert_eq!(sort.chapters.len(), 3);
+    assert_eq!(sort.chapters[2].number, Some(3.0)); // Last number of "02-003"
+    assert_eq!(sort.chapters[0].pages[1].number, Some(2.0));
+    assert!(sort.to_string().contains("'p2.jpg' → 2"));
+
+    let grouping = config.explain_grouping().await?;
+    let volumes: Vec<usize> = grouping.chapters.iter().map(|c| c.volume_number).collect();
+    assert_eq!(volumes, vec![1, 1, 2]);
+    assert_eq!(grouping.chapters[2].parsed_volume, Some(2.0));
+    assert_eq!(grouping.chapters[2].parsed_chapter, Some(3.0));
+    assert_eq!(grouping.breaks.len(), 1);
+    assert_eq!(grouping.breaks[0].index, 2);
+    assert_eq!(grouping.breaks[0].reason, "volume changed 1→2");
+
+    let text = grouping.to_string();
+    assert!(text.contains("'02-003' → vol 2, ch 3 (volume 2)"));
+    assert!(text.contains("Break before index 2 because volume changed 1→2"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_long_output_paths() -> Result<()> {
+    let test_dirs = setup_test_dirs("long_output_paths").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    // Long title plus deep target folders push the output files past MAX_PATH
+    let title = "An Exceedingly Long Series Title For Testing Path Limits".repeat(2);
+    let mut target = test_dirs.target_dir.clone();
+    for depth in 0..3 {
+        target.push(format!("{} Library Folder {}", title, depth));
+    }
+
+    for format in [FileFormat::Cbz, FileFormat::Epub] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title(title.clone()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(target.clone())
+            .output_format(format)
+            .cover_sidecars(CoverSidecars::default())
+            .build()?;
+        config.convert_from_source(CoverOptions::None).await?;
+
+        let output = config
+            .output_directory()
+            .join(format!("{}.{}", title, format.extension()));
+        assert!(output.to_string_lossy().len() > 260);
+        assert_valid_zip_file(&output).await;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_entry_timestamps() -> Result<()> {
+    let test_dirs = setup_test_dirs("entry_timestamps").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let fixed = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 6).unwrap();
+    for format in [FileFormat::Cbz, FileFormat::Epub] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Stamped".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(format)
+            .entry_timestamps(EntryTimestamps::Fixed(fixed))
+            .build()?;
+        config.convert_from_source(CoverOptions::None).await?;
+
+        let output = config
+            .output_directory()
+            .join(format!("Stamped.{}", format.extension()));
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&output)?).unwrap();
+        for index in 0..archive.len() {
+            let entry = archive.by_index(index).unwrap();
+            let modified = entry.last_modified().unwrap();
+            assert_eq!(
+                (modified.year(), modified.month(), modified.day()),
+                (2020, 1, 2),
+                "{} in {:?}",
+                entry.name(),
+                format
+            );
+            assert_eq!(
+                (modified.hour(), modified.minute(), modified.second()),
+                (3, 4, 6)
+            );
+        }
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Stamped".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .entry_timestamps(EntryTimestamps::SourceModified)
+        .build()?;
+    assert!(
+        config
+            .ignored_options()
+            .iter()
+            .any(|option| option.option == "entry_timestamps")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_entry_and_output_permissions() -> Result<()> {
+    let test_dirs = setup_test_dirs("entry_and_output_permissions").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Modes".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .entry_permissions(0o644u32)
+        .output_permissions(0o640u32)
+        .build()?;
+    config.convert_from_source(CoverOptions::None).await?;
+
+    let output = config.output_directory().join("Modes.cbz");
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&output)?).unwrap();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).unwrap();
+        assert_eq!(entry.unix_mode().map(|mode| mode & 0o777), Some(0o644));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&output)?.permissions().mode();
+        assert_eq!(mode & 0o7777, 0o640);
+    }
+
+    let invalid = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Modes".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .entry_permissions(0o4755u32)
+        .build()?;
+    assert!(
+        invalid
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shared_cpu_budget_across_phases() -> Result<()> {
+    let test_dirs = setup_test_dirs("shared_cpu_budget").await;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("001-Chapter_A").join("001.jpg"))
+        .await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("002-Chapter_B").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("002-Chapter_B").join("002.jpg")).await?;
+
+    // A single CPU permit serializes cover analysis and page processing of both
+    // conversions without starving either
+    let limits = RuntimeLimits::new(2, 4)?.with_max_concurrent_cpu(1)?;
+    let mut configs = Vec::new();
+    for series in ["Budget A", "Budget B"] {
+        configs.push(
+            HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title(series.to_string()))
+                .source_path(test_dirs.source_dir.clone())
+                .target_path(test_dirs.target_dir.clone())
+                .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+                .image_processing(ImageProcessing {
+                    max_width: Some(50),
+                    ..Default::default()
+                })
+                .runtime_limits(limits.clone())
+                .build()?,
+        );
+    }
+    let conversions = configs
+        .iter()
+        .map(|config| config.convert_from_source(CoverOptions::None));
+    timeout(
+        LONG_TEST_TIMEOUT,
+        futures::future::try_join_all(conversions),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    for series in ["Budget A", "Budget B"] {
+        for volume in 1..=2 {
+            let path = test_dirs
+                .target_dir
+                .join(series)
+                .join(format!("{} - Volume {}.cbz", series, volume));
+            assert_valid_zip_file(&path).await;
+        }
+    }
+    assert!(format!("{:?}", limits).contains("available_cpu: 1"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_image_analysis_embedded_thumbnails() -> Result<()> {
+    let test_dirs = setup_test_dirs("image_analysis_thumbnails").await;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("001-Chapter_A").join("001.jpg"))
+        .await?;
+    // A grayscale page whose stale preview is in color
+    create_dummy_image_with_preview(
+        &test_dirs.source_dir.join("002-Chapter_B").join("001.jpg"),
+        image::Rgb([128, 128, 128]),
+        image::Rgb([255, 0, 0]),
+    )
+    .await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Previews".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+        .build()?;
+    let full = config.structure_from_source().await?;
+    assert_eq!(full.volumes_with_chapters_and_pages.len(), 1);
+
+    // Only the preview is analyzed, so the second chapter looks like a colored cover
+    config.image_analysis_thumbnails = true;
+    let previews = config.structure_from_source().await?;
+    assert_eq!(previews.volumes_with_chapters_and_pages.len(), 2);
+
+    config.volume_grouping_strategy = VolumeGroupingStrategy::Name;
+    assert!(
+        config
+            .ignored_options()
+            .iter()
+            .any(|option| option.option == "image_analysis_thumbnails")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_image_analysis_cache() -> Result<()> {
+    let test_dirs = setup_test_dirs("image_analysis_cache").await;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("001-Chapter_A").join("001.jpg"))
+        .await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("002-Chapter_B").join("001.jpg")).await?;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("003-Chapter_C").join("001.jpg"))
+        .await?;
+
+    let cache_path = test_dirs.target_dir.join("cache").join("analysis.json");
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Cached".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+        .analysis_cache(cache_path.clone())
+        .build()?;
+    let first = config.structure_from_source().await?;
+    assert_eq!(first.volumes_with_chapters_and_pages.len(), 2);
+
+    // Both grayscale covers have the same contents
+    let cache = AnalysisCache::open(&cache_path)?;
+    assert_eq!(cache.len(), 2);
+    let cover = test_dirs.source_dir.join("002-Chapter_B").join("001.jpg");
+    let analysis = cache.analyze(&cover, false)?;
+    assert_eq!((analysis.width, analysis.height), (100, 100));
+    assert!(!analysis.is_grayscale(0.75));
+
+    // Cached shares give the same verdicts at any sensibility
+    config.image_analysis_sensibility = 10;
+    let second = config.structure_from_source().await?;
+    assert_eq!(second.volumes_with_chapters_and_pages.len(), 2);
+    assert_eq!(AnalysisCache::open(&cache_path)?.len(), 2);
+
+    // Changed contents are analyzed again under a new key
+    create_dummy_image(
+        &test_dirs.source_dir.join("003-Chapter_C").join("001.jpg"),
+        image::Rgb([0, 0, 255]),
+    )
+    .await?;
+    config.image_analysis_sensibility = 75;
+    let third = config.structure_from_source().await?;
+    assert_eq!(third.volumes_with_chapters_and_pages.len(), 3);
+    assert_eq!(AnalysisCache::open(&cache_path)?.len(), 3);
+
+    cache.clear()?;
+    assert!(cache.is_empty());
+    assert!(!cache_path.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sweep_sensibility() -> Result<()> {
+    let test_dirs = setup_test_dirs("sweep_sensibility").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("001-Chapter_A").join("001.jpg")).await?;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("002-Chapter_B").join("001.jpg"))
+        .await?;
+    // Half gray, half red: a cover only at sensibilities of 50% and above
+    let mixed = image::RgbImage::from_fn(100, 100, |x, _| {
+        if x < 50 {
+            image::Rgb([128, 128, 128])
+        } else {
+            image::Rgb([255, 0, 0])
+        }
+    });
+    let mixed_path = test_dirs.source_dir.join("003-Chapter_C").join("001.png");
+    std::fs::create_dir_all(mixed_path.parent().unwrap())?;
+    mixed.save(&mixed_path)?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Sweep".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+        .build()?;
+    let sweep = config.sweep_sensibility((10..=90).step_by(40)).await?;
+    assert_eq!(sweep, vec![(10, 1), (50, 2), (90, 2)]);
+
+    // The counts match full structuring runs
+    for (sensibility, volumes) in sweep {
+        let mut config = config.clone();
+        config.image_analysis_sensibility = sensibility;
+        let structured = config.structure_from_source().await?;
+        assert_eq!(structured.volumes_with_chapters_and_pages.len(), volumes);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expected_volume_count() -> Result<()> {
+    let test_dirs = setup_test_dirs("expected_volume_count").await;
+    for (volume, chapter) in [(1, 1), (1, 2), (2, 3), (3, 4), (3, 5), (3, 6)] {
+        create_dummy_color_image(
+            &test_dirs
+                .source_dir
+                .join(format!("{:02}-{:03}", volume, chapter))
+                .join("001.jpg"),
+        )
+        .await?;
+    }
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Expected".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .expected_volume_count(2usize)
+        .build()?;
+
+    // Volumes 1 and 2 together are the smallest neighbors
+    let merged = config.structure_from_source().await?;
+    assert_eq!(merged.report.chapter_counts_per_volume, vec![3, 3]);
+    assert_eq!(
+        merged.report.adjustments,
+        vec![VolumeCountAdjustment::Merged { volume: 1 }]
+    );
+
+    config.expected_volume_count = Some(4);
+    let split = config.structure_from_source().await?;
+    assert_eq!(split.report.chapter_counts_per_volume, vec![2, 1, 1, 2]);
+    assert_eq!(
+        split.report.adjustments,
+        vec![VolumeCountAdjustment::Split { volume: 3 }]
+    );
+    assert_eq!(
+        split.report.adjustments[0].to_string(),
+        "volume 3 split in two"
+    );
+
+    config.expected_volume_count = Some(0);
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expected_volume_count_adjusts_sensibility() -> Result<()> {
+    let test_dirs = setup_test_dirs("expected_volume_count_sensibility").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("001-Chapter_A").join("001.jpg")).await?;
+    create_dummy_grayscale_image(&test_dirs.source_dir.join("002-Chapter_B").join("001.jpg"))
+        .await?;
+    // Half gray: a volume start only at sensibilities of 50% and above
+    let mixed = image::RgbImage::from_fn(100, 100, |x, _| {
+        if x < 50 {
+            image::Rgb([128, 128, 128])
+        } else {
+            image::Rgb([255, 0, 0])
+        }
+    });
+    let mixed_path = test_dirs.source_dir.join("003-Chapter_C").join("001.png");
+    std::fs::create_dir_all(mixed_path.parent().unwrap())?;
+    mixed.save(&mixed_path)?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Expected".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+        .expected_volume_count(1usize)
+        .build()?;
+    let structured = config.structure_from_source().await?;
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![3]);
+    assert_eq!(
+        structured.report.adjustments,
+        vec![VolumeCountAdjustment::Sensibility { from: 75, to: 49 }]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_volume_mapping_file() -> Result<()> {
+    let test_dirs = setup_test_dirs("volume_mapping_file").await;
+    for chapter in 1..=5 {
+        create_dummy_color_image(
+            &test_dirs
+                .source_dir
+                .join(format!("Chapter_{:02}", chapter))
+                .join("001.jpg"),
+        )
+        .await?;
+    }
+    let csv_path = test_dirs.target_dir.join("volumes.csv");
+    tokio::fs::write(
+        &csv_path,
+        "chapter,volume\n# Volume 2 was released first\n1-2,2\n3,1\n5,2\n",
+    )
+    .await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Mapped".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Mapping)
+        .volume_mapping_file(csv_path)
+        .build()?;
+
+    // Chapter 4 is missing from the mapping
+    let collected = config.analyze_source().await?;
+    let unmapped: Vec<_> = collected
+        .report
+        .findings
+        .iter()
+        .filter_map(|finding| match finding {
+            AnalyzeFinding::UnmappedChapter {
+                chapter_path,
+                chapter_number,
+            } => Some((chapter_path.clone(), *chapter_number)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        unmapped,
+        vec![(test_dirs.source_dir.join("Chapter_04"), Some(4.0))]
+    );
+    let error = config.structure_from_source().await.unwrap_err();
+    assert!(error.to_string().contains("Chapter_04"));
+
+    // Volumes follow the mapped numbers, chapters keep their order
+    let json_path = test_dirs.target_dir.join("volumes.json");
+    tokio::fs::write(&json_path, r#"{ "1-2": 2, "3": 1, "4-5": "2" }"#).await?;
+    config.volume_mapping_file = Some(json_path);
+    let structured = config.structure_from_source().await?;
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![1, 4]);
+    assert!(
+        structured.volumes_with_chapters_and_pages[0][0][0]
+            .starts_with(test_dirs.source_dir.join("Chapter_03"))
+    );
+
+    let conflicting = VolumeMapping::parse_csv("1-5,1\n5,2\n").unwrap_err();
+    assert!(conflicting.to_string().contains("line 2"));
+
+    config.volume_mapping_file = None;
+    assert!(
+        config
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extra_chapters() -> Result<()> {
+    let test_dirs = setup_test_dirs("extra_chapters").await;
+    for chapter in ["Chapter_01", "Chapter_02", "Oneshot", "Omake"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Extras".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_sizes_override(vec![2])
+        .extra_chapters(ExtraChapters::AppendToLastVolume)
+        .build()?;
+
+    let collected = config.analyze_source().await?;
+    let mut unnumbered: Vec<_> = collected
+        .report
+        .findings
+        .iter()
+        .filter_map(|finding| match finding {
+            AnalyzeFinding::UnnumberedChapter { chapter_path } => Some(chapter_path.clone()),
+            _ => None,
+        })
+        .collect();
+    unnumbered.sort();
+    assert_eq!(
+        unnumbered,
+        vec![
+            test_dirs.source_dir.join("Omake"),
+            test_dirs.source_dir.join("Oneshot")
+        ]
+    );
+
+    // Extras follow the numbered chapters, ordered by name
+    let appended = config.structure_from_source().await?;
+    assert_eq!(appended.report.chapter_counts_per_volume, vec![4]);
+    let chapter_dirs: Vec<_> = appended.volumes_with_chapters_and_pages[0]
+        .iter()
+        .map(|pages| pages[0].parent().unwrap().to_path_buf())
+        .collect();
+    assert_eq!(
+        chapter_dirs[2..],
+        [
+            test_dirs.source_dir.join("Omake"),
+            test_dirs.source_dir.join("Oneshot")
+        ]
+    );
+
+    config.extra_chapters = ExtraChapters::Skip;
+    let skipped = config.structure_from_source().await?;
+    assert_eq!(skipped.report.chapter_counts_per_volume, vec![2]);
+    assert_eq!(skipped.report.total_chapters_processed, 2);
+
+    config.extra_chapters = ExtraChapters::SeparateVolume;
+    let separate = config.structure_from_source().await?;
+    assert_eq!(separate.report.chapter_counts_per_volume, vec![2, 2]);
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let output_dir = config.output_directory();
+    assert_valid_zip_file(&output_dir.join("Extras - Volume 1.cbz")).await;
+    assert_valid_zip_file(&output_dir.join("Extras - Extras.cbz")).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lost_chapter_policy() -> Result<()> {
+    let test_dirs = setup_test_dirs("lost_chapter_policy").await;
+    for chapter in ["01-001", "01-002", "02-003"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+    // A chapter without pages can't be matched back to its directory by name grouping
+    let empty_chapter = test_dirs.source_dir.join("02-004");
+    tokio::fs::create_dir_all(&empty_chapter).await?;
+    tokio::fs::write(empty_chapter.join("notes.txt"), "no pages").await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Lost".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .build()?;
+    assert_eq!(config.analyze_source().await?.chapters_with_pages.len(), 4);
+
+    match config.structure_from_source().await {
+        Err(hozon::error::Error::LostChapters(collected, placed)) => {
+            assert_eq!((collected, placed), (4, 3));
+        }
+        other => panic!("Expected lost chapters, got {:?}", other.map(|s| s.report)),
+    }
+
+    config.lost_chapter_policy = LostChapterPolicy::Warn;
+    let structured = config.structure_from_source().await?;
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![2, 1]);
+    Ok(())
+}
+
+#[cfg(feature = "testkit")]
+#[tokio::test]
+async fn test_testkit_synthetic_library() -> Result<()> {
+    use hozon::testkit::{ChapterNaming, ImageFormat, SyntheticLibrary};
+
+    let test_dirs = setup_test_dirs("testkit_synthetic_library").await;
+    let chapters = SyntheticLibrary::new()
+        .with_volumes(2)
+        .with_chapters_per_volume(2)
+        .with_pages_per_chapter(3)
+        .with_format(ImageFormat::Png)
+        .with_naming(ChapterNaming::VolumeChapter)
+        .generate(&test_dirs.source_dir)
+        .await?;
+    assert_eq!(chapters.len(), 4);
+    assert!(chapters[2][0].ends_with("02-003/001.png"));
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Synthetic".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .build()?;
+    let collected = config.analyze_source().await?;
+    assert_eq!(collected.chapters_with_pages, chapters);
+    let by_name = config.structure_from_source().await?;
+    assert_eq!(by_name.report.chapter_counts_per_volume, vec![2, 2]);
+
+    // Every volume starts with a colored cover
+    config.volume_grouping_strategy = VolumeGroupingStrategy::ImageAnalysis;
+    let by_covers = config.structure_from_source().await?;
+    assert_eq!(by_covers.report.chapter_counts_per_volume, vec![2, 2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_comicinfo_round_trip() -> Result<()> {
+    use hozon::metadata::read_comicinfo;
+
+    let test_dirs = setup_test_dirs("read_comicinfo_round_trip").await;
+
+    for chapter in ["01-001", "01-002", "02-003"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+
+    let metadata = EbookMetadata {
+        series: Some("Tom & Jerry".to_string()),
+        authors: vec!["Ann O'Brien".to_string(), "Jo <Jr.>".to_string()],
+        publisher: Some("Small & Co.".to_string()),
+        description: Some("Cats > mice?".to_string()),
+        tags: vec!["comedy".to_string(), "cats".to_string()],
+        language: "de".to_string(),
+        rights: Some("All rights reserved".to_string()),
+        identifier: Some("legacy-id".to_string()),
+        identifiers: vec![
+            Identifier::new(IdentifierScheme::Isbn, "9781234567897"),
+            Identifier::new(IdentifierScheme::Anilist, "30013"),
+        ],
+        release_date: Some(Utc.with_ymd_and_hms(2024, 3, 9, 0, 0, 0).unwrap()),
+        genre: Some("Comedy".to_string()),
+        web: Some("https://example.com/?a=1&b=2".to_string()),
+        custom_fields: HashMap::from([("translator".to_string(), "Kim & Lee".to_string())]),
+        ..EbookMetadata::default_with_title("Tom & Jerry's <Big> Day".to_string())
+    };
+
+    for format in [NotesFormat::Lines, NotesFormat::Json] {
+        let target = test_dirs.target_dir.join(format!("{:?}", format));
+        let config = HozonConfig::builder()
+            .metadata(metadata.clone())
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(target)
+            .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+            .comic_info_notes(format.clone())
+            .build()?;
+        timeout(
+            LONG_TEST_TIMEOUT,
+            config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let info = read_comicinfo(
+            &config
+                .output_directory()
+                .join("Tom & Jerry's -Big- Day - Volume 2.cbz"),
+        )?;
+        assert_eq!(info.volume, Some(2));
+        assert_eq!(info.chapters, vec!["02-003".to_string()]);
+        assert!(info.page_count.is_some());
+
+        let read = info.metadata;
+        assert_eq!(read.title, metadata.title, "{:?}", format);
+        assert_eq!(read.series, metadata.series);
+        assert_eq!(read.authors, metadata.authors);
+        assert_eq!(read.publisher, metadata.publisher);
+        assert_eq!(read.description, metadata.description);
+        assert_eq!(read.tags, metadata.tags);
+        assert_eq!(read.language, metadata.language);
+        assert_eq!(read.rights, metadata.rights);
+        assert_eq!(read.identifier, metadata.identifier);
+        assert_eq!(read.release_date, metadata.release_date);
+        assert_eq!(read.genre, metadata.genre);
+        assert_eq!(read.web, metadata.web);
+        assert_eq!(read.custom_fields, metadata.custom_fields);
+        // Only JSON notes keep every typed identifier; otherwise the GTIN element remains
+        let expected_identifiers = match format {
+            NotesFormat::Json => metadata.identifiers.clone(),
+            _ => metadata.identifiers[..1].to_vec(),
+        };
+        assert_eq!(read.identifiers, expected_identifiers);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retag_outputs() -> Result<()> {
+    use hozon::metadata::read_comicinfo;
+
+    let test_dirs = setup_test_dirs("retag_outputs").await;
+
+    for chapter in ["01-001", "01-002", "02-003"] {
+        for page in ["001.jpg", "002.jpg"] {
+            create_dummy_color_image(&test_dirs.source_dir.join(chapter).join(page)).await?;
+        }
+    }
+
+    let old_metadata = EbookMetadata {
+        series: Some("Tom & Jery".to_string()),
+        authors: vec!["Ann".to_string()],
+        tags: vec!["old".to_string()],
+        custom_fields: HashMap::from([("typo".to_string(), "yes".to_string())]),
+        ..EbookMetadata::default_with_title("Chase".to_string())
+    };
+    let new_metadata = EbookMetadata {
+        series: Some("Tom & Jerry".to_string()),
+        authors: vec!["Ann".to_string(), "Bob <Jr.>".to_string()],
+        tags: vec!["comedy".to_string()],
+        publisher: Some("Small & Co.".to_string()),
+        custom_fields: HashMap::from([("edition".to_string(), "Second".to_string())]),
+        ..EbookMetadata::default_with_title("Chase".to_string())
+    };
+
+    for (format, version) in [
+        (FileFormat::Cbz, EpubVersion::V3),
+        (FileFormat::Epub, EpubVersion::V3),
+        (FileFormat::Epub, EpubVersion::V2),
+    ] {
+        let target = test_dirs
+            .target_dir
+            .join(format!("{:?}-{:?}", format, version));
+        let build = |metadata: &EbookMetadata| {
+            HozonConfig::builder()
+                .metadata(metadata.clone())
+                .source_path(test_dirs.source_dir.clone())
+                .target_path(target.clone())
+                .output_format(format)
+                .epub_version(version)
+                .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+                .embed_source_fingerprint(true)
+                .build()
+        };
+        let old_config = build(&old_metadata)?;
+        timeout(
+            LONG_TEST_TIMEOUT,
+            old_config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let config = build(&new_metadata)?;
+        let dir = config.output_directory();
+        let volume_2 = dir.join(format!("Chase - Volume 2.{}", format.extension()));
+        let page_entry = match format {
+            FileFormat::Cbz => "page_001.jpg",
+            FileFormat::Epub => "OEBPS/chapters/chapter_001/page_001.jpg",
+            FileFormat::Mobi | FileFormat::Cb7 | FileFormat::Kepub | FileFormat::Directory => {
+                unreachable!("only CBZ and EPUB are retagged")
+            }
+        };
+        let page_crc = |path: &std::path::Path| {
+            let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+            archive.by_name(page_entry).unwrap().crc32()
+        };
+        let page_before = page_crc(&volume_2);
+        assert!(
+            !config
+                .check_outputs(&CoverOptions::None)
+                .await?
+                .is_up_to_date()
+        );
+
+        let retagged = config.retag_outputs(&dir).await?;
+        assert_eq!(retagged.len(), 2);
+        assert!(retagged.contains(&volume_2));
+        assert_valid_zip_file(&volume_2).await;
+        assert_eq!(page_crc(&volume_2), page_before);
+        assert!(
+            config
+                .check_outputs(&CoverOptions::None)
+                .await?
+                .is_up_to_date()
+        );
+
+        match format {
+            FileFormat::Cbz => {
+                let info = read_comicinfo(&volume_2)?;
+                assert_eq!(info.volume, Some(2));
+                assert_eq!(info.chapters, vec!["02-003".to_string()]);
+                assert_eq!(info.metadata.series, new_metadata.series);
+                assert_eq!(info.metadata.authors, new_metadata.authors);
+                assert_eq!(info.metadata.tags, new_metadata.tags);
+                assert_eq!(info.metadata.publisher, new_metadata.publisher);
+                assert_eq!(info.metadata.custom_fields, new_metadata.custom_fields);
+            }
+            FileFormat::Epub => {
+                let opf = get_epub_opf(&volume_2).await;
+                assert!(opf.contains("<dc:title>Tom &amp; Jerry - Chase Vol 2</dc:title>"));
+                assert!(opf.contains(">Bob &lt;Jr.&gt;</dc:creator>"));
+                assert!(opf.contains("<dc:subject>comedy</dc:subject>"));
+                assert!(opf.contains("<dc:publisher>Small &amp; Co.</dc:publisher>"));
+                assert!(opf.contains("<meta name=\"edition\" content=\"Second\"/>"));
+                assert!(opf.contains("urn:uuid:"));
+                assert!(opf.contains("schema:accessMode"));
+                assert!(opf.contains("<meta name=\"cover\""));
+                for old in ["Jery", ">old<", "typo"] {
+                    assert!(!opf.contains(old), "{} left in {:?} OPF", old, version);
+                }
+                assert_eq!(opf.matches("<dc:title>").count(), 1);
+            }
+            FileFormat::Mobi | FileFormat::Cb7 | FileFormat::Kepub | FileFormat::Directory => {
+                unreachable!()
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_output_directory_template() -> Result<()> {
+    let test_dirs = setup_test_dirs("output_directory_template").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata {
+            series: Some("Cats/Dogs".to_string()),
+            authors: vec!["Ann O'Brien".to_string()],
+            ..EbookMetadata::default_with_title("Pets".to_string())
+        })
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cbz)
+        .output_directory_template("{target}/{author}/{series}/{format}/")
+        .build()?;
+    let expected = test_dirs
+        .target_dir
+        .join("Ann O'Brien")
+        .join("Cats-Dogs")
+        .join("cbz");
+    assert_eq!(config.output_directory(), expected);
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    assert!(expected.join("Pets.cbz").exists());
+
+    config.output_directory_template = Some("{publisher}/{year}".to_string());
+    assert_eq!(
+        config.output_directory(),
+        test_dirs.target_dir.join("Unknown").join("Unknown")
+    );
+
+    for invalid in ["{series}/../escape", "/absolute/{series}", "{unknown}"] {
+        config.output_directory_template = Some(invalid.to_string());
+        assert!(
+            config
+                .preflight_check(HozonExecutionMode::FromSource)
+                .is_err(),
+            "{} was accepted",
+            invalid
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_cleanup() -> Result<()> {
+    let test_dirs = setup_test_dirs("source_cleanup").await;
+    let inbox = test_dirs.source_dir.join("inbox");
+    let archive = test_dirs.source_dir.join("archive");
+    let create_source = || async {
+        create_dummy_color_image(&inbox.join("Chapter 1").join("001.jpg")).await?;
+        create_dummy_color_image(&inbox.join("Chapter 2").join("001.jpg")).await
+    };
+    let convert = |cleanup: SourceCleanup| {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Inbox".to_string()))
+            .source_path(inbox.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .source_cleanup(cleanup)
+            .build()
+            .unwrap();
+        async move {
+            timeout(
+                LONG_TEST_TIMEOUT,
+                config.convert_from_source(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")
+        }
+    };
+    let output = test_dirs.target_dir.join("Inbox").join("Inbox.cbz");
+
+    create_source().await?;
+    convert(SourceCleanup::KeepSource).await?;
+    assert!(output.exists());
+    assert!(inbox.join("Chapter 1").join("001.jpg").exists());
+
+    convert(SourceCleanup::MoveSourceTo(archive.clone())).await?;
+    assert!(!inbox.exists());
+    assert!(
+        archive
+            .join("inbox")
+            .join("Chapter 2")
+            .join("001.jpg")
+            .exists()
+    );
+
+    // Moving never overwrites an earlier move
+    create_source().await?;
+    assert!(
+        convert(SourceCleanup::MoveSourceTo(archive.clone()))
+            .await
+            .is_err()
+    );
+    assert!(inbox.exists());
+
+    convert(SourceCleanup::DeleteSource).await?;
+    assert!(!inbox.exists());
+    assert_valid_zip_file(&output).await;
+
+    // The output must not be inside the source it removes
+    let nested = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Inbox".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.source_dir.join("output"))
+        .source_cleanup(SourceCleanup::DeleteSource)
+        .build()?;
+    assert!(
+        nested
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_cleanup_to_trash() -> Result<()> {
+    let test_dirs = setup_test_dirs("source_cleanup_to_trash").await;
+    let inbox = test_dirs.source_dir.join("trash_inbox");
+    create_dummy_color_image(&inbox.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Trash".to_string()))
+        .source_path(inbox.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .source_cleanup(SourceCleanup::DeleteSource)
+        .use_trash(true)
+        .build()?;
+    if !cfg!(feature = "trash") {
+        assert!(
+            config
+                .preflight_check(HozonExecutionMode::FromSource)
+                .is_err()
+        );
+        return Ok(());
+    }
+
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    assert!(!inbox.exists());
+    assert_valid_zip_file(&test_dirs.target_dir.join("Trash").join("Trash.cbz")).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_registered_image_format_is_copied() -> Result<()> {
+    let test_dirs = setup_test_dirs("registered_image_format").await;
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    create_dummy_color_image(&chapter.join("001.jpg")).await?;
+    // Not decodable by Hozon; only the extension matters
+    tokio::fs::write(chapter.join("002.jxl"), b"\xff\x0a jxl codestream").await?;
+    tokio::fs::write(chapter.join("notes.txt"), b"ignored").await?;
+
+    hozon::types::register_image_format("jxl", "image/jxl")?;
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Registered".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .image_processing(ImageProcessing {
+            max_width: Some(50),
+            ..Default::default()
+        })
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let cbz_path = test_dirs
+        .target_dir
+        .join("Registered")
+        .join("Registered.cbz");
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&cbz_path)?).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, ["ComicInfo.xml", "page_001.jpg", "page_002.jxl"]);
+    let mut copied = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("page_002.jxl").unwrap(), &mut copied)?;
+    assert_eq!(copied, b"\xff\x0a jxl codestream");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_page_mapping_report() -> Result<()> {
+    let test_dirs = setup_test_dirs("page_mapping_report").await;
+    let source = &test_dirs.source_dir;
+    for (chapter, pages) in [("Chapter 1", 2), ("Chapter 2", 3), ("Chapter 3", 1)] {
+        for page in 1..=pages {
+            create_dummy_color_image(&source.join(chapter).join(format!("{:03}.jpg", page)))
+                .await?;
+        }
+    }
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Mapped".to_string()))
+        .source_path(source.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_sizes_override(vec![2, 1])
+        .build()?;
+
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let first = &report.files[0].pages;
+    assert_eq!(first.len(), 5);
+    assert_eq!(
+        first[3],
+        PageMapping {
+            source: source.join("Chapter 2").join("002.jpg"),
+            chapter: 2,
+            series_chapter: 2,
+            chapter_page: 2,
+            volume_page: 4,
+        }
+    );
+    assert_eq!(
+        report.files[1].pages,
+        vec![PageMapping {
+            source: source.join("Chapter 3").join("001.jpg"),
+            chapter: 1,
+            series_chapter: 3,
+            chapter_page: 1,
+            volume_page: 1,
+        }]
+    );
+    // CBZ entries are numbered by `volume_page`
+    let archive = zip::ZipArchive::new(std::fs::File::open(&report.files[0].path)?).unwrap();
+    assert!(archive.file_names().any(|name| name == "page_005.jpg"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_low_throttle_conversion() -> Result<()> {
+    let test_dirs = setup_test_dirs("low_throttle").await;
+    for chapter in ["Chapter 1", "Chapter 2"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+        create_dummy_grayscale_image(&test_dirs.source_dir.join(chapter).join("002.jpg")).await?;
+    }
+
+    for format in [FileFormat::Cbz, FileFormat::Epub] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Background".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(format)
+            .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+            .throttle(ThrottleProfile::Low)
+            .build()?;
+        assert!(config.ignored_options().is_empty());
+        timeout(
+            LONG_TEST_TIMEOUT,
+            config.convert_from_source(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+        for volume in 1..=2 {
+            let path = test_dirs.target_dir.join("Background").join(format!(
+                "Background - Volume {}.{}",
+                volume,
+                format.extension()
+            ));
+            assert_valid_zip_file(&path).await;
+        }
+    }
+
+    // Shared limits carry their own throttle
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Background".to_string()))
+        .target_path(test_dirs.target_dir.clone())
+        .throttle(ThrottleProfile::Low)
+        .runtime_limits(RuntimeLimits::default())
+        .build()?;
+    assert!(
+        config
+            .ignored_options()
+            .iter()
+            .any(|ignored| ignored.option == "throttle")
+    );
+    Ok(())
+}
+
+/// Sums every counter increment per metric name; other tests convert concurrently, so
+/// only lower bounds are meaningful.
+#[cfg(feature = "metrics")]
+mod counting_recorder {
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    pub struct CountingRecorder(pub Arc<Mutex<HashMap<String, u64>>>);
+
+    struct NamedCounter(String, Arc<Mutex<HashMap<String, u64>>>);
+
+    impl CounterFn for NamedCounter {
+        fn increment(&self, value: u64) {
+            *self.1.lock().unwrap().entry(self.0.clone()).or_default() += value;
+        }
+        fn absolute(&self, _value: u64) {}
+    }
+
+    impl Recorder for CountingRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let name = key.name().to_string();
+            Counter::from_arc(Arc::new(NamedCounter(name, Arc::clone(&self.0))))
+        }
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn test_conversion_metrics() -> Result<()> {
+    use hozon::telemetry;
+
+    let recorder = counting_recorder::CountingRecorder::default();
+    let counts = std::sync::Arc::clone(&recorder.0);
+    metrics::set_global_recorder(recorder).expect("Only this test installs a recorder");
+
+    let test_dirs = setup_test_dirs("conversion_metrics").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Metrics".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    // Fails in analysis, after the preflight checks
+    let empty = test_dirs.source_dir.join("empty");
+    tokio::fs::create_dir_all(&empty).await?;
+    let mut failing = config.clone();
+    failing.source_path = empty;
+    assert!(
+        failing
+            .convert_from_source(CoverOptions::None)
+            .await
+            .is_err()
+    );
+
+    let counts = counts.lock().unwrap();
+    let count = |name: &str| counts.get(name).copied().unwrap_or(0);
+    assert!(count(telemetry::VOLUMES_GENERATED) >= 1);
+    assert!(count(telemetry::PAGES_PROCESSED) >= 2);
+    assert!(count(telemetry::BYTES_WRITTEN) > 0);
+    assert!(count(telemetry::ERRORS) >= 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pipeline_event_sinks() -> Result<()> {
+    use hozon::events::{ChannelSink, JsonLinesSink};
+
+    let test_dirs = setup_test_dirs("pipeline_event_sinks").await;
+    for chapter in ["01-001", "01-002", "02-003"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+    let (channel, mut events) = ChannelSink::new();
+    let log_path = test_dirs.target_dir.join("events.jsonl");
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Events".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .event_sinks(vec![
+            Arc::new(channel) as Arc<dyn EventSink>,
+            Arc::new(JsonLinesSink::new(&log_path)?),
+        ])
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        received.push(event);
+    }
+    assert_eq!(
+        received.first(),
+        Some(&PipelineEvent::AnalysisFinished {
+            chapters: 3,
+            pages: 3
+        })
+    );
+    assert_eq!(
+        received.get(1),
+        Some(&PipelineEvent::StructureFinished { volumes: 2 })
+    );
+    assert!(matches!(
+        received.last(),
+        Some(PipelineEvent::ConversionFinished { files: 2, .. })
+    ));
+    let finished: Vec<usize> = received
+        .iter()
+        .filter_map(|event| match event {
+            PipelineEvent::VolumeFinished {
+                volume_number,
+                path,
+                page_count,
+                ..
+            } => {
+                assert!(path.exists());
+                assert_eq!(*page_count, if *volume_number == 1 { 2 } else { 1 });
+                Some(*volume_number)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(finished.len(), 2);
+    assert_eq!(received.len(), 7);
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&log_path)?
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), received.len());
+    assert_eq!(lines[0]["event"], "analysis_finished");
+    assert_eq!(lines[0]["chapters"], 3);
+    assert_eq!(lines[6]["event"], "conversion_finished");
+
+    // Failures end the run with a failure event
+    let (channel, mut events) = ChannelSink::new();
+    let mut failing = config.clone();
+    failing.event_sinks = vec![Arc::new(channel)];
+    let empty = test_dirs.source_dir.join("empty");
+    tokio::fs::create_dir_all(&empty).await?;
+    failing.source_path = empty;
+    assert!(
+        failing
+            .convert_from_source(CoverOptions::None)
+            .await
+            .is_err()
+    );
+    let mut last = None;
+    while let Ok(event) = events.try_recv() {
+        last = Some(event);
+    }
+    assert!(matches!(last, Some(PipelineEvent::ConversionFailed { .. })));
+    Ok(())
+}
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn test_webhook_sink_posts_events() -> Result<()> {
+    use hozon::events::WebhookSink;
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/hook", listener.local_addr()?);
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    });
+
+    let sink = WebhookSink::new(url).with_body(Arc::new(|event: &PipelineEvent| {
+        Some(serde_json::json!({ "content": event.summary() }))
+    }));
+    sink.send(&PipelineEvent::StructureFinished { volumes: 4 });
+    let body = tokio::task::spawn_blocking(move || server.join().unwrap()).await?;
+    assert_eq!(body["content"], "Planned 4 volumes");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shutdown_finishes_current_volume() -> Result<()> {
+    use hozon::events::ChannelSink;
+
+    let test_dirs = setup_test_dirs("shutdown_current_volume").await;
+    for chapter in ["01-001", "02-002", "03-003"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+    let (sink, mut events) = ChannelSink::new();
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Shutdown".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::Name)
+        .throttle(ThrottleProfile::Low) // One volume at a time
+        .series_manifest(true)
+        .result_bundle(true)
+        .event_sinks(vec![Arc::new(sink) as Arc<dyn EventSink>])
+        .build()?;
+
+    let handle = HozonPipeline::new(config.clone()).spawn(CoverOptions::None);
+    while let Some(event) = events.recv().await {
+        if matches!(event, PipelineEvent::VolumeStarted { .. }) {
+            handle.shutdown();
+            break;
+        }
+    }
+    assert!(handle.is_shutting_down());
+    let report = timeout(LONG_TEST_TIMEOUT, handle.join())
+        .await
+        .expect("Test timed out")?;
+
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(report.files[0].volume_number, 1);
+    assert_valid_zip_file(&report.files[0].path).await;
+    assert_eq!(
+        report.skipped,
+        vec![
+            SkippedVolume {
+                volume_number: 2,
+                part_number: None
+            },
+            SkippedVolume {
+                volume_number: 3,
+                part_number: None
+            },
+        ]
+    );
+
+    // Incomplete runs don't publish a manifest
+    let output_dir = config.output_directory();
+    assert!(!output_dir.join("series.json").exists());
+    let bundle: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        output_dir.join("hozon-report.json"),
+    )?)
+    .unwrap();
+    assert_eq!(bundle["status"], "shut_down");
+    assert_eq!(bundle["conversion"]["skipped"][0]["volume_number"], 2);
+
+    // Without a shutdown, spawned conversions run to completion
+    let report = HozonPipeline::new(config)
+        .spawn(CoverOptions::None)
+        .join()
+        .await?;
+    assert_eq!(report.files.len(), 3);
+    assert!(report.skipped.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_conversion() -> Result<()> {
+    use hozon::events::ChannelSink;
+
+    let test_dirs = setup_test_dirs("pause_resume_conversion").await;
+    let pages = vec![
+        test_dirs.source_dir.join("001.jpg"),
+        test_dirs.source_dir.join("002.jpg"),
+    ];
+    for page in &pages {
+        create_dummy_color_image(page).await?;
+    }
+    let (sink, mut events) = ChannelSink::new();
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Paused".to_string()))
+        .target_path(test_dirs.target_dir.clone())
+        .event_sinks(vec![Arc::new(sink) as Arc<dyn EventSink>])
+        .build()?;
+
+    // The current-thread test runtime doesn't start the task before the first await
+    let handle = config.spawn_from_structured_data(vec![vec![pages]], CoverOptions::None);
+    handle.pause();
+    assert!(handle.is_paused());
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!handle.is_finished());
+    let mut started = false;
+    while let Ok(event) = events.try_recv() {
+        assert!(!matches!(event, PipelineEvent::VolumeFinished { .. }));
+        started |= matches!(event, PipelineEvent::VolumeStarted { .. });
+    }
+    assert!(started);
+
+    handle.resume();
+    assert!(!handle.is_paused());
+    let report = timeout(LONG_TEST_TIMEOUT, handle.join())
+        .await
+        .expect("Test timed out")?;
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(report.files[0].page_count, 2);
+    assert_valid_zip_file(&report.files[0].path).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missing_page_placeholders() -> Result<()> {
+    let test_dirs = setup_test_dirs("missing_page_placeholders").await;
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    for page in ["001.jpg", "002.jpg", "004.jpg", "005.jpg"] {
+        create_dummy_color_image(&chapter.join(page)).await?;
+    }
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Gaps".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .missing_pages(MissingPagePolicy::Placeholder)
+        .build()?;
+
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let file = &report.files[0];
+    assert_eq!(file.page_count, 5);
+    let placeholder = &file.pages[2];
+    assert_eq!(placeholder.chapter_page, 3);
+    assert_eq!(
+        placeholder.source.file_name().unwrap(),
+        "chapter_0001_page_0003.png"
+    );
+    // Rendered in memory; the name only marks its place
+    assert!(!placeholder.source.exists());
+    assert_eq!(file.pages[3].source, chapter.join("004.jpg"));
+
+    let archive = zip::ZipArchive::new(std::fs::File::open(&file.path)?).unwrap();
+    assert!(archive.file_names().any(|name| name == "page_003.png"));
+
+    // EPUBs number the placeholder within its chapter like the source pages
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Gaps EPUB".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .missing_pages(MissingPagePolicy::Placeholder)
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    let file = &report.files[0];
+    assert_eq!(file.page_count, 5);
+    let archive = zip::ZipArchive::new(std::fs::File::open(&file.path)?).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(
+        names
+            .iter()
+            .any(|name| name.contains("chapter_001") && name.ends_with("page_003.png")),
+        "{:?}",
+        names
+    );
+    assert!(
+        names
+            .iter()
+            .any(|name| name.contains("chapter_001") && name.ends_with("page_005.xhtml"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cover_break_confidence() -> Result<()> {
+    let test_dirs = setup_test_dirs("cover_break_confidence").await;
+    let source = &test_dirs.source_dir;
+    create_dummy_color_image(&source.join("001-Chapter_A").join("001.jpg")).await?;
+    create_dummy_grayscale_image(&source.join("002-Chapter_B").join("001.jpg")).await?;
+    // Half gray, half red: a borderline cover at a sensibility of 60%
+    let mixed = image::RgbImage::from_fn(100, 100, |x, _| {
+        if x < 50 {
+            image::Rgb([128, 128, 128])
+        } else {
+            image::Rgb([255, 0, 0])
+        }
+    });
+    let mixed_path = source.join("003-Chapter_C").join("001.png");
+    std::fs::create_dir_all(mixed_path.parent().unwrap())?;
+    mixed.save(&mixed_path)?;
+    create_dummy_color_image(&source.join("004-Chapter_D").join("001.jpg")).await?;
+
+    let mut config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Confidence".to_string()))
+        .source_path(source.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::ImageAnalysis)
+        .image_analysis_sensibility(60)
+        .build()?;
+    let structured = config.structure_from_source().await?;
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![2, 1, 1]);
+    let breaks = &structured.report.cover_breaks;
+    assert_eq!(breaks.len(), 2);
+    assert_eq!((breaks[0].index, breaks[1].index), (2, 3));
+    assert!(breaks[0].confidence > 0.0 && breaks[0].confidence < 0.5);
+    assert_eq!(breaks[1].confidence, 1.0);
+    assert!(breaks.iter().all(|cover_break| cover_break.applied));
+
+    // The borderline cover no longer starts a volume, but is still reported
+    config.image_analysis_min_confidence = 0.5;
+    let structured = config.structure_from_source().await?;
+    assert_eq!(structured.report.chapter_counts_per_volume, vec![3, 1]);
+    let breaks = &structured.report.cover_breaks;
+    assert_eq!(breaks.len(), 2);
+    assert!(!breaks[0].applied && breaks[1].applied);
+
+    config.image_analysis_min_confidence = 1.5;
+    assert!(config.structure_from_source().await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_compression_per_resource_type() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_compression").await;
+    for page in ["001.jpg", "002.jpg"] {
+        create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join(page)).await?;
+    }
+    let entry_methods = |path: &Path| {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let entry = archive.by_index(i).unwrap();
+                (entry.name().to_string(), entry.compression())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (compression, image_method) in [
+        (EpubCompression::default(), zip::CompressionMethod::Stored),
+        (
+            EpubCompression::deflate_all(),
+            zip::CompressionMethod::Deflated,
+        ),
+    ] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Compressed".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(FileFormat::Epub)
+            .strict_epub(true)
+            .epub_compression(compression)
+            .build()?;
+        let report = timeout(
+            LONG_TEST_TIMEOUT,
+            HozonPipeline::new(config)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let methods = entry_methods(&report.files[0].path);
+        assert_eq!(
+            methods[0],
+            ("mimetype".to_string(), zip::CompressionMethod::Stored)
+        );
+        let images: Vec<_> = methods
+            .iter()
+            .filter(|(n, _)| n.ends_with(".jpg"))
+            .collect();
+        assert!(images.len() >= 2);
+        assert!(images.iter().all(|(_, method)| *method == image_method));
+        assert!(
+            methods
+                .iter()
+                .filter(|(name, _)| name.ends_with(".xhtml") || name.ends_with(".opf"))
+                .all(|(_, method)| *method == zip::CompressionMethod::Deflated)
+        );
+
+        // Stored images are written on save; their bytes must survive
+        let mut archive =
+            zip::ZipArchive::new(std::fs::File::open(&report.files[0].path)?).unwrap();
+        let mut image = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name(&images[0].0).unwrap(), &mut image)?;
+        assert!(image::load_from_memory(&image).is_ok());
+    }
+
+    let invalid = EpubCompression {
+        level: Some(12),
+        ..Default::default()
+    };
+    assert!(
+        HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Invalid".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(FileFormat::Epub)
+            .epub_compression(invalid)
+            .build()?
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_image_format_normalization() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_image_format").await;
+    let chapter = test_dirs.source_dir.join("Chapter 1");
+    create_dummy_color_image(&chapter.join("001.jpg")).await?;
+    // A PNG page, and a PNG page saved with a `.jpg` extension
+    let png = image::RgbImage::from_pixel(100, 100, image::Rgb([0, 0, 255]));
+    png.save(chapter.join("002.png")).unwrap();
+    png.save_with_format(chapter.join("003.jpg"), image::ImageFormat::Png)
+        .unwrap();
+
+    let image_entries = |path: &Path| {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let names: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.contains("/images/") || name.contains("/chapters/"))
+            .filter(|name| !name.ends_with(".xhtml"))
+            .map(str::to_string)
+            .collect();
+        names
+            .into_iter()
+            .map(|name| {
+                let mut bytes = Vec::new();
+                std::io::Read::read_to_end(&mut archive.by_name(&name).unwrap(), &mut bytes)
+                    .unwrap();
+                (name, ImageFormat::from_bytes(&bytes))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for forced in [None, Some(ProcessedImageFormat::Jpeg)] {
+        let mut config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Formats".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(FileFormat::Epub)
+            .strict_epub(true)
+            .build()?;
+        config.epub_image_format = forced;
+        let report = timeout(
+            LONG_TEST_TIMEOUT,
+            HozonPipeline::new(config)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let entries = image_entries(&report.files[0].path);
+        assert_eq!(entries.len(), 4); // Cover and three pages
+        for (name, format) in &entries {
+            let format = format.expect("image entry");
+            assert!(
+                name.ends_with(&format!(".{}", format.extension())),
+                "{}",
+                name
+            );
+        }
+        let opf = get_epub_opf(&report.files[0].path).await;
+        match forced {
+            None => {
+                assert!(
+                    entries
+                        .iter()
+                        .any(|(name, _)| name.ends_with("page_003.png"))
+                );
+                assert!(opf.contains("image/png") && opf.contains("image/jpeg"));
+            }
+            Some(_) => {
+                assert!(entries.iter().all(|(_, f)| *f == Some(ImageFormat::Jpeg)));
+                assert!(!opf.contains("image/png"));
+            }
+        }
+    }
+
+    let conflicting = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Conflict".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .epub_image_format(ProcessedImageFormat::Png)
+        .image_processing(ImageProcessing {
+            output_format: Some(ProcessedImageFormat::WebP),
+            ..Default::default()
+        })
+        .build()?;
+    assert!(
+        conflicting
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_spine_follows_page_order_under_concurrency() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_spine_order").await;
+    // Pages of very different sizes take very different times to process, so reads and
+    // processing finish out of order
+    for chapter in 1..=3 {
+        for page in 1..=8u32 {
+            let size = if page % 3 == 0 { 820 } else { 20 + page * 10 };
+            let path = test_dirs
+                .source_dir
+                .join(format!("Chapter {}", chapter))
+                .join(format!("{:03}.png", page));
+            tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+            image::RgbImage::from_pixel(size, size, image::Rgb([page as u8 * 20, 0, 0]))
+                .save(&path)
+                .unwrap();
+        }
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Spine".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .runtime_limits(RuntimeLimits::new(4, 16)?)
+        .image_processing(ImageProcessing {
+            max_width: Some(800),
+            ..Default::default()
+        })
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let opf = get_epub_opf(&report.files[0].path).await;
+    let attribute = |tag: &str, name: &str| {
+        let start = tag.find(&format!("{}=\"", name))? + name.len() + 2;
+        Some(tag[start..start + tag[start..].find('"')?].to_string())
+    };
+    let hrefs: HashMap<String, String> = opf
+        .split("<item ")
+        .skip(1)
+        .filter_map(|tag| Some((attribute(tag, "id")?, attribute(tag, "href")?)))
+        .collect();
+    let spine: Vec<String> = opf
+        .split("<itemref ")
+        .skip(1)
+        .filter_map(|tag| hrefs.get(&attribute(tag, "idref")?).cloned())
+        .filter(|href| href.starts_with("chapters/"))
+        .collect();
+
+    let expected: Vec<String> = (1..=3)
+        .flat_map(|chapter| {
+            (1..=8)
+                .map(move |page| format!("chapters/chapter_{:03}/page_{:03}.xhtml", chapter, page))
+        })
+        .collect();
+    assert_eq!(spine, expected);
+
+    // Every page document shows the image read for its position
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&report.files[0].path)?).unwrap();
+    for (index, document) in expected.iter().enumerate() {
+        let xhtml = get_zip_entry(&report.files[0].path, &format!("OEBPS/{}", document)).await;
+        let source = attribute(&xhtml, "src").unwrap();
+        let image_path = format!("OEBPS/{}", source.trim_start_matches("../"));
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name(&image_path).unwrap(), &mut bytes)?;
+        let pixel = image::load_from_memory(&bytes).unwrap().to_rgb8()[(0, 0)];
+        assert_eq!(pixel[0], (index % 8 + 1) as u8 * 20, "{}", document);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_path_layouts() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_path_layout").await;
+    for chapter in ["Chapter 1", "Chapter 2"] {
+        for page in ["001.jpg", "002.jpg"] {
+            create_dummy_color_image(&test_dirs.source_dir.join(chapter).join(page)).await?;
+        }
+    }
+
+    for (layout, page, image, cover) in [
+        (
+            EpubPathLayout::Nested,
+            "OEBPS/chapters/chapter_002/page_001.xhtml",
+            "OEBPS/chapters/chapter_002/page_001.jpg",
+            "OEBPS/images/cover.jpg",
+        ),
+        (
+            EpubPathLayout::ByType,
+            "OEBPS/text/chapter_002_page_001.xhtml",
+            "OEBPS/images/chapter_002_page_001.jpg",
+            "OEBPS/images/cover.jpg",
+        ),
+        (
+            EpubPathLayout::Flat,
+            "OEBPS/chapter_002_page_001.xhtml",
+            "OEBPS/chapter_002_page_001.jpg",
+            "OEBPS/cover.jpg",
+        ),
+    ] {
+        let target_dir = test_dirs.target_dir.join(format!("{:?}", layout));
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Layout".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(target_dir)
+            .output_format(FileFormat::Epub)
+            .strict_epub(true)
+            .epub_layout(layout)
+            .build()?;
+        let report = timeout(
+            LONG_TEST_TIMEOUT,
+            HozonPipeline::new(config)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let path = &report.files[0].path;
+        let archive = zip::ZipArchive::new(std::fs::File::open(path)?).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        for entry in [page, image, cover] {
+            assert!(names.contains(&entry), "{:?}: missing {}", layout, entry);
+        }
+        let xhtml = get_zip_entry(path, page).await;
+        let to_root = "../".repeat(page.matches('/').count() - 1);
+        assert!(xhtml.contains(&format!(
+            "src=\"{}{}\"",
+            to_root,
+            image.trim_start_matches("OEBPS/")
+        )));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mobi_output() -> Result<()> {
+    let test_dirs = setup_test_dirs("mobi_output").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    std::fs::create_dir_all(test_dirs.source_dir.join("Chapter 2"))?;
+    image::RgbImage::from_pixel(60, 80, image::Rgb([10, 120, 200]))
+        .save(test_dirs.source_dir.join("Chapter 2").join("001.png"))
+        .unwrap();
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Kindle".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Mobi)
+        .embed_source_fingerprint(true)
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config.clone())
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let path = &report.files[0].path;
+    assert_eq!(path.extension().unwrap(), "mobi");
+    let bytes = std::fs::read(path)?;
+    assert_eq!(&bytes[60..68], b"BOOKMOBI");
+
+    let u32_at = |offset: usize| u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let record_count = u16::from_be_bytes([bytes[76], bytes[77]]) as usize;
+    let record = |index: usize| {
+        let start = u32_at(78 + 8 * index) as usize;
+        let end = if index + 1 < record_count {
+            u32_at(78 + 8 * (index + 1)) as usize
+        } else {
+            bytes.len()
+        };
+        &bytes[start..end]
+    };
+    let header = record(0);
+    assert_eq!(&header[16..20], b"MOBI");
+    let text_records = u16::from_be_bytes([header[8], header[9]]) as usize;
+    let first_image = u32::from_be_bytes(header[108..112].try_into().unwrap()) as usize;
+    assert_eq!(first_image, 1 + text_records);
+
+    let html: Vec<u8> = (1..=text_records)
+        .flat_map(|i| record(i).to_vec())
+        .collect();
+    let html = String::from_utf8(html).unwrap();
+    assert_eq!(html.matches("<mbp:pagebreak />").count(), 3);
+    assert!(html.contains("Chapter 2</a>"));
+    assert!(html.contains("<reference type=\"toc\""));
+
+    // One decodable image record per page, in reading order
+    let colors: Vec<_> = (first_image..first_image + 3)
+        .map(|i| image::load_from_memory(record(i)).unwrap().to_rgb8())
+        .collect();
+    assert_eq!(colors[2].get_pixel(0, 0), &image::Rgb([10, 120, 200]));
+
+    let outputs = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(outputs.outputs[0].state, OutputState::UpToDate);
+    Ok(())
+}
+
+#[cfg(feature = "cb7")]
+#[tokio::test]
+async fn test_cb7_output() -> Result<()> {
+    let test_dirs = setup_test_dirs("cb7_output").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Seven".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Cb7)
+        .embed_source_fingerprint(true)
+        .build()?;
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let path = test_dirs.target_dir.join("Seven").join("Seven.cb7");
+    let mut names = Vec::new();
+    let mut comic_info = String::new();
+    sevenz_rust::SevenZReader::open(&path, sevenz_rust::Password::empty())
+        .unwrap()
+        .for_each_entries(|entry, data| {
+            names.push(entry.name().to_string());
+            if entry.name() == "ComicInfo.xml" {
+                data.read_to_string(&mut comic_info)?;
+            }
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(names, ["page_001.jpg", "page_002.jpg", "ComicInfo.xml"]);
+    assert!(comic_info.contains("<Title>Seven</Title>"));
+    let outputs = config.check_outputs(&CoverOptions::None).await?;
+    assert_eq!(outputs.outputs[0].state, OutputState::UpToDate);
+
+    // Moving the source verifies the archive first
+    let done = test_dirs.test_dir.join("done");
+    let mut config = config;
+    config.source_cleanup = SourceCleanup::MoveSourceTo(done.clone());
+    timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+    assert!(done.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_image_fit_selects_stylesheet() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_image_fit").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    for (fit, rule) in [
+        (ImageFit::FitWidth, "max-width: 100%;"),
+        (ImageFit::FitHeight, "height: 100%; /* Fill the height"),
+        (ImageFit::FullBleed, "@page"),
+        (ImageFit::OriginalSize, "max-width: none;"),
+    ] {
+        let config = HozonConfig::builder()
+            .metadata(EbookMetadata::default_with_title("Fit".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.join(format!("{:?}", fit)))
+            .output_format(FileFormat::Epub)
+            .strict_epub(true)
+            .epub_image_fit(fit)
+            .build()?;
+        let report = timeout(
+            LONG_TEST_TIMEOUT,
+            HozonPipeline::new(config)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None),
+        )
+        .await
+        .expect("Test timed out")?;
+
+        let stylesheet = get_zip_entry(&report.files[0].path, "OEBPS/stylesheet.css").await;
+        assert!(stylesheet.contains(rule), "{:?}: {}", fit, stylesheet);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epub_page_style() -> Result<()> {
+    let test_dirs = setup_test_dirs("epub_page_style").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Styled".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .strict_epub(true)
+        .epub_image_fit(ImageFit::FullBleed)
+        .epub_page_style(PageStyle {
+            background_color: Some("#f4f1ea".to_string()),
+            margin: Some("0 2%".to_string()),
+        })
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let stylesheet = get_zip_entry(&report.files[0].path, "OEBPS/stylesheet.css").await;
+    assert!(stylesheet.contains("@page"));
+    assert!(stylesheet.contains("background-color: #f4f1ea;"));
+    assert!(stylesheet.contains("padding: 0 2%;"));
+
+    // Invalid values never reach the stylesheet
+    let invalid = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Styled".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Epub)
+        .epub_page_style(PageStyle {
+            background_color: Some("red} img {display:none".to_string()),
+            margin: None,
+        })
+        .build()?;
+    assert!(
+        invalid
+            .preflight_check(HozonExecutionMode::FromSource)
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chapter_intro_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("chapter_intro_pages").await;
+    for chapter in ["01 Arrival", "02 Departure"] {
+        let chapter_dir = test_dirs.source_dir.join(chapter);
+        std::fs::create_dir_all(&chapter_dir)?;
+        for page in ["001.png", "002.png"] {
+            image::RgbImage::from_pixel(60, 90, image::Rgb([200, 200, 200]))
+                .save(chapter_dir.join(page))
+                .unwrap();
+        }
+    }
+
+    let convert = |format: FileFormat| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(format.extension());
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Intro".to_string()))
+                .source_path(source)
+                .target_path(target)
+                .output_format(format)
+                .strict_epub(true)
+                .chapter_intro(ChapterIntro::default())
+                .build()?;
+            timeout(
+                LONG_TEST_TIMEOUT,
+                HozonPipeline::new(config)
+                    .collect()
+                    .await?
+                    .structure()
+                    .await?
+                    .generate(CoverOptions::None),
+            )
+            .await
+            .expect("Test timed out")
+        }
+    };
+
+    // Archives get one rendered page in front of each chapter, sized like its pages
+    let report = convert(FileFormat::Cbz).await?;
+    assert_eq!(report.files[0].page_count, 6);
+    assert_eq!(report.files[0].pages[3].chapter, 2);
+    assert_eq!(report.files[0].pages[3].chapter_page, 1);
+    // Rendered in memory, so no file is behind the page's source
+    assert!(!report.files[0].pages[3].source.exists());
+    let file = std::fs::File::open(&report.files[0].path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut intro = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name("page_001.png")?, &mut intro)?;
+    let intro = image::load_from_memory(&intro).unwrap();
+    assert_eq!((intro.width(), intro.height()), (60, 90));
+
+    // EPUBs get a text page that takes the chapter's table of contents entry
+    let report = convert(FileFormat::Epub).await?;
+    let epub = &report.files[0].path;
+    let intro = get_zip_entry(epub, "OEBPS/chapters/chapter_002/intro.xhtml").await;
+    assert!(intro.contains("<h1>Chapter 2</h1>"));
+    assert!(intro.contains("<p>02 Departure</p>"));
+    let nav = get_zip_entry(epub, "OEBPS/nav.xhtml").await;
+    assert!(nav.contains("chapters/chapter_001/intro.xhtml"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kepub_output() -> Result<()> {
+    let test_dirs = setup_test_dirs("kepub_output").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+
+    let mut metadata = EbookMetadata::default_with_title("Kobo".to_string());
+    metadata.series = Some("Kobo Series".to_string());
+    let config = HozonConfig::builder()
+        .metadata(metadata)
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Kepub)
+        .strict_epub(true)
+        .chapter_intro(ChapterIntro::default())
+        .embed_source_fingerprint(true)
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config.clone())
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::None),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    let kepub = &report.files[0].path;
+    assert!(kepub.to_string_lossy().ends_with("Kobo.kepub.epub"));
+    let page = get_zip_entry(kepub, "OEBPS/chapters/chapter_001/page_002.xhtml").await;
+    assert!(page.contains("<div id=\"book-columns\">"));
+    assert!(page.contains("<span class=\"koboSpan\" id=\"kobo.1.1\"><img"));
+    let intro = get_zip_entry(kepub, "OEBPS/chapters/chapter_001/intro.xhtml").await;
+    assert!(intro.contains("<p><span class=\"koboSpan\" id=\"kobo.2.1\">Chapter 1</span></p>"));
+    let opf = get_epub_opf(kepub).await;
+    assert!(opf.contains("<meta name=\"calibre:series\" content=\"Kobo Series\"/>"));
+    assert!(opf.contains("belongs-to-collection"));
+
+    // Outputs are recognized by their double extension
+    assert!(
+        config
+            .check_outputs(&CoverOptions::None)
+            .await?
+            .is_up_to_date()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_directory_output() -> Result<()> {
+    let test_dirs = setup_test_dirs("directory_output").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+    let cover_path = test_dirs.test_dir.join("cover.jpg");
+    create_dummy_grayscale_image(&cover_path).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Loose".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Directory)
+        .source_cleanup(SourceCleanup::MoveSourceTo(test_dirs.test_dir.join("done")))
+        .build()?;
+    let report = timeout(
+        LONG_TEST_TIMEOUT,
+        HozonPipeline::new(config)
+            .collect()
+            .await?
+            .structure()
+            .await?
+            .generate(CoverOptions::Single(cover_path)),
+    )
+    .await
+    .expect("Test timed out")?;
+
+    // Verified before the source was moved
+    let volume = &report.files[0].path;
+    assert_eq!(volume, &test_dirs.target_dir.join("Loose").join("Loose"));
+    assert!(test_dirs.test_dir.join("done").exists());
+    let mut names: Vec<String> = std::fs::read_dir(volume)?
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["000_cover.jpg", "001.jpg", "002.jpg", "003.jpg"]);
+
+    // Nothing is left of the staging directory
+    let siblings = std::fs::read_dir(volume.parent().unwrap())?.count();
+    assert_eq!(siblings, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_first_page_side() -> Result<()> {
+    let test_dirs = setup_test_dirs("first_page_side").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    let cover_path = test_dirs.test_dir.join("cover.jpg");
+    create_dummy_grayscale_image(&cover_path).await?;
+
+    let convert = |format: FileFormat, side: PageSide, cover: CoverOptions, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Sides".to_string()))
+                .source_path(source)
+                .target_path(target)
+                .output_format(format)
+                .first_page_side(side)
+                .build()?;
+            let report = timeout(
+                LONG_TEST_TIMEOUT,
+                HozonPipeline::new(config)
+                    .collect()
+                    .await?
+                    .structure()
+                    .await?
+                    .generate(cover),
+            )
+            .await
+            .expect("Test timed out")?;
+            Result::Ok(report.files[0].clone())
+        }
+    };
+
+    // The cover page puts the content on a verso; a blank page moves it to the recto
+    let file = convert(
+        FileFormat::Cbz,
+        PageSide::Recto,
+        CoverOptions::Single(cover_path.clone()),
+        "recto",
+    )
+    .await?;
+    assert_eq!(file.page_count, 3);
+    // Rendered in memory, so no file is behind the page's source
+    assert_eq!(file.pages[0].source.file_name().unwrap(), "blank.png");
+    assert!(!file.pages[0].source.exists());
+    // Already on the verso
+    let file = convert(
+        FileFormat::Cbz,
+        PageSide::Verso,
+        CoverOptions::Single(cover_path),
+        "verso",
+    )
+    .await?;
+    assert_eq!(file.page_count, 2);
+
+    // EPUB covers aren't pages, so the content starts on a recto
+    let file = convert(
+        FileFormat::Epub,
+        PageSide::Verso,
+        CoverOptions::None,
+        "epub",
+    )
+    .await?;
+    let opf = get_epub_opf(&file.path).await;
+    let spine = &opf[opf.find("<spine").unwrap()..];
+    let first = spine.find("<itemref").unwrap();
+    assert!(spine[first..].starts_with("<itemref idref=\"id_chapters_blank.xhtml\""));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_archive_sources() -> Result<()> {
+    use std::io::Write;
+
+    let test_dirs = setup_test_dirs("archive_sources").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    let page = test_dirs.test_dir.join("page.jpg");
+    create_dummy_color_image(&page).await?;
+
+    // Pages in a folder of the archive, next to files that aren't pages
+    let archive_path = test_dirs.source_dir.join("Chapter 2.cbz");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path)?);
+    let options = zip::write::SimpleFileOptions::default();
+    for name in [
+        "Chapter 2/001.jpg",
+        "Chapter 2/002.jpg",
+        "__MACOSX/._001.jpg",
+    ] {
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&std::fs::read(&page)?)?;
+    }
+    writer.start_file("ComicInfo.xml", options).unwrap();
+    writer.write_all(b"<ComicInfo/>")?;
+    writer.finish().unwrap();
+
+    let convert = |source: PathBuf, name: &str| {
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Archives".to_string()))
+                .source_path(source)
+                .target_path(target)
+                .output_format(FileFormat::Directory)
+                .build()?;
+            let collected = config.analyze_source().await?;
+            let report = HozonPipeline::new(config)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None)
+                .await?;
+            let volume = &report.files[0].path;
+            let pages = std::fs::read_dir(volume)?.count();
+            Ok::<_, hozon::error::Error>((collected, pages))
+        }
+    };
+
+    // Archives are chapters next to the chapter directories
+    let (collected, pages) = convert(test_dirs.source_dir.clone(), "deep").await?;
+    assert_eq!(collected.chapters_with_pages.len(), 2);
+    assert_eq!(collected.chapters_with_pages[1].len(), 2);
+    assert!(
+        !collected
+            .report
+            .findings
+            .iter()
+            .any(|f| matches!(f, AnalyzeFinding::NestedArchive { .. }))
+    );
+    assert_eq!(pages, 4);
+
+    // An archive as the source is a single chapter
+    let (collected, pages) = convert(archive_path.clone(), "single").await?;
+    assert_eq!(collected.report.recommended_depth, CollectionDepth::Shallow);
+    assert_eq!(pages, 2);
+
+    // A directory of only archives is recognized as `Deep`
+    std::fs::remove_dir_all(test_dirs.source_dir.join("Chapter 1"))?;
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Archives".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .collection_depth(CollectionDepth::Shallow)
+        .build()?;
+    let collected = config.analyze_source().await?;
+    assert_eq!(collected.report.recommended_depth, CollectionDepth::Deep);
+
+    // A broken archive is skipped, and the extractions are removed with the config
+    std::fs::write(test_dirs.source_dir.join("Chapter 3.cbz"), b"PK")?;
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Archives".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .build()?;
+    let collected = config.analyze_source().await?;
+    assert_eq!(collected.chapters_with_pages.len(), 1);
+    assert!(collected.report.findings.iter().any(|f| matches!(
+        f,
+        AnalyzeFinding::UnreadableArchive { path, .. } if path.ends_with("Chapter 3.cbz")
+    )));
+    let extracted = collected.chapters_with_pages[0][0].clone();
+    assert!(extracted.is_file());
+    drop(config);
+    assert!(!extracted.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_volume_links() -> Result<()> {
+    let test_dirs = setup_test_dirs("volume_links").await;
+    for chapter in ["Chapter 1", "Chapter 2", "Chapter 3"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+
+    let convert = |format: FileFormat, notes: NotesFormat, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let config = HozonConfig::builder()
+                .metadata(EbookMetadata::default_with_title("Linked".to_string()))
+                .source_path(source)
+                .target_path(target.clone())
+                .output_format(format)
+                .comic_info_notes(notes)
+                .volume_grouping_strategy(VolumeGroupingStrategy::Manual)
+                .volume_sizes_override(vec![1, 1, 1])
+                .volume_links(true)
+                .build()?;
+            config.convert_from_source(CoverOptions::None).await?;
+            Ok::<_, hozon::error::Error>(target.join("Linked"))
+        }
+    };
+
+    // The middle volume links both neighbours, the first one only the next
+    let output_dir = convert(FileFormat::Cbz, NotesFormat::default(), "lines").await?;
+    let notes = get_comic_info_xml(&output_dir.join("Linked - Volume 2.cbz")).await;
+    assert!(notes.contains("Previous volume: Linked - Volume 1 (Linked - Volume 1.cbz)"));
+    assert!(notes.contains("Next volume: Linked - Volume 3 (Linked - Volume 3.cbz)"));
+    let notes = get_comic_info_xml(&output_dir.join("Linked - Volume 1.cbz")).await;
+    assert!(!notes.contains("Previous volume"));
+    assert!(notes.contains("Next volume: Linked - Volume 2"));
+
+    let output_dir = convert(FileFormat::Epub, NotesFormat::default(), "epub").await?;
+    let opf = get_epub_opf(&output_dir.join("Linked - Volume 3.epub")).await;
+    assert!(opf.contains(r#"<meta name="hozon:previous_volume" content="Linked - Volume 2"/>"#));
+    assert!(
+        opf.contains(
+            r#"<meta name="hozon:previous_volume_file" content="Linked - Volume 2.epub"/>"#
+        )
+    );
+    assert!(!opf.contains("hozon:next_volume"));
+
+    let output_dir = convert(FileFormat::Cbz, NotesFormat::Json, "json").await?;
+    let xml = get_comic_info_xml(&output_dir.join("Linked - Volume 3.cbz")).await;
+    let notes = xml
+        .split("<Notes>")
+        .nth(1)
+        .and_then(|rest| rest.split("</Notes>").next())
+        .unwrap()
+        .replace("&quot;", "\"");
+    let notes: serde_json::Value = serde_json::from_str(&notes).expect("Notes are JSON");
+    assert_eq!(notes["previous_volume"]["volume_number"], 2);
+    assert_eq!(
+        notes["previous_volume"]["file_name"],
+        "Linked - Volume 2.cbz"
+    );
+    assert!(notes["next_volume"].is_null());
+    Ok(())
+}
+
+#[cfg(feature = "cbr")]
+#[tokio::test]
+async fn test_cbr_sources() -> Result<()> {
+    use std::io::Write;
+
+    let test_dirs = setup_test_dirs("cbr_sources").await;
+    let page = test_dirs.test_dir.join("page.jpg");
+    create_dummy_color_image(&page).await?;
+
+    // `bsdtar` detects the format from the contents, so a ZIP stands in for a RAR here
+    let archive_path = test_dirs.source_dir.join("Chapter 1.cbr");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path)?);
+    let options = zip::write::SimpleFileOptions::default();
+    for name in [
+        "Chapter 1/001.jpg",
+        "Chapter 1/002.jpg",
+        "Chapter 1/.thumb.jpg",
+    ] {
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&std::fs::read(&page)?)?;
+    }
+    // A symlink to a page outside the archive isn't followed
+    writer
+        .add_symlink("Chapter 1/003.jpg", page.to_str().unwrap(), options)
+        .unwrap();
+    writer.finish().unwrap();
+    // A broken archive is skipped
+    std::fs::write(test_dirs.source_dir.join("Chapter 2.cbr"), b"Rar!")?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Rar".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .build()?;
+    let collected = config.analyze_source().await?;
+    let unreadable = |path: &std::path::Path| {
+        collected.report.findings.iter().any(|f| {
+            matches!(f, AnalyzeFinding::UnreadableArchive { path: archive, .. } if archive == path)
+        })
+    };
+    assert!(unreadable(&test_dirs.source_dir.join("Chapter 2.cbr")));
+    if unreadable(&archive_path) {
+        // Neither `unrar` nor `bsdtar` is installed
+        return Ok(());
+    }
+    assert_eq!(collected.chapters_with_pages.len(), 1);
+    assert_eq!(collected.chapters_with_pages[0].len(), 2);
+
+    config.convert_from_source(CoverOptions::None).await?;
+    assert_valid_zip_file(&test_dirs.target_dir.join("Rar").join("Rar.cbz")).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_file_name_collisions() -> Result<()> {
+    let test_dirs = setup_test_dirs("file_name_collisions").await;
+    for chapter in ["Chapter 1", "Chapter 2", "Chapter 3"] {
+        create_dummy_color_image(&test_dirs.source_dir.join(chapter).join("001.jpg")).await?;
+    }
+
+    // Every chapter renders to the same file name
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Collide".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_grouping_strategy(VolumeGroupingStrategy::PerChapter)
+        .file_name_template("{series}")
+        .result_bundle(true)
+        .build()?;
+    let report = HozonPipeline::new(config)
+        .collect()
+        .await?
+        .structure()
+        .await?
+        .generate(CoverOptions::None)
+        .await?;
+
+    let output_dir = test_dirs.target_dir.join("Collide");
+    let paths: Vec<PathBuf> = report.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        paths,
+        [
+            output_dir.join("Collide.cbz"),
+            output_dir.join("Collide (2).cbz"),
+            output_dir.join("Collide (3).cbz"),
+        ]
+    );
+    for path in &paths {
+        assert_valid_zip_file(path).await;
+    }
+
+    let bundle: serde_json::Value = serde_json::from_str(
+        &tokio::fs::read_to_string(output_dir.join("hozon-report.json")).await?,
+    )
+    .expect("Report is valid JSON");
+    assert_eq!(bundle["warnings"].as_array().unwrap().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_foreign_output_policy() -> Result<()> {
+    let test_dirs = setup_test_dirs("foreign_output_policy").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+
+    // Two series with the same title, told apart by their authors
+    let config = |author: &str, policy: ForeignOutputPolicy| {
+        let mut metadata = EbookMetadata::default_with_title("Twin".to_string());
+        metadata.authors = vec![author.to_string()];
+        HozonConfig::builder()
+            .metadata(metadata)
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .embed_source_fingerprint(true)
+            .foreign_output_policy(policy)
+            .result_bundle(true)
+            .build()
+    };
+    config("Alice", ForeignOutputPolicy::Fail)?
+        .convert_from_source(CoverOptions::None)
+        .await?;
+
+    let result = config("Bob", ForeignOutputPolicy::Fail)?
+        .convert_from_source(CoverOptions::None)
+        .await;
+    assert!(
+        matches!(&result, Err(hozon::error::Error::ForeignOutputs(_, message)) if message.contains("Twin.cbz")),
+        "{:?}",
+        result
+    );
+
+    // Outputs of the same series are no reason to stop
+    config("Alice", ForeignOutputPolicy::Fail)?
+        .convert_from_source(CoverOptions::None)
+        .await?;
+
+    config("Bob", ForeignOutputPolicy::Warn)?
+        .convert_from_source(CoverOptions::None)
+        .await?;
+    let output_dir = test_dirs.target_dir.join("Twin");
+    let bundle: serde_json::Value = serde_json::from_str(
+        &tokio::fs::read_to_string(output_dir.join("hozon-report.json")).await?,
+    )
+    .expect("Report is valid JSON");
+    let warnings = bundle["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].as_str().unwrap().contains("another series"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_to_directory() -> Result<()> {
+    let test_dirs = setup_test_dirs("extract_to_directory").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+    let cover_path = test_dirs.target_dir.join("cover.jpg");
+    create_dummy_grayscale_image(&cover_path).await?;
+
+    let mut metadata = EbookMetadata::default_with_title("Round Trip".to_string());
+    metadata.series = Some("Trips".to_string());
+    metadata.authors = vec!["Alice".to_string(), "Bob".to_string()];
+    for format in [FileFormat::Cbz, FileFormat::Epub] {
+        let config = HozonConfig::builder()
+            .metadata(metadata.clone())
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone())
+            .output_format(format)
+            .comic_info_chapter_map(true)
+            .build()?;
+        config
+            .convert_from_source(CoverOptions::Single(cover_path.clone()))
+            .await?;
+        let ebook = config
+            .output_directory()
+            .join(format.output_name("Round Trip"));
+
+        let extract_config = HozonConfig::builder()
+            .target_path(test_dirs.target_dir.join(format!("extracted-{:?}", format)))
+            .build()?;
+        let extracted = extract_config.extract_to_directory(&ebook).await?;
+        assert_eq!(
+            extracted.directory,
+            test_dirs
+                .target_dir
+                .join(format!("extracted-{:?}", format))
+                .join("Round Trip")
+        );
+        assert_eq!(extracted.metadata.title, "Round Trip", "{:?}", format);
+        assert_eq!(extracted.metadata.series.as_deref(), Some("Trips"));
+        assert_eq!(extracted.metadata.authors, ["Alice", "Bob"]);
+        assert_eq!(extracted.chapter_titles, ["Chapter 1", "Chapter 2"]);
+        assert_eq!(
+            extracted.chapters[0],
+            [
+                extracted.directory.join("001 Chapter 1").join("001.jpg"),
+                extracted.directory.join("001 Chapter 1").join("002.jpg"),
+            ]
+        );
+        assert_eq!(extracted.chapters[1].len(), 1);
+        for page in extracted.chapters.iter().flatten() {
+            assert!(page.is_file());
+        }
+        let cover = extracted.cover.expect("The cover is extracted");
+        assert_eq!(cover, extracted.directory.join("cover.jpg"));
+        let metadata_file = match format {
+            FileFormat::Cbz => "ComicInfo.xml",
+            _ => "metadata.opf",
+        };
+        assert!(extracted.directory.join(metadata_file).is_file());
+
+        // The directory is only written once
+        let again = extract_config.extract_to_directory(&ebook).await;
+        assert!(matches!(again, Err(hozon::error::Error::InvalidPath(..))));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rescan_flat_volume() -> Result<()> {
+    let test_dirs = setup_test_dirs("rescan_flat_volume").await;
+    for chapter in ["Chapter 1", "Chapter 2"] {
+        for page in 1..=3 {
+            let path = test_dirs
+                .source_dir
+                .join(chapter)
+                .join(format!("{:03}.jpg", page));
+            create_dummy_color_image(&path).await?;
+        }
+    }
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Rescanned".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .output_format(FileFormat::Directory)
+        .volume_grouping_strategy(VolumeGroupingStrategy::Flat)
+        .source_change_policy(SourceChangePolicy::Rescan)
+        .build()?;
+    let collected = HozonPipeline::new(config).collect().await?;
+
+    // A page arrives in each chapter of the single flat volume after the analysis
+    for chapter in ["Chapter 1", "Chapter 2"] {
+        let path = test_dirs.source_dir.join(chapter).join("004.jpg");
+        create_dummy_color_image(&path).await?;
+    }
+    let report = collected
+        .structure()
+        .await?
+        .generate(CoverOptions::None)
+        .await?;
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(std::fs::read_dir(&report.files[0].path)?.count(), 8);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_encrypted_archive_source() -> Result<()> {
+    use std::io::Write;
+
+    let test_dirs = setup_test_dirs("encrypted_archive_source").await;
+    let page = test_dirs.test_dir.join("page.jpg");
+    create_dummy_color_image(&page).await?;
+    let archive_path = test_dirs.source_dir.join("Chapter 1.cbz");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive_path)?);
+    let encrypted = zip::write::SimpleFileOptions::default()
+        .with_aes_encryption(zip::AesMode::Aes256, "secret");
+    for name in ["001.jpg", "002.jpg"] {
+        writer.start_file(name, encrypted).unwrap();
+        writer.write_all(&std::fs::read(&page)?)?;
+    }
+    // Unencrypted entries next to encrypted ones are read as they are
+    writer
+        .start_file("003.jpg", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    writer.write_all(&std::fs::read(&page)?)?;
+    writer.finish().unwrap();
+
+    let analyze = |password: Option<&str>| {
+        let mut builder = HozonConfig::builder();
+        builder
+            .metadata(EbookMetadata::default_with_title("Encrypted".to_string()))
+            .source_path(test_dirs.source_dir.clone())
+            .target_path(test_dirs.target_dir.clone());
+        if let Some(password) = password {
+            builder.input_password(password);
+        }
+        async move { builder.build()?.analyze_source().await }
+    };
+
+    let collected = analyze(Some("secret")).await?;
+    assert_eq!(collected.chapters_with_pages.len(), 1);
+    assert_eq!(collected.chapters_with_pages[0].len(), 3);
+
+    // Without the right password the archive is skipped
+    for password in [None, Some("wrong")] {
+        let collected = analyze(password).await?;
+        assert!(collected.chapters_with_pages.is_empty());
+        assert!(
+            collected
+                .report
+                .findings
+                .iter()
+                .any(|f| matches!(f, AnalyzeFinding::UnreadableArchive { .. }))
+        );
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_directory_output_links_pages() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let test_dirs = setup_test_dirs("directory_output_links_pages").await;
+    let source_page = test_dirs.source_dir.join("Chapter 1").join("001.jpg");
+    create_dummy_color_image(&source_page).await?;
+    let png_page = test_dirs.source_dir.join("Chapter 2").join("001.png");
+    create_dummy_color_image(&png_page).await?;
+    let animated_page = test_dirs.source_dir.join("Chapter 3").join("001.png");
+    std::fs::create_dir_all(animated_page.parent().unwrap())?;
+    std::fs::write(&animated_page, create_apng(40, 60))?;
+
+    let convert = |permissions: Option<u32>, name: &str| {
+        let source = test_dirs.source_dir.clone();
+        let target = test_dirs.target_dir.join(name);
+        async move {
+            let mut builder = HozonConfig::builder();
+            builder
+                .metadata(EbookMetadata::default_with_title("Linked".to_string()))
+                .source_path(source)
+                .target_path(target)
+                .output_format(FileFormat::Directory)
+                .link_pages(true);
+            if let Some(permissions) = permissions {
+                builder.output_permissions(permissions);
+            }
+            let report = HozonPipeline::new(builder.build()?)
+                .collect()
+                .await?
+                .structure()
+                .await?
+                .generate(CoverOptions::None)
+                .await?;
+            Ok::<_, hozon::error::Error>(report.files[0].path.clone())
+        }
+    };
+
+    // Pages share the data of their source files
+    let volume = convert(None, "linked").await?;
+    let inode = |path: &std::path::Path| std::fs::metadata(path).unwrap().ino();
+    assert_eq!(inode(&volume.join("001.jpg")), inode(&source_page));
+    assert_eq!(std::fs::metadata(&source_page)?.nlink(), 2);
+    assert_eq!(inode(&volume.join("002.png")), inode(&png_page));
+    // Animated pages are reduced to their first frame instead
+    assert_ne!(inode(&volume.join("003.png")), inode(&animated_page));
+    assert!(
+        !std::fs::read(volume.join("003.png"))?
+            .windows(4)
+            .any(|w| w == b"acTL")
+    );
+
+    // Permissions would apply to the source files as well, so the pages are copied
+    let volume = convert(Some(0o600), "copied").await?;
+    assert_ne!(inode(&volume.join("001.jpg")), inode(&source_page));
+    Ok(())
+}
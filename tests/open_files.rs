@@ -0,0 +1,89 @@
+//! Tests for the process-wide open file budget.
+//!
+//! The budget is shared by every conversion in the process, so these tests live in their
+//! own binary to keep the lowered budget away from the other tests.
+
+use hozon::error::Result;
+use hozon::prelude::*;
+use tokio::time::timeout;
+
+mod common;
+use common::{LONG_TEST_TIMEOUT, assert_valid_zip_file, create_dummy_color_image, setup_test_dirs};
+
+/// Counts the file descriptors the process has open.
+#[cfg(target_os = "linux")]
+fn open_file_count() -> usize {
+    std::fs::read_dir("/proc/self/fd").map_or(0, |entries| entries.count())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_more_volumes_than_open_file_budget() -> Result<()> {
+    let test_dirs = setup_test_dirs("open_file_budget").await;
+    for chapter in 1..=6 {
+        let chapter_dir = test_dirs.source_dir.join(format!("Chapter {}", chapter));
+        for page in 1..=3 {
+            create_dummy_color_image(&chapter_dir.join(format!("{:03}.jpg", page))).await?;
+        }
+    }
+
+    hozon::runtime::set_open_file_budget(2);
+    assert_eq!(hozon::runtime::open_file_budget(), 2);
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Budget".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .volume_sizes_override(vec![1; 6])
+        .runtime_limits(RuntimeLimits::new(6, 6)?)
+        .lock_output_directory(true)
+        .series_manifest(true)
+        .build()?;
+
+    #[cfg(target_os = "linux")]
+    let (baseline, peak, sampling) = (
+        open_file_count(),
+        std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true)),
+    );
+    #[cfg(target_os = "linux")]
+    let sampler = {
+        let (peak, sampling) = (peak.clone(), sampling.clone());
+        std::thread::spawn(move || {
+            while sampling.load(std::sync::atomic::Ordering::SeqCst) {
+                peak.fetch_max(open_file_count(), std::sync::atomic::Ordering::SeqCst);
+                std::thread::yield_now();
+            }
+        })
+    };
+
+    let result = timeout(
+        LONG_TEST_TIMEOUT,
+        config.convert_from_source(CoverOptions::None),
+    )
+    .await
+    .expect("Conversion with a small open file budget timed out");
+
+    #[cfg(target_os = "linux")]
+    {
+        sampling.store(false, std::sync::atomic::Ordering::SeqCst);
+        sampler.join().expect("Sampler thread panicked");
+        // The sampler's own directory handle is the only other file opened meanwhile
+        let peak = peak.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(
+            peak <= baseline + 2 + 1,
+            "{} files were open at once with a budget of 2 (baseline {})",
+            peak,
+            baseline
+        );
+    }
+
+    result?;
+    for volume in 1..=6 {
+        let path = test_dirs
+            .target_dir
+            .join("Budget")
+            .join(format!("Budget - Volume {}.cbz", volume));
+        assert_valid_zip_file(&path).await;
+    }
+    Ok(())
+}
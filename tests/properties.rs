@@ -0,0 +1,183 @@
+//! Property-based tests for sorting and grouping.
+//!
+//! Asserts invariants over generated chapter names and volume layouts instead of
+//! hand-picked examples: comparators are total orders, sorting doesn't depend on the
+//! input order, and structuring places every chapter in exactly one volume without
+//! reordering it.
+
+use hozon::collector::{Collector, sort_spec_comparator};
+use hozon::path_utils::compare_names_natural;
+use hozon::prelude::*;
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+
+/// Runs `future` to completion on a fresh single-threaded runtime.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// Chapter directory names in the shapes found in real sources.
+fn chapter_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (0u32..500).prop_map(|n| format!("Chapter {:03}", n)),
+        (0u32..500, 0u32..10).prop_map(|(n, part)| format!("Chapter {}.{}", n, part)),
+        (0u32..500, "[a-cA-C]?").prop_map(|(n, part)| format!("{:03}{}.jpg", n, part)),
+        (1u32..20, 0u32..300).prop_map(|(v, c)| format!("{:02}-{:03}", v, c)),
+        "[A-Za-z _]{1,12}",
+        "[A-Za-z0-9 ._-]{1,16}",
+    ]
+}
+
+/// One page per chapter, as the page lists structuring receives.
+fn chapters_of(names: &[String]) -> Vec<Vec<PathBuf>> {
+    names
+        .iter()
+        .map(|name| vec![PathBuf::from("source").join(name).join("001.jpg")])
+        .collect()
+}
+
+fn config(strategy: VolumeGroupingStrategy) -> HozonConfigBuilder {
+    let mut builder = HozonConfig::builder();
+    builder
+        .metadata(EbookMetadata::default_with_title("Properties".to_string()))
+        .target_path(PathBuf::from("./output"))
+        .volume_grouping_strategy(strategy);
+    builder
+}
+
+/// The chapters of `structured` in output order.
+fn placed_chapters(structured: &StructuredContent) -> Vec<Vec<PathBuf>> {
+    structured
+        .volumes_with_chapters_and_pages
+        .iter()
+        .flatten()
+        .cloned()
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn natural_order_is_total(a in chapter_name(), b in chapter_name(), c in chapter_name()) {
+        prop_assert_eq!(compare_names_natural(&a, &a), Ordering::Equal);
+        prop_assert_eq!(compare_names_natural(&a, &b), compare_names_natural(&b, &a).reverse());
+        if compare_names_natural(&a, &b) != Ordering::Greater
+            && compare_names_natural(&b, &c) != Ordering::Greater
+        {
+            prop_assert_ne!(compare_names_natural(&a, &c), Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn number_order_is_total(a in chapter_name(), b in chapter_name(), c in chapter_name()) {
+        let (a, b, c) = (PathBuf::from(a), PathBuf::from(b), PathBuf::from(c));
+        let compare = Collector::sort_name_by_number_default;
+        prop_assert_eq!(compare(&a, &b), compare(&b, &a).reverse());
+        if compare(&a, &b) != Ordering::Greater && compare(&b, &c) != Ordering::Greater {
+            prop_assert_ne!(compare(&a, &c), Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn sorting_ignores_input_order(
+        names in prop::collection::vec(chapter_name(), 0..40),
+        spec in prop_oneof![
+            Just(SortSpec::Numeric),
+            Just(SortSpec::Natural),
+            Just(SortSpec::Lexicographic),
+        ],
+    ) {
+        let comparator = sort_spec_comparator(&spec).unwrap();
+        let mut sorted: Vec<PathBuf> = names.iter().map(PathBuf::from).collect();
+        sorted.sort_by(comparator.as_ref());
+        let mut reversed: Vec<PathBuf> = names.iter().rev().map(PathBuf::from).collect();
+        reversed.sort_by(comparator.as_ref());
+
+        if spec == SortSpec::Numeric {
+            // Equal numbers keep their input order; the numbers themselves must match
+            let is_sorted = |paths: &[PathBuf]| {
+                paths
+                    .windows(2)
+                    .all(|pair| comparator(&pair[0], &pair[1]) != Ordering::Greater)
+            };
+            prop_assert!(is_sorted(&sorted) && is_sorted(&reversed));
+        } else {
+            prop_assert_eq!(sorted, reversed);
+        }
+    }
+
+    #[test]
+    fn numeric_sorting_is_stable(names in prop::collection::vec(chapter_name(), 0..40)) {
+        let paths: Vec<PathBuf> = names.iter().map(PathBuf::from).collect();
+        let mut sorted: Vec<(usize, PathBuf)> = paths.into_iter().enumerate().collect();
+        sorted.sort_by(|(_, a), (_, b)| Collector::sort_name_by_number_default(a, b));
+        for pair in sorted.windows(2) {
+            if Collector::sort_name_by_number_default(&pair[0].1, &pair[1].1) == Ordering::Equal {
+                prop_assert!(pair[0].0 < pair[1].0);
+            }
+        }
+    }
+
+    #[test]
+    fn manual_grouping_places_every_chapter_once_in_order(
+        names in prop::collection::vec(chapter_name(), 1..40),
+        sizes in prop::collection::vec(1usize..8, 0..6),
+    ) {
+        let chapters = chapters_of(&names);
+        let config = config(VolumeGroupingStrategy::Manual)
+            .volume_sizes_override(sizes.clone())
+            .build()
+            .unwrap();
+        match block_on(config.structure_from_collected_data(chapters.clone())) {
+            Ok(structured) => {
+                prop_assert_eq!(placed_chapters(&structured), chapters);
+                prop_assert_eq!(
+                    structured.report.chapter_counts_per_volume.iter().sum::<usize>(),
+                    names.len()
+                );
+            }
+            // Only sizes asking for more chapters than there are may fail
+            Err(_) => prop_assert!(sizes.iter().sum::<usize>() > names.len()),
+        }
+    }
+
+    #[test]
+    fn per_chapter_grouping_keeps_order(names in prop::collection::vec(chapter_name(), 0..40)) {
+        let chapters = chapters_of(&names);
+        let config = config(VolumeGroupingStrategy::PerChapter).build().unwrap();
+        let structured = block_on(config.structure_from_collected_data(chapters.clone())).unwrap();
+        prop_assert_eq!(structured.report.total_volumes_created, names.len());
+        prop_assert_eq!(placed_chapters(&structured), chapters);
+    }
+
+    #[test]
+    fn name_grouping_places_every_chapter_once(
+        numbers in prop::collection::btree_set((1u32..10, 1u32..200), 1..40),
+        expected_volumes in prop::option::of(1usize..12),
+    ) {
+        // Chapters arrive reversed; name grouping orders them by volume, then chapter
+        let names: Vec<String> = numbers
+            .iter()
+            .rev()
+            .map(|(volume, chapter)| format!("{:02}-{:03}", volume, chapter))
+            .collect();
+        let mut builder = config(VolumeGroupingStrategy::Name);
+        if let Some(expected) = expected_volumes {
+            builder.expected_volume_count(expected);
+        }
+        let config = builder.build().unwrap();
+        let structured = block_on(config.structure_from_collected_data(chapters_of(&names))).unwrap();
+
+        let mut in_order = names.clone();
+        in_order.reverse();
+        prop_assert_eq!(placed_chapters(&structured), chapters_of(&in_order));
+        prop_assert!(structured.volumes_with_chapters_and_pages.iter().all(|v| !v.is_empty()));
+
+        let volumes: BTreeSet<u32> = numbers.iter().map(|(volume, _)| *volume).collect();
+        let expected = expected_volumes.map_or(volumes.len(), |e| e.min(names.len()));
+        prop_assert_eq!(structured.report.total_volumes_created, expected);
+    }
+}
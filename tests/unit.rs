@@ -170,6 +170,10 @@ async fn test_collector_is_grayscale() -> Result<()> {
         !Collector::is_grayscale(&color_img, 0.1),
         "Dummy color image should not be detected as grayscale with low sensibility"
     );
+
+    // The share is what `is_grayscale` compares against the sensibility
+    assert_eq!(Collector::grayscale_share(&gray_img), 1.0);
+    assert_eq!(Collector::grayscale_share(&color_img), 0.0);
     Ok(())
 }
 
@@ -243,6 +247,60 @@ async fn test_collector_collection_depth() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_collector_recommended_depth() -> Result<()> {
+    let test_dirs = setup_test_dirs("recommended_depth").await;
+
+    // Flat source analyzed with the default Deep depth
+    let flat_dir = test_dirs.source_dir.join("flat");
+    create_dummy_color_image(&flat_dir.join("page_001.jpg")).await?;
+    create_dummy_color_image(&flat_dir.join("page_002.jpg")).await?;
+    let collector = Collector::new(&flat_dir, CollectionDepth::Deep, None, None, 75);
+    let content = collector.analyze_source_content().await?;
+    assert_eq!(content.report.recommended_depth, CollectionDepth::Shallow);
+    assert!(content.report.findings.iter().any(|f| matches!(
+        f,
+        AnalyzeFinding::CollectionDepthMismatch {
+            configured: CollectionDepth::Deep,
+            recommended: CollectionDepth::Shallow,
+        }
+    )));
+
+    // Volume folders holding chapter folders
+    let nested_dir = test_dirs.source_dir.join("nested");
+    for (volume, chapter) in [("Volume 2", "Chapter 1"), ("Volume 10", "Chapter 1")] {
+        let chapter_dir = nested_dir.join(volume).join(chapter);
+        create_dummy_color_image(&chapter_dir.join("page_001.jpg")).await?;
+    }
+    create_dummy_color_image(&nested_dir.join("Volume 2").join("cover.jpg")).await?;
+    let collector = Collector::new(&nested_dir, CollectionDepth::Deep, None, None, 75);
+    let content = collector.analyze_source_content().await?;
+    assert_eq!(content.report.recommended_depth, CollectionDepth::Recursive);
+
+    let collector = Collector::new(&nested_dir, CollectionDepth::Recursive, None, None, 75);
+    let chapters = collector
+        .collect_chapters(None::<fn(&PathBuf, &PathBuf) -> Ordering>)
+        .await?;
+    assert_eq!(
+        chapters,
+        vec![
+            nested_dir.join("Volume 2"),
+            nested_dir.join("Volume 2").join("Chapter 1"),
+            nested_dir.join("Volume 10").join("Chapter 1"),
+        ]
+    );
+    let content = collector.analyze_source_content().await?;
+    assert_eq!(content.chapters_with_pages.len(), 3);
+    assert!(
+        !content
+            .report
+            .findings
+            .iter()
+            .any(|f| matches!(f, AnalyzeFinding::CollectionDepthMismatch { .. }))
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_collector_calculate_volume_sizes() -> Result<()> {
     let path = PathBuf::new();
@@ -327,6 +385,167 @@ async fn test_collector_analysis_unsupported_files() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_collector_analysis_nested_archives() -> Result<()> {
+    let test_dirs = setup_test_dirs("analysis_nested_archives").await;
+    let nested = |findings: &[AnalyzeFinding]| -> Vec<(String, bool)> {
+        findings
+            .iter()
+            .filter_map(|f| match f {
+                AnalyzeFinding::NestedArchive {
+                    path,
+                    alongside_images,
+                } => Some((
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    *alongside_images,
+                )),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // An archive next to images, and a chapter that is only an archive
+    let chapter_1 = test_dirs.source_dir.join("Chapter_1");
+    create_dummy_color_image(&chapter_1.join("page_001.jpg")).await?;
+    tokio::fs::write(chapter_1.join("extras.zip"), b"PK").await?;
+    tokio::fs::write(chapter_1.join("notes.txt"), b"notes").await?;
+    let chapter_2 = test_dirs.source_dir.join("Chapter_2");
+    tokio::fs::create_dir_all(&chapter_2).await?;
+    tokio::fs::write(chapter_2.join("Chapter 2.CBZ"), b"PK").await?;
+
+    let source_dir = test_dirs.source_dir.clone();
+    let collector = Collector::new(&source_dir, CollectionDepth::Deep, None, None, 75);
+    let report = collector.analyze_source_content().await?.report;
+    let mut archives = nested(&report.findings);
+    archives.sort();
+    assert_eq!(
+        archives,
+        vec![
+            ("Chapter 2.CBZ".to_string(), false),
+            ("extras.zip".to_string(), true)
+        ]
+    );
+    // Archives aren't reported twice as unsupported files
+    let unsupported = report
+        .findings
+        .iter()
+        .filter(|f| matches!(f, AnalyzeFinding::UnsupportedFileIgnored { .. }))
+        .count();
+    assert_eq!(unsupported, 1);
+
+    // A directory of archived volumes Hozon can't read has no chapters to convert
+    let archived = test_dirs.source_dir.join("archived");
+    tokio::fs::create_dir_all(&archived).await?;
+    tokio::fs::write(archived.join("Volume 1.cbt"), b"tar").await?;
+    tokio::fs::write(archived.join("Volume 2.cb7"), b"7z").await?;
+    let collector = Collector::new(&archived, CollectionDepth::Deep, None, None, 75);
+    let report = collector.analyze_source_content().await?.report;
+    assert_eq!(nested(&report.findings).len(), 2);
+    assert!(
+        report
+            .findings
+            .iter()
+            .any(|f| matches!(f, AnalyzeFinding::NoChaptersFound))
+    );
+
+    // CBZ/ZIP archives are chapters, and a broken one is skipped and reported once
+    tokio::fs::write(archived.join("Volume 3.cbz"), b"PK").await?;
+    let collector = Collector::new(&archived, CollectionDepth::Deep, None, None, 75);
+    let report = collector.analyze_source_content().await?.report;
+    let unreadable: Vec<&PathBuf> = report
+        .findings
+        .iter()
+        .filter_map(|f| match f {
+            AnalyzeFinding::UnreadableArchive { path, .. } => Some(path),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(unreadable, vec![&archived.join("Volume 3.cbz")]);
+    assert_eq!(nested(&report.findings).len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_collector_split_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("analysis_split_pages").await;
+    let chapter_dir = test_dirs.source_dir.join("Chapter_1");
+    for page in ["13.jpg", "12b.jpg", "12.jpg", "12a.jpg", "11.jpg"] {
+        create_dummy_color_image(&chapter_dir.join(page)).await?;
+    }
+
+    let source_dir = test_dirs.source_dir.clone();
+    let collector = Collector::new(&source_dir, CollectionDepth::Deep, None, None, 75);
+    let result = collector.analyze_source_content().await?;
+    let names: Vec<_> = result.chapters_with_pages[0]
+        .iter()
+        .map(|page| page.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["11.jpg", "12.jpg", "12a.jpg", "12b.jpg", "13.jpg"]);
+
+    let split: Vec<_> = result
+        .report
+        .findings
+        .iter()
+        .filter_map(|f| match f {
+            AnalyzeFinding::SplitPagesFound {
+                chapter_path,
+                count,
+            } => Some((chapter_path.clone(), *count)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(split, vec![(chapter_dir, 2)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_collector_missing_pages() -> Result<()> {
+    let test_dirs = setup_test_dirs("analysis_missing_pages").await;
+    let incomplete = test_dirs.source_dir.join("Chapter_1");
+    for page in [
+        "01.jpg",
+        "02.jpg",
+        "04.jpg",
+        "05.jpg",
+        "08.jpg",
+        "credits.jpg",
+    ] {
+        create_dummy_color_image(&incomplete.join(page)).await?;
+    }
+    let complete = test_dirs.source_dir.join("Chapter_2");
+    for page in ["01.jpg", "02.jpg", "02a.jpg", "03.jpg"] {
+        create_dummy_color_image(&complete.join(page)).await?;
+    }
+
+    let source_dir = test_dirs.source_dir.clone();
+    let missing_pages = |report: &AnalyzeReport| -> Vec<_> {
+        report
+            .findings
+            .iter()
+            .filter_map(|f| match f {
+                AnalyzeFinding::MissingPages { chapter, gaps } => {
+                    Some((chapter.clone(), gaps.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Off by default
+    let collector = Collector::new(&source_dir, CollectionDepth::Deep, None, None, 75);
+    let result = collector.analyze_source_content().await?;
+    assert!(missing_pages(&result.report).is_empty());
+
+    let collector = Collector::new(&source_dir, CollectionDepth::Deep, None, None, 75)
+        .with_page_gap_check(true);
+    let result = collector.analyze_source_content().await?;
+    assert_eq!(
+        missing_pages(&result.report),
+        vec![(incomplete, vec![3, 6, 7])]
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_collector_analysis_inconsistent_page_count() -> Result<()> {
     let test_dirs = setup_test_dirs("analysis_inconsistent").await;
@@ -502,6 +721,7 @@ async fn test_volume_separator_default_value() -> Result<()> {
 }
 
 #[tokio::test]
+#[allow(deprecated)]
 async fn test_get_file_info_utility() -> Result<()> {
     use hozon::types::get_file_info;
     use std::path::PathBuf;
@@ -545,3 +765,573 @@ async fn test_get_file_info_utility() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_image_format() -> Result<()> {
+    use hozon::types::register_image_format;
+
+    assert_eq!(
+        ImageFormat::from_path(Path::new("page.JPEG"))?,
+        ImageFormat::Jpeg
+    );
+    assert_eq!(ImageFormat::WebP.mime(), "image/webp");
+    assert!(ImageFormat::BUILT_IN.iter().all(|f| f.can_decode()));
+    assert!(ImageFormat::from_path(Path::new("page.jxl")).is_err());
+
+    let jxl = register_image_format("jxl", "image/jxl")?;
+    assert_eq!(ImageFormat::from_path(Path::new("page.JXL"))?, jxl);
+    assert_eq!((jxl.extension(), jxl.mime()), ("jxl", "image/jxl"));
+    assert!(!jxl.can_decode() && !jxl.can_encode());
+    assert!(ImageFormat::all().contains(&jxl));
+    assert_eq!(register_image_format("jxl", "image/jxl")?, jxl);
+
+    assert!(register_image_format("jxl", "image/x-jxl").is_err());
+    assert!(register_image_format("png", "image/apng").is_err());
+    assert!(register_image_format("tar.gz", "application/gzip").is_err());
+    assert!(register_image_format("", "image/none").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_fingerprint_round_trip() -> Result<()> {
+    let fingerprint = SourceFingerprint {
+        hozon_version: "0.1.5".to_string(),
+        config_digest: "0123456789abcdef".to_string(),
+        content_hash: "deadbeef".to_string(),
+        series_digest: Some("fedcba9876543210".to_string()),
+    };
+
+    let parsed = SourceFingerprint::parse(&fingerprint.to_string()).unwrap();
+    assert_eq!(parsed, fingerprint);
+
+    // Version differences don't make an output stale
+    let newer = SourceFingerprint {
+        hozon_version: "9.9.9".to_string(),
+        ..fingerprint.clone()
+    };
+    assert!(newer.is_up_to_date_with(&fingerprint));
+
+    // Fingerprints of older versions carry no series digest
+    let older = SourceFingerprint::parse("v1;hozon=0.1.4;config=0123456789abcdef;content=deadbeef")
+        .unwrap();
+    assert_eq!(older.series_digest, None);
+    assert!(older.is_up_to_date_with(&fingerprint));
+
+    assert!(SourceFingerprint::parse("not a fingerprint").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_runtime_limits() -> Result<()> {
+    assert!(RuntimeLimits::new(0, 4).is_err());
+    assert!(RuntimeLimits::new(2, 0).is_err());
+
+    let limits = RuntimeLimits::new(2, 8)?;
+    let shared = limits.clone();
+    assert_eq!(shared.max_concurrent_volumes(), 2);
+    assert_eq!(shared.max_concurrent_io(), 8);
+    assert!(shared.max_concurrent_cpu() >= 1);
+
+    assert!(
+        RuntimeLimits::new(2, 8)?
+            .with_max_concurrent_cpu(0)
+            .is_err()
+    );
+    let cpu = RuntimeLimits::new(2, 8)?.with_max_concurrent_cpu(3)?;
+    assert_eq!(cpu.clone().max_concurrent_cpu(), 3);
+    assert!(format!("{:?}", cpu).contains("available_cpu: 3"));
+
+    let default = RuntimeLimits::default();
+    assert!(default.max_concurrent_volumes() >= 1);
+    assert!(default.max_concurrent_io() >= default.max_concurrent_volumes());
+
+    let hdd = RuntimeLimits::for_storage(StorageKind::Hdd);
+    assert_eq!(
+        hdd.max_concurrent_volumes(),
+        default.max_concurrent_volumes()
+    );
+    assert!(hdd.max_concurrent_io() <= 2);
+    let ssd = RuntimeLimits::for_storage(StorageKind::Ssd);
+    assert_eq!(ssd.max_concurrent_io(), default.max_concurrent_io());
+
+    let low = RuntimeLimits::new(4, 16)?
+        .with_max_concurrent_cpu(8)?
+        .throttled(ThrottleProfile::Low);
+    assert_eq!(low.throttle(), ThrottleProfile::Low);
+    assert_eq!(
+        (
+            low.max_concurrent_volumes(),
+            low.max_concurrent_io(),
+            low.max_concurrent_cpu()
+        ),
+        (1, 2, 2)
+    );
+    let normal = RuntimeLimits::new(3, 5)?.throttled(ThrottleProfile::Normal);
+    assert_eq!(
+        (normal.max_concurrent_volumes(), normal.max_concurrent_io()),
+        (3, 5)
+    );
+    let high = RuntimeLimits::new(1, 1)?.throttled(ThrottleProfile::High);
+    assert!(high.max_concurrent_volumes() >= 1);
+    assert_eq!(high.max_concurrent_io(), high.max_concurrent_volumes() * 8);
+
+    let budget = hozon::runtime::open_file_budget();
+    assert!((8..=1024).contains(&budget));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_collected_content_stats() -> Result<()> {
+    let test_dirs = setup_test_dirs("source_stats").await;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("001.jpg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 1").join("002.jpeg")).await?;
+    create_dummy_color_image(&test_dirs.source_dir.join("Chapter 2").join("001.jpg")).await?;
+
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Stats".to_string()))
+        .source_path(test_dirs.source_dir.clone())
+        .target_path(test_dirs.target_dir.clone())
+        .build()?;
+    let stats = config.analyze_source().await?.stats();
+
+    assert_eq!(stats.total_chapters, 2);
+    assert_eq!(stats.total_pages, 3);
+    assert_eq!(stats.pages_per_format.get("jpg"), Some(&3));
+    assert_eq!(stats.average_pages_per_chapter, 1.5);
+    assert!(stats.total_bytes > 0);
+    assert_eq!(stats.unreadable_pages, 0);
+    // Dummy pages are tiny, so all land in the first bucket
+    assert_eq!(stats.size_histogram.len(), 5);
+    assert_eq!(stats.size_histogram[0].pages, 3);
+    assert_eq!(stats.size_histogram[4].max_bytes, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cbz_custom_archive_writer() -> Result<()> {
+    use hozon::generator::Generator;
+    use hozon::generator::archive::ArchiveWriter;
+    use hozon::generator::cbz::Cbz;
+    use std::sync::{Arc, Mutex};
+
+    /// Records entry names and sizes in memory.
+    struct Recorder(Arc<Mutex<Vec<(String, usize)>>>);
+
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if let Some(entry) = self.0.lock().unwrap().last_mut() {
+                entry.1 += buf.len();
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ArchiveWriter for Recorder {
+        fn start_entry(&mut self, name: &str) -> Result<()> {
+            self.0.lock().unwrap().push((name.to_string(), 0));
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let test_dirs = setup_test_dirs("custom_archive_writer").await;
+    let page = test_dirs.source_dir.join("001.jpg");
+    create_dummy_color_image(&page).await?;
+
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let mut generator = Cbz::with_writer(Box::new(Recorder(entries.clone())));
+    // Encryption is backend-specific
+    assert!(generator.set_password("secret").is_err());
+    generator.add_pages(&[page.clone(), page]).await?;
+    generator
+        .set_metadata(
+            "Custom",
+            Some(1),
+            &EbookMetadata::default_with_title("Custom".to_string()),
+            2,
+            &["Chapter 1".to_string()],
+        )
+        .await?;
+    generator.save().await?;
+
+    let entries = entries.lock().unwrap();
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["page_001.jpg", "page_002.jpg", "ComicInfo.xml"]);
+    assert!(entries.iter().all(|(_, size)| *size > 0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generator_pages_from_images() -> Result<()> {
+    use hozon::generator::Generator;
+    use hozon::generator::cbz::Cbz;
+    use hozon::generator::epub::EPub;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    let test_dirs = setup_test_dirs("pages_from_images").await;
+    let title_page = DynamicImage::ImageRgb8(RgbImage::from_pixel(60, 80, Rgb([20, 40, 60])));
+    let page_path = test_dirs.source_dir.join("001.jpg");
+    create_dummy_color_image(&page_path).await?;
+    let page_bytes = std::fs::read(&page_path)?;
+    let metadata = EbookMetadata::default_with_title("Synth".to_string());
+
+    let mut cbz = Cbz::new(&test_dirs.target_dir, "Synth")?;
+    cbz.add_page_from_image(&title_page, "title.png").await?;
+    cbz.add_page_from_bytes(page_bytes.clone(), "page.jpg")
+        .await?;
+    cbz.add_page(&page_path).await?;
+    assert!(
+        cbz.add_page_from_bytes(Vec::new(), "page.gif")
+            .await
+            .is_err()
+    );
+    cbz.set_metadata("Synth", Some(1), &metadata, 3, &[])
+        .await?;
+    cbz.save().await?;
+
+    let cbz_path = test_dirs.target_dir.join("Synth.cbz");
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&cbz_path)?).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(names.contains(&"page_001.png"));
+    assert!(names.contains(&"page_002.jpg"));
+    assert!(names.contains(&"page_003.jpg"));
+    let mut title_bytes = Vec::new();
+    std::io::Read::read_to_end(
+        &mut archive.by_name("page_001.png").unwrap(),
+        &mut title_bytes,
+    )?;
+    let decoded = image::load_from_memory(&title_bytes).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (60, 80));
+
+    let mut epub = EPub::new(&test_dirs.target_dir, "Synth")?;
+    epub.set_cover(&page_path)?;
+    epub.set_metadata("Synth", Some(1), &metadata, 2, &[])
+        .await?;
+    epub.add_page_from_image(&title_page, "title.webp").await?;
+    epub.add_page(&page_path).await?;
+    epub.save().await?;
+
+    let epub_path = test_dirs.target_dir.join("Synth.epub");
+    let archive = zip::ZipArchive::new(std::fs::File::open(&epub_path)?).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(
+        names
+            .iter()
+            .any(|name| name.ends_with("images/1/page_001.webp"))
+    );
+    assert!(
+        names
+            .iter()
+            .any(|name| name.ends_with("images/1/page_002.jpg"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generator_without_memory_pages() -> Result<()> {
+    use hozon::error::Error;
+    use hozon::generator::Generator;
+    use image::{DynamicImage, RgbImage};
+    use std::path::{Path, PathBuf};
+
+    /// A generator implementing only the required methods, as written outside the crate.
+    struct PathList(Vec<PathBuf>);
+
+    #[async_trait::async_trait]
+    impl Generator for PathList {
+        fn new(_output_dir: &Path, _base_filename: &str) -> Result<Self> {
+            Ok(Self(Vec::new()))
+        }
+
+        async fn add_page(&mut self, image_path: &PathBuf) -> Result<&mut Self> {
+            self.0.push(image_path.clone());
+            Ok(self)
+        }
+
+        async fn set_metadata(
+            &mut self,
+            _file_name_base: &str,
+            _file_volume_number: Option<usize>,
+            _series_metadata: &EbookMetadata,
+            _total_pages_in_file: usize,
+            _collected_chapter_titles: &[String],
+        ) -> Result<&mut Self> {
+            Ok(self)
+        }
+
+        async fn save(self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut generator = PathList::new(Path::new("."), "List")?;
+    generator.add_page(&PathBuf::from("001.jpg")).await?;
+    let page = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+    assert!(matches!(
+        generator.add_page_from_image(&page, "title.png").await,
+        Err(Error::Unsupported(_))
+    ));
+    assert_eq!(generator.0, [PathBuf::from("001.jpg")]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generator_capabilities() {
+    let cbz = FileFormat::Cbz.capabilities();
+    assert!(!cbz.supports_rtl);
+    assert!(cbz.supports_encryption);
+    assert!(cbz.supports_metadata_fields.contains(&"genre".to_string()));
+
+    let epub = FileFormat::Epub.capabilities();
+    assert!(epub.supports_rtl && epub.supports_toc && epub.supports_alt_text);
+    assert!(!epub.supports_encryption);
+    assert!(!epub.supports_metadata_fields.contains(&"web".to_string()));
+    assert!(epub.supports_metadata_fields.contains(&"title".to_string()));
+}
+
+#[tokio::test]
+async fn test_config_ignored_options() {
+    let config = HozonConfig::builder()
+        .metadata(EbookMetadata::default_with_title("Ignored".to_string()))
+        .target_path(PathBuf::from("./output"))
+        .build()
+        .unwrap();
+    assert!(config.ignored_options().is_empty());
+
+    let mut metadata = EbookMetadata::default_with_title("Ignored".to_string());
+    metadata.genre = Some("Action".to_string());
+    let cbz = HozonConfig::builder()
+        .metadata(metadata.clone())
+        .target_path(PathBuf::from("./output"))
+        .reading_direction(Direction::Rtl)
+        .image_analysis_sensibility(50)
+        .build()
+        .unwrap();
+    let options: Vec<String> = cbz
+        .ignored_options()
+        .into_iter()
+        .map(|ignored| ignored.option)
+        .collect();
+    assert_eq!(
+        options,
+        vec![
+            "reading_direction".to_string(),
+            "image_analysis_sensibility".to_string()
+        ]
+    );
+
+    let epub = HozonConfig::builder()
+        .metadata(metadata)
+        .target_path(PathBuf::from("./output"))
+        .output_format(FileFormat::Epub)
+        .reading_direction(Direction::Rtl)
+        .comic_info_chapter_map(true)
+        .build()
+        .unwrap();
+    let ignored = epub.ignored_options();
+    let options: Vec<&str> = ignored.iter().map(|i| i.option.as_str()).collect();
+    assert_eq!(options, vec!["comic_info_chapter_map", "metadata.genre"]);
+    assert!(ignored[1].to_string().contains("has no effect"));
+}
+
+fn preset_number(preset: &str, chapter: bool, name: &str) -> Option<f64> {
+    let preset = hozon::presets::naming_preset(preset).expect("preset exists");
+    let pattern = if chapter {
+        preset.chapter_name_regex
+    } else {
+        preset.page_name_regex
+    };
+    let regex = Regex::new(pattern).unwrap();
+    hozon::path_utils::extract_number_from_filename_safe(Path::new(name), &regex)
+}
+
+#[test]
+fn test_naming_preset_tachiyomi() {
+    assert_eq!(
+        preset_number("tachiyomi", true, "Scanlator_Vol.2 Ch.15.5 - Title"),
+        Some(15.5)
+    );
+    assert_eq!(
+        preset_number("tachiyomi", true, "Chapter 7 - 2 Swords"),
+        Some(7.0)
+    );
+    assert_eq!(preset_number("tachiyomi", false, "012.jpg"), Some(12.0));
+}
+
+#[test]
+fn test_naming_preset_hakuneko() {
+    assert_eq!(
+        preset_number("hakuneko", true, "Chapter 12 - Title 3"),
+        Some(12.0)
+    );
+    assert_eq!(preset_number("hakuneko", true, "#012 Title"), Some(12.0));
+    assert_eq!(preset_number("hakuneko", true, "Ch.0003"), Some(3.0));
+    assert_eq!(preset_number("hakuneko", false, "001.png"), Some(1.0));
+}
+
+#[test]
+fn test_naming_preset_fanbox() {
+    assert_eq!(
+        preset_number("fanbox", true, "4812034 Sketches 2"),
+        Some(4812034.0)
+    );
+    assert_eq!(preset_number("fanbox", false, "4812034_p0.jpg"), Some(0.0));
+    assert_eq!(
+        preset_number("fanbox", false, "4812034_p12.jpg"),
+        Some(12.0)
+    );
+}
+
+#[test]
+fn test_naming_preset_calibre() {
+    assert_eq!(
+        preset_number("calibre", true, "Series [3] Title 2"),
+        Some(3.0)
+    );
+    assert_eq!(preset_number("calibre", true, "Series [10.5]"), Some(10.5));
+    assert_eq!(preset_number("calibre", false, "page_0007.jpg"), Some(7.0));
+}
+
+#[test]
+fn test_naming_preset_selection() {
+    let config = HozonConfig::builder()
+        .naming_preset("Fanbox")
+        .build()
+        .unwrap();
+    assert_eq!(config.naming_preset.as_deref(), Some("Fanbox"));
+
+    let error = HozonConfig::builder()
+        .naming_preset("unknown")
+        .build()
+        .unwrap_err();
+    assert!(error.to_string().contains("tachiyomi"));
+}
+
+#[test]
+fn test_parse_comicinfo() {
+    use hozon::metadata::parse_comicinfo;
+
+    // Written by another tool: attributes, character references and no Hozon notes
+    let info = parse_comicinfo(
+        r#"<?xml version="1.0"?>
+<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Title>Caf&#233; &amp; Cr&#xE8;me</Title>
+  <Number>3</Number>
+  <Writer>A, B</Writer>
+  <LanguageISO>fr</LanguageISO>
+  <Notes>Scanned by someone</Notes>
+  <Year>2021</Year>
+  <GTIN>4006381333931</GTIN>
+</ComicInfo>"#,
+    )
+    .unwrap();
+    assert_eq!(info.metadata.title, "Café & Crème");
+    assert_eq!(info.volume, Some(3));
+    assert_eq!(info.page_count, None);
+    assert_eq!(info.metadata.authors, vec!["A", "B"]);
+    assert_eq!(info.metadata.language, "fr");
+    assert!(info.metadata.tags.is_empty());
+    assert_eq!(
+        info.metadata.release_date.map(|date| date.to_rfc3339()),
+        Some("2021-01-01T00:00:00+00:00".to_string())
+    );
+    assert_eq!(
+        info.metadata.identifiers,
+        vec![Identifier::new(IdentifierScheme::Gtin, "4006381333931")]
+    );
+
+    assert!(parse_comicinfo("<ComicInfo><Title></Title></ComicInfo>").is_err());
+}
+
+#[test]
+fn test_parse_opf() {
+    use hozon::metadata::parse_opf;
+
+    let metadata = parse_opf(
+        r#"<package version="3.0" unique-identifier="epub-id-1">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="epub-id-1">urn:uuid:0f4b1c3e-6a53-4b6f-9d8e-2f4e3a1b2c3d</dc:identifier>
+    <dc:title>Trips - Round &amp; Trip Part 2</dc:title>
+    <dc:creator id="epub-creator-0">Alice</dc:creator>
+    <dc:creator id="epub-creator-1">Bob</dc:creator>
+    <dc:language>de</dc:language>
+    <dc:subject>Travel</dc:subject>
+    <dc:date>2020-05-17</dc:date>
+    <meta property="belongs-to-collection" id="hozon-series">Trips</meta>
+    <dc:identifier id="hozon-id-0">my-id</dc:identifier>
+    <dc:identifier id="hozon-id-1">urn:isbn:9780000000002</dc:identifier>
+    <dc:identifier id="hozon-id-2">anilist:30013</dc:identifier>
+    <meta name="cover" content="cover-image"/>
+    <meta name="Scanlator" content="Team &quot;A&quot;"/>
+  </metadata>
+</package>"#,
+    )
+    .unwrap();
+    assert_eq!(metadata.title, "Round & Trip");
+    assert_eq!(metadata.series.as_deref(), Some("Trips"));
+    assert_eq!(metadata.authors, vec!["Alice", "Bob"]);
+    assert_eq!(metadata.language, "de");
+    assert_eq!(metadata.tags, vec!["Travel"]);
+    assert_eq!(
+        metadata.release_date.map(|date| date.to_rfc3339()),
+        Some("2020-05-17T00:00:00+00:00".to_string())
+    );
+    assert_eq!(metadata.identifier.as_deref(), Some("my-id"));
+    assert_eq!(
+        metadata.identifiers,
+        vec![
+            Identifier::new(IdentifierScheme::Isbn, "9780000000002"),
+            Identifier::new(IdentifierScheme::Anilist, "30013"),
+        ]
+    );
+    assert_eq!(metadata.custom_fields.len(), 1);
+    assert_eq!(metadata.custom_fields["Scanlator"], "Team \"A\"");
+
+    assert!(parse_opf("<package><metadata></metadata></package>").is_err());
+}
+
+#[test]
+fn test_page_style_validation() {
+    let style = |color: Option<&str>, margin: Option<&str>| PageStyle {
+        background_color: color.map(str::to_string),
+        margin: margin.map(str::to_string),
+    };
+    for (color, margin) in [
+        (Some("#f4f1ea"), None),
+        (Some("#000"), Some("0")),
+        (Some("black"), Some("1em")),
+        (None, Some("0 2%")),
+        (None, Some("1.5rem 2px 0 3vh")),
+    ] {
+        assert!(
+            style(color, margin).validate().is_ok(),
+            "{:?} {:?}",
+            color,
+            margin
+        );
+    }
+    for (color, margin) in [
+        (Some("#f4f1e"), None),
+        (Some("red; } body { display: none"), None),
+        (Some(""), None),
+        (None, Some("1")),
+        (None, Some("-1em")),
+        (None, Some("1em 1em 1em 1em 1em")),
+        (None, Some("1em;}")),
+        (None, Some("")),
+    ] {
+        assert!(
+            style(color, margin).validate().is_err(),
+            "{:?} {:?}",
+            color,
+            margin
+        );
+    }
+}